@@ -0,0 +1,73 @@
+use std::io::Write;
+use std::path::Path;
+
+use clap::CommandFactory;
+use clap_complete::Shell;
+use tracing::info;
+
+use crate::cmd::Cli;
+use crate::errors::Result;
+
+/// The CLI as installed: completions and man pages are keyed by the
+/// executable's name, not by `apitap-run` shown in `--help`.
+fn command() -> clap::Command {
+    Cli::command().name(env!("CARGO_PKG_NAME"))
+}
+
+/// Write the completion script for `shell` to `out`.
+pub fn write_completions(shell: Shell, out: &mut dyn Write) {
+    let mut cmd = command();
+    let name = cmd.get_name().to_string();
+    clap_complete::generate(shell, &mut cmd, name, out);
+}
+
+/// Render `apitap.1` plus one page per subcommand (`apitap-state.1`,
+/// `apitap-state-export.1`, ...) into `dir`; returns the files written.
+pub fn write_man_pages(dir: &Path) -> Result<Vec<String>> {
+    std::fs::create_dir_all(dir)?;
+    let mut cmd = command();
+    // Subcommands inherit the parent's version and global args.
+    cmd.build();
+    let mut written = Vec::new();
+    render_pages(&cmd, dir, &mut written)?;
+    Ok(written)
+}
+
+fn render_pages(cmd: &clap::Command, dir: &Path, written: &mut Vec<String>) -> Result<()> {
+    let file = format!("{}.1", cmd.get_display_name().unwrap_or(cmd.get_name()));
+    let mut page = Vec::new();
+    clap_mangen::Man::new(cmd.clone()).render(&mut page)?;
+    std::fs::write(dir.join(&file), page)?;
+    written.push(file);
+    for sub in cmd.get_subcommands().filter(|s| !s.is_hide_set()) {
+        render_pages(sub, dir, written)?;
+    }
+    Ok(())
+}
+
+/// Run `apitap completions <shell>`: the script goes to stdout alone so it
+/// can be sourced or redirected.
+pub fn run_completions_command(shell: Shell) -> Result<()> {
+    // Buffered: clap_complete panics when a direct write fails, e.g. on `| head`.
+    let mut script = Vec::new();
+    write_completions(shell, &mut script);
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(&script)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Run `apitap man`: pages into `out_dir`, or the top-level page to stdout.
+pub fn run_man_command(out_dir: Option<&str>) -> Result<()> {
+    match out_dir {
+        Some(dir) => {
+            let pages = write_man_pages(Path::new(dir))?;
+            info!(dir, pages = pages.len(), "man pages written");
+        }
+        None => {
+            let mut stdout = std::io::stdout().lock();
+            clap_mangen::Man::new(command()).render(&mut stdout)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use std::io::Write;
+
+use tracing::{info, instrument};
+
+use serde::Serialize;
+
+use crate::cmd::{print_json, ConfigCommand, OutputFormat};
+use crate::config::migrate::{migrate_str, Change, CONFIG_VERSION};
+use crate::errors::Result;
+
+/// `apitap config migrate --output json`.
+#[derive(Serialize)]
+struct MigrateReport<'a> {
+    file: &'a str,
+    from_version: u64,
+    to_version: u64,
+    changes: &'a [Change],
+    /// Where the original went, when the file was rewritten.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backup: Option<String>,
+    /// The migrated document, when it was not written.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    yaml: Option<&'a str>,
+}
+
+/// Run `apitap config migrate`, defaulting to the `--yaml-config` file.
+#[instrument(name = "config", err, skip(action))]
+pub fn run_config_command(default_path: &str, action: &ConfigCommand) -> Result<()> {
+    match action {
+        ConfigCommand::Migrate {
+            file,
+            write,
+            output,
+        } => {
+            let path = file.as_deref().unwrap_or(default_path);
+            let migration = migrate_str(&std::fs::read_to_string(path)?)?;
+            let mut report = MigrateReport {
+                file: path,
+                from_version: migration.from_version,
+                to_version: CONFIG_VERSION,
+                changes: &migration.changes,
+                backup: None,
+                yaml: None,
+            };
+            if migration.is_noop() {
+                info!(file = %path, version = CONFIG_VERSION, "config already current");
+            } else if *write {
+                for change in &migration.changes {
+                    info!(path = %change.path, "{}", change.note);
+                }
+                let backup = format!("{path}.bak");
+                std::fs::copy(path, &backup)?;
+                std::fs::write(path, &migration.yaml)?;
+                info!(
+                    file = %path,
+                    backup = %backup,
+                    from = migration.from_version,
+                    to = CONFIG_VERSION,
+                    changes = migration.changes.len(),
+                    "config migrated; comments from the original are only in the backup"
+                );
+                report.backup = Some(backup);
+            } else if *output == OutputFormat::Text {
+                // Nothing else on stdout so the result can be redirected; the
+                // header comments list the changes.
+                let mut stdout = std::io::stdout().lock();
+                stdout.write_all(migration.yaml.as_bytes())?;
+            } else {
+                report.yaml = Some(&migration.yaml);
+            }
+            if *output == OutputFormat::Json {
+                print_json(&report)?;
+            }
+        }
+    }
+    Ok(())
+}
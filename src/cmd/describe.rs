@@ -0,0 +1,300 @@
+//! `apitap describe`: the plan a run would execute, for audits and for tools
+//! drawing the pipeline's topology.
+//!
+//! Sources and targets are shown as configured, defaults filled in, with
+//! credentials removed: secret-looking values (passwords, tokens, keys,
+//! credentials in URLs, auth headers) read `<redacted>`, and environment
+//! variables are listed by name with whether they are set, never by value.
+//! Secret references such as `vault://...` are kept; they hold no secret.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::cmd::{print_json, OutputFormat};
+use crate::config::inspect_config_from_path;
+use crate::config::templating::{
+    build_env_with_captures, list_sql_templates, render_one, ModuleDoc, ModuleStatus, RenderCapture,
+};
+use crate::errors::Result;
+use crate::pipeline::Config;
+
+pub const REDACTED: &str = "<redacted>";
+
+/// Everything `apitap describe` prints.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PipelinePlan {
+    pub sources: Vec<Value>,
+    pub targets: Vec<Value>,
+    pub modules: Vec<ModulePlan>,
+    /// Environment variables the config reads.
+    pub env: Vec<EnvReference>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ModulePlan {
+    pub module: String,
+    pub source: String,
+    pub status: ModuleStatus,
+    /// `merge` on the key, or `append` for snapshot sources.
+    pub write_mode: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub primary_key: Option<String>,
+    pub routes: Vec<RoutePlan>,
+    #[serde(flatten)]
+    pub doc: ModuleDoc,
+}
+
+/// One SELECT of a module and where its rows land.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RoutePlan {
+    pub sink: String,
+    /// The target's `type`; `None` when no target has that name.
+    pub sink_type: Option<String>,
+    pub table: String,
+    pub sql: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EnvReference {
+    pub name: String,
+    pub set: bool,
+    /// `source:<name>` or `target:<name>`, per use.
+    pub used_by: Vec<String>,
+}
+
+/// Schemes of the references `apitap run` resolves; see
+/// [`secrets`](crate::config::secrets).
+const SECRET_REFERENCES: [&str; 3] = ["vault://", "aws-sm://", "gcp-sm://"];
+
+/// Keys holding a secret by name: `password`, `client_secret`,
+/// `refresh_token`, ... but not `token_url` or `private_key_file`.
+fn is_secret_name(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    [
+        "password",
+        "secret",
+        "token",
+        "api_key",
+        "apikey",
+        "private_key",
+        "credentials",
+        "webhook_url",
+    ]
+    .iter()
+    .any(|word| name.ends_with(word))
+}
+
+fn is_secret_header(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    [
+        "authorization",
+        "cookie",
+        "key",
+        "token",
+        "secret",
+        "signature",
+    ]
+    .iter()
+    .any(|word| name.contains(word))
+}
+
+/// `value` with the password of a URL replaced; `None` when it has none.
+fn redact_url(value: &str) -> Option<String> {
+    let mut url = url::Url::parse(value).ok()?;
+    url.password()?;
+    url.set_password(Some("redacted")).ok()?;
+    // `set_password` percent-encodes the brackets of the marker.
+    Some(
+        url.to_string()
+            .replacen(":redacted@", &format!(":{REDACTED}@"), 1),
+    )
+}
+
+/// Strip credentials from `value`, the config of `owner`, noting the
+/// environment variables it names in `env`.
+fn redact(value: &mut Value, owner: &str, env: &mut BTreeMap<String, Vec<String>>) {
+    match value {
+        Value::Object(map) => redact_object(map, owner, env),
+        Value::Array(items) => items.iter_mut().for_each(|item| redact(item, owner, env)),
+        Value::String(text) => {
+            if let Some(redacted) = redact_url(text) {
+                *text = redacted;
+            }
+        }
+        _ => {}
+    }
+}
+
+fn redact_object(
+    map: &mut Map<String, Value>,
+    owner: &str,
+    env: &mut BTreeMap<String, Vec<String>>,
+) {
+    // `{key, value}` pairs: headers and query parameters.
+    let sensitive_pair = map
+        .get("key")
+        .and_then(Value::as_str)
+        .is_some_and(is_secret_header);
+    for (key, value) in map.iter_mut() {
+        match value {
+            Value::String(name) if key.ends_with("_env") => {
+                let users = env.entry(name.clone()).or_default();
+                if !users.iter().any(|u| u == owner) {
+                    users.push(owner.to_string());
+                }
+            }
+            Value::String(text) if SECRET_REFERENCES.iter().any(|s| text.starts_with(s)) => {}
+            Value::String(text) if is_secret_name(key) || (sensitive_pair && key == "value") => {
+                *text = REDACTED.to_string();
+            }
+            value => redact(value, owner, env),
+        }
+    }
+}
+
+/// The plan of running the modules under `root` with `cfg`.
+pub fn describe(root: &str, cfg: &Config) -> Result<PipelinePlan> {
+    let mut env = BTreeMap::new();
+    let mut sources = Vec::with_capacity(cfg.sources.len());
+    for src in &cfg.sources {
+        let mut value = serde_json::to_value(src)?;
+        redact(&mut value, &format!("source:{}", src.name), &mut env);
+        sources.push(value);
+    }
+    let mut targets = Vec::with_capacity(cfg.targets.len());
+    let mut target_types = BTreeMap::new();
+    for tgt in &cfg.targets {
+        let mut value = serde_json::to_value(tgt)?;
+        let name = value["name"].as_str().unwrap_or_default().to_string();
+        if let Some(kind) = value["type"].as_str() {
+            target_types.insert(name.clone(), kind.to_string());
+        }
+        redact(&mut value, &format!("target:{name}"), &mut env);
+        targets.push(value);
+    }
+
+    let capture = Arc::new(Mutex::new(RenderCapture::default()));
+    let templates = build_env_with_captures(root, &capture);
+    let modules = list_sql_templates(root)?
+        .into_iter()
+        .map(|name| {
+            let rendered = render_one(&templates, &capture, &name)?;
+            let src = cfg.source(&rendered.capture.source);
+            let default_table = src
+                .and_then(|s| s.table_destination_name.as_deref())
+                .unwrap_or("?");
+            let routes = rendered
+                .statements()?
+                .into_iter()
+                .map(|stmt| RoutePlan {
+                    sink_type: target_types.get(&stmt.sink).cloned(),
+                    table: stmt.table.unwrap_or_else(|| default_table.to_string()),
+                    sink: stmt.sink,
+                    sql: stmt.sql,
+                })
+                .collect();
+            let snapshot = src.is_some_and(|s| s.snapshot.enabled);
+            Ok(ModulePlan {
+                status: rendered.capture.status(src),
+                source: rendered.capture.source,
+                write_mode: if snapshot { "append" } else { "merge" }.to_string(),
+                primary_key: src
+                    .filter(|_| !snapshot)
+                    .and_then(|s| s.primary_key_in_dest.clone()),
+                routes,
+                doc: rendered.doc,
+                module: name,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let env = env
+        .into_iter()
+        .map(|(name, used_by)| EnvReference {
+            set: std::env::var(&name).is_ok_and(|v| !v.trim().is_empty()),
+            name,
+            used_by,
+        })
+        .collect();
+    Ok(PipelinePlan {
+        sources,
+        targets,
+        modules,
+        env,
+    })
+}
+
+/// Indented plain-text outline of `plan`.
+pub fn render_plan(plan: &PipelinePlan) -> String {
+    let text = |value: &Value, key: &str| value[key].as_str().unwrap_or("-").to_string();
+    let mut out = String::from("SOURCES\n");
+    for src in &plan.sources {
+        let endpoint = if src["sql"].is_object() {
+            "sql".to_string()
+        } else {
+            text(src, "url")
+        };
+        let pagination = src["pagination"]["kind"].as_str().unwrap_or("none");
+        out.push_str(&format!(
+            "  {}  {endpoint}  (pagination: {pagination})\n",
+            text(src, "name")
+        ));
+    }
+    out.push_str("TARGETS\n");
+    for tgt in &plan.targets {
+        out.push_str(&format!("  {}  {}\n", text(tgt, "name"), text(tgt, "type")));
+    }
+    out.push_str("MODULES\n");
+    for module in &plan.modules {
+        let routes: Vec<String> = module
+            .routes
+            .iter()
+            .map(|r| format!("{}:{}", r.sink, r.table))
+            .collect();
+        let mode = match &module.primary_key {
+            Some(key) => format!("{} on {key}", module.write_mode),
+            None => module.write_mode.clone(),
+        };
+        let status = match &module.status {
+            ModuleStatus::Enabled => String::new(),
+            ModuleStatus::Deprecated(note) => format!("  [deprecated: {note}]"),
+            ModuleStatus::Disabled(reason) => format!("  [disabled: {reason}]"),
+        };
+        out.push_str(&format!(
+            "  {}  {} → {}  ({mode}){status}\n",
+            module.module,
+            module.source,
+            routes.join(", ")
+        ));
+    }
+    if !plan.env.is_empty() {
+        out.push_str("ENVIRONMENT\n");
+        for var in &plan.env {
+            let set = if var.set { "set" } else { "NOT SET" };
+            out.push_str(&format!(
+                "  {}  {set}  ({})\n",
+                var.name,
+                var.used_by.join(", ")
+            ));
+        }
+    }
+    out
+}
+
+/// Run `apitap describe`: the outline (or the plan as JSON) goes to stdout.
+pub fn run_describe_command(root: &str, cfg_path: &str, output: OutputFormat) -> Result<()> {
+    // Unset credentials are reported, not an error.
+    let cfg = inspect_config_from_path(cfg_path)?;
+    let plan = describe(root, &cfg)?;
+    if output == OutputFormat::Json {
+        return print_json(&plan);
+    }
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(render_plan(&plan).as_bytes())?;
+    stdout.flush()?;
+    Ok(())
+}
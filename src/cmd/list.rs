@@ -0,0 +1,112 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use crate::cmd::{print_json, OutputFormat};
+use crate::config::load_config_from_path;
+use crate::config::templating::{
+    build_env_with_captures, list_sql_templates, render_one, ModuleDoc, ModuleStatus, RenderCapture,
+};
+use crate::errors::Result;
+
+/// One row of `apitap list`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ModuleListing {
+    pub module: String,
+    pub source: String,
+    /// `sink:table` per statement.
+    pub destinations: Vec<String>,
+    pub status: ModuleStatus,
+    /// Description, owner and SLA from the module's comment header.
+    #[serde(flatten)]
+    pub doc: ModuleDoc,
+}
+
+/// Render every module under `root` against the config at `cfg_path`.
+pub fn list_modules(root: &str, cfg_path: &str) -> Result<Vec<ModuleListing>> {
+    let cfg = load_config_from_path(cfg_path)?;
+    let capture = Arc::new(Mutex::new(RenderCapture::default()));
+    let env = build_env_with_captures(root, &capture);
+
+    list_sql_templates(root)?
+        .into_iter()
+        .map(|name| {
+            let rendered = render_one(&env, &capture, &name)?;
+            let src = cfg.source(&rendered.capture.source);
+            let default_table = src
+                .and_then(|s| s.table_destination_name.as_deref())
+                .unwrap_or("?");
+            let destinations = rendered
+                .statements()?
+                .iter()
+                .map(|stmt| {
+                    format!(
+                        "{}:{}",
+                        stmt.sink,
+                        stmt.table.as_deref().unwrap_or(default_table)
+                    )
+                })
+                .collect();
+            Ok(ModuleListing {
+                status: rendered.capture.status(src),
+                source: rendered.capture.source,
+                destinations,
+                doc: rendered.doc,
+                module: name,
+            })
+        })
+        .collect()
+}
+
+/// Plain-text table of `modules`, one line each.
+pub fn render_listing(modules: &[ModuleListing]) -> String {
+    let width = |f: &dyn Fn(&ModuleListing) -> usize, title: &str| {
+        modules.iter().map(f).max().unwrap_or(0).max(title.len())
+    };
+    let or_dash = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+    let status = |m: &ModuleListing| match &m.status {
+        ModuleStatus::Enabled => "enabled".to_string(),
+        ModuleStatus::Deprecated(note) => format!("deprecated: {note}"),
+        ModuleStatus::Disabled(reason) => format!("disabled ({reason})"),
+    };
+    let module_w = width(&|m| m.module.len(), "MODULE");
+    let source_w = width(&|m| m.source.len(), "SOURCE");
+    let dest_w = width(&|m| m.destinations.join(", ").len(), "DESTINATION");
+    let owner_w = width(&|m| or_dash(&m.doc.owner).len(), "OWNER");
+    let sla_w = width(&|m| or_dash(&m.doc.sla).len(), "SLA");
+    let status_w = width(&|m| status(m).len(), "STATUS");
+
+    let mut out = format!(
+        "{:<module_w$}  {:<source_w$}  {:<dest_w$}  {:<owner_w$}  {:<sla_w$}  {:<status_w$}  DESCRIPTION\n",
+        "MODULE", "SOURCE", "DESTINATION", "OWNER", "SLA", "STATUS"
+    );
+    for m in modules {
+        let line = format!(
+            "{:<module_w$}  {:<source_w$}  {:<dest_w$}  {:<owner_w$}  {:<sla_w$}  {:<status_w$}  {}",
+            m.module,
+            m.source,
+            m.destinations.join(", "),
+            or_dash(&m.doc.owner),
+            or_dash(&m.doc.sla),
+            status(m),
+            m.doc.description.as_deref().unwrap_or_default()
+        );
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// Run `apitap list`: the table (or a JSON array) goes to stdout.
+pub fn run_list_command(root: &str, cfg_path: &str, output: OutputFormat) -> Result<()> {
+    let modules = list_modules(root, cfg_path)?;
+    if output == OutputFormat::Json {
+        return print_json(&modules);
+    }
+    let listing = render_listing(&modules);
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(listing.as_bytes())?;
+    stdout.flush()?;
+    Ok(())
+}
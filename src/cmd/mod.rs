@@ -1,20 +1,68 @@
+use std::collections::BTreeMap;
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
-use crate::config::load_config_from_path;
+use crate::config::load_config_with_secrets;
+use crate::config::secrets::SecretResolvers;
 use crate::config::templating::{
-    build_env_with_captures, list_sql_templates, render_one, RenderCapture,
+    build_env_with_captures, list_sql_templates, render_one, ModuleStatus, RenderCapture,
 };
 use crate::errors::{self, Result};
-use crate::http::fetcher::Pagination;
+use crate::http::auth::SourceAuth;
+use crate::http::bandwidth::{parse_bandwidth, BandwidthLimiter};
+use crate::http::body::{RequestBody, RequestMethod};
+use crate::http::csv_stream::ResponseFormat;
+use crate::http::deprecation::{DeprecationNotice, DeprecationWatch};
+use crate::http::failure_samples::{FailureSampler, DEFAULT_REPORT_DIR};
+use crate::http::fetcher::{DataFusionPageWriter, Pagination, RequestOptions};
+use crate::http::limits::{MemoryBudget, ResponseLimits};
+use crate::http::oauth2::RefreshTokenSlot;
+use crate::http::paginator::{Paginator, Paginators};
+use crate::http::throttle::ServerThrottle;
+use crate::http::usage::UsageMeter;
+use crate::http::vcr::{Vcr, VcrMode};
 use crate::http::Http;
-use crate::pipeline::run::{run_fetch, FetchOpts};
+use crate::pipeline::audit::{write_audit, AuditConfig, ModuleAudit};
+use crate::pipeline::connections::TargetConnections;
+use crate::pipeline::consistency::check_consistency;
+use crate::pipeline::download_state::DownloadTracker;
+use crate::pipeline::duplicates::check_duplicates;
+use crate::pipeline::freshness::{FreshnessSeverity, FreshnessStatus};
+use crate::pipeline::http_cache::HttpCache;
+use crate::pipeline::mock::run_mock_fetch;
+use crate::pipeline::page_progress::ProgressTracker;
+use crate::pipeline::query_template::{parse_var, QueryTemplate};
+use crate::pipeline::reconcile::{apply_reconcile, SeenKeys};
+use crate::pipeline::retention::apply_retention;
+use crate::pipeline::retry_state::{RetryStateStore, RetryTracker};
+use crate::pipeline::run::{
+    new_run_id, run_fetch, FetchOpts, RunSummary, Schedule, LOW_MEMORY_BATCH_SIZE,
+};
+use crate::pipeline::run_history::{ModuleRun, SchemaCapture};
 use crate::pipeline::sink::{MakeWriter, WriterOpts};
-use crate::pipeline::SinkConn;
-use crate::writer::WriteMode;
-use clap::Parser;
+use crate::pipeline::sla::SlaWatch;
+use crate::pipeline::sql_source::{run_sql_fetch, SqlSource};
+use crate::pipeline::state::{record_run, DEFAULT_STATE_PATH};
+use crate::pipeline::watermark::WatermarkTracker;
+use crate::pipeline::{Config, QueryParam};
+use crate::transform::{BinaryFields, PageHooks, TransformChain, WasmTransform};
+use crate::utils::datafusion_ext::{configure_shared_context, ContextLimits};
+use crate::writer::metadata_columns::MetadataColumns;
+use crate::writer::middleware::MiddlewareChain;
+use crate::writer::rollup::{Rollup, RollupCollector};
+use crate::writer::{DataWriter, WriteMode};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use serde_json::Value;
 use tracing::{debug, info, instrument, warn};
 
+pub mod completions;
+pub mod config;
+pub mod describe;
+pub mod list;
+pub mod runs;
+pub mod state;
+
 const CONCURRENCY: usize = 5;
 const DEFAULT_PAGE_SIZE: usize = 50;
 const FETCH_BATCH_SIZE: usize = 256;
@@ -30,11 +78,15 @@ HTTP-to-warehouse ETL powered by DataFusion.\n\n\
 Resources:\n  • Modules: Jinja-like SQL templates that declare {{ sink(...) }} and {{ use_source(...) }}\n  • YAML config: defines sources (HTTP + pagination) and targets (warehouses)\n  • Execution: fetch JSON → DataFusion SQL → write via sink-specific writers"
 )]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     #[arg(
         long = "modules",
         short = 'm',
         value_name = "DIR",
-        default_value = "pipelines"
+        default_value = "pipelines",
+        global = true
     )]
     pub modules: String,
 
@@ -42,7 +94,8 @@ pub struct Cli {
         long = "yaml-config",
         short = 'y',
         value_name = "FILE",
-        default_value = "pipelines.yaml"
+        default_value = "pipelines.yaml",
+        global = true
     )]
     pub yaml_config: String,
     /// Emit logs in JSON format
@@ -52,6 +105,254 @@ pub struct Cli {
     /// Set log level (overrides env vars like RUST_LOG). Example: info,warn,debug
     #[arg(long = "log-level")]
     pub log_level: Option<String>,
+
+    /// Cap response body download rate across all fetches. Example: 10MB/s, 512KiB/s
+    #[arg(long = "max-bandwidth", value_name = "RATE", value_parser = parse_bandwidth)]
+    pub max_bandwidth: Option<u64>,
+
+    /// Re-attempt pages that exhausted retries in a previous run before each module
+    #[arg(long = "resume")]
+    pub resume: bool,
+
+    /// Fetch `incremental` sources from their `initial` value, ignoring the stored watermarks
+    #[arg(long = "full-refresh")]
+    pub full_refresh: bool,
+
+    /// State store: SQLite database, or a JSON file when the path ends in `.json`
+    #[arg(
+        long = "state",
+        alias = "retry-state",
+        value_name = "FILE",
+        default_value = DEFAULT_STATE_PATH,
+        global = true
+    )]
+    pub state: String,
+
+    /// Scheduled mode: skip modules whose destination is within its freshness window
+    #[arg(long = "skip-if-fresh")]
+    pub skip_if_fresh: bool,
+
+    /// Debug: EXPLAIN the first MERGE/INSERT batch of each destination and log its plan
+    #[arg(long = "explain-first-batch")]
+    pub explain_first_batch: bool,
+
+    /// Run reports, e.g. failed responses kept by `sample_failures`, go under DIR/<run id>
+    #[arg(long = "report-dir", value_name = "DIR", default_value = DEFAULT_REPORT_DIR)]
+    pub report_dir: String,
+
+    /// Serve each source's `mock:` fixture instead of calling its API (demos, CI)
+    #[arg(long = "mock")]
+    pub mock: bool,
+
+    /// Run within ~256MB: one request at a time, small batches, JSON parsed as it arrives
+    #[arg(long = "low-memory")]
+    pub low_memory: bool,
+
+    /// Set a var for `{{ var('name') }}` in query_params, over the config's `vars:`. Repeatable
+    #[arg(long = "var", value_name = "NAME=VALUE", value_parser = parse_var)]
+    pub vars: Vec<(String, String)>,
+
+    /// Write every page response to DIR/<source>/, for --replay
+    #[arg(long = "record", value_name = "DIR", conflicts_with = "replay")]
+    pub record: Option<String>,
+
+    /// Answer page requests from responses recorded with --record instead of calling the APIs
+    #[arg(long = "replay", value_name = "DIR")]
+    pub replay: Option<String>,
+}
+
+impl Cli {
+    pub fn run_options(&self) -> RunOptions {
+        RunOptions {
+            max_bandwidth: self.max_bandwidth,
+            resume: self.resume,
+            full_refresh: self.full_refresh,
+            state_path: Some(self.state.clone()),
+            skip_if_fresh: self.skip_if_fresh,
+            explain_first_batch: self.explain_first_batch,
+            report_dir: Some(self.report_dir.clone()),
+            mock: self.mock,
+            low_memory: self.low_memory,
+            vars: self.vars.iter().cloned().collect(),
+            vcr: match (&self.record, &self.replay) {
+                (Some(dir), _) => Some(Vcr::new(dir, VcrMode::Record)),
+                (None, Some(dir)) => Some(Vcr::new(dir, VcrMode::Replay)),
+                (None, None) => None,
+            },
+            page_hooks: PageHooks::default(),
+            paginators: Paginators::default(),
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Inspect or move the state store between machines
+    State {
+        #[command(subcommand)]
+        action: StateCommand,
+    },
+    /// Work with the YAML config
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+    /// Print a shell completion script, e.g. `apitap completions zsh > _apitap`
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+    /// List the modules with their source, destinations, owner, SLA and enabled/deprecated status
+    List {
+        #[arg(long = "output", value_enum, default_value_t)]
+        output: OutputFormat,
+    },
+    /// Print the plan a run would execute: sources and targets (credentials
+    /// redacted), modules with their routes and write modes, and the
+    /// environment variables read
+    Describe {
+        #[arg(long = "output", value_enum, default_value_t)]
+        output: OutputFormat,
+    },
+    /// Look at runs recorded in the state store
+    Runs {
+        #[command(subcommand)]
+        action: RunsCommand,
+    },
+    /// Render man pages; the top-level page goes to stdout without `--out-dir`
+    Man {
+        /// Directory for `apitap.1` and one page per subcommand
+        #[arg(long = "out-dir", value_name = "DIR")]
+        out_dir: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum StateCommand {
+    /// Write the state store as JSON
+    Export {
+        /// File to write; stdout when omitted
+        #[arg(long = "file", short = 'o', value_name = "FILE")]
+        file: Option<String>,
+    },
+    /// Load state from an exported JSON document, replacing the current state
+    Import {
+        /// Exported JSON file, or `-` for stdin
+        #[arg(value_name = "FILE")]
+        input: String,
+        /// Merge into the existing state instead of replacing it
+        #[arg(long = "merge")]
+        merge: bool,
+        #[arg(long = "output", value_enum, default_value_t)]
+        output: OutputFormat,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Rewrite a config written for an older release to the current schema
+    Migrate {
+        /// Config file; `--yaml-config` when omitted
+        #[arg(value_name = "FILE")]
+        file: Option<String>,
+        /// Rewrite the file in place, keeping the original as `FILE.bak`;
+        /// otherwise the migrated config is printed
+        #[arg(long = "write")]
+        write: bool,
+        #[arg(long = "output", value_enum, default_value_t)]
+        output: OutputFormat,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RunsCommand {
+    /// Compare two runs: rows, durations, failed pages and schema per module
+    Diff {
+        /// Baseline run: a run id, a unique prefix of one, `latest` or `latest~N`
+        #[arg(value_name = "RUN_A")]
+        run_a: String,
+        /// Run compared against the baseline
+        #[arg(value_name = "RUN_B")]
+        run_b: String,
+        /// Exit with an error when a regression is found
+        #[arg(long = "fail-on-regression")]
+        fail_on_regression: bool,
+        #[arg(long = "output", value_enum, default_value_t)]
+        output: OutputFormat,
+    },
+}
+
+/// How a subcommand prints its result: tables for people (default) or one
+/// JSON document for scripts.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Print `value` to stdout as pretty JSON.
+pub(crate) fn print_json(value: &impl Serialize) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    serde_json::to_writer_pretty(&mut stdout, value)?;
+    std::io::Write::write_all(&mut stdout, b"\n")?;
+    std::io::Write::flush(&mut stdout)?;
+    Ok(())
+}
+
+/// Run-wide settings that come from the command line rather than the YAML config.
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Bytes per second shared by every HTTP fetch.
+    pub max_bandwidth: Option<u64>,
+    /// Re-attempt recorded failed pages first.
+    pub resume: bool,
+    /// Ignore stored watermarks; they still move forward afterwards.
+    pub full_refresh: bool,
+    /// State store recording pages that exhausted retries; not tracked when `None`.
+    pub state_path: Option<String>,
+    /// Skip modules whose `freshness` contract is already met.
+    pub skip_if_fresh: bool,
+    /// Log the plan of each destination's first write statement.
+    pub explain_first_batch: bool,
+    /// Directory of per-run reports; `sample_failures` keeps nothing when `None`.
+    pub report_dir: Option<String>,
+    /// Serve sources' `mock:` fixtures instead of fetching.
+    pub mock: bool,
+    /// Trade speed for a small memory footprint; see [`FetchOpts::low_memory`]
+    /// and [`ContextLimits::LOW_MEMORY`].
+    pub low_memory: bool,
+    /// `{{ var(...) }}` values for query_params, over the config's `vars:`.
+    pub vars: BTreeMap<String, String>,
+    /// Record page responses, or replay recorded ones instead of fetching.
+    pub vcr: Option<Vcr>,
+    /// Per-source page hooks registered by embedders; the CLI sets none.
+    pub page_hooks: PageHooks,
+    /// Paginators for `kind: custom` sources, registered by embedders.
+    pub paginators: Paginators,
+}
+
+impl RunOptions {
+    /// Rewrite every page fetched from `source` with `hook`, after the row transforms.
+    pub fn with_page_hook(
+        mut self,
+        source: impl Into<String>,
+        hook: impl Fn(Vec<Value>) -> Vec<Value> + Send + Sync + 'static,
+    ) -> Self {
+        self.page_hooks.register(source, hook);
+        self
+    }
+
+    /// Make `paginator` available to sources with `kind: custom` and
+    /// `paginator: <name>`.
+    pub fn with_paginator(
+        mut self,
+        name: impl Into<String>,
+        paginator: impl Paginator + 'static,
+    ) -> Self {
+        self.paginators.register(name, paginator);
+        self
+    }
 }
 
 fn _pagelabel(p: &Option<Pagination>) -> &'static str {
@@ -60,17 +361,67 @@ fn _pagelabel(p: &Option<Pagination>) -> &'static str {
         Some(Pagination::PageNumber { .. }) => "page_number",
         Some(Pagination::PageOnly { .. }) => "page_only",
         Some(Pagination::Cursor { .. }) => "cursor",
+        Some(Pagination::LinkHeader { .. }) => "link_header",
+        Some(Pagination::NextUrl { .. }) => "next_url",
+        Some(Pagination::Custom { .. }) => "custom",
         Some(Pagination::Default) => "default",
         None => "none",
     }
 }
 
+pub async fn run_pipeline(root: &str, cfg_path: &str) -> Result<()> {
+    run_pipeline_with(root, cfg_path, &RunOptions::default()).await
+}
+
 #[instrument(
     name = "run_pipeline",
     err,
     skip_all,                    // don’t record large args by defaul
+    fields(run_id = tracing::field::Empty)
 )]
-pub async fn run_pipeline(root: &str, cfg_path: &str) -> Result<()> {
+pub async fn run_pipeline_with(root: &str, cfg_path: &str, run: &RunOptions) -> Result<()> {
+    let run_id = new_run_id();
+    tracing::Span::current().record("run_id", run_id.as_str());
+
+    let t0 = Instant::now();
+    let started_at = chrono::Utc::now();
+    let mut summary = RunSummary::default();
+    let outcome = run_modules(root, cfg_path, run, &run_id, started_at, &mut summary).await;
+    summary.log_usage();
+    summary.log_completed(t0.elapsed(), &outcome);
+    if let Some(path) = &run.state_path {
+        let record = summary.to_record(&run_id, started_at, t0.elapsed(), &outcome);
+        if let Err(e) = record_run(path, record).await {
+            warn!(error = %e, "could not record the run history");
+        }
+    }
+    outcome
+}
+
+async fn write_run_audit(
+    audit: &AuditConfig,
+    cfg: &Config,
+    conns: &mut TargetConnections,
+    modules: &[ModuleAudit],
+) -> Result<()> {
+    let tgt = cfg.target(&audit.sink).ok_or_else(|| {
+        errors::ApitapError::ConfigError(format!("target not found in config: {}", audit.sink))
+    })?;
+    let (writer, _) = conns
+        .acquire(&audit.sink, tgt)
+        .await?
+        .make_writer(&audit.writer_opts())?;
+    write_audit(&audit.table, modules, writer).await
+}
+
+async fn run_modules(
+    root: &str,
+    cfg_path: &str,
+    run: &RunOptions,
+    run_id: &str,
+    started_at: chrono::DateTime<chrono::Utc>,
+    summary: &mut RunSummary,
+) -> Result<()> {
     info!("═══════════════════════════════════════════════════════════");
     info!("🚀 Starting Apitap Pipeline Execution");
     info!("═══════════════════════════════════════════════════════════");
@@ -81,116 +432,850 @@ pub async fn run_pipeline(root: &str, cfg_path: &str) -> Result<()> {
     let names = list_sql_templates(root)?;
     info!("📂 Discovered {} SQL module(s)", names.len());
 
-    let cfg = load_config_from_path(cfg_path)?;
+    let cfg =
+        load_config_with_secrets(cfg_path, &SecretResolvers::from_env(reqwest::Client::new()))
+            .await?;
     info!("⚙️  Configuration loaded successfully");
+    summary.schedule = cfg.schedule.as_ref().map(Schedule::interval).transpose()?;
+    if let Some(audit) = &cfg.audit {
+        if cfg.target(&audit.sink).is_none() {
+            return Err(errors::ApitapError::ConfigError(format!(
+                "audit: target not found in config: {}",
+                audit.sink
+            )));
+        }
+    }
+
+    let mut vars = cfg.vars.clone();
+    vars.extend(run.vars.clone());
+    let query_template = QueryTemplate::new(started_at, vars);
 
     // Build templating env
     let capture = Arc::new(Mutex::new(RenderCapture::default()));
     let env = build_env_with_captures(root, &capture);
 
-    // Shared fetch options
+    // Shared fetch options, with the config's `fetch:` defaults
     let fetch_opts = FetchOpts {
         concurrency: CONCURRENCY,
         default_page_size: DEFAULT_PAGE_SIZE,
         fetch_batch_size: FETCH_BATCH_SIZE,
-    };
+        flush_interval: None,
+    }
+    .with_overrides(&cfg.fetch)?;
     debug!(?fetch_opts, "fetch options");
+    if run.low_memory {
+        if configure_shared_context(ContextLimits::LOW_MEMORY) {
+            info!("🪶 low-memory mode");
+        } else {
+            warn!("--low-memory: the query engine was already sized, keeping its limits");
+        }
+    }
+
+    let retry_state = match &run.state_path {
+        Some(path) => Some(Arc::new(RetryStateStore::open(path).await?)),
+        None if run.resume => {
+            return Err(errors::ApitapError::ConfigError(
+                "--resume needs a state store".to_string(),
+            ))
+        }
+        None => None,
+    };
+
+    match run
+        .vcr
+        .as_ref()
+        .map(|vcr| (vcr.mode(), vcr.dir().display()))
+    {
+        Some((VcrMode::Record, dir)) => info!(%dir, "🎞️ recording page responses"),
+        Some((VcrMode::Replay, dir)) => info!(%dir, "🎞️ replaying recorded responses"),
+        None => {}
+    }
+
+    let bandwidth = run.max_bandwidth.map(|bps| {
+        info!(bytes_per_sec = bps, "outbound bandwidth capped");
+        Arc::new(BandwidthLimiter::new(bps))
+    });
+
+    let usage = Arc::new(UsageMeter::default());
+
+    let mut stale_modules: Vec<String> = Vec::new();
+    let mut diverged_modules: Vec<String> = Vec::new();
+    // Modules whose tables hold duplicate keys, with the extra rows.
+    let mut duplicated_modules: Vec<(String, u64, bool)> = Vec::new();
+    let mut deprecations: Vec<(String, DeprecationNotice)> = Vec::new();
+    let mut late_modules: Vec<String> = Vec::new();
+    let alert_client = match &cfg.proxy {
+        Some(proxy_cfg) => reqwest::Client::builder()
+            .proxy(proxy_cfg.to_proxy()?)
+            .build()?,
+        None => reqwest::Client::new(),
+    };
+
+    // One connection per sink, shared by its modules and closed when the run ends.
+    let mut conns = TargetConnections::new();
+    // Modules recorded in the `audit:` table, and the one running.
+    let mut audited: Vec<ModuleAudit> = Vec::new();
+    let mut audit_running: Option<ModuleAudit> = None;
 
     // Process each template
-    for (idx, name) in names.into_iter().enumerate() {
-        let span = tracing::info_span!("module", idx = idx + 1, name = %name);
-        let _g = span.enter();
-
-        let rendered = render_one(&env, &capture, &name)?;
-        let source_name = &rendered.capture.source;
-        let sink_name = &rendered.capture.sink;
-
-        // Resolve source/target from config
-        let src = match cfg.source(source_name) {
-            Some(s) => s,
-            None => {
-                return Err(errors::ApitapError::PipelineError(format!(
-                    "source not found in config: {source_name}"
-                )));
+    let modules = async {
+        for (idx, name) in names.into_iter().enumerate() {
+            let span = tracing::info_span!(
+                "module",
+                idx = idx + 1,
+                module = %name,
+                source = tracing::field::Empty
+            );
+            let _g = span.enter();
+
+            let rendered = render_one(&env, &capture, &name)?;
+            let source_name = &rendered.capture.source;
+            span.record("source", source_name.as_str());
+
+            match rendered.capture.status(cfg.source(source_name)) {
+                ModuleStatus::Disabled(reason) => {
+                    info!(module = %name, %reason, "⏸️  Module disabled, skipping");
+                    summary.modules_skipped += 1;
+                    continue;
+                }
+                ModuleStatus::Deprecated(note) => {
+                    warn!(module = %name, %note, "module is deprecated");
+                }
+                ModuleStatus::Enabled => {}
             }
-        };
-        let tgt = match cfg.target(sink_name) {
-            Some(t) => t,
-            None => {
-                return Err(errors::ApitapError::PipelineError(format!(
-                    "target not found in config: {sink_name}"
+            summary.current_module = Some((name.clone(), rendered.doc.clone()));
+            let statements = rendered.statements()?;
+
+            // Resolve source/target from config
+            let src = match cfg.source(source_name) {
+                Some(s) => s,
+                None => {
+                    return Err(errors::ApitapError::PipelineError(format!(
+                        "source not found in config: {source_name}"
+                    )));
+                }
+            };
+
+            if cfg.audit.is_some() {
+                audit_running = Some(ModuleAudit::start(run_id, &name, source_name));
+            }
+
+            // Destination table + inject into SQL
+            let dest_table = src.table_destination_name.as_deref().ok_or_else(|| {
+                warn!(%source_name, "missing table_destination_name");
+                errors::ApitapError::PipelineError(format!(
+                    "table_destination_name is required for source: {source_name}"
+                ))
+            })?;
+            // Snapshots append every run's extract; everything else merges.
+            let write_mode = if src.snapshot.enabled {
+                WriteMode::Append
+            } else {
+                WriteMode::Merge
+            };
+
+            let plugins = rendered
+                .capture
+                .transforms
+                .iter()
+                .map(|path| Ok(WasmTransform::load(path)?.into_page_hook()))
+                .collect::<Result<Vec<_>>>()?;
+            let transforms = Arc::new(
+                TransformChain::from_source(src)?
+                    .with_page_hooks(plugins)
+                    .with_page_hooks(run.page_hooks.for_source(source_name).iter().cloned()),
+            );
+            if src.body.is_some() && src.method == RequestMethod::Get {
+                return Err(errors::ApitapError::ConfigError(format!(
+                    "source {source_name} has a body but uses GET; set method: POST or PUT"
                 )));
             }
-        };
+            let deprecation = Arc::new(DeprecationWatch::new(source_name.clone()));
+            let progress = match &retry_state {
+                Some(store) => {
+                    Some(ProgressTracker::start(Arc::clone(store), name.clone(), run.resume).await)
+                }
+                None => None,
+            };
+            let limits = ResponseLimits::default()
+                .with_overrides(&cfg.fetch)?
+                .with_overrides(&src.fetch)?;
+            let mut request = RequestOptions {
+                throttle: Some(Arc::new(ServerThrottle::new(source_name.clone()))),
+                rate_limit: src
+                    .rate_limit
+                    .as_ref()
+                    .map(|limit| limit.limiter().map(Arc::new))
+                    .transpose()?,
+                deprecation: Some(Arc::clone(&deprecation)),
+                bandwidth: bandwidth.clone(),
+                usage: Some(Arc::clone(&usage)),
+                failures: retry_state
+                    .as_ref()
+                    .map(|store| RetryTracker::new(Arc::clone(store), name.clone(), run.resume)),
+                downloads: retry_state
+                    .as_ref()
+                    .filter(|_| src.ndjson.checkpoint_every.is_some())
+                    .map(|store| DownloadTracker::new(Arc::clone(store), name.clone(), run.resume)),
+                http_cache: match (src.http_cache, &retry_state) {
+                    (false, _) => None,
+                    // A skipped page would be missing from the snapshot.
+                    (true, _) if src.snapshot.enabled => {
+                        return Err(errors::ApitapError::ConfigError(format!(
+                            "source {source_name}: http_cache cannot be used with snapshot"
+                        )));
+                    }
+                    (true, Some(store)) => Some(HttpCache::new(Arc::clone(store), name.clone())),
+                    (true, None) => {
+                        warn!(%source_name, "http_cache needs a state store; not caching");
+                        None
+                    }
+                },
+                progress: progress.clone(),
+                method: src.method,
+                body: src
+                    .body
+                    .as_ref()
+                    .map(|body| RequestBody::encode(body, src.content_type))
+                    .transpose()?,
+                format: src.format,
+                csv: src.csv.clone(),
+                xml: src.xml.clone(),
+                sse: src.sse.clone(),
+                ndjson: src.ndjson.clone(),
+                sequence: src.sequence,
+                stream_json: run.low_memory,
+                limits,
+                vcr: run.vcr.as_ref().map(|vcr| vcr.source(source_name)),
+                memory: limits
+                    .max_in_flight
+                    .map(|bytes| Arc::new(MemoryBudget::new(bytes))),
+                // Needs the source's client; set once it is built.
+                auth: None,
+                failure_samples: match (src.sample_failures, &run.report_dir) {
+                    (Some(0) | None, _) => None,
+                    (Some(limit), Some(dir)) => Some(Arc::new(FailureSampler::new(
+                        std::path::Path::new(dir)
+                            .join(run_id)
+                            .join("failures")
+                            .join(&name),
+                        limit,
+                    ))),
+                    (Some(_), None) => {
+                        warn!(%source_name, "sample_failures needs a report directory; not sampling");
+                        None
+                    }
+                },
+            };
+
+            if let Some(lookback) = src
+                .incremental
+                .as_ref()
+                .and_then(|inc| inc.lookback.as_ref())
+            {
+                lookback.check_merge(
+                    source_name,
+                    &write_mode,
+                    src.primary_key_in_dest.as_deref(),
+                )?;
+            }
+            // Where an incremental source picks up, and what it reaches this time.
+            let (since, watermark) = match (&src.incremental, &retry_state) {
+                (None, _) => (None, None),
+                (Some(incremental), None) => {
+                    warn!(%source_name, "incremental needs a state store; fetching everything");
+                    (incremental.since(None)?, None)
+                }
+                (Some(incremental), Some(store)) => {
+                    let stored = store.watermark(&name).await;
+                    let stored = incremental.stored(&name, stored.as_ref());
+                    let since = if run.full_refresh {
+                        incremental.since(None)?
+                    } else {
+                        incremental.since(stored)?
+                    };
+                    match &since {
+                        Some(since) => info!(%source_name, %since, "⏩ fetching from watermark"),
+                        None => info!(%source_name, "⏩ no watermark yet, fetching everything"),
+                    }
+                    let tracker = WatermarkTracker::new(
+                        incremental.cursor.clone(),
+                        stored.map(|mark| mark.value.as_str()),
+                    );
+                    (since, Some(Arc::new(tracker)))
+                }
+            };
+
+            // Validated up front so a bad `keep` fails before anything is fetched.
+            let module_started_at = chrono::Utc::now();
+            let retention_cutoff = src
+                .retention
+                .as_ref()
+                .map(|policy| policy.cutoff(module_started_at))
+                .transpose()?;
+            let snapshot_retention = match src.snapshot.retention() {
+                Some(policy) => Some((policy.cutoff(module_started_at)?, policy)),
+                None => None,
+            };
+
+            let reconcile_key = match &src.reconcile {
+                None => None,
+                // Every snapshot appends; none holds the whole extract.
+                Some(_) if src.snapshot.enabled => {
+                    return Err(errors::ApitapError::ConfigError(format!(
+                        "source {source_name}: reconcile cannot be used with snapshot"
+                    )));
+                }
+                Some(reconcile) => Some(
+                    reconcile
+                        .key
+                        .as_deref()
+                        .or(src.primary_key_in_dest.as_deref())
+                        .ok_or_else(|| {
+                            errors::ApitapError::ConfigError(format!(
+                                "source {source_name}: reconcile needs a key or primary_key_in_dest"
+                            ))
+                        })?,
+                ),
+            };
 
-        // HTTP client
-        let mut http = Http::new(src.url.clone());
+            // Target writers via factory, one per statement of the module
+            let mut routes = Vec::with_capacity(statements.len());
+            let mut truncate_hooks = Vec::new();
+            let mut seen_keys = Vec::new();
+            let collector = (!src.rollups.is_empty()).then(|| {
+                Arc::new(RollupCollector::new(
+                    statements[0].table.as_deref().unwrap_or(dest_table),
+                ))
+            });
+            for (idx, stmt) in statements.iter().enumerate() {
+                let tgt = cfg.target(&stmt.sink).ok_or_else(|| {
+                    errors::ApitapError::PipelineError(format!(
+                        "target not found in config: {}",
+                        stmt.sink
+                    ))
+                })?;
+                let writer_opts = WriterOpts {
+                    dest_table: stmt.table.as_deref().unwrap_or(dest_table),
+                    // Keys repeat across snapshots.
+                    primary_key: src
+                        .primary_key_in_dest
+                        .clone()
+                        .filter(|_| !src.snapshot.enabled),
+                    infer_primary_key: src.infer_primary_key && !src.snapshot.enabled,
+                    batch_size: 50,
+                    sample_size: 10,
+                    auto_create: true,
+                    auto_truncate: false,
+                    truncate_first: false,
+                    write_mode: write_mode.clone(),
+                    mutation_report: src.mutation_report,
+                    binary_columns: src
+                        .binary_fields
+                        .as_ref()
+                        .map(BinaryFields::columns)
+                        .unwrap_or_default(),
+                    compressed_columns: src.compress_columns.clone(),
+                    explain_first_batch: run.explain_first_batch,
+                    coercion_policy: src.coercion_policy,
+                };
+                debug!(?writer_opts, "writer opts");
 
-        if let Some(header_from_cfg) = src.headers.clone() {
-            for header in header_from_cfg {
-                http = http.header(header.key, header.value);
+                let conn = conns.acquire(&stmt.sink, tgt).await?;
+                let (writer, maybe_truncate) = conn.make_writer(&writer_opts)?;
+                let mut chain = MiddlewareChain::from_source(src);
+                if let Some(stamp) = src.snapshot.stamp(module_started_at) {
+                    chain = chain.with(stamp);
+                }
+                if let (0, Some(collector)) = (idx, &collector) {
+                    chain = chain.with_shared(collector.clone());
+                }
+                if let Some(key) = reconcile_key {
+                    let seen = Arc::new(SeenKeys::new(key));
+                    chain = chain.with_shared(seen.clone());
+                    seen_keys.push(seen);
+                }
+                let writer = chain.wrap(writer);
+                truncate_hooks.extend(maybe_truncate);
+                routes.push((
+                    writer_opts.dest_table.to_string(),
+                    stmt.sql.replace(source_name, dest_table),
+                    writer,
+                    MetadataColumns::new(tgt.metadata_columns(), run_id, source_name.as_str()),
+                ));
             }
-        }
 
-        let client = http.build_client();
-        let url_s = http.get_url();
-        let url = reqwest::Url::parse(&url_s)?;
+            // Rollups aggregate the first statement's rows, by default into its sink.
+            let mut rollups = Vec::with_capacity(src.rollups.len());
+            for rollup in &src.rollups {
+                let sink = rollup.sink.as_deref().unwrap_or(&statements[0].sink);
+                let tgt = cfg.target(sink).ok_or_else(|| {
+                    errors::ApitapError::PipelineError(format!(
+                        "target not found in config: {sink}"
+                    ))
+                })?;
+                let writer_opts = WriterOpts {
+                    dest_table: &rollup.table,
+                    primary_key: rollup.primary_key.clone(),
+                    infer_primary_key: false,
+                    batch_size: 50,
+                    sample_size: 10,
+                    auto_create: true,
+                    auto_truncate: false,
+                    truncate_first: false,
+                    write_mode: rollup.write_mode(),
+                    mutation_report: false,
+                    binary_columns: Vec::new(),
+                    compressed_columns: Vec::new(),
+                    explain_first_batch: run.explain_first_batch,
+                    coercion_policy: src.coercion_policy,
+                };
+                let (writer, _) = conns.acquire(sink, tgt).await?.make_writer(&writer_opts)?;
+                rollups.push(Rollup {
+                    table: rollup.table.clone(),
+                    sql: rollup.sql.clone(),
+                    writer,
+                    write_mode: rollup.write_mode(),
+                });
+            }
 
-        // Destination table + inject into SQL
-        let dest_table = src.table_destination_name.as_deref().ok_or_else(|| {
-            warn!(%source_name, "missing table_destination_name");
-            errors::ApitapError::PipelineError(format!(
-                "table_destination_name is required for source: {source_name}"
-            ))
-        })?;
-        let sql = rendered.sql.replace(source_name, dest_table);
-
-        // Target writer via factory
-        let writer_opts = WriterOpts {
-            dest_table,
-            primary_key: src.primary_key_in_dest.clone(),
-            batch_size: 50,
-            sample_size: 10,
-            auto_create: true,
-            auto_truncate: false,
-            truncate_first: false,
-            write_mode: WriteMode::Merge,
-        };
-        debug!(?writer_opts, "writer opts");
-
-        let conn = tgt.create_conn().await?;
-        let (writer, maybe_truncate) = conn.make_writer(&writer_opts)?;
-        if let Some(hook) = maybe_truncate {
-            hook().await?;
+            // A routed module is as fresh as its first destination.
+            if let Some(freshness) = &rendered.capture.freshness {
+                let (table, _, writer, _) = &routes[0];
+                let newest = writer.newest_loaded_at(&freshness.column).await?;
+                match freshness.evaluate(newest, chrono::Utc::now()) {
+                    FreshnessStatus::Fresh { age } if run.skip_if_fresh => {
+                        info!(
+                            module = %name,
+                            table = %table,
+                            age_secs = age.as_secs(),
+                            max_age_secs = freshness.max_age.as_secs(),
+                            "⏭️  Destination is fresh, skipping module"
+                        );
+                        if let Some(audit) = audit_running.take() {
+                            audited.push(audit.skipped());
+                        }
+                        summary.modules_skipped += 1;
+                        summary.current_module = None;
+                        continue;
+                    }
+                    FreshnessStatus::Fresh { .. } => {}
+                    FreshnessStatus::Stale { age } => {
+                        warn!(
+                            module = %name,
+                            table = %table,
+                            age_secs = age.as_secs(),
+                            max_age_secs = freshness.max_age.as_secs(),
+                            severity = ?freshness.severity,
+                            "freshness contract violated"
+                        );
+                        if freshness.severity == FreshnessSeverity::Error {
+                            stale_modules.push(name.clone());
+                        }
+                    }
+                    FreshnessStatus::Unknown => {
+                        debug!(
+                            module = %name,
+                            table = %table,
+                            column = %freshness.column,
+                            "no load timestamp (is the column in the target's metadata_columns?), freshness unknown"
+                        );
+                    }
+                }
+            }
+
+            for hook in truncate_hooks {
+                hook().await?;
+            }
+
+            let tables: Vec<String> = routes.iter().map(|(table, ..)| table.clone()).collect();
+            let route_writers: Vec<(String, Arc<dyn DataWriter>)> = routes
+                .iter()
+                .map(|(table, _, writer, _)| (table.clone(), Arc::clone(writer)))
+                .collect();
+            let schemas = Arc::new(SchemaCapture::default());
+            let mut page_writer = routes.into_iter().fold(
+                DataFusionPageWriter::routed(dest_table)
+                    .with_transforms(transforms)
+                    .with_schema_capture(Arc::clone(&schemas)),
+                |page_writer, (table, sql, writer, metadata)| {
+                    let page_writer = page_writer.with_route(table, sql, writer);
+                    match metadata {
+                        Some(metadata) => page_writer.with_metadata_columns(metadata),
+                        None => page_writer,
+                    }
+                },
+            );
+            if let Some(binary) = &src.binary_fields {
+                page_writer =
+                    page_writer.with_binary_fields(Arc::new(BinaryFields::from_config(binary)?));
+            }
+            if run.low_memory {
+                page_writer = page_writer.with_batch_rows(LOW_MEMORY_BATCH_SIZE);
+            }
+            if let Some(tracker) = &watermark {
+                page_writer = page_writer.with_watermark(Arc::clone(tracker));
+            }
+
+            info!("───────────────────────────────────────────────────────────");
+            info!(
+                "📋 Module: {} | Source: {} → Table: {}",
+                name,
+                source_name,
+                tables.join(", ")
+            );
+            info!("🔄 Starting ETL Pipeline...");
+            let step_t0 = Instant::now();
+            // Alerts while the module runs; never stops it.
+            let sla_watch = rendered
+                .capture
+                .sla
+                .as_ref()
+                .map(|sla| {
+                    SlaWatch::start(
+                        sla,
+                        &cfg.alerts,
+                        alert_client.clone(),
+                        run_id,
+                        &name,
+                        rendered.doc.owner.as_deref(),
+                    )
+                })
+                .transpose()?;
+            if run.mock && src.mock.is_none() {
+                warn!(%source_name, "--mock: source has no mock fixture, fetching it live");
+            }
+            let mock = src.mock.as_ref().filter(|_| run.mock);
+            let stats = match (mock, &src.sql) {
+                (Some(mock), _) => {
+                    info!(%source_name, file = %mock.file, "🎭 serving mock fixture");
+                    run_mock_fetch(mock, src.data_path.as_deref(), page_writer, write_mode).await?
+                }
+                (None, Some(sql)) if src.incremental.is_some() => {
+                    let sql = SqlSource {
+                        query: query_template.render_query(
+                            source_name,
+                            &sql.query,
+                            since.as_deref(),
+                        )?,
+                        ..sql.clone()
+                    };
+                    run_sql_fetch(&sql, page_writer, write_mode).await?
+                }
+                (None, Some(sql)) => run_sql_fetch(sql, page_writer, write_mode).await?,
+                (None, None) => {
+                    // HTTP client
+                    let mut http = Http::new(src.url.clone());
+
+                    if let Some(header_from_cfg) = src.headers.clone() {
+                        for header in header_from_cfg {
+                            let value = header.resolve()?;
+                            http = if header.is_secret() {
+                                http.secret_header(header.key, value)
+                            } else {
+                                http.header(header.key, value)
+                            };
+                        }
+                    }
+                    if let Some(settings) = &src.http {
+                        let settings = settings.resolve()?;
+                        debug!(%source_name, ?settings, "source http settings");
+                        http = http.settings(settings);
+                    }
+                    if let Some(proxy_cfg) = cfg.proxy_for(src) {
+                        debug!(%source_name, url = %proxy_cfg.url, "using proxy");
+                        http = http.proxy(proxy_cfg.to_proxy()?);
+                    }
+                    if let Some(tls) = &src.tls {
+                        debug!(%source_name, "using source tls settings");
+                        if let Some(identity) = tls.identity()? {
+                            http = http.identity(identity);
+                        }
+                        for cert in tls.root_certificates()? {
+                            http = http.root_certificate(cert);
+                        }
+                    }
+                    if let Some(version) = &src.api_version {
+                        debug!(%source_name, version = %version.value, "pinning API version");
+                        http = version.apply(http);
+                    }
+                    if src.cookies || src.pre_request.is_some() {
+                        http = http.cookie_jar(Arc::new(reqwest::cookie::Jar::default()));
+                    }
+
+                    if let Some(value) = src
+                        .auth
+                        .as_ref()
+                        .map(SourceAuth::authorization)
+                        .transpose()?
+                        .flatten()
+                    {
+                        http = http.authorization(value);
+                    }
+
+                    let mut source_fetch_opts = fetch_opts.with_overrides(&src.fetch)?;
+                    if run.low_memory {
+                        source_fetch_opts = source_fetch_opts.low_memory();
+                    }
+                    if src.fetch != Default::default() {
+                        debug!(%source_name, ?source_fetch_opts, "source fetch options");
+                    }
+
+                    let client = http.build_client()?;
+                    let replaying = run
+                        .vcr
+                        .as_ref()
+                        .is_some_and(|vcr| vcr.mode() == VcrMode::Replay);
+                    if let Some(login) = src.pre_request.as_ref().filter(|_| !replaying) {
+                        login.send(&client).await?;
+                    }
+                    request.auth = src
+                        .auth
+                        .as_ref()
+                        .map(|auth| {
+                            let slot = retry_state.as_ref().map(|store| RefreshTokenSlot {
+                                store: Arc::clone(store),
+                                source: source_name.clone(),
+                            });
+                            auth.authenticator(&client, slot, Some(Arc::clone(&usage)))
+                        })
+                        .transpose()?;
+                    let url_s = http.get_url();
+                    let url = reqwest::Url::parse(&url_s)?;
+                    let mut params = src
+                        .query_params
+                        .as_deref()
+                        .map(|params| {
+                            query_template.render_with_watermark(
+                                source_name,
+                                params,
+                                since.as_deref(),
+                            )
+                        })
+                        .transpose()?;
+                    let param = src.incremental.as_ref().and_then(|inc| inc.param.as_ref());
+                    if let (Some(key), Some(since)) = (param, &since) {
+                        params.get_or_insert_with(Vec::new).push(QueryParam {
+                            key: key.clone(),
+                            value: since.as_str().into(),
+                        });
+                    }
+
+                    run_fetch(
+                        client,
+                        url,
+                        src.data_path.clone(),
+                        params,
+                        &src.pagination,
+                        src.start.as_ref(),
+                        page_writer,
+                        write_mode,
+                        &source_fetch_opts,
+                        &src.retry,
+                        request,
+                        &run.paginators,
+                    )
+                    .await?
+                }
+            };
+
+            if let Some(collector) = &collector {
+                collector.write_rollups(&rollups).await?;
+            }
+
+            if let Some(progress) = &progress {
+                progress.finish(stats.error_count == 0).await;
+            }
+            if let (Some(tracker), Some(store)) = (&watermark, &retry_state) {
+                if stats.error_count > 0 {
+                    warn!(
+                        module = %name,
+                        failed_pages = stats.error_count,
+                        "pages failed; watermark not moved"
+                    );
+                } else if let Some(mark) = tracker.watermark() {
+                    info!(module = %name, watermark = %mark.value, "⏩ watermark saved");
+                    store.save_watermark(&name, mark).await?;
+                }
+            }
+
+            for (table, writer) in &route_writers {
+                if let Some(mutations) = writer.merge_stats() {
+                    mutations.log(table);
+                }
+                if let Some(coercions) = writer.coercion_stats() {
+                    coercions.log(table);
+                }
+            }
+
+            if let Some(check) = &src.consistency_check {
+                let sinks: Vec<(String, Arc<dyn DataWriter>)> = statements
+                    .iter()
+                    .zip(&route_writers)
+                    .map(|(stmt, (table, writer))| {
+                        (format!("{}:{}", stmt.sink, table), Arc::clone(writer))
+                    })
+                    .collect();
+                let key = check.key.as_deref().or(src.primary_key_in_dest.as_deref());
+                let report = check_consistency(check, key, &sinks).await?;
+                report.log();
+                if check.fail_on_divergence && !report.diverged().is_empty() {
+                    diverged_modules.push(name.clone());
+                }
+            }
+
+            if let Some(check) = &src.duplicate_check {
+                match check.key.as_deref().or(src.primary_key_in_dest.as_deref()) {
+                    Some(key) => {
+                        let tables: Vec<(String, Arc<dyn DataWriter>)> = statements
+                            .iter()
+                            .zip(&route_writers)
+                            .map(|(stmt, (table, writer))| {
+                                (format!("{}:{}", stmt.sink, table), Arc::clone(writer))
+                            })
+                            .collect();
+                        let report = check_duplicates(key, src.snapshot.enabled, &tables).await?;
+                        report.log(&name, src.snapshot.enabled);
+                        let extra_rows = report.extra_rows();
+                        if extra_rows > 0 {
+                            duplicated_modules.push((
+                                name.clone(),
+                                extra_rows,
+                                check.fail_on_duplicates,
+                            ));
+                        }
+                    }
+                    None => warn!(
+                        module = %name,
+                        "duplicate_check needs a key or primary_key_in_dest; skipped"
+                    ),
+                }
+            }
+
+            if let (Some(reconcile), Some(key)) = (&src.reconcile, reconcile_key) {
+                let skipped = progress.as_ref().map_or(0, ProgressTracker::skipped_pages);
+                if stats.error_count > 0 {
+                    warn!(module = %name, "pages failed; not reconciling");
+                } else if since.is_some() {
+                    info!(module = %name, "fetched from a watermark; not reconciling");
+                } else if skipped > 0 || stats.not_modified > 0 {
+                    warn!(module = %name, "pages were skipped; not reconciling");
+                } else if src.start.is_some() || stats.resumed_lines > 0 {
+                    warn!(module = %name, "fetch did not start at the beginning; not reconciling");
+                } else if src.format == ResponseFormat::Sse {
+                    info!(module = %name, "event streams have no whole extract; not reconciling");
+                } else {
+                    let tables: Vec<_> = route_writers
+                        .iter()
+                        .zip(&seen_keys)
+                        .map(|((table, writer), seen)| {
+                            (table.clone(), Arc::clone(writer), Arc::clone(seen))
+                        })
+                        .collect();
+                    apply_reconcile(reconcile, key, chrono::Utc::now(), &tables).await?;
+                }
+            }
+
+            if let (Some(policy), Some(cutoff)) = (&src.retention, retention_cutoff) {
+                apply_retention(policy, cutoff, &route_writers).await?;
+            }
+            if let Some((cutoff, policy)) = &snapshot_retention {
+                apply_retention(policy, *cutoff, &route_writers).await?;
+            }
+
+            if let Some(notice) = deprecation.notice() {
+                deprecations.push((source_name.clone(), notice));
+            }
+
+            let sla_breached = match sla_watch {
+                Some(watch) => watch.finish().await,
+                None => false,
+            };
+            if sla_breached {
+                late_modules.push(name.clone());
+            }
+
+            if let Some(audit) = audit_running.take() {
+                audited.push(audit.finished(&stats));
+            }
+            summary.add_module(&stats);
+            summary.module_runs.insert(
+                name.clone(),
+                ModuleRun {
+                    doc: rendered.doc.clone(),
+                    sla_breached,
+                    ..ModuleRun::new(&stats, step_t0.elapsed(), schemas.take())
+                },
+            );
+            summary.current_module = None;
+            info!(
+                "✅ Module Completed | Records: {} | Duration: {}ms",
+                stats.total_items,
+                step_t0.elapsed().as_millis()
+            );
         }
+        Ok::<(), errors::ApitapError>(())
+    };
+
+    // On Ctrl-C stop between awaits and still drain the open connections.
+    let outcome = tokio::select! {
+        res = modules => res,
+        _ = tokio::signal::ctrl_c() => {
+            warn!("🛑 Interrupted, closing target connections");
+            Err(errors::ApitapError::PipelineError("interrupted".to_string()))
+        }
+    };
+    if let (Err(e), Some(audit)) = (&outcome, audit_running.take()) {
+        audited.push(audit.failed(e));
+    }
+    if let Some(audit) = &cfg.audit {
+        if let Err(e) = write_run_audit(audit, &cfg, &mut conns, &audited).await {
+            warn!(table = %audit.table, error = %e, "could not write the run audit");
+        }
+    }
+    conns.close_all().await;
+    summary.add_usage(&usage);
+    // Repeated at the end so the warnings are not lost among the page logs.
+    for (source, notice) in &deprecations {
+        notice.warn(source);
+    }
+
+    // Repeated at the end, per module, so the counts are not lost among the page logs.
+    for (module, extra_rows, _) in &duplicated_modules {
+        warn!(%module, extra_rows, "⚠️ module's tables hold duplicate keys");
+    }
+    // SLAs alert but never fail the run.
+    if !late_modules.is_empty() {
+        warn!(modules = %late_modules.join(", "), "⏰ module(s) exceeded their SLA");
+    }
+
+    outcome?;
+
+    // Stale modules were still refreshed above; fail afterwards so monitoring sees it.
+    if !stale_modules.is_empty() {
+        return Err(errors::ApitapError::PipelineError(format!(
+            "freshness contract violated for module(s): {}",
+            stale_modules.join(", ")
+        )));
+    }
+
+    if !diverged_modules.is_empty() {
+        return Err(errors::ApitapError::PipelineError(format!(
+            "sinks diverged for module(s): {}",
+            diverged_modules.join(", ")
+        )));
+    }
 
-        info!("───────────────────────────────────────────────────────────");
-        info!(
-            "📋 Module: {} | Source: {} → Table: {}",
-            name, source_name, dest_table
-        );
-        info!("🔄 Starting ETL Pipeline...");
-        let step_t0 = Instant::now();
-        let stats = run_fetch(
-            client,
-            url,
-            src.data_path.clone(),
-            src.query_params.clone(),
-            &src.pagination,
-            &sql,
-            dest_table,
-            writer,
-            writer_opts.write_mode,
-            &fetch_opts,
-            &src.retry,
-        )
-        .await?;
-
-        info!(
-            "✅ Module Completed | Records: {} | Duration: {}ms",
-            stats.total_items,
-            step_t0.elapsed().as_millis()
-        );
+    let failing: Vec<&str> = duplicated_modules
+        .iter()
+        .filter(|(_, _, fail)| *fail)
+        .map(|(module, _, _)| module.as_str())
+        .collect();
+    if !failing.is_empty() {
+        return Err(errors::ApitapError::PipelineError(format!(
+            "duplicate keys found for module(s): {}",
+            failing.join(", ")
+        )));
     }
 
     info!("═══════════════════════════════════════════════════════════");
@@ -0,0 +1,52 @@
+use std::io::Write;
+
+use serde::Serialize;
+use tracing::instrument;
+
+use crate::cmd::{print_json, OutputFormat, RunsCommand};
+use crate::errors::{ApitapError, Result};
+use crate::pipeline::run_history::{diff_runs, find_run, RunDiff};
+use crate::pipeline::state::export_state;
+
+/// `apitap runs diff --output json`.
+#[derive(Serialize)]
+struct DiffReport<'a> {
+    #[serde(flatten)]
+    diff: &'a RunDiff,
+    has_regressions: bool,
+}
+
+/// Run `apitap runs diff` against the store at `state_path`; the report
+/// goes to stdout.
+#[instrument(name = "runs", err, skip(action))]
+pub async fn run_runs_command(state_path: &str, action: &RunsCommand) -> Result<()> {
+    match action {
+        RunsCommand::Diff {
+            run_a,
+            run_b,
+            fail_on_regression,
+            output,
+        } => {
+            let runs = export_state(state_path).await?.runs;
+            let diff = diff_runs(find_run(&runs, run_a)?, find_run(&runs, run_b)?);
+            match output {
+                OutputFormat::Json => print_json(&DiffReport {
+                    diff: &diff,
+                    has_regressions: diff.has_regressions(),
+                })?,
+                OutputFormat::Text => {
+                    let mut stdout = std::io::stdout().lock();
+                    stdout.write_all(diff.render().as_bytes())?;
+                    stdout.flush()?;
+                }
+            }
+            if *fail_on_regression && diff.has_regressions() {
+                return Err(ApitapError::PipelineError(format!(
+                    "run {} regressed against {}",
+                    diff.b.run_id, diff.a.run_id
+                )));
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use std::io::{Read, Write};
+
+use tracing::{info, instrument};
+
+use crate::cmd::{print_json, OutputFormat, StateCommand};
+use crate::errors::Result;
+use crate::pipeline::state::{export_state, import_state, StateSnapshot};
+
+/// Run `apitap state export|import` against the store at `state_path`.
+#[instrument(name = "state", err, skip(action))]
+pub async fn run_state_command(state_path: &str, action: &StateCommand) -> Result<()> {
+    match action {
+        StateCommand::Export { file } => {
+            let snapshot = export_state(state_path).await?;
+            let bytes = snapshot.to_json()?;
+            match file {
+                Some(path) => {
+                    std::fs::write(path, &bytes)?;
+                    info!(
+                        to = %path,
+                        modules = snapshot.retry.modules.len(),
+                        "state exported"
+                    );
+                }
+                // Nothing else on stdout so the document can be piped.
+                None => {
+                    let mut stdout = std::io::stdout().lock();
+                    stdout.write_all(&bytes)?;
+                    stdout.write_all(b"\n")?;
+                }
+            }
+        }
+        StateCommand::Import {
+            input,
+            merge,
+            output,
+        } => {
+            let bytes = if input == "-" {
+                let mut buf = Vec::new();
+                std::io::stdin().read_to_end(&mut buf)?;
+                buf
+            } else {
+                std::fs::read(input)?
+            };
+            let snapshot = StateSnapshot::from_json(&bytes)?;
+            let modules = snapshot.retry.modules.len();
+            import_state(state_path, snapshot, *merge).await?;
+            info!(modules, merge = *merge, "state imported");
+            if *output == OutputFormat::Json {
+                print_json(&serde_json::json!({ "modules": modules, "merge": merge }))?;
+            }
+        }
+    }
+    Ok(())
+}
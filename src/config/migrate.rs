@@ -0,0 +1,271 @@
+//! `apitap config migrate`: rewrite configs written for older releases.
+//!
+//! A config records its schema in a top-level `version:`; files without one
+//! are version 1. Each step below upgrades a raw YAML document by one
+//! version and notes every rewrite, so the migrated file can say what
+//! changed and why.
+
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+
+use crate::errors::{ApitapError, Result};
+
+/// Schema version written by this release.
+pub const CONFIG_VERSION: u64 = 2;
+
+/// Auth keys that older configs set directly on a target.
+const AUTH_KEYS: &[&str] = &[
+    "username",
+    "password",
+    "username_env",
+    "password_env",
+    "private_key_path",
+    "public_key_fp",
+];
+
+/// Targets whose credentials live under `auth:`.
+const AUTH_TARGETS: &[&str] = &["postgres", "snowflake", "redshift"];
+
+/// One rewrite applied to the document.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Change {
+    /// Where it happened, e.g. `sources[0] (orders).pagination`.
+    pub path: String,
+    pub note: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Migration {
+    pub from_version: u64,
+    pub changes: Vec<Change>,
+    /// The migrated document, led by comments describing each change.
+    pub yaml: String,
+}
+
+impl Migration {
+    /// Whether the file needs rewriting at all.
+    pub fn is_noop(&self) -> bool {
+        self.from_version == CONFIG_VERSION && self.changes.is_empty()
+    }
+}
+
+/// Version declared by a document; 1 when absent.
+pub fn document_version(doc: &Value) -> Result<u64> {
+    match doc.get("version") {
+        None | Some(Value::Null) => Ok(1),
+        Some(v) => v.as_u64().ok_or_else(|| {
+            ApitapError::ConfigError(format!("config version must be an integer, got {v:?}"))
+        }),
+    }
+}
+
+/// Reject configs written for a newer release than this one.
+pub fn check_version(version: Option<u64>) -> Result<()> {
+    match version {
+        Some(v) if v > CONFIG_VERSION => Err(ApitapError::ConfigError(format!(
+            "config version {v} is newer than this apitap supports ({CONFIG_VERSION}); upgrade apitap"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Upgrade `doc` in place to [`CONFIG_VERSION`]; returns the version it
+/// had and the rewrites made.
+pub fn migrate_value(doc: &mut Value) -> Result<(u64, Vec<Change>)> {
+    let from = document_version(doc)?;
+    check_version(Some(from))?;
+    let Some(root) = doc.as_mapping_mut() else {
+        return Err(ApitapError::ConfigError(
+            "config must be a mapping with `sources` and `targets`".into(),
+        ));
+    };
+
+    let mut changes = Vec::new();
+    if from < 2 {
+        v1_to_v2(root, &mut changes);
+    }
+    // Keep `version` first, where readers look for it.
+    if from < CONFIG_VERSION {
+        root.remove("version");
+        let mut stamped = Mapping::new();
+        stamped.insert("version".into(), CONFIG_VERSION.into());
+        stamped.extend(std::mem::take(root));
+        *root = stamped;
+    }
+    Ok((from, changes))
+}
+
+/// Migrate the text of a config file.
+pub fn migrate_str(text: &str) -> Result<Migration> {
+    let mut doc: Value = serde_yaml::from_str(text)?;
+    let (from_version, changes) = migrate_value(&mut doc)?;
+
+    let mut yaml = String::new();
+    if from_version != CONFIG_VERSION || !changes.is_empty() {
+        yaml.push_str(&format!(
+            "# Migrated by `apitap config migrate` from config version {from_version} to {CONFIG_VERSION}.\n"
+        ));
+        for change in &changes {
+            yaml.push_str(&format!("# - {}: {}\n", change.path, change.note));
+        }
+        yaml.push('\n');
+    }
+    yaml.push_str(&serde_yaml::to_string(&doc)?);
+    Ok(Migration {
+        from_version,
+        changes,
+        yaml,
+    })
+}
+
+/// Whether `text` parses as YAML still using a pre-current schema.
+pub fn needs_migration(text: &str) -> bool {
+    serde_yaml::from_str::<Value>(text)
+        .ok()
+        .and_then(|mut doc| migrate_value(&mut doc).ok())
+        .is_some_and(|(from, changes)| from < CONFIG_VERSION && !changes.is_empty())
+}
+
+// ---------------------------------------------------------------- v1 -> v2
+
+fn v1_to_v2(root: &mut Mapping, changes: &mut Vec<Change>) {
+    if let Some(Value::Sequence(sources)) = root.get_mut("sources") {
+        for (i, source) in sources.iter_mut().enumerate() {
+            let Some(source) = source.as_mapping_mut() else {
+                continue;
+            };
+            let label = label("sources", i, source);
+            migrate_pagination(source, &label, changes);
+            for key in ["headers", "query_params"] {
+                key_value_list(source, key, &label, changes);
+            }
+        }
+    }
+    if let Some(Value::Sequence(targets)) = root.get_mut("targets") {
+        for (i, target) in targets.iter_mut().enumerate() {
+            let Some(target) = target.as_mapping_mut() else {
+                continue;
+            };
+            let label = label("targets", i, target);
+            nest_auth(target, &label, changes);
+        }
+    }
+}
+
+fn label(list: &str, index: usize, item: &Mapping) -> String {
+    match item.get("name").and_then(Value::as_str) {
+        Some(name) => format!("{list}[{index}] ({name})"),
+        None => format!("{list}[{index}]"),
+    }
+}
+
+/// `kind: PageNumber` spellings become snake_case; `size_param` became
+/// `per_page_param`.
+fn migrate_pagination(source: &mut Mapping, label: &str, changes: &mut Vec<Change>) {
+    let Some(pagination) = source.get_mut("pagination").and_then(Value::as_mapping_mut) else {
+        return;
+    };
+    let path = format!("{label}.pagination");
+    if let Some(kind) = pagination.get("kind").and_then(Value::as_str) {
+        let snake = snake_case(kind);
+        if snake != kind {
+            changes.push(Change {
+                path: path.clone(),
+                note: format!("kind `{kind}` renamed to `{snake}`"),
+            });
+            pagination.insert("kind".into(), snake.into());
+        }
+    }
+    let is_page_number = pagination.get("kind").and_then(Value::as_str) == Some("page_number");
+    if is_page_number && !pagination.contains_key("per_page_param") {
+        if let Some(size) = pagination.remove("size_param") {
+            pagination.insert("per_page_param".into(), size);
+            changes.push(Change {
+                path,
+                note: "`size_param` renamed to `per_page_param`".into(),
+            });
+        }
+    }
+}
+
+fn snake_case(kind: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in kind.chars().enumerate() {
+        if c == '-' {
+            out.push('_');
+        } else if c.is_ascii_uppercase() {
+            if i > 0 && !out.ends_with('_') {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// `headers: {Accept: application/json}` becomes a list of `key`/`value`
+/// entries, the only form supported now.
+fn key_value_list(source: &mut Mapping, key: &str, label: &str, changes: &mut Vec<Change>) {
+    let Some(Value::Mapping(map)) = source.get(key) else {
+        return;
+    };
+    let list: Vec<Value> = map
+        .iter()
+        .map(|(k, v)| {
+            let mut entry = Mapping::new();
+            entry.insert("key".into(), k.clone());
+            entry.insert("value".into(), scalar_string(v));
+            Value::Mapping(entry)
+        })
+        .collect();
+    source.insert(key.into(), Value::Sequence(list));
+    changes.push(Change {
+        path: format!("{label}.{key}"),
+        note: "mapping converted to a list of `key`/`value` entries".into(),
+    });
+}
+
+fn scalar_string(v: &Value) -> Value {
+    match v {
+        Value::Bool(b) => b.to_string().into(),
+        Value::Number(n) => n.to_string().into(),
+        other => other.clone(),
+    }
+}
+
+/// Credentials set directly on a postgres, snowflake or redshift target
+/// move under its `auth:` block.
+fn nest_auth(target: &mut Mapping, label: &str, changes: &mut Vec<Change>) {
+    let kind = target.get("type").and_then(Value::as_str).unwrap_or("");
+    if !AUTH_TARGETS.contains(&kind) {
+        return;
+    }
+    let moved: Vec<(&str, Value)> = AUTH_KEYS
+        .iter()
+        .filter_map(|key| target.remove(*key).map(|v| (*key, v)))
+        .collect();
+    if moved.is_empty() {
+        return;
+    }
+    let auth = target
+        .entry("auth".into())
+        .or_insert_with(|| Value::Mapping(Mapping::new()));
+    if !auth.is_mapping() {
+        *auth = Value::Mapping(Mapping::new());
+    }
+    let auth = auth.as_mapping_mut().expect("auth is a mapping");
+    let mut names = Vec::new();
+    for (key, value) in moved {
+        // An explicit `auth:` entry wins over the legacy one.
+        if !auth.contains_key(key) {
+            auth.insert(key.into(), value);
+        }
+        names.push(format!("`{key}`"));
+    }
+    changes.push(Change {
+        path: label.to_string(),
+        note: format!("{} moved under `auth`", names.join(", ")),
+    });
+}
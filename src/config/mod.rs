@@ -1,7 +1,7 @@
 use crate::errors::Result;
 use crate::pipeline::Config as PipelineConfig;
 use std::env;
-use std::{fs::File, path::Path};
+use std::path::Path;
 
 // Validate credentials for targets that require authentication.
 fn validate_credentials(cfg: &PipelineConfig) -> Result<()> {
@@ -17,9 +17,13 @@ fn validate_credentials(cfg: &PipelineConfig) -> Result<()> {
                 if has_env_keys {
                     // Ensure referenced env vars exist and are non-empty
                     // Safe: checked by has_env_keys = username_env.is_some() && password_env.is_some()
-                    let u_key = auth.username_env.as_ref()
+                    let u_key = auth
+                        .username_env
+                        .as_ref()
                         .expect("username_env is Some, checked by has_env_keys guard");
-                    let p_key = auth.password_env.as_ref()
+                    let p_key = auth
+                        .password_env
+                        .as_ref()
                         .expect("password_env is Some, checked by has_env_keys guard");
                     let u_val = env::var(u_key).map_err(|_| {
                         crate::errors::ApitapError::ConfigError(format!(
@@ -31,8 +35,7 @@ fn validate_credentials(cfg: &PipelineConfig) -> Result<()> {
                         return Err(crate::errors::ApitapError::ConfigError(format!(
                             "environment variable '{}' for postgres username is empty",
                             u_key
-                        ))
-                        .into());
+                        )));
                     }
                     let p_val = env::var(p_key).map_err(|_| {
                         crate::errors::ApitapError::ConfigError(format!(
@@ -44,24 +47,169 @@ fn validate_credentials(cfg: &PipelineConfig) -> Result<()> {
                         return Err(crate::errors::ApitapError::ConfigError(format!(
                             "environment variable '{}' for postgres password is empty",
                             p_key
-                        ))
-                        .into());
+                        )));
                     }
                     continue;
                 }
-                return Err(crate::errors::ApitapError::ConfigError(format!("postgres target '{}' missing credentials; provide username/password or username_env/password_env", pg.name)).into());
+                return Err(crate::errors::ApitapError::ConfigError(format!("postgres target '{}' missing credentials; provide username/password or username_env/password_env", pg.name)));
+            }
+            crate::pipeline::Target::Snowflake(sf) => {
+                let auth = &sf.auth;
+                if auth.username.is_none() && auth.username_env.is_none() {
+                    return Err(crate::errors::ApitapError::ConfigError(format!(
+                        "snowflake target '{}' missing username; provide username or username_env",
+                        sf.name
+                    )));
+                }
+                let has_password = auth.password.is_some() || auth.password_env.is_some();
+                let has_key_pair = auth.private_key_path.is_some() && auth.public_key_fp.is_some();
+                if !has_password && !has_key_pair {
+                    return Err(crate::errors::ApitapError::ConfigError(format!("snowflake target '{}' missing credentials; provide password/password_env or private_key_path with public_key_fp", sf.name)));
+                }
+                for key in [&auth.username_env, &auth.password_env]
+                    .into_iter()
+                    .flatten()
+                {
+                    let val = env::var(key).map_err(|_| {
+                        crate::errors::ApitapError::ConfigError(format!(
+                            "environment variable '{}' for snowflake target '{}' not set",
+                            key, sf.name
+                        ))
+                    })?;
+                    if val.trim().is_empty() {
+                        return Err(crate::errors::ApitapError::ConfigError(format!(
+                            "environment variable '{}' for snowflake target '{}' is empty",
+                            key, sf.name
+                        )));
+                    }
+                }
+            }
+            crate::pipeline::Target::Redshift(rs) => {
+                let auth = &rs.auth;
+                if auth.username.is_none() && auth.username_env.is_none()
+                    || auth.password.is_none() && auth.password_env.is_none()
+                {
+                    return Err(crate::errors::ApitapError::ConfigError(format!("redshift target '{}' missing credentials; provide username/password or username_env/password_env", rs.name)));
+                }
+                for key in [&auth.username_env, &auth.password_env]
+                    .into_iter()
+                    .flatten()
+                {
+                    let val = env::var(key).map_err(|_| {
+                        crate::errors::ApitapError::ConfigError(format!(
+                            "environment variable '{}' for redshift target '{}' not set",
+                            key, rs.name
+                        ))
+                    })?;
+                    if val.trim().is_empty() {
+                        return Err(crate::errors::ApitapError::ConfigError(format!(
+                            "environment variable '{}' for redshift target '{}' is empty",
+                            key, rs.name
+                        )));
+                    }
+                }
+            }
+            crate::pipeline::Target::Mongodb(mongo) => match &mongo.uri_env {
+                Some(key) => {
+                    let val = env::var(key).map_err(|_| {
+                        crate::errors::ApitapError::ConfigError(format!(
+                            "environment variable '{}' for mongodb target '{}' not set",
+                            key, mongo.name
+                        ))
+                    })?;
+                    if val.trim().is_empty() {
+                        return Err(crate::errors::ApitapError::ConfigError(format!(
+                            "environment variable '{}' for mongodb target '{}' is empty",
+                            key, mongo.name
+                        )));
+                    }
+                }
+                None if mongo.uri.is_none() => {
+                    return Err(crate::errors::ApitapError::ConfigError(format!(
+                        "mongodb target '{}' missing connection string; provide uri or uri_env",
+                        mongo.name
+                    )));
+                }
+                None => {}
+            },
+            crate::pipeline::Target::Webhook(hook) => {
+                if let Some(key) = &hook.bearer_token_env {
+                    let val = env::var(key).map_err(|_| {
+                        crate::errors::ApitapError::ConfigError(format!(
+                            "environment variable '{}' for webhook target '{}' not set",
+                            key, hook.name
+                        ))
+                    })?;
+                    if val.trim().is_empty() {
+                        return Err(crate::errors::ApitapError::ConfigError(format!(
+                            "environment variable '{}' for webhook target '{}' is empty",
+                            key, hook.name
+                        )));
+                    }
+                }
             }
+            crate::pipeline::Target::Parquet(_)
+            | crate::pipeline::Target::File(_)
+            | crate::pipeline::Target::Sqlite(_)
+            | crate::pipeline::Target::Kafka(_)
+            | crate::pipeline::Target::Delta(_)
+            | crate::pipeline::Target::Debug(_) => {}
         }
     }
     Ok(())
 }
 
+pub mod migrate;
+pub mod secrets;
 pub mod templating;
 
-pub fn load_config_from_path<P: AsRef<Path>>(path: P) -> Result<PipelineConfig> {
-    let f = File::open(path)?;
-    let cfg: PipelineConfig = serde_yaml::from_reader(f)?;
+/// The typed config of `text` once parsed, checked for its schema version.
+fn parse_config(text: &str, parsed: serde_yaml::Result<PipelineConfig>) -> Result<PipelineConfig> {
+    let cfg = match parsed {
+        Ok(cfg) => cfg,
+        Err(e) if migrate::needs_migration(text) => {
+            return Err(crate::errors::ApitapError::ConfigError(format!(
+                "{e}; the file uses an older config schema, run `apitap config migrate` to update it"
+            )));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    migrate::check_version(cfg.version)?;
+    Ok(cfg)
+}
+
+/// [`parse_config`], with the credentials checked.
+fn finish_config(text: &str, parsed: serde_yaml::Result<PipelineConfig>) -> Result<PipelineConfig> {
+    let cfg = parse_config(text, parsed)?;
     // Validate credentials (ensures env vars referenced exist)
     validate_credentials(&cfg)?;
     Ok(cfg)
 }
+
+pub fn load_config_from_path<P: AsRef<Path>>(path: P) -> Result<PipelineConfig> {
+    let text = std::fs::read_to_string(path)?;
+    finish_config(&text, serde_yaml::from_str(&text))
+}
+
+/// Like [`load_config_from_path`] without requiring the credentials to be
+/// set, for commands that only look at the config.
+pub fn inspect_config_from_path<P: AsRef<Path>>(path: P) -> Result<PipelineConfig> {
+    let text = std::fs::read_to_string(path)?;
+    parse_config(&text, serde_yaml::from_str(&text))
+}
+
+/// Like [`load_config_from_path`], with the secret references among its
+/// values (`vault://...`, `aws-sm://...`, `gcp-sm://...`) fetched from
+/// `resolvers`.
+pub async fn load_config_with_secrets<P: AsRef<Path>>(
+    path: P,
+    resolvers: &secrets::SecretResolvers,
+) -> Result<PipelineConfig> {
+    let text = std::fs::read_to_string(path)?;
+    let mut doc: serde_yaml::Value = serde_yaml::from_str(&text)?;
+    if resolvers.resolve_yaml(&mut doc).await? == 0 {
+        // Parsed from the text again so errors keep their line numbers.
+        return finish_config(&text, serde_yaml::from_str(&text));
+    }
+    finish_config(&text, serde_yaml::from_value(doc))
+}
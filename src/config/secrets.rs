@@ -0,0 +1,610 @@
+//! Secret references in config values, resolved when the config is loaded.
+//!
+//! Any string value of the config may name a secret instead of holding it:
+//!
+//! ```yaml
+//! targets:
+//!   - type: postgres
+//!     auth:
+//!       username: etl
+//!       password: vault://secret/warehouse#password   # Vault KV v2: mount/path#field
+//! sources:
+//!   - name: stripe
+//!     headers:
+//!       - key: Authorization
+//!         value: aws-sm://prod/stripe#bearer          # AWS Secrets Manager: name or ARN[#json field]
+//!   - name: shopify
+//!     headers:
+//!       - key: X-Shopify-Access-Token
+//!         value: gcp-sm://my-project/shopify-token    # GCP Secret Manager: project/secret[/version][#field]
+//! ```
+//!
+//! Each backend reads its endpoint and credentials from the usual environment
+//! (`VAULT_ADDR`/`VAULT_TOKEN`, `AWS_REGION` plus the AWS credential chain,
+//! `GOOGLE_APPLICATION_CREDENTIALS` or the metadata server), so nothing
+//! secret has to sit in an env file. Other backends plug in through
+//! [`SecretResolver`].
+//!
+//! AWS credentials come from the same chain as `s3://` sinks: the
+//! `AWS_ACCESS_KEY_ID`/`AWS_SECRET_ACCESS_KEY` variables, a web identity
+//! token (EKS IRSA), ECS task or EKS pod identity credentials, then the EC2
+//! instance profile. Named profiles in `~/.aws` are not read; export the
+//! profile's keys or set `AWS_WEB_IDENTITY_TOKEN_FILE` instead.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use object_store::aws::{AmazonS3Builder, AwsCredential, AwsCredentialProvider};
+use object_store::StaticCredentialProvider;
+use reqwest::Client;
+use serde_json::{json, Value};
+use serde_yaml::Value as Yaml;
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::errors::{ApitapError, Result};
+use crate::http::jwt::{JwtAssertion, JwtConfig};
+use crate::http::oauth2::TokenManager;
+
+/// A reference with its scheme removed: `secret/warehouse#password` has the
+/// path `secret/warehouse` and the key `password`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretRef {
+    pub path: String,
+    /// Field of a structured secret.
+    pub key: Option<String>,
+}
+
+impl SecretRef {
+    pub fn parse(reference: &str) -> Self {
+        match reference.split_once('#') {
+            Some((path, key)) => Self {
+                path: path.to_string(),
+                key: Some(key.to_string()),
+            },
+            None => Self {
+                path: reference.to_string(),
+                key: None,
+            },
+        }
+    }
+}
+
+/// A secrets backend answering references of one URL scheme.
+#[async_trait]
+pub trait SecretResolver: Send + Sync {
+    /// The scheme, without `://`, e.g. `vault`.
+    fn scheme(&self) -> &str;
+
+    async fn resolve(&self, reference: &SecretRef) -> Result<String>;
+}
+
+/// The field `key` of a secret stored as JSON text; the whole text without
+/// a key.
+fn json_field(text: String, key: Option<&str>) -> Result<String> {
+    let Some(key) = key else {
+        return Ok(text);
+    };
+    let fields: BTreeMap<String, Value> = serde_json::from_str(&text)
+        .map_err(|_| ApitapError::ConfigError("secret is not a JSON object".to_string()))?;
+    field(&fields, key)
+}
+
+fn field(fields: &BTreeMap<String, Value>, key: &str) -> Result<String> {
+    match fields.get(key) {
+        Some(Value::String(value)) => Ok(value.clone()),
+        Some(value) => Ok(value.to_string()),
+        None => Err(ApitapError::ConfigError(format!(
+            "secret has no field '{key}'"
+        ))),
+    }
+}
+
+fn missing_env(what: &str, vars: &str) -> ApitapError {
+    ApitapError::ConfigError(format!("{what} needs {vars} set"))
+}
+
+async fn json_response(request: reqwest::RequestBuilder) -> Result<Value> {
+    let response = request.send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        // The body may echo the request; only the status is reported.
+        return Err(ApitapError::ConfigError(format!(
+            "secrets backend answered {status}"
+        )));
+    }
+    Ok(response.json().await?)
+}
+
+/// HashiCorp Vault, KV version 2: `vault://<mount>/<path>#<field>`.
+// The backends derive no `Debug`: they hold credentials.
+#[derive(Clone)]
+pub struct Vault {
+    client: Client,
+    addr: Option<String>,
+    token: Option<String>,
+    namespace: Option<String>,
+}
+
+impl Vault {
+    pub fn new(client: Client, addr: impl Into<String>, token: impl Into<String>) -> Self {
+        Self {
+            client,
+            addr: Some(addr.into()),
+            token: Some(token.into()),
+            namespace: None,
+        }
+    }
+
+    /// `VAULT_ADDR`, `VAULT_TOKEN` and `VAULT_NAMESPACE`; checked when a
+    /// reference is resolved.
+    pub fn from_env(client: Client) -> Self {
+        Self {
+            client,
+            addr: std::env::var("VAULT_ADDR").ok(),
+            token: std::env::var("VAULT_TOKEN").ok(),
+            namespace: std::env::var("VAULT_NAMESPACE").ok(),
+        }
+    }
+
+    pub fn with_namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespace = Some(namespace.into());
+        self
+    }
+}
+
+#[async_trait]
+impl SecretResolver for Vault {
+    fn scheme(&self) -> &str {
+        "vault"
+    }
+
+    async fn resolve(&self, reference: &SecretRef) -> Result<String> {
+        let (Some(addr), Some(token)) = (&self.addr, &self.token) else {
+            return Err(missing_env("vault://", "VAULT_ADDR and VAULT_TOKEN"));
+        };
+        let Some((mount, path)) = reference.path.split_once('/') else {
+            return Err(ApitapError::ConfigError(
+                "vault:// takes <mount>/<path>#<field>".to_string(),
+            ));
+        };
+        let url = format!("{}/v1/{mount}/data/{path}", addr.trim_end_matches('/'));
+        let mut request = self.client.get(url).header("X-Vault-Token", token);
+        if let Some(namespace) = &self.namespace {
+            request = request.header("X-Vault-Namespace", namespace);
+        }
+        let body = json_response(request).await?;
+        let fields: BTreeMap<String, Value> = serde_json::from_value(body["data"]["data"].clone())
+            .map_err(|_| {
+                ApitapError::ConfigError("vault answered without KV v2 data".to_string())
+            })?;
+        match (&reference.key, fields.len()) {
+            (Some(key), _) => field(&fields, key),
+            (None, 1) => field(&fields, fields.keys().next().expect("one field")),
+            (None, _) => Err(ApitapError::ConfigError(
+                "vault secret has several fields; name one after '#'".to_string(),
+            )),
+        }
+    }
+}
+
+/// AWS Secrets Manager: `aws-sm://<name or ARN>[#<json field>]`.
+#[derive(Clone)]
+pub struct AwsSecretsManager {
+    client: Client,
+    region: Option<String>,
+    endpoint: Option<String>,
+    /// The environment's credential chain when `None`.
+    credentials: Option<AwsCredentialProvider>,
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex_sha256(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+impl AwsSecretsManager {
+    /// Fixed credentials instead of the environment's chain.
+    pub fn new(client: Client, region: impl Into<String>, credential: AwsCredential) -> Self {
+        Self {
+            client,
+            region: Some(region.into()),
+            endpoint: None,
+            credentials: Some(Arc::new(StaticCredentialProvider::new(credential))),
+        }
+    }
+
+    /// `AWS_REGION` (or `AWS_DEFAULT_REGION`) and
+    /// `AWS_ENDPOINT_URL_SECRETS_MANAGER` (or `AWS_ENDPOINT_URL`); the
+    /// credentials are looked up on first use, see the module docs.
+    pub fn from_env(client: Client) -> Self {
+        let var = |key: &str| std::env::var(key).ok();
+        Self {
+            client,
+            region: var("AWS_REGION").or_else(|| var("AWS_DEFAULT_REGION")),
+            endpoint: var("AWS_ENDPOINT_URL_SECRETS_MANAGER").or_else(|| var("AWS_ENDPOINT_URL")),
+            credentials: None,
+        }
+    }
+
+    pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// The region of an ARN (`arn:aws:secretsmanager:<region>:...`), else
+    /// the configured one.
+    fn region(&self, name: &str) -> Option<String> {
+        name.strip_prefix("arn:")
+            .and_then(|arn| arn.split(':').nth(2))
+            .filter(|region| !region.is_empty())
+            .map(str::to_string)
+            .or_else(|| self.region.clone())
+    }
+
+    /// Current credentials; temporary ones are renewed by the provider.
+    async fn credential(&self, region: &str) -> Result<Arc<AwsCredential>> {
+        let provider = match &self.credentials {
+            Some(provider) => Arc::clone(provider),
+            // S3's builder assembles the chain; the bucket is never used.
+            None => AmazonS3Builder::from_env()
+                .with_region(region)
+                .with_bucket_name("apitap-secrets")
+                .build()?
+                .credentials()
+                .clone(),
+        };
+        provider.get_credential().await.map_err(|e| {
+            ApitapError::ConfigError(format!("aws-sm:// found no AWS credentials: {e}"))
+        })
+    }
+}
+
+#[async_trait]
+impl SecretResolver for AwsSecretsManager {
+    fn scheme(&self) -> &str {
+        "aws-sm"
+    }
+
+    async fn resolve(&self, reference: &SecretRef) -> Result<String> {
+        let region = self
+            .region(&reference.path)
+            .ok_or_else(|| missing_env("aws-sm://", "AWS_REGION"))?;
+        let credential = self.credential(&region).await?;
+        let endpoint = self
+            .endpoint
+            .clone()
+            .unwrap_or_else(|| format!("https://secretsmanager.{region}.amazonaws.com"));
+        let url = url::Url::parse(&endpoint)
+            .map_err(|e| ApitapError::ConfigError(format!("invalid aws endpoint: {e}")))?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.to_string(),
+            (None, _) => {
+                return Err(ApitapError::ConfigError(
+                    "aws endpoint has no host".to_string(),
+                ))
+            }
+        };
+
+        // Signature Version 4 over the GetSecretValue call.
+        let body = json!({ "SecretId": reference.path }).to_string();
+        let now = chrono::Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let mut headers = vec![
+            ("content-type", "application/x-amz-json-1.1".to_string()),
+            ("host", host),
+            ("x-amz-date", amz_date.clone()),
+            ("x-amz-target", "secretsmanager.GetSecretValue".to_string()),
+        ];
+        if let Some(token) = &credential.token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        headers.sort();
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{name}:{value}\n"))
+            .collect();
+        let canonical_request = format!(
+            "POST\n/\n\n{canonical_headers}\n{signed_headers}\n{}",
+            hex_sha256(body.as_bytes())
+        );
+        let scope = format!("{date}/{region}/secretsmanager/aws4_request");
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+            hex_sha256(canonical_request.as_bytes())
+        );
+        let key = hmac(format!("AWS4{}", credential.secret_key).as_bytes(), &date);
+        let key = hmac(&key, &region);
+        let key = hmac(&key, "secretsmanager");
+        let key = hmac(&key, "aws4_request");
+        let signature: String = hmac(&key, &string_to_sign)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+
+        let mut request = self.client.post(url).body(body).header(
+            "Authorization",
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{scope}, \
+                 SignedHeaders={signed_headers}, Signature={signature}",
+                credential.key_id
+            ),
+        );
+        for (name, value) in headers.into_iter().filter(|(name, _)| *name != "host") {
+            request = request.header(name, value);
+        }
+        let answer = json_response(request).await?;
+        let text = match (
+            answer["SecretString"].as_str(),
+            answer["SecretBinary"].as_str(),
+        ) {
+            (Some(text), _) => text.to_string(),
+            (None, Some(binary)) => STANDARD
+                .decode(binary)
+                .ok()
+                .and_then(|bytes| String::from_utf8(bytes).ok())
+                .ok_or_else(|| {
+                    ApitapError::ConfigError("aws secret binary is not UTF-8 text".to_string())
+                })?,
+            (None, None) => {
+                return Err(ApitapError::ConfigError(
+                    "aws answered without a secret value".to_string(),
+                ))
+            }
+        };
+        json_field(text, reference.key.as_deref())
+    }
+}
+
+/// GCP Secret Manager: `gcp-sm://<project>/<secret>[/<version>][#<json field>]`,
+/// version `latest` by default.
+#[derive(Clone)]
+pub struct GcpSecretManager {
+    client: Client,
+    endpoint: String,
+    access_token: Option<String>,
+    credentials_file: Option<String>,
+}
+
+const GCP_ENDPOINT: &str = "https://secretmanager.googleapis.com";
+const GCP_METADATA_TOKEN: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+const GCP_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+
+impl GcpSecretManager {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            endpoint: GCP_ENDPOINT.to_string(),
+            access_token: None,
+            credentials_file: None,
+        }
+    }
+
+    /// Authenticates with `GOOGLE_OAUTH_ACCESS_TOKEN`, else the
+    /// service-account key in `GOOGLE_APPLICATION_CREDENTIALS`, else the
+    /// metadata server.
+    pub fn from_env(client: Client) -> Self {
+        Self {
+            access_token: std::env::var("GOOGLE_OAUTH_ACCESS_TOKEN").ok(),
+            credentials_file: std::env::var("GOOGLE_APPLICATION_CREDENTIALS").ok(),
+            ..Self::new(client)
+        }
+    }
+
+    pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = endpoint.into();
+        self
+    }
+
+    pub fn with_access_token(mut self, token: impl Into<String>) -> Self {
+        self.access_token = Some(token.into());
+        self
+    }
+
+    async fn token(&self) -> Result<String> {
+        if let Some(token) = &self.access_token {
+            return Ok(token.clone());
+        }
+        if let Some(file) = &self.credentials_file {
+            let assertion = JwtAssertion::new(&JwtConfig {
+                service_account_file: Some(file.clone()),
+                token_url: None,
+                private_key_file: None,
+                private_key_env: None,
+                algorithm: jsonwebtoken::Algorithm::RS256,
+                issuer: None,
+                subject: None,
+                audience: None,
+                scopes: vec![GCP_SCOPE.to_string()],
+                key_id: None,
+                lifetime: None,
+                claims: BTreeMap::new(),
+                refresh_every: None,
+            })?;
+            return TokenManager::jwt_bearer(self.client.clone(), assertion, None)
+                .token()
+                .await;
+        }
+        let answer = json_response(
+            self.client
+                .get(GCP_METADATA_TOKEN)
+                .header("Metadata-Flavor", "Google"),
+        )
+        .await
+        .map_err(|_| {
+            missing_env(
+                "gcp-sm://",
+                "GOOGLE_APPLICATION_CREDENTIALS (or a GCP metadata server)",
+            )
+        })?;
+        answer["access_token"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| {
+                ApitapError::ConfigError("gcp metadata server sent no access token".to_string())
+            })
+    }
+}
+
+#[async_trait]
+impl SecretResolver for GcpSecretManager {
+    fn scheme(&self) -> &str {
+        "gcp-sm"
+    }
+
+    async fn resolve(&self, reference: &SecretRef) -> Result<String> {
+        let name = match reference.path.split('/').collect::<Vec<_>>().as_slice() {
+            [project, secret] => format!("projects/{project}/secrets/{secret}/versions/latest"),
+            [project, secret, version] => {
+                format!("projects/{project}/secrets/{secret}/versions/{version}")
+            }
+            ["projects", _, "secrets", _, "versions", _] => reference.path.clone(),
+            _ => {
+                return Err(ApitapError::ConfigError(
+                    "gcp-sm:// takes <project>/<secret>[/<version>]".to_string(),
+                ))
+            }
+        };
+        let url = format!("{}/v1/{name}:access", self.endpoint.trim_end_matches('/'));
+        let token = self.token().await?;
+        let answer = json_response(self.client.get(url).bearer_auth(token)).await?;
+        let text = answer["payload"]["data"]
+            .as_str()
+            .and_then(|data| STANDARD.decode(data).ok())
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or_else(|| {
+                ApitapError::ConfigError("gcp answered without a text payload".to_string())
+            })?;
+        json_field(text, reference.key.as_deref())
+    }
+}
+
+/// The backends consulted for `scheme://` values.
+#[derive(Clone, Default)]
+pub struct SecretResolvers {
+    resolvers: Vec<Arc<dyn SecretResolver>>,
+}
+
+impl std::fmt::Debug for SecretResolvers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.resolvers.iter().map(|r| r.scheme()))
+            .finish()
+    }
+}
+
+impl SecretResolvers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `vault://`, `aws-sm://` and `gcp-sm://`, configured from the
+    /// environment.
+    pub fn from_env(client: Client) -> Self {
+        Self::new()
+            .with(Vault::from_env(client.clone()))
+            .with(AwsSecretsManager::from_env(client.clone()))
+            .with(GcpSecretManager::from_env(client))
+    }
+
+    /// Add `resolver`, replacing any for the same scheme.
+    pub fn with(mut self, resolver: impl SecretResolver + 'static) -> Self {
+        self.resolvers.retain(|r| r.scheme() != resolver.scheme());
+        self.resolvers.push(Arc::new(resolver));
+        self
+    }
+
+    fn resolver_for<'a>(&'a self, value: &'a str) -> Option<(&'a dyn SecretResolver, &'a str)> {
+        let (scheme, reference) = value.split_once("://")?;
+        self.resolvers
+            .iter()
+            .find(|r| r.scheme() == scheme)
+            .map(|r| (r.as_ref(), reference))
+    }
+
+    /// The secret `value` refers to; `None` when it is no reference.
+    pub async fn resolve(&self, value: &str) -> Result<Option<String>> {
+        let Some((resolver, reference)) = self.resolver_for(value) else {
+            return Ok(None);
+        };
+        resolver
+            .resolve(&SecretRef::parse(reference))
+            .await
+            .map(Some)
+            .map_err(|e| ApitapError::ConfigError(format!("secret '{value}': {e}")))
+    }
+
+    /// Replace every reference among the string values of `doc` with its
+    /// secret; how many values were replaced. Each secret is fetched once.
+    pub async fn resolve_yaml(&self, doc: &mut Yaml) -> Result<usize> {
+        let mut references = BTreeMap::new();
+        collect(doc, &mut |value| {
+            if self.resolver_for(value).is_some() {
+                references.insert(value.to_string(), String::new());
+            }
+        });
+        for (reference, secret) in references.iter_mut() {
+            *secret = self.resolve(reference).await?.expect("a reference");
+        }
+        let mut replaced = 0;
+        replace(doc, &mut |value| {
+            let secret = references.get(value.as_str())?;
+            replaced += 1;
+            Some(secret.clone())
+        });
+        if replaced > 0 {
+            info!(
+                references = references.len(),
+                values = replaced,
+                "🔐 resolved secret references"
+            );
+        }
+        Ok(replaced)
+    }
+}
+
+fn collect(doc: &Yaml, found: &mut impl FnMut(&str)) {
+    match doc {
+        Yaml::String(value) => found(value),
+        Yaml::Sequence(items) => items.iter().for_each(|item| collect(item, found)),
+        Yaml::Mapping(map) => map.values().for_each(|value| collect(value, found)),
+        Yaml::Tagged(tagged) => collect(&tagged.value, found),
+        _ => {}
+    }
+}
+
+fn replace(doc: &mut Yaml, secret: &mut impl FnMut(&String) -> Option<String>) {
+    match doc {
+        Yaml::String(value) => {
+            if let Some(resolved) = secret(value) {
+                *value = resolved;
+            }
+        }
+        Yaml::Sequence(items) => items.iter_mut().for_each(|item| replace(item, secret)),
+        Yaml::Mapping(map) => map.values_mut().for_each(|value| replace(value, secret)),
+        Yaml::Tagged(tagged) => replace(&mut tagged.value, secret),
+        _ => {}
+    }
+}
@@ -1,23 +1,227 @@
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 
-use crate::errors::Result;
+use crate::errors::{ApitapError, Result};
+use crate::pipeline::freshness::{parse_duration, Freshness};
+use crate::pipeline::sla::Sla;
+use crate::pipeline::Source;
+use datafusion::sql::parser::{DFParser, Statement as DFStatement};
+use datafusion::sql::sqlparser::ast::Statement;
 use minijinja::path_loader;
 use minijinja::value::{Kwargs, Value};
 use minijinja::{Environment, Error as MjError};
+use serde::{Deserialize, Serialize};
 use walkdir::WalkDir;
 
 #[derive(Debug, Default, Clone)]
 pub struct RenderCapture {
     pub sink: String,
     pub source: String,
+    pub freshness: Option<Freshness>,
+    /// `{{ sla(max_duration="15m", alert="slack") }}`.
+    pub sla: Option<Sla>,
+    /// WASM plugin paths from `{{ transform("...") }}`, in call order.
+    pub transforms: Vec<String>,
+    /// `{{ sink(name=..., table=...) }}` calls, one per routed statement.
+    pub routes: Vec<SinkRoute>,
+    /// `{{ module(enabled=false) }}`.
+    pub disabled: bool,
+    /// `{{ module(deprecated="use orders_v2") }}`.
+    pub deprecated: Option<String>,
 }
 
+/// Whether a module runs, from its own `module(...)` call and its source's
+/// `enabled` / `deprecated`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", content = "reason", rename_all = "snake_case")]
+pub enum ModuleStatus {
+    Enabled,
+    /// Runs, with a warning carrying the note.
+    Deprecated(String),
+    /// Skipped; says which setting disabled it.
+    Disabled(String),
+}
+
+impl RenderCapture {
+    pub fn status(&self, source: Option<&Source>) -> ModuleStatus {
+        if self.disabled {
+            return ModuleStatus::Disabled("module(enabled=false)".to_string());
+        }
+        if let Some(source) = source.filter(|s| !s.enabled) {
+            return ModuleStatus::Disabled(format!("source {} has enabled: false", source.name));
+        }
+        match self
+            .deprecated
+            .clone()
+            .or_else(|| source.and_then(|s| s.deprecated.clone()))
+        {
+            Some(note) => ModuleStatus::Deprecated(note),
+            None => ModuleStatus::Enabled,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SinkRoute {
+    pub sink: String,
+    pub table: String,
+}
+
+/// Who and what a module is for, from `-- key: value` comments at the top
+/// of its template:
+///
+/// ```sql
+/// -- description: Shopify orders, merged on id
+/// -- owner: @data-platform (#data-oncall)
+/// -- sla: 06:00 UTC daily
+/// {{ sink(name="warehouse") }}
+/// ```
+///
+/// Other leading comments are ignored; the header ends at the first line
+/// that is neither a comment nor blank.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModuleDoc {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sla: Option<String>,
+}
+
+impl ModuleDoc {
+    pub fn parse(template: &str) -> Self {
+        let mut doc = ModuleDoc::default();
+        for line in template.lines().map(str::trim) {
+            if line.is_empty() {
+                continue;
+            }
+            let Some(comment) = line.strip_prefix("--") else {
+                break;
+            };
+            let Some((key, value)) = comment.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            let slot = match key.trim().to_ascii_lowercase().as_str() {
+                "description" => &mut doc.description,
+                "owner" => &mut doc.owner,
+                "sla" => &mut doc.sla,
+                _ => continue,
+            };
+            slot.get_or_insert_with(|| value.to_string());
+        }
+        doc
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.description.is_none() && self.owner.is_none() && self.sla.is_none()
+    }
+}
+
+/// One SELECT of a module together with where its rows go.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoutedSql {
+    pub sink: String,
+    /// Destination table; `None` means the source's `table_destination_name`.
+    pub table: Option<String>,
+    pub sql: String,
+}
+
+/// Emitted by `sink(table=...)` so the rendered SQL can be split per route.
+const ROUTE_MARKER: &str = "/*apitap:route*/";
+
 #[derive(Debug, Clone)]
 pub struct RenderedSql {
     pub name: String,
     pub sql: String,
     pub capture: RenderCapture,
+    pub doc: ModuleDoc,
+}
+
+impl RenderedSql {
+    /// The module's statements with their sinks.
+    ///
+    /// A plain module is one statement for `capture.sink`. A module with
+    /// `{{ sink(name=..., table=...) }}` blocks yields one statement per block:
+    /// the SQL between that call and the next one, trailing `;` removed.
+    ///
+    /// Each statement must be a single query: modules transform the fetched
+    /// rows in memory, so `INSERT`, `CREATE`, `DROP` and the like are refused.
+    pub fn statements(&self) -> Result<Vec<RoutedSql>> {
+        if self.capture.routes.is_empty() {
+            check_read_only(&self.name, &self.sql)?;
+            return Ok(vec![RoutedSql {
+                sink: self.capture.sink.clone(),
+                table: None,
+                sql: self.sql.clone(),
+            }]);
+        }
+
+        let mut parts = self.sql.split(ROUTE_MARKER);
+        let preamble = parts.next().unwrap_or_default();
+        if !strip_statement(preamble).is_empty() {
+            return Err(ApitapError::ConfigError(format!(
+                "{}: SQL before the first sink(name=..., table=...) has no destination",
+                self.name
+            )));
+        }
+
+        self.capture
+            .routes
+            .iter()
+            .zip(parts)
+            .map(|(route, sql)| {
+                let sql = strip_statement(sql);
+                if sql.is_empty() {
+                    return Err(ApitapError::ConfigError(format!(
+                        "{}: no SELECT follows sink(name=\"{}\", table=\"{}\")",
+                        self.name, route.sink, route.table
+                    )));
+                }
+                check_read_only(&self.name, sql)?;
+                Ok(RoutedSql {
+                    sink: route.sink.clone(),
+                    table: Some(route.table.clone()),
+                    sql: sql.to_string(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Refuse anything but one query. SQL the parser cannot read is left for
+/// DataFusion to report when it runs.
+fn check_read_only(module: &str, sql: &str) -> Result<()> {
+    let Ok(statements) = DFParser::parse_sql(sql) else {
+        return Ok(());
+    };
+    if statements.len() > 1 {
+        return Err(ApitapError::ConfigError(format!(
+            "{module}: expected one SELECT, found {} statements",
+            statements.len()
+        )));
+    }
+    match statements.front() {
+        Some(DFStatement::Statement(stmt)) if matches!(**stmt, Statement::Query(_)) => Ok(()),
+        None => Ok(()),
+        Some(stmt) => {
+            let text = stmt.to_string();
+            let keyword = text.split_whitespace().next().unwrap_or_default();
+            Err(ApitapError::ConfigError(format!(
+                "{module}: only SELECT queries can run against the fetched rows, found {}; \
+                 run warehouse-side SQL in the warehouse",
+                keyword.to_uppercase()
+            )))
+        }
+    }
+}
+
+fn strip_statement(sql: &str) -> &str {
+    sql.trim().trim_end_matches(';').trim_end()
 }
 
 pub fn build_env_with_captures(
@@ -27,16 +231,24 @@ pub fn build_env_with_captures(
     let mut env = Environment::new();
     env.set_loader(path_loader(root));
 
-    // {{ sink(name="...") }}
+    // {{ sink(name="...") }} or, once per statement, {{ sink(name="...", table="...") }}
     {
         let cap = Arc::clone(shared_cap);
         env.add_function(
             "sink",
             move |kwargs: Kwargs| -> std::result::Result<Value, MjError> {
                 let name: String = kwargs.get("name")?;
+                let table: Option<String> = kwargs.get("table")?;
+                kwargs.assert_all_used()?;
                 let mut c = cap.lock().expect("RenderCapture mutex poisoned - this indicates a panic occurred while holding the lock");
-                c.sink = name;
-                Ok(Value::from(""))
+                c.sink = name.clone();
+                match table {
+                    Some(table) => {
+                        c.routes.push(SinkRoute { sink: name, table });
+                        Ok(Value::from(ROUTE_MARKER))
+                    }
+                    None => Ok(Value::from("")),
+                }
             },
         );
     }
@@ -54,6 +266,85 @@ pub fn build_env_with_captures(
         );
     }
 
+    // {{ freshness(max_age="6h", column="_loaded_at", severity="warn") }}
+    {
+        let cap = Arc::clone(shared_cap);
+        env.add_function(
+            "freshness",
+            move |kwargs: Kwargs| -> std::result::Result<Value, MjError> {
+                let invalid = |e: crate::errors::ApitapError| {
+                    MjError::new(minijinja::ErrorKind::InvalidOperation, e.to_string())
+                };
+                let max_age: String = kwargs.get("max_age")?;
+                let mut freshness = Freshness::new(parse_duration(&max_age).map_err(invalid)?);
+                if let Some(column) = kwargs.get::<Option<String>>("column")? {
+                    freshness.column = column;
+                }
+                if let Some(severity) = kwargs.get::<Option<String>>("severity")? {
+                    freshness.severity = severity.parse().map_err(invalid)?;
+                }
+                kwargs.assert_all_used()?;
+
+                let mut c = cap.lock().expect("RenderCapture mutex poisoned - this indicates a panic occurred while holding the lock");
+                c.freshness = Some(freshness);
+                Ok(Value::from(""))
+            },
+        );
+    }
+
+    // {{ sla(max_duration="15m", alert="slack") }}
+    {
+        let cap = Arc::clone(shared_cap);
+        env.add_function(
+            "sla",
+            move |kwargs: Kwargs| -> std::result::Result<Value, MjError> {
+                let max_duration: String = kwargs.get("max_duration")?;
+                let max_duration = parse_duration(&max_duration).map_err(|e| {
+                    MjError::new(minijinja::ErrorKind::InvalidOperation, e.to_string())
+                })?;
+                let alert: Option<String> = kwargs.get("alert")?;
+                kwargs.assert_all_used()?;
+
+                let mut c = cap.lock().expect("RenderCapture mutex poisoned - this indicates a panic occurred while holding the lock");
+                c.sla = Some(Sla {
+                    max_duration,
+                    alert,
+                });
+                Ok(Value::from(""))
+            },
+        );
+    }
+
+    // {{ module(enabled=false, deprecated="use orders_v2") }}
+    {
+        let cap = Arc::clone(shared_cap);
+        env.add_function(
+            "module",
+            move |kwargs: Kwargs| -> std::result::Result<Value, MjError> {
+                let enabled: Option<bool> = kwargs.get("enabled")?;
+                let deprecated: Option<String> = kwargs.get("deprecated")?;
+                kwargs.assert_all_used()?;
+                let mut c = cap.lock().expect("RenderCapture mutex poisoned - this indicates a panic occurred while holding the lock");
+                c.disabled = enabled == Some(false);
+                c.deprecated = deprecated;
+                Ok(Value::from(""))
+            },
+        );
+    }
+
+    // {{ transform("plugins/clean.wasm") }}
+    {
+        let cap = Arc::clone(shared_cap);
+        env.add_function(
+            "transform",
+            move |path: String| -> std::result::Result<Value, MjError> {
+                let mut c = cap.lock().expect("RenderCapture mutex poisoned - this indicates a panic occurred while holding the lock");
+                c.transforms.push(path);
+                Ok(Value::from(""))
+            },
+        );
+    }
+
     env
 }
 
@@ -68,9 +359,16 @@ pub fn render_one(
         );
         c.sink.clear();
         c.source.clear();
+        c.freshness = None;
+        c.sla = None;
+        c.transforms.clear();
+        c.routes.clear();
+        c.disabled = false;
+        c.deprecated = None;
     }
 
     let tmpl = env.get_template(name)?;
+    let doc = ModuleDoc::parse(tmpl.source());
     let sql = tmpl.render(())?;
 
     let capture = shared_cap
@@ -83,6 +381,7 @@ pub fn render_one(
         name: name.to_string(),
         sql,
         capture,
+        doc,
     })
 }
 
@@ -39,9 +39,21 @@ pub enum ApitapError {
     #[error("Parquet error: {0}")]
     Parquet(#[from] datafusion::parquet::errors::ParquetError),
 
+    #[error("Object store error: {0}")]
+    ObjectStore(#[from] object_store::Error),
+
+    #[error("Delta Lake error: {0}")]
+    Delta(#[from] deltalake::DeltaTableError),
+
+    #[error("MongoDB error: {0}")]
+    Mongodb(#[from] mongodb::error::Error),
+
     #[error("Serde Arrow error: {0}")]
     SerdeArrow(#[from] serde_arrow::Error),
 
+    #[error("XML error: {0}")]
+    Xml(#[from] quick_xml::Error),
+
     #[error("YAML error: {0}")]
     SerdeYaml(#[from] serde_yaml::Error),
 
@@ -0,0 +1,373 @@
+//! Credentials for source requests.
+//!
+//! A source's `auth:` block is turned into an [`Authenticator`] that adds
+//! credentials to every page request. When a response comes back `401`, the
+//! authenticator is told so; if it has fresher credentials to offer (e.g. a
+//! new OAuth2 access token) the request is sent once more with them.
+//!
+//! ```yaml
+//! auth:
+//!   kind: oauth2
+//!   token_url: https://auth.example.com/oauth/token
+//!   client_id_env: API_CLIENT_ID
+//!   client_secret_env: API_CLIENT_SECRET
+//!   scopes: [orders:read]
+//! ```
+//!
+//! ```yaml
+//! auth:
+//!   kind: api_key
+//!   in: query          # header (default) | query | cookie
+//!   name: api_key
+//!   value_env: API_KEY
+//! ```
+//!
+//! ```yaml
+//! auth:
+//!   kind: basic
+//!   username_env: API_USER
+//!   password_env: API_PASSWORD
+//! ```
+//!
+//! ```yaml
+//! auth:
+//!   kind: authorization
+//!   template: "SSWS ${OKTA_TOKEN}"   # any scheme; ${VAR} read from the environment
+//! ```
+//!
+//! `kind: hmac` signs each request instead; see [`crate::http::signing`].
+//! `kind: jwt` exchanges a signed assertion for an OAuth2 token; see
+//! [`crate::http::jwt`].
+//!
+//! Static credentials (`basic`, `authorization`) are also set as default
+//! headers on the source's client, so every request it sends carries them.
+
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use reqwest::header::{HeaderName, HeaderValue, AUTHORIZATION, COOKIE};
+use reqwest::{Client, Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::errors::{ApitapError, Result};
+use crate::http::jwt::{JwtAssertion, JwtConfig};
+use crate::http::oauth2::{OAuth2Config, RefreshTokenSlot, TokenManager};
+use crate::http::signing::{HmacConfig, HmacSigner};
+use crate::http::usage::UsageMeter;
+
+/// Adds credentials to outgoing requests.
+#[async_trait]
+pub trait Authenticator: Debug + Send + Sync {
+    /// Add credentials to `request` before it is sent.
+    async fn authorize(&self, request: &mut Request) -> Result<()>;
+
+    /// `true` when the credentials go stale within a request, so every
+    /// attempt, retries included, must be authorized again.
+    fn signs_each_attempt(&self) -> bool {
+        false
+    }
+
+    /// The server answered `request` with `401`. Drop whatever credentials
+    /// it carried; `true` when sending it again with new ones may succeed.
+    async fn rejected(&self, _request: &Request) -> bool {
+        false
+    }
+}
+
+/// `auth:` on a source.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SourceAuth {
+    /// OAuth2 client-credentials or refresh-token grant.
+    Oauth2(OAuth2Config),
+    /// A static key sent as a header, query parameter or cookie.
+    ApiKey(ApiKeyConfig),
+    /// HTTP basic auth.
+    Basic(BasicAuthConfig),
+    /// An `Authorization` header built from a template.
+    Authorization(AuthorizationConfig),
+    /// An HMAC signature of each request.
+    Hmac(HmacConfig),
+    /// A signed JWT exchanged for an OAuth2 access token.
+    Jwt(JwtConfig),
+}
+
+impl SourceAuth {
+    /// The authenticator for requests sent with `client`; credentials named
+    /// by environment variable are read here, so missing ones fail early.
+    /// Credentials issued during the run are kept in `slot`, if any, and
+    /// token requests are counted on `usage`.
+    pub fn authenticator(
+        &self,
+        client: &Client,
+        slot: Option<RefreshTokenSlot>,
+        usage: Option<Arc<UsageMeter>>,
+    ) -> Result<Arc<dyn Authenticator>> {
+        match self {
+            SourceAuth::Oauth2(config) => Ok(Arc::new(
+                TokenManager::new(client.clone(), config.clone())?
+                    .with_refresh_token_slot(slot)
+                    .with_usage(usage),
+            )),
+            SourceAuth::ApiKey(config) => Ok(Arc::new(ApiKey::new(config)?)),
+            SourceAuth::Hmac(config) => Ok(Arc::new(HmacSigner::new(config)?)),
+            SourceAuth::Jwt(config) => Ok(Arc::new(
+                TokenManager::jwt_bearer(
+                    client.clone(),
+                    JwtAssertion::new(config)?,
+                    config.refresh_every()?,
+                )
+                .with_usage(usage),
+            )),
+            SourceAuth::Basic(_) | SourceAuth::Authorization(_) => {
+                let value = self.authorization()?.unwrap_or_default();
+                Ok(Arc::new(StaticAuthorization::new(&value)?))
+            }
+        }
+    }
+
+    /// The `Authorization` value of static credentials, `None` for kinds
+    /// that obtain or place credentials per request.
+    pub fn authorization(&self) -> Result<Option<String>> {
+        match self {
+            SourceAuth::Basic(config) => {
+                let username = env_credential(&config.username_env, "basic auth username")?;
+                let password = env_credential(&config.password_env, "basic auth password")?;
+                let encoded = STANDARD.encode(format!("{username}:{password}"));
+                Ok(Some(format!("Basic {encoded}")))
+            }
+            SourceAuth::Authorization(config) => {
+                expand_env(&config.template, "authorization template").map(Some)
+            }
+            SourceAuth::Oauth2(_)
+            | SourceAuth::ApiKey(_)
+            | SourceAuth::Hmac(_)
+            | SourceAuth::Jwt(_) => Ok(None),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BasicAuthConfig {
+    /// Environment variable holding the username.
+    pub username_env: String,
+    /// Environment variable holding the password.
+    pub password_env: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthorizationConfig {
+    /// The header value, e.g. `"Token token=${API_TOKEN}"`; each `${VAR}`
+    /// is replaced by that environment variable.
+    pub template: String,
+}
+
+/// `template` with each `${VAR}` replaced by the environment variable;
+/// `what` names the template in errors.
+pub fn expand_env(template: &str, what: &str) -> Result<String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let Some(len) = rest[start + 2..].find('}') else {
+            return Err(ApitapError::ConfigError(format!(
+                "unterminated ${{ in {what} '{template}'"
+            )));
+        };
+        let key = &rest[start + 2..start + 2 + len];
+        out.push_str(&env_credential(key, what)?);
+        rest = &rest[start + 3 + len..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Sets the same `Authorization` header on every request.
+pub struct StaticAuthorization {
+    value: HeaderValue,
+}
+
+impl Debug for StaticAuthorization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StaticAuthorization")
+            .finish_non_exhaustive()
+    }
+}
+
+impl StaticAuthorization {
+    pub fn new(value: &str) -> Result<Self> {
+        let mut value = HeaderValue::from_str(value).map_err(|_| {
+            ApitapError::ConfigError("authorization is not a valid header value".into())
+        })?;
+        value.set_sensitive(true);
+        Ok(Self { value })
+    }
+}
+
+#[async_trait]
+impl Authenticator for StaticAuthorization {
+    async fn authorize(&self, request: &mut Request) -> Result<()> {
+        request
+            .headers_mut()
+            .insert(AUTHORIZATION, self.value.clone());
+        Ok(())
+    }
+}
+
+/// Where an API key goes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKeyLocation {
+    #[default]
+    Header,
+    Query,
+    Cookie,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiKeyConfig {
+    #[serde(rename = "in", default)]
+    pub location: ApiKeyLocation,
+    /// Header, query parameter or cookie name, e.g. `X-Api-Key`.
+    pub name: String,
+    /// Environment variable holding the key.
+    pub value_env: String,
+    /// Put before the key, e.g. `"Token "` for `Authorization: Token <key>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+}
+
+/// Adds a static API key to every request.
+pub struct ApiKey {
+    location: ApiKeyLocation,
+    name: String,
+    value: String,
+}
+
+impl Debug for ApiKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiKey")
+            .field("location", &self.location)
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ApiKey {
+    /// Reads the key from its environment variable.
+    pub fn new(config: &ApiKeyConfig) -> Result<Self> {
+        let key = env_credential(&config.value_env, "api key")?;
+        let value = format!("{}{key}", config.prefix.as_deref().unwrap_or_default());
+        if config.location == ApiKeyLocation::Header {
+            HeaderName::from_bytes(config.name.as_bytes()).map_err(|_| {
+                ApitapError::ConfigError(format!("invalid api key header name '{}'", config.name))
+            })?;
+            HeaderValue::from_str(&value).map_err(|_| {
+                ApitapError::ConfigError(format!(
+                    "api key in '{}' is not a valid header value",
+                    config.value_env
+                ))
+            })?;
+        }
+        Ok(Self {
+            location: config.location,
+            name: config.name.clone(),
+            value,
+        })
+    }
+}
+
+#[async_trait]
+impl Authenticator for ApiKey {
+    async fn authorize(&self, request: &mut Request) -> Result<()> {
+        let invalid =
+            || ApitapError::PipelineError(format!("api key {} is not a valid header", self.name));
+        match self.location {
+            ApiKeyLocation::Header => {
+                let name = HeaderName::from_bytes(self.name.as_bytes()).map_err(|_| invalid())?;
+                let value = HeaderValue::from_str(&self.value).map_err(|_| invalid())?;
+                request.headers_mut().insert(name, value);
+            }
+            ApiKeyLocation::Query => {
+                let url = request.url_mut();
+                let pairs: Vec<(String, String)> = url
+                    .query_pairs()
+                    .filter(|(k, _)| *k != self.name)
+                    .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                    .collect();
+                url.query_pairs_mut()
+                    .clear()
+                    .extend_pairs(pairs)
+                    .append_pair(&self.name, &self.value);
+            }
+            ApiKeyLocation::Cookie => {
+                // Keep cookies already on the request, replacing only ours.
+                let own = format!("{}=", self.name);
+                let mut cookies: Vec<String> = request
+                    .headers()
+                    .get_all(COOKIE)
+                    .iter()
+                    .filter_map(|v| v.to_str().ok())
+                    .flat_map(|v| v.split(';'))
+                    .map(str::trim)
+                    .filter(|c| !c.is_empty() && !c.starts_with(&own))
+                    .map(str::to_string)
+                    .collect();
+                cookies.push(format!("{own}{}", self.value));
+                let value = HeaderValue::from_str(&cookies.join("; ")).map_err(|_| invalid())?;
+                request.headers_mut().insert(COOKIE, value);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Read the credential in environment variable `key`, for `what`.
+pub fn env_credential(key: &str, what: &str) -> Result<String> {
+    match std::env::var(key) {
+        Ok(value) if !value.trim().is_empty() => Ok(value),
+        Ok(_) => Err(ApitapError::ConfigError(format!(
+            "environment variable '{key}' for {what} is empty"
+        ))),
+        Err(_) => Err(ApitapError::ConfigError(format!(
+            "environment variable '{key}' for {what} not set"
+        ))),
+    }
+}
+
+/// Send `request` through `send` with credentials from `auth`; after a `401`
+/// the authenticator may refresh them and the request is sent once more.
+/// Requests whose body cannot be replayed are not retried.
+pub async fn send_authorized<F, Fut>(
+    auth: Option<&Arc<dyn Authenticator>>,
+    mut request: Request,
+    send: F,
+) -> Result<Response>
+where
+    F: Fn(Request) -> Fut,
+    Fut: Future<Output = Result<Response>>,
+{
+    let Some(auth) = auth else {
+        return send(request).await;
+    };
+    auth.authorize(&mut request).await?;
+    let replay = request.try_clone();
+    let resp = send(request).await?;
+    if resp.status() != StatusCode::UNAUTHORIZED {
+        return Ok(resp);
+    }
+    let Some(mut replay) = replay else {
+        return Ok(resp);
+    };
+    if !auth.rejected(&replay).await {
+        return Ok(resp);
+    }
+    debug!(url = %replay.url(), "credentials rejected; retrying with fresh ones");
+    auth.authorize(&mut replay).await?;
+    send(replay).await
+}
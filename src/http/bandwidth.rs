@@ -0,0 +1,148 @@
+//! Global outbound bandwidth cap (`--max-bandwidth`).
+//!
+//! A single [`BandwidthLimiter`] is shared by every fetch in the run; each
+//! chunk of response body draws from a token bucket refilled at the configured
+//! rate, so concurrent pages together stay under the cap.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::{Stream, StreamExt};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tracing::trace;
+
+use crate::errors::{ApitapError, Result};
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+/// Token bucket metering response body bytes.
+#[derive(Debug)]
+pub struct BandwidthLimiter {
+    bytes_per_sec: u64,
+    bucket: Mutex<Bucket>,
+}
+
+impl BandwidthLimiter {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        let bytes_per_sec = bytes_per_sec.max(1);
+        Self {
+            bytes_per_sec,
+            // Allow up to one second of burst.
+            bucket: Mutex::new(Bucket {
+                tokens: bytes_per_sec as f64,
+                last: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bytes_per_sec
+    }
+
+    /// Account for `n` bytes, sleeping if the budget is overdrawn.
+    pub async fn consume(&self, n: usize) {
+        if n == 0 {
+            return;
+        }
+        let rate = self.bytes_per_sec as f64;
+        let wait = {
+            let mut bucket = self.bucket.lock().await;
+            let now = Instant::now();
+            let refill = now.duration_since(bucket.last).as_secs_f64() * rate;
+            bucket.tokens = (bucket.tokens + refill).min(rate) - n as f64;
+            bucket.last = now;
+            if bucket.tokens < 0.0 {
+                Duration::from_secs_f64(-bucket.tokens / rate)
+            } else {
+                Duration::ZERO
+            }
+        };
+        if wait > Duration::ZERO {
+            trace!(
+                bytes = n,
+                wait_ms = wait.as_millis(),
+                "bandwidth cap reached"
+            );
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Meter a body byte stream chunk by chunk.
+    pub fn meter<S, B, E>(
+        self: Arc<Self>,
+        stream: S,
+    ) -> impl Stream<Item = std::result::Result<B, E>>
+    where
+        S: Stream<Item = std::result::Result<B, E>>,
+        B: AsRef<[u8]>,
+    {
+        stream.then(move |chunk| {
+            let limiter = Arc::clone(&self);
+            async move {
+                if let Ok(bytes) = &chunk {
+                    limiter.consume(bytes.as_ref().len()).await;
+                }
+                chunk
+            }
+        })
+    }
+}
+
+/// Parse a rate such as `10MB/s`, `512KiB/s` or `1000000` into bytes per second.
+///
+/// Units are those of [`parse_size`]. The `/s` suffix is optional.
+pub fn parse_bandwidth(text: &str) -> Result<u64> {
+    let s = text.trim();
+    let s = s
+        .strip_suffix("/s")
+        .or_else(|| s.strip_suffix("ps"))
+        .unwrap_or(s);
+    let bytes = size_bytes(s)
+        .ok_or_else(|| ApitapError::ConfigError(format!("invalid bandwidth '{text}'")))?;
+    if bytes < 1.0 {
+        return Err(ApitapError::ConfigError(format!(
+            "bandwidth must be at least 1 byte per second, got '{text}'"
+        )));
+    }
+    Ok(bytes as u64)
+}
+
+/// Parse a size such as `256MB`, `16MiB` or `1000000` into bytes.
+///
+/// Decimal units (`KB`, `MB`, `GB`) are powers of 1000, binary units
+/// (`KiB`, `MiB`, `GiB`) powers of 1024.
+pub fn parse_size(text: &str) -> Result<u64> {
+    let bytes = size_bytes(text)
+        .ok_or_else(|| ApitapError::ConfigError(format!("invalid size '{text}'")))?;
+    if bytes < 1.0 {
+        return Err(ApitapError::ConfigError(format!(
+            "size must be at least 1 byte, got '{text}'"
+        )));
+    }
+    Ok(bytes as u64)
+}
+
+fn size_bytes(text: &str) -> Option<f64> {
+    let s = text.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let value: f64 = num.parse().ok()?;
+    let multiplier: f64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1.0,
+        "k" | "kb" => 1e3,
+        "m" | "mb" => 1e6,
+        "g" | "gb" => 1e9,
+        "kib" => 1024.0,
+        "mib" => 1024.0 * 1024.0,
+        "gib" => 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some((value * multiplier).round())
+}
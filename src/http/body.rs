@@ -0,0 +1,95 @@
+//! Method and request body for sources that are queried with POST/PUT.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::errors::{ApitapError, Result};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum RequestMethod {
+    #[default]
+    Get,
+    Post,
+    Put,
+}
+
+impl RequestMethod {
+    pub fn as_method(self) -> reqwest::Method {
+        match self {
+            RequestMethod::Get => reqwest::Method::GET,
+            RequestMethod::Post => reqwest::Method::POST,
+            RequestMethod::Put => reqwest::Method::PUT,
+        }
+    }
+}
+
+/// How a source's `body` is encoded on the wire.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BodyFormat {
+    #[default]
+    #[serde(rename = "application/json", alias = "json")]
+    Json,
+    #[serde(rename = "application/x-www-form-urlencoded", alias = "form")]
+    Form,
+}
+
+impl BodyFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            BodyFormat::Json => "application/json",
+            BodyFormat::Form => "application/x-www-form-urlencoded",
+        }
+    }
+}
+
+/// A body encoded once from config and sent with every page request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestBody {
+    pub format: BodyFormat,
+    pub bytes: Vec<u8>,
+}
+
+impl RequestBody {
+    /// Encode `body`. Form bodies must be a flat object; arrays repeat the key
+    /// and nulls are sent as empty values.
+    pub fn encode(body: &Value, format: BodyFormat) -> Result<Self> {
+        let bytes = match format {
+            BodyFormat::Json => serde_json::to_vec(body)?,
+            BodyFormat::Form => encode_form(body)?.into_bytes(),
+        };
+        Ok(Self { format, bytes })
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        self.format.content_type()
+    }
+}
+
+fn encode_form(body: &Value) -> Result<String> {
+    let fields = body.as_object().ok_or_else(|| {
+        ApitapError::ConfigError("form body must be a mapping of field names to values".into())
+    })?;
+
+    let mut form = url::form_urlencoded::Serializer::new(String::new());
+    for (key, value) in fields {
+        let values = match value {
+            Value::Array(items) => items.iter().collect(),
+            other => vec![other],
+        };
+        for value in values {
+            let text = match value {
+                Value::String(s) => s.clone(),
+                Value::Null => String::new(),
+                Value::Bool(_) | Value::Number(_) => value.to_string(),
+                Value::Array(_) | Value::Object(_) => {
+                    return Err(ApitapError::ConfigError(format!(
+                        "form body field '{key}' must be a scalar or a list of scalars"
+                    )))
+                }
+            };
+            form.append_pair(key, &text);
+        }
+    }
+    Ok(form.finish())
+}
@@ -0,0 +1,102 @@
+//! Connection settings of a source's HTTP client (`http:`).
+//!
+//! ```yaml
+//! http:
+//!   request_timeout: 2m           # whole request, body included (default 30s)
+//!   connect_timeout: 5s           # default 10s
+//!   pool_max_idle_per_host: 4     # idle connections kept per host (default 10)
+//!   pool_idle_timeout: 30s        # default 90s
+//!   tcp_keepalive: 0s             # default 60s; 0s turns keepalive probes off
+//!   http2_prior_knowledge: true   # HTTP/2 without negotiation (h2c or known h2 servers)
+//! ```
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{ApitapError, Result};
+use crate::pipeline::freshness::parse_duration;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HttpSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_timeout: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_timeout: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool_max_idle_per_host: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool_idle_timeout: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tcp_keepalive: Option<String>,
+    #[serde(default)]
+    pub http2_prior_knowledge: bool,
+}
+
+/// The settings a client is built with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientSettings {
+    pub request_timeout: Duration,
+    pub connect_timeout: Duration,
+    pub pool_max_idle_per_host: usize,
+    pub pool_idle_timeout: Duration,
+    /// `None` leaves TCP keepalive off.
+    pub tcp_keepalive: Option<Duration>,
+    pub http2_prior_knowledge: bool,
+}
+
+impl Default for ClientSettings {
+    fn default() -> Self {
+        Self {
+            request_timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(10),
+            pool_max_idle_per_host: 10,
+            pool_idle_timeout: Duration::from_secs(90),
+            tcp_keepalive: Some(Duration::from_secs(60)),
+            http2_prior_knowledge: false,
+        }
+    }
+}
+
+impl HttpSettings {
+    /// These settings over the defaults.
+    pub fn resolve(&self) -> Result<ClientSettings> {
+        let defaults = ClientSettings::default();
+        let timeout = |value: &Option<String>, name: &str, default: Duration| {
+            let Some(text) = value else {
+                return Ok(default);
+            };
+            let timeout = parse_duration(text)?;
+            if timeout.is_zero() {
+                return Err(ApitapError::ConfigError(format!(
+                    "http.{name} must be longer than zero"
+                )));
+            }
+            Ok(timeout)
+        };
+        Ok(ClientSettings {
+            request_timeout: timeout(
+                &self.request_timeout,
+                "request_timeout",
+                defaults.request_timeout,
+            )?,
+            connect_timeout: timeout(
+                &self.connect_timeout,
+                "connect_timeout",
+                defaults.connect_timeout,
+            )?,
+            pool_max_idle_per_host: self
+                .pool_max_idle_per_host
+                .unwrap_or(defaults.pool_max_idle_per_host),
+            pool_idle_timeout: match &self.pool_idle_timeout {
+                Some(text) => parse_duration(text)?,
+                None => defaults.pool_idle_timeout,
+            },
+            tcp_keepalive: match &self.tcp_keepalive {
+                Some(text) => Some(parse_duration(text)?).filter(|d| !d.is_zero()),
+                None => defaults.tcp_keepalive,
+            },
+            http2_prior_knowledge: self.http2_prior_knowledge,
+        })
+    }
+}
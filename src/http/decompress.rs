@@ -0,0 +1,121 @@
+//! Compressed response bodies.
+//!
+//! Clients built by [`crate::http::Http`] decode `Content-Encoding` (gzip,
+//! deflate, brotli, zstd) transparently. Bodies that are still compressed
+//! when they reach the parsers — a client without decoding, a download
+//! served as `application/gzip`, or an export that is simply a `.gz` file —
+//! are decoded here, recognised by header, content type or magic bytes.
+
+use async_compression::tokio::bufread::{BrotliDecoder, GzipDecoder, ZlibDecoder, ZstdDecoder};
+use bytes::Bytes;
+use futures::stream::{self, BoxStream};
+use futures::{Stream, StreamExt, TryStreamExt};
+use reqwest::header::{HeaderMap, CONTENT_ENCODING, CONTENT_TYPE};
+use tokio_util::io::{ReaderStream, StreamReader};
+
+/// Bytes needed to recognise every sniffed format.
+const MAGIC_LEN: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Gzip,
+    Deflate,
+    Brotli,
+    Zstd,
+}
+
+impl Encoding {
+    /// A `Content-Encoding` token such as `gzip` or `br`.
+    pub fn from_token(token: &str) -> Option<Self> {
+        match token.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Some(Self::Gzip),
+            "deflate" => Some(Self::Deflate),
+            "br" => Some(Self::Brotli),
+            "zstd" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Encoding announced by a response that is still compressed: a
+    /// `Content-Encoding` the client left in place, or a compressed
+    /// content type.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let text = |name| headers.get(name).and_then(|v| v.to_str().ok());
+        if let Some(encoding) = text(CONTENT_ENCODING).and_then(Self::from_token) {
+            return Some(encoding);
+        }
+        let content_type = text(CONTENT_TYPE)?.to_ascii_lowercase();
+        if content_type.contains("gzip") {
+            Some(Self::Gzip)
+        } else if content_type.contains("zstd") {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
+
+    /// Recognise gzip and zstd by their magic bytes.
+    pub fn sniff(head: &[u8]) -> Option<Self> {
+        if head.starts_with(&[0x1f, 0x8b]) {
+            Some(Self::Gzip)
+        } else if head.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
+}
+
+/// Decode `body` with `hint`, or with the encoding its first bytes reveal;
+/// anything else passes through unchanged.
+pub fn decompressed<S, B, E>(
+    body: S,
+    hint: Option<Encoding>,
+) -> BoxStream<'static, std::io::Result<Bytes>>
+where
+    S: Stream<Item = std::result::Result<B, E>> + Send + 'static,
+    B: Into<Bytes> + 'static,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let body = body
+        .map_ok(Into::into)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e));
+    stream::once(async move {
+        let mut body = body.boxed();
+        // Buffer enough of the body to sniff, however it is chunked.
+        let mut head = Vec::new();
+        let mut chunks = Vec::new();
+        if hint.is_none() {
+            while head.len() < MAGIC_LEN {
+                match body.next().await {
+                    Some(Ok(chunk)) => {
+                        head.extend_from_slice(&chunk);
+                        chunks.push(Ok(chunk));
+                    }
+                    Some(Err(e)) => {
+                        chunks.push(Err(e));
+                        break;
+                    }
+                    None => break,
+                }
+            }
+        }
+        let encoding = hint.or_else(|| Encoding::sniff(&head));
+        let body = stream::iter(chunks).chain(body);
+        let reader = StreamReader::new(body);
+        match encoding {
+            None => ReaderStream::new(reader).boxed(),
+            Some(Encoding::Gzip) => {
+                let mut decoder = GzipDecoder::new(reader);
+                // Concatenated gzip members, as written by appending exports.
+                decoder.multiple_members(true);
+                ReaderStream::new(decoder).boxed()
+            }
+            Some(Encoding::Deflate) => ReaderStream::new(ZlibDecoder::new(reader)).boxed(),
+            Some(Encoding::Brotli) => ReaderStream::new(BrotliDecoder::new(reader)).boxed(),
+            Some(Encoding::Zstd) => ReaderStream::new(ZstdDecoder::new(reader)).boxed(),
+        }
+    })
+    .flatten()
+    .boxed()
+}
@@ -0,0 +1,184 @@
+//! API version pinning and deprecation notices.
+//!
+//! A source can pin the API version it was written against with
+//! `api_version`, sent as a header or query parameter on every request.
+//! Responses are checked for the `Deprecation` and `Sunset` headers
+//! (RFC 9745 / RFC 8594); the first notice per source is logged as a warning
+//! and summarized again at the end of the run, well before the provider
+//! switches the endpoint off.
+
+use chrono::{DateTime, Utc};
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tracing::warn;
+
+use crate::http::Http;
+
+/// Header used when `api_version` is given as a bare version string.
+pub const DEFAULT_API_VERSION_HEADER: &str = "Api-Version";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "ApiVersionWire")]
+pub struct ApiVersion {
+    pub value: String,
+    /// Header carrying the version, e.g. `Stripe-Version`.
+    pub header: Option<String>,
+    /// Query parameter carrying the version, e.g. `api-version`.
+    pub param: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ApiVersionWire {
+    Value(String),
+    Full {
+        value: String,
+        #[serde(default)]
+        header: Option<String>,
+        #[serde(default)]
+        param: Option<String>,
+    },
+}
+
+impl From<ApiVersionWire> for ApiVersion {
+    fn from(wire: ApiVersionWire) -> Self {
+        match wire {
+            ApiVersionWire::Value(value) => Self {
+                value,
+                header: Some(DEFAULT_API_VERSION_HEADER.to_string()),
+                param: None,
+            },
+            ApiVersionWire::Full {
+                value,
+                header,
+                param,
+            } => Self {
+                value,
+                // Neither given: fall back to the default header.
+                header: header.or_else(|| {
+                    param
+                        .is_none()
+                        .then(|| DEFAULT_API_VERSION_HEADER.to_string())
+                }),
+                param,
+            },
+        }
+    }
+}
+
+impl ApiVersion {
+    /// Add the version header and/or query parameter to every request.
+    pub fn apply(&self, mut http: Http) -> Http {
+        if let Some(header) = &self.header {
+            http = http.header(header, &self.value);
+        }
+        if let Some(param) = &self.param {
+            http = http.param(param, &self.value);
+        }
+        http
+    }
+}
+
+/// Deprecation signals carried by a response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeprecationNotice {
+    /// Raw `Deprecation` value: `true`, `@<unix seconds>` or an HTTP date.
+    pub deprecation: Option<String>,
+    /// Raw `Sunset` value, an HTTP date.
+    pub sunset: Option<String>,
+    /// Target of a `Link` with `rel="deprecation"` or `rel="sunset"`.
+    pub link: Option<String>,
+}
+
+impl DeprecationNotice {
+    /// `None` unless the response has a `Deprecation` or `Sunset` header.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let text = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|s| s.trim().to_string())
+        };
+        let deprecation = text("deprecation");
+        let sunset = text("sunset");
+        if deprecation.is_none() && sunset.is_none() {
+            return None;
+        }
+        let link = headers
+            .get_all("link")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .find(|part| {
+                let part = part.to_ascii_lowercase();
+                part.contains("rel=\"deprecation\"") || part.contains("rel=\"sunset\"")
+            })
+            .and_then(|part| {
+                let (_, rest) = part.split_once('<')?;
+                rest.split_once('>').map(|(url, _)| url.to_string())
+            });
+        Some(Self {
+            deprecation,
+            sunset,
+            link,
+        })
+    }
+
+    /// When the endpoint goes away, if the `Sunset` date parses.
+    pub fn sunset_at(&self) -> Option<DateTime<Utc>> {
+        let sunset = self.sunset.as_deref()?;
+        DateTime::parse_from_rfc2822(sunset)
+            .ok()
+            .map(|ts| ts.with_timezone(&Utc))
+    }
+
+    /// Log the notice for `source` as a warning.
+    pub fn warn(&self, source: &str) {
+        let days_left = self.sunset_at().map(|at| (at - Utc::now()).num_days());
+        warn!(
+            source,
+            deprecation = self.deprecation.as_deref().unwrap_or("-"),
+            sunset = self.sunset.as_deref().unwrap_or("-"),
+            days_left,
+            link = self.link.as_deref().unwrap_or("-"),
+            "⚠️ API endpoint is deprecated"
+        );
+    }
+}
+
+/// Per-source record of the deprecation notices seen in responses.
+#[derive(Debug)]
+pub struct DeprecationWatch {
+    source: String,
+    notice: Mutex<Option<DeprecationNotice>>,
+}
+
+impl DeprecationWatch {
+    pub fn new(source: impl Into<String>) -> Self {
+        Self {
+            source: source.into(),
+            notice: Mutex::new(None),
+        }
+    }
+
+    /// Check a response; a notice is logged the first time it (or a changed
+    /// one) shows up, not once per page.
+    pub fn observe(&self, headers: &HeaderMap) {
+        let Some(notice) = DeprecationNotice::from_headers(headers) else {
+            return;
+        };
+        let Ok(mut seen) = self.notice.lock() else {
+            return;
+        };
+        if seen.as_ref() != Some(&notice) {
+            notice.warn(&self.source);
+            *seen = Some(notice);
+        }
+    }
+
+    /// The last notice seen, if any.
+    pub fn notice(&self) -> Option<DeprecationNotice> {
+        self.notice.lock().ok()?.clone()
+    }
+}
@@ -0,0 +1,150 @@
+//! Raw responses of failed pages, kept for debugging after the run.
+//!
+//! With `sample_failures: N` on a source, the first `N` pages of a run that
+//! come back with an error status (after retries) are written to the run's
+//! report directory, one JSON file each:
+//!
+//! ```text
+//! <report_dir>/<run_id>/failures/<module>/1.json
+//! ```
+//!
+//! Each file holds the URL, status, response headers and body. Credentials
+//! are redacted: sensitive headers (`Authorization`, cookies, anything
+//! naming a token, key or secret) and query parameters named like them.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use chrono::{DateTime, Utc};
+use reqwest::header::HeaderMap;
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// Where run reports go unless `--report-dir` says otherwise.
+pub const DEFAULT_REPORT_DIR: &str = ".apitap/reports";
+
+/// Bodies are cut to this many bytes.
+pub const MAX_SAMPLE_BODY: usize = 1024 * 1024;
+
+const REDACTED: &str = "[redacted]";
+
+/// Header and query parameter names whose values are never written.
+fn is_sensitive(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    matches!(
+        name.as_str(),
+        "authorization" | "proxy-authorization" | "cookie" | "set-cookie"
+    ) || ["token", "key", "secret", "password", "signature", "session"]
+        .iter()
+        .any(|word| name.contains(word))
+}
+
+/// `url` with the values of sensitive query parameters and any userinfo
+/// replaced.
+pub fn redact_url(url: &Url) -> String {
+    let mut url = url.clone();
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+    if url.query().is_some() {
+        let pairs: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(k, v)| {
+                let v = if is_sensitive(&k) {
+                    REDACTED.to_string()
+                } else {
+                    v.into_owned()
+                };
+                (k.into_owned(), v)
+            })
+            .collect();
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    url.to_string()
+}
+
+/// `headers` as text, with sensitive values replaced.
+pub fn redact_headers(headers: &HeaderMap) -> BTreeMap<String, String> {
+    let mut out = BTreeMap::new();
+    for (name, value) in headers {
+        let value = if is_sensitive(name.as_str()) {
+            REDACTED.to_string()
+        } else {
+            String::from_utf8_lossy(value.as_bytes()).into_owned()
+        };
+        out.entry(name.as_str().to_string())
+            .and_modify(|v: &mut String| {
+                v.push_str(", ");
+                v.push_str(&value);
+            })
+            .or_insert(value);
+    }
+    out
+}
+
+/// One failed response as written to disk.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FailureSample {
+    pub url: String,
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    pub body: String,
+    /// The body was longer than [`MAX_SAMPLE_BODY`] and was cut.
+    #[serde(default)]
+    pub truncated: bool,
+    pub captured_at: DateTime<Utc>,
+}
+
+impl FailureSample {
+    /// A redacted sample of a response; `body` is already decoded.
+    pub fn new(url: &Url, status: StatusCode, headers: &HeaderMap, body: &[u8]) -> Self {
+        Self {
+            url: redact_url(url),
+            status: status.as_u16(),
+            headers: redact_headers(headers),
+            body: String::from_utf8_lossy(&body[..body.len().min(MAX_SAMPLE_BODY)]).into_owned(),
+            truncated: body.len() > MAX_SAMPLE_BODY,
+            captured_at: Utc::now(),
+        }
+    }
+}
+
+/// Keeps up to `limit` failed responses of one module in `dir`.
+#[derive(Debug)]
+pub struct FailureSampler {
+    dir: PathBuf,
+    limit: usize,
+    taken: AtomicUsize,
+}
+
+impl FailureSampler {
+    pub fn new(dir: impl Into<PathBuf>, limit: usize) -> Self {
+        Self {
+            dir: dir.into(),
+            limit,
+            taken: AtomicUsize::new(0),
+        }
+    }
+
+    /// The number of the next sample, or `None` once `limit` were taken.
+    pub fn claim(&self) -> Option<usize> {
+        let n = self.taken.fetch_add(1, Ordering::Relaxed) + 1;
+        (n <= self.limit).then_some(n)
+    }
+
+    /// Write sample `n`. Sampling never fails the page; problems are logged.
+    pub fn write(&self, n: usize, sample: &FailureSample) {
+        let path = self.dir.join(format!("{n}.json"));
+        let written = std::fs::create_dir_all(&self.dir)
+            .map_err(|e| e.to_string())
+            .and_then(|_| serde_json::to_vec_pretty(sample).map_err(|e| e.to_string()))
+            .and_then(|json| std::fs::write(&path, json).map_err(|e| e.to_string()));
+        match written {
+            Ok(()) => {
+                info!(path = %path.display(), status = sample.status, "sampled failed response")
+            }
+            Err(e) => warn!(path = %path.display(), error = %e, "could not write failure sample"),
+        }
+    }
+}
@@ -1,27 +1,55 @@
 use crate::errors::{ApitapError, Result};
-use crate::utils::datafusion_ext::{
-    get_shared_context, DataFrameExt, JsonStreamType, JsonValueExt, QueryResultStream,
-};
+use crate::http::auth::{send_authorized, Authenticator};
+use crate::http::bandwidth::BandwidthLimiter;
+use crate::http::body::{RequestBody, RequestMethod};
+use crate::http::csv_stream::{csv_stream, CsvOptions, ResponseFormat};
+use crate::http::decompress::{decompressed, Encoding};
+use crate::http::deprecation::DeprecationWatch;
+use crate::http::failure_samples::{FailureSample, FailureSampler};
+use crate::http::json_stream::json_stream;
+use crate::http::limits::{MemoryBudget, Reservation, ResponseLimits};
+use crate::http::link::next_link;
+use crate::http::ndjson_export::NdjsonOptions;
+use crate::http::paginator::{PageRequest, PageResponse, Paginator};
+use crate::http::rate_limit::RateLimiter;
+use crate::http::sse_stream::{sse_rows, SseOptions};
+use crate::http::throttle::{ServerThrottle, Throttled};
+use crate::http::usage::UsageMeter;
+use crate::http::vcr::{Vcr, VcrMode};
+use crate::http::xml_stream::{xml_stream, XmlOptions};
+use crate::pipeline::download_state::DownloadTracker;
+use crate::pipeline::http_cache::{HttpCache, PageValidator};
+use crate::pipeline::page_progress::ProgressTracker;
+use crate::pipeline::retry_state::RetryTracker;
+use crate::pipeline::run_history::SchemaCapture;
+use crate::pipeline::watermark::WatermarkTracker;
+use crate::transform::{BinaryFields, TransformChain};
+use crate::utils::datafusion_ext::{get_shared_context, JsonStreamType, JsonValueExt};
+use crate::utils::http_retry::{self, AttemptHooks};
+use crate::utils::schema;
 use crate::utils::schema::infer_schema_from_values;
 use crate::utils::table_provider::JsonStreamTableProvider;
-use crate::utils::{http_retry, schema};
+use crate::writer::metadata_columns::MetadataColumns;
 use crate::writer::{DataWriter, WriteMode};
 use async_trait::async_trait;
+use bytes::Bytes;
 use datafusion::arrow;
 use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::dataframe::DataFrame;
 use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
 use futures::Stream;
-use reqwest::header::CONTENT_TYPE;
-use reqwest::Client;
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use reqwest::{Client, StatusCode, Url};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::VecDeque;
 use std::pin::Pin;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
 use tokio_util::{
-    codec::{FramedRead, LinesCodec},
+    codec::{FramedRead, LinesCodec, LinesCodecError},
     io::StreamReader,
 };
 use tracing::{debug, debug_span, error, info, info_span, trace, warn};
@@ -29,6 +57,330 @@ use tracing::{debug, debug_span, error, info, info_span, trace, warn};
 // =========================== NDJSON helper ===================================
 pub type BoxStreamCustom<T> = Pin<Box<dyn Stream<Item = T> + Send + 'static>>;
 
+/// Per-source request behaviour shared by every page fetch.
+#[derive(Debug, Clone, Default)]
+pub struct RequestOptions {
+    /// Paces requests using the server's advertised rate-limit budget.
+    pub throttle: Option<Arc<ServerThrottle>>,
+    /// The source's own `rate_limit:`, shared by its concurrent requests.
+    pub rate_limit: Option<Arc<RateLimiter>>,
+    /// Records `Deprecation` / `Sunset` headers seen in responses.
+    pub deprecation: Option<Arc<DeprecationWatch>>,
+    /// Run-wide cap on response body bytes per second.
+    pub bandwidth: Option<Arc<BandwidthLimiter>>,
+    /// Run-wide count of requests and downloaded bytes; requests are
+    /// counted per attempt by the client built with [`Self::attempt_hooks`].
+    pub usage: Option<Arc<UsageMeter>>,
+    /// Persists pages that exhausted their retries.
+    pub failures: Option<RetryTracker>,
+    /// Persists how far a checkpointed NDJSON export got.
+    pub downloads: Option<DownloadTracker>,
+    /// Validators of pages fetched before, for conditional requests.
+    pub http_cache: Option<HttpCache>,
+    /// Records the pages written, and skips those an interrupted run wrote.
+    pub progress: Option<ProgressTracker>,
+    /// Method of every page request; GET unless the source says otherwise.
+    pub method: RequestMethod,
+    /// Body sent with every page request, e.g. a POST search query.
+    pub body: Option<RequestBody>,
+    /// How response bodies are parsed.
+    pub format: ResponseFormat,
+    pub csv: CsvOptions,
+    pub xml: XmlOptions,
+    pub sse: SseOptions,
+    pub ndjson: NdjsonOptions,
+    /// Stamp every row with its fetch-order position in [`SEQUENCE_COLUMN`].
+    pub sequence: bool,
+    /// Adds the source's credentials to every request.
+    pub auth: Option<Arc<dyn Authenticator>>,
+    /// Keeps the raw responses of the first failed pages.
+    pub failure_samples: Option<Arc<FailureSampler>>,
+    /// Parse JSON bodies as they arrive instead of reading them whole
+    /// (`--low-memory`); see [`json_stream`].
+    pub stream_json: bool,
+    /// Caps on page body size and NDJSON line length.
+    pub limits: ResponseLimits,
+    /// Shared by the source's pages for the bodies they read whole
+    /// (`max_in_flight`).
+    pub memory: Option<Arc<MemoryBudget>>,
+    /// Records page responses, or answers page requests from recordings.
+    pub vcr: Option<Vcr>,
+}
+
+/// Column holding a row's position in fetch order.
+pub const SEQUENCE_COLUMN: &str = "_seq";
+
+/// Fetch-order position of the `index`-th row (0-based) of `page`: the page
+/// in the high 32 bits, the row in the low ones. It grows with page and row,
+/// so sorting on it restores fetch order however the pages completed, and a
+/// re-fetched page gets the same values again.
+pub fn row_sequence(page: u64, index: u64) -> u64 {
+    (page << 32) | index
+}
+
+fn stamp_sequence(row: &mut Value, page: u64, index: u64) {
+    if let Value::Object(fields) = row {
+        fields.insert(
+            SEQUENCE_COLUMN.to_string(),
+            Value::from(row_sequence(page, index)),
+        );
+    }
+}
+
+impl RequestOptions {
+    /// `resp` if its status is a success; otherwise the status error, after
+    /// sampling the response.
+    pub async fn check_status(&self, resp: reqwest::Response) -> Result<reqwest::Response> {
+        match resp.error_for_status_ref() {
+            Ok(_) => Ok(resp),
+            Err(e) => {
+                if let Some((sampler, n)) = self
+                    .failure_samples
+                    .as_ref()
+                    .and_then(|sampler| Some((sampler, sampler.claim()?)))
+                {
+                    let url = resp.url().clone();
+                    let (status, headers) = (resp.status(), resp.headers().clone());
+                    let body = self.read_body(resp).await.unwrap_or_else(|read| {
+                        warn!(%url, error = %read, "could not read the body of a failed response");
+                        Vec::new()
+                    });
+                    sampler.write(n, &FailureSample::new(&url, status, &headers, &body));
+                }
+                Err(e.into())
+            }
+        }
+    }
+
+    async fn page_failed(&self, page: u64, error: &ApitapError) {
+        if let Some(tracker) = &self.failures {
+            tracker.page_failed(page, &error.to_string()).await;
+        }
+    }
+
+    async fn page_succeeded(&self, page: u64) {
+        if let Some(tracker) = &self.failures {
+            tracker.page_succeeded(page).await;
+        }
+    }
+
+    /// Record `page` as done once `written` says its rows reached the
+    /// sink, or as failed otherwise.
+    async fn page_done<T>(&self, page: u64, written: Result<T>) -> Result<T> {
+        match &written {
+            Ok(_) => self.page_succeeded(page).await,
+            Err(e) => self.page_failed(page, e).await,
+        }
+        written
+    }
+
+    /// Keep the validators of a page once its `rows` rows were read.
+    async fn keep_validator(&self, mark: Option<PageMark>, rows: usize) {
+        if let (
+            Some(cache),
+            Some(PageMark {
+                url,
+                validator: Some(validator),
+            }),
+        ) = (&self.http_cache, mark)
+        {
+            cache.fetched(url, validator, rows).await;
+        }
+    }
+
+    /// Keep the validators of a page and record it as done once a sink
+    /// took its `rows` rows.
+    async fn page_written(&self, mark: Option<PageMark>, rows: usize) {
+        if let (Some(progress), Some(mark)) = (&self.progress, &mark) {
+            progress.page_written(mark.url.clone(), rows).await;
+        }
+        self.keep_validator(mark, rows).await;
+    }
+
+    /// With `sequence`, stamp the rows of `page` as they stream by.
+    pub fn sequenced(
+        &self,
+        page: u64,
+        rows: BoxStream<'static, Result<Value>>,
+    ) -> BoxStream<'static, Result<Value>> {
+        if !self.sequence {
+            return rows;
+        }
+        rows.enumerate()
+            .map(move |(index, row)| {
+                row.map(|mut row| {
+                    stamp_sequence(&mut row, page, index as u64);
+                    row
+                })
+            })
+            .boxed()
+    }
+
+    /// With `sequence`, stamp the rows of an already buffered `page`.
+    pub fn sequence_page(&self, page: u64, rows: &mut [Value]) {
+        if self.sequence {
+            for (index, row) in rows.iter_mut().enumerate() {
+                stamp_sequence(row, page, index as u64);
+            }
+        }
+    }
+
+    /// What the source's client does for every attempt of a request.
+    pub fn attempt_hooks(&self) -> AttemptHooks {
+        AttemptHooks {
+            rate_limit: self.rate_limit.clone(),
+            throttle: self.throttle.clone(),
+            signer: self.auth.clone().filter(|auth| auth.signs_each_attempt()),
+            usage: self.usage.clone(),
+        }
+    }
+
+    /// Send `req` through `execute` with the source's auth, or answer it
+    /// from the `--replay` recordings without sending it; with `--record`
+    /// the response is written first. Live responses feed the throttle and
+    /// deprecation watch. The status is left to the caller.
+    pub async fn send<F, Fut>(&self, req: reqwest::Request, execute: F) -> Result<reqwest::Response>
+    where
+        F: Fn(reqwest::Request) -> Fut,
+        Fut: std::future::Future<Output = Result<reqwest::Response>>,
+    {
+        if let Some(vcr) = self.vcr.as_ref().filter(|v| v.mode() == VcrMode::Replay) {
+            return vcr.replay(&req);
+        }
+
+        let recorded = self.vcr.as_ref().zip(req.try_clone());
+        let resp = send_authorized(self.auth.as_ref(), req, execute).await?;
+        let resp = match recorded {
+            Some((vcr, req)) => vcr.record(&req, resp).await?,
+            None => resp,
+        };
+
+        if let Some(throttle) = &self.throttle {
+            throttle.observe(resp.headers()).await;
+        }
+        if let Some(watch) = &self.deprecation {
+            watch.observe(resp.headers());
+        }
+        Ok(resp)
+    }
+
+    /// The body as received, counted for usage and metered by the
+    /// bandwidth cap if set.
+    pub fn raw_body(
+        &self,
+        resp: reqwest::Response,
+    ) -> BoxStream<'static, std::result::Result<Bytes, reqwest::Error>> {
+        let body = resp.bytes_stream().boxed();
+        let body = match &self.usage {
+            Some(usage) => Arc::clone(usage).count(body).boxed(),
+            None => body,
+        };
+        match &self.bandwidth {
+            Some(limiter) => Arc::clone(limiter).meter(body).boxed(),
+            None => body,
+        }
+    }
+
+    /// Stream a response body like [`Self::raw_body`], decoded if it is
+    /// still compressed.
+    pub fn body_stream(
+        &self,
+        resp: reqwest::Response,
+    ) -> BoxStream<'static, std::io::Result<Bytes>> {
+        let encoding = Encoding::from_headers(resp.headers());
+        decompressed(self.raw_body(resp), encoding)
+    }
+
+    /// The body of a page response like [`Self::body_stream`], failing
+    /// once it grows past `max_body_size`.
+    pub fn page_body(
+        &self,
+        resp: reqwest::Response,
+    ) -> Result<BoxStream<'static, std::io::Result<Bytes>>> {
+        self.limits.check_content_length(resp.content_length())?;
+        Ok(self.limits.cap_body(self.body_stream(resp)))
+    }
+
+    /// Read a whole page body, like [`Self::page_body`].
+    pub async fn read_body(&self, resp: reqwest::Response) -> Result<Vec<u8>> {
+        Ok(self.read_body_reserved(resp).await?.0)
+    }
+
+    /// [`Self::read_body`], along with the share of `max_in_flight` the
+    /// body holds; keep it for as long as what was parsed from the body.
+    pub async fn read_body_reserved(
+        &self,
+        resp: reqwest::Response,
+    ) -> Result<(Vec<u8>, Option<Reservation>)> {
+        let mut reservation = self.memory.as_ref().map(MemoryBudget::reservation);
+        let mut body = Vec::new();
+        let mut chunks = self.page_body(resp)?;
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk?;
+            if let Some(reservation) = &mut reservation {
+                reservation.grow(chunk.len() as u64).await?;
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok((body, reservation))
+    }
+}
+
+/// `items` as a row stream holding `reservation` until it is dropped.
+fn held(items: Vec<Value>, reservation: Option<Reservation>) -> BoxStream<'static, Result<Value>> {
+    stream::iter(items.into_iter().map(Ok))
+        .map(move |row| {
+            let _ = &reservation;
+            row
+        })
+        .boxed()
+}
+
+/// Group `rows` into batches of `batch_size`; with `flush_interval`, a
+/// batch is also cut short once its first row has waited that long.
+pub fn batched<T: Send + 'static>(
+    rows: BoxStream<'static, T>,
+    batch_size: usize,
+    flush_interval: Option<Duration>,
+) -> BoxStream<'static, Vec<T>> {
+    let batch_size = batch_size.max(1);
+    match flush_interval {
+        Some(interval) => {
+            tokio_stream::StreamExt::chunks_timeout(rows, batch_size, interval).boxed()
+        }
+        None => rows.chunks(batch_size).boxed(),
+    }
+}
+
+/// Push the records of one JSON value, as [`json_records`] finds them.
+pub fn push_records(v: Value, data_path: Option<&str>, out: &mut Vec<Value>) {
+    out.extend(json_records(v, data_path));
+}
+
+/// Records of a JSON document: the array or value at `data_path` if given,
+/// else the whole document, arrays flattened. A `data_path` that is missing
+/// (logged) or null holds no records.
+fn json_records(mut v: Value, data_path: Option<&str>) -> Vec<Value> {
+    let target = match data_path {
+        Some(p) => match v.pointer_mut(p) {
+            Some(inner) => inner.take(),
+            None => {
+                warn!(
+                    data_path = p,
+                    "data_path not found in the response; no records taken"
+                );
+                return Vec::new();
+            }
+        },
+        None => v,
+    };
+    match target {
+        Value::Array(items) => items,
+        Value::Null => Vec::new(),
+        other => vec![other],
+    }
+}
+
 /// Stream an HTTP response as NDJSON and flatten an optional JSON pointer (`/data`, etc.).
 /// If `data_path` is None, it will try to flatten the top-level array; otherwise it yields the object.
 pub async fn ndjson_stream_qs(
@@ -37,74 +389,326 @@ pub async fn ndjson_stream_qs(
     query: &[(String, String)],
     data_path: Option<&str>,
     config_retry: &crate::pipeline::Retry,
+) -> Result<BoxStream<'static, Result<Value>>> {
+    ndjson_stream_with(
+        client,
+        url,
+        query,
+        data_path,
+        config_retry,
+        &RequestOptions::default(),
+    )
+    .await
+}
+
+/// Same as [`ndjson_stream_qs`], honouring the given [`RequestOptions`].
+pub async fn ndjson_stream_with(
+    client: &reqwest::Client,
+    url: &str,
+    query: &[(String, String)],
+    data_path: Option<&str>,
+    config_retry: &crate::pipeline::Retry,
+    request: &RequestOptions,
 ) -> Result<BoxStream<'static, Result<Value>>> {
     // Instrument HTTP/NDJSON parsing for tracing with source and optional data_path
     let span = info_span!("http.ndjson_stream", source = %url, query_len = query.len());
     let _g = span.enter();
-    let client_with_retry = http_retry::build_client_with_retry(client.clone(), config_retry);
+    let resp = send_page_request(client, url, query, config_retry, request).await?;
+    response_rows(resp, data_path, request).await
+}
+
+/// Send one page request with retries, throttling and deprecation tracking;
+/// error statuses become errors.
+pub async fn send_page_request(
+    client: &reqwest::Client,
+    url: &str,
+    query: &[(String, String)],
+    config_retry: &crate::pipeline::Retry,
+    request: &RequestOptions,
+) -> Result<reqwest::Response> {
+    send_page_request_with_headers(client, url, query, &[], config_retry, request).await
+}
+
+/// [`send_page_request`] with extra headers for this request only.
+pub async fn send_page_request_with_headers(
+    client: &reqwest::Client,
+    url: &str,
+    query: &[(String, String)],
+    headers: &[(String, String)],
+    config_retry: &crate::pipeline::Retry,
+    request: &RequestOptions,
+) -> Result<reqwest::Response> {
+    let client_with_retry = http_retry::build_client_with_retry_after(
+        client.clone(),
+        config_retry,
+        request.attempt_hooks(),
+    );
 
     // Instrument the HTTP request/response at debug level with timing and status
+    let method = request.method.as_method();
     let req_span =
-        debug_span!("http.request", method = "GET", source = %url, query_len = query.len());
+        debug_span!("http.request", method = %method, source = %url, query_len = query.len());
     let _req_g = req_span.enter();
     let started = std::time::Instant::now();
 
-    let resp = client_with_retry.get(url).query(query).send().await?;
+    let mut req = client_with_retry.request(method, url).query(query);
+    for (name, value) in headers {
+        req = req.header(name.as_str(), value.as_str());
+    }
+    if let Some(body) = &request.body {
+        req = req
+            .header(CONTENT_TYPE, body.content_type())
+            .body(body.bytes.clone());
+    }
+    let resp = request
+        .send(req.build()?, |req| async {
+            Ok(client_with_retry.execute(req).await?)
+        })
+        .await?;
 
     let status = resp.status();
     let elapsed = started.elapsed();
     debug!(status = %status, elapsed_ms = elapsed.as_millis(), "http response received");
 
-    let resp = resp.error_for_status()?;
+    request.check_status(resp).await
+}
 
+/// A page requested with the validators `http_cache` kept for it.
+pub enum PageFetch {
+    /// Its rows, and what to keep about the page once they are written.
+    Rows(BoxStream<'static, Result<Value>>, Option<PageMark>),
+    /// 304 Not Modified, or written by the interrupted run being resumed;
+    /// `rows` is what the page held then.
+    Unchanged { rows: usize },
+}
+
+/// A fetched page's request URL, and its validators with `http_cache`.
+#[derive(Debug, Clone)]
+pub struct PageMark {
+    pub url: String,
+    pub validator: Option<PageValidator>,
+}
+
+/// How a page of a concurrent fetch ended.
+enum PageOutcome {
+    Written(usize),
+    Unchanged,
+    Failed,
+}
+
+/// The rows of a page; with `http_cache`, the server is asked to answer 304
+/// instead when the page is unchanged since it was last fetched. A page the
+/// resumed run already wrote is not requested at all.
+pub async fn conditional_rows(
+    client: &reqwest::Client,
+    url: &str,
+    query: &[(String, String)],
+    data_path: Option<&str>,
+    config_retry: &crate::pipeline::Retry,
+    request: &RequestOptions,
+) -> Result<PageFetch> {
+    let stop = StopWhen::default();
+    let (page, _) = page_rows(client, url, query, data_path, config_retry, request, &stop).await?;
+    Ok(page)
+}
+
+/// [`conditional_rows`] for a walk of unknown length, also telling whether
+/// the body marks the page as the last one through `stop.pointer`. A page
+/// that is not fetched again is never flagged; the walk then ends at the
+/// next empty page.
+async fn page_rows(
+    client: &reqwest::Client,
+    url: &str,
+    query: &[(String, String)],
+    data_path: Option<&str>,
+    config_retry: &crate::pipeline::Retry,
+    request: &RequestOptions,
+    stop: &StopWhen,
+) -> Result<(PageFetch, bool)> {
+    match conditional_response(client, url, query, config_retry, request).await? {
+        Conditional::Unchanged { rows } => Ok((PageFetch::Unchanged { rows }, false)),
+        Conditional::Fetched(resp, mark) => {
+            let (rows, last) = response_page(*resp, data_path, request, stop).await?;
+            Ok((PageFetch::Rows(rows, mark), last))
+        }
+    }
+}
+
+/// A page request made through [`conditional_response`].
+enum Conditional {
+    /// The response, and what to keep about the page once it is written.
+    Fetched(Box<reqwest::Response>, Option<PageMark>),
+    /// Not fetched again; `rows` is what the page held before.
+    Unchanged { rows: usize },
+}
+
+/// The first page of a walk with a total hint.
+enum FirstPage {
+    /// A JSON page read whole, as its headers or body tell the total.
+    Json {
+        headers: HeaderMap,
+        body: Value,
+        mark: Option<PageMark>,
+    },
+    /// A page with no total to read: not JSON, or not fetched again.
+    Other(PageFetch, bool),
+}
+
+/// Request a page unless the resumed run already wrote it, with the
+/// validators `http_cache` kept for it.
+async fn conditional_response(
+    client: &reqwest::Client,
+    url: &str,
+    query: &[(String, String)],
+    config_retry: &crate::pipeline::Retry,
+    request: &RequestOptions,
+) -> Result<Conditional> {
+    let span = info_span!("http.ndjson_stream", source = %url, query_len = query.len());
+    let _g = span.enter();
+    if request.http_cache.is_none() && request.progress.is_none() {
+        let resp = send_page_request(client, url, query, config_retry, request).await?;
+        return Ok(Conditional::Fetched(Box::new(resp), None));
+    }
+    let key = reqwest::Url::parse_with_params(url, query)?.to_string();
+    if let Some(rows) = request.progress.as_ref().and_then(|p| p.written(&key)) {
+        debug!(url = %key, rows, "page written before the interruption; skipping");
+        return Ok(Conditional::Unchanged { rows });
+    }
+    let Some(cache) = &request.http_cache else {
+        let resp = send_page_request(client, url, query, config_retry, request).await?;
+        let mark = PageMark {
+            url: key,
+            validator: None,
+        };
+        return Ok(Conditional::Fetched(Box::new(resp), Some(mark)));
+    };
+    let cached = cache.validator(&key).await;
+    let headers = cached
+        .as_ref()
+        .map(PageValidator::conditional_headers)
+        .unwrap_or_default();
+    let resp =
+        send_page_request_with_headers(client, url, query, &headers, config_retry, request).await?;
+    if resp.status() == StatusCode::NOT_MODIFIED {
+        if let Some(cached) = cached {
+            debug!(url = %key, rows = cached.rows, "page not modified; skipping");
+            cache.unchanged();
+            return Ok(Conditional::Unchanged { rows: cached.rows });
+        }
+    }
+    let mark = PageMark {
+        url: key,
+        validator: PageValidator::from_headers(resp.headers()),
+    };
+    Ok(Conditional::Fetched(Box::new(resp), Some(mark)))
+}
+
+/// Parse a page response into rows, by `request.format` or its content type.
+pub async fn response_rows(
+    resp: reqwest::Response,
+    data_path: Option<&str>,
+    request: &RequestOptions,
+) -> Result<BoxStream<'static, Result<Value>>> {
+    let (rows, _) = response_page(resp, data_path, request, &StopWhen::default()).await?;
+    Ok(rows)
+}
+
+/// [`response_rows`], also telling whether a JSON body marks the page as the
+/// last one through `stop.pointer`. Such a body is read whole rather than
+/// streamed, as the flag may follow the rows.
+async fn response_page(
+    resp: reqwest::Response,
+    data_path: Option<&str>,
+    request: &RequestOptions,
+    stop: &StopWhen,
+) -> Result<(BoxStream<'static, Result<Value>>, bool)> {
     // Heuristic: treat as NDJSON only if content-type says so
-    let is_ndjson = resp
+    let content_type = resp
         .headers()
         .get(CONTENT_TYPE)
         .and_then(|h| h.to_str().ok())
-        .map(|ct| ct.contains("ndjson") || ct.contains("x-ndjson"))
-        .unwrap_or(false);
+        .unwrap_or_default();
+    let is_ndjson = content_type.contains("ndjson") || content_type.contains("x-ndjson");
+    let is_csv = request.format == ResponseFormat::Csv || content_type.contains("text/csv");
+
+    let is_xml = request.format == ResponseFormat::Xml
+        || (request.format == ResponseFormat::Json && content_type.contains("xml"));
+
+    if request.format == ResponseFormat::Sse || content_type.contains("text/event-stream") {
+        debug!("parsing event-stream response");
+        return Ok((sse_rows(request.page_body(resp)?, data_path), false));
+    }
+
+    if is_csv || is_xml {
+        let byte_stream = request.page_body(resp)?;
+        if is_xml {
+            debug!(record = %request.xml.record, "parsing XML response");
+            return Ok((xml_stream(byte_stream, &request.xml), false));
+        }
+        debug!("parsing CSV response");
+        return Ok((csv_stream(byte_stream, &request.csv)?, false));
+    }
+
+    if !is_ndjson && request.stream_json && stop.pointer.is_none() {
+        debug!("parsing JSON response as it arrives");
+        return Ok((json_stream(request.page_body(resp)?, data_path), false));
+    }
 
     if !is_ndjson {
         // -------- Regular JSON (object or array) path --------
-        let bytes = resp.bytes().await?;
-        let v: Value = serde_json::from_slice(&bytes)?;
-
-        // If data_path is provided, drill into it; else use the whole value.
-        let target = if let Some(p) = data_path {
-            v.pointer(p).cloned().unwrap_or(Value::Null)
-        } else {
-            v
+        let (bytes, reservation) = request.read_body_reserved(resp).await?;
+        let v: Value = match serde_json::from_slice(&bytes) {
+            Ok(v) => v,
+            // Compressed exports are often NDJSON without saying so.
+            Err(e) => {
+                let mut items = Vec::new();
+                for line in bytes.split(|b| *b == b'\n') {
+                    if line.iter().all(u8::is_ascii_whitespace) {
+                        continue;
+                    }
+                    let Ok(v) = serde_json::from_slice(line) else {
+                        return Err(e.into());
+                    };
+                    push_records(v, data_path, &mut items);
+                }
+                if items.is_empty() {
+                    return Err(e.into());
+                }
+                debug!(items = items.len(), "parsed response as NDJSON");
+                return Ok((held(items, reservation), false));
+            }
         };
 
-        let items: Vec<Value> = if let Some(arr) = target.as_array() {
-            arr.clone()
-        } else if target.is_null() {
-            Vec::new()
-        } else {
-            vec![target]
-        };
+        let last = stop.flagged_last(&v);
+        let items = json_records(v, data_path);
 
         debug!(items = items.len(), "parsed JSON response items");
 
         // Emit as a stream of Values
-        let st = stream::iter(items.into_iter().map(Ok)).boxed();
-        return Ok(st);
+        return Ok((held(items, reservation), last));
     }
 
     // -------- NDJSON path (one JSON per line) --------
-    let byte_stream = resp
-        .bytes_stream()
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e));
-
+    let byte_stream = request.page_body(resp)?;
     let reader = StreamReader::new(byte_stream);
-    let lines = FramedRead::new(reader, LinesCodec::new());
+    let max_line_length = request.limits.max_line_length;
+    let codec = match max_line_length {
+        Some(max) => LinesCodec::new_with_max_length(max),
+        None => LinesCodec::new(),
+    };
+    let lines = FramedRead::new(reader, codec);
     let data_path_owned = data_path.map(|s| s.to_owned());
 
     let s = async_stream::try_stream! {
         let mut lines = lines;
         while let Some(line_res) = lines.next().await {
-            let line = line_res?;
+            let line = line_res.map_err(|e| match e {
+                LinesCodecError::MaxLineLengthExceeded => ApitapError::PipelineError(format!(
+                    "NDJSON line longer than max_line_length ({} bytes)",
+                    max_line_length.unwrap_or_default()
+                )),
+                e => e.into(),
+            })?;
             let trimmed = line.trim();
             if trimmed.is_empty() { continue; }
 
@@ -112,25 +716,12 @@ pub async fn ndjson_stream_qs(
 
             let v: Value = serde_json::from_str(trimmed)?;
 
-            if let Some(ref p) = data_path_owned {
-                if let Some(inner) = v.pointer(p) {
-                    if let Some(arr) = inner.as_array() {
-                        for item in arr { yield item.clone(); }
-                    } else if !inner.is_null() {
-                        yield inner.clone();
-                    }
-                    continue;
-                }
-            }
-
-            if let Some(arr) = v.as_array() {
-                for item in arr { yield item.clone(); }
-            } else {
-                yield v;
-            }
+            let mut records = Vec::new();
+            push_records(v, data_path_owned.as_deref(), &mut records);
+            for record in records { yield record; }
         }
     };
-    Ok(s.boxed())
+    Ok((s.boxed(), false))
 }
 
 // =============================== Page Writer =================================
@@ -152,6 +743,16 @@ pub trait PageWriter: Send + Sync {
         Ok(())
     }
 
+    /// [`Self::write_page_stream`] for the rows of page `page_number`.
+    async fn write_numbered_page_stream(
+        &self,
+        _page_number: u64,
+        stream_data: Pin<Box<dyn Stream<Item = Result<Value>> + Send>>,
+        write_mode: WriteMode,
+    ) -> Result<()> {
+        self.write_page_stream(stream_data, write_mode).await
+    }
+
     async fn on_page_error(&self, page_number: u64, error: String) -> Result<()> {
         error!(page = page_number, %error, "error fetching page");
         Ok(())
@@ -173,21 +774,190 @@ pub enum Pagination {
     LimitOffset {
         limit_param: String,
         offset_param: String,
+        /// JSON pointer to the total item count in the first response; with
+        /// it (or `total_pages_pointer`) the remaining pages are fetched
+        /// concurrently instead of until an empty page.
+        #[serde(default)]
+        total_items_pointer: Option<String>,
+        /// JSON pointer to the total page count in the first response.
+        #[serde(default)]
+        total_pages_pointer: Option<String>,
+        /// Response header with the total item count, e.g. `X-Total-Count`;
+        /// an alternative to `total_items_pointer`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        total_items_header: Option<String>,
+        /// Response header with the total page count, e.g. `X-Total-Pages`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        total_pages_header: Option<String>,
+        /// When a walk without a total ends, besides an empty page.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        stop_when: Option<StopWhen>,
     },
     PageNumber {
         page_param: String,
         per_page_param: String,
+        #[serde(default)]
+        total_items_pointer: Option<String>,
+        #[serde(default)]
+        total_pages_pointer: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        total_items_header: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        total_pages_header: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        stop_when: Option<StopWhen>,
     },
     PageOnly {
         page_param: String,
     },
+    /// Send the cursor each response names as `cursor_param` until it is
+    /// missing or empty. The cursor is read from the body at the JSON
+    /// pointer `next_cursor_path`, or from the `next_cursor_header` response
+    /// header (e.g. `X-Next-Page`).
     Cursor {
         cursor_param: String,
         page_size_param: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        next_cursor_path: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        next_cursor_header: Option<String>,
+    },
+    /// Follow `rel="next"` in the `Link` response header until it is absent.
+    /// `page_size_param` is only sent on the first request; next links
+    /// carry their own query.
+    LinkHeader {
+        #[serde(default)]
+        page_size_param: Option<String>,
+    },
+    /// Follow the full next-page URL at the JSON pointer `next_path`
+    /// (`/paging/next`, `/links/next`) until it is missing, null or empty.
+    NextUrl {
+        next_path: String,
+        #[serde(default)]
+        page_size_param: Option<String>,
+    },
+    /// A [`Paginator`] registered under `paginator` through the library API.
+    Custom {
+        paginator: String,
     },
     Default,
 }
 
+impl Pagination {
+    /// The total hint configured by `total_items_pointer`,
+    /// `total_pages_pointer`, `total_items_header` or `total_pages_header`,
+    /// for the modes that support one.
+    pub fn total_hint(&self) -> Result<Option<TotalHint>> {
+        let (items, pages, items_header, pages_header) = match self {
+            Pagination::LimitOffset {
+                total_items_pointer,
+                total_pages_pointer,
+                total_items_header,
+                total_pages_header,
+                ..
+            }
+            | Pagination::PageNumber {
+                total_items_pointer,
+                total_pages_pointer,
+                total_items_header,
+                total_pages_header,
+                ..
+            } => (
+                total_items_pointer,
+                total_pages_pointer,
+                total_items_header,
+                total_pages_header,
+            ),
+            _ => return Ok(None),
+        };
+        let mut hints = Vec::new();
+        hints.extend(items.clone().map(|pointer| TotalHint::Items { pointer }));
+        hints.extend(pages.clone().map(|pointer| TotalHint::Pages { pointer }));
+        hints.extend(
+            items_header
+                .clone()
+                .map(|header| TotalHint::ItemsHeader { header }),
+        );
+        hints.extend(
+            pages_header
+                .clone()
+                .map(|header| TotalHint::PagesHeader { header }),
+        );
+        if hints.len() > 1 {
+            return Err(ApitapError::ConfigError(
+                "set only one of total_items_pointer, total_pages_pointer, total_items_header and total_pages_header".into(),
+            ));
+        }
+        Ok(hints.pop())
+    }
+}
+
+impl Pagination {
+    /// The `stop_when` of the modes that walk pages until told to stop.
+    pub fn stop_when(&self) -> Result<StopWhen> {
+        let stop = match self {
+            Pagination::LimitOffset { stop_when, .. }
+            | Pagination::PageNumber { stop_when, .. } => stop_when.clone().unwrap_or_default(),
+            _ => return Ok(StopWhen::default()),
+        };
+        if let Some(pointer) = &stop.pointer {
+            if !pointer.starts_with('/') {
+                return Err(ApitapError::ConfigError(format!(
+                    "stop_when.pointer must be a JSON pointer like /has_more, got {pointer}"
+                )));
+            }
+        }
+        Ok(stop)
+    }
+}
+
+/// When a page walk of unknown length ends. An empty page always ends it;
+/// these catch APIs that send a short last page or a `has_more` flag.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StopWhen {
+    /// A page with fewer rows than the page size is the last one.
+    #[serde(default)]
+    pub short_page: bool,
+    /// JSON pointer into each response body, e.g. `/has_more`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pointer: Option<String>,
+    /// The page is the last one when the value at `pointer` equals this.
+    #[serde(default = "default_stop_value")]
+    pub equals: Value,
+}
+
+fn default_stop_value() -> Value {
+    Value::Bool(false)
+}
+
+impl Default for StopWhen {
+    fn default() -> Self {
+        Self {
+            short_page: false,
+            pointer: None,
+            equals: default_stop_value(),
+        }
+    }
+}
+
+impl StopWhen {
+    /// Whether the page after one of `rows` rows should be fetched;
+    /// `envelope` is the response body when [`Self::pointer`] is set.
+    pub fn more(&self, envelope: Option<&Value>, rows: usize, page_size: u64) -> bool {
+        if rows == 0 || (self.short_page && (rows as u64) < page_size) {
+            return false;
+        }
+        !envelope.is_some_and(|doc| self.flagged_last(doc))
+    }
+
+    /// Whether the body marks its page as the last one through `pointer`.
+    pub fn flagged_last(&self, envelope: &Value) -> bool {
+        self.pointer
+            .as_ref()
+            .is_some_and(|pointer| envelope.pointer(pointer) == Some(&self.equals))
+    }
+}
+
 /// Hint to compute total pages.
 /// - Items: pointer points to total items; pages = ceil(items/limit)
 /// - Pages:  pointer points directly to total pages
@@ -195,6 +965,121 @@ pub enum Pagination {
 pub enum TotalHint {
     Items { pointer: String },
     Pages { pointer: String },
+    ItemsHeader { header: String },
+    PagesHeader { header: String },
+}
+
+impl TotalHint {
+    /// Total pages announced by the first response, if it has the field.
+    pub fn total_pages(&self, first: &Value, per_page: u64) -> Option<u64> {
+        self.pages_after(first, per_page, 0)
+    }
+
+    /// Pages left from item `start` (0-based) on, for a run that does not
+    /// begin at the first item.
+    pub fn pages_after(&self, first: &Value, per_page: u64, start: u64) -> Option<u64> {
+        self.pages_after_response(&HeaderMap::new(), first, per_page, start)
+    }
+
+    /// Like [`Self::pages_after`], also reading header totals from the first
+    /// response's `headers`.
+    pub fn pages_after_response(
+        &self,
+        headers: &HeaderMap,
+        first: &Value,
+        per_page: u64,
+        start: u64,
+    ) -> Option<u64> {
+        let per_page = per_page.max(1);
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok())
+        };
+        let items = |total_items: u64| {
+            let left = total_items.saturating_sub(start);
+            left / per_page + u64::from(left % per_page != 0)
+        };
+        let pages = |total_pages: u64| total_pages.saturating_sub(start / per_page);
+        match self {
+            TotalHint::Items { pointer } => {
+                first.pointer(pointer).and_then(Value::as_u64).map(items)
+            }
+            TotalHint::Pages { pointer } => {
+                first.pointer(pointer).and_then(Value::as_u64).map(pages)
+            }
+            TotalHint::ItemsHeader { header: name } => header(name).map(items),
+            TotalHint::PagesHeader { header: name } => header(name).map(pages),
+        }
+    }
+}
+
+/// Where [`PaginatedFetcher::follow_next_stream`] finds the next page.
+enum NextFrom {
+    /// `rel="next"` in the `Link` header.
+    Link,
+    /// A URL at this JSON pointer in the body.
+    UrlAt(String),
+    /// A cursor at this JSON pointer in the body.
+    CursorAt(String),
+    /// A cursor in this response header.
+    CursorHeader(String),
+}
+
+/// `start:` on a source: where pagination begins instead of the first page,
+/// to resume an interrupted backfill. Set the key matching the pagination.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StartAt {
+    /// First offset for `limit_offset`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u64>,
+    /// First page for `page_number` (pages count from 1).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page: Option<u64>,
+    /// First URL for `link_header`, `next_url` or `cursor`, e.g. the last
+    /// next link logged; it replaces the source URL and its query.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// First cursor for `cursor`, sent in its `cursor_param` with the
+    /// source's query.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+impl StartAt {
+    /// Check the start fits `pagination`.
+    pub fn validate(&self, pagination: Option<&Pagination>) -> Result<()> {
+        let (key, needs) = match (&self.offset, &self.page, &self.url, &self.cursor) {
+            (None, None, None, None) => return Ok(()),
+            (Some(_), None, None, None) => ("offset", "limit_offset"),
+            (None, Some(0), None, None) => {
+                return Err(ApitapError::ConfigError("start.page counts from 1".into()))
+            }
+            (None, Some(_), None, None) => ("page", "page_number"),
+            (None, None, Some(_), None) => ("url", "link_header, next_url or cursor"),
+            (None, None, None, Some(_)) => ("cursor", "cursor"),
+            _ => {
+                return Err(ApitapError::ConfigError(
+                    "set only one of start.offset, start.page, start.url and start.cursor".into(),
+                ))
+            }
+        };
+        let fits = match pagination {
+            Some(Pagination::LimitOffset { .. }) => key == "offset",
+            Some(Pagination::PageNumber { .. }) => key == "page",
+            Some(Pagination::LinkHeader { .. } | Pagination::NextUrl { .. }) => key == "url",
+            Some(Pagination::Cursor { .. }) => key == "url" || key == "cursor",
+            _ => false,
+        };
+        if fits {
+            Ok(())
+        } else {
+            Err(ApitapError::ConfigError(format!(
+                "start.{key} needs {needs} pagination"
+            )))
+        }
+    }
 }
 
 // =========================== Fetcher =========================================
@@ -205,6 +1090,10 @@ pub struct PaginatedFetcher {
     concurrency: usize,
     pagination_config: Pagination,
     batch_size: usize,
+    flush_interval: Option<Duration>,
+    stop: StopWhen,
+    request: RequestOptions,
+    start: StartAt,
 }
 
 impl PaginatedFetcher {
@@ -215,9 +1104,34 @@ impl PaginatedFetcher {
             concurrency,
             pagination_config: Pagination::Default,
             batch_size: 256,
+            flush_interval: None,
+            stop: StopWhen::default(),
+            request: RequestOptions::default(),
+            start: StartAt::default(),
         }
     }
 
+    /// End walks of unknown length on `stop` as well as on an empty page.
+    pub fn with_stop_when(mut self, stop: StopWhen) -> Self {
+        self.stop = stop;
+        self
+    }
+
+    /// Begin at `start` instead of the first page.
+    pub fn with_start(mut self, start: StartAt) -> Self {
+        self.start = start;
+        self
+    }
+
+    pub fn with_request_options(mut self, request: RequestOptions) -> Self {
+        self.request = request;
+        self
+    }
+
+    pub fn request_options(&self) -> &RequestOptions {
+        &self.request
+    }
+
     pub fn with_limit_offset(
         mut self,
         limit_param: impl Into<String>,
@@ -226,6 +1140,11 @@ impl PaginatedFetcher {
         self.pagination_config = Pagination::LimitOffset {
             limit_param: limit_param.into(),
             offset_param: offset_param.into(),
+            total_items_pointer: None,
+            total_pages_pointer: None,
+            total_items_header: None,
+            total_pages_header: None,
+            stop_when: None,
         };
         self
     }
@@ -238,6 +1157,33 @@ impl PaginatedFetcher {
         self.pagination_config = Pagination::PageNumber {
             page_param: page_param.into(),
             per_page_param: per_page_param.into(),
+            total_items_pointer: None,
+            total_pages_pointer: None,
+            total_items_header: None,
+            total_pages_header: None,
+            stop_when: None,
+        };
+        self
+    }
+
+    pub fn with_pagination(mut self, pagination: Pagination) -> Self {
+        self.pagination_config = pagination;
+        self
+    }
+
+    pub fn with_link_header(mut self, page_size_param: Option<String>) -> Self {
+        self.pagination_config = Pagination::LinkHeader { page_size_param };
+        self
+    }
+
+    pub fn with_next_url(
+        mut self,
+        next_path: impl Into<String>,
+        page_size_param: Option<String>,
+    ) -> Self {
+        self.pagination_config = Pagination::NextUrl {
+            next_path: next_path.into(),
+            page_size_param,
         };
         self
     }
@@ -247,6 +1193,13 @@ impl PaginatedFetcher {
         self
     }
 
+    /// Write a partial batch once its first row has waited `interval`,
+    /// instead of holding rows until `batch_size` is reached.
+    pub fn with_flush_interval(mut self, interval: Option<Duration>) -> Self {
+        self.flush_interval = interval;
+        self
+    }
+
     pub async fn limit_offset_stream(
         &self,
         limit: u64,
@@ -258,6 +1211,7 @@ impl PaginatedFetcher {
             Pagination::LimitOffset {
                 limit_param,
                 offset_param,
+                ..
             } => (limit_param.clone(), offset_param.clone()),
             other => {
                 return Err(crate::errors::ApitapError::PaginationError(format!(
@@ -271,10 +1225,13 @@ impl PaginatedFetcher {
         let data_path_owned = data_path.map(|s| s.to_string());
         let retry_cfg = config_retry.clone();
         let extra_params_owned = extra_params.map(|p| p.to_vec()).unwrap_or_default();
+        let request = self.request.clone();
+        let stop = self.stop.clone();
 
         // Build the stream
+        let start_offset = self.start.offset.unwrap_or(0);
         let s = async_stream::try_stream! {
-            let mut offset: u64 = 0;
+            let mut offset: u64 = start_offset;
 
             loop {
                 // Merge pagination params with extra params
@@ -282,24 +1239,50 @@ impl PaginatedFetcher {
                 query_params.push((limit_param.clone(), limit.to_string()));
                 query_params.push((offset_param.clone(), offset.to_string()));
 
-                let mut page_stream: BoxStream<'static, crate::errors::Result<Value>> =
-                    ndjson_stream_qs(
-                        &client,
-                        &base_url,
-                        &query_params,
-                        data_path_owned.as_deref(),
-                        &retry_cfg,
-                    ).await?;
+                let page = offset / limit.max(1) + 1;
+                let fetched = page_rows(
+                    &client,
+                    &base_url,
+                    &query_params,
+                    data_path_owned.as_deref(),
+                    &retry_cfg,
+                    &request,
+                    &stop,
+                ).await;
+                let (fetched, last) = match fetched {
+                    Ok(fetched) => fetched,
+                    Err(e) => {
+                        request.page_failed(page, &e).await;
+                        Err(e)?
+                    }
+                };
+                let page_count = match fetched {
+                    PageFetch::Unchanged { rows } => rows,
+                    PageFetch::Rows(rows, mark) => {
+                        let mut page_stream: BoxStream<'static, crate::errors::Result<Value>> =
+                            request.sequenced(page, rows);
 
-                let mut page_count = 0usize;
+                        let mut page_count = 0usize;
 
-                while let Some(item) = page_stream.next().await {
-                    let v = item?;
-                    page_count += 1;
-                    yield v;
-                }
+                        while let Some(item) = page_stream.next().await {
+                            let v = match item {
+                                Ok(v) => v,
+                                Err(e) => {
+                                    request.page_failed(page, &e).await;
+                                    Err(e)?
+                                }
+                            };
+                            page_count += 1;
+                            yield v;
+                        }
+                        request.keep_validator(mark, page_count).await;
+                        page_count
+                    }
+                };
+                // The sink took every row of the page once it asks for more.
+                request.page_succeeded(page).await;
 
-                if page_count == 0 {
+                if last || !stop.more(None, page_count, limit) {
                     break;
                 }
 
@@ -310,42 +1293,532 @@ impl PaginatedFetcher {
         Ok(Box::pin(s))
     }
 
-    /// LIMIT/OFFSET mode. If `total_hint` is None, it fetches until a page yields 0 rows.
+    /// LIMIT/OFFSET mode. If `total_hint` is None, it fetches until a page yields 0 rows;
+    /// with one, the first response gives the page count and the rest are fetched concurrently.
+    #[allow(clippy::too_many_arguments)]
     pub async fn fetch_limit_offset(
         &self,
         limit: u64,
         data_path: Option<String>,
         extra_params: Option<&[(String, String)]>,
-        _total_hint: Option<TotalHint>,
-        writer: Arc<dyn PageWriter>,
-        write_mode: WriteMode,
+        total_hint: Option<TotalHint>,
+        writer: Arc<dyn PageWriter>,
+        write_mode: WriteMode,
+        config_retry: &crate::pipeline::Retry,
+    ) -> Result<FetchStats> {
+        let span = info_span!("fetch.limit_offset.stream", source = %self.base_url, limit = limit);
+        let _g = span.enter();
+
+        let mut stats = FetchStats::new();
+        if let Some(offset) = self.start.offset.filter(|&o| o > 0) {
+            info!(offset, "starting at a later offset");
+        }
+
+        match total_hint {
+            // No JSON envelope to read a total from otherwise.
+            Some(hint) if self.request.format == ResponseFormat::Json => {
+                let (limit_param, offset_param) = match &self.pagination_config {
+                    Pagination::LimitOffset {
+                        limit_param,
+                        offset_param,
+                        ..
+                    } => (limit_param.clone(), offset_param.clone()),
+                    other => {
+                        return Err(ApitapError::PaginationError(format!(
+                            "Pagination::LimitOffset not configured {other:?}"
+                        )));
+                    }
+                };
+                let extra = extra_params.map(|p| p.to_vec()).unwrap_or_default();
+                // Pages are numbered from offset 0, also when starting later.
+                let start = self.start.offset.unwrap_or(0);
+                let first_page = start / limit.max(1) + 1;
+                let query_for = |page: u64| {
+                    let mut query = extra.clone();
+                    query.push((limit_param.clone(), limit.to_string()));
+                    let offset = start + (page - first_page) * limit;
+                    query.push((offset_param.clone(), offset.to_string()));
+                    query
+                };
+
+                writer.begin().await?;
+                let first = match self
+                    .first_page(&query_for(first_page), data_path.as_deref(), config_retry)
+                    .await
+                {
+                    Ok(first) => first,
+                    Err(e) => {
+                        self.request.page_failed(first_page, &e).await;
+                        return Err(e);
+                    }
+                };
+                let (remaining, first_is_last, n) = match first {
+                    FirstPage::Json {
+                        headers,
+                        body,
+                        mark,
+                    } => {
+                        let remaining = hint.pages_after_response(&headers, &body, limit, start);
+                        let last = self.stop.flagged_last(&body);
+                        let rows = json_records(body, data_path.as_deref());
+                        let n = self
+                            .write_held_page(
+                                first_page,
+                                rows,
+                                mark,
+                                &*writer,
+                                &mut stats,
+                                write_mode.clone(),
+                            )
+                            .await;
+                        (remaining, last, n)
+                    }
+                    FirstPage::Other(fetched, last) => {
+                        let n = self
+                            .write_fetched_page(
+                                first_page,
+                                fetched,
+                                &*writer,
+                                &mut stats,
+                                write_mode.clone(),
+                            )
+                            .await;
+                        (None, last, n)
+                    }
+                };
+                let n = self.request.page_done(first_page, n).await?;
+
+                match remaining {
+                    Some(remaining) => {
+                        debug!(remaining, "fetching remaining pages concurrently");
+                        self.fetch_pages_concurrently(
+                            first_page + 1..=first_page + remaining.saturating_sub(1),
+                            query_for,
+                            data_path.as_deref(),
+                            &writer,
+                            &write_mode,
+                            config_retry,
+                            &mut stats,
+                        )
+                        .await;
+                    }
+                    None => {
+                        warn!(
+                            ?hint,
+                            "no total in the first response; fetching until an empty page"
+                        );
+                        let mut page = first_page + 1;
+                        let mut more = !first_is_last && self.stop.more(None, n, limit);
+                        while more {
+                            let fetched = page_rows(
+                                &self.client,
+                                &self.base_url,
+                                &query_for(page),
+                                data_path.as_deref(),
+                                config_retry,
+                                &self.request,
+                                &self.stop,
+                            )
+                            .await;
+                            let (fetched, last) = match fetched {
+                                Ok(fetched) => fetched,
+                                Err(e) => {
+                                    self.request.page_failed(page, &e).await;
+                                    return Err(e);
+                                }
+                            };
+                            let wrote = self
+                                .write_fetched_page(
+                                    page,
+                                    fetched,
+                                    &*writer,
+                                    &mut stats,
+                                    write_mode.clone(),
+                                )
+                                .await;
+                            let wrote = self.request.page_done(page, wrote).await?;
+                            more = !last && self.stop.more(None, wrote, limit);
+                            page += 1;
+                        }
+                    }
+                }
+                writer.commit().await?;
+                return Ok(stats);
+            }
+            Some(_) => warn!("total hints need JSON responses; fetching until an empty page"),
+            None => {}
+        }
+
+        // Build a single JsonStreamType over all pages
+        let json_stream = self
+            .limit_offset_stream(limit, data_path.as_deref(), extra_params, config_retry)
+            .await?;
+
+        // Now you can wrap it into your QueryResultStream abstraction
+
+        self.write_streamed_page(1, json_stream, &*writer, &mut stats, write_mode.clone())
+            .await?;
+
+        // You don't have per-page stats here easily, but you could compute total_items
+        // inside write_stream, or wrap the stream to count rows.
+        Ok(stats)
+    }
+
+    /// Stream every page reachable from the base URL through next-page
+    /// URLs: `rel="next"` in the `Link` header, the URL at `next_path` in
+    /// the body, or the first request repeated with the cursor the response
+    /// names. A URL already visited ends the walk.
+    pub async fn follow_next_stream(
+        &self,
+        page_size: u64,
+        data_path: Option<&str>,
+        extra_params: Option<&[(String, String)]>,
+        config_retry: &crate::pipeline::Retry,
+    ) -> Result<JsonStreamType> {
+        let (next_from, page_size_param, cursor_param) = match &self.pagination_config {
+            Pagination::LinkHeader { page_size_param } => {
+                (NextFrom::Link, page_size_param.clone(), String::new())
+            }
+            Pagination::NextUrl {
+                next_path,
+                page_size_param,
+            } => (
+                NextFrom::UrlAt(next_path.clone()),
+                page_size_param.clone(),
+                String::new(),
+            ),
+            Pagination::Cursor {
+                cursor_param,
+                page_size_param,
+                next_cursor_path,
+                next_cursor_header,
+            } => {
+                let from = match (next_cursor_path, next_cursor_header) {
+                    (Some(pointer), None) => NextFrom::CursorAt(pointer.clone()),
+                    (None, Some(header)) => NextFrom::CursorHeader(header.clone()),
+                    _ => {
+                        return Err(ApitapError::ConfigError(
+                            "cursor pagination needs one of next_cursor_path and next_cursor_header".into(),
+                        ));
+                    }
+                };
+                (from, page_size_param.clone(), cursor_param.clone())
+            }
+            other => {
+                return Err(ApitapError::PaginationError(format!(
+                    "Pagination::LinkHeader, NextUrl or Cursor not configured {other:?}"
+                )));
+            }
+        };
+
+        let client = self.client.clone();
+        let data_path_owned = data_path.map(|s| s.to_string());
+        let retry_cfg = config_retry.clone();
+        let request = self.request.clone();
+        let mut query = extra_params.map(|p| p.to_vec()).unwrap_or_default();
+        if let Some(param) = page_size_param {
+            query.push((param, page_size.to_string()));
+        }
+        // Cursor requests repeat the first request's query with the cursor added.
+        let cursor_base = Url::parse(&self.base_url).map(|mut base| {
+            base.query_pairs_mut().extend_pairs(&query);
+            base
+        });
+        let next_cursor = move |cursor: String| -> Option<Url> {
+            let mut next = cursor_base.as_ref().ok()?.clone();
+            next.query_pairs_mut().append_pair(&cursor_param, &cursor);
+            Some(next)
+        };
+        let mut url = self.base_url.clone();
+        if let Some(start) = &self.start.url {
+            // A next link from an earlier run carries its own query.
+            info!(start = %start, "starting at a later page URL");
+            url = start.clone();
+            query.clear();
+        } else if let Some(cursor) = &self.start.cursor {
+            info!(cursor = %cursor, "starting at a later cursor");
+            if let Some(first) = next_cursor(cursor.clone()) {
+                url = first.into();
+                query.clear();
+            }
+        }
+
+        let s = async_stream::try_stream! {
+            let mut visited = std::collections::HashSet::new();
+            let mut page: u64 = 1;
+            loop {
+                let fetched: Result<_> = async {
+                    let resp = send_page_request(&client, &url, &query, &retry_cfg, &request).await?;
+                    let base = resp.url().clone();
+                    Ok(match &next_from {
+                        NextFrom::Link => {
+                            let next = next_link(resp.headers(), &base);
+                            (response_rows(resp, data_path_owned.as_deref(), &request).await?, next)
+                        }
+                        NextFrom::CursorHeader(header) => {
+                            let next = resp
+                                .headers()
+                                .get(header.as_str())
+                                .and_then(|v| v.to_str().ok())
+                                .map(str::trim)
+                                .filter(|c| !c.is_empty())
+                                .and_then(|c| next_cursor(c.to_string()));
+                            (response_rows(resp, data_path_owned.as_deref(), &request).await?, next)
+                        }
+                        NextFrom::UrlAt(pointer) => {
+                            let doc: Value = serde_json::from_slice(&request.read_body(resp).await?)?;
+                            let next = doc
+                                .pointer(pointer)
+                                .and_then(Value::as_str)
+                                .filter(|u| !u.trim().is_empty())
+                                .and_then(|u| base.join(u.trim()).ok());
+                            let records = json_records(doc, data_path_owned.as_deref());
+                            (stream::iter(records.into_iter().map(Ok)).boxed(), next)
+                        }
+                        NextFrom::CursorAt(pointer) => {
+                            let doc: Value = serde_json::from_slice(&request.read_body(resp).await?)?;
+                            let next = match doc.pointer(pointer) {
+                                Some(Value::String(c)) if !c.trim().is_empty() => {
+                                    next_cursor(c.trim().to_string())
+                                }
+                                Some(Value::Number(n)) => next_cursor(n.to_string()),
+                                _ => None,
+                            };
+                            let records = json_records(doc, data_path_owned.as_deref());
+                            (stream::iter(records.into_iter().map(Ok)).boxed(), next)
+                        }
+                    })
+                }
+                .await;
+                let (rows, next) = match fetched {
+                    Ok(fetched) => fetched,
+                    Err(e) => {
+                        request.page_failed(page, &e).await;
+                        Err(e)?
+                    }
+                };
+                let mut page_stream = request.sequenced(page, rows);
+                while let Some(item) = page_stream.next().await {
+                    match item {
+                        Ok(v) => yield v,
+                        Err(e) => {
+                            request.page_failed(page, &e).await;
+                            Err(e)?
+                        }
+                    }
+                }
+                // The sink took every row of the page once it asks for more.
+                request.page_succeeded(page).await;
+
+                visited.insert(url.clone());
+                match next {
+                    Some(next) if !visited.contains(next.as_str()) => {
+                        debug!(page, next = %next, "following next page URL");
+                        url = next.into();
+                        // The next URL already holds the full query.
+                        query.clear();
+                        page += 1;
+                    }
+                    Some(next) => {
+                        warn!(next = %next, "next page URL or cursor was already visited; stopping");
+                        break;
+                    }
+                    None => break,
+                }
+            }
+        };
+
+        Ok(Box::pin(s))
+    }
+
+    /// Link-header, next-URL and cursor modes: follow next pages until the
+    /// server stops naming one.
+    pub async fn fetch_follow_next(
+        &self,
+        page_size: u64,
+        data_path: Option<&str>,
+        extra_params: Option<&[(String, String)]>,
+        writer: Arc<dyn PageWriter>,
+        write_mode: WriteMode,
+        config_retry: &crate::pipeline::Retry,
+    ) -> Result<FetchStats> {
+        let span = info_span!("fetch.follow_next.stream", source = %self.base_url);
+        let _g = span.enter();
+
+        let mut stats = FetchStats::new();
+        let json_stream = self
+            .follow_next_stream(page_size, data_path, extra_params, config_retry)
+            .await?;
+        self.write_streamed_page(1, json_stream, &*writer, &mut stats, write_mode)
+            .await?;
+        Ok(stats)
+    }
+
+    /// Custom mode: `paginator` picks each request from the previous
+    /// response until it returns `None`. A request already made ends the
+    /// walk, like a next link pointing back.
+    pub async fn fetch_custom(
+        &self,
+        paginator: Arc<dyn Paginator>,
+        data_path: Option<&str>,
+        extra_params: Option<&[(String, String)]>,
+        writer: Arc<dyn PageWriter>,
+        write_mode: WriteMode,
+        config_retry: &crate::pipeline::Retry,
+    ) -> Result<FetchStats> {
+        let span = info_span!("fetch.custom", source = %self.base_url);
+        let _g = span.enter();
+
+        let mut stats = FetchStats::new();
+        let mut visited = std::collections::HashSet::new();
+        let mut next = Some(paginator.first());
+        let mut page: u64 = 1;
+        writer.begin().await?;
+        while let Some(req) = next.take() {
+            if !visited.insert(req.clone()) {
+                warn!(page, request = ?req, "paginator repeated a request; stopping");
+                break;
+            }
+            let written = match self
+                .custom_page(
+                    &*paginator,
+                    &req,
+                    page,
+                    data_path,
+                    extra_params,
+                    config_retry,
+                )
+                .await
+            {
+                Ok((mut rows, following)) => {
+                    next = following;
+                    let n = rows.len();
+                    self.request.sequence_page(page, &mut rows);
+                    writer
+                        .write_page(page, rows, write_mode.clone())
+                        .await
+                        .map(|()| n)
+                }
+                Err(e) => Err(e),
+            };
+            match written {
+                Ok(n) => {
+                    stats.add_page(page, n);
+                    self.request.page_succeeded(page).await;
+                    debug!(
+                        page,
+                        rows = n,
+                        more = next.is_some(),
+                        "custom paginator page written"
+                    );
+                }
+                Err(e) => {
+                    self.request.page_failed(page, &e).await;
+                    stats.add_error(page);
+                    let _ = writer.on_page_error(page, e.to_string()).await;
+                    break;
+                }
+            }
+            page += 1;
+        }
+        writer.commit().await?;
+        Ok(stats)
+    }
+
+    /// Fetch one page of a custom walk: its rows and the request that
+    /// follows it, if any.
+    async fn custom_page(
+        &self,
+        paginator: &dyn Paginator,
+        req: &PageRequest,
+        page: u64,
+        data_path: Option<&str>,
+        extra_params: Option<&[(String, String)]>,
         config_retry: &crate::pipeline::Retry,
-    ) -> Result<FetchStats> {
-        let span = info_span!("fetch.limit_offset.stream", source = %self.base_url, limit = limit);
-        let _g = span.enter();
-
-        let mut stats = FetchStats::new();
-
-        // Build a single JsonStreamType over all pages
-        let json_stream = self
-            .limit_offset_stream(limit, data_path.as_deref(), extra_params, config_retry)
-            .await?;
-
-        // Now you can wrap it into your QueryResultStream abstraction
+    ) -> Result<(Vec<Value>, Option<PageRequest>)> {
+        let (url, mut query) = match &req.url {
+            Some(url) => (url.as_str(), Vec::new()),
+            None => (
+                self.base_url.as_str(),
+                extra_params.map(|p| p.to_vec()).unwrap_or_default(),
+            ),
+        };
+        query.extend(req.query.iter().cloned());
+        let resp = send_page_request_with_headers(
+            &self.client,
+            url,
+            &query,
+            &req.headers,
+            config_retry,
+            &self.request,
+        )
+        .await?;
+        let final_url = resp.url().clone();
+        let headers = resp.headers().clone();
+        let body: Value = serde_json::from_slice(&self.request.read_body(resp).await?)?;
+
+        let rows = json_records(body.clone(), data_path);
+        let following = paginator.next(
+            req,
+            &PageResponse {
+                page,
+                url: &final_url,
+                headers: &headers,
+                body: &body,
+                rows: rows.len(),
+            },
+        )?;
+        Ok((rows, following))
+    }
 
-        self.write_streamed_page(1, json_stream, &*writer, &mut stats, write_mode.clone())
+    /// Request the first page of a walk with a total hint like any later
+    /// page, reading a JSON one whole for its total.
+    async fn first_page(
+        &self,
+        query: &[(String, String)],
+        data_path: Option<&str>,
+        config_retry: &crate::pipeline::Retry,
+    ) -> Result<FirstPage> {
+        if self.request.format != ResponseFormat::Json {
+            let (fetched, last) = page_rows(
+                &self.client,
+                &self.base_url,
+                query,
+                data_path,
+                config_retry,
+                &self.request,
+                &self.stop,
+            )
             .await?;
-
-        // You don't have per-page stats here easily, but you could compute total_items
-        // inside write_stream, or wrap the stream to count rows.
-        Ok(stats)
+            return Ok(FirstPage::Other(fetched, last));
+        }
+        let conditional = conditional_response(
+            &self.client,
+            &self.base_url,
+            query,
+            config_retry,
+            &self.request,
+        )
+        .await?;
+        Ok(match conditional {
+            Conditional::Unchanged { rows } => {
+                FirstPage::Other(PageFetch::Unchanged { rows }, false)
+            }
+            Conditional::Fetched(resp, mark) => FirstPage::Json {
+                headers: resp.headers().clone(),
+                body: serde_json::from_slice(&self.request.read_body(*resp).await?)?,
+                mark,
+            },
+        })
     }
 
     /// PAGE/PER_PAGE mode.
+    #[allow(clippy::too_many_arguments)]
     pub async fn fetch_page_number(
         &self,
         per_page: u64,
         data_path: Option<&str>,
+        extra_params: Option<&[(String, String)]>,
         total_hint: Option<TotalHint>,
         writer: Arc<dyn PageWriter>,
         write_mode: WriteMode,
@@ -355,6 +1828,7 @@ impl PaginatedFetcher {
             Pagination::PageNumber {
                 page_param,
                 per_page_param,
+                ..
             } => (page_param.clone(), per_page_param.clone()),
             other => {
                 return Err(ApitapError::PaginationError(format!(
@@ -363,167 +1837,116 @@ impl PaginatedFetcher {
             }
         };
 
+        let extra = extra_params.map(|p| p.to_vec()).unwrap_or_default();
+        let query_for = |page: u64| {
+            let mut query = extra.clone();
+            query.push((page_param.clone(), page.to_string()));
+            query.push((per_page_param.clone(), per_page.to_string()));
+            query
+        };
+
         let span = info_span!("fetch.page_number", source = %self.base_url, per_page = per_page);
         let _g = span.enter();
 
-        writer.begin().await?;
+        // Pages before `start.page` were loaded by an earlier run.
+        let first_page = self.start.page.unwrap_or(1);
+        if first_page > 1 {
+            info!(first_page, "starting at a later page");
+        }
 
-        // First request as JSON (page=1)
-        let first_json: Value = self
-            .client
-            .get(&self.base_url)
-            .query(&[(page_param.as_str(), "1".to_string())])
-            .query(&[(per_page_param.as_str(), per_page.to_string())])
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
-            .await?;
+        writer.begin().await?;
 
+        let first = match self
+            .first_page(&query_for(first_page), data_path, config_retry)
+            .await
+        {
+            Ok(first) => first,
+            Err(e) => {
+                self.request.page_failed(first_page, &e).await;
+                return Err(e);
+            }
+        };
         let mut stats = FetchStats::new();
-
-        // Write page 1
-        let mut wrote_first = false;
-        if let Some(p) = data_path {
-            if let Some(arr) = first_json.pointer(p).and_then(|v| v.as_array()).cloned() {
-                let n = arr.len();
-                writer.write_page(1, arr, write_mode.clone()).await?;
-                stats.add_page(1, n);
-                wrote_first = true;
+        let (pages_opt, first_is_last, first_rows) = match first {
+            FirstPage::Json {
+                headers,
+                body,
+                mark,
+            } => {
+                let pages = total_hint
+                    .as_ref()
+                    .and_then(|hint| hint.pages_after_response(&headers, &body, per_page, 0));
+                let last = self.stop.flagged_last(&body);
+                let rows = json_records(body, data_path);
+                let n = self
+                    .write_held_page(
+                        first_page,
+                        rows,
+                        mark,
+                        &*writer,
+                        &mut stats,
+                        write_mode.clone(),
+                    )
+                    .await;
+                (pages, last, n)
             }
-        }
-        if !wrote_first {
-            let s = ndjson_stream_qs(
-                &self.client,
-                &self.base_url,
-                &[
-                    (page_param.clone(), "1".into()),
-                    (per_page_param.clone(), per_page.to_string()),
-                ],
-                data_path,
-                config_retry,
-            )
-            .await?;
-            self.write_streamed_page(1, s, &*writer, &mut stats, write_mode.clone())
-                .await?;
-        }
-
-        // Determine total pages
-        let pages_opt = match total_hint {
-            Some(TotalHint::Items { ref pointer }) => first_json
-                .pointer(pointer)
-                .and_then(|v| v.as_u64())
-                .map(|total_items| (total_items + per_page - 1) / per_page),
-            Some(TotalHint::Pages { ref pointer }) => {
-                first_json.pointer(pointer).and_then(|v| v.as_u64())
+            FirstPage::Other(fetched, last) => {
+                let n = self
+                    .write_fetched_page(
+                        first_page,
+                        fetched,
+                        &*writer,
+                        &mut stats,
+                        write_mode.clone(),
+                    )
+                    .await;
+                (None, last, n)
             }
-            None => None,
         };
+        let first_rows = self.request.page_done(first_page, first_rows).await?;
 
         if let Some(total_pages) = pages_opt {
-            // pages 2..=total_pages
-            let client = self.client.clone();
-            let url = self.base_url.clone();
-            let page_param_c = page_param.clone();
-            let per_page_param_c = per_page_param.clone();
-            let data_path_c = data_path.map(|s| s.to_string());
-            let writer_ref = Arc::clone(&writer);
-            let batch_size = self.batch_size;
-            let write_mode_clone = write_mode.clone();
-
-            stream::iter(2..=total_pages)
-                .map(move |page| {
-                    let client = client.clone();
-                    let url = url.clone();
-                    let page_param = page_param_c.clone();
-                    let per_page_param = per_page_param_c.clone();
-                    let data_path = data_path_c.clone();
-                    let writer = Arc::clone(&writer_ref);
-                    let write_mode_c = write_mode_clone.clone();
-
-                    async move {
-                        let mut s = match ndjson_stream_qs(
-                            &client,
-                            &url,
-                            &[
-                                (page_param, page.to_string()),
-                                (per_page_param, per_page.to_string()),
-                            ],
-                            data_path.as_deref(),
-                            config_retry,
-                        )
-                        .await
-                        {
-                            Ok(s) => s,
-                            Err(e) => {
-                                let _ = writer.on_page_error(page, e.to_string()).await;
-                                return;
-                            }
-                        };
-                        let mut buf = Vec::with_capacity(batch_size);
-                        while let Some(item) = s.next().await {
-                            match item {
-                                Ok(v) => {
-                                    buf.push(v);
-                                    if buf.len() == batch_size {
-                                        let out = std::mem::take(&mut buf);
-                                        if let Err(e) =
-                                            writer.write_page(page, out, write_mode_c.clone()).await
-                                        {
-                                            let _ = writer.on_page_error(page, e.to_string()).await;
-                                        }
-                                        trace!(page = page, batch = true, "wrote batch for page");
-                                    }
-                                }
-                                Err(e) => {
-                                    let _ = writer.on_page_error(page, e.to_string()).await;
-                                }
-                            }
-                        }
-                        if !buf.is_empty() {
-                            let out = std::mem::take(&mut buf);
-                            let cnt = out.len();
-                            if let Err(e) = writer.write_page(page, out, write_mode_c.clone()).await
-                            {
-                                let _ = writer.on_page_error(page, e.to_string()).await;
-                            } else {
-                                info!(page = page, items = cnt, source = %url, "wrote page remainder");
-                            }
-                        }
-                    }
-                })
-                .buffer_unordered(self.concurrency)
-                .collect::<Vec<_>>()
-                .await;
+            // the pages after the first, up to total_pages
+            self.fetch_pages_concurrently(
+                first_page + 1..=total_pages,
+                query_for,
+                data_path,
+                &writer,
+                &write_mode,
+                config_retry,
+                &mut stats,
+            )
+            .await;
         } else {
-            // Unknown total pages: fetch page=2,3,... until empty
-            let mut page = 2u64;
-            loop {
-                let s = match ndjson_stream_qs(
+            // Unknown total pages: fetch the next pages until one is empty
+            // or `stop_when` says it was the last
+            let mut page = first_page + 1;
+            let mut more = !first_is_last && self.stop.more(None, first_rows, per_page);
+            while more {
+                let (s, last) = match page_rows(
                     &self.client,
                     &self.base_url,
-                    &[
-                        (page_param.clone(), page.to_string()),
-                        (per_page_param.clone(), per_page.to_string()),
-                    ],
+                    &query_for(page),
                     data_path,
                     config_retry,
+                    &self.request,
+                    &self.stop,
                 )
                 .await
                 {
-                    Ok(s) => s,
+                    Ok(fetched) => fetched,
                     Err(e) => {
+                        self.request.page_failed(page, &e).await;
                         let _ = writer.on_page_error(page, e.to_string()).await;
                         break;
                     }
                 };
 
                 let wrote = self
-                    .write_streamed_page(page, s, &*writer, &mut stats, write_mode.clone())
-                    .await?;
-                if wrote == 0 {
-                    break;
-                } // stop on empty page
+                    .write_fetched_page(page, s, &*writer, &mut stats, write_mode.clone())
+                    .await;
+                let wrote = self.request.page_done(page, wrote).await?;
+                more = !last && self.stop.more(None, wrote, per_page);
                 page += 1;
             }
         }
@@ -532,16 +1955,231 @@ impl PaginatedFetcher {
         Ok(stats)
     }
 
+    /// Re-fetch specific pages (1-based), e.g. those that exhausted retries in
+    /// an earlier run. Works for page-number and limit/offset pagination.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn fetch_pages(
+        &self,
+        pages: &[u64],
+        per_page: u64,
+        data_path: Option<&str>,
+        extra_params: Option<&[(String, String)]>,
+        writer: Arc<dyn PageWriter>,
+        write_mode: WriteMode,
+        config_retry: &crate::pipeline::Retry,
+    ) -> Result<FetchStats> {
+        let mut stats = FetchStats::new();
+
+        for &page in pages {
+            let mut query = extra_params.map(|p| p.to_vec()).unwrap_or_default();
+            match &self.pagination_config {
+                Pagination::PageNumber {
+                    page_param,
+                    per_page_param,
+                    ..
+                } => {
+                    query.push((page_param.clone(), page.to_string()));
+                    query.push((per_page_param.clone(), per_page.to_string()));
+                }
+                Pagination::LimitOffset {
+                    limit_param,
+                    offset_param,
+                    ..
+                } => {
+                    query.push((limit_param.clone(), per_page.to_string()));
+                    query.push((
+                        offset_param.clone(),
+                        (page.saturating_sub(1) * per_page).to_string(),
+                    ));
+                }
+                other => {
+                    return Err(ApitapError::PaginationError(format!(
+                        "cannot re-fetch individual pages with {other:?}"
+                    )));
+                }
+            }
+
+            match conditional_rows(
+                &self.client,
+                &self.base_url,
+                &query,
+                data_path,
+                config_retry,
+                &self.request,
+            )
+            .await
+            {
+                Ok(fetched) => {
+                    let wrote = self
+                        .write_fetched_page(page, fetched, &*writer, &mut stats, write_mode.clone())
+                        .await;
+                    self.request.page_done(page, wrote).await?;
+                }
+                Err(e) => {
+                    self.request.page_failed(page, &e).await;
+                    stats.add_error(page);
+                    let _ = writer.on_page_error(page, e.to_string()).await;
+                }
+            }
+        }
+
+        Ok(stats)
+    }
+
     // -------------------- Private helpers ------------------------------------
 
+    /// Fetch `pages` with up to `concurrency` requests in flight, writing
+    /// each page in `batch_size` chunks as it streams in.
+    #[allow(clippy::too_many_arguments)]
+    async fn fetch_pages_concurrently<Q>(
+        &self,
+        pages: std::ops::RangeInclusive<u64>,
+        query_for: Q,
+        data_path: Option<&str>,
+        writer: &Arc<dyn PageWriter>,
+        write_mode: &WriteMode,
+        config_retry: &crate::pipeline::Retry,
+        stats: &mut FetchStats,
+    ) where
+        Q: Fn(u64) -> Vec<(String, String)>,
+    {
+        let results: Vec<(u64, PageOutcome)> = stream::iter(pages)
+            .map(|page| {
+                let query = query_for(page);
+                async move {
+                    let (s, mark) = match conditional_rows(
+                        &self.client,
+                        &self.base_url,
+                        &query,
+                        data_path,
+                        config_retry,
+                        &self.request,
+                    )
+                    .await
+                    {
+                        Ok(PageFetch::Rows(s, mark)) => (self.request.sequenced(page, s), mark),
+                        Ok(PageFetch::Unchanged { .. }) => {
+                            self.request.page_succeeded(page).await;
+                            return (page, PageOutcome::Unchanged);
+                        }
+                        Err(e) => {
+                            self.request.page_failed(page, &e).await;
+                            let _ = writer.on_page_error(page, e.to_string()).await;
+                            return (page, PageOutcome::Failed);
+                        }
+                    };
+                    let mut written = 0;
+                    // The first error of the page; the rest are only reported.
+                    let mut failure = None;
+                    let mut batches = batched(s, self.batch_size, self.flush_interval);
+                    while let Some(batch) = batches.next().await {
+                        let mut rows = Vec::with_capacity(batch.len());
+                        for item in batch {
+                            match item {
+                                Ok(v) => rows.push(v),
+                                Err(e) => {
+                                    let _ = writer.on_page_error(page, e.to_string()).await;
+                                    failure.get_or_insert(e);
+                                }
+                            }
+                        }
+                        if rows.is_empty() {
+                            continue;
+                        }
+                        let cnt = rows.len();
+                        match writer.write_page(page, rows, write_mode.clone()).await {
+                            Ok(()) => written += cnt,
+                            Err(e) => {
+                                let _ = writer.on_page_error(page, e.to_string()).await;
+                                failure.get_or_insert(e);
+                            }
+                        }
+                        trace!(page = page, items = cnt, "wrote batch for page");
+                    }
+                    if let Some(e) = failure {
+                        self.request.page_failed(page, &e).await;
+                        return (page, PageOutcome::Failed);
+                    }
+                    self.request.page_written(mark, written).await;
+                    self.request.page_succeeded(page).await;
+                    (page, PageOutcome::Written(written))
+                }
+            })
+            .buffer_unordered(self.concurrency)
+            .collect()
+            .await;
+
+        for (page, outcome) in results {
+            match outcome {
+                PageOutcome::Written(n) => stats.add_page(page, n),
+                PageOutcome::Unchanged => {}
+                PageOutcome::Failed => stats.add_error(page),
+            }
+        }
+    }
+
+    /// Write a JSON page read whole in one [`PageWriter::write_page`] call.
+    #[allow(clippy::too_many_arguments)]
+    async fn write_held_page(
+        &self,
+        page: u64,
+        mut rows: Vec<Value>,
+        mark: Option<PageMark>,
+        writer: &dyn PageWriter,
+        stats: &mut FetchStats,
+        write_mode: WriteMode,
+    ) -> Result<usize> {
+        self.request.sequence_page(page, &mut rows);
+        let n = rows.len();
+        writer.write_page(page, rows, write_mode).await?;
+        stats.add_page(page, n);
+        self.request.page_written(mark, n).await;
+        Ok(n)
+    }
+
+    /// Write a page from [`conditional_rows`]; an unchanged one is skipped
+    /// and counts the rows it held before.
+    async fn write_fetched_page(
+        &self,
+        page: u64,
+        fetched: PageFetch,
+        writer: &dyn PageWriter,
+        stats: &mut FetchStats,
+        write_mode: WriteMode,
+    ) -> Result<usize> {
+        match fetched {
+            PageFetch::Unchanged { rows } => Ok(rows),
+            PageFetch::Rows(s, mark) => {
+                let s = self.request.sequenced(page, s);
+                let wrote = self
+                    .write_streamed_page(page, s, writer, stats, write_mode)
+                    .await?;
+                self.request.page_written(mark, wrote).await;
+                Ok(wrote)
+            }
+        }
+    }
+
     async fn write_streamed_page(
         &self,
-        _page: u64,
+        page: u64,
         s: BoxStreamCustom<Result<Value>>,
         writer: &dyn PageWriter,
         stats: &mut FetchStats,
         write_mode: WriteMode,
     ) -> Result<usize> {
+        if let Some(interval) = self.flush_interval {
+            let mut written = 0;
+            let mut batches = batched(s, self.batch_size, Some(interval));
+            while let Some(batch) = batches.next().await {
+                let rows = batch.into_iter().collect::<Result<Vec<_>>>()?;
+                written += rows.len();
+                writer.write_page(page, rows, write_mode.clone()).await?;
+            }
+            stats.add_page(page, written);
+            return Ok(written);
+        }
+
         // Use atomic counter instead of Mutex for better performance
         let count = Arc::new(AtomicUsize::new(0));
         let count_clone = Arc::clone(&count);
@@ -554,37 +2192,39 @@ impl PaginatedFetcher {
         });
 
         writer
-            .write_page_stream(Box::pin(counted_stream), write_mode)
+            .write_numbered_page_stream(page, Box::pin(counted_stream), write_mode)
             .await?;
 
         // Get final count
         let final_count = count.load(Ordering::Relaxed);
-        stats.add_page(_page, final_count);
+        stats.add_page(page, final_count);
         Ok(final_count)
     }
 }
 
 // ============================== Stats =======================================
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct FetchStats {
     pub success_count: usize,
     pub error_count: usize,
     pub total_items: usize,
+    /// 429 responses waited out, and the time spent waiting.
+    pub throttled: Throttled,
+    /// Pages skipped because the server answered 304 Not Modified.
+    pub not_modified: usize,
+    /// Lines of a resumed NDJSON export written by an earlier run and not
+    /// read again.
+    pub resumed_lines: u64,
 }
 impl FetchStats {
     pub fn new() -> Self {
-        Self {
-            success_count: 0,
-            error_count: 0,
-            total_items: 0,
-        }
+        Self::default()
     }
     fn add_page(&mut self, _page: u64, items: usize) {
         self.success_count += 1;
         self.total_items += items;
     }
-    #[allow(dead_code)]
     fn add_error(&mut self, _page: u64) {
         self.error_count += 1;
     }
@@ -592,10 +2232,31 @@ impl FetchStats {
 
 // ===================== Example Writers (unchanged in spirit) =================
 
+/// One SELECT over the fetched page and the sink its rows go to.
+struct PageRoute {
+    table: String,
+    sql: String,
+    writer: Arc<dyn DataWriter>,
+    metadata: Option<MetadataColumns>,
+}
+
+impl PageRoute {
+    fn with_metadata(&self, df: DataFrame, page_number: u64) -> Result<DataFrame> {
+        match &self.metadata {
+            Some(metadata) => metadata.apply(df, page_number),
+            None => Ok(df),
+        }
+    }
+}
+
 pub struct DataFusionPageWriter {
     table_name: String,
-    sql: String,
-    final_writer: Arc<dyn DataWriter>,
+    routes: Vec<PageRoute>,
+    transforms: Arc<TransformChain>,
+    binary: Option<Arc<BinaryFields>>,
+    schemas: Option<Arc<SchemaCapture>>,
+    watermark: Option<Arc<WatermarkTracker>>,
+    batch_rows: Option<usize>,
 }
 impl DataFusionPageWriter {
     pub fn new(
@@ -603,17 +2264,82 @@ impl DataFusionPageWriter {
         sql: impl Into<String>,
         final_writer: Arc<dyn DataWriter>,
     ) -> Self {
+        let table_name = table_name.into();
+        Self::routed(table_name.clone()).with_route(table_name, sql, final_writer)
+    }
+
+    /// A writer without statements yet; add them with [`Self::with_route`].
+    /// Pages are registered as `table_name` for the SQL to select from.
+    pub fn routed(table_name: impl Into<String>) -> Self {
         Self {
             table_name: table_name.into(),
+            routes: Vec::new(),
+            transforms: Arc::new(TransformChain::new()),
+            binary: None,
+            schemas: None,
+            watermark: None,
+            batch_rows: None,
+        }
+    }
+
+    /// Run `sql` over every page and send its rows to `writer` as `table`.
+    pub fn with_route(
+        mut self,
+        table: impl Into<String>,
+        sql: impl Into<String>,
+        writer: Arc<dyn DataWriter>,
+    ) -> Self {
+        self.routes.push(PageRoute {
+            table: table.into(),
             sql: sql.into(),
-            final_writer,
+            writer,
+            metadata: None,
+        });
+        self
+    }
+
+    /// Lineage columns added to the rows of the route added last.
+    pub fn with_metadata_columns(mut self, metadata: MetadataColumns) -> Self {
+        if let Some(route) = self.routes.last_mut() {
+            route.metadata = Some(metadata);
         }
+        self
     }
-}
 
-#[async_trait]
-impl PageWriter for DataFusionPageWriter {
-    async fn write_page(
+    /// Row transforms applied to every fetched record before the SQL runs.
+    pub fn with_transforms(mut self, transforms: Arc<TransformChain>) -> Self {
+        self.transforms = transforms;
+        self
+    }
+
+    /// Base64 fields decoded (and possibly offloaded) before the row transforms.
+    pub fn with_binary_fields(mut self, binary: Arc<BinaryFields>) -> Self {
+        self.binary = Some(binary);
+        self
+    }
+
+    /// Note the columns the SQL sends to each table, for the run history.
+    pub fn with_schema_capture(mut self, schemas: Arc<SchemaCapture>) -> Self {
+        self.schemas = Some(schemas);
+        self
+    }
+
+    /// Note the highest cursor value among the rows, before any transform.
+    pub fn with_watermark(mut self, watermark: Arc<WatermarkTracker>) -> Self {
+        self.watermark = Some(watermark);
+        self
+    }
+
+    /// Write streamed pages `rows` at a time, each batch through the SQL on
+    /// its own, rather than holding a page for routes, binary fields or page
+    /// hooks (`--low-memory`). Page hooks then see one batch at a time.
+    pub fn with_batch_rows(mut self, rows: usize) -> Self {
+        self.batch_rows = Some(rows.max(1));
+        self
+    }
+
+    /// Rows already seen by the watermark through the SQL to the sinks.
+    async fn write_rows(
         &self,
         page_number: u64,
         data: Vec<Value>,
@@ -624,38 +2350,107 @@ impl PageWriter for DataFusionPageWriter {
         let span = info_span!("transform.load", table = %self.table_name, page = page_number, items = items);
         let _g = span.enter();
 
+        let Some(first) = self.routes.first() else {
+            return Ok(());
+        };
+        let mut data = data;
+        if let Some(binary) = &self.binary {
+            binary.apply_page(&mut data).await?;
+        }
+        let data = self.transforms.apply_page(data)?;
         let json_array = Value::Array(data);
-        let sdf = json_array.to_sql(&self.table_name, &self.sql).await?;
-        let result_stream = sdf.inner().to_stream().await?;
-        // Use structured fields for the downstream writer call
-        let table_page = format!("{}_page_{}", self.table_name, page_number);
-        self.final_writer
-            .write_stream(
-                QueryResultStream {
-                    table_name: table_page,
-                    data: result_stream,
-                },
-                write_mode,
-            )
-            .await?;
+        let sdf = json_array.to_sql(&self.table_name, &first.sql).await?;
+        for (idx, route) in self.routes.iter().enumerate() {
+            let df = if idx == 0 {
+                sdf.inner().clone()
+            } else {
+                sdf.sql(&route.sql).await?
+            };
+            let df = route.with_metadata(df, page_number)?;
+            if let Some(schemas) = &self.schemas {
+                schemas.record(&route.table, df.schema().fields());
+            }
+            let batches = df.execute_stream().await?;
+            // Use structured fields for the downstream writer call
+            let table_page = format!("{}_page_{}", route.table, page_number);
+            route
+                .writer
+                .write_batches(&table_page, batches, write_mode.clone())
+                .await?;
+        }
+        // Keep the registered page table alive until the streams are drained.
+        drop(sdf);
         Ok(())
     }
 
-    async fn write_page_stream(
+    /// Rows of one streamed page through the SQL to the sinks.
+    async fn stream_rows(
         &self,
+        page_number: u64,
         json_stream: Pin<Box<dyn Stream<Item = Result<serde_json::Value>> + Send>>,
-        _write_mode: WriteMode,
+        write_mode: WriteMode,
     ) -> Result<()> {
         debug!("starting streaming pipeline");
+
+        let json_stream = match &self.watermark {
+            Some(watermark) => {
+                let watermark = Arc::clone(watermark);
+                json_stream
+                    .inspect_ok(move |row| watermark.observe(row))
+                    .boxed()
+            }
+            None => json_stream,
+        };
+
+        if let Some(rows) = self.batch_rows {
+            let mut batches = batched(json_stream.boxed(), rows, None);
+            while let Some(batch) = batches.next().await {
+                let batch = batch.into_iter().collect::<Result<Vec<_>>>()?;
+                self.write_rows(page_number, batch, write_mode.clone())
+                    .await?;
+            }
+            return Ok(());
+        }
+
+        // Every route scans the page, which a one-shot stream can't serve;
+        // offloading binary fields is async, so it runs on whole pages too.
+        if self.routes.len() > 1 || self.binary.is_some() {
+            let rows: Vec<Value> = json_stream.try_collect().await?;
+            if rows.is_empty() {
+                info!("Stream empty. Exit");
+                return Ok(());
+            }
+            return self.write_rows(page_number, rows, write_mode).await;
+        }
+        let Some(route) = self.routes.first() else {
+            return Ok(());
+        };
+
         let ctx = get_shared_context().await;
 
         // Single-producer, single-consumer channel with increased buffer for better throughput
         let (tx, mut rx) = tokio::sync::mpsc::channel::<Result<serde_json::Value>>(8192);
 
+        // Page hooks need the whole page: buffer it and transform it up front.
+        let (json_stream, transforms) = if self.transforms.has_page_hooks() {
+            let rows: Vec<Value> = json_stream.try_collect().await?;
+            let rows = self.transforms.apply_page(rows)?;
+            let buffered: Pin<Box<dyn Stream<Item = Result<Value>> + Send>> =
+                Box::pin(stream::iter(rows.into_iter().map(Ok)));
+            (buffered, Arc::new(TransformChain::new()))
+        } else {
+            (json_stream, Arc::clone(&self.transforms))
+        };
+
         // Move the ONLY sender into the task so the channel closes when done.
         let _stream_task = tokio::spawn(async move {
             let mut pinned = json_stream;
             while let Some(item) = pinned.next().await {
+                let item = if transforms.is_empty() {
+                    item
+                } else {
+                    item.and_then(|row| transforms.apply(row))
+                };
                 // If the receiver is gone, stop.
                 if tx.send(item).await.is_err() {
                     break;
@@ -745,25 +2540,20 @@ impl PageWriter for DataFusionPageWriter {
         ctx.register_table(unique_table_name.clone(), Arc::new(table_provider))?;
 
         // Replace the original table name in SQL with the unique table name
-        let sql_with_unique_table = self.sql.replace(&self.table_name, &unique_table_name);
+        let sql_with_unique_table = route.sql.replace(&self.table_name, &unique_table_name);
 
-        let df = ctx.sql(&sql_with_unique_table).await?;
+        let df = route.with_metadata(ctx.sql(&sql_with_unique_table).await?, page_number)?;
+        if let Some(schemas) = &self.schemas {
+            schemas.record(&route.table, df.schema().fields());
+        }
 
         // Execute query and get streaming results
         let record_batch_stream = df.execute_stream().await?;
 
-        // Convert RecordBatch stream to JSON stream for the writer
-        let json_value_stream = convert_record_batch_to_json(record_batch_stream);
-
-        // Write the streaming results to the final destination
-        self.final_writer
-            .write_stream(
-                QueryResultStream {
-                    table_name: self.table_name.clone(),
-                    data: json_value_stream,
-                },
-                _write_mode,
-            )
+        // Hand the batches to the sink; row-oriented sinks convert to JSON themselves
+        route
+            .writer
+            .write_batches(&route.table, record_batch_stream, write_mode)
             .await?;
 
         // Clean up: deregister the table
@@ -771,8 +2561,43 @@ impl PageWriter for DataFusionPageWriter {
 
         Ok(())
     }
+}
+
+#[async_trait]
+impl PageWriter for DataFusionPageWriter {
+    async fn write_page(
+        &self,
+        page_number: u64,
+        data: Vec<Value>,
+        write_mode: WriteMode,
+    ) -> Result<()> {
+        if let Some(watermark) = &self.watermark {
+            watermark.observe_all(&data);
+        }
+        self.write_rows(page_number, data, write_mode).await
+    }
+
+    async fn write_page_stream(
+        &self,
+        json_stream: Pin<Box<dyn Stream<Item = Result<serde_json::Value>> + Send>>,
+        write_mode: WriteMode,
+    ) -> Result<()> {
+        self.stream_rows(0, json_stream, write_mode).await
+    }
+
+    async fn write_numbered_page_stream(
+        &self,
+        page_number: u64,
+        json_stream: Pin<Box<dyn Stream<Item = Result<serde_json::Value>> + Send>>,
+        write_mode: WriteMode,
+    ) -> Result<()> {
+        self.stream_rows(page_number, json_stream, write_mode).await
+    }
     async fn commit(&self) -> Result<()> {
-        self.final_writer.commit().await
+        for route in &self.routes {
+            route.writer.commit().await?;
+        }
+        Ok(())
     }
 }
 
@@ -813,8 +2638,8 @@ pub async fn infer_schema_and_create_factory(
     Ok((schema, factory))
 }
 
-#[allow(dead_code)]
-fn convert_record_batch_to_json(
+/// Convert a RecordBatch stream into a stream of JSON objects (one per row).
+pub fn convert_record_batch_to_json(
     mut stream: datafusion::execution::SendableRecordBatchStream,
 ) -> std::pin::Pin<Box<dyn Stream<Item = Result<serde_json::Value>> + Send + 'static>> {
     let json_stream = async_stream::try_stream! {
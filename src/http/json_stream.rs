@@ -0,0 +1,211 @@
+//! Streaming JSON responses row by row, for `--low-memory`.
+//!
+//! A regular JSON page is otherwise read whole and parsed into one tree
+//! before its rows are taken out, so a 50MB page costs well over 100MB. Here
+//! the body is deserialized as it arrives and each record under the
+//! `data_path` pointer is sent on as soon as it is complete; the rest of the
+//! document is skipped without being kept. The records are those the
+//! buffered path yields: the array at the pointer (or at the top level
+//! without one) as one row per element, any other value as a single row, and
+//! nothing when the pointer is missing or null. Concatenated documents
+//! (NDJSON served as JSON) are read one after another.
+
+use std::fmt;
+use std::io::{BufReader, Read};
+
+use bytes::{Buf, Bytes};
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use serde::de::value::MapAccessDeserializer;
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+
+use crate::errors::Result;
+
+/// Rows parsed ahead of the writer; bounds what a slow sink lets pile up.
+const ROWS_AHEAD: usize = 64;
+
+/// Parse a JSON byte stream into the records at `data_path`, one at a time.
+pub fn json_stream(
+    bytes: BoxStream<'static, std::io::Result<Bytes>>,
+    data_path: Option<&str>,
+) -> BoxStream<'static, Result<Value>> {
+    let path = pointer_segments(data_path.unwrap_or_default());
+    let s = async_stream::try_stream! {
+        let (tx, mut rx) = mpsc::channel(ROWS_AHEAD);
+        let body = BlockingBody {
+            chunks: bytes,
+            current: Bytes::new(),
+            handle: Handle::current(),
+        };
+        let parser = tokio::task::spawn_blocking(move || {
+            if let Err(e) = parse_documents(BufReader::new(body), &path, &tx) {
+                // Nothing to report to once the reader is gone.
+                let _ = tx.blocking_send(Err(e.into()));
+            }
+        });
+        while let Some(row) = rx.recv().await {
+            yield row?;
+        }
+        parser.await?;
+    };
+    s.boxed()
+}
+
+/// Unescaped segments of a JSON pointer such as `/data/items`.
+fn pointer_segments(pointer: &str) -> Vec<String> {
+    pointer
+        .split('/')
+        .skip(1)
+        .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+        .collect()
+}
+
+fn parse_documents(
+    body: impl Read,
+    path: &[String],
+    tx: &mpsc::Sender<Result<Value>>,
+) -> serde_json::Result<()> {
+    let mut de = serde_json::Deserializer::from_reader(body);
+    loop {
+        Records { path, tx }.deserialize(&mut de)?;
+        // Anything but trailing whitespace is the next document.
+        if de.end().is_ok() {
+            return Ok(());
+        }
+    }
+}
+
+/// Sends the records found under `path` of the value it deserializes.
+struct Records<'a> {
+    path: &'a [String],
+    tx: &'a mpsc::Sender<Result<Value>>,
+}
+
+impl Records<'_> {
+    fn send<E: de::Error>(&self, value: Value) -> std::result::Result<(), E> {
+        self.tx
+            .blocking_send(Ok(value))
+            .map_err(|_| E::custom("rows no longer read"))
+    }
+
+    /// A scalar is a record only when it is the value at the pointer.
+    fn scalar<E: de::Error>(self, value: Value) -> std::result::Result<(), E> {
+        if self.path.is_empty() {
+            self.send(value)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for Records<'_> {
+    type Value = ();
+
+    fn deserialize<D: de::Deserializer<'de>>(self, d: D) -> std::result::Result<(), D::Error> {
+        d.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Records<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a JSON document")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<(), A::Error> {
+        let Some((key, rest)) = self.path.split_first() else {
+            let value = Value::deserialize(MapAccessDeserializer::new(map))?;
+            return self.send(value);
+        };
+        while let Some(name) = map.next_key::<String>()? {
+            if name == *key {
+                map.next_value_seed(Records {
+                    path: rest,
+                    tx: self.tx,
+                })?;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<(), A::Error> {
+        let Some((index, rest)) = self.path.split_first() else {
+            while let Some(value) = seq.next_element::<Value>()? {
+                self.send(value)?;
+            }
+            return Ok(());
+        };
+        let mut i = 0usize;
+        loop {
+            let found = if index.parse() == Ok(i) {
+                seq.next_element_seed(Records {
+                    path: rest,
+                    tx: self.tx,
+                })?
+            } else {
+                seq.next_element::<IgnoredAny>()?.map(|_| ())
+            };
+            if found.is_none() {
+                return Ok(());
+            }
+            i += 1;
+        }
+    }
+
+    fn visit_unit<E: de::Error>(self) -> std::result::Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> std::result::Result<(), E> {
+        self.scalar(Value::from(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> std::result::Result<(), E> {
+        self.scalar(Value::from(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> std::result::Result<(), E> {
+        self.scalar(Value::from(v))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> std::result::Result<(), E> {
+        self.scalar(Value::from(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<(), E> {
+        self.scalar(Value::from(v))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> std::result::Result<(), E> {
+        self.scalar(Value::from(v))
+    }
+}
+
+/// The response body as a blocking reader, for serde_json on a blocking
+/// thread; each read waits on the next chunk only when the last is used up.
+struct BlockingBody {
+    chunks: BoxStream<'static, std::io::Result<Bytes>>,
+    current: Bytes,
+    handle: Handle,
+}
+
+impl Read for BlockingBody {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.current.is_empty() {
+            match self.handle.block_on(self.chunks.next()) {
+                Some(chunk) => self.current = chunk?,
+                None => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.current.len());
+        buf[..n].copy_from_slice(&self.current[..n]);
+        self.current.advance(n);
+        Ok(n)
+    }
+}
@@ -0,0 +1,236 @@
+//! JWT bearer assertions (RFC 7523), as used by Google service accounts and
+//! the Salesforce JWT bearer flow.
+//!
+//! A JWT is signed with a private key and exchanged at the token endpoint
+//! for an access token, which [`TokenManager`](crate::http::oauth2::TokenManager)
+//! caches and renews like any OAuth2 token:
+//!
+//! ```yaml
+//! auth:
+//!   kind: jwt
+//!   service_account_file: /secrets/sa.json   # Google key file: key, issuer, token URL
+//!   scopes: [https://www.googleapis.com/auth/analytics.readonly]
+//! ```
+//!
+//! ```yaml
+//! auth:
+//!   kind: jwt
+//!   token_url: https://login.salesforce.com/services/oauth2/token
+//!   private_key_file: /secrets/salesforce.pem
+//!   issuer: 3MVG9...            # connected app consumer key
+//!   subject: etl@example.com
+//!   audience: https://login.salesforce.com
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::time::Duration;
+
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::errors::{ApitapError, Result};
+use crate::http::auth::env_credential;
+use crate::pipeline::freshness::parse_duration;
+
+/// Assertions are valid this long unless `lifetime` says otherwise; Google
+/// accepts no more.
+const DEFAULT_LIFETIME: Duration = Duration::from_secs(3600);
+
+const KEY_SOURCES: &str = "service_account_file, private_key_file or private_key_env";
+
+fn default_algorithm() -> Algorithm {
+    Algorithm::RS256
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JwtConfig {
+    /// Google service-account key file; supplies the private key, issuer,
+    /// key id and token URL.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_account_file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_url: Option<String>,
+    /// PEM file holding the private key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub private_key_file: Option<String>,
+    /// Environment variable holding the PEM private key (or the secret, for
+    /// `HS*` algorithms).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub private_key_env: Option<String>,
+    #[serde(default = "default_algorithm")]
+    pub algorithm: Algorithm,
+    /// `iss` claim.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+    /// `sub` claim, e.g. the user to act as.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    /// `aud` claim; defaults to the token URL.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audience: Option<String>,
+    /// Sent space-separated in the `scope` claim.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<String>,
+    /// `kid` header.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+    /// How long each assertion is valid, e.g. `5m`; defaults to an hour.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lifetime: Option<String>,
+    /// Further claims, added as given.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub claims: BTreeMap<String, Value>,
+    /// Renew the access token at least this often (e.g. `45m`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_every: Option<String>,
+}
+
+impl JwtConfig {
+    pub fn refresh_every(&self) -> Result<Option<Duration>> {
+        self.refresh_every
+            .as_deref()
+            .map(parse_duration)
+            .transpose()
+    }
+}
+
+/// The fields of a Google service-account key file that are used.
+#[derive(Debug, Deserialize)]
+struct ServiceAccountKey {
+    client_email: String,
+    private_key: String,
+    #[serde(default)]
+    private_key_id: Option<String>,
+    #[serde(default)]
+    token_uri: Option<String>,
+}
+
+/// Signs assertions for one token endpoint.
+pub struct JwtAssertion {
+    token_url: String,
+    key: EncodingKey,
+    header: Header,
+    issuer: String,
+    subject: Option<String>,
+    audience: String,
+    scope: Option<String>,
+    lifetime: Duration,
+    claims: BTreeMap<String, Value>,
+}
+
+impl Debug for JwtAssertion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JwtAssertion")
+            .field("token_url", &self.token_url)
+            .field("algorithm", &self.header.alg)
+            .field("issuer", &self.issuer)
+            .field("subject", &self.subject)
+            .finish_non_exhaustive()
+    }
+}
+
+fn config_error(what: impl std::fmt::Display) -> ApitapError {
+    ApitapError::ConfigError(format!("jwt auth: {what}"))
+}
+
+fn encoding_key(algorithm: Algorithm, key: &str) -> Result<EncodingKey> {
+    let key = match algorithm {
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+            Ok(EncodingKey::from_secret(key.as_bytes()))
+        }
+        Algorithm::ES256 | Algorithm::ES384 => EncodingKey::from_ec_pem(key.as_bytes()),
+        Algorithm::EdDSA => EncodingKey::from_ed_pem(key.as_bytes()),
+        _ => EncodingKey::from_rsa_pem(key.as_bytes()),
+    };
+    key.map_err(|e| config_error(format!("invalid {algorithm:?} private key: {e}")))
+}
+
+impl JwtAssertion {
+    /// Reads the key (and the service-account file, if any).
+    pub fn new(config: &JwtConfig) -> Result<Self> {
+        let account = config
+            .service_account_file
+            .as_deref()
+            .map(|path| -> Result<ServiceAccountKey> {
+                let text = std::fs::read_to_string(path)
+                    .map_err(|e| config_error(format!("cannot read {path}: {e}")))?;
+                serde_json::from_str(&text)
+                    .map_err(|e| config_error(format!("{path} is not a service-account key: {e}")))
+            })
+            .transpose()?;
+
+        let pem = match (&config.private_key_file, &config.private_key_env, &account) {
+            (Some(path), None, None) => std::fs::read_to_string(path)
+                .map_err(|e| config_error(format!("cannot read {path}: {e}")))?,
+            (None, Some(env), None) => env_credential(env, "jwt private key")?,
+            (None, None, Some(account)) => account.private_key.clone(),
+            (None, None, None) => return Err(config_error(format!("needs one of {KEY_SOURCES}"))),
+            _ => return Err(config_error(format!("takes only one of {KEY_SOURCES}"))),
+        };
+
+        let token_url = config
+            .token_url
+            .clone()
+            .or_else(|| account.as_ref().and_then(|a| a.token_uri.clone()))
+            .ok_or_else(|| config_error("token_url is required"))?;
+        let issuer = config
+            .issuer
+            .clone()
+            .or_else(|| account.as_ref().map(|a| a.client_email.clone()))
+            .ok_or_else(|| config_error("issuer is required"))?;
+        let mut header = Header::new(config.algorithm);
+        header.kid = config
+            .key_id
+            .clone()
+            .or_else(|| account.as_ref().and_then(|a| a.private_key_id.clone()));
+        let scope = config.scopes.join(" ");
+
+        Ok(Self {
+            key: encoding_key(config.algorithm, &pem)?,
+            header,
+            issuer,
+            subject: config.subject.clone(),
+            audience: config.audience.clone().unwrap_or_else(|| token_url.clone()),
+            scope: (!scope.is_empty()).then_some(scope),
+            lifetime: match &config.lifetime {
+                Some(lifetime) => parse_duration(lifetime)?,
+                None => DEFAULT_LIFETIME,
+            },
+            claims: config.claims.clone(),
+            token_url,
+        })
+    }
+
+    pub fn token_url(&self) -> &str {
+        &self.token_url
+    }
+
+    /// The claims of an assertion issued now.
+    pub fn claims(&self) -> Map<String, Value> {
+        let now = chrono::Utc::now().timestamp();
+        let mut claims: Map<String, Value> = self
+            .claims
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        claims.insert("iss".into(), self.issuer.clone().into());
+        if let Some(subject) = &self.subject {
+            claims.insert("sub".into(), subject.clone().into());
+        }
+        claims.insert("aud".into(), self.audience.clone().into());
+        if let Some(scope) = &self.scope {
+            claims.insert("scope".into(), scope.clone().into());
+        }
+        claims.insert("iat".into(), now.into());
+        claims.insert("exp".into(), (now + self.lifetime.as_secs() as i64).into());
+        claims
+    }
+
+    /// A freshly signed assertion.
+    pub fn sign(&self) -> Result<String> {
+        jsonwebtoken::encode(&self.header, &self.claims(), &self.key)
+            .map_err(|e| ApitapError::PipelineError(format!("jwt signing failed: {e}")))
+    }
+}
@@ -0,0 +1,235 @@
+//! Caps on what a misbehaving endpoint can make the process hold.
+//!
+//! ```yaml
+//! fetch:
+//!   max_body_size: 256MB     # fail a page whose body grows past this
+//!   max_line_length: 16MB    # fail an NDJSON line longer than this
+//!   max_in_flight: 1GB       # bodies buffered at once across a source's pages
+//! ```
+//!
+//! Set under the top-level `fetch:` or on a source, like the page size. The
+//! body size counts decoded bytes, so a small compressed body expanding to
+//! gigabytes is caught too. `max_in_flight` covers JSON bodies read whole
+//! before parsing (the default, without `--low-memory`): each holds its share
+//! of the budget until its rows are written, and a page that would take the
+//! source over it waits for others to be written; only a single body larger
+//! than the whole budget fails. Nothing is capped by default.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use tokio::sync::Notify;
+use tracing::debug;
+
+use crate::errors::{ApitapError, Result};
+use crate::http::bandwidth::parse_size;
+use crate::pipeline::run::FetchSettings;
+
+/// The resolved caps of one source; `None` is unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResponseLimits {
+    pub max_body_size: Option<u64>,
+    pub max_line_length: Option<usize>,
+    pub max_in_flight: Option<u64>,
+}
+
+impl ResponseLimits {
+    /// These limits with the sizes `settings` sets replacing them.
+    pub fn with_overrides(&self, settings: &FetchSettings) -> Result<Self> {
+        let size = |text: &Option<String>, default: Option<u64>, key: &str| match text {
+            Some(text) => parse_size(text).map(Some).map_err(|_| {
+                ApitapError::ConfigError(format!("{key} must be a size such as 16MB, got '{text}'"))
+            }),
+            None => Ok(default),
+        };
+        Ok(Self {
+            max_body_size: size(&settings.max_body_size, self.max_body_size, "max_body_size")?,
+            max_line_length: size(
+                &settings.max_line_length,
+                self.max_line_length.map(|n| n as u64),
+                "max_line_length",
+            )?
+            .map(|n| usize::try_from(n).unwrap_or(usize::MAX)),
+            max_in_flight: size(&settings.max_in_flight, self.max_in_flight, "max_in_flight")?,
+        })
+    }
+
+    /// Fail before reading a body whose announced length is over the cap.
+    pub fn check_content_length(&self, length: Option<u64>) -> Result<()> {
+        match (length, self.max_body_size) {
+            (Some(length), Some(max)) if length > max => {
+                Err(ApitapError::PipelineError(body_too_large(max)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// `body`, failing once more than `max_body_size` bytes came through.
+    pub fn cap_body(
+        &self,
+        body: BoxStream<'static, std::io::Result<Bytes>>,
+    ) -> BoxStream<'static, std::io::Result<Bytes>> {
+        let Some(max) = self.max_body_size else {
+            return body;
+        };
+        let mut seen = 0u64;
+        body.map(move |chunk| {
+            let chunk = chunk?;
+            seen += chunk.len() as u64;
+            if seen > max {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    body_too_large(max),
+                ));
+            }
+            Ok(chunk)
+        })
+        .boxed()
+    }
+}
+
+fn body_too_large(max: u64) -> String {
+    format!("response body is larger than max_body_size ({max} bytes)")
+}
+
+/// Bytes of response bodies a source holds at once, shared by its
+/// concurrent pages.
+#[derive(Debug)]
+pub struct MemoryBudget {
+    limit: u64,
+    state: Mutex<BudgetState>,
+    changed: Notify,
+}
+
+#[derive(Debug, Default)]
+struct BudgetState {
+    used: u64,
+    /// Reservations holding any bytes.
+    holders: usize,
+    /// Of those, the ones waiting for more.
+    waiting: usize,
+}
+
+impl MemoryBudget {
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            state: Mutex::new(BudgetState::default()),
+            changed: Notify::new(),
+        }
+    }
+
+    /// Bytes currently reserved.
+    pub fn used(&self) -> u64 {
+        self.lock().used
+    }
+
+    /// An empty reservation, grown as a body is read.
+    pub fn reservation(self: &Arc<Self>) -> Reservation {
+        Reservation {
+            budget: Arc::clone(self),
+            bytes: 0,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BudgetState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A share of a [`MemoryBudget`], given back when dropped.
+#[derive(Debug)]
+pub struct Reservation {
+    budget: Arc<MemoryBudget>,
+    bytes: u64,
+}
+
+impl Reservation {
+    /// Take `bytes` more, waiting for other pages to give theirs back if the
+    /// budget has not that much left. Fails only when this body alone would
+    /// be larger than the whole budget.
+    ///
+    /// Bodies still being read hold what they have while they wait, so if
+    /// every body holding a share is waiting, none could finish; one of them
+    /// is then let past the limit instead.
+    pub async fn grow(&mut self, bytes: u64) -> Result<()> {
+        let budget = Arc::clone(&self.budget);
+        let limit = budget.limit;
+        if self.bytes.saturating_add(bytes) > limit {
+            return Err(ApitapError::PipelineError(format!(
+                "response body is larger than max_in_flight ({limit} bytes)"
+            )));
+        }
+
+        let mut waiting = Waiting {
+            budget: &budget,
+            registered: false,
+        };
+        loop {
+            let changed = budget.changed.notified();
+            tokio::pin!(changed);
+            changed.as_mut().enable();
+            {
+                let mut state = budget.lock();
+                let holding = self.bytes > 0;
+                let fits = state.used + bytes <= limit;
+                let stalled = holding && waiting.registered && state.waiting == state.holders;
+                if fits || stalled {
+                    if stalled && !fits {
+                        debug!(
+                            used = state.used,
+                            limit, "every body in flight is waiting, going over max_in_flight"
+                        );
+                    }
+                    if waiting.registered {
+                        state.waiting -= 1;
+                        waiting.registered = false;
+                    }
+                    if !holding && bytes > 0 {
+                        state.holders += 1;
+                    }
+                    state.used += bytes;
+                    self.bytes += bytes;
+                    return Ok(());
+                }
+                if holding && !waiting.registered {
+                    state.waiting += 1;
+                    waiting.registered = true;
+                    // May leave every holder waiting: let them re-check.
+                    budget.changed.notify_waiters();
+                }
+            }
+            changed.await;
+        }
+    }
+}
+
+/// Counts a holder as waiting until its `grow` returns or is dropped.
+struct Waiting<'a> {
+    budget: &'a MemoryBudget,
+    registered: bool,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        if self.registered {
+            self.budget.lock().waiting -= 1;
+        }
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if self.bytes == 0 {
+            return;
+        }
+        {
+            let mut state = self.budget.lock();
+            state.used -= self.bytes;
+            state.holders -= 1;
+        }
+        self.budget.changed.notify_waiters();
+    }
+}
@@ -0,0 +1,87 @@
+//! `Link` response headers (RFC 8288, formerly RFC 5988).
+//!
+//! GitHub, GitLab and many other REST APIs page by sending the URL of the
+//! next page in a header instead of a cursor in the body:
+//!
+//! ```text
+//! Link: <https://api.github.com/repos/o/r/issues?page=2>; rel="next",
+//!       <https://api.github.com/repos/o/r/issues?page=5>; rel="last"
+//! ```
+
+use reqwest::header::{HeaderMap, LINK};
+use reqwest::Url;
+
+/// One `<uri>; param=value; ...` entry of a `Link` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkValue {
+    pub uri: String,
+    /// Relation types, lowercased; `rel` may list several (`rel="next last"`).
+    pub rels: Vec<String>,
+}
+
+/// Parse every entry of one `Link` header value; malformed entries are skipped.
+pub fn parse_link_header(value: &str) -> Vec<LinkValue> {
+    let mut links = Vec::new();
+    let mut rest = value;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+        let Some(after) = rest.strip_prefix('<') else {
+            // Not at a link: skip to the next entry.
+            match split_outside_quotes(rest, ',') {
+                (_, Some(next)) => {
+                    rest = next;
+                    continue;
+                }
+                (_, None) => break,
+            }
+        };
+        let Some((uri, tail)) = after.split_once('>') else {
+            break;
+        };
+        let (params, next) = split_outside_quotes(tail, ',');
+        let mut rels = Vec::new();
+        for param in params.split(';') {
+            let Some((name, value)) = param.split_once('=') else {
+                continue;
+            };
+            if name.trim().eq_ignore_ascii_case("rel") {
+                let value = value.trim().trim_matches('"');
+                rels.extend(value.split_whitespace().map(str::to_ascii_lowercase));
+            }
+        }
+        links.push(LinkValue {
+            uri: uri.trim().to_string(),
+            rels,
+        });
+        match next {
+            Some(next) => rest = next,
+            None => break,
+        }
+    }
+    links
+}
+
+/// Split at the first `sep` outside double quotes.
+fn split_outside_quotes(s: &str, sep: char) -> (&str, Option<&str>) {
+    let mut quoted = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c == sep && !quoted => return (&s[..i], Some(&s[i + c.len_utf8()..])),
+            _ => {}
+        }
+    }
+    (s, None)
+}
+
+/// The `rel="next"` target among all `Link` headers, resolved against
+/// `base` (the URL of the response) when relative.
+pub fn next_link(headers: &HeaderMap, base: &Url) -> Option<Url> {
+    headers
+        .get_all(LINK)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(parse_link_header)
+        .find(|link| link.rels.iter().any(|rel| rel == "next"))
+        .and_then(|link| base.join(&link.uri).ok())
+}
@@ -1,27 +1,73 @@
+pub mod auth;
+pub mod bandwidth;
+pub mod body;
+pub mod client;
+pub mod csv_stream;
+pub mod decompress;
+pub mod deprecation;
+pub mod failure_samples;
 pub mod fetcher;
+pub mod json_stream;
+pub mod jwt;
+pub mod limits;
+pub mod link;
+pub mod ndjson_export;
+pub mod oauth2;
+pub mod paginator;
+pub mod rate_limit;
+pub mod session;
+pub mod signing;
+pub mod sse_stream;
+pub mod throttle;
+pub mod usage;
+pub mod vcr;
+pub mod xml_stream;
+use std::sync::Arc;
+
 use datafusion::common::HashMap;
+use reqwest::cookie::Jar;
 use reqwest::Client;
+use tracing::warn;
+
+use crate::errors::{ApitapError, Result};
+use crate::http::client::ClientSettings;
 
 #[derive(Clone)]
 pub struct Http {
     url: String,
-    params: Option<HashMap<String, String>>,
+    /// Query parameters in the order added; a key may repeat.
+    params: Vec<(String, String)>,
     headers: Option<HashMap<String, String>>,
-    bearer_auth: Option<String>,
+    /// Header names whose values are kept out of debug output.
+    secret_headers: Vec<String>,
+    authorization: Option<String>,
+    proxy: Option<reqwest::Proxy>,
+    identity: Option<reqwest::Identity>,
+    root_certificates: Vec<reqwest::Certificate>,
+    settings: ClientSettings,
+    /// Cookies set by responses, sent back on later requests.
+    cookie_jar: Option<Arc<Jar>>,
 }
 
 impl Http {
     pub fn new(url: impl Into<String>) -> Self {
         Self {
             url: url.into(),
-            params: None,
+            params: Vec::new(),
             headers: None,
-            bearer_auth: None,
+            secret_headers: Vec::new(),
+            authorization: None,
+            proxy: None,
+            identity: None,
+            root_certificates: Vec::new(),
+            settings: ClientSettings::default(),
+            cookie_jar: None,
         }
     }
+    /// Add a query parameter. Adding a key again sends it again, as in
+    /// `tag=a&tag=b`; name it `tag[]` for array-style parameters.
     pub fn param(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
-        let map = self.params.get_or_insert_with(HashMap::new);
-        map.insert(key.into(), value.into());
+        self.params.push((key.into(), value.into()));
         self
     }
     pub fn header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
@@ -29,66 +75,152 @@ impl Http {
         map.insert(key.into(), value.into());
         self
     }
+    /// Like [`header`](Self::header), for a value holding a credential.
+    pub fn secret_header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let key = key.into();
+        self.secret_headers.push(key.clone());
+        self.header(key, value)
+    }
     pub fn bearer_auth(mut self, token: impl Into<String>) -> Self {
-        self.bearer_auth = Some(token.into());
+        self.authorization = Some(format!("Bearer {}", token.into()));
+        self
+    }
+    pub fn basic_auth(self, username: &str, password: &str) -> Self {
+        use base64::Engine;
+        let encoded =
+            base64::engine::general_purpose::STANDARD.encode(format!("{username}:{password}"));
+        self.authorization(format!("Basic {encoded}"))
+    }
+    /// Send this `Authorization` value (scheme and credentials) on every
+    /// request.
+    pub fn authorization(mut self, value: impl Into<String>) -> Self {
+        self.authorization = Some(value.into());
+        self
+    }
+    /// Send requests through this proxy. Without one, `HTTP_PROXY`,
+    /// `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY` from the environment apply.
+    pub fn proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+    /// Present this client certificate (mTLS).
+    pub fn identity(mut self, identity: reqwest::Identity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+    /// Trust this CA on top of the system roots.
+    pub fn root_certificate(mut self, cert: reqwest::Certificate) -> Self {
+        self.root_certificates.push(cert);
+        self
+    }
+    /// Timeouts, pooling and protocol of the client.
+    pub fn settings(mut self, settings: ClientSettings) -> Self {
+        self.settings = settings;
         self
     }
-    pub fn build_client(&self) -> Client {
+    /// Keep cookies in `jar` across the client's requests, e.g. a session
+    /// cookie set by a login request.
+    pub fn cookie_jar(mut self, jar: Arc<Jar>) -> Self {
+        self.cookie_jar = Some(jar);
+        self
+    }
+    /// Build the client, failing with a [`ApitapError::ConfigError`] when
+    /// reqwest rejects the settings, e.g. a TLS backend it cannot load.
+    pub fn build_client(&self) -> Result<Client> {
         let mut headers = reqwest::header::HeaderMap::new();
 
         if let Some(header_map) = &self.headers {
             for (key, value) in header_map {
-                if let (Ok(name), Ok(val)) = (
+                if let (Ok(name), Ok(mut val)) = (
                     reqwest::header::HeaderName::from_bytes(key.as_bytes()),
                     reqwest::header::HeaderValue::from_str(value),
                 ) {
+                    val.set_sensitive(self.secret_headers.contains(key));
                     headers.insert(name, val);
                 }
             }
         }
-        if let Some(token) = &self.bearer_auth {
-            match reqwest::header::HeaderValue::from_str(&format!("Bearer {}", token)) {
-                Ok(header_value) => {
+        if let Some(authorization) = &self.authorization {
+            match reqwest::header::HeaderValue::from_str(authorization) {
+                Ok(mut header_value) => {
+                    header_value.set_sensitive(true);
                     headers.insert(reqwest::header::AUTHORIZATION, header_value);
                 }
                 Err(_) => {
-                    // Token contains invalid header characters, skip adding the header
+                    // Credentials contain invalid header characters, skip adding the header
                     // This prevents panic while still allowing the client to be built
-                    eprintln!("Warning: Invalid characters in bearer token, skipping authorization header");
+                    warn!("invalid characters in authorization, skipping authorization header");
                 }
             }
         }
 
-        Client::builder()
+        let mut builder = Client::builder();
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy.clone());
+        }
+        // Identities are loaded from PEM, which needs the rustls backend.
+        if self.identity.is_some() || !self.root_certificates.is_empty() {
+            builder = builder.use_rustls_tls();
+        }
+        if let Some(identity) = &self.identity {
+            builder = builder.identity(identity.clone());
+        }
+        for cert in &self.root_certificates {
+            builder = builder.add_root_certificate(cert.clone());
+        }
+
+        if let Some(jar) = &self.cookie_jar {
+            builder = builder.cookie_provider(Arc::clone(jar));
+        }
+
+        if self.settings.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
+
+        builder
             .default_headers(headers)
             // ===== HTTP Connection Pooling & Keep-Alive Optimizations =====
             // Based on flamegraph analysis: reduce TLS handshake overhead (6.48% CPU time)
             // Enable HTTP connection reuse and configure pool settings
-            .pool_max_idle_per_host(10) // Keep up to 10 idle connections per host
-            .pool_idle_timeout(Some(std::time::Duration::from_secs(90))) // Keep connections alive for 90s
-            .timeout(std::time::Duration::from_secs(30)) // Request timeout
-            .connect_timeout(std::time::Duration::from_secs(10)) // Connection timeout
-            .tcp_keepalive(Some(std::time::Duration::from_secs(60))) // TCP keepalive
+            .pool_max_idle_per_host(self.settings.pool_max_idle_per_host)
+            .pool_idle_timeout(Some(self.settings.pool_idle_timeout))
+            .timeout(self.settings.request_timeout)
+            .connect_timeout(self.settings.connect_timeout)
+            .tcp_keepalive(self.settings.tcp_keepalive)
+            // Decode compressed bodies (and advertise it with Accept-Encoding)
+            .gzip(true)
+            .deflate(true)
+            .brotli(true)
+            .zstd(true)
             // TLS session resumption is enabled by default in reqwest
             .build()
-            .unwrap_or_else(|_| Client::new())
+            .map_err(|e| ApitapError::ConfigError(format!("cannot build HTTP client: {e}")))
     }
+    /// The URL with the query parameters appended, percent-encoded. Any
+    /// query already in the URL is kept.
     pub fn get_url(&self) -> String {
-        if let Some(params) = &self.params {
-            // keep any base params (we'll override limit/offset at call time)
-            let query: Vec<String> = params
-                .iter()
-                .filter(|(k, _)| k.as_str() != "page") // ignore any page param
-                .map(|(k, v)| format!("{}={}", k, v))
-                .collect();
-
-            if query.is_empty() {
-                self.url.clone()
-            } else {
-                format!("{}?{}", self.url, query.join("&"))
+        // keep any base params (we'll override limit/offset at call time)
+        let mut params = self
+            .params
+            .iter()
+            .filter(|(k, _)| k.as_str() != "page") // ignore any page param
+            .peekable();
+        if params.peek().is_none() {
+            return self.url.clone();
+        }
+        match url::Url::parse(&self.url) {
+            Ok(mut url) => {
+                url.query_pairs_mut().extend_pairs(params);
+                url.to_string()
+            }
+            // Not a URL the request could be sent to anyway; leave the
+            // error to whoever parses it.
+            Err(_) => {
+                let query = url::form_urlencoded::Serializer::new(String::new())
+                    .extend_pairs(params)
+                    .finish();
+                format!("{}?{}", self.url, query)
             }
-        } else {
-            self.url.clone()
         }
     }
 }
@@ -0,0 +1,298 @@
+//! Single-response NDJSON exports, checkpointed as they are read.
+//!
+//! Bulk export endpoints often return one enormous NDJSON body instead of
+//! pages. With `ndjson: { checkpoint_every: N }` the body is written in
+//! chunks of `N` lines and the progress saved after each one (see
+//! [`crate::pipeline::download_state`]). A resumed run asks for the rest of
+//! the body with `Range: bytes=...` guarded by `If-Range`; when the server
+//! sends the whole body anyway, the lines already written are skipped, or
+//! the download starts over if the export changed in between.
+
+use std::time::Duration;
+
+use futures::{stream, StreamExt};
+use reqwest::header::{
+    HeaderMap, ACCEPT_RANGES, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG,
+    IF_RANGE, LAST_MODIFIED, RANGE,
+};
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, info, info_span, warn};
+
+use crate::errors::{ApitapError, Result};
+use crate::http::decompress::Encoding;
+use crate::http::fetcher::{push_records, FetchStats, PageWriter, RequestOptions};
+use crate::pipeline::download_state::DownloadCheckpoint;
+use crate::utils::http_retry;
+use crate::writer::WriteMode;
+
+/// Per-request timeout: an export may take hours to download.
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(24 * 3600);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NdjsonOptions {
+    /// Read an unpaginated source as one NDJSON body, writing and
+    /// checkpointing it every this many lines.
+    #[serde(default)]
+    pub checkpoint_every: Option<u64>,
+}
+
+impl NdjsonOptions {
+    pub fn checkpoint_every(&self) -> Result<Option<u64>> {
+        match self.checkpoint_every {
+            Some(0) => Err(ApitapError::ConfigError(
+                "ndjson.checkpoint_every must be at least 1".into(),
+            )),
+            every => Ok(every),
+        }
+    }
+}
+
+/// `ETag`, else `Last-Modified`, of a response.
+fn validator(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(ETAG)
+        .or_else(|| headers.get(LAST_MODIFIED))
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Whether byte offsets into this body can be requested again: the server
+/// accepts ranges and the bytes read are the bytes sent (a decoded body
+/// loses its `Content-Length`).
+fn byte_addressable(headers: &HeaderMap) -> bool {
+    let accepts = headers
+        .get(ACCEPT_RANGES)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("bytes"));
+    accepts
+        && headers.contains_key(CONTENT_LENGTH)
+        && !headers.contains_key(CONTENT_ENCODING)
+        && Encoding::from_headers(headers).is_none()
+}
+
+/// First byte of a `Content-Range: bytes start-end/size` header.
+fn range_start(headers: &HeaderMap) -> Option<u64> {
+    let value = headers.get(CONTENT_RANGE)?.to_str().ok()?;
+    let range = value.trim().strip_prefix("bytes")?.trim_start();
+    range.split('-').next()?.trim().parse().ok()
+}
+
+/// Where reading starts: the line and byte counts of the bytes already
+/// behind us, and how many further lines to drop before writing again.
+#[derive(Debug, Default)]
+struct Position {
+    lines: u64,
+    bytes: u64,
+    skip: u64,
+}
+
+fn resume_position(
+    resp_status: StatusCode,
+    headers: &HeaderMap,
+    checkpoint: Option<&DownloadCheckpoint>,
+    url: &str,
+) -> Result<Position> {
+    let Some(checkpoint) = checkpoint else {
+        return Ok(Position::default());
+    };
+    if resp_status == StatusCode::PARTIAL_CONTENT {
+        let offset = checkpoint.bytes.unwrap_or_default();
+        if range_start(headers) != Some(offset) {
+            return Err(ApitapError::PaginationError(format!(
+                "{url}: server answered the range request from byte {:?}, expected {offset}",
+                range_start(headers)
+            )));
+        }
+        info!(%url, lines = checkpoint.lines, bytes = offset, "continuing download with a range request");
+        return Ok(Position {
+            lines: checkpoint.lines,
+            bytes: offset,
+            skip: 0,
+        });
+    }
+    if checkpoint.validator.is_some() && validator(headers) != checkpoint.validator {
+        warn!(%url, "export changed since the checkpoint; downloading it again from the start");
+        return Ok(Position::default());
+    }
+    info!(%url, lines = checkpoint.lines, "skipping lines written before the checkpoint");
+    Ok(Position {
+        skip: checkpoint.lines,
+        ..Position::default()
+    })
+}
+
+/// Read one NDJSON response into `page_writer`, a page per
+/// `checkpoint_every` lines, saving progress after each page when
+/// `request.downloads` is set.
+#[allow(clippy::too_many_arguments)]
+pub async fn run_ndjson_export(
+    client: reqwest::Client,
+    url: &Url,
+    query: Vec<(String, String)>,
+    data_path: Option<String>,
+    config_retry: &crate::pipeline::Retry,
+    request: RequestOptions,
+    page_writer: &dyn PageWriter,
+    write_mode: WriteMode,
+    checkpoint_every: u64,
+) -> Result<FetchStats> {
+    let mut url = url.clone();
+    if !query.is_empty() {
+        url.query_pairs_mut().extend_pairs(&query);
+    }
+    let span = info_span!("ndjson.export", source = %url, checkpoint_every);
+    let _g = span.enter();
+
+    let tracker = request.downloads.clone();
+    let checkpoint = match &tracker {
+        Some(tracker) => tracker.resume_point(url.as_str()).await,
+        None => None,
+    };
+
+    let client =
+        http_retry::build_client_with_retry_after(client, config_retry, request.attempt_hooks());
+    let mut req = client
+        .request(request.method.as_method(), url.as_str())
+        .timeout(EXPORT_TIMEOUT);
+    if let Some(body) = &request.body {
+        req = req
+            .header(CONTENT_TYPE, body.content_type())
+            .body(body.bytes.clone());
+    }
+    // `If-Range` needs a strong validator; without one only line skipping is safe.
+    if let Some(cp) = &checkpoint {
+        if let (Some(bytes), Some(validator)) = (cp.bytes, &cp.validator) {
+            if !validator.starts_with("W/") {
+                req = req
+                    .header(RANGE, format!("bytes={bytes}-"))
+                    .header(IF_RANGE, validator.as_str());
+            }
+        }
+    }
+    let resp = request
+        .send(req.build()?, |req| async { Ok(client.execute(req).await?) })
+        .await?;
+    let resp = request.check_status(resp).await?;
+
+    let status = resp.status();
+    let headers = resp.headers().clone();
+    let current_validator = validator(&headers);
+    let ranges = status == StatusCode::PARTIAL_CONTENT || byte_addressable(&headers);
+    let Position {
+        mut lines,
+        mut bytes,
+        mut skip,
+    } = resume_position(status, &headers, checkpoint.as_ref(), url.as_str())?;
+    debug!(status = %status, ranges, lines, skip, "export response received");
+
+    let mut stats = FetchStats {
+        resumed_lines: lines + skip,
+        ..FetchStats::new()
+    };
+    let mut batch = Vec::new();
+    let mut batch_lines = 0u64;
+    let mut buf = Vec::new();
+    let max_line_length = request.limits.max_line_length;
+    let mut body = request.body_stream(resp);
+    let mut done = false;
+    while !done {
+        let chunk = body.next().await.transpose()?;
+        match chunk {
+            Some(chunk) => buf.extend_from_slice(&chunk),
+            None => done = true,
+        }
+        let mut consumed = 0;
+        while consumed < buf.len() {
+            let line = match buf[consumed..].iter().position(|&b| b == b'\n') {
+                Some(end) => &buf[consumed..consumed + end + 1],
+                // A last line without a newline, once the body ended.
+                None if done => &buf[consumed..],
+                None => break,
+            };
+            consumed += line.len();
+            bytes += line.len() as u64;
+            lines += 1;
+            let length = line.len() - usize::from(line.ends_with(b"\n"));
+            if let Some(max) = max_line_length.filter(|max| length > *max) {
+                return Err(line_too_long(url.as_str(), lines, max));
+            }
+            if skip > 0 {
+                skip -= 1;
+                continue;
+            }
+            if !line.iter().all(u8::is_ascii_whitespace) {
+                let value: Value = serde_json::from_slice(line).map_err(|e| {
+                    ApitapError::PaginationError(format!("{url}: line {lines}: {e}"))
+                })?;
+                push_records(value, data_path.as_deref(), &mut batch);
+            }
+            batch_lines += 1;
+            if batch_lines == checkpoint_every {
+                write_chunk(
+                    &request,
+                    page_writer,
+                    &write_mode,
+                    &mut stats,
+                    lines / checkpoint_every,
+                    std::mem::take(&mut batch),
+                )
+                .await?;
+                batch_lines = 0;
+                if let Some(tracker) = &tracker {
+                    tracker
+                        .save(DownloadCheckpoint {
+                            url: url.to_string(),
+                            lines,
+                            bytes: ranges.then_some(bytes),
+                            validator: current_validator.clone(),
+                            saved_at: chrono::Utc::now(),
+                        })
+                        .await;
+                }
+            }
+        }
+        buf.drain(..consumed);
+        // The rest is a line still arriving.
+        if let Some(max) = max_line_length.filter(|max| buf.len() > *max) {
+            return Err(line_too_long(url.as_str(), lines + 1, max));
+        }
+    }
+    if batch_lines > 0 {
+        let page = lines / checkpoint_every + 1;
+        write_chunk(&request, page_writer, &write_mode, &mut stats, page, batch).await?;
+    }
+    if let Some(tracker) = &tracker {
+        tracker.finish().await;
+    }
+    info!(lines, rows = stats.total_items, "export downloaded");
+    Ok(stats)
+}
+
+fn line_too_long(url: &str, line: u64, max: usize) -> ApitapError {
+    ApitapError::PaginationError(format!(
+        "{url}: line {line}: longer than max_line_length ({max} bytes)"
+    ))
+}
+
+/// Write one chunk of lines as page `page`; numbered by line position, so a
+/// resumed download continues the same page sequence.
+async fn write_chunk(
+    request: &RequestOptions,
+    page_writer: &dyn PageWriter,
+    write_mode: &WriteMode,
+    stats: &mut FetchStats,
+    page: u64,
+    rows: Vec<Value>,
+) -> Result<()> {
+    let rows = request
+        .sequenced(page, stream::iter(rows.into_iter().map(Ok)).boxed())
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>>>()?;
+    stats.success_count += 1;
+    stats.total_items += rows.len();
+    page_writer.write_page(page, rows, write_mode.clone()).await
+}
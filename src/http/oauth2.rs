@@ -0,0 +1,398 @@
+//! OAuth2 client-credentials, refresh-token and JWT bearer grants.
+//!
+//! [`TokenManager`] fetches an access token from the token endpoint, sends
+//! it as a bearer token and keeps it until shortly before it expires (or
+//! `refresh_every` has passed). A `401` drops the cached token so the next
+//! request fetches a new one, for servers that revoke tokens before their
+//! announced expiry.
+//!
+//! With `refresh_token_env`, access tokens are obtained by exchanging a
+//! long-lived refresh token. Providers such as Google and Xero may answer
+//! with a new refresh token and invalidate the old one; the new one is then
+//! kept in the state store, so the next run does not start from a token
+//! that no longer works.
+//!
+//! With `kind: jwt` (see [`crate::http::jwt`]) the token is obtained by
+//! exchanging a freshly signed assertion instead, and cached the same way.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use reqwest::header::{HeaderValue, AUTHORIZATION};
+use reqwest::{Client, Request};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+use crate::errors::{ApitapError, Result};
+use crate::http::auth::{env_credential, Authenticator};
+use crate::http::jwt::JwtAssertion;
+use crate::http::usage::UsageMeter;
+use crate::pipeline::freshness::parse_duration;
+use crate::pipeline::retry_state::RetryStateStore;
+use crate::pipeline::state::StoredRefreshToken;
+
+/// Tokens are renewed this long before they expire, so one does not run
+/// out between being handed out and reaching the server.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+/// `grant_type` of the JWT bearer flow (RFC 7523 §2.1).
+const JWT_BEARER_GRANT: &str = "urn:ietf:params:oauth:grant-type:jwt-bearer";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OAuth2Config {
+    pub token_url: String,
+    /// Environment variable holding the client id.
+    pub client_id_env: String,
+    /// Environment variable holding the client secret.
+    pub client_secret_env: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<String>,
+    /// Send the client credentials as HTTP basic auth instead of in the
+    /// form body; some providers accept only one of the two.
+    #[serde(default)]
+    pub basic_auth: bool,
+    /// Environment variable holding a refresh token; switches to the
+    /// refresh-token grant.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token_env: Option<String>,
+    /// Renew the access token at least this often (e.g. `45m`), for
+    /// providers that announce no lifetime.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_every: Option<String>,
+}
+
+impl OAuth2Config {
+    pub fn refresh_every(&self) -> Result<Option<Duration>> {
+        self.refresh_every
+            .as_deref()
+            .map(parse_duration)
+            .transpose()
+    }
+}
+
+/// Where rotated refresh tokens are kept between runs: the state store,
+/// under the source's name.
+#[derive(Debug, Clone)]
+pub struct RefreshTokenSlot {
+    pub store: Arc<RetryStateStore>,
+    pub source: String,
+}
+
+/// Token endpoint response (RFC 6749 §5.1).
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    token_type: Option<String>,
+    #[serde(default)]
+    expires_in: Option<u64>,
+    /// Set when the provider rotates refresh tokens.
+    #[serde(default)]
+    refresh_token: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct CachedToken {
+    value: String,
+    /// `None` when neither the server nor `refresh_every` gave a lifetime;
+    /// kept until rejected.
+    renew_at: Option<Instant>,
+}
+
+impl CachedToken {
+    fn fresh(&self) -> bool {
+        self.renew_at.map_or(true, |at| Instant::now() < at)
+    }
+}
+
+/// Hex SHA-256 of a configured refresh token, to tell which one a stored
+/// token descends from without storing the configured one.
+fn fingerprint(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[derive(Debug)]
+struct RefreshGrant {
+    /// The token from the environment.
+    configured: String,
+    slot: Option<RefreshTokenSlot>,
+    /// The token to exchange next; loaded from the slot on first use.
+    current: Mutex<Option<String>>,
+}
+
+/// How access tokens are obtained from the token endpoint.
+#[derive(Debug)]
+enum Grant {
+    /// Client credentials, or a refresh token exchanged with them.
+    Client {
+        client_id: String,
+        client_secret: String,
+        basic_auth: bool,
+        refresh: Option<RefreshGrant>,
+    },
+    /// A signed JWT exchanged for a token (RFC 7523).
+    JwtBearer(Box<JwtAssertion>),
+}
+
+/// Fetches, caches and renews OAuth2 access tokens.
+#[derive(Debug)]
+pub struct TokenManager {
+    client: Client,
+    token_url: String,
+    scopes: Vec<String>,
+    grant: Grant,
+    refresh_every: Option<Duration>,
+    cached: Mutex<Option<CachedToken>>,
+    /// Counts token requests with the run's API calls.
+    usage: Option<Arc<UsageMeter>>,
+}
+
+impl TokenManager {
+    /// Reads the client id and secret, and any refresh token, from their
+    /// environment variables.
+    pub fn new(client: Client, config: OAuth2Config) -> Result<Self> {
+        let client_id = env_credential(&config.client_id_env, "oauth2 client id")?;
+        let client_secret = env_credential(&config.client_secret_env, "oauth2 client secret")?;
+        let refresh = config
+            .refresh_token_env
+            .as_deref()
+            .map(|key| env_credential(key, "oauth2 refresh token"))
+            .transpose()?
+            .map(|configured| RefreshGrant {
+                configured,
+                slot: None,
+                current: Mutex::new(None),
+            });
+        Ok(Self {
+            client,
+            refresh_every: config.refresh_every()?,
+            grant: Grant::Client {
+                client_id,
+                client_secret,
+                basic_auth: config.basic_auth,
+                refresh,
+            },
+            token_url: config.token_url,
+            scopes: config.scopes,
+            cached: Mutex::new(None),
+            usage: None,
+        })
+    }
+
+    /// Exchanges assertions signed by `assertion` at its token endpoint.
+    pub fn jwt_bearer(
+        client: Client,
+        assertion: JwtAssertion,
+        refresh_every: Option<Duration>,
+    ) -> Self {
+        Self {
+            client,
+            token_url: assertion.token_url().to_string(),
+            // Sent in the assertion's `scope` claim instead.
+            scopes: Vec::new(),
+            grant: Grant::JwtBearer(Box::new(assertion)),
+            refresh_every,
+            cached: Mutex::new(None),
+            usage: None,
+        }
+    }
+
+    /// Count each token request on `usage`.
+    pub fn with_usage(mut self, usage: Option<Arc<UsageMeter>>) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Keep rotated refresh tokens in `slot`, and start from the one stored
+    /// there when it descends from the configured token.
+    pub fn with_refresh_token_slot(mut self, slot: Option<RefreshTokenSlot>) -> Self {
+        if let Grant::Client {
+            refresh: Some(refresh),
+            ..
+        } = &mut self.grant
+        {
+            refresh.slot = slot;
+        }
+        self
+    }
+
+    /// A valid access token, fetched when none is cached or it is about to
+    /// expire. Concurrent callers wait for a single fetch.
+    pub async fn token(&self) -> Result<String> {
+        let mut cached = self.cached.lock().await;
+        if let Some(token) = cached.as_ref().filter(|t| t.fresh()) {
+            return Ok(token.value.clone());
+        }
+        let token = self.fetch().await?;
+        let value = token.value.clone();
+        *cached = Some(token);
+        Ok(value)
+    }
+
+    /// Forget `token` if it is still the cached one.
+    pub async fn invalidate(&self, token: &str) {
+        let mut cached = self.cached.lock().await;
+        if cached.as_ref().is_some_and(|t| t.value == token) {
+            *cached = None;
+        }
+    }
+
+    /// The refresh token to exchange: the stored rotation of the configured
+    /// one, or the configured one itself.
+    async fn current_refresh_token(refresh: &RefreshGrant) -> String {
+        let mut current = refresh.current.lock().await;
+        if current.is_none() {
+            let stored = match &refresh.slot {
+                Some(slot) => slot.store.refresh_token(&slot.source).await,
+                None => None,
+            };
+            *current = Some(match stored {
+                Some(stored) if stored.origin == fingerprint(&refresh.configured) => {
+                    debug!(rotated_at = %stored.rotated_at, "using stored oauth2 refresh token");
+                    stored.token
+                }
+                _ => refresh.configured.clone(),
+            });
+        }
+        current.clone().unwrap_or_default()
+    }
+
+    /// Remember a refresh token the provider rotated to, persisting it when
+    /// there is a slot for it.
+    async fn rotate(&self, refresh: &RefreshGrant, token: String) {
+        *refresh.current.lock().await = Some(token.clone());
+        let Some(slot) = &refresh.slot else {
+            warn!(
+                token_url = %self.token_url,
+                "oauth2 refresh token was rotated but no state store is configured; \
+                 later runs will start from the configured token"
+            );
+            return;
+        };
+        let stored = StoredRefreshToken {
+            token,
+            origin: fingerprint(&refresh.configured),
+            rotated_at: chrono::Utc::now(),
+        };
+        match slot.store.save_refresh_token(&slot.source, stored).await {
+            Ok(()) => info!(source = %slot.source, "stored rotated oauth2 refresh token"),
+            Err(e) => {
+                warn!(source = %slot.source, error = %e, "failed to store rotated oauth2 refresh token")
+            }
+        }
+    }
+
+    async fn fetch(&self) -> Result<CachedToken> {
+        let mut req = self.client.post(&self.token_url);
+        let mut form = match &self.grant {
+            Grant::Client {
+                refresh: Some(refresh),
+                ..
+            } => vec![
+                ("grant_type", "refresh_token".to_string()),
+                ("refresh_token", Self::current_refresh_token(refresh).await),
+            ],
+            Grant::Client { refresh: None, .. } => {
+                vec![("grant_type", "client_credentials".to_string())]
+            }
+            Grant::JwtBearer(assertion) => vec![
+                ("grant_type", JWT_BEARER_GRANT.to_string()),
+                ("assertion", assertion.sign()?),
+            ],
+        };
+        let scope = self.scopes.join(" ");
+        if !scope.is_empty() {
+            form.push(("scope", scope));
+        }
+        if let Grant::Client {
+            client_id,
+            client_secret,
+            basic_auth,
+            ..
+        } = &self.grant
+        {
+            if *basic_auth {
+                req = req.basic_auth(client_id, Some(client_secret));
+            } else {
+                form.push(("client_id", client_id.clone()));
+                form.push(("client_secret", client_secret.clone()));
+            }
+        }
+        if let Some(usage) = &self.usage {
+            usage.request();
+        }
+        let resp = req.form(&form).send().await?;
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.text().await.unwrap_or_default();
+            return Err(ApitapError::PipelineError(format!(
+                "oauth2 token request to {} failed with {status}: {}",
+                self.token_url,
+                body.chars().take(200).collect::<String>()
+            )));
+        }
+        let token: TokenResponse = resp.json().await?;
+        if let Some(kind) = &token.token_type {
+            if !kind.eq_ignore_ascii_case("bearer") {
+                debug!(token_type = %kind, "oauth2 token is not a bearer token; sending it as one");
+            }
+        }
+        info!(
+            token_url = %self.token_url,
+            expires_in = ?token.expires_in,
+            "fetched oauth2 access token"
+        );
+        let refresh = match &self.grant {
+            Grant::Client { refresh, .. } => refresh.as_ref(),
+            Grant::JwtBearer(_) => None,
+        };
+        if let (Some(refresh), Some(rotated)) = (refresh, token.refresh_token) {
+            if refresh.current.lock().await.as_deref() != Some(rotated.as_str()) {
+                self.rotate(refresh, rotated).await;
+            }
+        }
+        let now = Instant::now();
+        let expires = token
+            .expires_in
+            .map(|secs| now + Duration::from_secs(secs).saturating_sub(EXPIRY_MARGIN));
+        let scheduled = self.refresh_every.map(|every| now + every);
+        Ok(CachedToken {
+            value: token.access_token,
+            renew_at: match (expires, scheduled) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            },
+        })
+    }
+}
+
+#[async_trait]
+impl Authenticator for TokenManager {
+    async fn authorize(&self, request: &mut Request) -> Result<()> {
+        let token = self.token().await?;
+        let value = HeaderValue::from_str(&format!("Bearer {token}")).map_err(|_| {
+            ApitapError::PipelineError("oauth2 access token is not a valid header value".into())
+        })?;
+        request.headers_mut().insert(AUTHORIZATION, value);
+        Ok(())
+    }
+
+    async fn rejected(&self, request: &Request) -> bool {
+        let Some(token) = request
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+        else {
+            return false;
+        };
+        self.invalidate(token).await;
+        true
+    }
+}
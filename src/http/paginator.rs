@@ -0,0 +1,115 @@
+//! Pagination schemes supplied by library users.
+//!
+//! The built-in modes cover offsets, page numbers and next links. APIs with
+//! anything more exotic (signed continuation tokens, page tokens in response
+//! headers, cursors that must be echoed in a body field) can implement
+//! [`Paginator`] and register it by name on
+//! [`RunOptions`](crate::cmd::RunOptions); a source then selects it with
+//!
+//! ```yaml
+//! pagination:
+//!   kind: custom
+//!   paginator: signed_tokens
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use reqwest::header::HeaderMap;
+use reqwest::Url;
+use serde_json::Value;
+
+use crate::errors::Result;
+
+/// One page request: where it goes and what it adds to the source's own
+/// query parameters and headers.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct PageRequest {
+    /// Absolute URL requested instead of the source's `url`, e.g. a signed
+    /// continuation link; the source's `query_params` are not added to it.
+    pub url: Option<String>,
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+}
+
+impl PageRequest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.url = Some(url.into());
+        self
+    }
+
+    pub fn with_query(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.query.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+}
+
+/// What a page's response looked like, for choosing the next request.
+#[derive(Debug)]
+pub struct PageResponse<'a> {
+    /// 1-based position of the page in this run.
+    pub page: u64,
+    /// URL the response came from, after redirects.
+    pub url: &'a Url,
+    pub headers: &'a HeaderMap,
+    /// The parsed JSON body.
+    pub body: &'a Value,
+    /// Rows the page held at the source's `data_path`.
+    pub rows: usize,
+}
+
+/// Computes each page request from the response to the previous one.
+pub trait Paginator: Send + Sync {
+    /// The first request; by default the source's URL and query as they are.
+    fn first(&self) -> PageRequest {
+        PageRequest::default()
+    }
+
+    /// The request after `previous`, whose response was `response`; `None`
+    /// when that was the last page.
+    fn next(
+        &self,
+        previous: &PageRequest,
+        response: &PageResponse<'_>,
+    ) -> Result<Option<PageRequest>>;
+}
+
+/// Paginators registered by name, for `kind: custom` sources.
+#[derive(Clone, Default)]
+pub struct Paginators {
+    by_name: HashMap<String, Arc<dyn Paginator>>,
+}
+
+impl std::fmt::Debug for Paginators {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.by_name.keys()).finish()
+    }
+}
+
+impl Paginators {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `paginator` as `name`; a later registration replaces it.
+    pub fn register(&mut self, name: impl Into<String>, paginator: impl Paginator + 'static) {
+        self.by_name.insert(name.into(), Arc::new(paginator));
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Paginator>> {
+        self.by_name.get(name).cloned()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+}
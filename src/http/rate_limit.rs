@@ -0,0 +1,101 @@
+//! Client-side request rate limit per source (`rate_limit:`).
+//!
+//! ```yaml
+//! rate_limit:
+//!   requests_per_second: 2   # fractions allowed, e.g. 0.5 for one every 2s
+//!   burst: 5                 # requests sent at once after a quiet spell (default 1)
+//! ```
+//!
+//! One [`RateLimiter`] is shared by all page requests of a source, however
+//! many are in flight, so `concurrency` sets how many requests wait on the
+//! API at once while the limit sets how often one starts. Retries take a
+//! token like any other attempt. It complements
+//! [`ServerThrottle`](crate::http::throttle::ServerThrottle), which only
+//! slows down once the server reports its budget running low.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tracing::trace;
+
+use crate::errors::{ApitapError, Result};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub requests_per_second: f64,
+    /// Requests that may start back to back before the rate applies.
+    #[serde(default = "default_burst")]
+    pub burst: u32,
+}
+
+fn default_burst() -> u32 {
+    1
+}
+
+impl RateLimit {
+    pub fn limiter(&self) -> Result<RateLimiter> {
+        if !(self.requests_per_second > 0.0 && self.requests_per_second.is_finite()) {
+            return Err(ApitapError::ConfigError(format!(
+                "rate_limit.requests_per_second must be above zero, got {}",
+                self.requests_per_second
+            )));
+        }
+        if self.burst == 0 {
+            return Err(ApitapError::ConfigError(
+                "rate_limit.burst must be at least 1".into(),
+            ));
+        }
+        Ok(RateLimiter::new(self.requests_per_second, self.burst))
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+/// Token bucket handing out one token per request.
+#[derive(Debug)]
+pub struct RateLimiter {
+    per_second: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    pub fn new(per_second: f64, burst: u32) -> Self {
+        let burst = f64::from(burst.max(1));
+        Self {
+            per_second,
+            burst,
+            bucket: Mutex::new(Bucket {
+                tokens: burst,
+                last: Instant::now(),
+            }),
+        }
+    }
+
+    /// Wait until a request may start. Tokens are claimed in call order, so
+    /// concurrent callers queue up rather than all waking at once.
+    pub async fn acquire(&self) {
+        let wait = {
+            let mut bucket = self.bucket.lock().await;
+            let now = Instant::now();
+            let refill = now.duration_since(bucket.last).as_secs_f64() * self.per_second;
+            bucket.tokens = (bucket.tokens + refill).min(self.burst) - 1.0;
+            bucket.last = now;
+            if bucket.tokens < 0.0 {
+                Duration::from_secs_f64(-bucket.tokens / self.per_second)
+            } else {
+                Duration::ZERO
+            }
+        };
+        if wait > Duration::ZERO {
+            trace!(wait_ms = wait.as_millis(), "rate limit reached");
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! Session logins for APIs that only serve data once a login request has
+//! set a cookie.
+//!
+//! ```yaml
+//! cookies: true                # keep cookies responses set (implied by pre_request)
+//! pre_request:
+//!   url: https://api.example.com/login
+//!   method: POST               # default
+//!   content_type: form         # json (default) | form
+//!   body:
+//!     username: "${API_USER}"
+//!     password: "${API_PASSWORD}"
+//! ```
+//!
+//! The login is sent once before the source's first page, with the
+//! source's client, so the cookies it sets go out with every page request
+//! after it. `${VAR}` in header values and in the body's strings are read
+//! from the environment. A login answered with an error status fails the
+//! module before any page is fetched.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, info};
+
+use crate::errors::Result;
+use crate::http::auth::expand_env;
+use crate::http::body::{BodyFormat, RequestBody, RequestMethod};
+use crate::pipeline::Header;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreRequest {
+    pub url: String,
+    #[serde(default = "default_method")]
+    pub method: RequestMethod,
+    /// Sent with the login only, besides the source's own headers.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<Header>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+    #[serde(default)]
+    pub content_type: BodyFormat,
+}
+
+fn default_method() -> RequestMethod {
+    RequestMethod::Post
+}
+
+impl PreRequest {
+    /// Send the login with `client`, whose cookie jar keeps what it sets.
+    pub async fn send(&self, client: &reqwest::Client) -> Result<()> {
+        let mut req = client.request(self.method.as_method(), &self.url);
+        for header in &self.headers {
+            req = req.header(header.key.as_str(), header.resolve()?);
+        }
+        if let Some(body) = &self.body {
+            let body = RequestBody::encode(&expand_body(body)?, self.content_type)?;
+            req = req
+                .header(reqwest::header::CONTENT_TYPE, body.content_type())
+                .body(body.bytes);
+        }
+        debug!(url = %self.url, "sending pre_request");
+        let resp = req.send().await?.error_for_status()?;
+        info!(url = %self.url, status = %resp.status(), "🔐 session login succeeded");
+        Ok(())
+    }
+}
+
+/// `body` with `${VAR}` in every string replaced from the environment.
+fn expand_body(body: &Value) -> Result<Value> {
+    Ok(match body {
+        Value::String(s) => Value::String(expand_env(s, "pre_request body")?),
+        Value::Array(items) => Value::Array(items.iter().map(expand_body).collect::<Result<_>>()?),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(k, v)| Ok((k.clone(), expand_body(v)?)))
+                .collect::<Result<_>>()?,
+        ),
+        other => other.clone(),
+    })
+}
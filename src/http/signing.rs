@@ -0,0 +1,263 @@
+//! HMAC request signing.
+//!
+//! Exchange and commerce APIs want every request signed with a shared
+//! secret: the HMAC of some of its parts, sent in a header. The parts are
+//! joined with `separator` in the order listed under `sign`:
+//!
+//! ```yaml
+//! auth:
+//!   kind: hmac
+//!   secret_env: API_SECRET
+//!   algorithm: sha256               # sha256 (default) | sha512
+//!   sign: [timestamp, method, path, query, body]
+//!   header: X-Signature
+//!   encoding: hex                   # hex (default) | base64
+//!   timestamp_header: X-Timestamp   # unix seconds, sent and signed
+//!   key_header: X-Api-Key           # optional key id sent alongside
+//!   key_env: API_KEY
+//! ```
+//!
+//! Requests are signed again for every attempt, so a retry after a 5xx, a
+//! 429's `Retry-After` wait or a `401` carries a fresh timestamp and fits
+//! the server's receive window.
+
+use std::fmt::Debug;
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::Request;
+use serde::{Deserialize, Serialize};
+use sha2::{Sha256, Sha512};
+
+use crate::errors::{ApitapError, Result};
+use crate::http::auth::{env_credential, Authenticator};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HmacAlgorithm {
+    #[default]
+    Sha256,
+    Sha512,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureEncoding {
+    #[default]
+    Hex,
+    Base64,
+}
+
+/// A part of the request that goes into the signed message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignedPart {
+    /// `GET`, `POST`, ...
+    Method,
+    /// The URL path, e.g. `/v1/orders`.
+    Path,
+    /// The query string without `?`; empty when there is none.
+    Query,
+    /// The full URL.
+    Url,
+    /// The request body; empty for requests without one.
+    Body,
+    /// Unix time in seconds (milliseconds with `timestamp_ms`).
+    Timestamp,
+}
+
+fn default_sign() -> Vec<SignedPart> {
+    vec![SignedPart::Method, SignedPart::Path, SignedPart::Query]
+}
+
+fn default_separator() -> String {
+    "\n".to_string()
+}
+
+fn default_header() -> String {
+    "X-Signature".to_string()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HmacConfig {
+    /// Environment variable holding the shared secret.
+    pub secret_env: String,
+    #[serde(default)]
+    pub algorithm: HmacAlgorithm,
+    /// Parts signed, in order.
+    #[serde(default = "default_sign")]
+    pub sign: Vec<SignedPart>,
+    /// Put between the signed parts.
+    #[serde(default = "default_separator")]
+    pub separator: String,
+    /// Header carrying the signature.
+    #[serde(default = "default_header")]
+    pub header: String,
+    #[serde(default)]
+    pub encoding: SignatureEncoding,
+    /// Put before the signature, e.g. `"sha256="`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    /// Send the signed timestamp in this header.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp_header: Option<String>,
+    /// Timestamps in milliseconds instead of seconds.
+    #[serde(default)]
+    pub timestamp_ms: bool,
+    /// Send the key id from `key_env` in this header.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_header: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_env: Option<String>,
+}
+
+/// Signs every request with an HMAC of its parts.
+pub struct HmacSigner {
+    config: HmacConfig,
+    secret: String,
+    header: HeaderName,
+    timestamp_header: Option<HeaderName>,
+    key: Option<(HeaderName, HeaderValue)>,
+}
+
+impl Debug for HmacSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HmacSigner")
+            .field("algorithm", &self.config.algorithm)
+            .field("sign", &self.config.sign)
+            .field("header", &self.header)
+            .finish_non_exhaustive()
+    }
+}
+
+fn header_name(name: &str) -> Result<HeaderName> {
+    HeaderName::from_bytes(name.as_bytes())
+        .map_err(|_| ApitapError::ConfigError(format!("invalid hmac header name '{name}'")))
+}
+
+impl HmacSigner {
+    /// Reads the secret, and the key id if any, from their environment
+    /// variables.
+    pub fn new(config: &HmacConfig) -> Result<Self> {
+        let secret = env_credential(&config.secret_env, "hmac secret")?;
+        let key = match (&config.key_header, &config.key_env) {
+            (Some(header), Some(env)) => {
+                let value =
+                    HeaderValue::from_str(&env_credential(env, "hmac key id")?).map_err(|_| {
+                        ApitapError::ConfigError(format!(
+                            "hmac key id in '{env}' is not a valid header value"
+                        ))
+                    })?;
+                Some((header_name(header)?, value))
+            }
+            (None, None) => None,
+            _ => {
+                return Err(ApitapError::ConfigError(
+                    "hmac key_header and key_env go together".to_string(),
+                ))
+            }
+        };
+        Ok(Self {
+            header: header_name(&config.header)?,
+            timestamp_header: config
+                .timestamp_header
+                .as_deref()
+                .map(header_name)
+                .transpose()?,
+            key,
+            secret,
+            config: config.clone(),
+        })
+    }
+
+    /// The message signed for `request` at `timestamp`.
+    pub fn message(&self, request: &Request, timestamp: &str) -> Vec<u8> {
+        let url = request.url();
+        let mut message = Vec::new();
+        for (i, part) in self.config.sign.iter().enumerate() {
+            if i > 0 {
+                message.extend_from_slice(self.config.separator.as_bytes());
+            }
+            match part {
+                SignedPart::Method => {
+                    message.extend_from_slice(request.method().as_str().as_bytes())
+                }
+                SignedPart::Path => message.extend_from_slice(url.path().as_bytes()),
+                SignedPart::Query => {
+                    message.extend_from_slice(url.query().unwrap_or_default().as_bytes())
+                }
+                SignedPart::Url => message.extend_from_slice(url.as_str().as_bytes()),
+                SignedPart::Body => {
+                    if let Some(body) = request.body().and_then(|b| b.as_bytes()) {
+                        message.extend_from_slice(body);
+                    }
+                }
+                SignedPart::Timestamp => message.extend_from_slice(timestamp.as_bytes()),
+            }
+        }
+        message
+    }
+
+    /// The encoded signature of `message`, prefix included.
+    pub fn signature(&self, message: &[u8]) -> String {
+        let secret = self.secret.as_bytes();
+        let mac = match self.config.algorithm {
+            HmacAlgorithm::Sha256 => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(secret)
+                    .expect("HMAC accepts keys of any length");
+                mac.update(message);
+                mac.finalize().into_bytes().to_vec()
+            }
+            HmacAlgorithm::Sha512 => {
+                let mut mac = Hmac::<Sha512>::new_from_slice(secret)
+                    .expect("HMAC accepts keys of any length");
+                mac.update(message);
+                mac.finalize().into_bytes().to_vec()
+            }
+        };
+        let encoded = match self.config.encoding {
+            SignatureEncoding::Hex => mac.iter().map(|b| format!("{b:02x}")).collect(),
+            SignatureEncoding::Base64 => STANDARD.encode(mac),
+        };
+        format!(
+            "{}{encoded}",
+            self.config.prefix.as_deref().unwrap_or_default()
+        )
+    }
+}
+
+#[async_trait]
+impl Authenticator for HmacSigner {
+    async fn authorize(&self, request: &mut Request) -> Result<()> {
+        let now = chrono::Utc::now();
+        let timestamp = if self.config.timestamp_ms {
+            now.timestamp_millis().to_string()
+        } else {
+            now.timestamp().to_string()
+        };
+        let signature = self.signature(&self.message(request, &timestamp));
+        let invalid = || ApitapError::PipelineError("hmac signature is not a valid header".into());
+        let headers = request.headers_mut();
+        if let Some(name) = &self.timestamp_header {
+            headers.insert(
+                name.clone(),
+                HeaderValue::from_str(&timestamp).map_err(|_| invalid())?,
+            );
+        }
+        if let Some((name, value)) = &self.key {
+            headers.insert(name.clone(), value.clone());
+        }
+        headers.insert(
+            self.header.clone(),
+            HeaderValue::from_str(&signature).map_err(|_| invalid())?,
+        );
+        Ok(())
+    }
+
+    fn signs_each_attempt(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,340 @@
+//! Server-Sent Events (`text/event-stream`) as JSON rows.
+//!
+//! Each event's `data:` lines are joined and parsed as JSON; objects become
+//! rows, arrays one row per element, and anything else (including non-JSON
+//! text) is wrapped as `{"data": ...}`. With `format: sse` the connection is
+//! kept open for up to `max_duration`, reconnecting with `Last-Event-ID`
+//! when the server drops it.
+
+use std::time::Duration;
+
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt};
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use reqwest_middleware::ClientWithMiddleware;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tokio::time::Instant;
+use tracing::{debug, info, info_span, warn};
+
+use crate::errors::{ApitapError, Result};
+use crate::http::fetcher::{batched, DataFusionPageWriter, FetchStats, PageWriter, RequestOptions};
+use crate::http::vcr::VcrMode;
+use crate::pipeline::freshness::parse_duration;
+use crate::utils::http_retry;
+use crate::writer::WriteMode;
+
+/// Wait before reconnecting until the server sends a `retry:` field.
+const DEFAULT_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Per-request timeout when no `max_duration` bounds the stream.
+const UNBOUNDED_TIMEOUT: Duration = Duration::from_secs(24 * 3600);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SseOptions {
+    /// Stop reading after this long, e.g. `5m`. Without it the module ends
+    /// when the server closes the stream.
+    #[serde(default)]
+    pub max_duration: Option<String>,
+    /// Reconnect when the stream drops before `max_duration` is up.
+    #[serde(default = "default_reconnect")]
+    pub reconnect: bool,
+}
+
+fn default_reconnect() -> bool {
+    true
+}
+
+impl Default for SseOptions {
+    fn default() -> Self {
+        Self {
+            max_duration: None,
+            reconnect: default_reconnect(),
+        }
+    }
+}
+
+impl SseOptions {
+    pub fn max_duration(&self) -> Result<Option<Duration>> {
+        self.max_duration.as_deref().map(parse_duration).transpose()
+    }
+}
+
+/// One dispatched event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseEvent {
+    /// `event:` type; `None` for the default `message`.
+    pub event: Option<String>,
+    pub data: String,
+    /// Last event ID in effect when the event was dispatched.
+    pub id: Option<String>,
+}
+
+/// Incremental `text/event-stream` parser.
+#[derive(Debug, Default)]
+pub struct SseParser {
+    line: Vec<u8>,
+    data: Vec<String>,
+    event: Option<String>,
+    /// Last `id:` seen; sent back as `Last-Event-ID` on reconnect.
+    pub last_event_id: Option<String>,
+    /// Reconnection delay requested with `retry:`.
+    pub retry: Option<Duration>,
+}
+
+impl SseParser {
+    /// Feed a chunk of the body and return the events it completed.
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        let mut events = Vec::new();
+        for &byte in chunk {
+            if byte == b'\n' {
+                let mut line = std::mem::take(&mut self.line);
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                if let Some(event) = self.process_line(&String::from_utf8_lossy(&line)) {
+                    events.push(event);
+                }
+            } else {
+                self.line.push(byte);
+            }
+        }
+        events
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            let event = self.event.take();
+            if self.data.is_empty() {
+                return None;
+            }
+            return Some(SseEvent {
+                event,
+                data: std::mem::take(&mut self.data).join("\n"),
+                id: self.last_event_id.clone(),
+            });
+        }
+        if line.starts_with(':') {
+            // Comment, typically a keep-alive.
+            return None;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "data" => self.data.push(value.to_string()),
+            "event" => self.event = Some(value.to_string()),
+            "id" if !value.contains('\0') => self.last_event_id = Some(value.to_string()),
+            "retry" => {
+                if let Ok(ms) = value.parse::<u64>() {
+                    self.retry = Some(Duration::from_millis(ms));
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Rows carried by one event.
+pub fn event_rows(event: &SseEvent, data_path: Option<&str>) -> Vec<Value> {
+    let value = match serde_json::from_str::<Value>(&event.data) {
+        Ok(value) => value,
+        Err(_) => Value::String(event.data.clone()),
+    };
+    let value = match data_path.and_then(|p| value.pointer(p)) {
+        Some(inner) => inner.clone(),
+        None => value,
+    };
+    let as_row = |value: Value| match value {
+        Value::Object(_) => value,
+        other => Value::Object(Map::from_iter([("data".to_string(), other)])),
+    };
+    match value {
+        Value::Array(items) => items.into_iter().map(as_row).collect(),
+        Value::Null => Vec::new(),
+        other => vec![as_row(other)],
+    }
+}
+
+/// Rows of a single event-stream response, until it ends.
+pub fn sse_rows<S, B, E>(bytes: S, data_path: Option<&str>) -> BoxStream<'static, Result<Value>>
+where
+    S: Stream<Item = std::result::Result<B, E>> + Send + 'static,
+    B: AsRef<[u8]> + Send,
+    E: Send,
+    ApitapError: From<E>,
+{
+    let data_path = data_path.map(str::to_owned);
+    let s = async_stream::try_stream! {
+        let mut parser = SseParser::default();
+        let mut bytes = Box::pin(bytes);
+        while let Some(chunk) = bytes.next().await {
+            for event in parser.feed(chunk?.as_ref()) {
+                for row in event_rows(&event, data_path.as_deref()) {
+                    yield row;
+                }
+            }
+        }
+    };
+    s.boxed()
+}
+
+/// Rows of an event stream kept open for `request.sse.max_duration`,
+/// reconnecting when it drops. The first connection must succeed;
+/// failed reconnects are retried until time is up.
+pub fn sse_stream(
+    client: ClientWithMiddleware,
+    url: String,
+    query: Vec<(String, String)>,
+    data_path: Option<String>,
+    request: RequestOptions,
+) -> Result<BoxStream<'static, Result<Value>>> {
+    let max_duration = request.sse.max_duration()?;
+    let reconnect = request.sse.reconnect && max_duration.is_some();
+    let replaying = request
+        .vcr
+        .as_ref()
+        .is_some_and(|vcr| vcr.mode() == VcrMode::Replay);
+
+    let s = async_stream::try_stream! {
+        let deadline = max_duration.map(|d| Instant::now() + d);
+        let mut parser = SseParser::default();
+        let mut connections = 0u32;
+
+        'connect: loop {
+            let remaining = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+                None => UNBOUNDED_TIMEOUT,
+            };
+            if remaining.is_zero() {
+                break;
+            }
+
+            let mut req = client
+                .request(request.method.as_method(), &url)
+                .query(&query)
+                .header(ACCEPT, "text/event-stream")
+                .timeout(remaining);
+            if let Some(body) = &request.body {
+                req = req
+                    .header(CONTENT_TYPE, body.content_type())
+                    .body(body.bytes.clone());
+            }
+            if let Some(id) = &parser.last_event_id {
+                req = req.header("Last-Event-ID", id.as_str());
+            }
+            connections += 1;
+            let sent = match req.build() {
+                Ok(req) => request.send(req, |req| async { Ok(client.execute(req).await?) }).await,
+                Err(e) => Err(e.into()),
+            };
+            let checked = match sent {
+                Ok(resp) => request.check_status(resp).await,
+                Err(e) => Err(e),
+            };
+            let resp = match checked {
+                Ok(resp) => resp,
+                // Replay ends with the last recorded connection.
+                Err(_) if connections > 1 && replaying => break,
+                Err(e) if connections > 1 => {
+                    warn!(%url, error = %e, "SSE reconnect failed");
+                    let delay = parser.retry.unwrap_or(DEFAULT_RECONNECT_DELAY).min(remaining);
+                    tokio::time::sleep(delay).await;
+                    continue 'connect;
+                }
+                Err(e) => Err(e)?,
+            };
+            debug!(%url, connection = connections, "SSE stream open");
+
+            let mut bytes = request.raw_body(resp);
+            loop {
+                let next = match deadline {
+                    Some(deadline) => match tokio::time::timeout_at(deadline, bytes.next()).await {
+                        Ok(next) => next,
+                        Err(_) => {
+                            info!(%url, "SSE max_duration reached");
+                            break 'connect;
+                        }
+                    },
+                    None => bytes.next().await,
+                };
+                match next {
+                    Some(Ok(chunk)) => {
+                        for event in parser.feed(&chunk) {
+                            for row in event_rows(&event, data_path.as_deref()) {
+                                yield row;
+                            }
+                        }
+                    }
+                    Some(Err(e)) => {
+                        warn!(%url, error = %e, "SSE stream dropped");
+                        break;
+                    }
+                    None => {
+                        debug!(%url, "SSE stream closed by server");
+                        break;
+                    }
+                }
+            }
+
+            if !reconnect {
+                break;
+            }
+            let delay = parser.retry.unwrap_or(DEFAULT_RECONNECT_DELAY);
+            info!(%url, delay_ms = delay.as_millis() as u64, "reconnecting SSE stream");
+            match deadline {
+                Some(deadline) => tokio::time::sleep(delay.min(deadline.saturating_duration_since(Instant::now()))).await,
+                None => tokio::time::sleep(delay).await,
+            }
+        }
+    };
+    Ok(s.boxed())
+}
+
+/// Read an event stream into `page_writer`, one page per burst of up to
+/// `batch_size` rows that arrived together, or with `flush_interval` per
+/// `batch_size` rows or that long since the first of them, whichever
+/// comes first.
+#[allow(clippy::too_many_arguments)]
+pub async fn run_sse_fetch(
+    client: reqwest::Client,
+    url: &str,
+    query: Vec<(String, String)>,
+    data_path: Option<String>,
+    config_retry: &crate::pipeline::Retry,
+    request: RequestOptions,
+    page_writer: &DataFusionPageWriter,
+    write_mode: WriteMode,
+    batch_size: usize,
+    flush_interval: Option<Duration>,
+) -> Result<FetchStats> {
+    let span = info_span!("sse.fetch", source = %url, max_duration = ?request.sse.max_duration);
+    let _g = span.enter();
+
+    let client =
+        http_retry::build_client_with_retry_after(client, config_retry, request.attempt_hooks());
+    let rows = sse_stream(client, url.to_string(), query, data_path, request.clone())?;
+    let rows = request.sequenced(0, rows);
+    let mut batches = match flush_interval {
+        Some(_) => batched(rows, batch_size, flush_interval),
+        None => rows.ready_chunks(batch_size.max(1)).boxed(),
+    };
+
+    let mut stats = FetchStats::new();
+    while let Some(batch) = batches.next().await {
+        let batch = batch.into_iter().collect::<Result<Vec<_>>>()?;
+        stats.success_count += 1;
+        stats.total_items += batch.len();
+        debug!(
+            batch = stats.success_count,
+            rows = batch.len(),
+            "SSE batch read"
+        );
+        page_writer
+            .write_page(stats.success_count as u64, batch, write_mode.clone())
+            .await?;
+    }
+    Ok(stats)
+}
@@ -0,0 +1,227 @@
+//! Server-driven throttling from `X-RateLimit-*` response headers.
+//!
+//! Many APIs advertise their remaining request budget. [`ServerThrottle`]
+//! records it after every response and, once the budget runs low, spaces out
+//! the following requests so the window resets before a 429 is returned.
+//! When a 429 comes back anyway, the retry waits as long as its
+//! `Retry-After` (or reset) header says, and the source's other requests
+//! hold off for as long; see [`retry_after`].
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+const REMAINING_HEADERS: &[&str] = &["x-ratelimit-remaining", "ratelimit-remaining"];
+const LIMIT_HEADERS: &[&str] = &["x-ratelimit-limit", "ratelimit-limit"];
+const RESET_HEADERS: &[&str] = &["x-ratelimit-reset", "ratelimit-reset"];
+
+/// Values above this are treated as a unix timestamp rather than seconds-until-reset.
+const EPOCH_THRESHOLD: u64 = 1_000_000_000;
+
+/// Start pacing once the remaining budget drops below this share of the limit.
+const LOW_WATER_RATIO: f64 = 0.1;
+
+/// Never sleep longer than this for a single request.
+pub const MAX_WAIT: Duration = Duration::from_secs(300);
+
+/// Seconds until a reset, or a unix timestamp of it.
+fn reset_delay(value: u64) -> Duration {
+    if value > EPOCH_THRESHOLD {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Duration::from_secs(value.saturating_sub(now))
+    } else {
+        Duration::from_secs(value)
+    }
+}
+
+/// How long a throttled (429) response asks to wait: `Retry-After` in
+/// seconds or as an HTTP date, else the rate-limit reset header.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    if let Some(value) = headers
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+    {
+        if let Ok(secs) = value.parse::<u64>() {
+            return Some(Duration::from_secs(secs));
+        }
+        if let Ok(at) = chrono::DateTime::parse_from_rfc2822(value) {
+            let wait = at.with_timezone(&chrono::Utc) - chrono::Utc::now();
+            return Some(wait.to_std().unwrap_or_default());
+        }
+    }
+    RESET_HEADERS.iter().find_map(|name| {
+        let value = headers.get(*name)?.to_str().ok()?;
+        let value = value.split([',', ';']).next()?.trim().parse().ok()?;
+        Some(reset_delay(value))
+    })
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RateBudget {
+    pub remaining: Option<u64>,
+    pub limit: Option<u64>,
+    /// Seconds until the window resets, as reported by the server.
+    pub reset_in: Option<Duration>,
+}
+
+impl RateBudget {
+    /// Parse rate-limit headers; `None` if the response carries none.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let num = |names: &[&str]| {
+            names.iter().find_map(|n| {
+                headers
+                    .get(*n)
+                    .and_then(|v| v.to_str().ok())
+                    // Some servers send `remaining;w=60` style values
+                    .and_then(|s| s.split([',', ';']).next())
+                    .and_then(|s| s.trim().parse::<u64>().ok())
+            })
+        };
+
+        let remaining = Some(num(REMAINING_HEADERS)?);
+        let reset_in = num(RESET_HEADERS).map(reset_delay);
+        Some(Self {
+            remaining,
+            limit: num(LIMIT_HEADERS),
+            reset_in,
+        })
+    }
+
+    /// Delay before the next request given this budget.
+    pub fn pacing_delay(&self) -> Duration {
+        let (Some(remaining), Some(reset_in)) = (self.remaining, self.reset_in) else {
+            return Duration::ZERO;
+        };
+        if remaining == 0 {
+            return reset_in.min(MAX_WAIT);
+        }
+        let low_water = self
+            .limit
+            .map(|l| ((l as f64) * LOW_WATER_RATIO).ceil() as u64)
+            .unwrap_or(1)
+            .max(1);
+        if remaining > low_water {
+            return Duration::ZERO;
+        }
+        // Spread what is left evenly over the rest of the window.
+        (reset_in / (remaining as u32).max(1)).min(MAX_WAIT)
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    budget: Option<RateBudget>,
+    /// When the next request may be sent.
+    not_before: Option<Instant>,
+    /// Gap kept between requests while the budget is low.
+    interval: Duration,
+    throttled: Throttled,
+}
+
+/// 429 responses a source got, and the time spent waiting them out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Throttled {
+    pub responses: usize,
+    pub waited: Duration,
+}
+
+/// Shared per-source throttle fed by response headers.
+#[derive(Debug)]
+pub struct ServerThrottle {
+    source: String,
+    state: Mutex<State>,
+}
+
+impl ServerThrottle {
+    pub fn new(source: impl Into<String>) -> Self {
+        Self {
+            source: source.into(),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Last budget seen from the server.
+    pub async fn budget(&self) -> Option<RateBudget> {
+        self.state.lock().await.budget
+    }
+
+    /// Record the budget advertised by a response.
+    pub async fn observe(&self, headers: &HeaderMap) {
+        let Some(budget) = RateBudget::from_headers(headers) else {
+            return;
+        };
+        debug!(
+            target: "apitap::metrics",
+            source = %self.source,
+            ratelimit_remaining = budget.remaining,
+            ratelimit_limit = budget.limit,
+            ratelimit_reset_secs = budget.reset_in.map(|d| d.as_secs()),
+            "rate limit budget"
+        );
+
+        let delay = budget.pacing_delay();
+        let mut state = self.state.lock().await;
+        state.budget = Some(budget);
+        state.interval = delay;
+        if delay > Duration::ZERO {
+            let until = Instant::now() + delay;
+            match state.not_before {
+                Some(t) if t >= until => {}
+                _ => state.not_before = Some(until),
+            }
+        }
+    }
+
+    /// Note a 429 that asked to wait `wait`; no request of the source is
+    /// sent before it is over.
+    pub async fn record_retry_after(&self, wait: Duration) {
+        let mut state = self.state.lock().await;
+        state.throttled.responses += 1;
+        state.throttled.waited += wait;
+        let until = Instant::now() + wait;
+        match state.not_before {
+            Some(t) if t >= until => {}
+            _ => state.not_before = Some(until),
+        }
+    }
+
+    /// The 429 responses recorded so far.
+    pub async fn throttled(&self) -> Throttled {
+        self.state.lock().await.throttled
+    }
+
+    /// Wait until the throttle allows the next request.
+    ///
+    /// Each caller takes the next free slot and, while the budget is low,
+    /// moves it on by the pacing interval, so concurrent fetches are sent
+    /// one interval apart instead of all at once when the wait is over.
+    pub async fn wait(&self) {
+        let until = {
+            let mut state = self.state.lock().await;
+            let now = Instant::now();
+            let slot = state.not_before.filter(|t| *t > now);
+            state.not_before = if state.interval > Duration::ZERO {
+                Some(slot.unwrap_or(now) + state.interval)
+            } else {
+                slot
+            };
+            match slot {
+                Some(t) => t,
+                None => return,
+            }
+        };
+        info!(
+            source = %self.source,
+            wait_ms = until.saturating_duration_since(Instant::now()).as_millis() as u64,
+            "rate limit budget low; slowing down"
+        );
+        tokio::time::sleep_until(until).await;
+    }
+}
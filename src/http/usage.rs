@@ -0,0 +1,47 @@
+//! Run-wide usage counters for metered APIs: requests sent and response
+//! body bytes read, summed over every source.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use futures::{Stream, StreamExt};
+
+#[derive(Debug, Default)]
+pub struct UsageMeter {
+    requests: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl UsageMeter {
+    /// Count one request sent: an attempt at a page or stream connection,
+    /// retries included, or a token request.
+    pub fn request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    /// Body bytes as delivered by the client, after transport decoding.
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Count the bytes of a body stream as they are read.
+    pub fn count<S, B, E>(
+        self: Arc<Self>,
+        stream: S,
+    ) -> impl Stream<Item = std::result::Result<B, E>>
+    where
+        S: Stream<Item = std::result::Result<B, E>>,
+        B: AsRef<[u8]>,
+    {
+        stream.inspect(move |chunk| {
+            if let Ok(bytes) = chunk {
+                self.bytes
+                    .fetch_add(bytes.as_ref().len() as u64, Ordering::Relaxed);
+            }
+        })
+    }
+}
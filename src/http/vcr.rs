@@ -0,0 +1,195 @@
+//! Recording page responses to disk and running from the recordings
+//! (`--record DIR` / `--replay DIR`).
+//!
+//! With `--record`, every page response a source gets (after retries) is
+//! written as one JSON file:
+//!
+//! ```text
+//! <dir>/<source>/<key>.json
+//! ```
+//!
+//! holding the method, URL, status, headers and body. The key hashes the
+//! method, the URL with its query, the request body and an event stream's
+//! `Last-Event-ID`, so the same request finds the same file whatever order
+//! concurrent pages complete in, and each reconnect of an event stream has
+//! its own.
+//! Credentials are kept out the way failure samples keep them out: sensitive
+//! headers and query parameters are redacted, and headers added by `auth:`
+//! are not part of the key.
+//!
+//! With `--replay`, page requests are answered from those files and never
+//! sent; a request without a recording fails. The rows then go through the
+//! modules and sinks as usual, so transforms can be debugged and tested
+//! against fixed responses. Templated query parameters that change from run
+//! to run, such as `{{ today() }}`, make requests that were not recorded.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use reqwest::{Request, Response, ResponseBuilderExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::debug;
+
+use crate::errors::{ApitapError, Result};
+use crate::http::failure_samples::{redact_headers, redact_url};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VcrMode {
+    Record,
+    Replay,
+}
+
+/// Where recordings are written to or read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vcr {
+    dir: PathBuf,
+    mode: VcrMode,
+}
+
+/// One response as written to disk.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    pub method: String,
+    pub url: String,
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    /// The decoded body; base64 when it is not UTF-8.
+    pub body: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub base64: bool,
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl Vcr {
+    pub fn new(dir: impl Into<PathBuf>, mode: VcrMode) -> Self {
+        Self {
+            dir: dir.into(),
+            mode,
+        }
+    }
+
+    pub fn mode(&self) -> VcrMode {
+        self.mode
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The recordings of `source`, in a directory of their own.
+    pub fn source(&self, source: &str) -> Self {
+        Self::new(self.dir.join(source), self.mode)
+    }
+
+    /// The file a response to `request` is kept in.
+    pub fn path(&self, request: &Request) -> PathBuf {
+        let mut hash = Sha256::new();
+        hash.update(request.method().as_str());
+        hash.update(b" ");
+        hash.update(redact_url(request.url()));
+        if let Some(body) = request.body().and_then(|body| body.as_bytes()) {
+            hash.update(b"\n");
+            hash.update(body);
+        }
+        if let Some(id) = request.headers().get("last-event-id") {
+            hash.update(b"\nLast-Event-ID: ");
+            hash.update(id.as_bytes());
+        }
+        let key: String = hash.finalize()[..8]
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        self.dir.join(format!("{key}.json"))
+    }
+
+    /// The recorded response to `request`.
+    pub fn replay(&self, request: &Request) -> Result<Response> {
+        let path = self.path(request);
+        let text = std::fs::read_to_string(&path).map_err(|e| {
+            ApitapError::PipelineError(format!(
+                "no recording of {} {} ({}: {e}); record it with --record",
+                request.method(),
+                redact_url(request.url()),
+                path.display()
+            ))
+        })?;
+        let recording: Recording = serde_json::from_str(&text).map_err(|e| {
+            ApitapError::PipelineError(format!("invalid recording {}: {e}", path.display()))
+        })?;
+        debug!(path = %path.display(), status = recording.status, "replaying recorded response");
+        recording.response(request.url().clone())
+    }
+
+    /// Write `resp` as the answer to `request`, returning the same response
+    /// read back from memory. A body cut off by the request timeout, as an
+    /// event stream kept open until `max_duration` is, is kept as far as it
+    /// was read.
+    pub async fn record(&self, request: &Request, resp: Response) -> Result<Response> {
+        let url = resp.url().clone();
+        let status = resp.status();
+        let headers = resp.headers().clone();
+        let mut body = Vec::new();
+        let mut chunks = resp.bytes_stream();
+        while let Some(chunk) = chunks.next().await {
+            match chunk {
+                Ok(chunk) => body.extend_from_slice(&chunk),
+                Err(e) if e.is_timeout() => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let (text, base64) = match std::str::from_utf8(&body) {
+            Ok(text) => (text.to_string(), false),
+            Err(_) => (
+                base64::engine::general_purpose::STANDARD.encode(&body),
+                true,
+            ),
+        };
+        let recording = Recording {
+            method: request.method().to_string(),
+            url: redact_url(request.url()),
+            status: status.as_u16(),
+            headers: redact_headers(&headers),
+            body: text,
+            base64,
+            recorded_at: Utc::now(),
+        };
+        let path = self.path(request);
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(&path, serde_json::to_vec_pretty(&recording)?)?;
+        debug!(path = %path.display(), status = status.as_u16(), "recorded response");
+
+        let mut builder = http::Response::builder().status(status).url(url);
+        if let Some(map) = builder.headers_mut() {
+            *map = headers;
+        }
+        builder
+            .body(body)
+            .map(Response::from)
+            .map_err(|e| ApitapError::PipelineError(format!("cannot rebuild response: {e}")))
+    }
+}
+
+impl Recording {
+    /// The response this recording holds, as if received from `url`.
+    pub fn response(&self, url: reqwest::Url) -> Result<Response> {
+        let body = if self.base64 {
+            base64::engine::general_purpose::STANDARD
+                .decode(&self.body)
+                .map_err(|e| ApitapError::PipelineError(format!("invalid recorded body: {e}")))?
+        } else {
+            self.body.clone().into_bytes()
+        };
+        let mut builder = http::Response::builder().status(self.status).url(url);
+        for (name, value) in &self.headers {
+            builder = builder.header(name, value);
+        }
+        builder
+            .body(body)
+            .map(Response::from)
+            .map_err(|e| ApitapError::PipelineError(format!("invalid recording: {e}")))
+    }
+}
@@ -0,0 +1,186 @@
+//! Streaming XML responses (RSS/Atom feeds, sitemaps, SOAP-style APIs) as
+//! JSON rows.
+//!
+//! Every element matching the configured record becomes one object:
+//! attributes are keyed `@name`, child elements by their (prefixed) name,
+//! repeated children turn into arrays and text next to attributes or
+//! children goes under `#text`. Leaf elements become plain strings, empty
+//! ones null; a record that is itself a leaf is emitted as `{"#text": ...}`.
+//! Values are never typed; cast them in the SQL module.
+
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt};
+use quick_xml::escape::resolve_predefined_entity;
+use quick_xml::events::{BytesStart, Event};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tokio_util::io::StreamReader;
+
+use crate::errors::Result;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct XmlOptions {
+    /// Element emitted as a row: a name matched at any depth (`item`,
+    /// `entry`, `url`) or a slash path from the root (`rss/channel/item`).
+    #[serde(default = "default_record")]
+    pub record: String,
+}
+
+fn default_record() -> String {
+    "item".to_string()
+}
+
+impl Default for XmlOptions {
+    fn default() -> Self {
+        Self {
+            record: default_record(),
+        }
+    }
+}
+
+/// Parse an XML byte stream into one JSON object per record element.
+pub fn xml_stream<S, B, E>(bytes: S, options: &XmlOptions) -> BoxStream<'static, Result<Value>>
+where
+    S: Stream<Item = std::result::Result<B, E>> + Send + 'static,
+    B: AsRef<[u8]> + Send,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let record = options.record.trim_start_matches('/').to_string();
+    let bytes = bytes.map(|chunk| {
+        chunk
+            .map(std::io::Cursor::new)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+    });
+
+    let s = async_stream::try_stream! {
+        let mut reader = quick_xml::Reader::from_reader(StreamReader::new(Box::pin(bytes)));
+        reader.config_mut().expand_empty_elements = true;
+        let mut buf = Vec::new();
+        // Elements enclosing the next record, and the record being built.
+        let mut path: Vec<String> = Vec::new();
+        let mut open: Vec<Element> = Vec::new();
+
+        loop {
+            match reader.read_event_into_async(&mut buf).await? {
+                Event::Start(start) => {
+                    let name = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+                    if !open.is_empty() || is_record(&record, &path, &name) {
+                        open.push(Element::new(name, &start)?);
+                    } else {
+                        path.push(name);
+                    }
+                }
+                Event::End(_) => match open.pop() {
+                    Some(element) => {
+                        let (name, value) = element.finish();
+                        match open.last_mut() {
+                            Some(parent) => parent.add_child(name, value),
+                            None => yield as_row(value),
+                        }
+                    }
+                    None => {
+                        path.pop();
+                    }
+                },
+                Event::Text(text) => {
+                    if let Some(element) = open.last_mut() {
+                        element.text.push_str(&text.xml_content().map_err(quick_xml::Error::from)?);
+                    }
+                }
+                Event::CData(cdata) => {
+                    if let Some(element) = open.last_mut() {
+                        element.text.push_str(&cdata.xml_content().map_err(quick_xml::Error::from)?);
+                    }
+                }
+                Event::GeneralRef(entity) => {
+                    if let Some(element) = open.last_mut() {
+                        match entity.resolve_char_ref()? {
+                            Some(ch) => element.text.push(ch),
+                            None => {
+                                let name = entity.decode().map_err(quick_xml::Error::from)?;
+                                match resolve_predefined_entity(&name) {
+                                    Some(resolved) => element.text.push_str(resolved),
+                                    // Entities declared in a DTD are kept as written.
+                                    None => element.text.push_str(&format!("&{name};")),
+                                }
+                            }
+                        }
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+    };
+    s.boxed()
+}
+
+/// Records are always objects, even when the element is a bare leaf.
+fn as_row(value: Value) -> Value {
+    match value {
+        Value::Object(_) => value,
+        Value::Null => Value::Object(Map::new()),
+        text => Value::Object(Map::from_iter([("#text".to_string(), text)])),
+    }
+}
+
+fn is_record(record: &str, path: &[String], name: &str) -> bool {
+    if !record.contains('/') {
+        return record == name;
+    }
+    let mut parts = record.split('/');
+    parts.next_back() == Some(name) && parts.eq(path.iter().map(String::as_str))
+}
+
+/// An element of the current record, collected until its end tag.
+struct Element {
+    name: String,
+    fields: Map<String, Value>,
+    text: String,
+}
+
+impl Element {
+    fn new(name: String, start: &BytesStart<'_>) -> Result<Self> {
+        let mut fields = Map::new();
+        for attr in start.attributes() {
+            let attr = attr.map_err(quick_xml::Error::from)?;
+            let key = format!("@{}", String::from_utf8_lossy(attr.key.as_ref()));
+            fields.insert(key, Value::String(attr.unescape_value()?.into_owned()));
+        }
+        Ok(Self {
+            name,
+            fields,
+            text: String::new(),
+        })
+    }
+
+    fn add_child(&mut self, name: String, value: Value) {
+        match self.fields.get_mut(&name) {
+            Some(Value::Array(items)) => items.push(value),
+            Some(existing) => {
+                let first = existing.take();
+                *existing = Value::Array(vec![first, value]);
+            }
+            None => {
+                self.fields.insert(name, value);
+            }
+        }
+    }
+
+    fn finish(mut self) -> (String, Value) {
+        let text = self.text.trim();
+        let value = match (self.fields.is_empty(), text.is_empty()) {
+            (true, true) => Value::Null,
+            (true, false) => Value::String(text.to_string()),
+            (false, empty) => {
+                if !empty {
+                    self.fields
+                        .insert("#text".to_string(), Value::String(text.to_string()));
+                }
+                Value::Object(self.fields)
+            }
+        };
+        (self.name, value)
+    }
+}
@@ -59,5 +59,6 @@ pub mod errors;
 pub mod http;
 pub mod log;
 pub mod pipeline;
+pub mod transform;
 pub mod utils;
 pub mod writer;
@@ -0,0 +1,147 @@
+//! JSON Lines log output with a fixed envelope.
+//!
+//! Every event becomes one JSON object with the same top-level keys:
+//! `timestamp`, `level`, `target`, `run_id`, `module`, `source`, `event`,
+//! `message` and `fields`. `run_id`, `module` and `source` come from the
+//! enclosing spans (or the event itself) and are `null` outside of them;
+//! `event` names structured events such as `run_completed`. The remaining
+//! event fields go under `fields`, so log pipelines can filter on the
+//! envelope without parsing messages.
+
+use std::io::Write;
+
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Span (or event) fields lifted into the envelope.
+pub const ENVELOPE_FIELDS: &[&str] = &["run_id", "module", "source"];
+
+/// Layer writing one envelope per event to `writer`.
+pub struct EnvelopeLayer<W> {
+    writer: W,
+}
+
+impl<W> EnvelopeLayer<W>
+where
+    W: for<'a> MakeWriter<'a> + 'static,
+{
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+/// Fields recorded on a span, kept in its extensions.
+struct SpanFields(Map<String, Value>);
+
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        self.0
+            .insert(field.name().to_string(), Value::from(value.to_string()));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), Value::from(format!("{value:?}")));
+    }
+}
+
+impl<S, W> Layer<S> for EnvelopeLayer<W>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'a> MakeWriter<'a> + 'static,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = Map::new();
+        attrs.record(&mut JsonVisitor(&mut fields));
+        span.extensions_mut().insert(SpanFields(fields));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(SpanFields(fields)) = extensions.get_mut::<SpanFields>() {
+            values.record(&mut JsonVisitor(fields));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = Map::new();
+        event.record(&mut JsonVisitor(&mut fields));
+        let meta = event.metadata();
+
+        let mut envelope = Map::new();
+        envelope.insert(
+            "timestamp".to_string(),
+            Value::from(chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)),
+        );
+        envelope.insert("level".to_string(), Value::from(meta.level().as_str()));
+        envelope.insert("target".to_string(), Value::from(meta.target()));
+        for key in ENVELOPE_FIELDS {
+            envelope.insert(key.to_string(), Value::Null);
+        }
+        // Outer spans first, so the innermost value wins.
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if let Some(SpanFields(span_fields)) = span.extensions().get::<SpanFields>() {
+                    for key in ENVELOPE_FIELDS {
+                        if let Some(value) = span_fields.get(*key) {
+                            envelope.insert(key.to_string(), value.clone());
+                        }
+                    }
+                }
+            }
+        }
+        for key in ENVELOPE_FIELDS {
+            if let Some(value) = fields.remove(*key) {
+                envelope.insert(key.to_string(), value);
+            }
+        }
+        envelope.insert(
+            "event".to_string(),
+            fields.remove("event").unwrap_or(Value::Null),
+        );
+        envelope.insert(
+            "message".to_string(),
+            fields.remove("message").unwrap_or(Value::Null),
+        );
+        envelope.insert("fields".to_string(), Value::Object(fields));
+
+        let Ok(mut line) = serde_json::to_vec(&Value::Object(envelope)) else {
+            return;
+        };
+        line.push(b'\n');
+        let _ = self.writer.make_writer_for(meta).write_all(&line);
+    }
+}
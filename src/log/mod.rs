@@ -1,4 +1,7 @@
 // tracing_setup.rs
+pub mod envelope;
+
+use envelope::EnvelopeLayer;
 use tracing_error::ErrorLayer;
 use tracing_subscriber::{fmt, layer::SubscriberExt, EnvFilter, Registry};
 
@@ -21,8 +24,8 @@ pub fn init_tracing() {
 /// Initialize tracing with explicit options.
 ///
 /// - `level`: optional log level string (e.g., "info", "debug,crate=trace"). If `None`, falls
-///    back to `RUST_LOG` or `info` as before.
-/// - `use_json`: if true, enable JSON formatter.
+///   back to `RUST_LOG` or `info` as before.
+/// - `use_json`: if true, write JSON Lines in the [`envelope`] format.
 pub fn init_tracing_with(level: Option<&str>, use_json: bool) {
     // Allow explicit level override, else fall back to RUST_LOG / default
     let filter = match level {
@@ -33,13 +36,7 @@ pub fn init_tracing_with(level: Option<&str>, use_json: bool) {
     if use_json {
         let subscriber = Registry::default()
             .with(filter)
-            .with(
-                fmt::layer()
-                    .json()
-                    .with_target(false)
-                    .with_file(false)
-                    .with_line_number(false),
-            )
+            .with(EnvelopeLayer::new(std::io::stdout))
             .with(ErrorLayer::default());
 
         tracing::subscriber::set_global_default(subscriber)
@@ -1,5 +1,14 @@
 use apitap::{
-    cmd::{run_pipeline, Cli},
+    cmd::{
+        completions::{run_completions_command, run_man_command},
+        config::run_config_command,
+        describe::run_describe_command,
+        list::run_list_command,
+        run_pipeline_with,
+        runs::run_runs_command,
+        state::run_state_command,
+        Cli, Command,
+    },
     log,
 };
 use clap::Parser;
@@ -12,7 +21,19 @@ async fn main() -> ExitCode {
     let cli = Cli::parse();
     log::init_tracing_with(cli.log_level.as_deref(), cli.log_json);
 
-    match run_pipeline(&cli.modules, &cli.yaml_config).await {
+    let result = match &cli.command {
+        Some(Command::State { action }) => run_state_command(&cli.state, action).await,
+        Some(Command::Config { action }) => run_config_command(&cli.yaml_config, action),
+        Some(Command::List { output }) => run_list_command(&cli.modules, &cli.yaml_config, *output),
+        Some(Command::Describe { output }) => {
+            run_describe_command(&cli.modules, &cli.yaml_config, *output)
+        }
+        Some(Command::Runs { action }) => run_runs_command(&cli.state, action).await,
+        Some(Command::Completions { shell }) => run_completions_command(*shell),
+        Some(Command::Man { out_dir }) => run_man_command(out_dir.as_deref()),
+        None => run_pipeline_with(&cli.modules, &cli.yaml_config, &cli.run_options()).await,
+    };
+    match result {
         Ok(_) => ExitCode::SUCCESS,
         Err(_) => ExitCode::from(1),
     }
@@ -0,0 +1,178 @@
+//! A run audit table written to one of the config's targets.
+//!
+//! ```yaml
+//! audit:
+//!   sink: warehouse              # a target name
+//!   table: _apitap_runs          # default
+//! ```
+//!
+//! Every module the run started adds a row: run id, module, source, when it
+//! started and finished, its status, pages fetched, failed pages, rows and
+//! the error that stopped it. A module skipped by `--skip-if-fresh` is
+//! recorded with status `skipped`. Rows are appended once, when the run ends, so
+//! a run that fails half way still records the modules it got through and
+//! the one that failed. Writing the audit never fails the run.
+
+use std::sync::Arc;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::stream;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::info;
+
+use crate::errors::{ApitapError, Result};
+use crate::http::fetcher::FetchStats;
+use crate::pipeline::sink::WriterOpts;
+use crate::utils::datafusion_ext::QueryResultStream;
+use crate::writer::coercion::CoercionPolicy;
+use crate::writer::{DataWriter, WriteMode};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditConfig {
+    /// Target the audit rows are written to.
+    pub sink: String,
+    #[serde(default = "default_table")]
+    pub table: String,
+}
+
+fn default_table() -> String {
+    "_apitap_runs".to_string()
+}
+
+impl AuditConfig {
+    /// Appends to `table`, creating it on first use.
+    pub fn writer_opts(&self) -> WriterOpts<'_> {
+        WriterOpts {
+            dest_table: &self.table,
+            primary_key: None,
+            infer_primary_key: false,
+            batch_size: 50,
+            sample_size: 10,
+            auto_create: true,
+            auto_truncate: false,
+            truncate_first: false,
+            write_mode: WriteMode::Append,
+            mutation_report: false,
+            binary_columns: Vec::new(),
+            compressed_columns: Vec::new(),
+            explain_first_batch: false,
+            coercion_policy: CoercionPolicy::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModuleOutcome {
+    Succeeded,
+    Failed,
+    /// Not fetched: the destination was fresh under `--skip-if-fresh`.
+    Skipped,
+}
+
+/// One module of a run, as recorded in the audit table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleAudit {
+    pub run_id: String,
+    pub module: String,
+    pub source: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub outcome: ModuleOutcome,
+    pub pages: usize,
+    pub failed_pages: usize,
+    pub rows: usize,
+    pub error: Option<String>,
+}
+
+impl ModuleAudit {
+    pub fn start(run_id: &str, module: &str, source: &str) -> Self {
+        Self {
+            run_id: run_id.to_string(),
+            module: module.to_string(),
+            source: source.to_string(),
+            started_at: Utc::now(),
+            finished_at: None,
+            // Until it is finished, failed or skipped.
+            outcome: ModuleOutcome::Failed,
+            pages: 0,
+            failed_pages: 0,
+            rows: 0,
+            error: None,
+        }
+    }
+
+    /// The module ran to the end; failed pages mark it failed.
+    pub fn finished(self, stats: &FetchStats) -> Self {
+        Self {
+            finished_at: Some(Utc::now()),
+            outcome: if stats.error_count == 0 {
+                ModuleOutcome::Succeeded
+            } else {
+                ModuleOutcome::Failed
+            },
+            pages: stats.success_count,
+            failed_pages: stats.error_count,
+            rows: stats.total_items,
+            ..self
+        }
+    }
+
+    /// The module was not fetched because its destination was fresh.
+    pub fn skipped(self) -> Self {
+        Self {
+            finished_at: Some(Utc::now()),
+            outcome: ModuleOutcome::Skipped,
+            ..self
+        }
+    }
+
+    /// The module was stopped by `error`.
+    pub fn failed(self, error: &ApitapError) -> Self {
+        Self {
+            finished_at: Some(Utc::now()),
+            outcome: ModuleOutcome::Failed,
+            error: Some(error.to_string()),
+            ..self
+        }
+    }
+
+    /// The audit table's row. Every column always holds a value of the same
+    /// type, so sinks that infer the schema from a sample get it right.
+    pub fn to_row(&self) -> Value {
+        let at = |t: DateTime<Utc>| t.to_rfc3339_opts(SecondsFormat::Millis, true);
+        json!({
+            "run_id": self.run_id,
+            "module": self.module,
+            "source": self.source,
+            "started_at": at(self.started_at),
+            "finished_at": at(self.finished_at.unwrap_or(self.started_at)),
+            "status": self.outcome,
+            "pages": self.pages,
+            "failed_pages": self.failed_pages,
+            "rows": self.rows,
+            "error": self.error.as_deref().unwrap_or(""),
+        })
+    }
+}
+
+/// Append `modules` to the audit table through `writer`.
+pub async fn write_audit(
+    table: &str,
+    modules: &[ModuleAudit],
+    writer: Arc<dyn DataWriter>,
+) -> Result<()> {
+    if modules.is_empty() {
+        return Ok(());
+    }
+    let rows: Vec<Value> = modules.iter().map(ModuleAudit::to_row).collect();
+    let result = QueryResultStream {
+        table_name: table.to_string(),
+        data: Box::pin(stream::iter(rows.into_iter().map(Ok))),
+    };
+    writer.write_stream(result, WriteMode::Append).await?;
+    writer.commit().await?;
+    info!(%table, modules = modules.len(), "📒 run audit written");
+    Ok(())
+}
@@ -0,0 +1,65 @@
+//! Target connections shared by the modules of one run.
+//!
+//! Each sink is connected once and reused by every module that writes to it.
+//! Before a module runs its connection is pinged; a stale one (database
+//! restart, idle timeout, expired session) is closed and transparently
+//! replaced. [`TargetConnections::close_all`] drains and closes everything on
+//! completion, failure or shutdown.
+
+use std::collections::HashMap;
+
+use tracing::{debug, info, warn};
+
+use crate::errors::Result;
+use crate::pipeline::{SinkConn, Target, TargetConn};
+
+#[derive(Debug, Default)]
+pub struct TargetConnections {
+    conns: HashMap<String, TargetConn>,
+}
+
+impl TargetConnections {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A healthy connection for `target`, connecting or reconnecting as needed.
+    pub async fn acquire(&mut self, name: &str, target: &Target) -> Result<&TargetConn> {
+        if let Some(conn) = self.conns.get(name) {
+            match conn.ping().await {
+                Ok(()) => {
+                    debug!(sink = %name, "reusing target connection");
+                }
+                Err(e) => {
+                    warn!(sink = %name, error = %e, "target connection is stale; reconnecting");
+                    if let Some(stale) = self.conns.remove(name) {
+                        stale.close().await;
+                    }
+                }
+            }
+        }
+
+        if !self.conns.contains_key(name) {
+            let conn = target.create_conn().await?;
+            debug!(sink = %name, "connected to target");
+            self.conns.insert(name.to_string(), conn);
+        }
+        Ok(&self.conns[name])
+    }
+
+    pub fn len(&self) -> usize {
+        self.conns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.conns.is_empty()
+    }
+
+    /// Drain and close every open connection.
+    pub async fn close_all(&mut self) {
+        for (name, conn) in self.conns.drain() {
+            conn.close().await;
+            info!(sink = %name, "closed target connection");
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Replication consistency checks across the sinks of a fanned-out module.
+//!
+//! With `consistency_check` on a source, every table the module wrote to is
+//! compared against the first one after the load: total row counts, and a
+//! random sample of keys from the first table looked up in the others.
+//! Meant for migrations where several statements copy the same rows into
+//! different warehouses.
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::errors::{ApitapError, Result};
+use crate::writer::DataWriter;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsistencyCheck {
+    /// Key column sampled and looked up; defaults to `primary_key_in_dest`.
+    #[serde(default)]
+    pub key: Option<String>,
+    /// Number of keys sampled from the first table.
+    #[serde(default = "default_sample_keys")]
+    pub sample_keys: usize,
+    /// Fail the run (after all modules ran) when the sinks diverge.
+    #[serde(default)]
+    pub fail_on_divergence: bool,
+}
+
+fn default_sample_keys() -> usize {
+    100
+}
+
+/// What one sink looked like next to the reference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SinkConsistency {
+    pub label: String,
+    /// `None` when the sink cannot count rows.
+    pub rows: Option<u64>,
+    /// Sampled keys absent from this sink; `None` when it cannot look keys up.
+    pub missing_keys: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsistencyReport {
+    pub reference: SinkConsistency,
+    pub sampled_keys: usize,
+    pub others: Vec<SinkConsistency>,
+}
+
+impl ConsistencyReport {
+    /// Sinks whose row count differs from the reference or that miss
+    /// sampled keys. Unsupported measurements never count as divergence.
+    pub fn diverged(&self) -> Vec<&SinkConsistency> {
+        self.others
+            .iter()
+            .filter(|sink| {
+                let count_differs = matches!(
+                    (self.reference.rows, sink.rows),
+                    (Some(a), Some(b)) if a != b
+                );
+                count_differs || sink.missing_keys.is_some_and(|n| n > 0)
+            })
+            .collect()
+    }
+
+    pub fn log(&self) {
+        for sink in std::iter::once(&self.reference).chain(&self.others) {
+            info!(
+                sink = %sink.label,
+                rows = ?sink.rows,
+                missing_keys = ?sink.missing_keys,
+                sampled_keys = self.sampled_keys,
+                "consistency check"
+            );
+        }
+        for sink in self.diverged() {
+            warn!(
+                reference = %self.reference.label,
+                sink = %sink.label,
+                reference_rows = ?self.reference.rows,
+                rows = ?sink.rows,
+                missing_keys = ?sink.missing_keys,
+                "⚠️ sinks diverged"
+            );
+        }
+    }
+}
+
+/// Compare every sink with the first one. `key` is the resolved key column.
+pub async fn check_consistency(
+    check: &ConsistencyCheck,
+    key: Option<&str>,
+    sinks: &[(String, Arc<dyn DataWriter>)],
+) -> Result<ConsistencyReport> {
+    let Some(((ref_label, ref_writer), others)) = sinks.split_first() else {
+        return Err(ApitapError::ConfigError(
+            "consistency_check needs at least one sink".to_string(),
+        ));
+    };
+
+    if others.is_empty() {
+        warn!(sink = %ref_label, "consistency_check needs two or more sinks to compare");
+    }
+
+    let mut sample = match key {
+        Some(key) if check.sample_keys > 0 => ref_writer
+            .sample_keys(key, check.sample_keys)
+            .await?
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    // Keys found are counted distinct, so a non-unique key must not repeat.
+    sample.sort_unstable();
+    sample.dedup();
+
+    let reference = SinkConsistency {
+        label: ref_label.clone(),
+        rows: ref_writer.row_count().await?,
+        missing_keys: None,
+    };
+    let mut report = ConsistencyReport {
+        reference,
+        sampled_keys: sample.len(),
+        others: Vec::with_capacity(others.len()),
+    };
+    for (label, writer) in others {
+        let missing_keys = match key {
+            Some(key) if !sample.is_empty() => writer
+                .count_keys(key, &sample)
+                .await?
+                .map(|found| (sample.len() as u64).saturating_sub(found)),
+            _ => None,
+        };
+        report.others.push(SinkConsistency {
+            label: label.clone(),
+            rows: writer.row_count().await?,
+            missing_keys,
+        });
+    }
+    Ok(report)
+}
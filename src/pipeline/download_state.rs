@@ -0,0 +1,96 @@
+//! How far a single-response export got, persisted across process restarts.
+//!
+//! A module with `ndjson: { checkpoint_every: N }` saves the number of lines
+//! (and, when the server serves byte ranges, body bytes) written after every
+//! `N` lines. With `--resume` the next run continues the download from there
+//! instead of starting a multi-hour export over; the checkpoint is dropped
+//! once the download completes.
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::pipeline::retry_state::RetryStateStore;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DownloadCheckpoint {
+    /// Request URL, query included, the offsets belong to.
+    pub url: String,
+    /// Lines read and written so far.
+    pub lines: u64,
+    /// Body bytes spanned by those lines; only recorded when the server
+    /// accepts byte ranges and the body is not transfer-encoded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
+    /// `ETag` or `Last-Modified` of the response, used to tell whether the
+    /// export is still the same one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validator: Option<String>,
+    pub saved_at: DateTime<Utc>,
+}
+
+/// Per-module handle the export reader uses to save and resume progress.
+#[derive(Debug, Clone)]
+pub struct DownloadTracker {
+    store: Arc<RetryStateStore>,
+    module: String,
+    resume: bool,
+}
+
+impl DownloadTracker {
+    pub fn new(store: Arc<RetryStateStore>, module: impl Into<String>, resume: bool) -> Self {
+        Self {
+            store,
+            module: module.into(),
+            resume,
+        }
+    }
+
+    /// Where to pick up a download of `url`; `None` unless resuming a
+    /// checkpoint taken for the same URL.
+    pub async fn resume_point(&self, url: &str) -> Option<DownloadCheckpoint> {
+        if !self.resume {
+            return None;
+        }
+        let checkpoint = self.store.download_checkpoint(&self.module).await?;
+        if checkpoint.url != url {
+            warn!(
+                module = %self.module,
+                checkpoint_url = %checkpoint.url,
+                "download checkpoint is for another URL; starting over"
+            );
+            return None;
+        }
+        info!(
+            module = %self.module,
+            lines = checkpoint.lines,
+            bytes = ?checkpoint.bytes,
+            "resuming download from checkpoint"
+        );
+        Some(checkpoint)
+    }
+
+    pub async fn save(&self, checkpoint: DownloadCheckpoint) {
+        let lines = checkpoint.lines;
+        if let Err(e) = self
+            .store
+            .save_download_checkpoint(&self.module, Some(checkpoint))
+            .await
+        {
+            warn!(module = %self.module, lines, error = %e, "could not persist download checkpoint");
+        }
+    }
+
+    /// Forget the checkpoint after the download completed.
+    pub async fn finish(&self) {
+        if let Err(e) = self
+            .store
+            .save_download_checkpoint(&self.module, None)
+            .await
+        {
+            warn!(module = %self.module, error = %e, "could not persist download checkpoint");
+        }
+    }
+}
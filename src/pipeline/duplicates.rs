@@ -0,0 +1,112 @@
+//! Duplicate keys piling up in tables loaded without merging.
+//!
+//! Snapshot tables and sinks that cannot merge append every run's rows, so a
+//! key the API returns on two runs ends up twice in the table. With
+//! `duplicate_check` on a source, every table the module wrote to is
+//! grouped by the key after the load and the repeated keys are counted.
+//! Snapshot tables are grouped by key and `snapshot_ts`, since each snapshot
+//! is expected to hold every key once.
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::errors::Result;
+use crate::pipeline::snapshot::SNAPSHOT_COLUMN;
+use crate::writer::DataWriter;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DuplicateCheck {
+    /// Key column; defaults to `primary_key_in_dest`.
+    #[serde(default)]
+    pub key: Option<String>,
+    /// Fail the run (after all modules ran) when a table holds duplicates.
+    #[serde(default)]
+    pub fail_on_duplicates: bool,
+}
+
+/// Keys present more than once in a table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DuplicateKeys {
+    /// Distinct keys with more than one row.
+    pub keys: u64,
+    /// Rows beyond the first for those keys.
+    pub extra_rows: u64,
+}
+
+/// What one table looked like. `duplicates` is `None` when the sink cannot
+/// group rows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableDuplicates {
+    pub label: String,
+    pub duplicates: Option<DuplicateKeys>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateReport {
+    /// Columns the rows were grouped by.
+    pub columns: Vec<String>,
+    pub tables: Vec<TableDuplicates>,
+}
+
+impl DuplicateReport {
+    /// Extra rows over all tables.
+    pub fn extra_rows(&self) -> u64 {
+        self.tables
+            .iter()
+            .filter_map(|t| t.duplicates)
+            .map(|d| d.extra_rows)
+            .sum()
+    }
+
+    pub fn log(&self, module: &str, snapshot: bool) {
+        for table in &self.tables {
+            match table.duplicates {
+                Some(d) if d.keys > 0 => warn!(
+                    module,
+                    table = %table.label,
+                    columns = ?self.columns,
+                    keys = d.keys,
+                    extra_rows = d.extra_rows,
+                    hint = if snapshot {
+                        "the source returned keys twice within one run; check the pagination"
+                    } else {
+                        "set primary_key_in_dest to merge instead of appending, or dedupe the table"
+                    },
+                    "⚠️ duplicate keys"
+                ),
+                Some(_) => info!(module, table = %table.label, "no duplicate keys"),
+                None => info!(
+                    module,
+                    table = %table.label,
+                    "sink cannot check for duplicate keys"
+                ),
+            }
+        }
+    }
+}
+
+/// Count repeated `key`s in every table; snapshot tables also group by
+/// their snapshot column.
+pub async fn check_duplicates(
+    key: &str,
+    snapshot: bool,
+    tables: &[(String, Arc<dyn DataWriter>)],
+) -> Result<DuplicateReport> {
+    let mut columns = vec![key.to_string()];
+    if snapshot {
+        columns.push(SNAPSHOT_COLUMN.to_string());
+    }
+    let mut report = DuplicateReport {
+        columns,
+        tables: Vec::with_capacity(tables.len()),
+    };
+    for (label, writer) in tables {
+        report.tables.push(TableDuplicates {
+            label: label.clone(),
+            duplicates: writer.duplicate_keys(&report.columns).await?,
+        });
+    }
+    Ok(report)
+}
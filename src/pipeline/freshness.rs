@@ -0,0 +1,142 @@
+//! Module freshness contracts.
+//!
+//! A module declares `{{ freshness(max_age="6h") }}`; before it runs, the
+//! newest `_loaded_at` in its destination table is compared against the
+//! window. With `--skip-if-fresh` (scheduled runs) a fresh module is skipped;
+//! a stale one is reported as a warning, or fails the run when the contract
+//! uses `severity="error"`.
+//!
+//! The default column is the one a target writes with
+//! `metadata_columns: [_loaded_at]`; a table without it (or another
+//! `column=` set on the contract) has unknown freshness and never counts
+//! as stale.
+
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{ApitapError, Result};
+use crate::writer::metadata_columns::MetadataColumn;
+
+pub const DEFAULT_FRESHNESS_COLUMN: &str = MetadataColumn::LoadedAt.name();
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FreshnessSeverity {
+    #[default]
+    Warn,
+    Error,
+}
+
+impl std::str::FromStr for FreshnessSeverity {
+    type Err = ApitapError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "warn" | "warning" => Ok(Self::Warn),
+            "error" | "fail" => Ok(Self::Error),
+            other => Err(ApitapError::ConfigError(format!(
+                "unknown freshness severity '{other}' (expected warn or error)"
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Freshness {
+    pub max_age: Duration,
+    /// Timestamp column holding the load time.
+    pub column: String,
+    pub severity: FreshnessSeverity,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FreshnessStatus {
+    Fresh {
+        age: Duration,
+    },
+    Stale {
+        age: Duration,
+    },
+    /// Table missing, empty, or the sink cannot report load times.
+    Unknown,
+}
+
+impl Freshness {
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            max_age,
+            column: DEFAULT_FRESHNESS_COLUMN.to_string(),
+            severity: FreshnessSeverity::default(),
+        }
+    }
+
+    pub fn evaluate(&self, newest: Option<DateTime<Utc>>, now: DateTime<Utc>) -> FreshnessStatus {
+        let Some(newest) = newest else {
+            return FreshnessStatus::Unknown;
+        };
+        // Clock skew can put the newest load slightly in the future.
+        let age = (now - newest).to_std().unwrap_or(Duration::ZERO);
+        if age <= self.max_age {
+            FreshnessStatus::Fresh { age }
+        } else {
+            FreshnessStatus::Stale { age }
+        }
+    }
+}
+
+/// Parse durations like `45s`, `30m`, `6h`, `1d`, `2w` or `1h30m`.
+pub fn parse_duration(text: &str) -> Result<Duration> {
+    let invalid = || ApitapError::ConfigError(format!("invalid duration '{text}'"));
+    let mut total = 0u64;
+    let mut digits = String::new();
+    let mut seen_unit = false;
+
+    for c in text.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let secs = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3_600,
+            'd' => 86_400,
+            'w' => 604_800,
+            _ => return Err(invalid()),
+        };
+        let n: u64 = digits.parse().map_err(|_| invalid())?;
+        total = n
+            .checked_mul(secs)
+            .and_then(|v| total.checked_add(v))
+            .ok_or_else(invalid)?;
+        digits.clear();
+        seen_unit = true;
+    }
+
+    if !digits.is_empty() || !seen_unit {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(total))
+}
+
+/// Parse a load timestamp as returned by a sink (RFC 3339 or SQL text, UTC if naive).
+pub fn parse_loaded_at(text: &str) -> Option<DateTime<Utc>> {
+    let text = text.trim();
+    if let Ok(ts) = DateTime::parse_from_rfc3339(text) {
+        return Some(ts.with_timezone(&Utc));
+    }
+    // Postgres timestamptz text: `2024-01-01 12:00:00.123+00`
+    for fmt in ["%Y-%m-%d %H:%M:%S%.f%#z", "%Y-%m-%d %H:%M:%S%.f%:z"] {
+        if let Ok(ts) = DateTime::parse_from_str(text, fmt) {
+            return Some(ts.with_timezone(&Utc));
+        }
+    }
+    for fmt in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"] {
+        if let Ok(ts) = NaiveDateTime::parse_from_str(text, fmt) {
+            return Some(ts.and_utc());
+        }
+    }
+    None
+}
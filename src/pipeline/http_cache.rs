@@ -0,0 +1,121 @@
+//! Conditional page requests for sources with `http_cache: true`.
+//!
+//! The `ETag` and `Last-Modified` of every page fetched are kept in the
+//! state store, keyed by module and page URL, and sent back as
+//! `If-None-Match` / `If-Modified-Since` on the next run. A page the server
+//! answers with `304 Not Modified` is not written again; its row count from
+//! last time lets pagination carry on past it. Validators are only stored
+//! once the module's fetch succeeded, so a page that never reached the
+//! destination is not skipped next time.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::pipeline::retry_state::RetryStateStore;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PageValidator {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+    /// Rows the page held, for pagination to go on past it unchanged.
+    pub rows: usize,
+    pub saved_at: DateTime<Utc>,
+}
+
+impl PageValidator {
+    /// The validators of a response, if it has any.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+        if etag.is_none() && last_modified.is_none() {
+            return None;
+        }
+        Some(Self {
+            etag,
+            last_modified,
+            rows: 0,
+            saved_at: Utc::now(),
+        })
+    }
+
+    /// Headers asking the server to answer 304 if the page is unchanged.
+    pub fn conditional_headers(&self) -> Vec<(String, String)> {
+        let mut headers = Vec::new();
+        if let Some(etag) = &self.etag {
+            headers.push((IF_NONE_MATCH.to_string(), etag.clone()));
+        }
+        if let Some(date) = &self.last_modified {
+            headers.push((IF_MODIFIED_SINCE.to_string(), date.clone()));
+        }
+        headers
+    }
+}
+
+/// Per-module handle the fetcher uses to look up and keep page validators.
+#[derive(Debug, Clone)]
+pub struct HttpCache {
+    store: Arc<RetryStateStore>,
+    module: String,
+    /// Validators of pages fetched this run, stored by [`Self::commit`].
+    fetched: Arc<Mutex<Vec<(String, PageValidator)>>>,
+    unchanged: Arc<AtomicUsize>,
+}
+
+impl HttpCache {
+    pub fn new(store: Arc<RetryStateStore>, module: impl Into<String>) -> Self {
+        Self {
+            store,
+            module: module.into(),
+            fetched: Arc::default(),
+            unchanged: Arc::default(),
+        }
+    }
+
+    /// Validators kept for the page at `url`.
+    pub async fn validator(&self, url: &str) -> Option<PageValidator> {
+        self.store.page_validator(&self.module, url).await
+    }
+
+    /// Remember `validator` for the page at `url`, which held `rows` rows.
+    pub async fn fetched(&self, url: String, mut validator: PageValidator, rows: usize) {
+        validator.rows = rows;
+        self.fetched.lock().await.push((url, validator));
+    }
+
+    /// Count a page the server reported unchanged.
+    pub fn unchanged(&self) {
+        self.unchanged.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn unchanged_pages(&self) -> usize {
+        self.unchanged.load(Ordering::Relaxed)
+    }
+
+    /// Store the validators of this run's pages, once they were written.
+    pub async fn commit(&self) {
+        let fetched = std::mem::take(&mut *self.fetched.lock().await);
+        let pages = fetched.len();
+        if let Err(e) = self.store.save_page_validators(&self.module, fetched).await {
+            warn!(module = %self.module, error = %e, "could not persist page validators");
+            return;
+        }
+        let unchanged = self.unchanged_pages();
+        if pages > 0 || unchanged > 0 {
+            info!(module = %self.module, pages, unchanged, "http cache updated");
+        }
+    }
+}
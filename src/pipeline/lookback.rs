@@ -0,0 +1,126 @@
+//! Lookback windows for late-arriving records.
+//!
+//! ```yaml
+//! lookback: 2d
+//! ```
+//!
+//! Some APIs backfill or change records after an incremental cursor has
+//! passed them. A lookback moves the timestamp a run asks from back by a
+//! duration, so the trailing window is fetched again on every run and
+//! merged over the rows already loaded.
+//!
+//! Only timestamps can be moved, and re-fetched rows only replace the old
+//! ones in a table merged on a primary key; both are config errors
+//! otherwise, rather than a window that is skipped or loaded twice.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{ApitapError, Result};
+use crate::pipeline::freshness::parse_duration;
+use crate::writer::WriteMode;
+
+const NAIVE_FORMATS: [&str; 2] = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"];
+
+/// A duration such as `2d` to re-fetch before a timestamp.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Lookback {
+    text: String,
+    window: chrono::Duration,
+}
+
+impl Lookback {
+    pub fn parse(text: &str) -> Result<Self> {
+        let window = chrono::Duration::from_std(parse_duration(text)?)
+            .map_err(|_| ApitapError::ConfigError(format!("lookback '{text}' is too long")))?;
+        Ok(Self {
+            text: text.to_string(),
+            window,
+        })
+    }
+
+    pub fn window(&self) -> chrono::Duration {
+        self.window
+    }
+
+    /// `value` moved back by the window, written the way it came: RFC 3339
+    /// in UTC, a naive date-time or a date.
+    pub fn rewind(&self, value: &str) -> Result<String> {
+        let (at, shape) = TimeShape::parse(value).ok_or_else(|| {
+            ApitapError::ConfigError(format!(
+                "lookback needs a timestamp or date cursor, got '{value}'"
+            ))
+        })?;
+        let moved = at.checked_sub_signed(self.window).ok_or_else(|| {
+            ApitapError::ConfigError(format!(
+                "lookback '{}' moves '{value}' out of range",
+                self.text
+            ))
+        })?;
+        Ok(shape.format(moved))
+    }
+
+    /// Fail unless `source` merges on a primary key, so re-fetched rows
+    /// replace the ones already loaded instead of being added again.
+    pub fn check_merge(
+        &self,
+        source: &str,
+        write_mode: &WriteMode,
+        primary_key: Option<&str>,
+    ) -> Result<()> {
+        if *write_mode != WriteMode::Merge || primary_key.is_none() {
+            return Err(ApitapError::ConfigError(format!(
+                "source {source} has a lookback but does not merge on a primary key; \
+                 set primary_key_in_dest so the window replaces rows instead of duplicating them"
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<String> for Lookback {
+    type Error = ApitapError;
+
+    fn try_from(text: String) -> Result<Self> {
+        Self::parse(&text)
+    }
+}
+
+impl From<Lookback> for String {
+    fn from(lookback: Lookback) -> Self {
+        lookback.text
+    }
+}
+
+/// How a timestamp was written, to write a moved one the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TimeShape {
+    Rfc3339,
+    Naive(&'static str),
+    Date,
+}
+
+impl TimeShape {
+    fn parse(text: &str) -> Option<(DateTime<Utc>, Self)> {
+        if let Ok(at) = DateTime::parse_from_rfc3339(text) {
+            return Some((at.with_timezone(&Utc), Self::Rfc3339));
+        }
+        for format in NAIVE_FORMATS {
+            if let Ok(at) = NaiveDateTime::parse_from_str(text, format) {
+                return Some((at.and_utc(), Self::Naive(format)));
+            }
+        }
+        NaiveDate::parse_from_str(text, "%Y-%m-%d")
+            .ok()
+            .map(|day| (day.and_time(NaiveTime::MIN).and_utc(), Self::Date))
+    }
+
+    fn format(self, at: DateTime<Utc>) -> String {
+        match self {
+            Self::Rfc3339 => at.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            Self::Naive(format) => at.naive_utc().format(format).to_string(),
+            Self::Date => at.date_naive().to_string(),
+        }
+    }
+}
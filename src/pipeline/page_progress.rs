@@ -0,0 +1,108 @@
+//! Pages a module already wrote, persisted across process restarts.
+//!
+//! With a state store, every page whose rows a sink accepted is saved as
+//! soon as it is written, keyed by its request URL (query included) with the
+//! number of rows it held. When a run dies half way — a crash, Ctrl-C, a
+//! sink going away — `--resume` skips the pages saved for the module
+//! instead of fetching and merging them again; their row counts let
+//! pagination carry on past them. The record is dropped once the module
+//! finished without a failed page, and a run without `--resume` starts over.
+//!
+//! Pages are matched by URL, so a resumed run only skips pages it asks for
+//! the same way: an `incremental` watermark does not move until the module
+//! completes, but a `{{ today() }}` parameter may on the next day. Pages
+//! requested by number or offset are skipped, the first one included;
+//! pages reached by next links or cursors hold what leads to the following
+//! ones and are fetched again.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::pipeline::retry_state::RetryStateStore;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WrittenPage {
+    pub rows: usize,
+    pub written_at: DateTime<Utc>,
+}
+
+/// Per-module handle the fetcher uses to skip and record written pages.
+#[derive(Debug, Clone)]
+pub struct ProgressTracker {
+    store: Arc<RetryStateStore>,
+    module: String,
+    /// Pages written by the interrupted run being resumed.
+    done: Arc<BTreeMap<String, WrittenPage>>,
+    skipped: Arc<AtomicUsize>,
+}
+
+impl ProgressTracker {
+    /// Start tracking `module`: with `resume`, pick up the pages saved by
+    /// the run before; otherwise forget them.
+    pub async fn start(
+        store: Arc<RetryStateStore>,
+        module: impl Into<String>,
+        resume: bool,
+    ) -> Self {
+        let module = module.into();
+        let done = if resume {
+            store.written_pages(&module).await
+        } else {
+            if let Err(e) = store.clear_written_pages(&module).await {
+                warn!(%module, error = %e, "could not reset page progress");
+            }
+            BTreeMap::new()
+        };
+        if !done.is_empty() {
+            info!(%module, pages = done.len(), "resuming after pages already written");
+        }
+        Self {
+            store,
+            module,
+            done: Arc::new(done),
+            skipped: Arc::default(),
+        }
+    }
+
+    /// Rows of the page at `url` if the resumed run wrote it; it is then
+    /// counted as skipped.
+    pub fn written(&self, url: &str) -> Option<usize> {
+        let page = self.done.get(url)?;
+        self.skipped.fetch_add(1, Ordering::Relaxed);
+        Some(page.rows)
+    }
+
+    pub fn skipped_pages(&self) -> usize {
+        self.skipped.load(Ordering::Relaxed)
+    }
+
+    /// Save that the page at `url` was written with `rows` rows.
+    pub async fn page_written(&self, url: String, rows: usize) {
+        let page = WrittenPage {
+            rows,
+            written_at: Utc::now(),
+        };
+        if let Err(e) = self.store.save_written_page(&self.module, url, page).await {
+            warn!(module = %self.module, error = %e, "could not persist page progress");
+        }
+    }
+
+    /// The module ended; with every page fetched, its progress is dropped.
+    pub async fn finish(&self, complete: bool) {
+        let skipped = self.skipped_pages();
+        if skipped > 0 {
+            info!(module = %self.module, pages = skipped, "⏭️ skipped pages written before the interruption");
+        }
+        if !complete {
+            return;
+        }
+        if let Err(e) = self.store.clear_written_pages(&self.module).await {
+            warn!(module = %self.module, error = %e, "could not clear page progress");
+        }
+    }
+}
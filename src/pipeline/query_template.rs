@@ -0,0 +1,167 @@
+//! Run-time expressions in `query_params` values.
+//!
+//! ```yaml
+//! query_params:
+//!   - key: updated_since
+//!     value: "{{ days_ago(1) }}"
+//!   - key: as_of
+//!     value: "{{ run_started_at }}"
+//!   - key: region
+//!     value: "{{ var('region') }}"
+//! ```
+//!
+//! Values are rendered once per run, so every page of a source asks for the
+//! same window. Dates are taken from the run's start in UTC:
+//!
+//! - `run_started_at`: RFC 3339 timestamp, e.g. `2024-05-01T06:00:00Z`
+//! - `today()`, `days_ago(n)`: `YYYY-MM-DD`, or any strftime `format=`
+//! - `var('name', default=...)`: the config's `vars:`, overridden by `--var`
+//! - `watermark`: for `incremental:` sources, the cursor value to fetch from
+//!   (see [`crate::pipeline::watermark`])
+//!
+//! Values without `{{` or `{%` are sent as written.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use minijinja::value::{Kwargs, Value};
+use minijinja::{Environment, Error as MjError, ErrorKind};
+
+use crate::errors::{ApitapError, Result};
+use crate::pipeline::{QueryParam, QueryValue};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+pub struct QueryTemplate {
+    env: Environment<'static>,
+    started_at: DateTime<Utc>,
+}
+
+impl QueryTemplate {
+    pub fn new(started_at: DateTime<Utc>, vars: BTreeMap<String, String>) -> Self {
+        let mut env = Environment::new();
+        let vars = Arc::new(vars);
+
+        let date = move |days: i64, kwargs: &Kwargs| -> std::result::Result<Value, MjError> {
+            let format: Option<String> = kwargs.get("format")?;
+            kwargs.assert_all_used()?;
+            let format = format.as_deref().unwrap_or(DATE_FORMAT);
+            let day = started_at - Duration::days(days);
+            // A bad specifier makes to_string() panic; write! returns it.
+            let mut out = String::new();
+            write!(out, "{}", day.format(format)).map_err(|_| {
+                MjError::new(
+                    ErrorKind::InvalidOperation,
+                    format!("invalid date format '{format}'"),
+                )
+            })?;
+            Ok(Value::from(out))
+        };
+        // {{ today() }}, {{ today(format="%Y%m%d") }}
+        env.add_function("today", move |kwargs: Kwargs| date(0, &kwargs));
+        // {{ days_ago(7) }}
+        env.add_function("days_ago", move |days: i64, kwargs: Kwargs| {
+            date(days, &kwargs)
+        });
+        // {{ var('region') }}, {{ var('region', default='eu') }}
+        env.add_function(
+            "var",
+            move |name: String, kwargs: Kwargs| -> std::result::Result<Value, MjError> {
+                let default: Option<String> = kwargs.get("default")?;
+                kwargs.assert_all_used()?;
+                match vars.get(&name).cloned().or(default) {
+                    Some(value) => Ok(Value::from(value)),
+                    None => Err(MjError::new(
+                        ErrorKind::UndefinedError,
+                        format!("var '{name}' is not set in vars: or with --var"),
+                    )),
+                }
+            },
+        );
+
+        Self { env, started_at }
+    }
+
+    /// `params` with every templated value rendered; `source` names the
+    /// source in errors.
+    pub fn render(&self, source: &str, params: &[QueryParam]) -> Result<Vec<QueryParam>> {
+        self.render_with_watermark(source, params, None)
+    }
+
+    /// [`Self::render`] with `{{ watermark }}` defined when `watermark` is set.
+    pub fn render_with_watermark(
+        &self,
+        source: &str,
+        params: &[QueryParam],
+        watermark: Option<&str>,
+    ) -> Result<Vec<QueryParam>> {
+        params
+            .iter()
+            .map(|param| {
+                let render = |value: &String| {
+                    self.render_value(value, watermark).map_err(|e| {
+                        ApitapError::ConfigError(format!(
+                            "{source}: query_params '{}': {e}",
+                            param.key
+                        ))
+                    })
+                };
+                let value = match &param.value {
+                    QueryValue::One(v) => QueryValue::One(render(v)?),
+                    QueryValue::Many(vs) => {
+                        QueryValue::Many(vs.iter().map(render).collect::<Result<_>>()?)
+                    }
+                };
+                Ok(QueryParam {
+                    key: param.key.clone(),
+                    value,
+                })
+            })
+            .collect()
+    }
+
+    /// A SQL source's `query` with its expressions rendered the same way.
+    pub fn render_query(
+        &self,
+        source: &str,
+        query: &str,
+        watermark: Option<&str>,
+    ) -> Result<String> {
+        self.render_value(query, watermark)
+            .map_err(|e| ApitapError::ConfigError(format!("{source}: sql.query: {e}")))
+    }
+
+    fn render_value(
+        &self,
+        value: &str,
+        watermark: Option<&str>,
+    ) -> std::result::Result<String, MjError> {
+        if !value.contains("{{") && !value.contains("{%") {
+            return Ok(value.to_string());
+        }
+        let started_at = self.started_at.to_rfc3339_opts(SecondsFormat::Secs, true);
+        // Left undefined without a watermark, for `{% if watermark %}`.
+        let ctx = match watermark {
+            Some(watermark) => minijinja::context! {
+                run_started_at => started_at,
+                watermark => watermark,
+            },
+            None => minijinja::context! { run_started_at => started_at },
+        };
+        self.env.render_str(value, ctx)
+    }
+}
+
+/// `--var region=eu`.
+pub fn parse_var(text: &str) -> Result<(String, String)> {
+    match text.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.to_string()))
+        }
+        _ => Err(ApitapError::ConfigError(format!(
+            "invalid var '{text}' (expected NAME=VALUE)"
+        ))),
+    }
+}
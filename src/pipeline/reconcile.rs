@@ -0,0 +1,149 @@
+//! Reconciling merges with rows deleted upstream.
+//!
+//! ```yaml
+//! reconcile:
+//!   mode: soft_delete            # soft_delete (default) | delete
+//!   column: _deleted_at          # stamped by soft_delete (default)
+//!   key: id                      # defaults to primary_key_in_dest
+//! ```
+//!
+//! Merges only add and update rows, so a record removed from the API stays
+//! in the table. With `reconcile:`, the keys a module wrote are collected on
+//! their way to each table, and once the module fetched everything without
+//! a failed page, rows whose key was not among them are stamped with
+//! `column` (`soft_delete`, which also clears it on rows seen again) or
+//! deleted (`delete`). Keys are compared as text.
+//!
+//! A module that did not see the whole extract is never reconciled: one
+//! fetched from an `incremental` watermark, resumed past pages written
+//! before, with pages answered 304 by `http_cache`, starting past the first
+//! page with `start:`, continuing an NDJSON export from its download
+//! checkpoint, or reading a `format: sse` stream, which has no whole
+//! extract. Neither is a table that received no row, in case the API
+//! answered with an empty list.
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::util::display::{ArrayFormatter, FormatOptions};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::errors::{ApitapError, Result};
+use crate::writer::middleware::WriterMiddleware;
+use crate::writer::DataWriter;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReconcileConfig {
+    #[serde(default)]
+    pub mode: ReconcileMode,
+    /// Timestamp column marking soft-deleted rows.
+    #[serde(default = "default_column")]
+    pub column: String,
+    /// Column identifying rows; `primary_key_in_dest` when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+}
+
+fn default_column() -> String {
+    "_deleted_at".to_string()
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReconcileMode {
+    #[default]
+    SoftDelete,
+    Delete,
+}
+
+impl ReconcileConfig {
+    /// The column stamped on rows gone upstream; `None` when they are deleted.
+    pub fn deleted_column(&self) -> Option<&str> {
+        match self.mode {
+            ReconcileMode::SoftDelete => Some(&self.column),
+            ReconcileMode::Delete => None,
+        }
+    }
+}
+
+/// Keys of the rows written to one table, collected from its batches.
+#[derive(Debug)]
+pub struct SeenKeys {
+    key: String,
+    keys: Mutex<HashSet<String>>,
+}
+
+impl SeenKeys {
+    pub fn new(key: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            keys: Mutex::default(),
+        }
+    }
+
+    pub fn keys(&self) -> Vec<String> {
+        let keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        keys.iter().cloned().collect()
+    }
+}
+
+impl WriterMiddleware for SeenKeys {
+    fn name(&self) -> &'static str {
+        "seen_keys"
+    }
+
+    fn map_batch(&self, table: &str, batch: RecordBatch) -> Result<RecordBatch> {
+        if batch.num_rows() == 0 {
+            return Ok(batch);
+        }
+        let column = batch.column_by_name(&self.key).cloned().ok_or_else(|| {
+            ApitapError::ConfigError(format!(
+                "reconcile: key column '{}' is not among the rows written to {table}",
+                self.key
+            ))
+        })?;
+        let formatter = ArrayFormatter::try_new(column.as_ref(), &FormatOptions::default())?;
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        for row in 0..column.len() {
+            if column.is_valid(row) {
+                keys.insert(formatter.value(row).to_string());
+            }
+        }
+        Ok(batch)
+    }
+}
+
+/// Mark or delete the rows of every table whose key was not seen, logging
+/// per table how many rows went. Sinks without support are skipped with a
+/// warning.
+pub async fn apply_reconcile(
+    config: &ReconcileConfig,
+    key: &str,
+    at: DateTime<Utc>,
+    tables: &[(String, Arc<dyn DataWriter>, Arc<SeenKeys>)],
+) -> Result<()> {
+    for (table, writer, seen) in tables {
+        let keys = seen.keys();
+        if keys.is_empty() {
+            warn!(table = %table, "no rows written; not reconciling");
+            continue;
+        }
+        match writer
+            .reconcile(key, &keys, config.deleted_column(), at)
+            .await?
+        {
+            Some(rows) => info!(
+                table = %table,
+                mode = ?config.mode,
+                kept = keys.len(),
+                rows,
+                "🧾 rows gone upstream reconciled"
+            ),
+            None => warn!(table = %table, "sink does not support reconcile, skipped"),
+        }
+    }
+    Ok(())
+}
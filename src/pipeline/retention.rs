@@ -0,0 +1,75 @@
+//! Retention policies for destination tables.
+//!
+//! A source declares `retention: {column: created_at, keep: 90d}`; after a
+//! successful load every table it wrote to drops rows whose `column` is
+//! older than `keep`. Postgres can detach whole range partitions instead of
+//! deleting their rows one by one.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::errors::{ApitapError, Result};
+use crate::pipeline::freshness::parse_duration;
+use crate::writer::DataWriter;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionConfig {
+    /// Timestamp column compared against the cutoff.
+    pub column: String,
+    /// How long rows are kept, e.g. `90d` or `12w`.
+    pub keep: String,
+    /// Detach range partitions lying entirely before the cutoff (Postgres)
+    /// before deleting the remaining old rows.
+    #[serde(default)]
+    pub detach_partitions: bool,
+}
+
+impl RetentionConfig {
+    pub fn max_age(&self) -> Result<Duration> {
+        let max_age = parse_duration(&self.keep)?;
+        if max_age.is_zero() {
+            return Err(ApitapError::ConfigError(format!(
+                "retention keep '{}' must be longer than zero",
+                self.keep
+            )));
+        }
+        Ok(max_age)
+    }
+
+    /// Rows with `column` before this instant are purged.
+    pub fn cutoff(&self, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+        let max_age = chrono::Duration::from_std(self.max_age()?)
+            .map_err(|_| ApitapError::ConfigError(format!("invalid duration '{}'", self.keep)))?;
+        now.checked_sub_signed(max_age)
+            .ok_or_else(|| ApitapError::ConfigError(format!("invalid duration '{}'", self.keep)))
+    }
+}
+
+/// Purge rows older than `cutoff` from every table, logging per table how
+/// many rows went. Sinks without retention support are skipped with a warning.
+pub async fn apply_retention(
+    policy: &RetentionConfig,
+    cutoff: DateTime<Utc>,
+    tables: &[(String, Arc<dyn DataWriter>)],
+) -> Result<()> {
+    for (table, writer) in tables {
+        match writer
+            .purge_before(&policy.column, cutoff, policy.detach_partitions)
+            .await?
+        {
+            Some(deleted) => info!(
+                table = %table,
+                column = %policy.column,
+                cutoff = %cutoff.to_rfc3339(),
+                deleted,
+                "🧹 retention applied"
+            ),
+            None => warn!(table = %table, "sink does not support retention, skipped"),
+        }
+    }
+    Ok(())
+}
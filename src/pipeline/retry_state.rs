@@ -0,0 +1,249 @@
+//! Pages that exhausted their retries, persisted across process restarts.
+//!
+//! Every page that still fails after the configured retries is written to the
+//! state store (see [`crate::pipeline::state`]) as soon as it happens, so the
+//! record survives an aborted run. With `--resume` those pages are fetched again before the module's
+//! normal run, and each page is dropped from the store once it succeeds.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::errors::Result;
+use crate::pipeline::download_state::DownloadCheckpoint;
+use crate::pipeline::http_cache::PageValidator;
+use crate::pipeline::page_progress::WrittenPage;
+use crate::pipeline::state::{StateBackend, StateSnapshot, StoredRefreshToken};
+use crate::pipeline::watermark::Watermark;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FailedPage {
+    pub error: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+/// Module name -> page number -> last failure.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RetryState {
+    #[serde(default)]
+    pub modules: BTreeMap<String, BTreeMap<u64, FailedPage>>,
+}
+
+#[derive(Debug)]
+pub struct RetryStateStore {
+    backend: StateBackend,
+    state: Mutex<StateSnapshot>,
+}
+
+impl RetryStateStore {
+    /// Open the state store; a missing store starts empty.
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let backend = StateBackend::open(path).await?;
+        let state = backend.load().await?;
+        Ok(Self {
+            backend,
+            state: Mutex::new(state),
+        })
+    }
+
+    pub async fn failed_pages(&self, module: &str) -> Vec<u64> {
+        self.state
+            .lock()
+            .await
+            .retry
+            .modules
+            .get(module)
+            .map(|pages| pages.keys().copied().collect())
+            .unwrap_or_default()
+    }
+
+    pub async fn record_failure(&self, module: &str, page: u64, error: &str) -> Result<()> {
+        let mut state = self.state.lock().await;
+        state
+            .retry
+            .modules
+            .entry(module.to_string())
+            .or_default()
+            .insert(
+                page,
+                FailedPage {
+                    error: error.to_string(),
+                    failed_at: Utc::now(),
+                },
+            );
+        self.backend.save_retry_page(&state, module, page).await
+    }
+
+    /// Forget a page after it was fetched successfully.
+    pub async fn clear(&self, module: &str, page: u64) -> Result<()> {
+        let mut state = self.state.lock().await;
+        let Some(pages) = state.retry.modules.get_mut(module) else {
+            return Ok(());
+        };
+        if pages.remove(&page).is_none() {
+            return Ok(());
+        }
+        if pages.is_empty() {
+            state.retry.modules.remove(module);
+        }
+        self.backend.save_retry_page(&state, module, page).await
+    }
+
+    pub async fn download_checkpoint(&self, module: &str) -> Option<DownloadCheckpoint> {
+        self.state.lock().await.downloads.get(module).cloned()
+    }
+
+    /// Store `checkpoint` for `module`, or drop its checkpoint when `None`.
+    pub async fn save_download_checkpoint(
+        &self,
+        module: &str,
+        checkpoint: Option<DownloadCheckpoint>,
+    ) -> Result<()> {
+        let mut state = self.state.lock().await;
+        match checkpoint {
+            Some(checkpoint) => {
+                state.downloads.insert(module.to_string(), checkpoint);
+            }
+            None => {
+                if state.downloads.remove(module).is_none() {
+                    return Ok(());
+                }
+            }
+        }
+        self.backend.save_download(&state, module).await
+    }
+
+    pub async fn page_validator(&self, module: &str, url: &str) -> Option<PageValidator> {
+        self.state
+            .lock()
+            .await
+            .validators
+            .get(module)?
+            .get(url)
+            .cloned()
+    }
+
+    /// Store the validators of `module`'s pages, keyed by page URL.
+    pub async fn save_page_validators(
+        &self,
+        module: &str,
+        validators: Vec<(String, PageValidator)>,
+    ) -> Result<()> {
+        if validators.is_empty() {
+            return Ok(());
+        }
+        let mut state = self.state.lock().await;
+        let urls: Vec<String> = validators.iter().map(|(url, _)| url.clone()).collect();
+        state
+            .validators
+            .entry(module.to_string())
+            .or_default()
+            .extend(validators);
+        self.backend.save_validators(&state, module, &urls).await
+    }
+
+    pub async fn watermark(&self, module: &str) -> Option<Watermark> {
+        self.state.lock().await.watermarks.get(module).cloned()
+    }
+
+    /// Store the highest cursor value `module` loaded.
+    pub async fn save_watermark(&self, module: &str, watermark: Watermark) -> Result<()> {
+        let mut state = self.state.lock().await;
+        state.watermarks.insert(module.to_string(), watermark);
+        self.backend.save_watermark(&state, module).await
+    }
+
+    /// Pages of `module` written by a run that did not finish, by URL.
+    pub async fn written_pages(&self, module: &str) -> BTreeMap<String, WrittenPage> {
+        self.state
+            .lock()
+            .await
+            .progress
+            .get(module)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub async fn save_written_page(
+        &self,
+        module: &str,
+        url: String,
+        page: WrittenPage,
+    ) -> Result<()> {
+        let mut state = self.state.lock().await;
+        let pages = state.progress.entry(module.to_string()).or_default();
+        pages.insert(url.clone(), page);
+        self.backend.save_progress(&state, module, Some(&url)).await
+    }
+
+    /// Forget the pages written for `module`.
+    pub async fn clear_written_pages(&self, module: &str) -> Result<()> {
+        let mut state = self.state.lock().await;
+        if state.progress.remove(module).is_none() {
+            return Ok(());
+        }
+        self.backend.save_progress(&state, module, None).await
+    }
+
+    pub async fn refresh_token(&self, source: &str) -> Option<StoredRefreshToken> {
+        self.state.lock().await.refresh_tokens.get(source).cloned()
+    }
+
+    /// Store the refresh token `source` was last issued.
+    pub async fn save_refresh_token(&self, source: &str, token: StoredRefreshToken) -> Result<()> {
+        let mut state = self.state.lock().await;
+        state.refresh_tokens.insert(source.to_string(), token);
+        self.backend.save_refresh_token(&state, source).await
+    }
+}
+
+/// Per-module handle the fetcher uses to report page outcomes.
+#[derive(Debug, Clone)]
+pub struct RetryTracker {
+    store: Arc<RetryStateStore>,
+    module: String,
+    resume: bool,
+}
+
+impl RetryTracker {
+    pub fn new(store: Arc<RetryStateStore>, module: impl Into<String>, resume: bool) -> Self {
+        Self {
+            store,
+            module: module.into(),
+            resume,
+        }
+    }
+
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    /// Pages to re-attempt before the normal run; empty unless resuming.
+    pub async fn resume_pages(&self) -> Vec<u64> {
+        if !self.resume {
+            return Vec::new();
+        }
+        let pages = self.store.failed_pages(&self.module).await;
+        if !pages.is_empty() {
+            info!(module = %self.module, pages = ?pages, "resuming pages that exhausted retries");
+        }
+        pages
+    }
+
+    pub async fn page_failed(&self, page: u64, error: &str) {
+        if let Err(e) = self.store.record_failure(&self.module, page, error).await {
+            warn!(module = %self.module, page, error = %e, "could not persist retry state");
+        }
+    }
+
+    pub async fn page_succeeded(&self, page: u64) {
+        if let Err(e) = self.store.clear(&self.module, page).await {
+            warn!(module = %self.module, page, error = %e, "could not persist retry state");
+        }
+    }
+}
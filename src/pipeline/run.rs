@@ -1,52 +1,442 @@
+use chrono::{DateTime, Utc};
 use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
 use url::Url;
 
+use crate::config::templating::ModuleDoc;
+use crate::http::csv_stream::ResponseFormat;
 use crate::http::fetcher::FetchStats;
+use crate::http::ndjson_export::run_ndjson_export;
+use crate::http::paginator::Paginators;
+use crate::http::sse_stream::run_sse_fetch;
+use crate::http::usage::UsageMeter;
+use crate::pipeline::freshness::parse_duration;
+use crate::pipeline::run_history::{ModuleRun, RunRecord, RunStatus};
 use crate::pipeline::QueryParam;
 use crate::{
     errors::{ApitapError, Result},
-    http::fetcher::{DataFusionPageWriter, PaginatedFetcher, Pagination},
-    writer::{DataWriter, WriteMode},
+    http::fetcher::{DataFusionPageWriter, PaginatedFetcher, Pagination, RequestOptions, StartAt},
+    writer::WriteMode,
 };
 
+/// Largest batch of rows held at once under `--low-memory`.
+pub const LOW_MEMORY_BATCH_SIZE: usize = 100;
+
 #[derive(Debug, Clone)]
 pub struct FetchOpts {
     pub concurrency: usize,
     pub default_page_size: usize,
     pub fetch_batch_size: usize, // internal http batch size
+    /// Write a partial batch once its first row has waited this long.
+    pub flush_interval: Option<Duration>,
+}
+
+impl FetchOpts {
+    /// These options with the values `settings` sets replacing them.
+    pub fn with_overrides(&self, settings: &FetchSettings) -> Result<FetchOpts> {
+        let positive = |value: Option<usize>, default: usize, key: &str| match value {
+            Some(0) => Err(ApitapError::ConfigError(format!(
+                "{key} must be at least 1"
+            ))),
+            Some(v) => Ok(v),
+            None => Ok(default),
+        };
+        Ok(FetchOpts {
+            concurrency: positive(settings.concurrency, self.concurrency, "concurrency")?,
+            default_page_size: positive(settings.page_size, self.default_page_size, "page_size")?,
+            fetch_batch_size: positive(
+                settings.fetch_batch_size,
+                self.fetch_batch_size,
+                "fetch_batch_size",
+            )?,
+            flush_interval: match settings.flush_interval.as_deref() {
+                Some(text) => {
+                    let interval = parse_duration(text)?;
+                    if interval.is_zero() {
+                        return Err(ApitapError::ConfigError(
+                            "flush_interval must be longer than zero".into(),
+                        ));
+                    }
+                    Some(interval)
+                }
+                None => self.flush_interval,
+            },
+        })
+    }
+
+    /// These options under `--low-memory`: one request at a time and rows
+    /// written at most [`LOW_MEMORY_BATCH_SIZE`] at a time, whatever the
+    /// config says.
+    pub fn low_memory(&self) -> FetchOpts {
+        FetchOpts {
+            concurrency: 1,
+            fetch_batch_size: self.fetch_batch_size.min(LOW_MEMORY_BATCH_SIZE),
+            ..self.clone()
+        }
+    }
+}
+
+/// Page size and parallelism, set for every source under a top-level
+/// `fetch:` and overridden by the same keys on a source.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FetchSettings {
+    /// Rows requested per page (`limit` / `per_page`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_size: Option<usize>,
+    /// Pages requested at once when the page count is known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub concurrency: Option<usize>,
+    /// Rows buffered per write while streaming a page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fetch_batch_size: Option<usize>,
+    /// Longest a row waits for its batch to fill before it is written
+    /// anyway, e.g. `5s`; keeps slow trickles of data landing promptly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flush_interval: Option<String>,
+    /// Largest response body read for one page, e.g. `256MB`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_body_size: Option<String>,
+    /// Longest NDJSON line, e.g. `16MB`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_line_length: Option<String>,
+    /// Response bodies a source buffers at once across its pages, e.g. `1GB`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_in_flight: Option<String>,
+}
+
+/// `schedule:` in the config: how often the pipeline is run, used to
+/// project a run's usage over a month.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Schedule {
+    /// Interval between runs, e.g. `1h` or `15m`.
+    pub every: String,
+}
+
+impl Schedule {
+    pub fn interval(&self) -> Result<Duration> {
+        let every = parse_duration(&self.every)?;
+        if every.is_zero() {
+            return Err(ApitapError::ConfigError(
+                "schedule.every must be longer than zero".into(),
+            ));
+        }
+        Ok(every)
+    }
+}
+
+/// Days a monthly projection covers.
+const MONTH: Duration = Duration::from_secs(30 * 86_400);
+
+/// A run's usage multiplied by the runs in 30 days on its schedule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonthlyUsage {
+    pub runs: u64,
+    pub api_calls: u64,
+    pub bytes_downloaded: u64,
+    pub records: u64,
 }
 
+/// Totals of one pipeline run, logged as the final `run_completed` event.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunSummary {
+    pub modules_completed: usize,
+    /// Skipped with `--skip-if-fresh`, or disabled.
+    pub modules_skipped: usize,
+    pub records: usize,
+    pub pages: usize,
+    pub failed_pages: usize,
+    /// 429 responses waited out before retrying.
+    pub throttled: usize,
+    /// HTTP requests sent, stream reconnects included; not retries.
+    pub api_calls: u64,
+    /// Response body bytes read.
+    pub bytes_downloaded: u64,
+    /// Interval between runs, from the config's `schedule`.
+    pub schedule: Option<Duration>,
+    /// Per-module results kept in the run history.
+    pub module_runs: BTreeMap<String, ModuleRun>,
+    /// The module being run and its comment header; still set when the run
+    /// failed in it, so the failure names its owner.
+    pub current_module: Option<(String, ModuleDoc)>,
+}
+
+impl RunSummary {
+    pub fn add_module(&mut self, stats: &FetchStats) {
+        self.modules_completed += 1;
+        self.records += stats.total_items;
+        self.pages += stats.success_count;
+        self.failed_pages += stats.error_count;
+        self.throttled += stats.throttled.responses;
+    }
+
+    pub fn add_usage(&mut self, usage: &UsageMeter) {
+        self.api_calls += usage.requests();
+        self.bytes_downloaded += usage.bytes();
+    }
+
+    /// This run's usage repeated on the schedule for 30 days.
+    pub fn projected_monthly(&self) -> Option<MonthlyUsage> {
+        let every = self.schedule?;
+        let runs = (MONTH.as_secs_f64() / every.as_secs_f64()).floor().max(1.0) as u64;
+        Some(MonthlyUsage {
+            runs,
+            api_calls: self.api_calls.saturating_mul(runs),
+            bytes_downloaded: self.bytes_downloaded.saturating_mul(runs),
+            records: (self.records as u64).saturating_mul(runs),
+        })
+    }
+
+    /// Emit the `usage` event: calls, bytes and rows of this run, and the
+    /// monthly projection when a schedule is configured.
+    pub fn log_usage(&self) {
+        match self.projected_monthly() {
+            Some(month) => info!(
+                event = "usage",
+                api_calls = self.api_calls,
+                bytes_downloaded = self.bytes_downloaded,
+                records = self.records,
+                runs_per_month = month.runs,
+                monthly_api_calls = month.api_calls,
+                monthly_bytes_downloaded = month.bytes_downloaded,
+                monthly_records = month.records,
+                "usage: {} API calls, {} downloaded, {} rows; ~{} calls, {} and {} rows per month at {} runs",
+                self.api_calls,
+                format_bytes(self.bytes_downloaded),
+                self.records,
+                month.api_calls,
+                format_bytes(month.bytes_downloaded),
+                month.records,
+                month.runs
+            ),
+            None => info!(
+                event = "usage",
+                api_calls = self.api_calls,
+                bytes_downloaded = self.bytes_downloaded,
+                records = self.records,
+                "usage: {} API calls, {} downloaded, {} rows",
+                self.api_calls,
+                format_bytes(self.bytes_downloaded),
+                self.records
+            ),
+        }
+    }
+
+    /// Modules that ran longer than their SLA.
+    pub fn sla_breaches(&self) -> usize {
+        self.module_runs.values().filter(|m| m.sla_breached).count()
+    }
+
+    /// The comment header of the module the run is in (or failed in).
+    pub fn current_doc(&self) -> Option<&ModuleDoc> {
+        self.current_module.as_ref().map(|(_, doc)| doc)
+    }
+
+    /// Emit the `run_completed` event, at error level when the run failed.
+    pub fn log_completed(&self, elapsed: Duration, outcome: &Result<()>) {
+        let duration_ms = elapsed.as_millis() as u64;
+        match outcome {
+            Ok(()) => info!(
+                event = "run_completed",
+                status = "succeeded",
+                modules_completed = self.modules_completed,
+                modules_skipped = self.modules_skipped,
+                records = self.records,
+                pages = self.pages,
+                failed_pages = self.failed_pages,
+                throttled = self.throttled,
+                api_calls = self.api_calls,
+                bytes_downloaded = self.bytes_downloaded,
+                sla_breaches = self.sla_breaches(),
+                duration_ms,
+                "run completed"
+            ),
+            Err(e) => error!(
+                event = "run_completed",
+                status = "failed",
+                error = %e,
+                module = self.current_module.as_ref().map(|(name, _)| name.as_str()),
+                owner = self.current_doc().and_then(|doc| doc.owner.as_deref()),
+                sla = self.current_doc().and_then(|doc| doc.sla.as_deref()),
+                modules_completed = self.modules_completed,
+                modules_skipped = self.modules_skipped,
+                records = self.records,
+                pages = self.pages,
+                failed_pages = self.failed_pages,
+                api_calls = self.api_calls,
+                bytes_downloaded = self.bytes_downloaded,
+                sla_breaches = self.sla_breaches(),
+                duration_ms,
+                "run failed"
+            ),
+        }
+    }
+
+    /// This run as a run-history entry.
+    pub fn to_record(
+        &self,
+        run_id: &str,
+        started_at: DateTime<Utc>,
+        elapsed: Duration,
+        outcome: &Result<()>,
+    ) -> RunRecord {
+        RunRecord {
+            run_id: run_id.to_string(),
+            started_at,
+            duration_ms: elapsed.as_millis() as u64,
+            status: match outcome {
+                Ok(()) => RunStatus::Succeeded,
+                Err(_) => RunStatus::Failed,
+            },
+            error: outcome.as_ref().err().map(ToString::to_string),
+            modules: self.module_runs.clone(),
+        }
+    }
+}
+
+/// Human-readable byte count in decimal units, e.g. `12.3 MB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1000.0 && unit + 1 < UNITS.len() {
+        value /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+/// Identifier tagging every log event of one run.
+pub fn new_run_id() -> String {
+    let alphabet: Vec<char> = "0123456789abcdefghijklmnopqrstuvwxyz".chars().collect();
+    nanoid::nanoid!(12, &alphabet)
+}
+
+/// Fetch a source's pages into `page_writer`; the stats include the 429
+/// responses its throttle waited out and the pages skipped as unchanged.
+/// Validators of the pages fetched are only kept once all went well.
+#[allow(clippy::too_many_arguments)]
 pub async fn run_fetch(
     client: Client,
     url: Url,
     data_path: Option<String>,
     extra_params: Option<Vec<QueryParam>>,
     pagination: &Option<Pagination>,
-    sql: &str,
-    dest_table: &str,
-    writer: Arc<dyn DataWriter>,
+    start: Option<&StartAt>,
+    page_writer: DataFusionPageWriter,
     write_mode: WriteMode,
     opts: &FetchOpts,
     config_retry: &crate::pipeline::Retry,
+    request: RequestOptions,
+    paginators: &Paginators,
 ) -> Result<FetchStats> {
-    let page_writer = Arc::new(DataFusionPageWriter::new(dest_table, sql, writer.clone()));
+    let throttle = request.throttle.clone();
+    let cache = request.http_cache.clone();
+    let source = url.to_string();
+    let mut stats = fetch_pages(
+        client,
+        url,
+        data_path,
+        extra_params,
+        pagination,
+        start,
+        page_writer,
+        write_mode,
+        opts,
+        config_retry,
+        request,
+        paginators,
+    )
+    .await?;
+    if let Some(throttle) = throttle {
+        stats.throttled = throttle.throttled().await;
+    }
+    if let Some(cache) = cache {
+        stats.not_modified = cache.unchanged_pages();
+        cache.commit().await;
+    }
+    if stats.throttled.responses > 0 {
+        warn!(
+            %source,
+            responses = stats.throttled.responses,
+            waited_ms = stats.throttled.waited.as_millis() as u64,
+            "source was rate limited (429)"
+        );
+    }
+    Ok(stats)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn fetch_pages(
+    client: Client,
+    url: Url,
+    data_path: Option<String>,
+    extra_params: Option<Vec<QueryParam>>,
+    pagination: &Option<Pagination>,
+    start: Option<&StartAt>,
+    page_writer: DataFusionPageWriter,
+    write_mode: WriteMode,
+    opts: &FetchOpts,
+    config_retry: &crate::pipeline::Retry,
+    request: RequestOptions,
+    paginators: &Paginators,
+) -> Result<FetchStats> {
+    let page_writer = Arc::new(page_writer);
+    let start = start.cloned().unwrap_or_default();
+    start.validate(pagination.as_ref())?;
 
     // Convert QueryParam to (String, String) tuples
     let extra_params_vec: Vec<(String, String)> = extra_params
         .unwrap_or_default()
-        .into_iter()
-        .map(|q| (q.key, q.value))
+        .iter()
+        .flat_map(QueryParam::pairs)
         .collect();
 
+    // A live stream has no pages to request.
+    if request.format == ResponseFormat::Sse {
+        if !matches!(pagination, None | Some(Pagination::Default)) {
+            warn!(source = %url, "pagination is ignored for format: sse");
+        }
+        return run_sse_fetch(
+            client,
+            url.as_str(),
+            extra_params_vec,
+            data_path,
+            config_retry,
+            request,
+            &page_writer,
+            write_mode,
+            opts.fetch_batch_size,
+            opts.flush_interval,
+        )
+        .await;
+    }
+
+    if let Some(pagination) = pagination {
+        if pagination.stop_when()?.pointer.is_some() && request.format != ResponseFormat::Json {
+            return Err(ApitapError::ConfigError(
+                "stop_when.pointer needs JSON responses".into(),
+            ));
+        }
+    }
+
     match pagination {
-        Some(Pagination::LimitOffset {
-            limit_param,
-            offset_param,
-        }) => {
+        Some(pagination @ Pagination::LimitOffset { .. }) => {
+            let total_hint = pagination.total_hint()?;
             let fetcher = PaginatedFetcher::new(client, url, opts.concurrency)
-                .with_limit_offset(limit_param, offset_param)
-                .with_batch_size(opts.fetch_batch_size);
+                .with_start(start.clone())
+                .with_pagination(pagination.clone())
+                .with_batch_size(opts.fetch_batch_size)
+                .with_flush_interval(opts.flush_interval)
+                .with_stop_when(pagination.stop_when()?)
+                .with_request_options(request);
 
             let page_size: u64 = opts.default_page_size.try_into().map_err(|_| {
                 ApitapError::ConfigError(format!(
@@ -55,29 +445,41 @@ pub async fn run_fetch(
                 ))
             })?;
 
-            let stats = fetcher
+            let resumed = resume_failed_pages(
+                &fetcher,
+                page_size,
+                data_path.as_deref(),
+                Some(&extra_params_vec),
+                page_writer.clone(),
+                write_mode.clone(),
+                config_retry,
+            )
+            .await?;
+
+            let mut stats = fetcher
                 .fetch_limit_offset(
                     page_size,
                     data_path,
                     Some(&extra_params_vec),
-                    None,
+                    total_hint,
                     page_writer,
                     write_mode,
                     config_retry,
                 )
                 .await?;
-            return Ok(stats);
+            absorb(&mut stats, resumed);
+            Ok(stats)
         }
 
-        Some(Pagination::PageNumber {
-            page_param,
-            per_page_param,
-        }) => {
-            let page_writer = Arc::new(DataFusionPageWriter::new(dest_table, sql, writer.clone()));
-
+        Some(pagination @ Pagination::PageNumber { .. }) => {
+            let total_hint = pagination.total_hint()?;
             let fetcher = PaginatedFetcher::new(client, url, opts.concurrency)
+                .with_start(start.clone())
                 .with_batch_size(opts.fetch_batch_size)
-                .with_page_number(page_param, per_page_param);
+                .with_flush_interval(opts.flush_interval)
+                .with_stop_when(pagination.stop_when()?)
+                .with_pagination(pagination.clone())
+                .with_request_options(request);
 
             let per_page: u64 = opts.default_page_size.try_into().map_err(|_| {
                 ApitapError::ConfigError(format!(
@@ -86,16 +488,29 @@ pub async fn run_fetch(
                 ))
             })?;
 
-            let stats = fetcher
+            let resumed = resume_failed_pages(
+                &fetcher,
+                per_page,
+                data_path.as_deref(),
+                Some(&extra_params_vec),
+                page_writer.clone(),
+                write_mode.clone(),
+                config_retry,
+            )
+            .await?;
+
+            let mut stats = fetcher
                 .fetch_page_number(
                     per_page,
                     data_path.as_deref(),
-                    None,
+                    Some(&extra_params_vec),
+                    total_hint,
                     page_writer,
                     write_mode,
                     config_retry,
                 )
                 .await?;
+            absorb(&mut stats, resumed);
 
             Ok(stats)
         }
@@ -103,22 +518,128 @@ pub async fn run_fetch(
         Some(Pagination::PageOnly { page_param: _ }) => {
             let _fetcher = PaginatedFetcher::new(client, url, opts.concurrency)
                 .with_batch_size(opts.fetch_batch_size);
-            return Ok(FetchStats::new());
+            Ok(FetchStats::new())
         }
 
-        Some(Pagination::Cursor {
-            cursor_param: _,
-            page_size_param: _,
-        }) => {
-            let _fetcher = PaginatedFetcher::new(client, url, opts.concurrency)
-                .with_batch_size(opts.fetch_batch_size);
-            return Ok(FetchStats::new());
+        Some(
+            pagination @ (Pagination::LinkHeader { .. }
+            | Pagination::NextUrl { .. }
+            | Pagination::Cursor { .. }),
+        ) => {
+            if let Pagination::Cursor {
+                next_cursor_path: Some(_),
+                ..
+            } = pagination
+            {
+                if request.format != ResponseFormat::Json {
+                    return Err(ApitapError::ConfigError(
+                        "next_cursor_path needs JSON responses; use next_cursor_header".into(),
+                    ));
+                }
+            }
+            let fetcher = PaginatedFetcher::new(client, url, opts.concurrency)
+                .with_start(start.clone())
+                .with_pagination(pagination.clone())
+                .with_batch_size(opts.fetch_batch_size)
+                .with_flush_interval(opts.flush_interval)
+                .with_request_options(request);
+
+            let page_size: u64 = opts.default_page_size.try_into().map_err(|_| {
+                ApitapError::ConfigError(format!(
+                    "Invalid page size: {} (must fit in u64)",
+                    opts.default_page_size
+                ))
+            })?;
+
+            fetcher
+                .fetch_follow_next(
+                    page_size,
+                    data_path.as_deref(),
+                    Some(&extra_params_vec),
+                    page_writer,
+                    write_mode,
+                    config_retry,
+                )
+                .await
         }
 
-        Some(Pagination::Default) | None => {
-            return Err(ApitapError::PaginationError(
-                "no supported pagination configured".into(),
-            ));
+        Some(Pagination::Custom { paginator }) => {
+            let custom = paginators.get(paginator).ok_or_else(|| {
+                ApitapError::ConfigError(format!("no paginator registered as {paginator}"))
+            })?;
+            if request.format != ResponseFormat::Json {
+                return Err(ApitapError::ConfigError(
+                    "custom pagination needs JSON responses".into(),
+                ));
+            }
+            PaginatedFetcher::new(client, url, opts.concurrency)
+                .with_request_options(request)
+                .fetch_custom(
+                    custom,
+                    data_path.as_deref(),
+                    Some(&extra_params_vec),
+                    page_writer,
+                    write_mode,
+                    config_retry,
+                )
+                .await
         }
+
+        Some(Pagination::Default) | None => match request.ndjson.checkpoint_every()? {
+            Some(every) => {
+                run_ndjson_export(
+                    client,
+                    &url,
+                    extra_params_vec,
+                    data_path,
+                    config_retry,
+                    request,
+                    page_writer.as_ref(),
+                    write_mode,
+                    every,
+                )
+                .await
+            }
+            None => Err(ApitapError::PaginationError(
+                "no supported pagination configured".into(),
+            )),
+        },
+    }
+}
+
+/// With `--resume`, re-attempt the pages that exhausted retries last time.
+#[allow(clippy::too_many_arguments)]
+async fn resume_failed_pages(
+    fetcher: &PaginatedFetcher,
+    per_page: u64,
+    data_path: Option<&str>,
+    extra_params: Option<&[(String, String)]>,
+    page_writer: Arc<DataFusionPageWriter>,
+    write_mode: WriteMode,
+    config_retry: &crate::pipeline::Retry,
+) -> Result<FetchStats> {
+    let pages = match &fetcher.request_options().failures {
+        Some(tracker) => tracker.resume_pages().await,
+        None => Vec::new(),
+    };
+    if pages.is_empty() {
+        return Ok(FetchStats::new());
     }
+    fetcher
+        .fetch_pages(
+            &pages,
+            per_page,
+            data_path,
+            extra_params,
+            page_writer,
+            write_mode,
+            config_retry,
+        )
+        .await
+}
+
+fn absorb(stats: &mut FetchStats, other: FetchStats) {
+    stats.success_count += other.success_count;
+    stats.error_count += other.error_count;
+    stats.total_items += other.total_items;
 }
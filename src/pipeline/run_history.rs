@@ -0,0 +1,392 @@
+//! What each run loaded, kept in the state store for `apitap runs`.
+//!
+//! Every CLI run appends a [`RunRecord`]: per module the rows, pages,
+//! failed pages, duration and the columns each destination table received.
+//! [`diff_runs`] compares two records and flags regressions — fewer rows,
+//! slower modules, new failures and dropped or retyped columns.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use datafusion::arrow::datatypes::Fields;
+use serde::{Deserialize, Serialize};
+
+use crate::config::templating::ModuleDoc;
+use crate::errors::{ApitapError, Result};
+use crate::http::fetcher::FetchStats;
+
+/// Runs kept in the store; older ones are dropped as new ones are recorded.
+pub const RUN_HISTORY_LIMIT: usize = 100;
+
+/// A module losing more than this share of its rows is a regression.
+const ROWS_DROP_RATIO: f64 = 0.10;
+/// A module taking this much longer (and at least a second more) is a regression.
+const SLOWDOWN_RATIO: f64 = 1.5;
+const SLOWDOWN_MIN: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Succeeded,
+    Failed,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModuleRun {
+    pub rows: u64,
+    pub pages: u64,
+    pub failed_pages: u64,
+    pub duration_ms: u64,
+    /// Columns each destination table received.
+    #[serde(default)]
+    pub schema: TableSchemas,
+    /// Description, owner and SLA from the module's comment header.
+    #[serde(default, skip_serializing_if = "ModuleDoc::is_empty")]
+    pub doc: ModuleDoc,
+    /// Ran longer than its `sla(max_duration=...)`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sla_breached: bool,
+}
+
+impl ModuleRun {
+    pub fn new(stats: &FetchStats, elapsed: Duration, schema: TableSchemas) -> Self {
+        Self {
+            rows: stats.total_items as u64,
+            pages: stats.success_count as u64,
+            failed_pages: stats.error_count as u64,
+            duration_ms: elapsed.as_millis() as u64,
+            schema,
+            doc: ModuleDoc::default(),
+            sla_breached: false,
+        }
+    }
+}
+
+/// One recorded run, keyed by module name.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub run_id: String,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub status: RunStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub modules: BTreeMap<String, ModuleRun>,
+}
+
+impl RunRecord {
+    pub fn rows(&self) -> u64 {
+        self.modules.values().map(|m| m.rows).sum()
+    }
+
+    pub fn failed_pages(&self) -> u64 {
+        self.modules.values().map(|m| m.failed_pages).sum()
+    }
+}
+
+/// Table -> column -> Arrow type.
+pub type TableSchemas = BTreeMap<String, BTreeMap<String, String>>;
+
+/// Columns each destination table received during a module, as the SQL
+/// produced them.
+#[derive(Debug, Default)]
+pub struct SchemaCapture {
+    tables: Mutex<TableSchemas>,
+}
+
+impl SchemaCapture {
+    pub fn record(&self, table: &str, fields: &Fields) {
+        let mut tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+        let columns = tables.entry(table.to_string()).or_default();
+        for field in fields {
+            columns.insert(field.name().clone(), field.data_type().to_string());
+        }
+    }
+
+    pub fn take(&self) -> TableSchemas {
+        std::mem::take(&mut *self.tables.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+/// Find `id` among `runs`: an exact run id, a unique prefix, `latest`,
+/// or `latest~N` for the Nth run before it.
+pub fn find_run<'a>(runs: &'a [RunRecord], id: &str) -> Result<&'a RunRecord> {
+    let back = match (id, id.strip_prefix("latest~")) {
+        ("latest", _) => Some(0),
+        (_, Some(n)) => Some(
+            n.parse::<usize>()
+                .map_err(|_| ApitapError::ConfigError(format!("invalid run reference: {id}")))?,
+        ),
+        _ => None,
+    };
+    if let Some(back) = back {
+        let mut by_time: Vec<&RunRecord> = runs.iter().collect();
+        by_time.sort_by_key(|r| std::cmp::Reverse(r.started_at));
+        return by_time.get(back).copied().ok_or_else(|| {
+            ApitapError::ConfigError(format!("only {} run(s) recorded", runs.len()))
+        });
+    }
+    if let Some(run) = runs.iter().find(|r| r.run_id == id) {
+        return Ok(run);
+    }
+    let matches: Vec<&RunRecord> = runs.iter().filter(|r| r.run_id.starts_with(id)).collect();
+    match matches.as_slice() {
+        [run] => Ok(run),
+        [] => Err(ApitapError::ConfigError(format!("no recorded run {id}"))),
+        _ => Err(ApitapError::ConfigError(format!(
+            "run id prefix {id} matches {} runs",
+            matches.len()
+        ))),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum SchemaChange {
+    Added {
+        table: String,
+        column: String,
+        ty: String,
+    },
+    Removed {
+        table: String,
+        column: String,
+        ty: String,
+    },
+    Retyped {
+        table: String,
+        column: String,
+        from: String,
+        to: String,
+    },
+}
+
+impl SchemaChange {
+    /// Removed and retyped columns can break downstream queries.
+    pub fn is_breaking(&self) -> bool {
+        !matches!(self, SchemaChange::Added { .. })
+    }
+}
+
+impl std::fmt::Display for SchemaChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchemaChange::Added { table, column, ty } => write!(f, "+ {table}.{column} ({ty})"),
+            SchemaChange::Removed { table, column, ty } => write!(f, "- {table}.{column} ({ty})"),
+            SchemaChange::Retyped {
+                table,
+                column,
+                from,
+                to,
+            } => write!(f, "~ {table}.{column} ({from} -> {to})"),
+        }
+    }
+}
+
+/// One module compared across two runs; a side is `None` when the module
+/// did not run there.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ModuleDiff {
+    pub module: String,
+    pub a: Option<ModuleRun>,
+    pub b: Option<ModuleRun>,
+    pub schema_changes: Vec<SchemaChange>,
+    pub regressions: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RunDiff {
+    pub a: RunRecord,
+    pub b: RunRecord,
+    pub modules: Vec<ModuleDiff>,
+    /// Run-level regressions, e.g. a run that started failing.
+    pub regressions: Vec<String>,
+}
+
+impl RunDiff {
+    pub fn has_regressions(&self) -> bool {
+        !self.regressions.is_empty() || self.modules.iter().any(|m| !m.regressions.is_empty())
+    }
+
+    /// Plain-text report: one line per module, then its schema changes and
+    /// regressions.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "A  {}", run_line(&self.a));
+        let _ = writeln!(out, "B  {}", run_line(&self.b));
+        for regression in &self.regressions {
+            let _ = writeln!(out, "!! {regression}");
+        }
+        let _ = writeln!(out);
+        let _ = writeln!(
+            out,
+            "{:<24} {:>21} {:>23} {:>13}",
+            "module", "rows", "duration", "failed pages"
+        );
+        for m in &self.modules {
+            let rows = side_by_side(&m.a, &m.b, |r| r.rows.to_string());
+            let duration = side_by_side(&m.a, &m.b, |r| format!("{}ms", r.duration_ms));
+            let failed = side_by_side(&m.a, &m.b, |r| r.failed_pages.to_string());
+            let flag = if m.regressions.is_empty() { "" } else { "  !!" };
+            let _ = writeln!(
+                out,
+                "{:<24} {rows:>21} {duration:>23} {failed:>13}{flag}",
+                m.module
+            );
+            for change in &m.schema_changes {
+                let _ = writeln!(out, "    {change}");
+            }
+            for regression in &m.regressions {
+                let _ = writeln!(out, "    !! {regression}");
+            }
+        }
+        let regressions = self.regressions.len()
+            + self
+                .modules
+                .iter()
+                .map(|m| m.regressions.len())
+                .sum::<usize>();
+        let _ = writeln!(out);
+        let _ = writeln!(out, "{regressions} regression(s)");
+        out
+    }
+}
+
+fn run_line(run: &RunRecord) -> String {
+    let status = match run.status {
+        RunStatus::Succeeded => "succeeded",
+        RunStatus::Failed => "failed",
+    };
+    format!(
+        "{}  {}  {status}  {} rows  {}ms",
+        run.run_id,
+        run.started_at.format("%Y-%m-%d %H:%M:%S UTC"),
+        run.rows(),
+        run.duration_ms
+    )
+}
+
+fn side_by_side(
+    a: &Option<ModuleRun>,
+    b: &Option<ModuleRun>,
+    f: impl Fn(&ModuleRun) -> String,
+) -> String {
+    let show = |side: &Option<ModuleRun>| side.as_ref().map_or_else(|| "-".to_string(), &f);
+    format!("{} -> {}", show(a), show(b))
+}
+
+/// Compare run `a` (the baseline) with run `b`.
+pub fn diff_runs(a: &RunRecord, b: &RunRecord) -> RunDiff {
+    let mut regressions = Vec::new();
+    if a.status == RunStatus::Succeeded && b.status == RunStatus::Failed {
+        regressions.push(match &b.error {
+            Some(error) => format!("run failed: {error}"),
+            None => "run failed".to_string(),
+        });
+    }
+
+    let mut names: Vec<&String> = a.modules.keys().chain(b.modules.keys()).collect();
+    names.sort();
+    names.dedup();
+    let modules = names
+        .into_iter()
+        .map(|name| diff_module(name, a.modules.get(name), b.modules.get(name)))
+        .collect();
+
+    RunDiff {
+        a: a.clone(),
+        b: b.clone(),
+        modules,
+        regressions,
+    }
+}
+
+fn diff_module(module: &str, a: Option<&ModuleRun>, b: Option<&ModuleRun>) -> ModuleDiff {
+    let mut regressions = Vec::new();
+    let mut schema_changes = Vec::new();
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            if a.rows > 0 && (b.rows as f64) < a.rows as f64 * (1.0 - ROWS_DROP_RATIO) {
+                regressions.push(format!(
+                    "rows fell {:.0}% ({} -> {})",
+                    (1.0 - b.rows as f64 / a.rows as f64) * 100.0,
+                    a.rows,
+                    b.rows
+                ));
+            }
+            let (da, db) = (a.duration_ms as f64, b.duration_ms as f64);
+            if db > da * SLOWDOWN_RATIO && db - da >= SLOWDOWN_MIN.as_millis() as f64 {
+                regressions.push(format!(
+                    "{:.1}x slower ({}ms -> {}ms)",
+                    db / da.max(1.0),
+                    a.duration_ms,
+                    b.duration_ms
+                ));
+            }
+            if b.failed_pages > a.failed_pages {
+                regressions.push(format!(
+                    "failed pages rose ({} -> {})",
+                    a.failed_pages, b.failed_pages
+                ));
+            }
+            schema_changes = diff_schema(&a.schema, &b.schema);
+            let breaking = schema_changes.iter().filter(|c| c.is_breaking()).count();
+            if breaking > 0 {
+                regressions.push(format!("{breaking} column(s) removed or retyped"));
+            }
+        }
+        (Some(_), None) => regressions.push("module did not run".to_string()),
+        _ => {}
+    }
+    ModuleDiff {
+        module: module.to_string(),
+        a: a.cloned(),
+        b: b.cloned(),
+        schema_changes,
+        regressions,
+    }
+}
+
+fn diff_schema(a: &TableSchemas, b: &TableSchemas) -> Vec<SchemaChange> {
+    let mut changes = Vec::new();
+    let empty = BTreeMap::new();
+    let mut tables: Vec<&String> = a.keys().chain(b.keys()).collect();
+    tables.sort();
+    tables.dedup();
+    for table in tables {
+        let (ca, cb) = (
+            a.get(table).unwrap_or(&empty),
+            b.get(table).unwrap_or(&empty),
+        );
+        for (column, ty) in ca {
+            match cb.get(column) {
+                None => changes.push(SchemaChange::Removed {
+                    table: table.clone(),
+                    column: column.clone(),
+                    ty: ty.clone(),
+                }),
+                Some(to) if to != ty => changes.push(SchemaChange::Retyped {
+                    table: table.clone(),
+                    column: column.clone(),
+                    from: ty.clone(),
+                    to: to.clone(),
+                }),
+                Some(_) => {}
+            }
+        }
+        for (column, ty) in cb {
+            if !ca.contains_key(column) {
+                changes.push(SchemaChange::Added {
+                    table: table.clone(),
+                    column: column.clone(),
+                    ty: ty.clone(),
+                });
+            }
+        }
+    }
+    changes
+}
@@ -4,7 +4,19 @@ use std::sync::Arc;
 
 use crate::errors::Result;
 use crate::pipeline::TargetConn;
+use crate::utils::storage::split_cloud_location;
+use crate::writer::coercion::CoercionPolicy;
+use crate::writer::debug::DebugWriter;
+use crate::writer::delta::DeltaWriter;
+use crate::writer::file::FileWriter;
+use crate::writer::kafka::KafkaWriter;
+use crate::writer::mongodb::MongoWriter;
+use crate::writer::parquet::ParquetWriter;
 use crate::writer::postgres::PostgresWriter;
+use crate::writer::redshift::RedshiftWriter;
+use crate::writer::snowflake::SnowflakeWriter;
+use crate::writer::sqlite::SqliteWriter;
+use crate::writer::webhook::WebhookWriter;
 use crate::writer::{DataWriter, WriteMode};
 
 pub type HookFuture = Pin<Box<dyn Future<Output = Result<()>> + Send + 'static>>;
@@ -14,12 +26,25 @@ pub type Hook = Box<dyn FnOnce() -> HookFuture + Send>;
 pub struct WriterOpts<'a> {
     pub dest_table: &'a str,
     pub primary_key: Option<String>,
+    /// Without `primary_key`, merge on a key guessed from the first batch
+    /// (Postgres, SQLite).
+    pub infer_primary_key: bool,
     pub batch_size: usize,
     pub sample_size: usize,
     pub auto_create: bool,
     pub auto_truncate: bool,
     pub truncate_first: bool,
     pub write_mode: WriteMode,
+    /// Track inserted/changed/identical rows of merge loads (Postgres, SQLite).
+    pub mutation_report: bool,
+    /// Base64 columns stored as bytes where the sink supports it (Postgres).
+    pub binary_columns: Vec<String>,
+    /// Columns stored zstd-compressed as `<column>_compressed` (Postgres).
+    pub compressed_columns: Vec<String>,
+    /// Log the query plan of the first batch's statement (Postgres).
+    pub explain_first_batch: bool,
+    /// Values that do not fit their column type (Postgres).
+    pub coercion_policy: CoercionPolicy,
 }
 
 pub trait MakeWriter {
@@ -35,10 +60,16 @@ impl MakeWriter for TargetConn {
                 let pg = Arc::new(
                     PostgresWriter::new(pool.clone(), opts.dest_table)
                         .with_primary_key_single(opts.primary_key.clone())
+                        .with_infer_primary_key(opts.infer_primary_key)
                         .with_batch_size(opts.batch_size)
                         .with_sample_size(opts.sample_size)
                         .auto_create(opts.auto_create)
-                        .auto_truncate(opts.auto_truncate),
+                        .auto_truncate(opts.auto_truncate)
+                        .with_mutation_report(opts.mutation_report)
+                        .with_binary_columns(opts.binary_columns.clone())
+                        .with_compressed_columns(opts.compressed_columns.clone())
+                        .with_explain_first_batch(opts.explain_first_batch)
+                        .with_coercion_policy(opts.coercion_policy),
                 );
 
                 // 2) Optional truncate hook that captures the *concrete* writer
@@ -59,6 +90,231 @@ impl MakeWriter for TargetConn {
 
                 Ok((writer, hook))
             }
+            TargetConn::Snowflake {
+                session, staging, ..
+            } => {
+                // Transactions and temporary tables are per session.
+                let sf = Arc::new(
+                    SnowflakeWriter::new(session.fork(), staging.clone(), opts.dest_table)
+                        .with_primary_key_single(opts.primary_key.clone())
+                        .with_batch_size(opts.batch_size)
+                        .with_sample_size(opts.sample_size)
+                        .auto_create(opts.auto_create),
+                );
+
+                let hook: Option<Hook> = if opts.truncate_first {
+                    let sf_for_hook = Arc::clone(&sf);
+                    Some(Box::new(move || {
+                        Box::pin(async move {
+                            sf_for_hook.truncate().await?;
+                            Ok(())
+                        }) as HookFuture
+                    }))
+                } else {
+                    None
+                };
+
+                let writer: Arc<dyn DataWriter> = sf;
+                Ok((writer, hook))
+            }
+            TargetConn::Parquet {
+                store,
+                prefix,
+                options,
+            } => {
+                let pq = Arc::new(
+                    ParquetWriter::new(Arc::clone(store), prefix.clone(), opts.dest_table)
+                        .with_compression(options.compression)
+                        .with_max_file_size((options.max_file_size_mb * 1024 * 1024) as usize)
+                        .with_partition_by(options.partition_by.clone()),
+                );
+
+                let hook: Option<Hook> = if opts.truncate_first {
+                    let pq_for_hook = Arc::clone(&pq);
+                    Some(Box::new(move || {
+                        Box::pin(async move {
+                            pq_for_hook.truncate().await?;
+                            Ok(())
+                        }) as HookFuture
+                    }))
+                } else {
+                    None
+                };
+
+                let writer: Arc<dyn DataWriter> = pq;
+                Ok((writer, hook))
+            }
+            TargetConn::File { options, store } => {
+                let file = Arc::new(match (store, split_cloud_location(&options.path)) {
+                    (Some(store), Some((_, key))) => FileWriter::in_store(
+                        Arc::clone(store),
+                        key,
+                        opts.dest_table,
+                        options.format,
+                    ),
+                    _ => FileWriter::new(&options.path, opts.dest_table, options.format),
+                });
+
+                let hook: Option<Hook> = if opts.truncate_first {
+                    let file_for_hook = Arc::clone(&file);
+                    Some(Box::new(move || {
+                        Box::pin(async move {
+                            file_for_hook.truncate().await?;
+                            Ok(())
+                        }) as HookFuture
+                    }))
+                } else {
+                    None
+                };
+
+                let writer: Arc<dyn DataWriter> = file;
+                Ok((writer, hook))
+            }
+            TargetConn::Sqlite { pool, .. } => {
+                let lite = Arc::new(
+                    SqliteWriter::new(pool.clone(), opts.dest_table)
+                        .with_primary_key_single(opts.primary_key.clone())
+                        .with_infer_primary_key(opts.infer_primary_key)
+                        .with_batch_size(opts.batch_size)
+                        .with_sample_size(opts.sample_size)
+                        .auto_create(opts.auto_create)
+                        .with_mutation_report(opts.mutation_report),
+                );
+
+                let hook: Option<Hook> = if opts.truncate_first {
+                    let lite_for_hook = Arc::clone(&lite);
+                    Some(Box::new(move || {
+                        Box::pin(async move {
+                            lite_for_hook.truncate().await?;
+                            Ok(())
+                        }) as HookFuture
+                    }))
+                } else {
+                    None
+                };
+
+                let writer: Arc<dyn DataWriter> = lite;
+                Ok((writer, hook))
+            }
+            TargetConn::Kafka { producer, options } => {
+                if opts.truncate_first {
+                    tracing::warn!(
+                        sink = %options.name,
+                        "kafka topics cannot be truncated; ignoring truncate"
+                    );
+                }
+                let topic = options.topic.as_deref().unwrap_or(opts.dest_table);
+                let kafka = KafkaWriter::new(producer.0.clone(), topic)
+                    .with_key_field(opts.primary_key.clone())
+                    .with_batch_size(opts.batch_size);
+
+                let writer: Arc<dyn DataWriter> = Arc::new(kafka);
+                Ok((writer, None))
+            }
+            TargetConn::Redshift { pool, staging } => {
+                // COPY favours few large files, so keep the writer's own batch size.
+                let rs = Arc::new(
+                    RedshiftWriter::new(pool.clone(), staging.clone(), opts.dest_table)
+                        .with_primary_key_single(opts.primary_key.clone())
+                        .with_sample_size(opts.sample_size)
+                        .auto_create(opts.auto_create),
+                );
+
+                let hook: Option<Hook> = if opts.truncate_first {
+                    let rs_for_hook = Arc::clone(&rs);
+                    Some(Box::new(move || {
+                        Box::pin(async move {
+                            rs_for_hook.truncate().await?;
+                            Ok(())
+                        }) as HookFuture
+                    }))
+                } else {
+                    None
+                };
+
+                let writer: Arc<dyn DataWriter> = rs;
+                Ok((writer, hook))
+            }
+            TargetConn::Delta {
+                store,
+                prefix,
+                location,
+                options,
+            } => {
+                let delta = Arc::new(
+                    DeltaWriter::new(
+                        Arc::clone(store),
+                        prefix.clone(),
+                        location.clone(),
+                        opts.dest_table,
+                    )
+                    .with_compression(options.compression)
+                    .with_max_file_size((options.max_file_size_mb * 1024 * 1024) as usize)
+                    .with_primary_key_single(opts.primary_key.clone()),
+                );
+
+                let hook: Option<Hook> = if opts.truncate_first {
+                    let delta_for_hook = Arc::clone(&delta);
+                    Some(Box::new(move || {
+                        Box::pin(async move {
+                            delta_for_hook.truncate().await?;
+                            Ok(())
+                        }) as HookFuture
+                    }))
+                } else {
+                    None
+                };
+
+                let writer: Arc<dyn DataWriter> = delta;
+                Ok((writer, hook))
+            }
+            TargetConn::Mongodb { client, options } => {
+                let collection = options.collection.as_deref().unwrap_or(opts.dest_table);
+                let mongo = Arc::new(
+                    MongoWriter::new(client.database(&options.database), collection)
+                        .with_primary_key_single(opts.primary_key.clone())
+                        .with_batch_size(opts.batch_size)
+                        .auto_create(opts.auto_create),
+                );
+
+                let hook: Option<Hook> = if opts.truncate_first {
+                    let mongo_for_hook = Arc::clone(&mongo);
+                    Some(Box::new(move || {
+                        Box::pin(async move {
+                            mongo_for_hook.truncate().await?;
+                            Ok(())
+                        }) as HookFuture
+                    }))
+                } else {
+                    None
+                };
+
+                let writer: Arc<dyn DataWriter> = mongo;
+                Ok((writer, hook))
+            }
+            TargetConn::Webhook { client, options } => {
+                if opts.truncate_first {
+                    tracing::warn!(
+                        sink = %options.name,
+                        "webhook endpoints cannot be truncated; ignoring truncate"
+                    );
+                }
+                // Request bodies are sized by the sink's own batch_size.
+                let webhook = WebhookWriter::new(client.clone(), &options.url, opts.dest_table)
+                    .with_method(options.method)
+                    .with_batch_size(options.batch_size);
+
+                let writer: Arc<dyn DataWriter> = Arc::new(webhook);
+                Ok((writer, None))
+            }
+            TargetConn::Debug { options } => {
+                let debug = DebugWriter::new(opts.dest_table)
+                    .with_format(options.format)
+                    .with_limit(options.limit);
+
+                let writer: Arc<dyn DataWriter> = Arc::new(debug);
+                Ok((writer, None))
+            }
         }
     }
 }
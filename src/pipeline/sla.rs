@@ -0,0 +1,246 @@
+//! Module SLAs: how long a module may run before someone is told.
+//!
+//! A module declares `{{ sla(max_duration="15m", alert="slack") }}`. When it
+//! starts a watch is armed; once `max_duration` passes the breach is logged
+//! and sent to the named alert channel right away, while the module keeps
+//! running. An SLA is a promise to the table's consumers, not a timeout:
+//! nothing is cancelled. The breach is kept in the run history.
+//!
+//! Channels are declared once at the top of the config:
+//!
+//! ```yaml
+//! alerts:
+//!   - name: slack
+//!     kind: slack
+//!     webhook_url_env: SLACK_WEBHOOK_URL
+//!   - name: oncall
+//!     kind: webhook
+//!     url: https://alerts.example.com/apitap
+//! ```
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::errors::{ApitapError, Result};
+use crate::pipeline::resolve_secret;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sla {
+    pub max_duration: Duration,
+    /// Name of the `alerts:` channel told about a breach; logged only
+    /// without one.
+    pub alert: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlertChannel {
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: AlertKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AlertKind {
+    /// Slack incoming webhook; gets a one-line message.
+    Slack {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        webhook_url: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        webhook_url_env: Option<String>,
+    },
+    /// Any endpoint; gets the breach as JSON.
+    Webhook {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        url_env: Option<String>,
+    },
+}
+
+/// A module running longer than its SLA allows.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SlaBreach {
+    pub run_id: String,
+    pub module: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    pub max_duration_secs: u64,
+    pub elapsed_secs: u64,
+    /// The alert fired while the module was still running.
+    pub still_running: bool,
+}
+
+impl SlaBreach {
+    pub fn message(&self) -> String {
+        let owner = self
+            .owner
+            .as_deref()
+            .map(|owner| format!(" (owner: {owner})"))
+            .unwrap_or_default();
+        let state = if self.still_running {
+            "still running"
+        } else {
+            "finished"
+        };
+        format!(
+            "⏰ apitap module {}{owner} exceeded its {}s SLA: {}s elapsed, {state} (run {})",
+            self.module, self.max_duration_secs, self.elapsed_secs, self.run_id
+        )
+    }
+}
+
+impl AlertChannel {
+    pub async fn send(&self, client: &Client, breach: &SlaBreach) -> Result<()> {
+        let (url, body) = match &self.kind {
+            AlertKind::Slack {
+                webhook_url,
+                webhook_url_env,
+            } => (
+                resolve_secret(
+                    webhook_url.as_ref(),
+                    webhook_url_env.as_ref(),
+                    "slack webhook_url",
+                )?,
+                json!({ "text": breach.message() }),
+            ),
+            AlertKind::Webhook { url, url_env } => (
+                resolve_secret(url.as_ref(), url_env.as_ref(), "alert webhook url")?,
+                json!({ "event": "sla_breached", "breach": breach }),
+            ),
+        };
+        client
+            .post(&url)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+fn report(breach: &SlaBreach) {
+    warn!(
+        event = "sla_breached",
+        module = %breach.module,
+        owner = breach.owner.as_deref(),
+        max_duration_secs = breach.max_duration_secs,
+        elapsed_secs = breach.elapsed_secs,
+        still_running = breach.still_running,
+        "⏰ module exceeded its SLA"
+    );
+}
+
+async fn alert(client: &Client, channel: Option<&AlertChannel>, breach: &SlaBreach) {
+    report(breach);
+    let Some(channel) = channel else {
+        return;
+    };
+    match channel.send(client, breach).await {
+        Ok(()) => debug!(channel = %channel.name, "sla alert sent"),
+        Err(e) => warn!(channel = %channel.name, error = %e, "could not send the sla alert"),
+    }
+}
+
+/// Watches one module; fires the alert when the SLA passes mid-run.
+pub struct SlaWatch {
+    sla: Sla,
+    breach: SlaBreach,
+    channel: Option<AlertChannel>,
+    client: Client,
+    started: Instant,
+    fired: Arc<AtomicBool>,
+    task: JoinHandle<()>,
+}
+
+impl SlaWatch {
+    /// Arm the watch; `channels` are the config's `alerts:`.
+    pub fn start(
+        sla: &Sla,
+        channels: &[AlertChannel],
+        client: Client,
+        run_id: &str,
+        module: &str,
+        owner: Option<&str>,
+    ) -> Result<Self> {
+        let channel = match &sla.alert {
+            Some(name) => Some(
+                channels
+                    .iter()
+                    .find(|c| &c.name == name)
+                    .cloned()
+                    .ok_or_else(|| {
+                        ApitapError::ConfigError(format!(
+                            "{module}: sla alert '{name}' is not declared under alerts:"
+                        ))
+                    })?,
+            ),
+            None => None,
+        };
+        let breach = SlaBreach {
+            run_id: run_id.to_string(),
+            module: module.to_string(),
+            owner: owner.map(str::to_string),
+            max_duration_secs: sla.max_duration.as_secs(),
+            elapsed_secs: sla.max_duration.as_secs(),
+            still_running: true,
+        };
+        let fired = Arc::new(AtomicBool::new(false));
+        let task = tokio::spawn({
+            let (client, channel, breach, fired) = (
+                client.clone(),
+                channel.clone(),
+                breach.clone(),
+                fired.clone(),
+            );
+            let max_duration = sla.max_duration;
+            async move {
+                tokio::time::sleep(max_duration).await;
+                fired.store(true, Ordering::SeqCst);
+                alert(&client, channel.as_ref(), &breach).await;
+            }
+        });
+        Ok(Self {
+            sla: sla.clone(),
+            breach,
+            channel,
+            client,
+            started: Instant::now(),
+            fired,
+            task,
+        })
+    }
+
+    /// Disarm the watch when the module is done; whether the SLA was
+    /// breached. A breach the watch has not reported yet is reported now.
+    pub async fn finish(self) -> bool {
+        let elapsed = self.started.elapsed();
+        let breached = elapsed > self.sla.max_duration;
+        if breached && !self.fired.load(Ordering::SeqCst) {
+            self.task.abort();
+            let breach = SlaBreach {
+                elapsed_secs: elapsed.as_secs(),
+                still_running: false,
+                ..self.breach.clone()
+            };
+            alert(&self.client, self.channel.as_ref(), &breach).await;
+        }
+        breached
+    }
+}
+
+impl Drop for SlaWatch {
+    fn drop(&mut self) {
+        // A module that failed needs no more alerts; a send under way finishes.
+        if !self.fired.load(Ordering::SeqCst) {
+            self.task.abort();
+        }
+    }
+}
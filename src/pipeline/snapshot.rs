@@ -0,0 +1,86 @@
+//! Snapshot tables, for sources without an `updated_at` to merge on.
+//!
+//! With `snapshot: true` every run appends the full extract instead of
+//! merging it, each row stamped with the run's start in `snapshot_ts`, so
+//! the table keeps the data as it was at every run:
+//!
+//! ```sql
+//! SELECT * FROM users WHERE snapshot_ts = (SELECT max(snapshot_ts) FROM users)
+//! ```
+//!
+//! `snapshot: {keep: 30d}` also drops snapshots older than that after each
+//! load, the same way `retention` does.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::pipeline::retention::RetentionConfig;
+use crate::writer::middleware::InjectMetadata;
+
+/// Column holding the start of the run a row was loaded by.
+pub const SNAPSHOT_COLUMN: &str = "snapshot_ts";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "SnapshotWire")]
+pub struct SnapshotConfig {
+    pub enabled: bool,
+    /// How long snapshots are kept, e.g. `30d`; forever when unset.
+    pub keep: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SnapshotWire {
+    Enabled(bool),
+    Full {
+        #[serde(default = "enabled")]
+        enabled: bool,
+        #[serde(default)]
+        keep: Option<String>,
+    },
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl From<SnapshotWire> for SnapshotConfig {
+    fn from(wire: SnapshotWire) -> Self {
+        match wire {
+            SnapshotWire::Enabled(enabled) => Self {
+                enabled,
+                keep: None,
+            },
+            SnapshotWire::Full { enabled, keep } => Self { enabled, keep },
+        }
+    }
+}
+
+impl SnapshotConfig {
+    pub fn is_disabled(&self) -> bool {
+        !self.enabled
+    }
+
+    /// `snapshot_ts` for a run started at `at`; RFC 3339 in UTC with a fixed
+    /// width, so the text sorts like the instant.
+    pub fn stamp(&self, at: DateTime<Utc>) -> Option<InjectMetadata> {
+        self.enabled.then(|| {
+            InjectMetadata::new(BTreeMap::from([(
+                SNAPSHOT_COLUMN.to_string(),
+                at.to_rfc3339_opts(SecondsFormat::Micros, true),
+            )]))
+        })
+    }
+
+    /// The retention policy pruning old snapshots, if any.
+    pub fn retention(&self) -> Option<RetentionConfig> {
+        let keep = self.keep.clone().filter(|_| self.enabled)?;
+        Some(RetentionConfig {
+            column: SNAPSHOT_COLUMN.to_string(),
+            keep,
+            detach_partitions: false,
+        })
+    }
+}
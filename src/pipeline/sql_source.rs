@@ -0,0 +1,181 @@
+//! Sources that read rows with a SELECT from another database.
+//!
+//! A source with `sql: {url_env: LEGACY_DB_URL, query: SELECT ...}` skips
+//! the HTTP request: rows are streamed from Postgres or MySQL as JSON
+//! objects and written in batches through the same SQL transform and sinks
+//! as API pages.
+
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use sqlx::mysql::{MySqlPool, MySqlRow};
+use sqlx::types::Json;
+use sqlx::{Column, PgPool, Row, TypeInfo};
+use tracing::{debug, info_span};
+
+use crate::errors::{ApitapError, Result};
+use crate::http::fetcher::{DataFusionPageWriter, FetchStats, PageWriter};
+use crate::pipeline::resolve_secret;
+use crate::writer::WriteMode;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SqlSource {
+    /// `postgres://` or `mysql://` connection URL.
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Environment variable holding the URL; wins over `url`.
+    #[serde(default)]
+    pub url_env: Option<String>,
+    /// SELECT whose rows feed the module, named after the source in its SQL.
+    pub query: String,
+    /// Rows handed to the transform at a time.
+    #[serde(default = "default_batch_rows")]
+    pub batch_rows: usize,
+}
+
+fn default_batch_rows() -> usize {
+    10_000
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SqlDialect {
+    Postgres,
+    MySql,
+}
+
+impl SqlDialect {
+    pub fn from_url(url: &str) -> Result<Self> {
+        let scheme = url.split_once("://").map(|(scheme, _)| scheme);
+        match scheme {
+            Some("postgres" | "postgresql") => Ok(Self::Postgres),
+            Some("mysql" | "mariadb") => Ok(Self::MySql),
+            _ => Err(ApitapError::ConfigError(
+                "sql source url must start with postgres:// or mysql://".to_string(),
+            )),
+        }
+    }
+}
+
+impl SqlSource {
+    pub fn connection_url(&self) -> Result<String> {
+        resolve_secret(self.url.as_ref(), self.url_env.as_ref(), "sql source url")
+    }
+}
+
+/// Wrap a query so Postgres returns every row as one `jsonb` object, which
+/// keeps column types (numbers, booleans, nested json) without a mapping.
+pub fn postgres_json_query(query: &str) -> String {
+    let query = query.trim().trim_end_matches(';');
+    format!("SELECT to_jsonb(q) FROM ({query}) AS q")
+}
+
+/// Stream the rows of `source.query` as JSON objects.
+pub async fn sql_rows(source: &SqlSource) -> Result<BoxStream<'static, Result<Value>>> {
+    let url = source.connection_url()?;
+    let query = source.query.clone();
+    let rows = match SqlDialect::from_url(&url)? {
+        SqlDialect::Postgres => {
+            let pool = PgPool::connect(&url).await?;
+            let query = postgres_json_query(&query);
+            async_stream::try_stream! {
+                let mut rows = sqlx::query_scalar::<_, Json<Value>>(&query).fetch(&pool);
+                while let Some(Json(row)) = rows.try_next().await? {
+                    yield row;
+                }
+                drop(rows);
+                pool.close().await;
+            }
+            .boxed()
+        }
+        SqlDialect::MySql => {
+            let pool = MySqlPool::connect(&url).await?;
+            async_stream::try_stream! {
+                let mut rows = sqlx::query(&query).fetch(&pool);
+                while let Some(row) = rows.try_next().await? {
+                    yield mysql_row(&row)?;
+                }
+                drop(rows);
+                pool.close().await;
+            }
+            .boxed()
+        }
+    };
+    Ok(rows)
+}
+
+/// Run a SQL source: read its rows and write them `batch_rows` at a time.
+pub async fn run_sql_fetch(
+    source: &SqlSource,
+    page_writer: DataFusionPageWriter,
+    write_mode: WriteMode,
+) -> Result<FetchStats> {
+    if source.batch_rows == 0 {
+        return Err(ApitapError::ConfigError(
+            "sql source batch_rows must be at least 1".to_string(),
+        ));
+    }
+    let span = info_span!("sql_source.fetch", batch_rows = source.batch_rows);
+    let _g = span.enter();
+
+    let mut stats = FetchStats::new();
+    let mut batches = sql_rows(source).await?.try_chunks(source.batch_rows);
+    while let Some(batch) = batches.try_next().await.map_err(|e| e.1)? {
+        stats.success_count += 1;
+        stats.total_items += batch.len();
+        debug!(
+            batch = stats.success_count,
+            rows = batch.len(),
+            "sql batch read"
+        );
+        page_writer
+            .write_page(stats.success_count as u64, batch, write_mode.clone())
+            .await?;
+    }
+    Ok(stats)
+}
+
+/// Decode a MySQL row by column type. DECIMAL stays a string so no
+/// precision is lost; binary columns are read as (lossy) UTF-8 text.
+fn mysql_row(row: &MySqlRow) -> Result<Value> {
+    let mut object = Map::with_capacity(row.len());
+    for (idx, column) in row.columns().iter().enumerate() {
+        let value = match column.type_info().name() {
+            "BOOLEAN" => row.try_get::<Option<bool>, _>(idx)?.map(Value::Bool),
+            "TINYINT" | "SMALLINT" | "MEDIUMINT" | "INT" | "BIGINT" | "YEAR" => row
+                .try_get_unchecked::<Option<i64>, _>(idx)?
+                .map(Value::from),
+            "TINYINT UNSIGNED" | "SMALLINT UNSIGNED" | "MEDIUMINT UNSIGNED" | "INT UNSIGNED"
+            | "BIGINT UNSIGNED" => row
+                .try_get_unchecked::<Option<u64>, _>(idx)?
+                .map(Value::from),
+            "FLOAT" => row
+                .try_get::<Option<f32>, _>(idx)?
+                .and_then(|n| Number::from_f64(f64::from(n)))
+                .map(Value::Number),
+            "DOUBLE" => row
+                .try_get::<Option<f64>, _>(idx)?
+                .and_then(Number::from_f64)
+                .map(Value::Number),
+            "JSON" => row.try_get::<Option<Json<Value>>, _>(idx)?.map(|j| j.0),
+            "DATETIME" => row
+                .try_get::<Option<chrono::NaiveDateTime>, _>(idx)?
+                .map(|ts| Value::String(ts.format("%Y-%m-%dT%H:%M:%S%.f").to_string())),
+            "TIMESTAMP" => row
+                .try_get::<Option<chrono::DateTime<chrono::Utc>>, _>(idx)?
+                .map(|ts| Value::String(ts.to_rfc3339())),
+            "DATE" => row
+                .try_get::<Option<chrono::NaiveDate>, _>(idx)?
+                .map(|d| Value::String(d.to_string())),
+            "TIME" => row
+                .try_get::<Option<chrono::NaiveTime>, _>(idx)?
+                .map(|t| Value::String(t.to_string())),
+            "NULL" => None,
+            _ => row
+                .try_get_unchecked::<Option<Vec<u8>>, _>(idx)?
+                .map(|bytes| Value::String(String::from_utf8_lossy(&bytes).into_owned())),
+        };
+        object.insert(column.name().to_string(), value.unwrap_or(Value::Null));
+    }
+    Ok(Value::Object(object))
+}
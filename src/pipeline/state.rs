@@ -0,0 +1,703 @@
+//! Persistent run state and its storage backends: pages awaiting retry,
+//! checkpoints of interrupted downloads, the history of recent runs, OAuth2
+//! refresh tokens rotated by their provider, the validators of cached
+//! pages, the watermarks of incremental modules and the pages written by
+//! an unfinished run.
+//!
+//! State lives in a small embedded SQLite database by default
+//! (`.apitap/state.db`); a path ending in `.json` keeps it in a plain JSON
+//! file instead. Either backend can be exported to and imported from the
+//! same JSON document (`apitap state export|import`), so state can move
+//! between machines or be kept in secure storage for stateless CI runs.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::SqlitePool;
+
+use crate::errors::{ApitapError, Result};
+use crate::pipeline::download_state::DownloadCheckpoint;
+use crate::pipeline::http_cache::PageValidator;
+use crate::pipeline::page_progress::WrittenPage;
+use crate::pipeline::retry_state::{FailedPage, RetryState};
+use crate::pipeline::run_history::{RunRecord, RUN_HISTORY_LIMIT};
+use crate::pipeline::watermark::Watermark;
+
+pub const DEFAULT_STATE_PATH: &str = ".apitap/state.db";
+
+/// Bumped when the exported document changes incompatibly.
+pub const STATE_FORMAT_VERSION: u32 = 1;
+
+/// Everything apitap remembers between runs; also the export format.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    pub version: u32,
+    #[serde(default)]
+    pub retry: RetryState,
+    /// Module name -> progress of its interrupted export download.
+    #[serde(default)]
+    pub downloads: BTreeMap<String, DownloadCheckpoint>,
+    /// Recorded runs, oldest first.
+    #[serde(default)]
+    pub runs: Vec<RunRecord>,
+    /// Source name -> latest refresh token issued to it.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub refresh_tokens: BTreeMap<String, StoredRefreshToken>,
+    /// Module name -> page URL -> validators of its last fetch.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub validators: BTreeMap<String, BTreeMap<String, PageValidator>>,
+    /// Module name -> highest cursor value it loaded.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub watermarks: BTreeMap<String, Watermark>,
+    /// Module name -> page URL -> pages its unfinished run wrote.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub progress: BTreeMap<String, BTreeMap<String, WrittenPage>>,
+}
+
+/// A refresh token handed out in place of the configured one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredRefreshToken {
+    pub token: String,
+    /// SHA-256 of the configured token it descends from; once the
+    /// configured token changes, the stored one is stale.
+    pub origin: String,
+    pub rotated_at: DateTime<Utc>,
+}
+
+impl Default for StateSnapshot {
+    fn default() -> Self {
+        Self {
+            version: STATE_FORMAT_VERSION,
+            retry: RetryState::default(),
+            downloads: BTreeMap::new(),
+            runs: Vec::new(),
+            refresh_tokens: BTreeMap::new(),
+            validators: BTreeMap::new(),
+            watermarks: BTreeMap::new(),
+            progress: BTreeMap::new(),
+        }
+    }
+}
+
+impl StateSnapshot {
+    /// Parse an exported document; bare retry-state files from older releases are accepted too.
+    pub fn from_json(bytes: &[u8]) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_slice(bytes)?;
+        if value.get("version").is_none() && value.get("modules").is_some() {
+            return Ok(Self {
+                version: STATE_FORMAT_VERSION,
+                retry: serde_json::from_value(value)?,
+                ..Self::default()
+            });
+        }
+        let snapshot: Self = serde_json::from_value(value)?;
+        if snapshot.version > STATE_FORMAT_VERSION {
+            return Err(ApitapError::ConfigError(format!(
+                "state format version {} is newer than supported version {}",
+                snapshot.version, STATE_FORMAT_VERSION
+            )));
+        }
+        Ok(snapshot)
+    }
+
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec_pretty(self)?)
+    }
+}
+
+#[derive(Debug)]
+pub enum StateBackend {
+    Json(PathBuf),
+    Sqlite(SqlitePool),
+}
+
+impl StateBackend {
+    /// Open the store at `path`: `.json` selects the JSON file backend, anything else SQLite.
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        if path.extension().is_some_and(|ext| ext == "json") {
+            return Ok(Self::Json(path.to_path_buf()));
+        }
+
+        let opts = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .busy_timeout(std::time::Duration::from_secs(30));
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(opts)
+            .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS retry_pages (
+                module TEXT NOT NULL,
+                page INTEGER NOT NULL,
+                error TEXT NOT NULL,
+                failed_at TEXT NOT NULL,
+                PRIMARY KEY (module, page)
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS runs (
+                run_id TEXT PRIMARY KEY,
+                started_at TEXT NOT NULL,
+                record TEXT NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS download_checkpoints (
+                module TEXT PRIMARY KEY,
+                checkpoint TEXT NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS refresh_tokens (
+                source TEXT PRIMARY KEY,
+                record TEXT NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS page_validators (
+                module TEXT NOT NULL,
+                url TEXT NOT NULL,
+                record TEXT NOT NULL,
+                PRIMARY KEY (module, url)
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS page_progress (
+                module TEXT NOT NULL,
+                url TEXT NOT NULL,
+                record TEXT NOT NULL,
+                PRIMARY KEY (module, url)
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS watermarks (
+                module TEXT PRIMARY KEY,
+                record TEXT NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        Ok(Self::Sqlite(pool))
+    }
+
+    pub async fn load(&self) -> Result<StateSnapshot> {
+        match self {
+            Self::Json(path) => match std::fs::read(path) {
+                Ok(bytes) => StateSnapshot::from_json(&bytes),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(StateSnapshot::default()),
+                Err(e) => Err(e.into()),
+            },
+            Self::Sqlite(pool) => {
+                let rows: Vec<(String, i64, String, DateTime<Utc>)> = sqlx::query_as(
+                    "SELECT module, page, error, failed_at FROM retry_pages ORDER BY module, page",
+                )
+                .fetch_all(pool)
+                .await?;
+                let mut snapshot = StateSnapshot::default();
+                for (module, page, error, failed_at) in rows {
+                    snapshot
+                        .retry
+                        .modules
+                        .entry(module)
+                        .or_default()
+                        .insert(page as u64, FailedPage { error, failed_at });
+                }
+                let records: Vec<(String,)> =
+                    sqlx::query_as("SELECT record FROM runs ORDER BY started_at, run_id")
+                        .fetch_all(pool)
+                        .await?;
+                for (record,) in records {
+                    snapshot.runs.push(serde_json::from_str(&record)?);
+                }
+                let checkpoints: Vec<(String, String)> =
+                    sqlx::query_as("SELECT module, checkpoint FROM download_checkpoints")
+                        .fetch_all(pool)
+                        .await?;
+                for (module, checkpoint) in checkpoints {
+                    snapshot
+                        .downloads
+                        .insert(module, serde_json::from_str(&checkpoint)?);
+                }
+                let tokens: Vec<(String, String)> =
+                    sqlx::query_as("SELECT source, record FROM refresh_tokens")
+                        .fetch_all(pool)
+                        .await?;
+                for (source, record) in tokens {
+                    snapshot
+                        .refresh_tokens
+                        .insert(source, serde_json::from_str(&record)?);
+                }
+                let validators: Vec<(String, String, String)> =
+                    sqlx::query_as("SELECT module, url, record FROM page_validators")
+                        .fetch_all(pool)
+                        .await?;
+                for (module, url, record) in validators {
+                    snapshot
+                        .validators
+                        .entry(module)
+                        .or_default()
+                        .insert(url, serde_json::from_str(&record)?);
+                }
+                let watermarks: Vec<(String, String)> =
+                    sqlx::query_as("SELECT module, record FROM watermarks")
+                        .fetch_all(pool)
+                        .await?;
+                for (module, record) in watermarks {
+                    snapshot
+                        .watermarks
+                        .insert(module, serde_json::from_str(&record)?);
+                }
+                let progress: Vec<(String, String, String)> =
+                    sqlx::query_as("SELECT module, url, record FROM page_progress")
+                        .fetch_all(pool)
+                        .await?;
+                for (module, url, record) in progress {
+                    snapshot
+                        .progress
+                        .entry(module)
+                        .or_default()
+                        .insert(url, serde_json::from_str(&record)?);
+                }
+                Ok(snapshot)
+            }
+        }
+    }
+
+    /// Persist one retry page from `snapshot`; removed if it is no longer there.
+    pub async fn save_retry_page(
+        &self,
+        snapshot: &StateSnapshot,
+        module: &str,
+        page: u64,
+    ) -> Result<()> {
+        match self {
+            Self::Json(path) => write_json(path, snapshot),
+            Self::Sqlite(pool) => {
+                let entry = snapshot
+                    .retry
+                    .modules
+                    .get(module)
+                    .and_then(|pages| pages.get(&page));
+                match entry {
+                    Some(failed) => {
+                        sqlx::query(
+                            "INSERT INTO retry_pages (module, page, error, failed_at)
+                             VALUES (?, ?, ?, ?)
+                             ON CONFLICT (module, page)
+                             DO UPDATE SET error = excluded.error, failed_at = excluded.failed_at",
+                        )
+                        .bind(module)
+                        .bind(page as i64)
+                        .bind(&failed.error)
+                        .bind(failed.failed_at)
+                        .execute(pool)
+                        .await?;
+                    }
+                    None => {
+                        sqlx::query("DELETE FROM retry_pages WHERE module = ? AND page = ?")
+                            .bind(module)
+                            .bind(page as i64)
+                            .execute(pool)
+                            .await?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Persist `module`'s download checkpoint from `snapshot`; removed if it
+    /// is no longer there.
+    pub async fn save_download(&self, snapshot: &StateSnapshot, module: &str) -> Result<()> {
+        match self {
+            Self::Json(path) => write_json(path, snapshot),
+            Self::Sqlite(pool) => {
+                match snapshot.downloads.get(module) {
+                    Some(checkpoint) => {
+                        sqlx::query(
+                            "INSERT INTO download_checkpoints (module, checkpoint) VALUES (?, ?)
+                             ON CONFLICT (module) DO UPDATE SET checkpoint = excluded.checkpoint",
+                        )
+                        .bind(module)
+                        .bind(serde_json::to_string(checkpoint)?)
+                        .execute(pool)
+                        .await?;
+                    }
+                    None => {
+                        sqlx::query("DELETE FROM download_checkpoints WHERE module = ?")
+                            .bind(module)
+                            .execute(pool)
+                            .await?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Persist `source`'s refresh token from `snapshot`.
+    pub async fn save_refresh_token(&self, snapshot: &StateSnapshot, source: &str) -> Result<()> {
+        match self {
+            Self::Json(path) => write_json(path, snapshot),
+            Self::Sqlite(pool) => {
+                match snapshot.refresh_tokens.get(source) {
+                    Some(record) => {
+                        sqlx::query(
+                            "INSERT INTO refresh_tokens (source, record) VALUES (?, ?)
+                             ON CONFLICT (source) DO UPDATE SET record = excluded.record",
+                        )
+                        .bind(source)
+                        .bind(serde_json::to_string(record)?)
+                        .execute(pool)
+                        .await?;
+                    }
+                    None => {
+                        sqlx::query("DELETE FROM refresh_tokens WHERE source = ?")
+                            .bind(source)
+                            .execute(pool)
+                            .await?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Persist the validators of `module`'s `urls` from `snapshot`.
+    pub async fn save_validators(
+        &self,
+        snapshot: &StateSnapshot,
+        module: &str,
+        urls: &[String],
+    ) -> Result<()> {
+        match self {
+            Self::Json(path) => write_json(path, snapshot),
+            Self::Sqlite(pool) => {
+                let Some(validators) = snapshot.validators.get(module) else {
+                    return Ok(());
+                };
+                let mut tx = pool.begin().await?;
+                for url in urls {
+                    let Some(validator) = validators.get(url) else {
+                        continue;
+                    };
+                    sqlx::query(
+                        "INSERT INTO page_validators (module, url, record) VALUES (?, ?, ?)
+                         ON CONFLICT (module, url) DO UPDATE SET record = excluded.record",
+                    )
+                    .bind(module)
+                    .bind(url)
+                    .bind(serde_json::to_string(validator)?)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                tx.commit().await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Persist `module`'s watermark from `snapshot`.
+    pub async fn save_watermark(&self, snapshot: &StateSnapshot, module: &str) -> Result<()> {
+        match self {
+            Self::Json(path) => write_json(path, snapshot),
+            Self::Sqlite(pool) => {
+                match snapshot.watermarks.get(module) {
+                    Some(watermark) => {
+                        sqlx::query(
+                            "INSERT INTO watermarks (module, record) VALUES (?, ?)
+                             ON CONFLICT (module) DO UPDATE SET record = excluded.record",
+                        )
+                        .bind(module)
+                        .bind(serde_json::to_string(watermark)?)
+                        .execute(pool)
+                        .await?;
+                    }
+                    None => {
+                        sqlx::query("DELETE FROM watermarks WHERE module = ?")
+                            .bind(module)
+                            .execute(pool)
+                            .await?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Persist the page of `module` at `url` from `snapshot`; with no `url`,
+    /// all of the module's pages.
+    pub async fn save_progress(
+        &self,
+        snapshot: &StateSnapshot,
+        module: &str,
+        url: Option<&str>,
+    ) -> Result<()> {
+        match self {
+            Self::Json(path) => write_json(path, snapshot),
+            Self::Sqlite(pool) => {
+                let pages = snapshot.progress.get(module);
+                let Some(url) = url else {
+                    let mut tx = pool.begin().await?;
+                    sqlx::query("DELETE FROM page_progress WHERE module = ?")
+                        .bind(module)
+                        .execute(&mut *tx)
+                        .await?;
+                    for (url, page) in pages.into_iter().flatten() {
+                        sqlx::query(
+                            "INSERT INTO page_progress (module, url, record) VALUES (?, ?, ?)",
+                        )
+                        .bind(module)
+                        .bind(url)
+                        .bind(serde_json::to_string(page)?)
+                        .execute(&mut *tx)
+                        .await?;
+                    }
+                    tx.commit().await?;
+                    return Ok(());
+                };
+                match pages.and_then(|pages| pages.get(url)) {
+                    Some(page) => {
+                        sqlx::query(
+                            "INSERT INTO page_progress (module, url, record) VALUES (?, ?, ?)
+                             ON CONFLICT (module, url) DO UPDATE SET record = excluded.record",
+                        )
+                        .bind(module)
+                        .bind(url)
+                        .bind(serde_json::to_string(page)?)
+                        .execute(pool)
+                        .await?;
+                    }
+                    None => {
+                        sqlx::query("DELETE FROM page_progress WHERE module = ? AND url = ?")
+                            .bind(module)
+                            .bind(url)
+                            .execute(pool)
+                            .await?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Append `run` to the history in `snapshot` and persist it, dropping
+    /// the oldest runs beyond [`RUN_HISTORY_LIMIT`].
+    pub async fn save_run(&self, snapshot: &mut StateSnapshot, run: RunRecord) -> Result<()> {
+        snapshot.runs.retain(|r| r.run_id != run.run_id);
+        snapshot.runs.push(run.clone());
+        let excess = snapshot.runs.len().saturating_sub(RUN_HISTORY_LIMIT);
+        snapshot.runs.drain(..excess);
+        match self {
+            Self::Json(path) => write_json(path, snapshot),
+            Self::Sqlite(pool) => {
+                let mut tx = pool.begin().await?;
+                sqlx::query(
+                    "INSERT INTO runs (run_id, started_at, record) VALUES (?, ?, ?)
+                     ON CONFLICT (run_id)
+                     DO UPDATE SET started_at = excluded.started_at, record = excluded.record",
+                )
+                .bind(&run.run_id)
+                .bind(run.started_at)
+                .bind(serde_json::to_string(&run)?)
+                .execute(&mut *tx)
+                .await?;
+                sqlx::query(
+                    "DELETE FROM runs WHERE run_id NOT IN
+                     (SELECT run_id FROM runs ORDER BY started_at DESC, run_id DESC LIMIT ?)",
+                )
+                .bind(RUN_HISTORY_LIMIT as i64)
+                .execute(&mut *tx)
+                .await?;
+                tx.commit().await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Overwrite the whole store with `snapshot` (used by import).
+    pub async fn replace(&self, snapshot: &StateSnapshot) -> Result<()> {
+        match self {
+            Self::Json(path) => write_json(path, snapshot),
+            Self::Sqlite(pool) => {
+                let mut tx = pool.begin().await?;
+                sqlx::query("DELETE FROM retry_pages")
+                    .execute(&mut *tx)
+                    .await?;
+                sqlx::query("DELETE FROM runs").execute(&mut *tx).await?;
+                sqlx::query("DELETE FROM download_checkpoints")
+                    .execute(&mut *tx)
+                    .await?;
+                sqlx::query("DELETE FROM refresh_tokens")
+                    .execute(&mut *tx)
+                    .await?;
+                sqlx::query("DELETE FROM page_validators")
+                    .execute(&mut *tx)
+                    .await?;
+                sqlx::query("DELETE FROM watermarks")
+                    .execute(&mut *tx)
+                    .await?;
+                sqlx::query("DELETE FROM page_progress")
+                    .execute(&mut *tx)
+                    .await?;
+                for (module, pages) in &snapshot.progress {
+                    for (url, page) in pages {
+                        sqlx::query(
+                            "INSERT INTO page_progress (module, url, record) VALUES (?, ?, ?)",
+                        )
+                        .bind(module)
+                        .bind(url)
+                        .bind(serde_json::to_string(page)?)
+                        .execute(&mut *tx)
+                        .await?;
+                    }
+                }
+                for (module, watermark) in &snapshot.watermarks {
+                    sqlx::query("INSERT INTO watermarks (module, record) VALUES (?, ?)")
+                        .bind(module)
+                        .bind(serde_json::to_string(watermark)?)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                for (module, validators) in &snapshot.validators {
+                    for (url, validator) in validators {
+                        sqlx::query(
+                            "INSERT INTO page_validators (module, url, record) VALUES (?, ?, ?)",
+                        )
+                        .bind(module)
+                        .bind(url)
+                        .bind(serde_json::to_string(validator)?)
+                        .execute(&mut *tx)
+                        .await?;
+                    }
+                }
+                for (source, record) in &snapshot.refresh_tokens {
+                    sqlx::query("INSERT INTO refresh_tokens (source, record) VALUES (?, ?)")
+                        .bind(source)
+                        .bind(serde_json::to_string(record)?)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                for (module, checkpoint) in &snapshot.downloads {
+                    sqlx::query(
+                        "INSERT INTO download_checkpoints (module, checkpoint) VALUES (?, ?)",
+                    )
+                    .bind(module)
+                    .bind(serde_json::to_string(checkpoint)?)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                for run in &snapshot.runs {
+                    sqlx::query("INSERT INTO runs (run_id, started_at, record) VALUES (?, ?, ?)")
+                        .bind(&run.run_id)
+                        .bind(run.started_at)
+                        .bind(serde_json::to_string(run)?)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                for (module, pages) in &snapshot.retry.modules {
+                    for (page, failed) in pages {
+                        sqlx::query(
+                            "INSERT INTO retry_pages (module, page, error, failed_at) VALUES (?, ?, ?, ?)",
+                        )
+                        .bind(module)
+                        .bind(*page as i64)
+                        .bind(&failed.error)
+                        .bind(failed.failed_at)
+                        .execute(&mut *tx)
+                        .await?;
+                    }
+                }
+                tx.commit().await?;
+                Ok(())
+            }
+        }
+    }
+}
+
+fn write_json(path: &Path, snapshot: &StateSnapshot) -> Result<()> {
+    // Write-then-rename so a crash never leaves a truncated file behind.
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, snapshot.to_json()?)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Read every module's state, e.g. for export.
+pub async fn export_state(path: impl AsRef<Path>) -> Result<StateSnapshot> {
+    StateBackend::open(path).await?.load().await
+}
+
+/// Record a finished run in the store at `path`.
+pub async fn record_run(path: impl AsRef<Path>, run: RunRecord) -> Result<()> {
+    let backend = StateBackend::open(path).await?;
+    let mut snapshot = backend.load().await?;
+    backend.save_run(&mut snapshot, run).await
+}
+
+/// Replace the state at `path` with `snapshot`, or merge it in (imported entries win).
+pub async fn import_state(
+    path: impl AsRef<Path>,
+    mut snapshot: StateSnapshot,
+    merge: bool,
+) -> Result<()> {
+    let backend = StateBackend::open(path).await?;
+    if merge {
+        let mut current = backend.load().await?;
+        for (module, pages) in std::mem::take(&mut snapshot.retry.modules) {
+            current
+                .retry
+                .modules
+                .entry(module)
+                .or_default()
+                .extend(pages);
+        }
+        current
+            .downloads
+            .extend(std::mem::take(&mut snapshot.downloads));
+        current
+            .refresh_tokens
+            .extend(std::mem::take(&mut snapshot.refresh_tokens));
+        current
+            .watermarks
+            .extend(std::mem::take(&mut snapshot.watermarks));
+        for (module, pages) in std::mem::take(&mut snapshot.progress) {
+            current.progress.entry(module).or_default().extend(pages);
+        }
+        for (module, validators) in std::mem::take(&mut snapshot.validators) {
+            current
+                .validators
+                .entry(module)
+                .or_default()
+                .extend(validators);
+        }
+        for run in std::mem::take(&mut snapshot.runs) {
+            current.runs.retain(|r| r.run_id != run.run_id);
+            current.runs.push(run);
+        }
+        current.runs.sort_by_key(|r| r.started_at);
+        snapshot = current;
+    }
+    snapshot.version = STATE_FORMAT_VERSION;
+    backend.replace(&snapshot).await
+}
@@ -0,0 +1,151 @@
+//! Client certificates (mTLS) for sources and Postgres targets.
+//!
+//! Zero-trust networks admit only clients presenting a certificate. Each
+//! PEM comes from a file or from an environment variable holding the PEM
+//! text; the CA is optional and trusted on top of the system roots:
+//!
+//! ```yaml
+//! tls:
+//!   cert_file: /certs/client.crt
+//!   key_env: CLIENT_KEY_PEM
+//!   ca_file: /certs/internal-ca.crt
+//! ```
+
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::{PgConnectOptions, PgSslMode};
+
+use crate::errors::{ApitapError, Result};
+use crate::pipeline::resolve_secret;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// Client certificate chain (PEM).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cert_file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cert_env: Option<String>,
+    /// Private key of the client certificate (PEM).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_env: Option<String>,
+    /// CA the server certificate is checked against, besides the system roots.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_env: Option<String>,
+    /// How Postgres verifies the server (ignored by HTTP sources, which
+    /// always verify).
+    #[serde(default)]
+    pub ssl_mode: TlsMode,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TlsMode {
+    /// Encrypt without checking the server certificate.
+    Require,
+    /// Check the server certificate against the CA.
+    VerifyCa,
+    /// Also check that it names the host.
+    #[default]
+    VerifyFull,
+}
+
+impl From<TlsMode> for PgSslMode {
+    fn from(mode: TlsMode) -> Self {
+        match mode {
+            TlsMode::Require => PgSslMode::Require,
+            TlsMode::VerifyCa => PgSslMode::VerifyCa,
+            TlsMode::VerifyFull => PgSslMode::VerifyFull,
+        }
+    }
+}
+
+/// The PEM from `file` or `env`; `None` when neither is set.
+fn read_pem(file: Option<&String>, env: Option<&String>, what: &str) -> Result<Option<Vec<u8>>> {
+    match (file, env) {
+        (Some(_), Some(_)) => Err(ApitapError::ConfigError(format!(
+            "tls: {what} takes a file or an environment variable, not both"
+        ))),
+        (Some(path), None) => std::fs::read(path).map(Some).map_err(|e| {
+            ApitapError::ConfigError(format!("tls: cannot read {what} '{path}': {e}"))
+        }),
+        (None, Some(_)) => {
+            resolve_secret(None, env, &format!("tls {what}")).map(|pem| Some(pem.into_bytes()))
+        }
+        (None, None) => Ok(None),
+    }
+}
+
+/// A client certificate with its key.
+#[derive(Clone)]
+pub struct ClientCertificate {
+    pub cert: Vec<u8>,
+    pub key: Vec<u8>,
+}
+
+impl std::fmt::Debug for ClientCertificate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientCertificate").finish_non_exhaustive()
+    }
+}
+
+impl TlsConfig {
+    /// The client certificate and key; `None` when only a CA is configured.
+    pub fn client_certificate(&self) -> Result<Option<ClientCertificate>> {
+        let cert = read_pem(
+            self.cert_file.as_ref(),
+            self.cert_env.as_ref(),
+            "certificate",
+        )?;
+        let key = read_pem(self.key_file.as_ref(), self.key_env.as_ref(), "key")?;
+        match (cert, key) {
+            (Some(cert), Some(key)) => Ok(Some(ClientCertificate { cert, key })),
+            (None, None) => Ok(None),
+            _ => Err(ApitapError::ConfigError(
+                "tls: a client certificate needs both cert_* and key_*".to_string(),
+            )),
+        }
+    }
+
+    pub fn ca(&self) -> Result<Option<Vec<u8>>> {
+        read_pem(self.ca_file.as_ref(), self.ca_env.as_ref(), "ca")
+    }
+
+    /// The identity reqwest presents: key and certificate in one PEM.
+    pub fn identity(&self) -> Result<Option<reqwest::Identity>> {
+        let Some(ClientCertificate { cert, mut key }) = self.client_certificate()? else {
+            return Ok(None);
+        };
+        key.push(b'\n');
+        key.extend_from_slice(&cert);
+        reqwest::Identity::from_pem(&key)
+            .map(Some)
+            .map_err(|e| ApitapError::ConfigError(format!("tls: invalid client certificate: {e}")))
+    }
+
+    /// The CA certificates reqwest trusts in addition to the system roots.
+    pub fn root_certificates(&self) -> Result<Vec<reqwest::Certificate>> {
+        let Some(ca) = self.ca()? else {
+            return Ok(Vec::new());
+        };
+        reqwest::Certificate::from_pem_bundle(&ca)
+            .map_err(|e| ApitapError::ConfigError(format!("tls: invalid ca: {e}")))
+    }
+
+    /// Postgres connect options presenting the certificate and checking the
+    /// server per `ssl_mode`.
+    pub fn apply_pg(&self, mut options: PgConnectOptions) -> Result<PgConnectOptions> {
+        options = options.ssl_mode(self.ssl_mode.into());
+        if let Some(ClientCertificate { cert, key }) = self.client_certificate()? {
+            options = options
+                .ssl_client_cert_from_pem(cert)
+                .ssl_client_key_from_pem(key);
+        }
+        if let Some(ca) = self.ca()? {
+            options = options.ssl_root_cert_from_pem(ca);
+        }
+        Ok(options)
+    }
+}
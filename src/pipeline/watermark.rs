@@ -0,0 +1,186 @@
+//! Incremental loads for sources with `incremental:`.
+//!
+//! ```yaml
+//! incremental:
+//!   cursor: updated_at          # field of the API's rows, or a /json/pointer
+//!   param: updated_since        # sent with the watermark (optional)
+//!   initial: "2024-01-01"       # asked for on the first run (optional)
+//!   lookback: 2d                # re-fetch a window before the watermark (optional)
+//! ```
+//!
+//! The highest `cursor` value among the rows a module fetched is kept in the
+//! state store as its watermark, and the next run asks only for what came
+//! after it: with `param:` the watermark is sent as that query parameter,
+//! and `{{ watermark }}` can be used in `query_params` values or in a SQL
+//! source's `query` (undefined on a first run without `initial`, so
+//! `{% if watermark %}` works). `lookback` moves a timestamp watermark back
+//! before it is sent (see [`crate::pipeline::lookback`]); the source must
+//! merge on a primary key so the window replaces the rows it fetches again.
+//!
+//! The cursor is read from rows as the API returns them, before transforms.
+//! Timestamps and dates compare as instants, numbers as numbers and anything
+//! else as text, and the stored value is the one the API sent. The
+//! watermark only moves once the module's rows were written and no page
+//! failed, so a failed run fetches the same window again.
+
+use std::sync::Mutex;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::warn;
+
+use crate::errors::Result;
+use crate::pipeline::lookback::Lookback;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Incremental {
+    /// Field holding the row's last change, or a JSON pointer into the row.
+    pub cursor: String,
+    /// Query parameter the watermark is sent as.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub param: Option<String>,
+    /// Watermark of the first run, before anything was stored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub initial: Option<String>,
+    /// Window re-fetched before the watermark, such as `2d`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lookback: Option<Lookback>,
+}
+
+/// The highest cursor value a module loaded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Watermark {
+    pub cursor: String,
+    pub value: String,
+    pub saved_at: DateTime<Utc>,
+}
+
+impl Incremental {
+    /// The stored watermark, if it was kept for this cursor.
+    pub fn stored<'a>(&self, module: &str, stored: Option<&'a Watermark>) -> Option<&'a Watermark> {
+        match stored {
+            Some(mark) if mark.cursor != self.cursor => {
+                warn!(
+                    %module,
+                    stored = %mark.cursor,
+                    cursor = %self.cursor,
+                    "watermark was kept for another cursor; starting over"
+                );
+                None
+            }
+            other => other,
+        }
+    }
+
+    /// The value to ask for this run: the stored watermark, else `initial`,
+    /// moved back by `lookback`.
+    pub fn since(&self, stored: Option<&Watermark>) -> Result<Option<String>> {
+        let value = stored.map(|mark| &mark.value).or(self.initial.as_ref());
+        match (value, &self.lookback) {
+            (Some(value), Some(lookback)) => lookback.rewind(value).map(Some),
+            (value, _) => Ok(value.cloned()),
+        }
+    }
+}
+
+/// Keeps the highest cursor value seen in a module's rows.
+#[derive(Debug)]
+pub struct WatermarkTracker {
+    cursor: String,
+    max: Mutex<Option<(CursorValue, String)>>,
+}
+
+impl WatermarkTracker {
+    /// A tracker starting from `start`, so the watermark never moves back.
+    pub fn new(cursor: impl Into<String>, start: Option<&str>) -> Self {
+        Self {
+            cursor: cursor.into(),
+            max: Mutex::new(start.map(|value| (CursorValue::parse(value), value.to_string()))),
+        }
+    }
+
+    pub fn observe(&self, row: &Value) {
+        let field = if self.cursor.starts_with('/') {
+            row.pointer(&self.cursor)
+        } else {
+            row.get(&self.cursor)
+        };
+        let text = match field {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Number(n)) => n.to_string(),
+            _ => return,
+        };
+        let value = CursorValue::parse(&text);
+        let mut max = self.max.lock().unwrap_or_else(|e| e.into_inner());
+        if max
+            .as_ref()
+            .map_or(true, |(current, _)| value.is_after(current))
+        {
+            *max = Some((value, text));
+        }
+    }
+
+    pub fn observe_all(&self, rows: &[Value]) {
+        for row in rows {
+            self.observe(row);
+        }
+    }
+
+    /// The highest value seen, as the rows held it.
+    pub fn value(&self) -> Option<String> {
+        self.max
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .map(|(_, text)| text.clone())
+    }
+
+    /// The watermark to store.
+    pub fn watermark(&self) -> Option<Watermark> {
+        self.value().map(|value| Watermark {
+            cursor: self.cursor.clone(),
+            value,
+            saved_at: Utc::now(),
+        })
+    }
+}
+
+const NAIVE_FORMATS: [&str; 2] = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"];
+
+#[derive(Debug, Clone, PartialEq)]
+enum CursorValue {
+    Time(DateTime<Utc>),
+    Number(f64),
+    Text(String),
+}
+
+impl CursorValue {
+    fn parse(text: &str) -> Self {
+        if let Ok(at) = DateTime::parse_from_rfc3339(text) {
+            return Self::Time(at.with_timezone(&Utc));
+        }
+        for format in NAIVE_FORMATS {
+            if let Ok(at) = NaiveDateTime::parse_from_str(text, format) {
+                return Self::Time(at.and_utc());
+            }
+        }
+        if let Ok(day) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+            return Self::Time(day.and_time(NaiveTime::MIN).and_utc());
+        }
+        match text.parse::<f64>() {
+            Ok(n) if n.is_finite() => Self::Number(n),
+            _ => Self::Text(text.to_string()),
+        }
+    }
+
+    /// Values of different kinds never replace one another.
+    fn is_after(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Time(a), Self::Time(b)) => a > b,
+            (Self::Number(a), Self::Number(b)) => a > b,
+            (Self::Text(a), Self::Text(b)) => a > b,
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::sync::Arc;
+
+use base64::alphabet;
+use base64::engine::general_purpose::{GeneralPurpose, GeneralPurposeConfig};
+use base64::engine::DecodePaddingMode;
+use base64::Engine;
+use object_store::path::Path as ObjectPath;
+use object_store::{ObjectStore, PutPayload};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+
+use crate::errors::Result;
+use crate::utils::storage::resolve_object_store;
+
+/// Suffix of the column holding the URL of an offloaded value.
+pub const URL_SUFFIX: &str = "_url";
+
+/// `binary_fields:` block on a source.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BinaryFieldsConfig {
+    /// Fields holding base64 (keys or JSON pointers); a `data:` URI prefix
+    /// is accepted.
+    pub fields: Vec<String>,
+    /// Local directory or `s3://`, `gs://`, `az://` location; decoded
+    /// payloads above `max_inline_bytes` are stored there.
+    #[serde(default)]
+    pub offload: Option<String>,
+    /// Largest payload kept in the row; only applies with `offload`.
+    #[serde(default = "default_max_inline_bytes")]
+    pub max_inline_bytes: usize,
+    /// Base URL written for offloaded objects instead of the storage location,
+    /// e.g. a CDN in front of the bucket.
+    #[serde(default)]
+    pub url_prefix: Option<String>,
+}
+
+fn default_max_inline_bytes() -> usize {
+    1024 * 1024
+}
+
+const LENIENT: GeneralPurposeConfig =
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+const STANDARD: GeneralPurpose = GeneralPurpose::new(&alphabet::STANDARD, LENIENT);
+const URL_SAFE: GeneralPurpose = GeneralPurpose::new(&alphabet::URL_SAFE, LENIENT);
+
+/// Decode base64 in the standard or URL-safe alphabet, with or without
+/// padding, line breaks or a `data:<mime>;base64,` prefix.
+pub fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let text = match text.strip_prefix("data:") {
+        Some(uri) => uri.split_once(";base64,")?.1,
+        None => text,
+    };
+    let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    STANDARD
+        .decode(&compact)
+        .or_else(|_| URL_SAFE.decode(&compact))
+        .ok()
+}
+
+/// Standard padded base64, the form sinks decode binary columns from.
+pub fn encode_base64(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+struct Offload {
+    store: Arc<dyn ObjectStore>,
+    prefix: ObjectPath,
+    url_base: String,
+    max_inline_bytes: usize,
+}
+
+/// Decodes base64 fields so binary sinks can store them as bytes (`BYTEA` on
+/// Postgres). With `offload`, payloads above `max_inline_bytes` go to object
+/// storage instead, keyed by their SHA-256; the field becomes null and a
+/// sibling `<field>_url` column holds the object's URL.
+///
+/// Inline values stay in the row as canonical base64, since JSON carries no
+/// bytes; values that are not base64 are left untouched.
+pub struct BinaryFields {
+    fields: Vec<String>,
+    offload: Option<Offload>,
+}
+
+impl std::fmt::Debug for BinaryFields {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BinaryFields")
+            .field("fields", &self.fields)
+            .field("offload", &self.offload.as_ref().map(|o| &o.url_base))
+            .finish()
+    }
+}
+
+impl BinaryFields {
+    pub fn from_config(cfg: &BinaryFieldsConfig) -> Result<Self> {
+        let offload = match &cfg.offload {
+            Some(location) => {
+                let (store, prefix) = resolve_object_store(location)?;
+                let url_base = cfg.url_prefix.as_deref().unwrap_or(location);
+                Some(Offload {
+                    store,
+                    prefix,
+                    url_base: url_base.trim_end_matches('/').to_string(),
+                    max_inline_bytes: cfg.max_inline_bytes,
+                })
+            }
+            None => None,
+        };
+        Ok(Self {
+            fields: cfg.fields.clone(),
+            offload,
+        })
+    }
+
+    /// Top-level fields, which reach sinks as columns of their own.
+    pub fn columns(cfg: &BinaryFieldsConfig) -> Vec<String> {
+        cfg.fields
+            .iter()
+            .filter(|f| !f.starts_with('/'))
+            .cloned()
+            .collect()
+    }
+
+    pub async fn apply_page(&self, rows: &mut [Value]) -> Result<()> {
+        for row in rows.iter_mut() {
+            for field in &self.fields {
+                self.rewrite(row, field).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn rewrite(&self, row: &mut Value, field: &str) -> Result<()> {
+        let slot = if field.starts_with('/') {
+            row.pointer_mut(field)
+        } else {
+            row.get_mut(field)
+        };
+        let Some(slot) = slot else {
+            return Ok(());
+        };
+        let Some(bytes) = slot.as_str().and_then(decode_base64) else {
+            if !slot.is_null() {
+                debug!(field, "not base64; left as-is");
+            }
+            return Ok(());
+        };
+
+        let url = match &self.offload {
+            Some(offload) if bytes.len() > offload.max_inline_bytes => {
+                *slot = Value::Null;
+                Value::String(offload.put(bytes).await?)
+            }
+            Some(_) => {
+                *slot = Value::String(encode_base64(&bytes));
+                Value::Null
+            }
+            None => {
+                *slot = Value::String(encode_base64(&bytes));
+                return Ok(());
+            }
+        };
+
+        // The URL column sits next to the field, in the same object.
+        let (parent, name) = match field.rsplit_once('/') {
+            Some((parent, name)) if field.starts_with('/') => (row.pointer_mut(parent), name),
+            _ => (Some(row), field),
+        };
+        match parent.and_then(Value::as_object_mut) {
+            Some(object) => {
+                object.insert(format!("{name}{URL_SUFFIX}"), url);
+            }
+            None => warn!(field, "no object to hold the offloaded URL"),
+        }
+        Ok(())
+    }
+}
+
+impl Offload {
+    /// Store `bytes` under their hash (once) and return the object's URL.
+    async fn put(&self, bytes: Vec<u8>) -> Result<String> {
+        let key = hex_digest(&bytes);
+        let path = self.prefix.child(key.as_str());
+        if self.store.head(&path).await.is_err() {
+            debug!(%path, bytes = bytes.len(), "offloading binary field");
+            self.store.put(&path, PutPayload::from(bytes)).await?;
+        }
+        Ok(format!("{}/{key}", self.url_base))
+    }
+}
+
+fn hex_digest(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
@@ -0,0 +1,47 @@
+use serde_json::Value;
+use tracing::debug;
+
+use crate::errors::Result;
+use crate::transform::RowTransform;
+
+/// Parse fields whose value is JSON encoded as a string (`"{\"a\":1}"`) into
+/// structured JSON, so schema inference and JSONB columns see real objects.
+///
+/// Field names are top-level keys, or JSON pointers when they start with `/`.
+/// Values that are not strings, or do not parse, are left untouched.
+#[derive(Debug, Clone)]
+pub struct ParseJsonFields {
+    fields: Vec<String>,
+}
+
+impl ParseJsonFields {
+    pub fn new(fields: Vec<String>) -> Self {
+        Self { fields }
+    }
+}
+
+impl RowTransform for ParseJsonFields {
+    fn name(&self) -> &'static str {
+        "parse_json_fields"
+    }
+
+    fn apply(&self, row: &mut Value) -> Result<()> {
+        for field in &self.fields {
+            let slot = if field.starts_with('/') {
+                row.pointer_mut(field)
+            } else {
+                row.get_mut(field.as_str())
+            };
+            let Some(slot) = slot else { continue };
+            let Some(text) = slot.as_str() else { continue };
+
+            match serde_json::from_str::<Value>(text) {
+                Ok(parsed) => *slot = parsed,
+                Err(e) => {
+                    debug!(field = %field, error = %e, "field is not valid JSON; keeping string")
+                }
+            }
+        }
+        Ok(())
+    }
+}
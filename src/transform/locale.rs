@@ -0,0 +1,261 @@
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+use tracing::debug;
+
+use crate::errors::{ApitapError, Result};
+use crate::transform::RowTransform;
+
+/// `locale:` on a source: a tag such as `de-DE` for every top-level string,
+/// or a `default` plus per-field tags (keys or JSON pointers).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "LocaleWire")]
+pub struct LocaleParsing {
+    pub default: Option<String>,
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LocaleWire {
+    Tag(String),
+    Full {
+        #[serde(default)]
+        default: Option<String>,
+        #[serde(default)]
+        fields: BTreeMap<String, String>,
+    },
+}
+
+impl From<LocaleWire> for LocaleParsing {
+    fn from(wire: LocaleWire) -> Self {
+        match wire {
+            LocaleWire::Tag(tag) => Self {
+                default: Some(tag),
+                fields: BTreeMap::new(),
+            },
+            LocaleWire::Full { default, fields } => Self { default, fields },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DateOrder {
+    Dmy,
+    Mdy,
+    Ymd,
+}
+
+/// Number and date conventions of one locale.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocaleFormat {
+    tag: String,
+    /// Accepted digit-group separators.
+    thousands: &'static [char],
+    decimal: char,
+    order: DateOrder,
+    date_separator: char,
+}
+
+const SPACES: &[char] = &[' ', '\u{a0}', '\u{202f}'];
+
+/// Known tags, matched on the full tag first and then on the language.
+const LOCALES: &[(&str, &[char], char, DateOrder, char)] = &[
+    ("en-US", &[','], '.', DateOrder::Mdy, '/'),
+    ("en-CA", &[','], '.', DateOrder::Ymd, '-'),
+    ("en", &[','], '.', DateOrder::Dmy, '/'),
+    ("de-CH", &['\'', '’'], '.', DateOrder::Dmy, '.'),
+    ("de", &['.'], ',', DateOrder::Dmy, '.'),
+    ("nl", &['.'], ',', DateOrder::Dmy, '-'),
+    ("id", &['.'], ',', DateOrder::Dmy, '/'),
+    ("it", &['.'], ',', DateOrder::Dmy, '/'),
+    ("es", &['.'], ',', DateOrder::Dmy, '/'),
+    ("pt", &['.'], ',', DateOrder::Dmy, '/'),
+    ("da", &['.'], ',', DateOrder::Dmy, '.'),
+    ("tr", &['.'], ',', DateOrder::Dmy, '.'),
+    ("fr-CA", SPACES, ',', DateOrder::Ymd, '-'),
+    ("fr", SPACES, ',', DateOrder::Dmy, '/'),
+    ("sv", SPACES, ',', DateOrder::Ymd, '-'),
+    ("nb", SPACES, ',', DateOrder::Dmy, '.'),
+    ("fi", SPACES, ',', DateOrder::Dmy, '.'),
+    ("pl", SPACES, ',', DateOrder::Dmy, '.'),
+    ("cs", SPACES, ',', DateOrder::Dmy, '.'),
+    ("ru", SPACES, ',', DateOrder::Dmy, '.'),
+    ("ja", &[','], '.', DateOrder::Ymd, '/'),
+    ("zh", &[','], '.', DateOrder::Ymd, '/'),
+];
+
+impl LocaleFormat {
+    /// Look up a BCP 47 tag like `de-DE` or `pt_BR`.
+    pub fn parse(tag: &str) -> Result<Self> {
+        let normalized = tag.trim().replace('_', "-");
+        let language = normalized.split('-').next().unwrap_or_default();
+        let found = LOCALES
+            .iter()
+            .find(|(t, ..)| t.eq_ignore_ascii_case(&normalized))
+            .or_else(|| {
+                LOCALES
+                    .iter()
+                    .find(|(t, ..)| t.eq_ignore_ascii_case(language))
+            });
+        let Some(&(_, thousands, decimal, order, date_separator)) = found else {
+            return Err(ApitapError::ConfigError(format!(
+                "unsupported locale '{tag}'"
+            )));
+        };
+        Ok(Self {
+            tag: normalized,
+            thousands,
+            decimal,
+            order,
+            date_separator,
+        })
+    }
+
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// Parse a localized number such as `1.234,56`; `None` unless the whole
+    /// string is one. Integers with leading zeros (codes, zip codes) are
+    /// not numbers.
+    pub fn parse_number(&self, text: &str) -> Option<Value> {
+        let s = text.trim();
+        let (negative, body) = match s.strip_prefix(['-', '\u{2212}']) {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (int, frac) = match body.split_once(self.decimal) {
+            Some((int, frac)) => (int, Some(frac)),
+            None => (body, None),
+        };
+        let all_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+        if frac.is_some_and(|f| !all_digits(f)) {
+            return None;
+        }
+
+        let groups: Vec<&str> = int.split(self.thousands).collect();
+        let grouped = match groups.split_first() {
+            Some((first, [])) => all_digits(first),
+            Some((first, rest)) => {
+                all_digits(first)
+                    && first.len() <= 3
+                    && rest.iter().all(|g| g.len() == 3 && all_digits(g))
+            }
+            None => false,
+        };
+        if !grouped {
+            return None;
+        }
+        let digits = groups.concat();
+        if digits.len() > 1 && digits.starts_with('0') {
+            return None;
+        }
+
+        let sign = if negative { "-" } else { "" };
+        match frac {
+            None => digits
+                .parse::<i64>()
+                .ok()
+                .map(|n| Value::Number(Number::from(if negative { -n } else { n }))),
+            Some(frac) => format!("{sign}{digits}.{frac}")
+                .parse::<f64>()
+                .ok()
+                .and_then(Number::from_f64)
+                .map(Value::Number),
+        }
+    }
+
+    /// Parse a localized date (`31.12.2024`) or date and time
+    /// (`31.12.2024 14:30`) into ISO 8601 without a zone.
+    pub fn parse_date(&self, text: &str) -> Option<String> {
+        let s = text.trim();
+        let sep = self.date_separator;
+        let date_format = match self.order {
+            DateOrder::Dmy => format!("%d{sep}%m{sep}%Y"),
+            DateOrder::Mdy => format!("%m{sep}%d{sep}%Y"),
+            DateOrder::Ymd => format!("%Y{sep}%m{sep}%d"),
+        };
+        let four_digit_year = |year: i32| (1000..=9999).contains(&year);
+
+        if let Ok(date) = NaiveDate::parse_from_str(s, &date_format) {
+            return four_digit_year(date.year()).then(|| date.format("%Y-%m-%d").to_string());
+        }
+        ["%H:%M:%S", "%H:%M"].iter().find_map(|time| {
+            NaiveDateTime::parse_from_str(s, &format!("{date_format} {time}"))
+                .ok()
+                .filter(|dt| four_digit_year(dt.year()))
+                .map(|dt| dt.format("%Y-%m-%dT%H:%M:%S").to_string())
+        })
+    }
+
+    fn rewrite(&self, slot: &mut Value) {
+        let Some(text) = slot.as_str() else {
+            return;
+        };
+        if let Some(date) = self.parse_date(text) {
+            *slot = Value::String(date);
+        } else if let Some(number) = self.parse_number(text) {
+            *slot = number;
+        } else {
+            debug!(value = %text, locale = %self.tag, "not a localized number or date; left as-is");
+        }
+    }
+}
+
+/// Types strings written for a locale, e.g. `"1.234,56"` as `1234.56` and
+/// `"31.12.2024"` as `"2024-12-31"`, so they are inferred as numbers and
+/// dates instead of TEXT. Dates with a time become naive ISO timestamps,
+/// which `timestamp_normalization` can then place in a zone.
+#[derive(Debug, Clone)]
+pub struct ParseLocale {
+    default: Option<LocaleFormat>,
+    fields: Vec<(String, LocaleFormat)>,
+}
+
+impl ParseLocale {
+    pub fn from_config(cfg: &LocaleParsing) -> Result<Self> {
+        Ok(Self {
+            default: cfg
+                .default
+                .as_deref()
+                .map(LocaleFormat::parse)
+                .transpose()?,
+            fields: cfg
+                .fields
+                .iter()
+                .map(|(field, tag)| Ok((field.clone(), LocaleFormat::parse(tag)?)))
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+impl RowTransform for ParseLocale {
+    fn name(&self) -> &'static str {
+        "locale"
+    }
+
+    fn apply(&self, row: &mut Value) -> Result<()> {
+        for (field, locale) in &self.fields {
+            let slot = if field.starts_with('/') {
+                row.pointer_mut(field)
+            } else {
+                row.get_mut(field.as_str())
+            };
+            if let Some(slot) = slot {
+                locale.rewrite(slot);
+            }
+        }
+        if let (Some(locale), Some(obj)) = (&self.default, row.as_object_mut()) {
+            for (key, value) in obj.iter_mut() {
+                if !self.fields.iter().any(|(field, _)| field == key) {
+                    locale.rewrite(value);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+//! Row-level transforms applied to fetched JSON before it reaches DataFusion.
+//!
+//! Each source builds a [`TransformChain`] from its YAML options; the page writer
+//! runs every row through the chain so the SQL layer sees cleaned-up records.
+//! Embedders can also register [`PageHook`]s in Rust, which see a whole page
+//! after the row transforms (see [`PageHooks`]). A source's `transform_script`
+//! runs as the first page hook, so it may drop rows as well as rewrite them.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::errors::Result;
+use crate::pipeline::Source;
+
+pub mod binary;
+pub mod json_fields;
+pub mod locale;
+pub mod numbers;
+pub mod script;
+pub mod timestamps;
+pub mod wasm;
+
+pub use binary::{BinaryFields, BinaryFieldsConfig};
+pub use json_fields::ParseJsonFields;
+pub use locale::{LocaleParsing, ParseLocale};
+pub use numbers::{NormalizeNumbers, NumberNormalization};
+pub use script::RhaiScript;
+pub use timestamps::{NormalizeTimestamps, TimestampNormalization};
+pub use wasm::WasmTransform;
+
+/// A single in-place rewrite of one JSON row.
+pub trait RowTransform: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn apply(&self, row: &mut Value) -> Result<()>;
+}
+
+/// Rewrite of one whole page of rows, registered through the library API.
+pub type PageHook = Arc<dyn Fn(Vec<Value>) -> Result<Vec<Value>> + Send + Sync>;
+
+/// Page hooks registered per source name, applied in registration order.
+#[derive(Clone, Default)]
+pub struct PageHooks {
+    by_source: HashMap<String, Vec<PageHook>>,
+}
+
+impl std::fmt::Debug for PageHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(self.by_source.iter().map(|(k, v)| (k, v.len())))
+            .finish()
+    }
+}
+
+impl PageHooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `hook` for every page fetched from `source`.
+    pub fn register(
+        &mut self,
+        source: impl Into<String>,
+        hook: impl Fn(Vec<Value>) -> Vec<Value> + Send + Sync + 'static,
+    ) {
+        self.register_fallible(source, move |rows| Ok(hook(rows)));
+    }
+
+    /// Like [`PageHooks::register`], for hooks that can fail the page.
+    pub fn register_fallible(
+        &mut self,
+        source: impl Into<String>,
+        hook: impl Fn(Vec<Value>) -> Result<Vec<Value>> + Send + Sync + 'static,
+    ) {
+        self.by_source
+            .entry(source.into())
+            .or_default()
+            .push(Arc::new(hook));
+    }
+
+    pub fn for_source(&self, source: &str) -> &[PageHook] {
+        self.by_source.get(source).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_source.is_empty()
+    }
+}
+
+/// Ordered list of row transforms, then page hooks; empty chains are a no-op.
+#[derive(Default)]
+pub struct TransformChain {
+    steps: Vec<Box<dyn RowTransform>>,
+    page_hooks: Vec<PageHook>,
+}
+
+impl std::fmt::Debug for TransformChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.steps.iter().map(|s| s.name()))
+            .entries(self.page_hooks.iter().map(|_| "page_hook"))
+            .finish()
+    }
+}
+
+impl TransformChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, step: impl RowTransform + 'static) -> Self {
+        self.steps.push(Box::new(step));
+        self
+    }
+
+    pub fn with_page_hooks(mut self, hooks: impl IntoIterator<Item = PageHook>) -> Self {
+        self.page_hooks.extend(hooks);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty() && self.page_hooks.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len() + self.page_hooks.len()
+    }
+
+    /// Page hooks need the whole page, so streamed pages must be buffered.
+    pub fn has_page_hooks(&self) -> bool {
+        !self.page_hooks.is_empty()
+    }
+
+    /// Build the chain configured on a source, in a fixed order.
+    pub fn from_source(src: &Source) -> Result<Self> {
+        let mut chain = Self::new();
+        if let Some(fields) = &src.parse_json_fields {
+            if !fields.is_empty() {
+                chain = chain.with(ParseJsonFields::new(fields.clone()));
+            }
+        }
+        // Before timestamps, which then see the ISO form of localized dates.
+        if let Some(locale) = &src.locale {
+            chain = chain.with(ParseLocale::from_config(locale)?);
+        }
+        if let Some(ts) = &src.timestamp_normalization {
+            chain = chain.with(NormalizeTimestamps::from_config(ts)?);
+        }
+        for num in src.number_normalization.iter().flatten() {
+            chain = chain.with(NormalizeNumbers::from_config(num)?);
+        }
+        if let Some(script) = &src.transform_script {
+            chain = chain.with_page_hooks([RhaiScript::compile(script)?.into_page_hook()]);
+        }
+        Ok(chain)
+    }
+
+    /// Run the row transforms on one row; page hooks are not applied.
+    pub fn apply(&self, mut row: Value) -> Result<Value> {
+        for step in &self.steps {
+            step.apply(&mut row)?;
+        }
+        Ok(row)
+    }
+
+    /// Run the row transforms on every row, then the page hooks on the page.
+    pub fn apply_page(&self, rows: Vec<Value>) -> Result<Vec<Value>> {
+        let rows = if self.steps.is_empty() {
+            rows
+        } else {
+            rows.into_iter()
+                .map(|row| self.apply(row))
+                .collect::<Result<Vec<_>>>()?
+        };
+        self.page_hooks
+            .iter()
+            .try_fold(rows, |rows, hook| hook(rows))
+    }
+}
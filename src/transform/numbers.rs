@@ -0,0 +1,261 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+use tracing::debug;
+
+use crate::errors::{ApitapError, Result};
+use crate::transform::RowTransform;
+
+/// One entry of the `number_normalization:` list on a source.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NumberNormalization {
+    /// Fields to normalise (keys or JSON pointers).
+    pub fields: Vec<String>,
+    #[serde(default = "default_thousands_separator")]
+    pub thousands_separator: String,
+    #[serde(default = "default_decimal_separator")]
+    pub decimal_separator: String,
+    /// Divide the parsed value, e.g. `100` to turn cents into units.
+    #[serde(default)]
+    pub divide_by: Option<f64>,
+}
+
+fn default_thousands_separator() -> String {
+    ",".to_string()
+}
+
+fn default_decimal_separator() -> String {
+    ".".to_string()
+}
+
+/// Turns money-ish strings (`"$1,234.50"`, `"(12.00) USD"`, `"1.234,5 €"`)
+/// into JSON numbers so they are typed as numeric instead of TEXT.
+///
+/// A currency symbol or code, a sign and parentheses are accepted before or
+/// after the number, never inside it; parentheses or a leading/trailing
+/// minus mark negatives. Anything else, such as `"SKU-123"` or
+/// `"555-1234"`, is not a number and is left untouched.
+#[derive(Debug, Clone)]
+pub struct NormalizeNumbers {
+    fields: Vec<String>,
+    thousands: Option<char>,
+    decimal: char,
+    divide_by: Option<f64>,
+}
+
+fn single_char(s: &str, what: &str) -> Result<Option<char>> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (None, _) => Ok(None),
+        (Some(c), None) => Ok(Some(c)),
+        _ => Err(ApitapError::ConfigError(format!(
+            "{what} must be a single character, got '{s}'"
+        ))),
+    }
+}
+
+impl NormalizeNumbers {
+    pub fn from_config(cfg: &NumberNormalization) -> Result<Self> {
+        let decimal =
+            single_char(&cfg.decimal_separator, "decimal_separator")?.ok_or_else(|| {
+                ApitapError::ConfigError("decimal_separator must not be empty".to_string())
+            })?;
+        let thousands = single_char(&cfg.thousands_separator, "thousands_separator")?;
+        if thousands == Some(decimal) {
+            return Err(ApitapError::ConfigError(
+                "thousands_separator and decimal_separator must differ".to_string(),
+            ));
+        }
+        if cfg.divide_by == Some(0.0) {
+            return Err(ApitapError::ConfigError(
+                "divide_by must not be zero".to_string(),
+            ));
+        }
+        Ok(Self {
+            fields: cfg.fields.clone(),
+            thousands,
+            decimal,
+            divide_by: cfg.divide_by,
+        })
+    }
+
+    /// Parse a formatted number; `None` if nothing numeric remains.
+    pub fn parse(&self, text: &str) -> Option<Value> {
+        // Plain numbers (including exponents like `1e5`) need no cleanup.
+        if self.decimal == '.' {
+            let trimmed = text.trim();
+            if let Ok(n) = trimmed.parse::<i64>() {
+                return self.rescale(Value::Number(Number::from(n)));
+            }
+            if let Ok(n) = trimmed.parse::<f64>() {
+                return Number::from_f64(n).and_then(|n| self.rescale(Value::Number(n)));
+            }
+        }
+
+        let chars: Vec<char> = text.trim().chars().collect();
+        let (mut start, mut end) = (0, chars.len());
+        let mut negative = false;
+        let (mut signed, mut paren, mut currency) = (false, false, false);
+
+        // Leading tokens: a sign, an opening parenthesis and a currency.
+        while start < end {
+            let c = chars[start];
+            if c.is_whitespace() {
+                start += 1;
+            } else if !signed && is_sign(c) {
+                signed = true;
+                negative = c != '+';
+                start += 1;
+            } else if !paren && c == '(' {
+                paren = true;
+                start += 1;
+            } else if !currency && is_currency(c) {
+                currency = true;
+                start = currency_end(&chars, start, end)?;
+            } else {
+                break;
+            }
+        }
+
+        // Trailing tokens: the closing parenthesis, a currency or a minus.
+        let mut closed = false;
+        while start < end {
+            let c = chars[end - 1];
+            if c.is_whitespace() {
+                end -= 1;
+            } else if paren && !closed && c == ')' {
+                closed = true;
+                end -= 1;
+            } else if !signed && (c == '-' || c == '\u{2212}') {
+                signed = true;
+                negative = true;
+                end -= 1;
+            } else if !currency && is_currency(c) {
+                currency = true;
+                end = currency_start(&chars, start, end)?;
+            } else {
+                break;
+            }
+        }
+        if paren != closed {
+            return None;
+        }
+        negative |= paren;
+
+        // What is left is the number itself: digits and separators only.
+        let mut has_fraction = false;
+        let mut digits = String::with_capacity(end - start);
+        for &c in &chars[start..end] {
+            if c.is_ascii_digit() {
+                digits.push(c);
+            } else if c == self.decimal {
+                if has_fraction {
+                    return None;
+                }
+                has_fraction = true;
+                digits.push('.');
+            } else if Some(c) == self.thousands && !has_fraction && !digits.is_empty() {
+                // grouping only
+            } else {
+                return None;
+            }
+        }
+
+        if !digits.bytes().any(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        match self.divide_by {
+            None if !has_fraction => {
+                let n: i64 = digits.parse().ok()?;
+                Some(Value::Number(Number::from(if negative { -n } else { n })))
+            }
+            divisor => {
+                let mut n: f64 = digits.parse().ok()?;
+                if negative {
+                    n = -n;
+                }
+                if let Some(d) = divisor {
+                    n /= d;
+                }
+                Number::from_f64(n).map(Value::Number)
+            }
+        }
+    }
+
+    fn rescale(&self, value: Value) -> Option<Value> {
+        match (self.divide_by, value.as_f64()) {
+            (Some(d), Some(v)) => Number::from_f64(v / d).map(Value::Number),
+            _ => Some(value),
+        }
+    }
+
+    fn rewrite(&self, slot: &mut Value) {
+        let out = match slot {
+            Value::String(s) => self.parse(s),
+            Value::Number(_) if self.divide_by.is_some() => self.rescale(slot.clone()),
+            _ => None,
+        };
+        match out {
+            Some(v) => *slot = v,
+            None if slot.is_string() => debug!(value = %slot, "not a number; left as-is"),
+            None => {}
+        }
+    }
+}
+
+fn is_sign(c: char) -> bool {
+    matches!(c, '-' | '+' | '\u{2212}')
+}
+
+/// Start of a currency: `$`, a non-ASCII symbol (`€`, `£`, `¥`) or a letter.
+fn is_currency(c: char) -> bool {
+    c == '$' || c.is_alphabetic() || (!c.is_ascii() && !c.is_alphanumeric() && !is_sign(c))
+}
+
+fn is_currency_part(c: char) -> bool {
+    is_currency(c) && !c.is_whitespace()
+}
+
+/// End of a currency starting at `at` (`USD`, `Rp`, `R$`, `€`). A code of
+/// letters must be set apart from what follows by a space or a digit, so
+/// that `SKU-123` is not read as a currency and a sign.
+fn currency_end(chars: &[char], at: usize, end: usize) -> Option<usize> {
+    let mut i = at;
+    while i < end && is_currency_part(chars[i]) {
+        i += 1;
+    }
+    let letters = chars[at..i].iter().filter(|c| c.is_alphabetic()).count();
+    let apart = i == end || chars[i].is_whitespace() || chars[i].is_ascii_digit();
+    (letters <= 3 && (letters == 0 || chars[i - 1] == '$' || apart)).then_some(i)
+}
+
+/// Start of a currency ending at `end`, as [`currency_end`] from the right.
+fn currency_start(chars: &[char], start: usize, end: usize) -> Option<usize> {
+    let mut i = end;
+    while i > start && is_currency_part(chars[i - 1]) {
+        i -= 1;
+    }
+    let letters = chars[i..end].iter().filter(|c| c.is_alphabetic()).count();
+    let apart = i == start || chars[i - 1].is_whitespace() || chars[i - 1].is_ascii_digit();
+    (letters <= 3 && (letters == 0 || apart)).then_some(i)
+}
+
+impl RowTransform for NormalizeNumbers {
+    fn name(&self) -> &'static str {
+        "number_normalization"
+    }
+
+    fn apply(&self, row: &mut Value) -> Result<()> {
+        for field in &self.fields {
+            let slot = if field.starts_with('/') {
+                row.pointer_mut(field)
+            } else {
+                row.get_mut(field.as_str())
+            };
+            if let Some(slot) = slot {
+                self.rewrite(slot);
+            }
+        }
+        Ok(())
+    }
+}
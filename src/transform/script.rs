@@ -0,0 +1,92 @@
+//! Lightweight row transforms written in Rhai.
+//!
+//! A source sets `transform_script:` to a Rhai snippet that runs once per row
+//! before the SQL stage. The row is bound to a mutable `row` object map:
+//!
+//! ```yaml
+//! transform_script: |
+//!   row.full_name = row.first + " " + row.last;
+//!   row.amount > 0
+//! ```
+//!
+//! Assignments to `row` are kept; a script whose last expression is `false`
+//! drops the row (any other result keeps it). Each row gets an operation
+//! budget so a runaway loop fails the page instead of hanging the run.
+
+use std::sync::Arc;
+
+use rhai::{Dynamic, Engine, Scope, AST};
+use serde_json::Value;
+use tracing::debug;
+
+use crate::errors::{ApitapError, Result};
+use crate::transform::PageHook;
+
+/// Rhai operations a script may perform per row.
+pub const SCRIPT_MAX_OPERATIONS: u64 = 1_000_000;
+
+pub struct RhaiScript {
+    engine: Engine,
+    ast: AST,
+}
+
+impl std::fmt::Debug for RhaiScript {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RhaiScript").finish_non_exhaustive()
+    }
+}
+
+impl RhaiScript {
+    /// Compile `source`; syntax errors surface as config errors.
+    pub fn compile(source: &str) -> Result<Self> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(SCRIPT_MAX_OPERATIONS);
+        engine.on_print(|text| debug!(target: "apitap::script", "{text}"));
+        engine.on_debug(|text, _, pos| debug!(target: "apitap::script", %pos, "{text}"));
+
+        let ast = engine
+            .compile(source)
+            .map_err(|e| ApitapError::ConfigError(format!("invalid transform_script: {e}")))?;
+        Ok(Self { engine, ast })
+    }
+
+    /// Override the per-row operation budget (0 means unlimited).
+    pub fn with_max_operations(mut self, ops: u64) -> Self {
+        self.engine.set_max_operations(ops);
+        self
+    }
+
+    /// Run the script on one row; `None` when the script filtered it out.
+    pub fn apply_row(&self, row: Value) -> Result<Option<Value>> {
+        let row = rhai::serde::to_dynamic(row)
+            .map_err(|e| ApitapError::PipelineError(format!("transform_script input: {e}")))?;
+        let mut scope = Scope::new();
+        scope.push("row", row);
+
+        let keep = self
+            .engine
+            .eval_ast_with_scope::<Dynamic>(&mut scope, &self.ast)
+            .map_err(|e| ApitapError::PipelineError(format!("transform_script failed: {e}")))?;
+        if keep.as_bool() == Ok(false) {
+            return Ok(None);
+        }
+
+        let row = scope.get_value::<Dynamic>("row").unwrap_or(Dynamic::UNIT);
+        let row: Value = rhai::serde::from_dynamic(&row)
+            .map_err(|e| ApitapError::PipelineError(format!("transform_script output: {e}")))?;
+        Ok(Some(row))
+    }
+
+    pub fn apply_page(&self, rows: Vec<Value>) -> Result<Vec<Value>> {
+        let mut out = Vec::with_capacity(rows.len());
+        for row in rows {
+            out.extend(self.apply_row(row)?);
+        }
+        Ok(out)
+    }
+
+    pub fn into_page_hook(self) -> PageHook {
+        let script = Arc::new(self);
+        Arc::new(move |rows| script.apply_page(rows))
+    }
+}
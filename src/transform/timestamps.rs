@@ -0,0 +1,136 @@
+use chrono::{DateTime, LocalResult, NaiveDateTime, SecondsFormat, TimeZone};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::debug;
+
+use crate::errors::{ApitapError, Result};
+use crate::transform::RowTransform;
+
+/// Naive layouts recognised as timestamps (no offset in the text).
+const NAIVE_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+];
+
+/// `timestamp_normalization:` block on a source.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimestampNormalization {
+    /// Zone used to interpret timestamps that carry no offset.
+    pub assume_tz: String,
+    /// Zone every detected timestamp is rewritten into.
+    #[serde(default = "default_convert_to")]
+    pub convert_to: String,
+    /// Restrict to these fields (keys or JSON pointers); default is every
+    /// top-level string that looks like a timestamp.
+    #[serde(default)]
+    pub fields: Option<Vec<String>>,
+}
+
+fn default_convert_to() -> String {
+    "UTC".to_string()
+}
+
+/// Rewrites timestamp strings to RFC 3339 in a single zone so naive local
+/// times from APIs land consistently in the warehouse.
+#[derive(Debug, Clone)]
+pub struct NormalizeTimestamps {
+    assume: Tz,
+    target: Tz,
+    fields: Option<Vec<String>>,
+}
+
+fn parse_tz(name: &str) -> Result<Tz> {
+    name.parse::<Tz>()
+        .map_err(|e| ApitapError::ConfigError(format!("unknown time zone '{name}': {e}")))
+}
+
+impl NormalizeTimestamps {
+    pub fn new(assume_tz: &str, convert_to: &str) -> Result<Self> {
+        Ok(Self {
+            assume: parse_tz(assume_tz)?,
+            target: parse_tz(convert_to)?,
+            fields: None,
+        })
+    }
+
+    pub fn from_config(cfg: &TimestampNormalization) -> Result<Self> {
+        let mut t = Self::new(&cfg.assume_tz, &cfg.convert_to)?;
+        t.fields = cfg.fields.clone();
+        Ok(t)
+    }
+
+    /// Normalise one string; `None` when it is not a timestamp (or does not
+    /// exist in `assume_tz`, e.g. inside a DST gap).
+    pub fn normalize(&self, text: &str) -> Option<String> {
+        let s = text.trim();
+        // Cheap pre-check: YYYY-MM-DD followed by a time part.
+        let b = s.as_bytes();
+        if b.len() < 16 || b[4] != b'-' || b[7] != b'-' || !(b[10] == b'T' || b[10] == b' ') {
+            return None;
+        }
+
+        if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+            return Some(self.format(dt.with_timezone(&self.target)));
+        }
+
+        let naive = NAIVE_FORMATS
+            .iter()
+            .find_map(|f| NaiveDateTime::parse_from_str(s, f).ok())?;
+        match self.assume.from_local_datetime(&naive) {
+            LocalResult::Single(dt) => Some(self.format(dt.with_timezone(&self.target))),
+            // Repeated wall-clock hour at DST end: take the first occurrence.
+            LocalResult::Ambiguous(first, _) => {
+                Some(self.format(first.with_timezone(&self.target)))
+            }
+            LocalResult::None => {
+                debug!(value = %s, tz = %self.assume, "timestamp does not exist in zone; left as-is");
+                None
+            }
+        }
+    }
+
+    fn format(&self, dt: DateTime<Tz>) -> String {
+        let use_z = self.target == chrono_tz::UTC;
+        dt.to_rfc3339_opts(SecondsFormat::AutoSi, use_z)
+    }
+
+    fn rewrite(&self, slot: &mut Value) {
+        if let Some(out) = slot.as_str().and_then(|s| self.normalize(s)) {
+            *slot = Value::String(out);
+        }
+    }
+}
+
+impl RowTransform for NormalizeTimestamps {
+    fn name(&self) -> &'static str {
+        "timestamp_normalization"
+    }
+
+    fn apply(&self, row: &mut Value) -> Result<()> {
+        match &self.fields {
+            Some(fields) => {
+                for field in fields {
+                    let slot = if field.starts_with('/') {
+                        row.pointer_mut(field)
+                    } else {
+                        row.get_mut(field.as_str())
+                    };
+                    if let Some(slot) = slot {
+                        self.rewrite(slot);
+                    }
+                }
+            }
+            None => {
+                if let Some(obj) = row.as_object_mut() {
+                    for v in obj.values_mut() {
+                        self.rewrite(v);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
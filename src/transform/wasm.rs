@@ -0,0 +1,143 @@
+//! User-defined page transforms compiled to WebAssembly.
+//!
+//! A module opts in with `{{ transform("plugins/clean.wasm") }}`; every page
+//! fetched for it is passed through the plugin before the SQL runs. Plugins
+//! run sandboxed in wasmtime (behind the `wasm` cargo feature) with a small
+//! JSON-in/JSON-out ABI:
+//!
+//! - export `memory`
+//! - export `alloc(len: i32) -> i32`: a buffer of `len` bytes for the input
+//! - export `transform(ptr: i32, len: i32) -> i64`: reads the input page and
+//!   returns the output location packed as `(out_ptr << 32) | out_len`
+//!
+//! Input and output are UTF-8 JSON arrays of row objects. Each page gets a
+//! fresh instance, so plugins need not free memory, and a fuel budget stops
+//! runaway plugins.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::errors::{ApitapError, Result};
+use crate::transform::PageHook;
+
+/// Instructions (roughly) a plugin may execute per page.
+pub const WASM_FUEL_PER_PAGE: u64 = 10_000_000_000;
+
+pub struct WasmTransform {
+    path: PathBuf,
+    fuel: u64,
+    #[cfg(feature = "wasm")]
+    engine: wasmtime::Engine,
+    #[cfg(feature = "wasm")]
+    module: wasmtime::Module,
+}
+
+impl std::fmt::Debug for WasmTransform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmTransform")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WasmTransform {
+    /// Compile the plugin at `path` (`.wasm`, or `.wat` text).
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|e| {
+            ApitapError::ConfigError(format!("cannot read wasm plugin {}: {e}", path.display()))
+        })?;
+        Self::from_bytes(path, &bytes)
+    }
+
+    #[cfg(feature = "wasm")]
+    pub fn from_bytes(path: impl AsRef<Path>, bytes: &[u8]) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(true);
+        let compiled = wasmtime::Engine::new(&config).and_then(|engine| {
+            let module = wasmtime::Module::new(&engine, bytes)?;
+            Ok((engine, module))
+        });
+        let (engine, module) = compiled.map_err(|e| {
+            ApitapError::ConfigError(format!("invalid wasm plugin {}: {e:#}", path.display()))
+        })?;
+        Ok(Self {
+            path,
+            fuel: WASM_FUEL_PER_PAGE,
+            engine,
+            module,
+        })
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    pub fn from_bytes(path: impl AsRef<Path>, _bytes: &[u8]) -> Result<Self> {
+        Err(ApitapError::ConfigError(format!(
+            "wasm plugin {} needs apitap built with the `wasm` feature",
+            path.as_ref().display()
+        )))
+    }
+
+    /// Override the per-page fuel budget.
+    pub fn with_fuel(mut self, fuel: u64) -> Self {
+        self.fuel = fuel;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Run one page through the plugin.
+    pub fn apply_page(&self, rows: Vec<Value>) -> Result<Vec<Value>> {
+        let input = serde_json::to_vec(&rows)?;
+        let output = self.call(&input).map_err(|e| {
+            ApitapError::PipelineError(format!("wasm plugin {} failed: {e}", self.path.display()))
+        })?;
+        serde_json::from_slice(&output).map_err(|e| {
+            ApitapError::PipelineError(format!(
+                "wasm plugin {} must return a JSON array of rows: {e}",
+                self.path.display()
+            ))
+        })
+    }
+
+    #[cfg(feature = "wasm")]
+    fn call(&self, input: &[u8]) -> std::result::Result<Vec<u8>, String> {
+        use wasmtime::{Linker, Store};
+
+        let run = || -> wasmtime::Result<Vec<u8>> {
+            let mut store = Store::new(&self.engine, ());
+            store.set_fuel(self.fuel)?;
+            let instance = Linker::new(&self.engine).instantiate(&mut store, &self.module)?;
+            let memory = instance
+                .get_memory(&mut store, "memory")
+                .ok_or_else(|| anyhow::anyhow!("plugin does not export `memory`"))?;
+            let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc")?;
+            let transform = instance.get_typed_func::<(i32, i32), i64>(&mut store, "transform")?;
+
+            let len = i32::try_from(input.len())?;
+            let ptr = alloc.call(&mut store, len)?;
+            memory.write(&mut store, ptr as u32 as usize, input)?;
+
+            let packed = transform.call(&mut store, (ptr, len))? as u64;
+            let (out_ptr, out_len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+            let mut out = vec![0u8; out_len];
+            memory.read(&store, out_ptr, &mut out)?;
+            Ok(out)
+        };
+        run().map_err(|e| format!("{e:#}"))
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    fn call(&self, _input: &[u8]) -> std::result::Result<Vec<u8>, String> {
+        Err("apitap was built without the `wasm` feature".to_string())
+    }
+
+    pub fn into_page_hook(self) -> PageHook {
+        let plugin = Arc::new(self);
+        Arc::new(move |rows| plugin.apply_page(rows))
+    }
+}
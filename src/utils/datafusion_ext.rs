@@ -11,7 +11,10 @@ use datafusion::{
 };
 use futures::{stream, Stream, StreamExt};
 use serde_arrow::schema::{SchemaLike, TracingOptions};
-use std::{pin::Pin, sync::Arc};
+use std::{
+    pin::Pin,
+    sync::{Arc, OnceLock},
+};
 use tokio::sync::OnceCell;
 use tracing::error;
 
@@ -20,6 +23,37 @@ use crate::errors::{ApitapError, Result};
 // =========================== Shared SessionContext ========================== //
 
 static SHARED_CTX: OnceCell<Arc<SessionContext>> = OnceCell::const_new();
+static CONTEXT_LIMITS: OnceLock<ContextLimits> = OnceLock::new();
+
+/// Memory pool and record batch size of the shared context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextLimits {
+    pub memory_pool_bytes: usize,
+    pub batch_size: usize,
+}
+
+impl ContextLimits {
+    /// For `--low-memory`: small enough for a 256MB container.
+    pub const LOW_MEMORY: ContextLimits = ContextLimits {
+        memory_pool_bytes: 32 * 1024 * 1024,
+        batch_size: 256,
+    };
+}
+
+impl Default for ContextLimits {
+    fn default() -> Self {
+        Self {
+            memory_pool_bytes: 256 * 1024 * 1024,
+            batch_size: 2048,
+        }
+    }
+}
+
+/// Size the shared context. Only the first call counts, and only before
+/// the context is first used; returns whether `limits` apply.
+pub fn configure_shared_context(limits: ContextLimits) -> bool {
+    SHARED_CTX.get().is_none() && CONTEXT_LIMITS.set(limits).is_ok()
+}
 
 /// Stream of JSON rows (`Result<Value>`) boxed + pinned for dynamic dispatch.
 pub type JsonStreamType = Pin<Box<dyn Stream<Item = Result<serde_json::Value>> + Send + 'static>>;
@@ -27,9 +61,9 @@ pub type JsonStreamType = Pin<Box<dyn Stream<Item = Result<serde_json::Value>> +
 pub async fn get_shared_context() -> Arc<SessionContext> {
     SHARED_CTX
         .get_or_init(|| async {
-            const MEM_LIMIT: usize = 256 * 1024 * 1024;
+            let limits = CONTEXT_LIMITS.get().copied().unwrap_or_default();
             let setup_runtime_env = RuntimeEnvBuilder::new()
-                .with_memory_pool(Arc::new(GreedyMemoryPool::new(MEM_LIMIT)))
+                .with_memory_pool(Arc::new(GreedyMemoryPool::new(limits.memory_pool_bytes)))
                 .build();
 
             let runtime_env = match setup_runtime_env {
@@ -42,7 +76,7 @@ pub async fn get_shared_context() -> Arc<SessionContext> {
 
             let session_config = SessionConfig::new()
                 .with_target_partitions(1)
-                .with_batch_size(2048);
+                .with_batch_size(limits.batch_size);
 
             Arc::new(SessionContext::new_with_config_rt(session_config, runtime_env))
         })
@@ -62,6 +96,11 @@ impl SqlDataFrame {
     pub fn inner(&self) -> &DataFrame {
         &self.df
     }
+
+    /// Run another query while the registered table is still alive.
+    pub async fn sql(&self, sql: &str) -> Result<DataFrame> {
+        Ok(self.ctx.sql(sql).await?)
+    }
 }
 
 impl Drop for SqlDataFrame {
@@ -173,7 +212,7 @@ impl DataFrameExt for DataFrame {
 
         let mut out = Vec::<T>::new();
         while let Some(item) = rb_stream.next().await {
-            let batch = item.map_err(|e| ApitapError::Datafusion(e))?;
+            let batch = item.map_err(ApitapError::Datafusion)?;
             let vals: Vec<serde_json::Value> = serde_arrow::from_record_batch(&batch)?;
             let chunk: Vec<T> = serde_json::from_value(serde_json::Value::Array(vals))?;
             out.extend(chunk);
@@ -1,12 +1,22 @@
 use http::Extensions;
-use reqwest::{Client, Request, Response};
+use reqwest::{Client, Request, Response, StatusCode};
 use reqwest_middleware::{
-    ClientBuilder, ClientWithMiddleware, Middleware, Next, Result as MwResult,
+    ClientBuilder, ClientWithMiddleware, Error as MwError, Middleware, Next, Result as MwResult,
 };
-use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
+use reqwest_retry::{
+    default_on_request_failure, policies::ExponentialBackoff, Jitter, RetryTransientMiddleware,
+    Retryable, RetryableStrategy,
+};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tracing::warn;
 
+use crate::http::auth::Authenticator;
+use crate::http::rate_limit::RateLimiter;
+use crate::http::throttle::{retry_after, ServerThrottle, MAX_WAIT};
+use crate::http::usage::UsageMeter;
+use crate::pipeline::{Retry, RetryJitter};
+
 #[derive(Debug, Default, Clone)]
 struct AttemptCount(pub u32);
 
@@ -93,22 +103,169 @@ impl Middleware for SummaryLogger {
     }
 }
 
+/// A retryable 429 that says how long to wait, and the wait (capped at
+/// [`MAX_WAIT`]).
+fn throttled_for(resp: &Response, retry: &Retry) -> Option<Duration> {
+    if resp.status() != StatusCode::TOO_MANY_REQUESTS
+        || !retry.retries_status(resp.status().as_u16())
+    {
+        return None;
+    }
+    retry_after(resp.headers()).map(|wait| wait.min(MAX_WAIT))
+}
+
+/// Retries connection errors and the statuses of `retry.retry_on`, except
+/// the 429s [`RetryAfter`] handles with the server's own delay.
+struct RetryConfiguredStatuses {
+    retry: Retry,
+}
+
+impl RetryableStrategy for RetryConfiguredStatuses {
+    fn handle(&self, res: &Result<Response, MwError>) -> Option<Retryable> {
+        match res {
+            Ok(resp) if resp.status().is_success() => None,
+            Ok(resp) if throttled_for(resp, &self.retry).is_some() => None,
+            Ok(resp) if self.retry.retries_status(resp.status().as_u16()) => {
+                Some(Retryable::Transient)
+            }
+            Ok(_) => Some(Retryable::Fatal),
+            Err(err) => default_on_request_failure(err),
+        }
+    }
+}
+
+/// Retries a 429 after the `Retry-After` (or rate-limit reset) delay it
+/// carries instead of the exponential backoff, up to `max_attempts` times.
+/// The wait is recorded on the source's throttle, which holds its other
+/// requests back until it is over.
+struct RetryAfter {
+    retry: Retry,
+    throttle: Option<Arc<ServerThrottle>>,
+}
+
+#[async_trait::async_trait]
+impl Middleware for RetryAfter {
+    async fn handle(
+        &self,
+        mut req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> MwResult<Response> {
+        let mut retries = 0;
+        loop {
+            // A streamed body cannot be sent twice.
+            let copy = match req.try_clone() {
+                Some(copy) if retries < self.retry.max_attempts => copy,
+                _ => return next.run(req, extensions).await,
+            };
+            let resp = next.clone().run(req, extensions).await?;
+            let Some(wait) = throttled_for(&resp, &self.retry) else {
+                return Ok(resp);
+            };
+            retries += 1;
+            warn!(
+                url = %resp.url(),
+                wait_ms = wait.as_millis() as u64,
+                retry = retries,
+                "429 Too Many Requests; waiting as asked before retrying"
+            );
+            if let Some(throttle) = &self.throttle {
+                throttle.record_retry_after(wait).await;
+            }
+            tokio::time::sleep(wait).await;
+            req = copy;
+        }
+    }
+}
+
+/// What a source's client does for every attempt of a request, the retries
+/// of the middleware included.
+#[derive(Debug, Clone, Default)]
+pub struct AttemptHooks {
+    /// The source's `rate_limit:`; every attempt takes a token.
+    pub rate_limit: Option<Arc<RateLimiter>>,
+    /// Paces attempts by the server's advertised budget, counts 429 waits
+    /// and pauses the source's other requests for as long.
+    pub throttle: Option<Arc<ServerThrottle>>,
+    /// Credentials added again to each attempt, e.g. an HMAC signature over
+    /// the current time.
+    pub signer: Option<Arc<dyn Authenticator>>,
+    /// Counts every attempt sent, so metered APIs see retries too.
+    pub usage: Option<Arc<UsageMeter>>,
+}
+
+/// Runs the [`AttemptHooks`] right before each attempt goes out: waits for
+/// the rate limit and throttle first, so a signature is made after them.
+struct EachAttempt {
+    hooks: AttemptHooks,
+}
+
+#[async_trait::async_trait]
+impl Middleware for EachAttempt {
+    async fn handle(
+        &self,
+        mut req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> MwResult<Response> {
+        if let Some(limiter) = &self.hooks.rate_limit {
+            limiter.acquire().await;
+        }
+        if let Some(throttle) = &self.hooks.throttle {
+            throttle.wait().await;
+        }
+        if let Some(signer) = &self.hooks.signer {
+            signer
+                .authorize(&mut req)
+                .await
+                .map_err(|e| MwError::Middleware(e.into()))?;
+        }
+        if let Some(usage) = &self.hooks.usage {
+            usage.request();
+        }
+        next.run(req, extensions).await
+    }
+}
+
 pub fn build_client_with_retry(
     reqwest_client: Client,
-    config_retray: &crate::pipeline::Retry,
+    config_retray: &Retry,
 ) -> ClientWithMiddleware {
+    build_client_with_retry_after(reqwest_client, config_retray, AttemptHooks::default())
+}
+
+/// [`build_client_with_retry`] that runs `hooks` for every attempt.
+pub fn build_client_with_retry_after(
+    reqwest_client: Client,
+    config_retray: &Retry,
+    hooks: AttemptHooks,
+) -> ClientWithMiddleware {
+    let min_delay = Duration::from_secs(config_retray.min_delay_secs);
+    // A maximum below the minimum would be rejected by the policy builder.
+    let max_delay = Duration::from_secs(config_retray.max_delay_secs).max(min_delay);
+    let jitter = match config_retray.jitter {
+        RetryJitter::None => Jitter::None,
+        RetryJitter::Full => Jitter::Full,
+        RetryJitter::Bounded => Jitter::Bounded,
+    };
     let policy = ExponentialBackoff::builder()
-        .retry_bounds(
-            Duration::from_secs(config_retray.min_delay_secs),
-            Duration::from_secs(config_retray.max_delay_secs),
-        )
+        .retry_bounds(min_delay, max_delay)
+        .jitter(jitter)
         .build_with_max_retries(config_retray.max_attempts);
 
-    let client = ClientBuilder::new(reqwest_client)
+    ClientBuilder::new(reqwest_client)
+        .with(RetryAfter {
+            retry: config_retray.clone(),
+            throttle: hooks.throttle.clone(),
+        })
         .with(AttemptLogger)
-        .with(RetryTransientMiddleware::new_with_policy(policy))
+        .with(RetryTransientMiddleware::new_with_policy_and_strategy(
+            policy,
+            RetryConfiguredStatuses {
+                retry: config_retray.clone(),
+            },
+        ))
         .with(SummaryLogger)
-        .build();
-
-    client
+        .with(EachAttempt { hooks })
+        .build()
 }
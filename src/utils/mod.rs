@@ -1,11 +1,13 @@
 //! Utility modules for ApiTap.
 //!
 //! This module contains helper utilities for DataFusion integration,
-//! SQL execution, HTTP retry logic, schema management, and streaming operations.
+//! SQL execution, HTTP retry logic, schema management, object storage, and
+//! streaming operations.
 
 pub mod datafusion_ext;
 pub mod execution;
 pub mod http_retry;
 pub mod schema;
+pub mod storage;
 pub mod streaming;
 pub mod table_provider;
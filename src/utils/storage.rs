@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use object_store::aws::AmazonS3Builder;
+use object_store::azure::MicrosoftAzureBuilder;
+use object_store::gcp::GoogleCloudStorageBuilder;
+use object_store::local::LocalFileSystem;
+use object_store::path::Path as ObjectPath;
+use object_store::ObjectStore;
+use url::Url;
+
+use crate::errors::{ApitapError, Result};
+
+/// Resolve a sink location into an object store plus the key prefix inside it.
+///
+/// - `s3://bucket/prefix` uses credentials/region from the usual `AWS_*` env vars
+/// - `gs://bucket/prefix` uses `GOOGLE_SERVICE_ACCOUNT` / `GOOGLE_APPLICATION_CREDENTIALS`
+///   or the instance metadata server
+/// - `az://container/prefix` (account from `AZURE_STORAGE_ACCOUNT_NAME`) or
+///   `abfs[s]://container@account.dfs.core.windows.net/prefix` uses the `AZURE_*` env vars
+/// - `file:///abs/dir` or a plain path writes to the local filesystem (created if missing)
+pub fn resolve_object_store(location: &str) -> Result<(Arc<dyn ObjectStore>, ObjectPath)> {
+    if let Some(rest) = location.strip_prefix("file://") {
+        return local_store(rest);
+    }
+    if !location.contains("://") {
+        return local_store(location);
+    }
+
+    let url = Url::parse(location)?;
+    let bucket = url
+        .host_str()
+        .ok_or_else(|| ApitapError::ConfigError(format!("missing bucket in '{location}'")))?;
+    let prefix = ObjectPath::from(url.path().trim_matches('/'));
+
+    match url.scheme() {
+        "s3" | "s3a" => {
+            let store = AmazonS3Builder::from_env()
+                .with_bucket_name(bucket)
+                .build()?;
+            Ok((Arc::new(store), prefix))
+        }
+        "gs" => {
+            let store = GoogleCloudStorageBuilder::from_env()
+                .with_bucket_name(bucket)
+                .build()?;
+            Ok((Arc::new(store), prefix))
+        }
+        "az" | "azure" | "abfs" | "abfss" => {
+            let store = MicrosoftAzureBuilder::from_env()
+                .with_url(location)
+                .build()?;
+            Ok((Arc::new(store), prefix))
+        }
+        other => Err(ApitapError::ConfigError(format!(
+            "unsupported storage scheme '{other}' in '{location}'"
+        ))),
+    }
+}
+
+/// Split a bucket location into its `scheme://bucket` root and the path
+/// after it; `None` for local paths, `file://` included.
+pub fn split_cloud_location(location: &str) -> Option<(&str, &str)> {
+    if location.starts_with("file://") {
+        return None;
+    }
+    let (scheme, rest) = location.split_once("://")?;
+    let end = rest.find('/').unwrap_or(rest.len());
+    let root = &location[..scheme.len() + "://".len() + end];
+    Some((root, rest[end..].trim_start_matches('/')))
+}
+
+/// URL of a sink location as accepted by [`resolve_object_store`]; local
+/// paths become absolute `file://` URLs, so the directory must already exist.
+pub fn location_url(location: &str) -> Result<Url> {
+    if !location.contains("://") || location.starts_with("file://") {
+        let dir = std::fs::canonicalize(location.trim_start_matches("file://"))?;
+        return Url::from_directory_path(&dir).map_err(|_| {
+            ApitapError::ConfigError(format!("cannot turn '{}' into a URL", dir.display()))
+        });
+    }
+    Ok(Url::parse(location)?)
+}
+
+fn local_store(dir: &str) -> Result<(Arc<dyn ObjectStore>, ObjectPath)> {
+    std::fs::create_dir_all(dir)?;
+    let store = LocalFileSystem::new_with_prefix(dir)?;
+    Ok((Arc::new(store), ObjectPath::default()))
+}
@@ -0,0 +1,151 @@
+//! Values a typed column cannot hold.
+//!
+//! A column's type is inferred from sampled rows, so later rows can carry
+//! values that do not parse as it: `"N/A"` in a `BIGINT` column, `1.5` in
+//! one inferred from integers. The Postgres writer would bind those as NULL.
+//! `coercion_policy` on a source decides what happens instead:
+//!
+//! - `null` (default): bind NULL, counting the values lost per column
+//! - `error`: fail the batch, naming the column and value
+//! - `dead_letter`: keep the row out of the table and store it, with the
+//!   offending columns, in `<table>_dead_letter`
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::errors::{ApitapError, Result};
+use crate::writer::postgres::PgType;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoercionPolicy {
+    #[default]
+    Null,
+    Error,
+    DeadLetter,
+}
+
+/// Whether binding `value` to a column of type `ty` loses it to NULL.
+pub fn coerces_to_null(value: &Value, ty: PgType) -> bool {
+    match (value, ty) {
+        (Value::Number(n), PgType::BigInt) => n.as_i64().is_none(),
+        (Value::Number(n), PgType::Double) => n.as_f64().is_none(),
+        (Value::Number(_), PgType::Boolean) => true,
+        (Value::String(s), PgType::BigInt) => s.parse::<i64>().is_err(),
+        (Value::String(s), PgType::Double) => s.parse::<f64>().is_err(),
+        _ => false,
+    }
+}
+
+/// Values lost to NULL per column, and rows set aside, so far.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoercionStats {
+    pub nulled: BTreeMap<String, u64>,
+    pub dead_lettered: u64,
+}
+
+impl CoercionStats {
+    pub fn is_empty(&self) -> bool {
+        self.nulled.is_empty() && self.dead_lettered == 0
+    }
+
+    pub fn add(&mut self, other: &CoercionStats) {
+        for (column, n) in &other.nulled {
+            *self.nulled.entry(column.clone()).or_default() += n;
+        }
+        self.dead_lettered += other.dead_lettered;
+    }
+
+    /// Warn about `table`'s losses; silent when there were none.
+    pub fn log(&self, table: &str) {
+        if !self.nulled.is_empty() {
+            tracing::warn!(
+                table,
+                columns = ?self.nulled,
+                "values that did not fit their column type were written as NULL"
+            );
+        }
+        if self.dead_lettered > 0 {
+            tracing::warn!(
+                table,
+                rows = self.dead_lettered,
+                "rows with values that did not fit their column type were dead-lettered"
+            );
+        }
+    }
+}
+
+/// A row kept out of the table, and the columns whose values did not fit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejected {
+    pub row: Value,
+    pub columns: Vec<String>,
+}
+
+/// The rows to write after applying the policy.
+#[derive(Debug)]
+pub struct Screened<'a> {
+    pub rows: Cow<'a, [Value]>,
+    pub rejected: Vec<Rejected>,
+    pub stats: CoercionStats,
+}
+
+/// Apply `policy` to the values of `rows` that `schema` cannot hold; `table`
+/// names the destination in errors.
+pub fn screen<'a>(
+    rows: &'a [Value],
+    schema: &BTreeMap<String, PgType>,
+    policy: CoercionPolicy,
+    table: &str,
+) -> Result<Screened<'a>> {
+    let mut stats = CoercionStats::default();
+    let mut rejected = Vec::new();
+    let mut kept: Option<Vec<Value>> = None;
+    for (idx, row) in rows.iter().enumerate() {
+        let bad: Vec<&String> = schema
+            .iter()
+            .filter(|(column, ty)| {
+                row.get(column.as_str())
+                    .is_some_and(|value| coerces_to_null(value, **ty))
+            })
+            .map(|(column, _)| column)
+            .collect();
+        if bad.is_empty() {
+            if let Some(kept) = kept.as_mut() {
+                kept.push(row.clone());
+            }
+            continue;
+        }
+        match policy {
+            CoercionPolicy::Null => {
+                for column in bad {
+                    *stats.nulled.entry(column.clone()).or_default() += 1;
+                }
+            }
+            CoercionPolicy::Error => {
+                let column = bad[0];
+                return Err(ApitapError::WriterError(format!(
+                    "{table}: column {column} cannot hold {} as {}",
+                    row[column.as_str()],
+                    schema[column].as_sql()
+                )));
+            }
+            CoercionPolicy::DeadLetter => {
+                kept.get_or_insert_with(|| rows[..idx].to_vec());
+                stats.dead_lettered += 1;
+                rejected.push(Rejected {
+                    row: row.clone(),
+                    columns: bad.into_iter().cloned().collect(),
+                });
+            }
+        }
+    }
+    Ok(Screened {
+        rows: kept.map_or(Cow::Borrowed(rows), Cow::Owned),
+        rejected,
+        stats,
+    })
+}
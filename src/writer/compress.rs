@@ -0,0 +1,55 @@
+//! zstd-compressed JSON columns for raw landing tables.
+//!
+//! Wide payload columns (whole API responses kept for replay) dominate the
+//! size of landing tables and are rarely queried. A source listing them in
+//! `compress_columns` has the Postgres sink store each one as
+//! `<column>_compressed BYTEA`: the value's JSON text, zstd-compressed. The
+//! suffix marks the column for readers, who decompress it app-side with
+//! [`decompress_json`] or `zstd -d`; SQL can no longer look inside it.
+
+use serde_json::Value;
+
+use crate::errors::{ApitapError, Result};
+use crate::transform::binary::encode_base64;
+
+/// Appended to the name of a compressed column.
+pub const COMPRESSED_SUFFIX: &str = "_compressed";
+
+/// zstd's default; higher levels cost much more time for little gain on JSON.
+const LEVEL: i32 = 3;
+
+pub fn compressed_name(column: &str) -> String {
+    format!("{column}{COMPRESSED_SUFFIX}")
+}
+
+pub fn compress_json(value: &Value) -> Result<Vec<u8>> {
+    let text = serde_json::to_vec(value)?;
+    zstd::encode_all(text.as_slice(), LEVEL)
+        .map_err(|e| ApitapError::WriterError(format!("zstd compression failed: {e}")))
+}
+
+/// The value a compressed column was written from.
+pub fn decompress_json(bytes: &[u8]) -> Result<Value> {
+    let text = zstd::decode_all(bytes)
+        .map_err(|e| ApitapError::WriterError(format!("zstd decompression failed: {e}")))?;
+    Ok(serde_json::from_slice(&text)?)
+}
+
+/// Move each of `columns` in `row` to its `_compressed` column, as base64
+/// for the `BYTEA` binding. Nulls stay null; absent columns stay absent.
+pub fn compress_row(row: &mut Value, columns: &[String]) -> Result<()> {
+    let Some(object) = row.as_object_mut() else {
+        return Ok(());
+    };
+    for column in columns {
+        let Some(value) = object.remove(column) else {
+            continue;
+        };
+        let compressed = match value {
+            Value::Null => Value::Null,
+            value => Value::String(encode_base64(&compress_json(&value)?)),
+        };
+        object.insert(compressed_name(column), compressed);
+    }
+    Ok(())
+}
@@ -0,0 +1,138 @@
+// src/writer/debug.rs
+
+use crate::errors::{ApitapError, Result};
+use crate::utils::datafusion_ext::{QueryResult, QueryResultStream};
+use crate::writer::{DataWriter, WriteMode};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use tokio_stream::StreamExt;
+use tracing::info;
+
+//=============== Type Definitions ============================================//
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DebugFormat {
+    /// Indented JSON, one row after another.
+    #[default]
+    Pretty,
+    /// One compact JSON object per line.
+    Ndjson,
+}
+
+//=============== Debug Writer ================================================//
+
+/// Prints rows to stdout (or any writer) instead of loading them anywhere.
+///
+/// Meant for iterating on SQL modules without a database: at most `limit`
+/// rows are printed per module (0 prints everything) and the rest of the
+/// stream is dropped.
+pub struct DebugWriter {
+    out: Mutex<Box<dyn Write + Send>>,
+    pub table: String,
+    pub format: DebugFormat,
+    pub limit: usize,
+    printed: AtomicUsize,
+    limit_logged: AtomicBool,
+}
+
+impl DebugWriter {
+    pub fn new(table: impl Into<String>) -> Self {
+        Self {
+            out: Mutex::new(Box::new(std::io::stdout())),
+            table: table.into(),
+            format: DebugFormat::Pretty,
+            limit: 20,
+            printed: AtomicUsize::new(0),
+            limit_logged: AtomicBool::new(false),
+        }
+    }
+
+    pub fn with_format(mut self, format: DebugFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Print somewhere other than stdout, e.g. a buffer in tests.
+    pub fn with_output(mut self, out: impl Write + Send + 'static) -> Self {
+        self.out = Mutex::new(Box::new(out));
+        self
+    }
+
+    pub fn printed(&self) -> usize {
+        self.printed.load(Ordering::Relaxed)
+    }
+
+    /// Reserve a slot under the limit; false once it is used up.
+    fn claim(&self) -> bool {
+        if self.limit == 0 {
+            self.printed.fetch_add(1, Ordering::Relaxed);
+            return true;
+        }
+        self.printed
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+                (n < self.limit).then_some(n + 1)
+            })
+            .is_ok()
+    }
+
+    fn print(&self, row: &Value) -> Result<()> {
+        let text = match self.format {
+            DebugFormat::Pretty => serde_json::to_string_pretty(row)?,
+            DebugFormat::Ndjson => serde_json::to_string(row)?,
+        };
+        let mut out = self
+            .out
+            .lock()
+            .map_err(|e| ApitapError::PoisonError(e.to_string()))?;
+        writeln!(out, "{text}")?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DataWriter for DebugWriter {
+    async fn write_stream(
+        &self,
+        mut result: QueryResultStream,
+        _write_mode: WriteMode,
+    ) -> Result<()> {
+        while let Some(item) = result.data.next().await {
+            let row = item?;
+            if !self.claim() {
+                if !self.limit_logged.swap(true, Ordering::Relaxed) {
+                    info!(table = %self.table, limit = self.limit, "debug row limit reached; remaining rows are not shown");
+                }
+                break;
+            }
+            self.print(&row)?;
+        }
+        Ok(())
+    }
+
+    async fn write(&self, result: QueryResult) -> Result<()> {
+        let rows = match result.data {
+            Value::Array(rows) => rows,
+            _ => {
+                return Err(ApitapError::PipelineError(
+                    "Expected JSON array".to_string(),
+                ))
+            }
+        };
+        let stream = QueryResultStream {
+            table_name: result.table_name,
+            data: Box::pin(futures::stream::iter(rows.into_iter().map(Ok))),
+        };
+        self.write_stream(stream, WriteMode::Append).await
+    }
+}
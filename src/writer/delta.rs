@@ -0,0 +1,448 @@
+// src/writer/delta.rs
+
+use crate::errors::{ApitapError, Result};
+use crate::utils::datafusion_ext::{QueryResult, QueryResultStream};
+use crate::utils::schema::infer_schema_from_values;
+use crate::utils::streaming::TrueStreamingProcessor;
+use crate::writer::parquet::ParquetCompression;
+use crate::writer::{DataWriter, WriteMode};
+use async_trait::async_trait;
+use datafusion::arrow::array::{new_null_array, Array, ArrayRef, BooleanArray, RecordBatch};
+use datafusion::arrow::compute::{cast_with_options, filter_record_batch, CastOptions};
+use datafusion::arrow::datatypes::{DataType, Field, Fields, Schema, SchemaRef, TimeUnit};
+use datafusion::arrow::util::display::array_value_to_string;
+use datafusion::common::Column;
+use datafusion::execution::SendableRecordBatchStream;
+use datafusion::parquet::file::properties::WriterProperties;
+use datafusion::prelude::{Expr, SessionContext};
+use deltalake::delta_datafusion::DataFusionMixins;
+use deltalake::logstore::{default_logstore, LogStoreRef, StorageConfig};
+use deltalake::operations::write::SchemaMode;
+use deltalake::protocol::SaveMode;
+use deltalake::{DeltaOps, DeltaTable, DeltaTableConfig, DeltaTableError};
+use futures::StreamExt;
+use object_store::path::Path as ObjectPath;
+use object_store::prefix::PrefixStore;
+use object_store::ObjectStore;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tracing::{debug, info};
+use url::Url;
+
+const SOURCE_ALIAS: &str = "source";
+const TARGET_ALIAS: &str = "target";
+
+//=============== Schema Conversion ===========================================//
+
+/// Map an Arrow type onto one Delta can store; unsupported types become strings.
+pub fn normalize_type(data_type: &DataType) -> DataType {
+    match data_type {
+        DataType::Null | DataType::Utf8View | DataType::LargeUtf8 => DataType::Utf8,
+        DataType::UInt8 => DataType::Int16,
+        DataType::UInt16 => DataType::Int32,
+        DataType::UInt32 | DataType::UInt64 => DataType::Int64,
+        DataType::Float16 => DataType::Float32,
+        DataType::Timestamp(_, _) | DataType::Date64 => {
+            DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()))
+        }
+        DataType::LargeBinary | DataType::BinaryView | DataType::FixedSizeBinary(_) => {
+            DataType::Binary
+        }
+        DataType::List(f)
+        | DataType::LargeList(f)
+        | DataType::ListView(f)
+        | DataType::FixedSizeList(f, _) => DataType::List(Arc::new(Field::new(
+            "item",
+            normalize_type(f.data_type()),
+            true,
+        ))),
+        DataType::Struct(fields) => DataType::Struct(
+            fields
+                .iter()
+                .map(|f| Field::new(f.name(), normalize_type(f.data_type()), true))
+                .collect::<Fields>(),
+        ),
+        DataType::Dictionary(_, value) => normalize_type(value),
+        DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::Float32
+        | DataType::Float64
+        | DataType::Boolean
+        | DataType::Binary
+        | DataType::Utf8
+        | DataType::Date32
+        | DataType::Decimal128(_, _) => data_type.clone(),
+        _ => DataType::Utf8,
+    }
+}
+
+/// Table schema after adding any columns only present in `incoming`.
+/// Returns the schema and whether it differs from `table`.
+pub fn evolve_schema(table: Option<&Schema>, incoming: &Schema) -> (SchemaRef, bool) {
+    let mut fields: Vec<Field> = table
+        .map(|s| s.fields().iter().map(|f| f.as_ref().clone()).collect())
+        .unwrap_or_default();
+    let mut changed = table.is_none();
+    for field in incoming.fields() {
+        if fields.iter().any(|f| f.name() == field.name()) {
+            continue;
+        }
+        fields.push(Field::new(
+            field.name(),
+            normalize_type(field.data_type()),
+            true,
+        ));
+        changed = true;
+    }
+    (Arc::new(Schema::new(fields)), changed)
+}
+
+/// Reorder/cast `batch` to `schema`, filling columns it lacks with nulls.
+pub fn align_batch(batch: &RecordBatch, schema: &SchemaRef) -> Result<RecordBatch> {
+    let strict = CastOptions {
+        safe: false,
+        ..Default::default()
+    };
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| -> Result<ArrayRef> {
+            match batch.column_by_name(field.name()) {
+                Some(col) if col.data_type() == field.data_type() => Ok(Arc::clone(col)),
+                Some(col) => cast_with_options(col, field.data_type(), &strict).map_err(|e| {
+                    ApitapError::WriterError(format!(
+                        "column '{}' ({}) does not fit delta type {}: {e}",
+                        field.name(),
+                        col.data_type(),
+                        field.data_type()
+                    ))
+                }),
+                None => Ok(new_null_array(field.data_type(), batch.num_rows())),
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(RecordBatch::try_new(Arc::clone(schema), columns)?)
+}
+
+/// Key column rendered as text per row (`None` for null or a missing column).
+fn row_keys(batch: &RecordBatch, key: &str) -> Result<Vec<Option<String>>> {
+    let Some(col) = batch.column_by_name(key) else {
+        return Ok(vec![None; batch.num_rows()]);
+    };
+    (0..batch.num_rows())
+        .map(|i| {
+            if col.is_null(i) {
+                Ok(None)
+            } else {
+                Ok(Some(array_value_to_string(col.as_ref(), i)?))
+            }
+        })
+        .collect()
+}
+
+//=============== Delta Writer ================================================//
+
+/// Writes query results into a Delta Lake table under `<location>/<table>/`.
+///
+/// Commits go through delta-rs on top of the target's object store, so each
+/// write is one atomic `_delta_log` version and concurrent writers are
+/// resolved by its conflict checker. New JSON fields are added to the table
+/// schema as nullable columns. Merge mode runs a delta-rs `MERGE` on the
+/// primary key, updating matched rows and inserting the rest.
+pub struct DeltaWriter {
+    store: Arc<dyn ObjectStore>,
+    prefix: ObjectPath,
+    location: Url,
+    pub table_name: String,
+    pub compression: ParquetCompression,
+    pub max_file_size: usize,
+    pub primary_key: Option<String>,
+    pub batch_size: usize,
+    // Pages are written concurrently; one commit at a time per writer.
+    commit_lock: tokio::sync::Mutex<()>,
+}
+
+impl DeltaWriter {
+    /// `location` is the URL of `prefix` inside `store`; delta-rs records it
+    /// as the table root.
+    pub fn new(
+        store: Arc<dyn ObjectStore>,
+        prefix: ObjectPath,
+        location: Url,
+        table_name: impl Into<String>,
+    ) -> Self {
+        Self {
+            store,
+            prefix,
+            location,
+            table_name: table_name.into(),
+            compression: ParquetCompression::default(),
+            max_file_size: 128 * 1024 * 1024,
+            primary_key: None,
+            batch_size: 8192,
+            commit_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn with_compression(mut self, compression: ParquetCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn with_max_file_size(mut self, bytes: usize) -> Self {
+        self.max_file_size = bytes.max(1);
+        self
+    }
+
+    pub fn with_primary_key_single(mut self, name: impl Into<Option<String>>) -> Self {
+        self.primary_key = name.into();
+        self
+    }
+
+    pub fn with_batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size.max(1);
+        self
+    }
+
+    /// Root directory of the Delta table.
+    pub fn table_dir(&self) -> ObjectPath {
+        self.table_name
+            .split('.')
+            .fold(self.prefix.clone(), |p, part| p.child(part))
+    }
+
+    /// URL of the Delta table root.
+    pub fn table_url(&self) -> Url {
+        let mut url = self.location.clone();
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments.pop_if_empty().extend(self.table_name.split('.'));
+        }
+        url
+    }
+
+    fn log_store(&self) -> LogStoreRef {
+        let store = PrefixStore::new(Arc::clone(&self.store), self.table_dir());
+        default_logstore(
+            Arc::new(store),
+            &self.table_url(),
+            &StorageConfig::default(),
+        )
+    }
+
+    /// Load the latest version of the table; `None` if it was never committed.
+    pub async fn table(&self) -> Result<Option<DeltaTable>> {
+        let mut table = DeltaTable::new(self.log_store(), DeltaTableConfig::default());
+        match table.load().await {
+            Ok(()) => Ok(Some(table)),
+            Err(DeltaTableError::NotATable(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn writer_properties(&self) -> WriterProperties {
+        WriterProperties::builder()
+            .set_compression(self.compression.to_parquet())
+            .build()
+    }
+
+    /// Collect `batches` aligned to the table schema extended with any new columns.
+    async fn collect_aligned(
+        table: Option<&DeltaTable>,
+        mut batches: SendableRecordBatchStream,
+    ) -> Result<(SchemaRef, Vec<RecordBatch>)> {
+        let current = match table {
+            Some(t) => Some(t.snapshot()?.arrow_schema()?),
+            None => None,
+        };
+        let (schema, _) = evolve_schema(current.as_deref(), &batches.schema());
+        let mut aligned = Vec::new();
+        while let Some(batch) = batches.next().await {
+            let batch = batch?;
+            if batch.num_rows() > 0 {
+                aligned.push(align_batch(&batch, &schema)?);
+            }
+        }
+        Ok((schema, aligned))
+    }
+
+    async fn append(&self, batches: SendableRecordBatchStream) -> Result<()> {
+        let _guard = self.commit_lock.lock().await;
+        let table = self.table().await?;
+        let (_, batches) = Self::collect_aligned(table.as_ref(), batches).await?;
+        if batches.is_empty() {
+            return Ok(());
+        }
+        self.commit_write(table, batches).await
+    }
+
+    async fn commit_write(
+        &self,
+        table: Option<DeltaTable>,
+        batches: Vec<RecordBatch>,
+    ) -> Result<()> {
+        let rows: usize = batches.iter().map(RecordBatch::num_rows).sum();
+        let ops = match table {
+            Some(t) => DeltaOps(t),
+            None => DeltaOps(DeltaTable::new(
+                self.log_store(),
+                DeltaTableConfig::default(),
+            )),
+        };
+        let table = ops
+            .write(batches)
+            .with_save_mode(SaveMode::Append)
+            .with_schema_mode(SchemaMode::Merge)
+            .with_table_name(&self.table_name)
+            .with_writer_properties(self.writer_properties())
+            .with_target_file_size(self.max_file_size)
+            .await?;
+        info!(table = %self.table_name, version = table.version(), rows, "delta append committed");
+        Ok(())
+    }
+
+    async fn merge_on_key(&self, batches: SendableRecordBatchStream) -> Result<()> {
+        let key = self.primary_key.clone().ok_or_else(|| {
+            ApitapError::MergeError("Delta: primary key not configured".to_string())
+        })?;
+
+        let _guard = self.commit_lock.lock().await;
+        let table = self.table().await?;
+        let (schema, incoming) = Self::collect_aligned(table.as_ref(), batches).await?;
+        if incoming.is_empty() {
+            return Ok(());
+        }
+
+        // Incoming rows, deduplicated on the key (last occurrence wins).
+        let mut keys: HashSet<String> = HashSet::new();
+        let mut deduped = Vec::with_capacity(incoming.len());
+        for batch in incoming.iter().rev() {
+            let mut keep = vec![true; batch.num_rows()];
+            for (i, k) in row_keys(batch, &key)?.into_iter().enumerate().rev() {
+                if let Some(k) = k {
+                    keep[i] = keys.insert(k);
+                }
+            }
+            deduped.push(filter_record_batch(batch, &BooleanArray::from(keep))?);
+        }
+        deduped.reverse();
+
+        let Some(table) = table else {
+            // Nothing to match against yet.
+            return self.commit_write(None, deduped).await;
+        };
+
+        let source = SessionContext::new().read_batches(deduped)?;
+        let source_col = |name: &str| Expr::Column(Column::new(Some(SOURCE_ALIAS), name));
+        let predicate =
+            Expr::Column(Column::new(Some(TARGET_ALIAS), key.as_str())).eq(source_col(&key));
+        let names: Vec<String> = schema.fields().iter().map(|f| f.name().clone()).collect();
+
+        let (table, metrics) = DeltaOps(table)
+            .merge(source, predicate)
+            .with_source_alias(SOURCE_ALIAS)
+            .with_target_alias(TARGET_ALIAS)
+            .with_merge_schema(true)
+            .with_writer_properties(self.writer_properties())
+            .when_matched_update(|update| {
+                names.iter().fold(update, |u, name| {
+                    u.update(Column::new_unqualified(name), source_col(name))
+                })
+            })?
+            .when_not_matched_insert(|insert| {
+                names.iter().fold(insert, |i, name| {
+                    i.set(Column::new_unqualified(name), source_col(name))
+                })
+            })?
+            .await?;
+        info!(
+            table = %self.table_name,
+            version = table.version(),
+            inserted = metrics.num_target_rows_inserted,
+            updated = metrics.num_target_rows_updated,
+            rewritten = metrics.num_target_files_removed,
+            "delta merge committed"
+        );
+        Ok(())
+    }
+
+    /// Logically empty the table by removing every active file in a new commit.
+    pub async fn truncate(&self) -> Result<()> {
+        let _guard = self.commit_lock.lock().await;
+        let Some(table) = self.table().await? else {
+            return Ok(());
+        };
+        if table.get_files_count() == 0 {
+            return Ok(());
+        }
+        info!(table = %self.table_name, "truncating delta table");
+        DeltaOps(table).delete().await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DataWriter for DeltaWriter {
+    async fn write_batches(
+        &self,
+        _table_name: &str,
+        batches: SendableRecordBatchStream,
+        write_mode: WriteMode,
+    ) -> Result<()> {
+        match write_mode {
+            WriteMode::Append => self.append(batches).await,
+            WriteMode::Merge if self.primary_key.is_some() => self.merge_on_key(batches).await,
+            WriteMode::Merge => {
+                debug!(table = %self.table_name, "no primary key; merging as append");
+                self.append(batches).await
+            }
+        }
+    }
+
+    async fn write_stream(
+        &self,
+        mut result: QueryResultStream,
+        write_mode: WriteMode,
+    ) -> Result<()> {
+        // JSON input: infer a schema from the first chunk, then reuse the batch path.
+        let mut head: Vec<Value> = Vec::with_capacity(self.batch_size.min(1000));
+        while head.len() < self.batch_size.min(1000) {
+            match result.data.next().await {
+                Some(item) => head.push(item?),
+                None => break,
+            }
+        }
+        if head.is_empty() {
+            return Ok(());
+        }
+
+        let schema = infer_schema_from_values(&head)?;
+        let rows = futures::stream::iter(head.into_iter().map(Ok)).chain(result.data);
+        let batches = TrueStreamingProcessor::new(self.batch_size)
+            .json_to_batch_stream(Box::pin(rows), Arc::clone(&schema))
+            .await?
+            .map(|b| b.map_err(|e| datafusion::error::DataFusionError::External(Box::new(e))));
+        let stream =
+            datafusion::physical_plan::stream::RecordBatchStreamAdapter::new(schema, batches);
+        self.write_batches(&result.table_name, Box::pin(stream), write_mode)
+            .await
+    }
+
+    async fn write(&self, result: QueryResult) -> Result<()> {
+        let rows = match result.data {
+            Value::Array(rows) => rows,
+            _ => {
+                return Err(ApitapError::PipelineError(
+                    "Expected JSON array".to_string(),
+                ))
+            }
+        };
+        let stream = QueryResultStream {
+            table_name: result.table_name,
+            data: Box::pin(futures::stream::iter(rows.into_iter().map(Ok))),
+        };
+        self.write_stream(stream, WriteMode::Append).await
+    }
+}
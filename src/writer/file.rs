@@ -0,0 +1,434 @@
+// src/writer/file.rs
+
+use crate::errors::{ApitapError, Result};
+use crate::utils::datafusion_ext::{QueryResult, QueryResultStream};
+use crate::writer::{DataWriter, WriteMode};
+use async_trait::async_trait;
+use object_store::path::Path as ObjectPath;
+use object_store::{ObjectStore, WriteMultipart};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use tokio_stream::StreamExt;
+use tracing::{debug, info, warn};
+
+//=============== Type Definitions ============================================//
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileFormat {
+    Ndjson,
+    Csv,
+}
+
+impl FileFormat {
+    /// Guess from the file extension; NDJSON unless the path ends in `.csv`.
+    pub fn from_path(path: &str) -> Self {
+        if path.to_ascii_lowercase().ends_with(".csv") {
+            FileFormat::Csv
+        } else {
+            FileFormat::Ndjson
+        }
+    }
+}
+
+/// Expand `{table}`, `{date}` (YYYY-MM-DD) and `{datetime}` (YYYYMMDDTHHMMSS, UTC).
+pub fn render_path_template(template: &str, table: &str) -> String {
+    let now = chrono::Utc::now();
+    template
+        .replace("{table}", table)
+        .replace("{date}", &now.format("%Y-%m-%d").to_string())
+        .replace("{datetime}", &now.format("%Y%m%dT%H%M%S").to_string())
+}
+
+//=============== File Writer =================================================//
+
+/// Dumps query results to CSV or NDJSON, in a local file or an object store.
+///
+/// The path template is rendered once per writer, so every page of a run
+/// appends to the same local file. CSV columns come from the first row
+/// written (or the existing header); nested values are written as JSON text.
+/// Pages are written concurrently, so each chunk is encoded and appended
+/// while holding the writer's lock on the open file.
+///
+/// Objects cannot be appended to, so in a bucket each page is uploaded as
+/// its own object next to the rendered path (`users.ndjson` becomes
+/// `users-<datetime>-<id>-00000.ndjson`, ...), streamed as a multipart
+/// upload. Every CSV object starts with its own header.
+pub struct FileWriter {
+    pub path: PathBuf,
+    pub format: FileFormat,
+    pub table_name: String,
+    merge_warned: AtomicBool,
+    output: tokio::sync::Mutex<Output>,
+    remote: Option<RemoteFiles>,
+}
+
+/// Where pages go when the path is in an object store.
+struct RemoteFiles {
+    store: Arc<dyn ObjectStore>,
+    /// The rendered path, which objects are named after.
+    key: ObjectPath,
+    seq: AtomicUsize,
+}
+
+/// One page's object while it is uploaded.
+struct Upload {
+    path: ObjectPath,
+    upload: WriteMultipart,
+    columns: Option<Vec<String>>,
+    bytes: usize,
+}
+
+/// Parts of one object uploaded at once.
+const UPLOAD_CONCURRENCY: usize = 8;
+
+/// The file being appended to and the CSV columns resolved for it.
+#[derive(Default)]
+struct Output {
+    file: Option<tokio::fs::File>,
+    columns: Option<Vec<String>>,
+}
+
+impl FileWriter {
+    pub fn new(template: &str, table_name: impl Into<String>, format: Option<FileFormat>) -> Self {
+        let table_name = table_name.into();
+        Self {
+            path: PathBuf::from(render_path_template(template, &table_name)),
+            format: format.unwrap_or_else(|| FileFormat::from_path(template)),
+            table_name,
+            merge_warned: AtomicBool::new(false),
+            output: tokio::sync::Mutex::default(),
+            remote: None,
+        }
+    }
+
+    /// A writer uploading to `store`, with `template` the path inside it
+    /// (the part after `gs://bucket/`).
+    pub fn in_store(
+        store: Arc<dyn ObjectStore>,
+        template: &str,
+        table_name: impl Into<String>,
+        format: Option<FileFormat>,
+    ) -> Self {
+        let mut writer = Self::new(template, table_name, format);
+        let key = ObjectPath::from(writer.path.to_string_lossy().trim_matches('/'));
+        writer.remote = Some(RemoteFiles {
+            store,
+            key,
+            seq: AtomicUsize::new(0),
+        });
+        writer
+    }
+
+    pub async fn truncate(&self) -> Result<()> {
+        if let Some(remote) = &self.remote {
+            return remote.truncate().await;
+        }
+        info!(path = %self.path.display(), "truncating output file");
+        let mut output = self.output.lock().await;
+        *output = Output::default();
+        match tokio::fs::remove_file(&self.path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn existing_csv_header(path: &Path) -> Result<Option<Vec<String>>> {
+        let file = match tokio::fs::File::open(path).await {
+            Ok(f) => f,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut line = String::new();
+        tokio::io::BufReader::new(file).read_line(&mut line).await?;
+        if line.trim().is_empty() {
+            return Ok(None);
+        }
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(line.as_bytes());
+        let header = rdr
+            .records()
+            .next()
+            .transpose()
+            .map_err(|e| ApitapError::WriterError(format!("invalid CSV header: {e}")))?
+            .map(|r| r.iter().map(str::to_string).collect());
+        Ok(header)
+    }
+
+    /// Render one row as CSV cells in `columns` order.
+    pub fn csv_cells(row: &Value, columns: &[String]) -> Vec<String> {
+        columns
+            .iter()
+            .map(|c| match row.get(c) {
+                None | Some(Value::Null) => String::new(),
+                Some(Value::String(s)) => s.clone(),
+                Some(other) => other.to_string(),
+            })
+            .collect()
+    }
+
+    /// CSV columns of a new file or object, from the first row.
+    fn first_row_columns(rows: &[Value]) -> Option<Vec<String>> {
+        rows.first()
+            .and_then(|r| r.as_object())
+            .map(|o| o.keys().cloned().collect())
+    }
+
+    /// `rows` in the writer's format; CSV needs `columns`, with a header
+    /// line first when `header` is set.
+    fn encode(&self, rows: &[Value], columns: Option<&[String]>, header: bool) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        match self.format {
+            FileFormat::Ndjson => {
+                for row in rows {
+                    serde_json::to_writer(&mut buf, row)?;
+                    buf.push(b'\n');
+                }
+            }
+            FileFormat::Csv => {
+                let Some(cols) = columns else {
+                    return Ok(buf);
+                };
+                let mut out = csv::Writer::from_writer(&mut buf);
+                let csv_err = |e: csv::Error| ApitapError::WriterError(format!("CSV write: {e}"));
+                if header {
+                    out.write_record(cols).map_err(csv_err)?;
+                }
+                for row in rows {
+                    out.write_record(Self::csv_cells(row, cols))
+                        .map_err(csv_err)?;
+                }
+                out.flush()?;
+            }
+        }
+        Ok(buf)
+    }
+
+    async fn append_rows(&self, rows: &[Value]) -> Result<()> {
+        let mut output = self.output.lock().await;
+
+        let mut write_header = false;
+        if self.format == FileFormat::Csv && output.columns.is_none() {
+            output.columns = Self::existing_csv_header(&self.path).await?;
+            if output.columns.is_none() {
+                output.columns = Self::first_row_columns(rows);
+                write_header = true;
+            }
+        }
+        let buf = self.encode(rows, output.columns.as_deref(), write_header)?;
+        if buf.is_empty() {
+            return Ok(());
+        }
+
+        let file = match output.file.as_mut() {
+            Some(file) => file,
+            None => {
+                if let Some(parent) = self.path.parent() {
+                    if !parent.as_os_str().is_empty() {
+                        tokio::fs::create_dir_all(parent).await?;
+                    }
+                }
+                let file = tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)
+                    .await?;
+                output.file.insert(file)
+            }
+        };
+        file.write_all(&buf).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    /// Add `rows` to this page's object, starting the upload on the first rows.
+    async fn upload_rows(
+        &self,
+        remote: &RemoteFiles,
+        upload: &mut Option<Upload>,
+        rows: &[Value],
+    ) -> Result<()> {
+        let (current, header) = match upload {
+            Some(current) => (current, false),
+            None => {
+                let path = remote.next_object_path();
+                let columns = match self.format {
+                    FileFormat::Csv => Self::first_row_columns(rows),
+                    FileFormat::Ndjson => None,
+                };
+                let multipart = remote.store.put_multipart(&path).await?;
+                debug!(path = %path, "started object upload");
+                let current = upload.insert(Upload {
+                    path,
+                    upload: WriteMultipart::new(multipart),
+                    columns,
+                    bytes: 0,
+                });
+                (current, true)
+            }
+        };
+        let buf = self.encode(rows, current.columns.as_deref(), header)?;
+        current.write(&buf).await
+    }
+
+    /// Upload one page as its own object.
+    async fn upload_stream(
+        &self,
+        remote: &RemoteFiles,
+        result: &mut QueryResultStream,
+    ) -> Result<usize> {
+        const CHUNK: usize = 1000;
+        let mut upload = None;
+        let mut buf: Vec<Value> = Vec::with_capacity(CHUNK);
+        let mut total = 0usize;
+
+        let written: Result<()> = async {
+            while let Some(item) = result.data.next().await {
+                buf.push(item?);
+                if buf.len() >= CHUNK {
+                    self.upload_rows(remote, &mut upload, &buf).await?;
+                    total += buf.len();
+                    buf.clear();
+                }
+            }
+            if !buf.is_empty() {
+                self.upload_rows(remote, &mut upload, &buf).await?;
+                total += buf.len();
+            }
+            Ok(())
+        }
+        .await;
+
+        let Some(upload) = upload else {
+            return written.map(|()| 0);
+        };
+        if let Err(e) = written {
+            // Leave no half-written object (or dangling parts) behind.
+            let _ = upload.upload.abort().await;
+            return Err(e);
+        }
+        upload.upload.finish().await?;
+        debug!(path = %upload.path, bytes = upload.bytes, "uploaded object");
+        Ok(total)
+    }
+}
+
+impl Upload {
+    async fn write(&mut self, buf: &[u8]) -> Result<()> {
+        self.upload.wait_for_capacity(UPLOAD_CONCURRENCY).await?;
+        self.upload.write(buf);
+        self.bytes += buf.len();
+        Ok(())
+    }
+}
+
+impl RemoteFiles {
+    /// `<stem>-<datetime>-<id>-<seq><ext>` next to the rendered path.
+    fn next_object_path(&self) -> ObjectPath {
+        let key = self.key.as_ref();
+        let (dir, name) = key.rsplit_once('/').unwrap_or(("", key));
+        let (stem, ext) = match name.rfind('.') {
+            Some(dot) if dot > 0 => name.split_at(dot),
+            _ => (name, ""),
+        };
+        let alphabet: [char; 16] = [
+            '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'a', 'b', 'c', 'd', 'e', 'f',
+        ];
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        let name = format!(
+            "{stem}-{}-{}-{seq:05}{ext}",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S"),
+            nanoid::nanoid!(8, &alphabet),
+        );
+        if dir.is_empty() {
+            ObjectPath::from(name)
+        } else {
+            ObjectPath::from(format!("{dir}/{name}"))
+        }
+    }
+
+    /// Delete the objects written for the rendered path.
+    async fn truncate(&self) -> Result<()> {
+        let key = self.key.as_ref();
+        info!(path = %key, "truncating output objects");
+        let (dir, name) = key.rsplit_once('/').unwrap_or(("", key));
+        let stem = name
+            .rfind('.')
+            .filter(|dot| *dot > 0)
+            .map_or(name, |dot| &name[..dot]);
+        let dir = (!dir.is_empty()).then(|| ObjectPath::from(dir));
+        let mut listing = self.store.list(dir.as_ref());
+        while let Some(meta) = listing.next().await {
+            let meta = meta?;
+            let Some(file) = meta.location.filename() else {
+                continue;
+            };
+            if file == name || file.starts_with(&format!("{stem}-")) {
+                self.store.delete(&meta.location).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DataWriter for FileWriter {
+    async fn write_stream(
+        &self,
+        mut result: QueryResultStream,
+        write_mode: WriteMode,
+    ) -> Result<()> {
+        if write_mode == WriteMode::Merge && !self.merge_warned.swap(true, Ordering::Relaxed) {
+            warn!(path = %self.path.display(), "file sink cannot merge; appending rows");
+        }
+
+        if let Some(remote) = &self.remote {
+            let total = self.upload_stream(remote, &mut result).await?;
+            info!(path = %self.path.display(), rows = total, "uploaded rows to object store");
+            return Ok(());
+        }
+
+        const CHUNK: usize = 1000;
+        let mut buf: Vec<Value> = Vec::with_capacity(CHUNK);
+        let mut total = 0usize;
+
+        while let Some(item) = result.data.next().await {
+            buf.push(item?);
+            if buf.len() >= CHUNK {
+                self.append_rows(&buf).await?;
+                total += buf.len();
+                buf.clear();
+            }
+        }
+        if !buf.is_empty() {
+            self.append_rows(&buf).await?;
+            total += buf.len();
+        }
+
+        info!(path = %self.path.display(), rows = total, "wrote rows to file");
+        Ok(())
+    }
+
+    async fn write(&self, result: QueryResult) -> Result<()> {
+        let Value::Array(rows) = result.data else {
+            return Err(ApitapError::PipelineError(
+                "Expected JSON array".to_string(),
+            ));
+        };
+        if let Some(remote) = &self.remote {
+            let mut stream = QueryResultStream {
+                table_name: result.table_name,
+                data: Box::pin(futures::stream::iter(rows.into_iter().map(Ok))),
+            };
+            self.upload_stream(remote, &mut stream).await?;
+            return Ok(());
+        }
+        self.append_rows(&rows).await
+    }
+}
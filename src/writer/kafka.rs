@@ -0,0 +1,200 @@
+// src/writer/kafka.rs
+
+use crate::errors::{ApitapError, Result};
+use crate::utils::datafusion_ext::{QueryError, QueryResult, QueryResultStream};
+use crate::writer::{DataWriter, WriteMode};
+use async_trait::async_trait;
+use futures::future::join_all;
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::producer::{DeliveryFuture, FutureProducer, FutureRecord};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio_stream::StreamExt;
+use tracing::{debug, info, warn};
+
+//=============== Type Definitions ============================================//
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KafkaCompression {
+    #[default]
+    None,
+    Gzip,
+    Snappy,
+    Lz4,
+}
+
+impl KafkaCompression {
+    /// Value for librdkafka's `compression.type`.
+    pub fn as_config(self) -> &'static str {
+        match self {
+            KafkaCompression::None => "none",
+            KafkaCompression::Gzip => "gzip",
+            KafkaCompression::Snappy => "snappy",
+            KafkaCompression::Lz4 => "lz4",
+        }
+    }
+}
+
+/// Shared producer handle kept on the target connection.
+#[derive(Clone)]
+pub struct KafkaProducer(pub FutureProducer);
+
+impl std::fmt::Debug for KafkaProducer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KafkaProducer").finish_non_exhaustive()
+    }
+}
+
+//=============== Kafka Writer ================================================//
+
+/// Publishes each row as a JSON message to a Kafka topic.
+///
+/// Messages are keyed by the primary key column when one is configured, so
+/// updates to the same record land on the same partition. Up to `batch_size`
+/// messages are kept in flight; their delivery reports are then awaited and
+/// every failed delivery is passed to [`DataWriter::on_error`]. The write
+/// fails if any message could not be delivered.
+pub struct KafkaWriter {
+    producer: FutureProducer,
+    pub topic: String,
+    pub key_field: Option<String>,
+    pub batch_size: usize,
+    merge_warned: AtomicBool,
+}
+
+impl KafkaWriter {
+    pub fn new(producer: FutureProducer, topic: impl Into<String>) -> Self {
+        Self {
+            producer,
+            topic: topic.into(),
+            key_field: None,
+            batch_size: 5000,
+            merge_warned: AtomicBool::new(false),
+        }
+    }
+
+    pub fn with_key_field(mut self, field: impl Into<Option<String>>) -> Self {
+        self.key_field = field.into();
+        self
+    }
+
+    pub fn with_batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size.max(1);
+        self
+    }
+
+    /// Message key for a row: the key field rendered as text, if present.
+    pub fn message_key(row: &Value, key_field: Option<&str>) -> Option<String> {
+        match row.get(key_field?)? {
+            Value::Null => None,
+            Value::String(s) => Some(s.clone()),
+            other => Some(other.to_string()),
+        }
+    }
+
+    /// Queue one message, waiting for room when the local queue is full.
+    async fn enqueue(&self, payload: &str, key: Option<&str>) -> Result<DeliveryFuture> {
+        loop {
+            let mut record: FutureRecord<'_, str, str> =
+                FutureRecord::to(&self.topic).payload(payload);
+            if let Some(k) = key {
+                record = record.key(k);
+            }
+            match self.producer.send_result(record) {
+                Ok(delivery) => return Ok(delivery),
+                Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), _)) => {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+                Err((e, _)) => {
+                    return Err(ApitapError::WriterError(format!(
+                        "kafka produce to '{}' failed: {e}",
+                        self.topic
+                    )))
+                }
+            }
+        }
+    }
+
+    /// Await delivery reports; returns how many messages failed.
+    async fn drain(&self, pending: &mut Vec<DeliveryFuture>) -> Result<usize> {
+        let mut failed = 0usize;
+        for report in join_all(pending.drain(..)).await {
+            let error = match report {
+                Ok(Ok(_)) => continue,
+                Ok(Err((e, _))) => e.to_string(),
+                Err(_) => "delivery report dropped".to_string(),
+            };
+            failed += 1;
+            self.on_error(QueryError {
+                table_name: self.topic.clone(),
+                error,
+            })
+            .await?;
+        }
+        Ok(failed)
+    }
+}
+
+#[async_trait]
+impl DataWriter for KafkaWriter {
+    async fn write_stream(
+        &self,
+        mut result: QueryResultStream,
+        write_mode: WriteMode,
+    ) -> Result<()> {
+        if write_mode == WriteMode::Merge && !self.merge_warned.swap(true, Ordering::Relaxed) {
+            warn!(topic = %self.topic, "kafka sink cannot merge; publishing rows as messages");
+        }
+
+        let mut pending: Vec<DeliveryFuture> = Vec::with_capacity(self.batch_size);
+        let mut sent = 0usize;
+        let mut failed = 0usize;
+
+        while let Some(item) = result.data.next().await {
+            let row = item?;
+            let payload = serde_json::to_string(&row)?;
+            let key = Self::message_key(&row, self.key_field.as_deref());
+            pending.push(self.enqueue(&payload, key.as_deref()).await?);
+            sent += 1;
+
+            if pending.len() >= self.batch_size {
+                failed += self.drain(&mut pending).await?;
+                debug!(topic = %self.topic, sent, "kafka batch delivered");
+            }
+        }
+        failed += self.drain(&mut pending).await?;
+
+        if failed > 0 {
+            return Err(ApitapError::WriterError(format!(
+                "{failed} of {sent} messages to kafka topic '{}' were not delivered",
+                self.topic
+            )));
+        }
+        info!(topic = %self.topic, messages = sent, "published to kafka");
+        Ok(())
+    }
+
+    async fn write(&self, result: QueryResult) -> Result<()> {
+        let rows = match result.data {
+            Value::Array(rows) => rows,
+            _ => {
+                return Err(ApitapError::PipelineError(
+                    "Expected JSON array".to_string(),
+                ))
+            }
+        };
+        let stream = QueryResultStream {
+            table_name: result.table_name,
+            data: Box::pin(futures::stream::iter(rows.into_iter().map(Ok))),
+        };
+        self.write_stream(stream, WriteMode::Append).await
+    }
+
+    async fn on_error(&self, error: QueryError) -> Result<()> {
+        tracing::error!(topic = %error.table_name, error = %error.error, "kafka delivery failed");
+        Ok(())
+    }
+}
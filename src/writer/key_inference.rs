@@ -0,0 +1,180 @@
+//! Guessing the merge key of a source without `primary_key_in_dest`.
+//!
+//! Merges match rows on a key. When none is configured, the first batch is
+//! searched for columns that could be one: set and distinct in every row,
+//! and named like a key (`id`, `order_id`, `customerId`, `uuid`, ...) or
+//! holding UUIDs. Pairs of columns are tried when no single column fits.
+//! The candidates are suggested in the error, or with `infer_primary_key`
+//! the best single column is used.
+
+use std::collections::{BTreeSet, HashSet};
+use std::sync::OnceLock;
+
+use serde_json::Value;
+use tracing::info;
+
+use crate::errors::{ApitapError, Result};
+
+/// Candidates suggested at most.
+const MAX_CANDIDATES: usize = 3;
+
+/// `8-4-4-4-12` hex digits.
+fn is_uuid(s: &str) -> bool {
+    s.len() == 36
+        && s.char_indices().all(|(i, c)| match i {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        })
+}
+
+fn is_key_name(name: &str) -> bool {
+    let lower = name.to_ascii_lowercase();
+    ["id", "uuid", "guid", "key"].iter().any(|word| {
+        lower == *word
+            || lower.ends_with(&format!("_{word}"))
+            || name.ends_with(&format!("{}{}", word[..1].to_uppercase(), &word[1..]))
+    })
+}
+
+/// The value as a key, if it can be one: a non-empty string or an integer.
+fn key_text(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(s) if !s.is_empty() => Some(s.clone()),
+        Value::Number(n) if n.is_i64() || n.is_u64() => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// Whether `columns` are set in every row and identify it.
+fn unique(rows: &[Value], columns: &[&str]) -> bool {
+    let mut seen = HashSet::with_capacity(rows.len());
+    rows.iter().all(|row| {
+        let key: Option<Vec<String>> = columns.iter().map(|c| key_text(row.get(c))).collect();
+        key.is_some_and(|key| seen.insert(key))
+    })
+}
+
+/// Possible keys of `rows`, best first: `id`, then UUID columns, then other
+/// key-like names; column pairs only when no single column fits.
+pub fn candidate_keys(rows: &[Value]) -> Vec<Vec<String>> {
+    if rows.is_empty() {
+        return Vec::new();
+    }
+    let columns: BTreeSet<&str> = rows
+        .iter()
+        .filter_map(Value::as_object)
+        .flat_map(|row| row.keys().map(String::as_str))
+        .collect();
+    let complete: Vec<&str> = columns
+        .into_iter()
+        .filter(|c| rows.iter().all(|row| key_text(row.get(*c)).is_some()))
+        .collect();
+    let uuids = |c: &str| {
+        rows.iter()
+            .all(|row| row.get(c).and_then(Value::as_str).is_some_and(is_uuid))
+    };
+
+    let mut singles: Vec<(u8, &str)> = complete
+        .iter()
+        .filter(|c| unique(rows, &[**c]))
+        .filter_map(
+            |c| match (c.eq_ignore_ascii_case("id"), uuids(c), is_key_name(c)) {
+                (true, _, _) => Some((0, *c)),
+                (_, true, _) => Some((1, *c)),
+                (_, _, true) => Some((2, *c)),
+                _ => None,
+            },
+        )
+        .collect();
+    singles.sort();
+    if !singles.is_empty() {
+        return singles
+            .into_iter()
+            .take(MAX_CANDIDATES)
+            .map(|(_, c)| vec![c.to_string()])
+            .collect();
+    }
+
+    let mut pairs = Vec::new();
+    for first in complete.iter().filter(|c| is_key_name(c)) {
+        for second in complete.iter().filter(|c| *c != first) {
+            if pairs.len() < MAX_CANDIDATES && unique(rows, &[*first, *second]) {
+                pairs.push(vec![first.to_string(), second.to_string()]);
+            }
+        }
+    }
+    pairs
+}
+
+fn describe(candidates: &[Vec<String>]) -> String {
+    candidates
+        .iter()
+        .map(|key| match key.as_slice() {
+            [column] => column.clone(),
+            columns => format!("({})", columns.join(", ")),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The error for a merge without a key, suggesting what the rows offer.
+pub fn missing_key_error(table: &str, rows: &[Value]) -> ApitapError {
+    let candidates = candidate_keys(rows);
+    let hint = if candidates.is_empty() {
+        format!(
+            "no column of the first {} rows looks like a key",
+            rows.len()
+        )
+    } else {
+        format!(
+            "candidates from the first {} rows: {}",
+            rows.len(),
+            describe(&candidates)
+        )
+    };
+    ApitapError::MergeError(format!(
+        "merging into {table} needs primary_key_in_dest; {hint} \
+         (set primary_key_in_dest, or infer_primary_key: true)"
+    ))
+}
+
+/// The best single-column key of `rows`.
+pub fn infer_key(table: &str, rows: &[Value]) -> Result<String> {
+    let candidates = candidate_keys(rows);
+    match candidates.first().map(Vec::as_slice) {
+        Some([column]) => Ok(column.clone()),
+        Some(_) => Err(ApitapError::MergeError(format!(
+            "{table}: no single column of the first {} rows is a key, only {}; \
+             merges need one key column, set primary_key_in_dest",
+            rows.len(),
+            describe(&candidates)
+        ))),
+        None => Err(missing_key_error(table, rows)),
+    }
+}
+
+/// Settle the key of a merge into `table` that has none configured, from its
+/// first rows: inferred into `inferred` when `infer` is set, an error
+/// naming the candidates otherwise.
+pub fn resolve_key(
+    table: &str,
+    infer: bool,
+    inferred: &OnceLock<String>,
+    rows: &[Value],
+) -> Result<()> {
+    if inferred.get().is_some() {
+        return Ok(());
+    }
+    if !infer {
+        return Err(missing_key_error(table, rows));
+    }
+    let key = infer_key(table, rows)?;
+    info!(
+        %table,
+        key = %key,
+        rows = rows.len(),
+        "🔑 inferred primary key; set primary_key_in_dest to pin it"
+    );
+    let _ = inferred.set(key);
+    Ok(())
+}
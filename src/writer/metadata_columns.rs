@@ -0,0 +1,90 @@
+//! Lineage columns a target adds to every row it is sent.
+//!
+//! ```yaml
+//! targets:
+//!   - type: postgres
+//!     name: warehouse
+//!     metadata_columns: [_loaded_at, _run_id, _source_name, _page]
+//! ```
+//!
+//! `_loaded_at` is when the page was written (RFC 3339, UTC), `_run_id` the
+//! run's id as in its logs and run history, `_source_name` the config source
+//! and `_page` the page number the rows came in. The columns are added after
+//! the module's SQL, so they reach the table whatever it selects, and replace
+//! a column of the same name. Every sink gets them the same way.
+
+use chrono::{SecondsFormat, Utc};
+use datafusion::dataframe::DataFrame;
+use datafusion::prelude::lit;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MetadataColumn {
+    #[serde(rename = "_loaded_at")]
+    LoadedAt,
+    #[serde(rename = "_run_id")]
+    RunId,
+    #[serde(rename = "_source_name")]
+    SourceName,
+    #[serde(rename = "_page")]
+    Page,
+}
+
+impl MetadataColumn {
+    pub const ALL: [Self; 4] = [Self::LoadedAt, Self::RunId, Self::SourceName, Self::Page];
+
+    /// Whether `column` is one of the lineage columns, which change on every
+    /// load and are left out of row comparisons.
+    pub fn is_metadata(column: &str) -> bool {
+        Self::ALL.iter().any(|c| c.name() == column)
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::LoadedAt => "_loaded_at",
+            Self::RunId => "_run_id",
+            Self::SourceName => "_source_name",
+            Self::Page => "_page",
+        }
+    }
+}
+
+/// The columns one route adds, with the run and source they name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataColumns {
+    columns: Vec<MetadataColumn>,
+    run_id: String,
+    source: String,
+}
+
+impl MetadataColumns {
+    /// `None` when the target adds no column.
+    pub fn new(
+        columns: &[MetadataColumn],
+        run_id: impl Into<String>,
+        source: impl Into<String>,
+    ) -> Option<Self> {
+        (!columns.is_empty()).then(|| Self {
+            columns: columns.to_vec(),
+            run_id: run_id.into(),
+            source: source.into(),
+        })
+    }
+
+    /// `df` with the columns added for rows of page `page`.
+    pub fn apply(&self, mut df: DataFrame, page: u64) -> Result<DataFrame> {
+        let loaded_at = Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true);
+        for column in &self.columns {
+            let value = match column {
+                MetadataColumn::LoadedAt => lit(loaded_at.as_str()),
+                MetadataColumn::RunId => lit(self.run_id.as_str()),
+                MetadataColumn::SourceName => lit(self.source.as_str()),
+                MetadataColumn::Page => lit(page as i64),
+            };
+            df = df.with_column(column.name(), value)?;
+        }
+        Ok(df)
+    }
+}
@@ -0,0 +1,558 @@
+//! Middleware applied around a sink's batch writes.
+//!
+//! A [`MiddlewareChain`] wraps any [`DataWriter`]: every record batch passes
+//! through each step's [`WriterMiddleware::map_batch`] on its way to the sink,
+//! and the `before_write` / `after_write` hooks run around each call to
+//! `write_batches`. Cross-cutting concerns such as masking, metadata columns,
+//! timing and row counting live here once instead of inside every sink.
+
+use std::collections::{BTreeMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use datafusion::arrow::array::{ArrayRef, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::util::display::{ArrayFormatter, FormatOptions};
+use datafusion::error::DataFusionError;
+use datafusion::execution::SendableRecordBatchStream;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::errors::Result;
+use crate::pipeline::duplicates::DuplicateKeys;
+use crate::pipeline::Source;
+use crate::utils::datafusion_ext::{QueryError, QueryResult, QueryResultStream};
+use crate::writer::coercion::CoercionStats;
+use crate::writer::metadata_columns::MetadataColumn;
+use crate::writer::{DataWriter, MergeStats, WriteMode, ROW_HASH_COLUMN};
+
+/// What one `write_batches` call pushed through the chain.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WriteStats {
+    pub rows: u64,
+    pub batches: u64,
+    pub elapsed: Duration,
+}
+
+/// One step around a sink's batch writes. Every method defaults to a no-op.
+#[async_trait]
+pub trait WriterMiddleware: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Rewrite a batch on its way to the sink. Must be deterministic in its
+    /// output schema: it is also applied to an empty batch to derive it.
+    fn map_batch(&self, _table: &str, batch: RecordBatch) -> Result<RecordBatch> {
+        Ok(batch)
+    }
+
+    async fn before_write(&self, _table: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Runs after the sink returned, whether or not the write succeeded.
+    async fn after_write(&self, _table: &str, _stats: &WriteStats, _ok: bool) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// YAML options for the built-in middleware, set per source.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MiddlewareConfig {
+    /// Columns whose non-null values are replaced before they reach the sink.
+    #[serde(default)]
+    pub mask_columns: Vec<String>,
+    #[serde(default = "default_mask")]
+    pub mask_with: String,
+    /// Constant columns added to every row, e.g. `_source: users_api`.
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    /// Log rows and elapsed time of every batch write.
+    #[serde(default)]
+    pub timing: bool,
+    /// Add a `_row_hash` of the non-key columns; merges skip rows whose hash
+    /// did not change.
+    #[serde(default)]
+    pub row_hash: bool,
+    /// Columns left out of `_row_hash`, e.g. an `updated_at` the API bumps on
+    /// every call.
+    #[serde(default)]
+    pub row_hash_exclude: Vec<String>,
+}
+
+fn default_mask() -> String {
+    "***".to_string()
+}
+
+/// Ordered list of middleware; empty chains leave the writer untouched.
+#[derive(Default, Clone)]
+pub struct MiddlewareChain {
+    steps: Vec<Arc<dyn WriterMiddleware>>,
+}
+
+impl std::fmt::Debug for MiddlewareChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.steps.iter().map(|s| s.name()))
+            .finish()
+    }
+}
+
+impl MiddlewareChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, step: impl WriterMiddleware + 'static) -> Self {
+        self.steps.push(Arc::new(step));
+        self
+    }
+
+    pub fn with_shared(mut self, step: Arc<dyn WriterMiddleware>) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Build the chain configured on a source: mask, then metadata, then row
+    /// hash, then timing.
+    pub fn from_source(src: &Source) -> Self {
+        let mut chain = Self::new();
+        if let Some(cfg) = &src.middleware {
+            if !cfg.mask_columns.is_empty() {
+                chain = chain.with(
+                    MaskColumns::new(cfg.mask_columns.clone()).with_replacement(&cfg.mask_with),
+                );
+            }
+            if !cfg.metadata.is_empty() {
+                chain = chain.with(InjectMetadata::new(cfg.metadata.clone()));
+            }
+            if cfg.row_hash {
+                let key = src.primary_key_in_dest.iter().cloned();
+                let lineage = MetadataColumn::ALL.iter().map(|c| c.name().to_string());
+                chain = chain.with(RowHash::new(
+                    cfg.row_hash_exclude
+                        .iter()
+                        .cloned()
+                        .chain(key)
+                        .chain(lineage),
+                ));
+            }
+            if cfg.timing {
+                chain = chain.with(Timing);
+            }
+        }
+        chain
+    }
+
+    pub fn map_batch(&self, table: &str, batch: RecordBatch) -> Result<RecordBatch> {
+        self.steps
+            .iter()
+            .try_fold(batch, |batch, step| step.map_batch(table, batch))
+    }
+
+    /// Schema the sink will see for batches of `input`.
+    pub fn output_schema(&self, table: &str, input: SchemaRef) -> Result<SchemaRef> {
+        Ok(self
+            .map_batch(table, RecordBatch::new_empty(input))?
+            .schema())
+    }
+
+    /// Wrap `writer`; an empty chain returns it unchanged.
+    pub fn wrap(self, writer: Arc<dyn DataWriter>) -> Arc<dyn DataWriter> {
+        if self.is_empty() {
+            return writer;
+        }
+        Arc::new(MiddlewareWriter {
+            inner: writer,
+            chain: Arc::new(self),
+        })
+    }
+}
+
+/// A [`DataWriter`] running a [`MiddlewareChain`] around another writer.
+pub struct MiddlewareWriter {
+    inner: Arc<dyn DataWriter>,
+    chain: Arc<MiddlewareChain>,
+}
+
+#[async_trait]
+impl DataWriter for MiddlewareWriter {
+    async fn write(&self, result: QueryResult) -> Result<()> {
+        self.inner.write(result).await
+    }
+
+    async fn write_stream(&self, result: QueryResultStream, write_mode: WriteMode) -> Result<()> {
+        self.inner.write_stream(result, write_mode).await
+    }
+
+    async fn write_batches(
+        &self,
+        table_name: &str,
+        batches: SendableRecordBatchStream,
+        write_mode: WriteMode,
+    ) -> Result<()> {
+        for step in &self.chain.steps {
+            step.before_write(table_name).await?;
+        }
+
+        let schema = self.chain.output_schema(table_name, batches.schema())?;
+        let rows = Arc::new(AtomicU64::new(0));
+        let count = Arc::new(AtomicU64::new(0));
+        let mapped = {
+            let chain = Arc::clone(&self.chain);
+            let table = table_name.to_string();
+            let (rows, count) = (Arc::clone(&rows), Arc::clone(&count));
+            batches.map(move |batch| {
+                let out = chain
+                    .map_batch(&table, batch?)
+                    .map_err(|e| DataFusionError::External(Box::new(e)))?;
+                rows.fetch_add(out.num_rows() as u64, Ordering::Relaxed);
+                count.fetch_add(1, Ordering::Relaxed);
+                Ok(out)
+            })
+        };
+
+        let t0 = Instant::now();
+        let result = self
+            .inner
+            .write_batches(
+                table_name,
+                Box::pin(RecordBatchStreamAdapter::new(schema, mapped)),
+                write_mode,
+            )
+            .await;
+        let stats = WriteStats {
+            rows: rows.load(Ordering::Relaxed),
+            batches: count.load(Ordering::Relaxed),
+            elapsed: t0.elapsed(),
+        };
+
+        // Unwind in reverse so the outermost step sees the whole write.
+        let mut hooks = Ok(());
+        for step in self.chain.steps.iter().rev() {
+            let res = step.after_write(table_name, &stats, result.is_ok()).await;
+            if hooks.is_ok() {
+                hooks = res;
+            }
+        }
+        result.and(hooks)
+    }
+
+    async fn newest_loaded_at(&self, column: &str) -> Result<Option<DateTime<Utc>>> {
+        self.inner.newest_loaded_at(column).await
+    }
+
+    async fn merge(&self, result: QueryResultStream) -> Result<()> {
+        self.inner.merge(result).await
+    }
+
+    async fn purge_before(
+        &self,
+        column: &str,
+        cutoff: DateTime<Utc>,
+        detach_partitions: bool,
+    ) -> Result<Option<u64>> {
+        self.inner
+            .purge_before(column, cutoff, detach_partitions)
+            .await
+    }
+
+    async fn reconcile(
+        &self,
+        key: &str,
+        seen: &[String],
+        deleted_column: Option<&str>,
+        at: DateTime<Utc>,
+    ) -> Result<Option<u64>> {
+        self.inner.reconcile(key, seen, deleted_column, at).await
+    }
+
+    fn merge_stats(&self) -> Option<MergeStats> {
+        self.inner.merge_stats()
+    }
+
+    fn coercion_stats(&self) -> Option<CoercionStats> {
+        self.inner.coercion_stats()
+    }
+
+    async fn row_count(&self) -> Result<Option<u64>> {
+        self.inner.row_count().await
+    }
+
+    async fn sample_keys(&self, column: &str, limit: usize) -> Result<Option<Vec<String>>> {
+        self.inner.sample_keys(column, limit).await
+    }
+
+    async fn count_keys(&self, column: &str, keys: &[String]) -> Result<Option<u64>> {
+        self.inner.count_keys(column, keys).await
+    }
+
+    async fn duplicate_keys(&self, columns: &[String]) -> Result<Option<DuplicateKeys>> {
+        self.inner.duplicate_keys(columns).await
+    }
+
+    async fn on_error(&self, error: QueryError) -> Result<()> {
+        self.inner.on_error(error).await
+    }
+
+    async fn begin(&self) -> Result<()> {
+        self.inner.begin().await
+    }
+    async fn commit(&self) -> Result<()> {
+        self.inner.commit().await
+    }
+    async fn rollback(&self) -> Result<()> {
+        self.inner.rollback().await
+    }
+}
+
+//=============== Built-in middleware =========================================//
+
+/// Replace the values of sensitive columns; nulls stay null.
+#[derive(Debug, Clone)]
+pub struct MaskColumns {
+    columns: HashSet<String>,
+    replacement: String,
+}
+
+impl MaskColumns {
+    pub fn new(columns: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            columns: columns.into_iter().collect(),
+            replacement: default_mask(),
+        }
+    }
+
+    pub fn with_replacement(mut self, replacement: impl Into<String>) -> Self {
+        self.replacement = replacement.into();
+        self
+    }
+}
+
+impl WriterMiddleware for MaskColumns {
+    fn name(&self) -> &'static str {
+        "mask_columns"
+    }
+
+    fn map_batch(&self, _table: &str, batch: RecordBatch) -> Result<RecordBatch> {
+        let schema = batch.schema();
+        if !schema
+            .fields()
+            .iter()
+            .any(|f| self.columns.contains(f.name()))
+        {
+            return Ok(batch);
+        }
+
+        let mut fields = Vec::with_capacity(schema.fields().len());
+        let mut columns: Vec<ArrayRef> = Vec::with_capacity(schema.fields().len());
+        for (field, column) in schema.fields().iter().zip(batch.columns()) {
+            if self.columns.contains(field.name()) {
+                let masked: StringArray = (0..column.len())
+                    .map(|i| (!column.is_null(i)).then_some(self.replacement.as_str()))
+                    .collect();
+                fields.push(Arc::new(Field::new(field.name(), DataType::Utf8, true)));
+                columns.push(Arc::new(masked));
+            } else {
+                fields.push(Arc::clone(field));
+                columns.push(Arc::clone(column));
+            }
+        }
+        Ok(RecordBatch::try_new(
+            Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone())),
+            columns,
+        )?)
+    }
+}
+
+/// Add constant string columns to every batch, replacing same-named ones.
+#[derive(Debug, Clone)]
+pub struct InjectMetadata {
+    columns: BTreeMap<String, String>,
+}
+
+impl InjectMetadata {
+    pub fn new(columns: BTreeMap<String, String>) -> Self {
+        Self { columns }
+    }
+}
+
+impl WriterMiddleware for InjectMetadata {
+    fn name(&self) -> &'static str {
+        "inject_metadata"
+    }
+
+    fn map_batch(&self, _table: &str, batch: RecordBatch) -> Result<RecordBatch> {
+        let schema = batch.schema();
+        let rows = batch.num_rows();
+        let mut fields: Vec<_> = Vec::with_capacity(schema.fields().len() + self.columns.len());
+        let mut columns: Vec<ArrayRef> = Vec::with_capacity(fields.capacity());
+        for (field, column) in schema.fields().iter().zip(batch.columns()) {
+            if !self.columns.contains_key(field.name()) {
+                fields.push(Arc::clone(field));
+                columns.push(Arc::clone(column));
+            }
+        }
+        for (name, value) in &self.columns {
+            fields.push(Arc::new(Field::new(name, DataType::Utf8, false)));
+            columns.push(Arc::new(StringArray::from(vec![value.as_str(); rows])));
+        }
+        Ok(RecordBatch::try_new(
+            Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone())),
+            columns,
+        )?)
+    }
+}
+
+/// Add a `_row_hash` column: the hex SHA-256 of every other column's name
+/// and display value, in name order. Stable across runs and column order,
+/// so sinks can skip merge updates whose hash is unchanged.
+#[derive(Debug, Clone)]
+pub struct RowHash {
+    exclude: HashSet<String>,
+}
+
+impl RowHash {
+    /// `exclude` lists the key columns and any volatile ones to leave out.
+    pub fn new(exclude: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            exclude: exclude.into_iter().collect(),
+        }
+    }
+}
+
+impl WriterMiddleware for RowHash {
+    fn name(&self) -> &'static str {
+        "row_hash"
+    }
+
+    fn map_batch(&self, _table: &str, batch: RecordBatch) -> Result<RecordBatch> {
+        let schema = batch.schema();
+        let mut hashed: Vec<(&str, &ArrayRef)> = schema
+            .fields()
+            .iter()
+            .zip(batch.columns())
+            .map(|(field, column)| (field.name().as_str(), column))
+            .filter(|(name, _)| *name != ROW_HASH_COLUMN && !self.exclude.contains(*name))
+            .collect();
+        hashed.sort_by_key(|(name, _)| *name);
+
+        let options = FormatOptions::default();
+        let formatters = hashed
+            .iter()
+            .map(|(name, column)| Ok((*name, column, ArrayFormatter::try_new(column, &options)?)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let hashes: StringArray = (0..batch.num_rows())
+            .map(|row| {
+                let mut hasher = Sha256::new();
+                for (name, column, formatter) in &formatters {
+                    hasher.update(name.as_bytes());
+                    hasher.update([0x1f]);
+                    if column.is_null(row) {
+                        hasher.update([0x00]);
+                    } else {
+                        hasher.update([0x01]);
+                        hasher.update(formatter.value(row).to_string().as_bytes());
+                    }
+                    hasher.update([0x1e]);
+                }
+                Some(
+                    hasher
+                        .finalize()
+                        .iter()
+                        .map(|b| format!("{b:02x}"))
+                        .collect::<String>(),
+                )
+            })
+            .collect();
+
+        let mut fields: Vec<_> = Vec::with_capacity(schema.fields().len() + 1);
+        let mut columns: Vec<ArrayRef> = Vec::with_capacity(fields.capacity());
+        for (field, column) in schema.fields().iter().zip(batch.columns()) {
+            if field.name() != ROW_HASH_COLUMN {
+                fields.push(Arc::clone(field));
+                columns.push(Arc::clone(column));
+            }
+        }
+        fields.push(Arc::new(Field::new(ROW_HASH_COLUMN, DataType::Utf8, false)));
+        columns.push(Arc::new(hashes));
+        Ok(RecordBatch::try_new(
+            Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone())),
+            columns,
+        )?)
+    }
+}
+
+/// Log how many rows each write carried and how long the sink took.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timing;
+
+#[async_trait]
+impl WriterMiddleware for Timing {
+    fn name(&self) -> &'static str {
+        "timing"
+    }
+
+    async fn after_write(&self, table: &str, stats: &WriteStats, ok: bool) -> Result<()> {
+        info!(
+            table,
+            rows = stats.rows,
+            batches = stats.batches,
+            elapsed_ms = stats.elapsed.as_millis() as u64,
+            ok,
+            "sink write finished"
+        );
+        Ok(())
+    }
+}
+
+/// Running total of rows and writes that reached the sink.
+#[derive(Debug, Default)]
+pub struct RowCounter {
+    rows: AtomicU64,
+    writes: AtomicU64,
+}
+
+impl RowCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn rows(&self) -> u64 {
+        self.rows.load(Ordering::Relaxed)
+    }
+
+    pub fn writes(&self) -> u64 {
+        self.writes.load(Ordering::Relaxed)
+    }
+}
+
+#[async_trait]
+impl WriterMiddleware for RowCounter {
+    fn name(&self) -> &'static str {
+        "row_counter"
+    }
+
+    async fn after_write(&self, _table: &str, stats: &WriteStats, ok: bool) -> Result<()> {
+        if ok {
+            self.rows.fetch_add(stats.rows, Ordering::Relaxed);
+            self.writes.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
@@ -1,11 +1,36 @@
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use datafusion::execution::SendableRecordBatchStream;
 
 use crate::{
     errors::Result,
+    http::fetcher::convert_record_batch_to_json,
+    pipeline::duplicates::DuplicateKeys,
     utils::datafusion_ext::{QueryError, QueryResult, QueryResultStream},
+    writer::coercion::CoercionStats,
 };
 
+pub mod coercion;
+pub mod compress;
+pub mod debug;
+pub mod delta;
+pub mod file;
+pub mod kafka;
+pub mod key_inference;
+pub mod metadata_columns;
+pub mod middleware;
+pub mod mongodb;
+pub mod parquet;
 pub mod postgres;
+pub mod redshift;
+pub mod rollup;
+pub mod snowflake;
+pub mod sqlite;
+pub mod webhook;
+
+/// Column written by the `row_hash` middleware. SQL sinks that find it in a
+/// merge skip updating rows whose stored hash is unchanged.
+pub const ROW_HASH_COLUMN: &str = "_row_hash";
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum WriteMode {
@@ -13,6 +38,57 @@ pub enum WriteMode {
     Append,
 }
 
+/// How the rows of merge loads landed: new keys, matched keys whose values
+/// changed, and matched keys rewritten with identical values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MergeStats {
+    pub inserted: u64,
+    pub changed: u64,
+    pub unchanged: u64,
+}
+
+impl MergeStats {
+    pub fn matched(&self) -> u64 {
+        self.changed + self.unchanged
+    }
+
+    /// Share of matched rows whose values changed; `None` before any match.
+    pub fn churn(&self) -> Option<f64> {
+        match self.matched() {
+            0 => None,
+            matched => Some(self.changed as f64 / matched as f64),
+        }
+    }
+
+    pub fn add(&mut self, other: MergeStats) {
+        self.inserted += other.inserted;
+        self.changed += other.changed;
+        self.unchanged += other.unchanged;
+    }
+
+    /// Log the report for `table`. A table where every matched row changed
+    /// usually means the API rewrites a volatile field (e.g. `updated_at`) on
+    /// each call, so every run rewrites the whole table.
+    pub fn log(&self, table: &str) {
+        let churn_pct = self.churn().map(|c| (c * 1000.0).round() / 10.0);
+        tracing::info!(
+            table,
+            inserted = self.inserted,
+            changed = self.changed,
+            unchanged = self.unchanged,
+            churn_pct,
+            "🔁 Merge mutation report"
+        );
+        if self.unchanged == 0 && self.changed > 0 {
+            tracing::warn!(
+                table,
+                changed = self.changed,
+                "every matched row changed; the API may rewrite a field such as a timestamp on each call"
+            );
+        }
+    }
+}
+
 #[async_trait]
 pub trait DataWriter: Send + Sync {
     /// Write query result to destination (in-memory).
@@ -23,10 +99,91 @@ pub trait DataWriter: Send + Sync {
         Ok(())
     }
 
+    /// Write Arrow record batches straight from the query plan. Row-oriented
+    /// sinks keep the default, which converts to JSON and calls `write_stream`.
+    async fn write_batches(
+        &self,
+        table_name: &str,
+        batches: SendableRecordBatchStream,
+        write_mode: WriteMode,
+    ) -> Result<()> {
+        let stream = QueryResultStream {
+            table_name: table_name.to_string(),
+            data: convert_record_batch_to_json(batches),
+        };
+        self.write_stream(stream, write_mode).await
+    }
+
+    /// Newest value of a load-timestamp column in the destination, used by
+    /// freshness checks. `None` when unknown (missing table, unsupported sink).
+    async fn newest_loaded_at(&self, _column: &str) -> Result<Option<DateTime<Utc>>> {
+        Ok(None)
+    }
+
     async fn merge(&self, _result: QueryResultStream) -> Result<()> {
         Ok(())
     }
 
+    /// Delete rows whose `column` is older than `cutoff`, first detaching
+    /// partitions that lie wholly before it when asked to. Returns the number
+    /// of deleted rows, `None` when the sink has no retention support.
+    async fn purge_before(
+        &self,
+        _column: &str,
+        _cutoff: DateTime<Utc>,
+        _detach_partitions: bool,
+    ) -> Result<Option<u64>> {
+        Ok(None)
+    }
+
+    /// After a full load, handle the rows whose `key` (as text) is not among
+    /// `seen`: stamp `deleted_column` with `at` where it is unset, clearing
+    /// it on rows seen again, or delete them when there is no such column.
+    /// Returns the rows stamped or deleted, `None` when the sink cannot.
+    async fn reconcile(
+        &self,
+        _key: &str,
+        _seen: &[String],
+        _deleted_column: Option<&str>,
+        _at: DateTime<Utc>,
+    ) -> Result<Option<u64>> {
+        Ok(None)
+    }
+
+    /// Rows inserted, changed and left identical by merge loads so far, for
+    /// writers built with a mutation report. `None` when not tracked.
+    fn merge_stats(&self) -> Option<MergeStats> {
+        None
+    }
+
+    /// Values lost to NULL and rows dead-lettered because they did not fit
+    /// their column type. `None` when the sink does not check.
+    fn coercion_stats(&self) -> Option<CoercionStats> {
+        None
+    }
+
+    /// Rows currently in the destination table, for consistency checks.
+    /// `None` when the sink cannot count them.
+    async fn row_count(&self) -> Result<Option<u64>> {
+        Ok(None)
+    }
+
+    /// Up to `limit` random non-null values of `column`, as text.
+    async fn sample_keys(&self, _column: &str, _limit: usize) -> Result<Option<Vec<String>>> {
+        Ok(None)
+    }
+
+    /// How many of `keys` (compared as text) exist in `column`.
+    async fn count_keys(&self, _column: &str, _keys: &[String]) -> Result<Option<u64>> {
+        Ok(None)
+    }
+
+    /// Keys of `columns` that occur in more than one row. `None` when the
+    /// sink cannot group rows.
+    async fn duplicate_keys(&self, _columns: &[String]) -> Result<Option<DuplicateKeys>> {
+        Ok(None)
+    }
+
     /// Handle query errors.
     async fn on_error(&self, error: QueryError) -> Result<()> {
         tracing::error!("❌ Error in {}: {}", error.table_name, error.error);
@@ -0,0 +1,243 @@
+// src/writer/mongodb.rs
+
+use crate::errors::{ApitapError, Result};
+use crate::utils::datafusion_ext::{QueryError, QueryResult, QueryResultStream};
+use crate::writer::{DataWriter, WriteMode};
+use async_trait::async_trait;
+use mongodb::bson::{self, doc, Bson, Document};
+use mongodb::options::IndexOptions;
+use mongodb::{Database, IndexModel};
+use serde_json::Value;
+use std::collections::HashMap;
+use tokio::sync::OnceCell;
+use tokio_stream::StreamExt;
+use tracing::{debug, info, warn};
+
+//=============== MongoDB Writer ==============================================//
+
+/// Writes rows as documents into a MongoDB collection.
+///
+/// Nested objects and arrays are stored as sub-documents and BSON arrays, not
+/// as JSON strings. In merge mode each batch is sent as a single unordered
+/// `update` command that replaces (or inserts) the document whose primary key
+/// field matches; rows without a key, and every row in append mode, are
+/// inserted with `insertMany`. A unique index on the key field is created on
+/// first write when `auto_create` is set.
+pub struct MongoWriter {
+    database: Database,
+    pub collection: String,
+    pub primary_key: Option<String>,
+    pub batch_size: usize,
+    pub auto_create: bool,
+    index_ready: OnceCell<()>,
+}
+
+impl MongoWriter {
+    pub fn new(database: Database, collection: impl Into<String>) -> Self {
+        Self {
+            database,
+            collection: collection.into(),
+            primary_key: None,
+            batch_size: 1000,
+            auto_create: true,
+            index_ready: OnceCell::new(),
+        }
+    }
+
+    pub fn with_primary_key_single(mut self, key: impl Into<Option<String>>) -> Self {
+        self.primary_key = key.into();
+        self
+    }
+
+    pub fn with_batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size.max(1);
+        self
+    }
+
+    pub fn auto_create(mut self, enabled: bool) -> Self {
+        self.auto_create = enabled;
+        self
+    }
+
+    /// Convert one JSON row into a BSON document, keeping nested structure.
+    pub fn to_document(row: &Value) -> Result<Document> {
+        if !row.is_object() {
+            return Err(ApitapError::WriterError(format!(
+                "mongodb rows must be JSON objects, got: {row}"
+            )));
+        }
+        bson::to_document(row)
+            .map_err(|e| ApitapError::WriterError(format!("cannot convert row to BSON: {e}")))
+    }
+
+    /// `update` command upserting `docs` by `key`; later documents win on duplicate keys.
+    pub fn upsert_command(collection: &str, key: &str, docs: Vec<Document>) -> Document {
+        let mut order: Vec<String> = Vec::new();
+        let mut latest: HashMap<String, Document> = HashMap::new();
+        for d in docs {
+            let id = d.get(key).map(Bson::to_string).unwrap_or_default();
+            if latest.insert(id.clone(), d).is_none() {
+                order.push(id);
+            }
+        }
+
+        let updates: Vec<Document> = order
+            .into_iter()
+            .filter_map(|id| latest.remove(&id))
+            .map(|d| {
+                let filter = doc! { key: d.get(key).cloned().unwrap_or(Bson::Null) };
+                doc! { "q": filter, "u": d, "upsert": true }
+            })
+            .collect();
+
+        doc! { "update": collection, "updates": updates, "ordered": false }
+    }
+
+    /// Whether a document carries a usable (non-null) value for `key`.
+    fn has_key(d: &Document, key: &str) -> bool {
+        !matches!(d.get(key), None | Some(Bson::Null))
+    }
+
+    async fn ensure_index(&self, key: &str) -> Result<()> {
+        self.index_ready
+            .get_or_try_init(|| async {
+                let index = IndexModel::builder()
+                    .keys(doc! { key: 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build();
+                self.database
+                    .collection::<Document>(&self.collection)
+                    .create_index(index)
+                    .await?;
+                debug!(collection = %self.collection, key, "ensured unique index");
+                Ok::<(), ApitapError>(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn insert(&self, docs: Vec<Document>) -> Result<u64> {
+        if docs.is_empty() {
+            return Ok(0);
+        }
+        let result = self
+            .database
+            .collection::<Document>(&self.collection)
+            .insert_many(docs)
+            .ordered(false)
+            .await?;
+        Ok(result.inserted_ids.len() as u64)
+    }
+
+    async fn upsert(&self, key: &str, docs: Vec<Document>) -> Result<u64> {
+        if docs.is_empty() {
+            return Ok(0);
+        }
+        let reply = self
+            .database
+            .run_command(Self::upsert_command(&self.collection, key, docs))
+            .await?;
+
+        if let Ok(errors) = reply.get_array("writeErrors") {
+            for e in errors.iter().filter_map(Bson::as_document) {
+                self.on_error(QueryError {
+                    table_name: self.collection.clone(),
+                    error: e
+                        .get_str("errmsg")
+                        .map(str::to_string)
+                        .unwrap_or_else(|_| e.to_string()),
+                })
+                .await?;
+            }
+            if !errors.is_empty() {
+                return Err(ApitapError::WriterError(format!(
+                    "{} upsert(s) into mongodb collection '{}' failed",
+                    errors.len(),
+                    self.collection
+                )));
+            }
+        }
+        Ok(reply.get_i32("n").unwrap_or_default() as u64)
+    }
+
+    async fn flush(&self, docs: Vec<Document>, write_mode: &WriteMode) -> Result<u64> {
+        match (write_mode, self.primary_key.as_deref()) {
+            (WriteMode::Merge, Some(key)) => {
+                let (keyed, unkeyed): (Vec<_>, Vec<_>) =
+                    docs.into_iter().partition(|d| Self::has_key(d, key));
+                if !unkeyed.is_empty() {
+                    warn!(
+                        collection = %self.collection,
+                        rows = unkeyed.len(),
+                        key,
+                        "rows without a primary key value are inserted, not merged"
+                    );
+                }
+                Ok(self.upsert(key, keyed).await? + self.insert(unkeyed).await?)
+            }
+            _ => self.insert(docs).await,
+        }
+    }
+
+    /// Remove every document, keeping the collection and its indexes.
+    pub async fn truncate(&self) -> Result<()> {
+        self.database
+            .collection::<Document>(&self.collection)
+            .delete_many(doc! {})
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DataWriter for MongoWriter {
+    async fn write_stream(
+        &self,
+        mut result: QueryResultStream,
+        write_mode: WriteMode,
+    ) -> Result<()> {
+        if write_mode == WriteMode::Merge && self.primary_key.is_none() {
+            warn!(collection = %self.collection, "no primary key configured; inserting instead of merging");
+        }
+        if let (true, WriteMode::Merge, Some(key)) =
+            (self.auto_create, &write_mode, self.primary_key.as_deref())
+        {
+            self.ensure_index(key).await?;
+        }
+
+        let mut batch: Vec<Document> = Vec::with_capacity(self.batch_size);
+        let mut written = 0u64;
+
+        while let Some(item) = result.data.next().await {
+            batch.push(Self::to_document(&item?)?);
+            if batch.len() >= self.batch_size {
+                written += self.flush(std::mem::take(&mut batch), &write_mode).await?;
+            }
+        }
+        written += self.flush(batch, &write_mode).await?;
+
+        info!(collection = %self.collection, documents = written, "written to mongodb");
+        Ok(())
+    }
+
+    async fn write(&self, result: QueryResult) -> Result<()> {
+        let rows = match result.data {
+            Value::Array(rows) => rows,
+            _ => {
+                return Err(ApitapError::PipelineError(
+                    "Expected JSON array".to_string(),
+                ))
+            }
+        };
+        let stream = QueryResultStream {
+            table_name: result.table_name,
+            data: Box::pin(futures::stream::iter(rows.into_iter().map(Ok))),
+        };
+        self.write_stream(stream, WriteMode::Append).await
+    }
+
+    async fn on_error(&self, error: QueryError) -> Result<()> {
+        tracing::error!(collection = %error.table_name, error = %error.error, "mongodb write failed");
+        Ok(())
+    }
+}
@@ -0,0 +1,325 @@
+// src/writer/parquet.rs
+
+use crate::errors::{ApitapError, Result};
+use crate::utils::datafusion_ext::{QueryResult, QueryResultStream};
+use crate::utils::schema::infer_schema_from_values;
+use crate::utils::storage::resolve_object_store;
+use crate::utils::streaming::TrueStreamingProcessor;
+use crate::writer::{DataWriter, WriteMode};
+use async_trait::async_trait;
+use datafusion::arrow::array::{RecordBatch, UInt32Array};
+use datafusion::arrow::compute::take_record_batch;
+use datafusion::arrow::util::display::array_value_to_string;
+use datafusion::execution::SendableRecordBatchStream;
+use datafusion::parquet::arrow::async_writer::{AsyncArrowWriter, ParquetObjectWriter};
+use datafusion::parquet::basic::{Compression, GzipLevel, ZstdLevel};
+use datafusion::parquet::file::properties::WriterProperties;
+use futures::StreamExt;
+use object_store::path::Path as ObjectPath;
+use object_store::ObjectStore;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+/// Hive's placeholder directory for NULL partition values.
+const NULL_PARTITION: &str = "__HIVE_DEFAULT_PARTITION__";
+
+//=============== Type Definitions ============================================//
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParquetCompression {
+    None,
+    #[default]
+    Snappy,
+    Gzip,
+    Lz4,
+    Zstd,
+}
+
+impl ParquetCompression {
+    pub fn to_parquet(self) -> Compression {
+        match self {
+            ParquetCompression::None => Compression::UNCOMPRESSED,
+            ParquetCompression::Snappy => Compression::SNAPPY,
+            ParquetCompression::Gzip => Compression::GZIP(GzipLevel::default()),
+            ParquetCompression::Lz4 => Compression::LZ4_RAW,
+            ParquetCompression::Zstd => Compression::ZSTD(ZstdLevel::default()),
+        }
+    }
+}
+
+struct OpenFile {
+    path: ObjectPath,
+    writer: AsyncArrowWriter<ParquetObjectWriter>,
+}
+
+//=============== Parquet Writer ==============================================//
+
+/// Writes query results as Parquet files under `<location>/<table>/`.
+///
+/// Record batches come straight from the DataFusion plan. With `partition_by`
+/// rows are split into Hive-style `col=value/` directories (partition columns
+/// are not repeated inside the files). A file is closed and a new one started
+/// once it reaches `max_file_size` bytes.
+pub struct ParquetWriter {
+    store: Arc<dyn ObjectStore>,
+    prefix: ObjectPath,
+    pub table_name: String,
+    pub compression: ParquetCompression,
+    pub max_file_size: usize,
+    pub partition_by: Vec<String>,
+    pub batch_size: usize,
+    file_seq: AtomicUsize,
+    merge_warned: AtomicBool,
+}
+
+impl ParquetWriter {
+    pub fn new(
+        store: Arc<dyn ObjectStore>,
+        prefix: ObjectPath,
+        table_name: impl Into<String>,
+    ) -> Self {
+        Self {
+            store,
+            prefix,
+            table_name: table_name.into(),
+            compression: ParquetCompression::default(),
+            max_file_size: 128 * 1024 * 1024,
+            partition_by: Vec::new(),
+            batch_size: 8192,
+            file_seq: AtomicUsize::new(0),
+            merge_warned: AtomicBool::new(false),
+        }
+    }
+
+    /// Build a writer for a local path or `s3://bucket/prefix` URI.
+    pub fn from_location(location: &str, table_name: impl Into<String>) -> Result<Self> {
+        let (store, prefix) = resolve_object_store(location)?;
+        Ok(Self::new(store, prefix, table_name))
+    }
+
+    pub fn with_compression(mut self, compression: ParquetCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn with_max_file_size(mut self, bytes: usize) -> Self {
+        self.max_file_size = bytes.max(1);
+        self
+    }
+
+    pub fn with_partition_by(mut self, columns: Vec<String>) -> Self {
+        self.partition_by = columns;
+        self
+    }
+
+    pub fn with_batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size.max(1);
+        self
+    }
+
+    /// Directory holding every file of this table.
+    pub fn table_dir(&self) -> ObjectPath {
+        self.table_name
+            .split('.')
+            .fold(self.prefix.clone(), |p, part| p.child(part))
+    }
+
+    fn next_file_path(&self, partition: &[String]) -> ObjectPath {
+        let mut dir = self.table_dir();
+        for part in partition {
+            // `child` percent-encodes `/` and other reserved characters.
+            dir = dir.child(part.as_str());
+        }
+        let alphabet: [char; 16] = [
+            '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'a', 'b', 'c', 'd', 'e', 'f',
+        ];
+        let seq = self.file_seq.fetch_add(1, Ordering::Relaxed);
+        dir.child(format!(
+            "part-{}-{}-{:05}.parquet",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S"),
+            nanoid::nanoid!(8, &alphabet),
+            seq
+        ))
+    }
+
+    fn open_file(&self, partition: &[String], batch: &RecordBatch) -> Result<OpenFile> {
+        let path = self.next_file_path(partition);
+        let props = WriterProperties::builder()
+            .set_compression(self.compression.to_parquet())
+            .build();
+        let sink = ParquetObjectWriter::new(Arc::clone(&self.store), path.clone());
+        let writer = AsyncArrowWriter::try_new(sink, batch.schema(), Some(props))?;
+        debug!(path = %path, "opened parquet file");
+        Ok(OpenFile { path, writer })
+    }
+
+    /// Hive-style directory name for a single partition value.
+    pub fn partition_segment(column: &str, value: Option<&str>) -> String {
+        match value {
+            Some(v) if !v.is_empty() => format!("{column}={v}"),
+            _ => format!("{column}={NULL_PARTITION}"),
+        }
+    }
+
+    /// Split a batch by the partition columns; returns `(dirs, batch)` pairs.
+    pub fn split_partitions(&self, batch: &RecordBatch) -> Result<Vec<(Vec<String>, RecordBatch)>> {
+        if self.partition_by.is_empty() {
+            return Ok(vec![(Vec::new(), batch.clone())]);
+        }
+
+        let schema = batch.schema();
+        let mut part_ix = Vec::with_capacity(self.partition_by.len());
+        for col in &self.partition_by {
+            let ix = schema.index_of(col).map_err(|_| {
+                ApitapError::WriterError(format!(
+                    "partition column '{col}' not found in query result for {}",
+                    self.table_name
+                ))
+            })?;
+            part_ix.push(ix);
+        }
+
+        let mut groups: BTreeMap<Vec<String>, Vec<u32>> = BTreeMap::new();
+        for row in 0..batch.num_rows() {
+            let mut segments = Vec::with_capacity(part_ix.len());
+            for (col, &ix) in self.partition_by.iter().zip(&part_ix) {
+                let array = batch.column(ix);
+                let value = if array.is_null(row) {
+                    None
+                } else {
+                    Some(array_value_to_string(array.as_ref(), row)?)
+                };
+                segments.push(Self::partition_segment(col, value.as_deref()));
+            }
+            groups.entry(segments).or_default().push(row as u32);
+        }
+
+        let keep: Vec<usize> = (0..schema.fields().len())
+            .filter(|i| !part_ix.contains(i))
+            .collect();
+        let projected = batch.project(&keep)?;
+
+        groups
+            .into_iter()
+            .map(|(dir, rows)| {
+                let part = take_record_batch(&projected, &UInt32Array::from(rows))?;
+                Ok((dir, part))
+            })
+            .collect()
+    }
+
+    /// Delete every file under the table directory.
+    pub async fn truncate(&self) -> Result<()> {
+        let dir = self.table_dir();
+        info!(path = %dir, "truncating parquet table");
+        let mut listing = self.store.list(Some(&dir));
+        while let Some(meta) = listing.next().await {
+            self.store.delete(&meta?.location).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DataWriter for ParquetWriter {
+    async fn write_batches(
+        &self,
+        _table_name: &str,
+        mut batches: SendableRecordBatchStream,
+        write_mode: WriteMode,
+    ) -> Result<()> {
+        if write_mode == WriteMode::Merge && !self.merge_warned.swap(true, Ordering::Relaxed) {
+            warn!(table = %self.table_name, "parquet sink cannot merge; appending new files");
+        }
+
+        let mut open: HashMap<Vec<String>, OpenFile> = HashMap::new();
+        let mut files = 0usize;
+        let mut rows = 0usize;
+
+        while let Some(batch) = batches.next().await {
+            let batch = batch?;
+            if batch.num_rows() == 0 {
+                continue;
+            }
+            rows += batch.num_rows();
+
+            for (partition, part) in self.split_partitions(&batch)? {
+                let file = match open.get_mut(&partition) {
+                    Some(f) => f,
+                    None => {
+                        let f = self.open_file(&partition, &part)?;
+                        open.entry(partition.clone()).or_insert(f)
+                    }
+                };
+                file.writer.write(&part).await?;
+
+                let size = file.writer.bytes_written() + file.writer.in_progress_size();
+                if size >= self.max_file_size {
+                    if let Some(done) = open.remove(&partition) {
+                        done.writer.close().await?;
+                        debug!(path = %done.path, bytes = size, "rolled over parquet file");
+                        files += 1;
+                    }
+                }
+            }
+        }
+
+        for (_, f) in open.drain() {
+            f.writer.close().await?;
+            files += 1;
+        }
+
+        info!(table = %self.table_name, rows, files, "wrote parquet files");
+        Ok(())
+    }
+
+    async fn write_stream(
+        &self,
+        mut result: QueryResultStream,
+        write_mode: WriteMode,
+    ) -> Result<()> {
+        // JSON input: infer a schema from the first chunk, then reuse the batch path.
+        let mut head: Vec<Value> = Vec::with_capacity(self.batch_size.min(1000));
+        while head.len() < self.batch_size.min(1000) {
+            match result.data.next().await {
+                Some(item) => head.push(item?),
+                None => break,
+            }
+        }
+        if head.is_empty() {
+            return Ok(());
+        }
+
+        let schema = infer_schema_from_values(&head)?;
+        let rows = futures::stream::iter(head.into_iter().map(Ok)).chain(result.data);
+        let batches = TrueStreamingProcessor::new(self.batch_size)
+            .json_to_batch_stream(Box::pin(rows), Arc::clone(&schema))
+            .await?
+            .map(|b| b.map_err(|e| datafusion::error::DataFusionError::External(Box::new(e))));
+        let stream =
+            datafusion::physical_plan::stream::RecordBatchStreamAdapter::new(schema, batches);
+        self.write_batches(&result.table_name, Box::pin(stream), write_mode)
+            .await
+    }
+
+    async fn write(&self, result: QueryResult) -> Result<()> {
+        let rows = match result.data {
+            Value::Array(rows) => rows,
+            _ => {
+                return Err(ApitapError::PipelineError(
+                    "Expected JSON array".to_string(),
+                ))
+            }
+        };
+        let stream = QueryResultStream {
+            table_name: result.table_name,
+            data: Box::pin(futures::stream::iter(rows.into_iter().map(Ok))),
+        };
+        self.write_stream(stream, WriteMode::Append).await
+    }
+}
@@ -1,15 +1,24 @@
 // src/utils/postgres_writer.rs
 
 use crate::errors::{ApitapError, Result};
+use crate::pipeline::duplicates::DuplicateKeys;
+use crate::pipeline::freshness::parse_loaded_at;
+use crate::transform::binary::decode_base64;
 use crate::utils::datafusion_ext::{QueryResult, QueryResultStream};
-use crate::writer::{DataWriter, WriteMode};
+use crate::writer::coercion::{self, CoercionPolicy, CoercionStats};
+use crate::writer::compress::{compress_row, compressed_name};
+use crate::writer::key_inference::resolve_key;
+use crate::writer::metadata_columns::MetadataColumn;
+use crate::writer::{DataWriter, MergeStats, WriteMode, ROW_HASH_COLUMN};
 use async_trait::async_trait;
 use serde_json::Value;
-use sqlx::{types::Json, PgPool};
+use sqlx::{types::Json, PgPool, Row};
 use std::borrow::Cow;
 use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
 use tokio_stream::StreamExt;
-use tracing::{debug, debug_span, info};
+use tracing::{debug, debug_span, info, warn};
 
 //=============== Type Definitions ============================================//
 
@@ -20,6 +29,8 @@ pub enum PgType {
     BigInt,
     Double,
     Jsonb,
+    /// Bytes, bound from the base64 text carried in JSON rows.
+    Bytea,
 }
 
 impl PgType {
@@ -30,6 +41,7 @@ impl PgType {
             PgType::BigInt => "BIGINT",
             PgType::Double => "DOUBLE PRECISION",
             PgType::Jsonb => "JSONB",
+            PgType::Bytea => "BYTEA",
         }
     }
 
@@ -81,6 +93,16 @@ impl PrimaryKey {
     }
 }
 
+/// A column of an existing table whose values Postgres supplies itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComputedColumn {
+    /// `GENERATED ALWAYS AS (...) STORED`; never written.
+    Generated,
+    /// An identity column; `always` when only `OVERRIDING SYSTEM VALUE`
+    /// lets a value through.
+    Identity { always: bool },
+}
+
 pub struct PostgresWriter {
     pool: PgPool,
     pub table_name: String,
@@ -90,9 +112,26 @@ pub struct PostgresWriter {
     pub auto_truncate: bool,
     columns_cache: tokio::sync::RwLock<Option<BTreeMap<String, PgType>>>,
     pub primary_key: Option<String>,
+    /// Guess the merge key from the first batch when `primary_key` is unset.
+    infer_primary_key: bool,
+    inferred_key: OnceLock<String>,
     version_cache: tokio::sync::RwLock<Option<PostgresVersion>>,
+    mutations: Option<Mutex<MergeStats>>,
+    binary_columns: Vec<String>,
+    /// Stored zstd-compressed as `<column>_compressed BYTEA`.
+    compressed_columns: Vec<String>,
+    /// Set until the first batch's statement has been explained.
+    explain_pending: AtomicBool,
+    coercion_policy: CoercionPolicy,
+    coercions: Mutex<CoercionStats>,
+    /// Set when the key is a `GENERATED ALWAYS` identity column, whose
+    /// values need `OVERRIDING SYSTEM VALUE`.
+    override_identity: AtomicBool,
 }
 
+/// Bind parameters one statement may carry (the wire protocol's `u16`).
+pub const MAX_BIND_PARAMS: usize = u16::MAX as usize;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct PostgresVersion {
     pub major: u32,
@@ -166,7 +205,16 @@ impl PostgresWriter {
             auto_truncate: false,
             columns_cache: tokio::sync::RwLock::new(None),
             primary_key: None,
+            infer_primary_key: false,
+            inferred_key: OnceLock::new(),
             version_cache: tokio::sync::RwLock::new(None),
+            mutations: None,
+            binary_columns: Vec::new(),
+            compressed_columns: Vec::new(),
+            explain_pending: AtomicBool::new(false),
+            coercion_policy: CoercionPolicy::default(),
+            coercions: Mutex::new(CoercionStats::default()),
+            override_identity: AtomicBool::new(false),
         }
     }
 
@@ -175,6 +223,20 @@ impl PostgresWriter {
         self
     }
 
+    /// Merge on a key inferred from the first batch when none is configured;
+    /// without it such merges fail, naming the candidate keys.
+    pub fn with_infer_primary_key(mut self, enabled: bool) -> Self {
+        self.infer_primary_key = enabled;
+        self
+    }
+
+    /// The configured key, or the one inferred from the first batch.
+    pub fn key(&self) -> Option<&str> {
+        self.primary_key
+            .as_deref()
+            .or_else(|| self.inferred_key.get().map(String::as_str))
+    }
+
     pub fn with_batch_size(mut self, size: usize) -> Self {
         self.batch_size = size;
         self
@@ -195,6 +257,88 @@ impl PostgresWriter {
         self
     }
 
+    /// Count inserted, changed and identical rows of every merge batch; costs
+    /// one extra query per batch.
+    pub fn with_mutation_report(mut self, enabled: bool) -> Self {
+        self.mutations = enabled.then(|| Mutex::new(MergeStats::default()));
+        self
+    }
+
+    /// Columns holding base64 text that are stored as `BYTEA`.
+    pub fn with_binary_columns(mut self, columns: Vec<String>) -> Self {
+        self.binary_columns = columns;
+        self
+    }
+
+    /// Columns stored as zstd-compressed JSON in `<column>_compressed BYTEA`.
+    pub fn with_compressed_columns(mut self, columns: Vec<String>) -> Self {
+        self.compressed_columns = columns;
+        self
+    }
+
+    /// Run `EXPLAIN` on the first batch's MERGE/INSERT statement and log the
+    /// plan before executing it.
+    pub fn with_explain_first_batch(self, enabled: bool) -> Self {
+        self.explain_pending.store(enabled, Ordering::Relaxed);
+        self
+    }
+
+    /// What to do with values that do not fit their column's type.
+    pub fn with_coercion_policy(mut self, policy: CoercionPolicy) -> Self {
+        self.coercion_policy = policy;
+        self
+    }
+
+    /// Table holding the rows a `dead_letter` coercion policy sets aside.
+    pub fn dead_letter_table(&self) -> String {
+        format!("{}_dead_letter", self.table_name)
+    }
+
+    /// Statement creating [`Self::dead_letter_table`].
+    pub fn dead_letter_create_sql(&self) -> String {
+        format!(
+            r#"CREATE TABLE IF NOT EXISTS {} ("row" JSONB NOT NULL, "columns" TEXT NOT NULL, "failed_at" TIMESTAMPTZ NOT NULL DEFAULT now())"#,
+            Self::quote_ident_path(&self.dead_letter_table())
+        )
+    }
+
+    /// Apply the coercion policy to a batch, counting what it drops and
+    /// storing dead-lettered rows.
+    async fn screen_coercions<'a>(
+        &self,
+        rows: &'a [Value],
+        schema: &BTreeMap<String, PgType>,
+    ) -> Result<Cow<'a, [Value]>> {
+        let screened = coercion::screen(rows, schema, self.coercion_policy, &self.table_name)?;
+        if screened.stats.is_empty() {
+            return Ok(screened.rows);
+        }
+        debug!(table = %self.table_name, stats = ?screened.stats, "batch coercions");
+        if !screened.rejected.is_empty() {
+            sqlx::query(&self.dead_letter_create_sql())
+                .execute(&self.pool)
+                .await?;
+            let placeholders: Vec<String> = (0..screened.rejected.len())
+                .map(|i| format!("(${}, ${})", 2 * i + 1, 2 * i + 2))
+                .collect();
+            let sql = format!(
+                r#"INSERT INTO {} ("row", "columns") VALUES {}"#,
+                Self::quote_ident_path(&self.dead_letter_table()),
+                placeholders.join(", ")
+            );
+            let mut q = sqlx::query(&sql);
+            for rejected in &screened.rejected {
+                q = q.bind(Json(&rejected.row)).bind(rejected.columns.join(","));
+            }
+            q.execute(&self.pool).await?;
+        }
+        self.coercions
+            .lock()
+            .map_err(|e| ApitapError::PoisonError(e.to_string()))?
+            .add(&screened.stats);
+        Ok(screened.rows)
+    }
+
     async fn table_exists(&self) -> Result<bool> {
         let result: (bool,) = sqlx::query_as(
             "SELECT EXISTS (
@@ -222,10 +366,7 @@ impl PostgresWriter {
 
             for (key, value) in obj {
                 let pg_type = PgType::from_json_value(value);
-                column_types
-                    .entry(key.clone())
-                    .or_insert_with(Vec::new)
-                    .push(pg_type);
+                column_types.entry(key.clone()).or_default().push(pg_type);
             }
         }
 
@@ -252,6 +393,15 @@ impl PostgresWriter {
             .join(".")
     }
 
+    /// The schema and table of a table path; `None` when the path names no
+    /// schema, leaving it to the connection's `current_schema()`.
+    pub fn split_table_path(path: &str) -> (Option<&str>, &str) {
+        // mydb.public.users -> (Some("public"), "users")
+        let mut parts = path.rsplit('.');
+        let table = parts.next().unwrap_or(path);
+        (parts.next(), table)
+    }
+
     pub async fn create_table_from_schema(&self, schema: &BTreeMap<String, PgType>) -> Result<()> {
         if schema.is_empty() {
             return Err(ApitapError::PipelineError(
@@ -264,7 +414,7 @@ impl PostgresWriter {
             .map(|(name, pg_type)| format!(r#"{} {}"#, Self::quote_ident(name), pg_type.as_sql()))
             .collect();
 
-        let pk_clause: Option<String> = match &self.primary_key {
+        let pk_clause: Option<String> = match self.key() {
             Some(pk_name) => {
                 if schema.contains_key(pk_name) {
                     Some(format!(r#"PRIMARY KEY ({})"#, Self::quote_ident(pk_name)))
@@ -307,6 +457,86 @@ impl PostgresWriter {
         Ok(())
     }
 
+    /// Generated and identity columns of the existing table.
+    async fn computed_columns(&self) -> Result<BTreeMap<String, ComputedColumn>> {
+        let (schema, table) = Self::split_table_path(&self.table_name);
+        let rows: Vec<(String, bool, bool)> = sqlx::query_as(
+            "SELECT column_name::text,
+                    is_generated = 'ALWAYS',
+                    COALESCE(identity_generation = 'ALWAYS', false)
+             FROM information_schema.columns
+             WHERE table_schema = COALESCE($1::text, current_schema())
+             AND table_name = $2
+             AND (is_generated = 'ALWAYS' OR is_identity = 'YES')",
+        )
+        .bind(schema)
+        .bind(table)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(name, generated, always)| {
+                let kind = if generated {
+                    ComputedColumn::Generated
+                } else {
+                    ComputedColumn::Identity { always }
+                };
+                (name, kind)
+            })
+            .collect())
+    }
+
+    /// Drop the columns Postgres computes from the written ones. An identity
+    /// column that is the primary key is kept, since merges match on it.
+    pub fn exclude_computed(
+        &self,
+        mut schema: BTreeMap<String, PgType>,
+        computed: &BTreeMap<String, ComputedColumn>,
+    ) -> BTreeMap<String, PgType> {
+        let mut excluded = Vec::new();
+        for (name, kind) in computed {
+            let is_key = self.key() == Some(name.as_str());
+            match kind {
+                ComputedColumn::Identity { always } if is_key => {
+                    self.override_identity.store(*always, Ordering::Relaxed);
+                }
+                _ => {
+                    if schema.remove(name).is_some() {
+                        excluded.push(name.as_str());
+                    }
+                }
+            }
+        }
+        if !excluded.is_empty() {
+            info!(
+                table = %self.table_name,
+                columns = ?excluded,
+                "not writing generated/identity columns"
+            );
+        }
+        schema
+    }
+
+    /// `OVERRIDING SYSTEM VALUE`, followed by a space, when inserted keys go
+    /// into a `GENERATED ALWAYS` identity column.
+    pub fn insert_overriding(&self) -> &'static str {
+        if self.override_identity.load(Ordering::Relaxed) {
+            "OVERRIDING SYSTEM VALUE "
+        } else {
+            ""
+        }
+    }
+
+    fn with_binary_types(&self, mut schema: BTreeMap<String, PgType>) -> BTreeMap<String, PgType> {
+        let compressed = self.compressed_columns.iter().map(|c| compressed_name(c));
+        for column in self.binary_columns.iter().cloned().chain(compressed) {
+            if let Some(ty) = schema.get_mut(&column) {
+                *ty = PgType::Bytea;
+            }
+        }
+        schema
+    }
+
     async fn ensure_table(&self, sample_rows: &[Value]) -> Result<BTreeMap<String, PgType>> {
         if let Some(schema) = self.columns_cache.read().await.as_ref() {
             return Ok(schema.clone());
@@ -319,7 +549,8 @@ impl PostgresWriter {
                         "Need sample data to create table".to_string(),
                     ));
                 }
-                let detected_schema = Self::analyze_schema(sample_rows, self.sample_size)?;
+                let detected_schema =
+                    self.with_binary_types(Self::analyze_schema(sample_rows, self.sample_size)?);
                 self.create_table_from_schema(&detected_schema).await?;
                 detected_schema
             } else {
@@ -332,8 +563,10 @@ impl PostgresWriter {
             if sample_rows.is_empty() {
                 return Err(ApitapError::PipelineError("Need sample data".to_string()));
             }
-            Self::analyze_schema(sample_rows, self.sample_size)?
+            let schema = Self::analyze_schema(sample_rows, self.sample_size)?;
+            self.exclude_computed(schema, &self.computed_columns().await?)
         };
+        let schema = self.with_binary_types(schema);
 
         *self.columns_cache.write().await = Some(schema.clone());
 
@@ -366,6 +599,19 @@ impl PostgresWriter {
         Ok(version)
     }
 
+    /// Before the first merge batch: make sure there is a key to merge on.
+    fn settle_key(&self, rows: &[Value], write_mode: &WriteMode) -> Result<()> {
+        if *write_mode != WriteMode::Merge || self.primary_key.is_some() {
+            return Ok(());
+        }
+        resolve_key(
+            &self.table_name,
+            self.infer_primary_key,
+            &self.inferred_key,
+            rows,
+        )
+    }
+
     pub async fn truncate(&self) -> Result<()> {
         let table_sql = Self::quote_ident(&self.table_name);
         let sql = format!("TRUNCATE TABLE {}", table_sql);
@@ -373,11 +619,12 @@ impl PostgresWriter {
         tracing::info!(table = %self.table_name, "truncating table");
         tracing::debug!(sql = %sql, "truncate sql");
 
-        match {
+        let res = {
             let span = debug_span!("sql.execute", statement = "truncate", table = %self.table_name);
             let _g = span.enter();
             sqlx::query(&sql).execute(&self.pool).await
-        } {
+        };
+        match res {
             Ok(res) => {
                 debug!(rows_affected = res.rows_affected(), "truncate executed");
                 Ok(())
@@ -411,7 +658,7 @@ impl PostgresWriter {
             return Err(ApitapError::MergeError("No columns detected".to_string()));
         }
 
-        let pk_name = self.primary_key.clone().ok_or_else(|| {
+        let pk_name = self.key().map(str::to_string).ok_or_else(|| {
             ApitapError::MergeError("Postgres: primary key not configured".to_string())
         })?;
 
@@ -463,13 +710,21 @@ impl PostgresWriter {
         let conflict_clause = if non_pk_cols.is_empty() {
             format!("ON CONFLICT ({}) DO NOTHING", pk_quoted)
         } else {
-            format!("ON CONFLICT ({}) {}", pk_quoted, update_set)
+            format!(
+                "ON CONFLICT ({}) {}{}",
+                pk_quoted,
+                update_set,
+                Self::row_hash_guard(schema, &pk_name, "EXCLUDED")
+                    .map(|guard| format!(" WHERE {guard}"))
+                    .unwrap_or_default()
+            )
         };
 
         let query = format!(
-            "INSERT INTO {} ({}) VALUES {} {}",
+            "INSERT INTO {} AS t ({}) {}VALUES {} {}",
             table_sql,
             columns_str,
+            self.insert_overriding(),
             placeholders.join(", "),
             conflict_clause
         );
@@ -492,15 +747,9 @@ impl PostgresWriter {
             }
         }
 
-        let mut q = sqlx::query(&query);
-        for (idx, value) in all_values.iter().enumerate() {
-            let col_idx = idx % values_per_row;
-            let col_name = col_names_raw[col_idx];
-            let expected = schema
-                .get(col_name)
-                .expect("schema must contain column present in rows");
-            q = self.bind_value(q, value, expected)?;
-        }
+        self.explain_first_batch("upsert", &query, &all_values, &col_names_raw, schema)
+            .await;
+        let q = self.bind_all(sqlx::query(&query), &all_values, &col_names_raw, schema)?;
 
         // Execute
         let span = debug_span!("sql.execute", statement = "upsert", table = %self.table_name, batch_rows = rows.len());
@@ -511,26 +760,288 @@ impl PostgresWriter {
         Ok(())
     }
 
+    /// Query counting, for `rows` incoming rows, the keys already in the table
+    /// and those among them whose stored values differ. Rows are compared by
+    /// the md5 of their text form over the written columns.
+    pub fn mutation_count_sql(
+        &self,
+        schema: &BTreeMap<String, PgType>,
+        rows: usize,
+    ) -> Result<String> {
+        let pk = self.key().ok_or_else(|| {
+            ApitapError::MergeError("Postgres: primary key not configured".to_string())
+        })?;
+        let cols: Vec<String> = schema.keys().map(|c| Self::quote_ident(c)).collect();
+        let placeholders: Vec<String> = (0..rows)
+            .map(|row_idx| {
+                let row_ph: Vec<String> = (1..=cols.len())
+                    .map(|col_idx| format!("${}", row_idx * cols.len() + col_idx))
+                    .collect();
+                format!("({})", row_ph.join(", "))
+            })
+            .collect();
+        // Lineage columns change on every load; compare the data only.
+        let compared: Vec<String> = schema
+            .keys()
+            .filter(|c| !MetadataColumn::is_metadata(c))
+            .map(|c| Self::quote_ident(c))
+            .collect();
+        let prefixed = |alias: &str| {
+            compared
+                .iter()
+                .map(|c| format!("{alias}.{c}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let pk_q = Self::quote_ident(pk);
+
+        Ok(format!(
+            "WITH s ({cols}) AS (VALUES {values}) \
+             SELECT COUNT(t.{pk_q}), COUNT(t.{pk_q}) FILTER (WHERE md5(ROW({t_cols})::text) IS DISTINCT FROM md5(ROW({s_cols})::text)) \
+             FROM s LEFT JOIN {table} AS t ON t.{pk_q} = s.{pk_q}",
+            cols = cols.join(", "),
+            values = placeholders.join(", "),
+            t_cols = prefixed("t"),
+            s_cols = prefixed("s"),
+            table = Self::quote_ident_path(&self.table_name),
+        ))
+    }
+
+    /// Classify a merge batch against the table before it is written.
+    async fn record_mutations(
+        &self,
+        rows: &[Value],
+        schema: &BTreeMap<String, PgType>,
+    ) -> Result<()> {
+        let Some(mutations) = &self.mutations else {
+            return Ok(());
+        };
+        if rows.is_empty() || schema.is_empty() {
+            return Ok(());
+        }
+
+        let query = self.mutation_count_sql(schema, rows.len())?;
+        let mut q = sqlx::query(&query);
+        for row in rows {
+            for (col, ty) in schema {
+                q = self.bind_value(q, row.get(col).unwrap_or(&Value::Null), ty)?;
+            }
+        }
+        let counts = q.fetch_one(&self.pool).await?;
+        let matched = counts.try_get::<i64, _>(0)? as u64;
+        let changed = counts.try_get::<i64, _>(1)? as u64;
+
+        let batch = MergeStats {
+            inserted: (rows.len() as u64).saturating_sub(matched),
+            changed,
+            unchanged: matched - changed,
+        };
+        debug!(table = %self.table_name, ?batch, "merge batch mutations");
+        mutations
+            .lock()
+            .map_err(|e| ApitapError::PoisonError(e.to_string()))?
+            .add(batch);
+        Ok(())
+    }
+
+    /// Statement deleting rows whose `column` is before `$1`. Timestamps are
+    /// stored as text, so the column is cast before comparing.
+    pub fn retention_delete_sql(&self, column: &str) -> String {
+        format!(
+            "DELETE FROM {} WHERE ({})::timestamptz < $1",
+            Self::quote_ident_path(&self.table_name),
+            Self::quote_ident(column)
+        )
+    }
+
+    /// Statements reconciling the table with the keys `$1` of a full load:
+    /// with `deleted_column`, add it if missing, stamp it with `$2` on rows
+    /// gone and clear it on rows back; otherwise delete the rows gone.
+    pub fn reconcile_sql(&self, key: &str, deleted_column: Option<&str>) -> Vec<String> {
+        let table = Self::quote_ident_path(&self.table_name);
+        let key = Self::quote_ident(key);
+        let Some(column) = deleted_column else {
+            return vec![format!(
+                "DELETE FROM {table} WHERE NOT ({key}::text = ANY($1))"
+            )];
+        };
+        let column = Self::quote_ident(column);
+        vec![
+            format!("ALTER TABLE {table} ADD COLUMN IF NOT EXISTS {column} TIMESTAMPTZ"),
+            format!(
+                "UPDATE {table} SET {column} = $2 WHERE {column} IS NULL AND NOT ({key}::text = ANY($1))"
+            ),
+            format!(
+                "UPDATE {table} SET {column} = NULL WHERE {column} IS NOT NULL AND {key}::text = ANY($1)"
+            ),
+        ]
+    }
+
+    /// Query listing the range partitions of table `$1` whose upper bound is
+    /// at or before `$2`. Default, `MAXVALUE` and multi-column bounds never
+    /// match.
+    pub fn expired_partitions_sql() -> &'static str {
+        r#"SELECT c.oid::regclass::text
+           FROM pg_inherits i
+           JOIN pg_class c ON c.oid = i.inhrelid
+           WHERE i.inhparent = to_regclass($1)
+             AND (regexp_match(pg_get_expr(c.relpartbound, c.oid), 'TO \(''([^'']*)''\)$'))[1]::timestamptz <= $2
+           ORDER BY 1"#
+    }
+
+    /// Detach partitions holding only rows older than `cutoff`; they are
+    /// kept as standalone tables for archiving or dropping.
+    async fn detach_expired_partitions(&self, cutoff: chrono::DateTime<chrono::Utc>) -> Result<()> {
+        let parent = Self::quote_ident_path(&self.table_name);
+        let partitions: Vec<(String,)> = sqlx::query_as(Self::expired_partitions_sql())
+            .bind(&parent)
+            .bind(cutoff)
+            .fetch_all(&self.pool)
+            .await?;
+        for (partition,) in partitions {
+            sqlx::query(&format!(
+                "ALTER TABLE {parent} DETACH PARTITION {partition}"
+            ))
+            .execute(&self.pool)
+            .await?;
+            info!(table = %self.table_name, %partition, "detached expired partition");
+        }
+        Ok(())
+    }
+
+    /// What looks wrong in the `plan` of a statement with `params` bind
+    /// parameters: a sequential scan of the target table, which a merge on
+    /// an indexed key should never need (usually a type mismatch on the
+    /// key), or more parameters than one statement can carry.
+    pub fn plan_warnings(&self, plan: &[String], params: usize) -> Vec<String> {
+        let table = self.table_name.rsplit('.').next().unwrap_or_default();
+        let mut warnings: Vec<String> = plan
+            .iter()
+            .map(|line| line.trim())
+            .filter(|line| {
+                line.strip_prefix("->")
+                    .unwrap_or(line)
+                    .trim_start()
+                    .strip_prefix("Seq Scan on ")
+                    .and_then(|rest| rest.split_whitespace().next())
+                    .and_then(|scanned| scanned.rsplit('.').next())
+                    .is_some_and(|scanned| scanned.trim_matches('"') == table)
+            })
+            .map(|line| format!("sequential scan of the target table: {line}"))
+            .collect();
+        if params > MAX_BIND_PARAMS {
+            warnings.push(format!(
+                "{params} bind parameters exceed the limit of {MAX_BIND_PARAMS}; lower the batch size"
+            ));
+        }
+        warnings
+    }
+
+    /// `EXPLAIN` `query` with the batch's values bound, once per writer, and
+    /// log the plan. A statement that cannot be planned is only reported;
+    /// executing it surfaces the error.
+    async fn explain_first_batch(
+        &self,
+        statement: &str,
+        query: &str,
+        values: &[Value],
+        columns: &[&str],
+        schema: &BTreeMap<String, PgType>,
+    ) {
+        if !self.explain_pending.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        let sql = format!("EXPLAIN {}", query.trim().trim_end_matches(';'));
+        let plan = match self.bind_all(sqlx::query(&sql), values, columns, schema) {
+            Ok(q) => q.fetch_all(&self.pool).await.map_err(ApitapError::from),
+            Err(e) => Err(e),
+        };
+        let plan: Vec<String> = match plan {
+            Ok(rows) => rows
+                .iter()
+                .filter_map(|row| row.try_get::<String, _>(0).ok())
+                .collect(),
+            Err(e) => {
+                warn!(table = %self.table_name, statement, error = %e, "EXPLAIN of first batch failed");
+                return;
+            }
+        };
+        info!(
+            table = %self.table_name,
+            statement,
+            params = values.len(),
+            rows = values.len() / columns.len().max(1),
+            plan = %plan.join("\n"),
+            "first batch plan"
+        );
+        for warning in self.plan_warnings(&plan, values.len()) {
+            warn!(table = %self.table_name, statement, "{warning}");
+        }
+    }
+
+    /// Bind `values`, laid out row by row in `columns` order.
+    fn bind_all<'q>(
+        &self,
+        mut q: sqlx::query::Query<'q, sqlx::Postgres, sqlx::postgres::PgArguments>,
+        values: &'q [Value],
+        columns: &[&str],
+        schema: &BTreeMap<String, PgType>,
+    ) -> Result<sqlx::query::Query<'q, sqlx::Postgres, sqlx::postgres::PgArguments>> {
+        for (idx, value) in values.iter().enumerate() {
+            let col_name = columns[idx % columns.len()];
+            let expected = schema
+                .get(col_name)
+                .expect("schema must contain column present in rows");
+            q = self.bind_value(q, value, expected)?;
+        }
+        Ok(q)
+    }
+
+    /// With a `_row_hash` column, the condition under which a matched row
+    /// is updated: its stored hash differs from the incoming one (`source`
+    /// is the alias of the incoming row).
+    fn row_hash_guard(
+        schema: &BTreeMap<String, PgType>,
+        pk_name: &str,
+        source: &str,
+    ) -> Option<String> {
+        (schema.contains_key(ROW_HASH_COLUMN) && pk_name != ROW_HASH_COLUMN).then(|| {
+            let hash = Self::quote_ident(ROW_HASH_COLUMN);
+            format!("t.{hash} IS DISTINCT FROM {source}.{hash}")
+        })
+    }
+
     pub async fn merge_batch(
         &self,
         rows: &[Value],
         schema: &BTreeMap<String, PgType>,
     ) -> Result<()> {
+        if let Some(pk) = self.key() {
+            if !rows.is_empty() && !schema.is_empty() && !schema.contains_key(pk) {
+                return Err(ApitapError::MergeError(format!(
+                    "Postgres: primary key '{pk}' is not a written column of {} (missing from the rows, or generated)",
+                    self.table_name
+                )));
+            }
+        }
+        let rows = &*self.screen_coercions(rows, schema).await?;
+        self.record_mutations(rows, schema).await?;
+
         // ---- Version Detection -------------------------------------------------
         // Choose implementation based on PostgreSQL version
         let version = self.get_postgres_version().await?;
 
         if version.supports_merge() {
             // Use MERGE for PostgreSQL 15+
-            return self.merge_batch_pg15(rows, schema).await;
+            self.merge_batch_pg15(rows, schema).await
         } else if version.supports_upsert() {
             // Use INSERT ... ON CONFLICT for PostgreSQL 9.5-14
-            return self.upsert_batch(rows, schema).await;
+            self.upsert_batch(rows, schema).await
         } else {
-            return Err(ApitapError::MergeError(format!(
+            Err(ApitapError::MergeError(format!(
                 "Merge operation requires PostgreSQL 9.5 or higher (detected version: {})",
                 version
-            )));
+            )))
         }
     }
 
@@ -550,7 +1061,7 @@ impl PostgresWriter {
             return Err(ApitapError::MergeError("No columns detected".to_string()));
         }
 
-        let pk_name = self.primary_key.clone().ok_or_else(|| {
+        let pk_name = self.key().map(str::to_string).ok_or_else(|| {
             ApitapError::MergeError("Postgres: primary key not configured".to_string())
         })?;
 
@@ -630,11 +1141,11 @@ USING (VALUES
         {values}
 ) AS s({using_cols})
 ON {pk_t} = {pk_s}
-WHEN MATCHED THEN
+WHEN MATCHED{guard} THEN
   {set}
 WHEN NOT MATCHED THEN
   INSERT ({cols})
-  VALUES ({cols_s});
+  {overriding}VALUES ({cols_s});
 "#,
                 table = table_sql,
                 values = values_block,
@@ -642,7 +1153,11 @@ WHEN NOT MATCHED THEN
                 pk_t = pk_t,
                 pk_s = pk_s,
                 set = set,
+                guard = Self::row_hash_guard(schema, &pk_name, "s")
+                    .map(|guard| format!(" AND {guard}"))
+                    .unwrap_or_default(),
                 cols = columns_t_str,
+                overriding = self.insert_overriding(),
                 cols_s = columns_s_str,
             ),
             None => format!(
@@ -654,7 +1169,7 @@ USING (VALUES
 ON {pk_t} = {pk_s}
 WHEN NOT MATCHED THEN
   INSERT ({cols})
-  VALUES ({cols_s});
+  {overriding}VALUES ({cols_s});
 "#,
                 table = table_sql,
                 values = values_block,
@@ -662,6 +1177,7 @@ WHEN NOT MATCHED THEN
                 pk_t = pk_t,
                 pk_s = pk_s,
                 cols = columns_t_str,
+                overriding = self.insert_overriding(),
                 cols_s = columns_s_str,
             ),
         };
@@ -686,15 +1202,9 @@ WHEN NOT MATCHED THEN
             }
         }
 
-        let mut q = sqlx::query(&query);
-        for (idx, value) in all_values.iter().enumerate() {
-            let col_idx = idx % values_per_row;
-            let col_name = col_names_raw[col_idx];
-            let expected = schema
-                .get(col_name)
-                .expect("schema must contain column present in rows");
-            q = self.bind_value(q, value, expected)?;
-        }
+        self.explain_first_batch("merge", &query, &all_values, &col_names_raw, schema)
+            .await;
+        let q = self.bind_all(sqlx::query(&query), &all_values, &col_names_raw, schema)?;
 
         // Execute
         // Instrument the MERGE execution and log rows_affected
@@ -711,6 +1221,7 @@ WHEN NOT MATCHED THEN
         rows: &[Value],
         schema: &BTreeMap<String, PgType>,
     ) -> Result<()> {
+        let rows = &*self.screen_coercions(rows, schema).await?;
         if rows.is_empty() {
             return Ok(());
         }
@@ -737,9 +1248,10 @@ WHEN NOT MATCHED THEN
         let table_sql = Self::quote_ident_path(&self.table_name);
 
         let query = format!(
-            "INSERT INTO {} ({}) VALUES {}",
+            "INSERT INTO {} ({}) {}VALUES {}",
             table_sql,
             columns_str,
+            self.insert_overriding(),
             placeholders.join(", ")
         );
 
@@ -752,15 +1264,10 @@ WHEN NOT MATCHED THEN
             }
         }
 
+        self.explain_first_batch("insert", &query, &all_values, &col_names_raw, schema)
+            .await;
         // Bind in the same order as placeholders
-        let mut q = sqlx::query(&query);
-        for (idx, value) in all_values.iter().enumerate() {
-            let col_idx = idx % values_per_row;
-            let col_name = col_names_raw[col_idx];
-            let expected_type = schema.get(col_name).expect("schema must contain column");
-
-            q = self.bind_value(q, value, expected_type)?;
-        }
+        let q = self.bind_all(sqlx::query(&query), &all_values, &col_names_raw, schema)?;
 
         // Instrument the insert execution and log rows_affected
         let span = debug_span!("sql.execute", statement = "insert", table = %self.table_name, batch_rows = rows.len());
@@ -784,6 +1291,13 @@ WHEN NOT MATCHED THEN
             (Value::Null, PgType::Double) => query.bind::<Option<f64>>(None),
             (Value::Null, PgType::Boolean) => query.bind::<Option<bool>>(None),
             (Value::Null, PgType::Jsonb) => query.bind(Json(Value::Null)),
+            (Value::Null, PgType::Bytea) => query.bind::<Option<Vec<u8>>>(None),
+
+            // Bytes arrive as base64 text
+            (Value::String(s), PgType::Bytea) => query.bind(decode_base64(s).ok_or_else(|| {
+                ApitapError::WriterError(format!("{}: value is not base64", self.table_name))
+            })?),
+            (_, PgType::Bytea) => query.bind(value.to_string().into_bytes()),
             (Value::Null, _) => query.bind::<Option<String>>(None),
 
             // Boolean
@@ -865,20 +1379,39 @@ impl DataWriter for PostgresWriter {
                     WriteMode::Append => self.insert_batch($buf, $schema).await,
                     WriteMode::Merge => self.merge_batch($buf, $schema).await,
                 }
+                .map_err(|e| explain_timeout(e, &self.table_name))
             };
         }
 
         let mut buf: Vec<serde_json::Value> = Vec::with_capacity(self.batch_size);
         let mut schema: Option<BTreeMap<String, PgType>> = None;
 
+        if self
+            .primary_key
+            .as_ref()
+            .is_some_and(|pk| self.compressed_columns.contains(pk))
+        {
+            return Err(ApitapError::ConfigError(format!(
+                "{}: the primary key cannot be a compressed column",
+                self.table_name
+            )));
+        }
+
         // Stream → buffer → write in batches
         while let Some(item) = result.data.next().await {
-            buf.push(item?);
+            let mut row = item?;
+            compress_row(&mut row, &self.compressed_columns)?;
+            buf.push(row);
 
             if buf.len() >= self.batch_size {
                 // Lazily infer/create table schema from current batch
                 if schema.is_none() {
-                    schema = Some(self.ensure_table(&buf).await?);
+                    self.settle_key(&buf, &write_mode)?;
+                    schema = Some(
+                        self.ensure_table(&buf)
+                            .await
+                            .map_err(|e| explain_timeout(e, &self.table_name))?,
+                    );
                 }
                 let schema_ref = schema.as_ref().expect("schema just set");
                 write_chunk!(&buf, schema_ref)?;
@@ -889,7 +1422,12 @@ impl DataWriter for PostgresWriter {
         // Flush remainder
         if !buf.is_empty() {
             if schema.is_none() {
-                schema = Some(self.ensure_table(&buf).await?);
+                self.settle_key(&buf, &write_mode)?;
+                schema = Some(
+                    self.ensure_table(&buf)
+                        .await
+                        .map_err(|e| explain_timeout(e, &self.table_name))?,
+                );
             }
             let schema_ref = schema.as_ref().expect("schema just set");
             write_chunk!(&buf, schema_ref)?;
@@ -907,8 +1445,17 @@ impl DataWriter for PostgresWriter {
         if rows.is_empty() {
             return Ok(());
         }
+        let rows: Cow<'_, [Value]> = if self.compressed_columns.is_empty() {
+            Cow::Borrowed(rows)
+        } else {
+            let mut rows = rows.clone();
+            for row in &mut rows {
+                compress_row(row, &self.compressed_columns)?;
+            }
+            Cow::Owned(rows)
+        };
 
-        let schema = self.ensure_table(rows).await?;
+        let schema = self.ensure_table(&rows).await?;
 
         for chunk in rows.chunks(self.batch_size) {
             self.insert_batch(chunk, &schema).await?;
@@ -917,6 +1464,168 @@ impl DataWriter for PostgresWriter {
         Ok(())
     }
 
+    async fn newest_loaded_at(
+        &self,
+        column: &str,
+    ) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+        let sql = format!(
+            "SELECT MAX({})::text FROM {}",
+            Self::quote_ident(column),
+            Self::quote_ident_path(&self.table_name)
+        );
+        match sqlx::query_as::<_, (Option<String>,)>(&sql)
+            .fetch_one(&self.pool)
+            .await
+        {
+            Ok((newest,)) => Ok(newest.as_deref().and_then(parse_loaded_at)),
+            Err(e) => {
+                // undefined_table / undefined_column: nothing loaded yet
+                if let Some(db_err) = e.as_database_error() {
+                    if matches!(db_err.code().as_deref(), Some("42P01") | Some("42703")) {
+                        debug!(table = %self.table_name, column, "no load timestamps yet");
+                        return Ok(None);
+                    }
+                }
+                Err(e.into())
+            }
+        }
+    }
+
+    fn coercion_stats(&self) -> Option<CoercionStats> {
+        let coercions = self.coercions.lock().ok()?;
+        Some(coercions.clone())
+    }
+
+    fn merge_stats(&self) -> Option<MergeStats> {
+        let mutations = self.mutations.as_ref()?.lock().ok()?;
+        Some(*mutations)
+    }
+
+    async fn purge_before(
+        &self,
+        column: &str,
+        cutoff: chrono::DateTime<chrono::Utc>,
+        detach_partitions: bool,
+    ) -> Result<Option<u64>> {
+        if detach_partitions {
+            self.detach_expired_partitions(cutoff).await?;
+        }
+        match sqlx::query(&self.retention_delete_sql(column))
+            .bind(cutoff)
+            .execute(&self.pool)
+            .await
+        {
+            Ok(res) => Ok(Some(res.rows_affected())),
+            Err(e) if is_undefined_table(&e) => Ok(Some(0)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn reconcile(
+        &self,
+        key: &str,
+        seen: &[String],
+        deleted_column: Option<&str>,
+        at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<u64>> {
+        let statements = self.reconcile_sql(key, deleted_column);
+        // Rows stamped by the first update, or deleted.
+        let counted = usize::from(deleted_column.is_some());
+        let mut tx = self.pool.begin().await?;
+        let mut gone = 0;
+        for (idx, sql) in statements.iter().enumerate() {
+            let mut q = sqlx::query(sql);
+            if sql.contains("$1") {
+                q = q.bind(seen);
+            }
+            if sql.contains("$2") {
+                q = q.bind(at);
+            }
+            match q.execute(&mut *tx).await {
+                Ok(res) if idx == counted => gone = res.rows_affected(),
+                Ok(_) => {}
+                Err(e) if is_undefined_table(&e) => return Ok(Some(0)),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        tx.commit().await?;
+        Ok(Some(gone))
+    }
+
+    async fn row_count(&self) -> Result<Option<u64>> {
+        let sql = format!(
+            "SELECT COUNT(*) FROM {}",
+            Self::quote_ident_path(&self.table_name)
+        );
+        match sqlx::query_scalar::<_, i64>(&sql)
+            .fetch_one(&self.pool)
+            .await
+        {
+            Ok(count) => Ok(Some(count as u64)),
+            Err(e) if is_undefined_table(&e) => Ok(Some(0)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn sample_keys(&self, column: &str, limit: usize) -> Result<Option<Vec<String>>> {
+        let col = Self::quote_ident(column);
+        let sql = format!(
+            "SELECT {col}::text FROM {} WHERE {col} IS NOT NULL ORDER BY random() LIMIT $1",
+            Self::quote_ident_path(&self.table_name)
+        );
+        match sqlx::query_scalar::<_, String>(&sql)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await
+        {
+            Ok(keys) => Ok(Some(keys)),
+            Err(e) if is_undefined_table(&e) => Ok(Some(Vec::new())),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn count_keys(&self, column: &str, keys: &[String]) -> Result<Option<u64>> {
+        let col = Self::quote_ident(column);
+        let sql = format!(
+            "SELECT COUNT(DISTINCT {col}::text) FROM {} WHERE {col}::text = ANY($1)",
+            Self::quote_ident_path(&self.table_name)
+        );
+        match sqlx::query_scalar::<_, i64>(&sql)
+            .bind(keys)
+            .fetch_one(&self.pool)
+            .await
+        {
+            Ok(count) => Ok(Some(count as u64)),
+            Err(e) if is_undefined_table(&e) => Ok(Some(0)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn duplicate_keys(&self, columns: &[String]) -> Result<Option<DuplicateKeys>> {
+        let cols: Vec<String> = columns.iter().map(|c| Self::quote_ident(c)).collect();
+        let sql = format!(
+            "SELECT COUNT(*), COALESCE(SUM(n - 1), 0)::bigint FROM \
+             (SELECT COUNT(*) AS n FROM {} WHERE {} GROUP BY {} HAVING COUNT(*) > 1) d",
+            Self::quote_ident_path(&self.table_name),
+            cols.iter()
+                .map(|c| format!("{c} IS NOT NULL"))
+                .collect::<Vec<_>>()
+                .join(" AND "),
+            cols.join(", ")
+        );
+        match sqlx::query_as::<_, (i64, i64)>(&sql)
+            .fetch_one(&self.pool)
+            .await
+        {
+            Ok((keys, extra_rows)) => Ok(Some(DuplicateKeys {
+                keys: keys as u64,
+                extra_rows: extra_rows as u64,
+            })),
+            Err(e) if is_undefined_table(&e) => Ok(Some(DuplicateKeys::default())),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     async fn begin(&self) -> Result<()> {
         sqlx::query("BEGIN").execute(&self.pool).await?;
         Ok(())
@@ -932,3 +1641,32 @@ impl DataWriter for PostgresWriter {
         Ok(())
     }
 }
+
+/// A statement cancelled by the target's `statement_timeout` (`57014`) or
+/// `lock_timeout` (`55P03`), as a writer error naming the table and the
+/// setting; other errors are returned as they are.
+pub fn explain_timeout(e: ApitapError, table: &str) -> ApitapError {
+    let ApitapError::Sqlx(sqlx_err) = &e else {
+        return e;
+    };
+    let Some(db_err) = sqlx_err.as_database_error() else {
+        return e;
+    };
+    let setting = match db_err.code().as_deref() {
+        Some("57014") => "statement_timeout",
+        Some("55P03") => "lock_timeout",
+        _ => return e,
+    };
+    ApitapError::WriterError(format!(
+        "Postgres: writing to {table} was cancelled by the target's {setting} ({}); \
+         raise connection.{setting} or check for sessions holding the table",
+        db_err.message()
+    ))
+}
+
+/// `undefined_table`: the destination has not been created yet.
+fn is_undefined_table(e: &sqlx::Error) -> bool {
+    e.as_database_error()
+        .and_then(|db_err| db_err.code())
+        .is_some_and(|code| code == "42P01")
+}
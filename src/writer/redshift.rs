@@ -0,0 +1,408 @@
+// src/writer/redshift.rs
+
+use crate::errors::{ApitapError, Result};
+use crate::utils::datafusion_ext::{QueryResult, QueryResultStream};
+use crate::writer::postgres::{PgType, PostgresWriter};
+use crate::writer::{DataWriter, WriteMode};
+use async_trait::async_trait;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use object_store::path::Path as ObjectPath;
+use object_store::{ObjectStore, PutPayload};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio_stream::StreamExt;
+use tracing::{debug, debug_span, info, warn};
+
+//=============== Type Definitions ============================================//
+
+/// What happens to staged S3 objects once their batch has been loaded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StagingCleanup {
+    /// Delete after every batch, loaded or not.
+    Always,
+    /// Delete after a successful load; keep failed batches for inspection.
+    #[default]
+    OnSuccess,
+    /// Keep every staged object.
+    Never,
+}
+
+impl StagingCleanup {
+    pub fn should_delete(self, loaded: bool) -> bool {
+        match self {
+            StagingCleanup::Always => true,
+            StagingCleanup::OnSuccess => loaded,
+            StagingCleanup::Never => false,
+        }
+    }
+}
+
+/// Where batches are staged before `COPY`.
+#[derive(Debug, Clone)]
+pub struct RedshiftStaging {
+    pub store: Arc<dyn ObjectStore>,
+    /// Bucket name as referenced in `COPY ... FROM 's3://<bucket>/...'`.
+    pub bucket: String,
+    pub prefix: ObjectPath,
+    pub iam_role: String,
+    pub region: Option<String>,
+    pub cleanup: StagingCleanup,
+}
+
+//=============== Redshift Writer =============================================//
+
+/// Loads query results into Amazon Redshift through S3.
+///
+/// Multi-row `INSERT`s are slow on Redshift, so each batch is written to S3 as
+/// gzip-compressed NDJSON and loaded with `COPY ... FORMAT AS JSON`. Append
+/// copies straight into the destination; merge copies into a session-scoped
+/// temporary table and then runs `MERGE` on the primary key. Staged objects
+/// are removed according to the [`StagingCleanup`] policy.
+pub struct RedshiftWriter {
+    pool: PgPool,
+    staging: RedshiftStaging,
+    pub table_name: String,
+    pub batch_size: usize,
+    pub sample_size: usize,
+    pub auto_create: bool,
+    pub primary_key: Option<String>,
+    columns_cache: tokio::sync::RwLock<Option<BTreeMap<String, PgType>>>,
+    batch_seq: AtomicUsize,
+}
+
+impl RedshiftWriter {
+    pub fn new(pool: PgPool, staging: RedshiftStaging, table_name: impl Into<String>) -> Self {
+        Self {
+            pool,
+            staging,
+            table_name: table_name.into(),
+            batch_size: 50_000,
+            sample_size: 10,
+            auto_create: true,
+            primary_key: None,
+            columns_cache: tokio::sync::RwLock::new(None),
+            batch_seq: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn with_primary_key_single(mut self, name: impl Into<Option<String>>) -> Self {
+        self.primary_key = name.into();
+        self
+    }
+
+    pub fn with_batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size.max(1);
+        self
+    }
+
+    pub fn with_sample_size(mut self, size: usize) -> Self {
+        self.sample_size = size;
+        self
+    }
+
+    pub fn auto_create(mut self, enabled: bool) -> Self {
+        self.auto_create = enabled;
+        self
+    }
+
+    pub fn redshift_type(pg_type: &PgType) -> &'static str {
+        match pg_type {
+            PgType::Text => "VARCHAR(65535)",
+            PgType::Boolean => "BOOLEAN",
+            PgType::BigInt => "BIGINT",
+            PgType::Double => "DOUBLE PRECISION",
+            PgType::Jsonb => "SUPER",
+            PgType::Bytea => "VARBYTE",
+        }
+    }
+
+    pub fn stage_table(&self) -> String {
+        let base = self
+            .table_name
+            .rsplit('.')
+            .next()
+            .unwrap_or(&self.table_name);
+        PostgresWriter::quote_ident(&format!("{base}_apitap_stage"))
+    }
+
+    pub fn create_table_sql(&self, schema: &BTreeMap<String, PgType>) -> String {
+        let mut parts: Vec<String> = schema
+            .iter()
+            .map(|(name, ty)| {
+                format!(
+                    "{} {}",
+                    PostgresWriter::quote_ident(name),
+                    Self::redshift_type(ty)
+                )
+            })
+            .collect();
+        if let Some(pk) = &self.primary_key {
+            if schema.contains_key(pk) {
+                parts.push(format!("PRIMARY KEY ({})", PostgresWriter::quote_ident(pk)));
+            }
+        }
+        format!(
+            "CREATE TABLE IF NOT EXISTS {} (\n    {}\n)",
+            PostgresWriter::quote_ident_path(&self.table_name),
+            parts.join(",\n    ")
+        )
+    }
+
+    /// `COPY` of one staged object into `table` (already quoted).
+    pub fn copy_sql(
+        &self,
+        table: &str,
+        schema: &BTreeMap<String, PgType>,
+        object: &ObjectPath,
+    ) -> String {
+        let cols: Vec<String> = schema
+            .keys()
+            .map(|c| PostgresWriter::quote_ident(c))
+            .collect();
+        let mut sql = format!(
+            "COPY {table} ({}) FROM 's3://{}/{}' IAM_ROLE '{}' FORMAT AS JSON 'auto ignorecase' GZIP TIMEFORMAT 'auto'",
+            cols.join(", "),
+            self.staging.bucket,
+            object,
+            self.staging.iam_role.replace('\'', "''"),
+        );
+        if let Some(region) = &self.staging.region {
+            sql.push_str(&format!(" REGION '{}'", region.replace('\'', "''")));
+        }
+        sql
+    }
+
+    pub fn merge_sql(&self, schema: &BTreeMap<String, PgType>) -> Result<String> {
+        let pk = self.primary_key.as_ref().ok_or_else(|| {
+            ApitapError::MergeError("Redshift: primary key not configured".to_string())
+        })?;
+        let pk_q = PostgresWriter::quote_ident(pk);
+        let cols: Vec<String> = schema
+            .keys()
+            .map(|c| PostgresWriter::quote_ident(c))
+            .collect();
+        let sets: Vec<String> = schema
+            .keys()
+            .filter(|c| *c != pk)
+            .map(|c| {
+                let q = PostgresWriter::quote_ident(c);
+                format!("{q} = s.{q}")
+            })
+            .collect();
+
+        let mut sql = format!(
+            "MERGE INTO {} USING {} AS s\nON {}.{pk_q} = s.{pk_q}\n",
+            PostgresWriter::quote_ident_path(&self.table_name),
+            self.stage_table(),
+            PostgresWriter::quote_ident_path(&self.table_name),
+        );
+        // Redshift requires a WHEN MATCHED clause; a no-op update keeps PK-only tables valid.
+        let sets = if sets.is_empty() {
+            format!("{pk_q} = s.{pk_q}")
+        } else {
+            sets.join(", ")
+        };
+        sql.push_str(&format!("WHEN MATCHED THEN UPDATE SET {sets}\n"));
+        let values: Vec<String> = cols.iter().map(|c| format!("s.{c}")).collect();
+        sql.push_str(&format!(
+            "WHEN NOT MATCHED THEN INSERT ({}) VALUES ({})",
+            cols.join(", "),
+            values.join(", ")
+        ));
+        Ok(sql)
+    }
+
+    /// Gzip-compressed NDJSON for a batch.
+    pub fn encode_batch(rows: &[Value]) -> Result<Vec<u8>> {
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        for row in rows {
+            serde_json::to_writer(&mut gz, row)?;
+            gz.write_all(b"\n")?;
+        }
+        Ok(gz.finish()?)
+    }
+
+    /// Upload a batch to the staging location; returns the object path.
+    pub async fn stage_batch(&self, rows: &[Value]) -> Result<ObjectPath> {
+        let alphabet: [char; 16] = [
+            '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'a', 'b', 'c', 'd', 'e', 'f',
+        ];
+        let seq = self.batch_seq.fetch_add(1, Ordering::Relaxed);
+        let path = self
+            .table_name
+            .split('.')
+            .fold(self.staging.prefix.clone(), |p, part| p.child(part))
+            .child(format!(
+                "batch-{}-{}-{:05}.json.gz",
+                chrono::Utc::now().format("%Y%m%dT%H%M%S"),
+                nanoid::nanoid!(8, &alphabet),
+                seq
+            ));
+
+        let body = Self::encode_batch(rows)?;
+        debug!(path = %path, bytes = body.len(), rows = rows.len(), "staging batch");
+        self.staging
+            .store
+            .put(&path, PutPayload::from(body))
+            .await?;
+        Ok(path)
+    }
+
+    async fn ensure_table(&self, sample_rows: &[Value]) -> Result<BTreeMap<String, PgType>> {
+        if let Some(schema) = self.columns_cache.read().await.as_ref() {
+            return Ok(schema.clone());
+        }
+        if sample_rows.is_empty() {
+            return Err(ApitapError::PipelineError(
+                "Need sample data to create table".to_string(),
+            ));
+        }
+
+        let schema = PostgresWriter::analyze_schema(sample_rows, self.sample_size)?;
+        if self.auto_create {
+            let sql = self.create_table_sql(&schema);
+            debug!(%sql, "create table sql");
+            sqlx::query(&sql).execute(&self.pool).await?;
+        }
+
+        *self.columns_cache.write().await = Some(schema.clone());
+        Ok(schema)
+    }
+
+    async fn load_object(
+        &self,
+        object: &ObjectPath,
+        schema: &BTreeMap<String, PgType>,
+        write_mode: &WriteMode,
+    ) -> Result<()> {
+        let span = debug_span!("sql.execute", statement = "copy", table = %self.table_name);
+        let _g = span.enter();
+
+        match write_mode {
+            WriteMode::Append => {
+                let sql = self.copy_sql(
+                    &PostgresWriter::quote_ident_path(&self.table_name),
+                    schema,
+                    object,
+                );
+                sqlx::query(&sql).execute(&self.pool).await?;
+            }
+            WriteMode::Merge => {
+                let merge = self.merge_sql(schema)?;
+                // Temporary tables are per session: keep the whole sequence on one connection.
+                let mut conn = self.pool.acquire().await?;
+                sqlx::query(&format!(
+                    "CREATE TEMP TABLE IF NOT EXISTS {} (LIKE {})",
+                    self.stage_table(),
+                    PostgresWriter::quote_ident_path(&self.table_name)
+                ))
+                .execute(&mut *conn)
+                .await?;
+                sqlx::query(&format!("TRUNCATE {}", self.stage_table()))
+                    .execute(&mut *conn)
+                    .await?;
+                sqlx::query(&self.copy_sql(&self.stage_table(), schema, object))
+                    .execute(&mut *conn)
+                    .await?;
+                sqlx::query(&merge).execute(&mut *conn).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn write_batch(
+        &self,
+        rows: &[Value],
+        schema: &BTreeMap<String, PgType>,
+        write_mode: &WriteMode,
+    ) -> Result<()> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let object = self.stage_batch(rows).await?;
+        let loaded = self.load_object(&object, schema, write_mode).await;
+
+        if self.staging.cleanup.should_delete(loaded.is_ok()) {
+            if let Err(e) = self.staging.store.delete(&object).await {
+                warn!(path = %object, error = %e, "could not delete staged batch");
+            }
+        }
+        loaded?;
+        debug!(table = %self.table_name, rows = rows.len(), "redshift batch loaded");
+        Ok(())
+    }
+
+    pub async fn truncate(&self) -> Result<()> {
+        info!(table = %self.table_name, "truncating table");
+        let sql = format!(
+            "TRUNCATE {}",
+            PostgresWriter::quote_ident_path(&self.table_name)
+        );
+        if let Err(e) = sqlx::query(&sql).execute(&self.pool).await {
+            let missing = e
+                .as_database_error()
+                .and_then(|d| d.code())
+                .is_some_and(|c| c == "42P01");
+            if !missing {
+                return Err(e.into());
+            }
+            tracing::error!(table = %self.table_name, "table does not exist, skipping truncate");
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DataWriter for RedshiftWriter {
+    async fn write_stream(
+        &self,
+        mut result: QueryResultStream,
+        write_mode: WriteMode,
+    ) -> Result<()> {
+        let mut buf: Vec<Value> = Vec::with_capacity(self.batch_size.min(8192));
+        let mut schema: Option<BTreeMap<String, PgType>> = None;
+
+        while let Some(item) = result.data.next().await {
+            buf.push(item?);
+            if buf.len() >= self.batch_size {
+                if schema.is_none() {
+                    schema = Some(self.ensure_table(&buf).await?);
+                }
+                let schema_ref = schema.as_ref().expect("schema just set");
+                self.write_batch(&buf, schema_ref, &write_mode).await?;
+                buf.clear();
+            }
+        }
+
+        if !buf.is_empty() {
+            if schema.is_none() {
+                schema = Some(self.ensure_table(&buf).await?);
+            }
+            let schema_ref = schema.as_ref().expect("schema just set");
+            self.write_batch(&buf, schema_ref, &write_mode).await?;
+        }
+        Ok(())
+    }
+
+    async fn write(&self, result: QueryResult) -> Result<()> {
+        let rows = result
+            .data
+            .as_array()
+            .ok_or_else(|| ApitapError::PipelineError("Expected JSON array".to_string()))?;
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let schema = self.ensure_table(rows).await?;
+        for chunk in rows.chunks(self.batch_size) {
+            self.write_batch(chunk, &schema, &WriteMode::Append).await?;
+        }
+        Ok(())
+    }
+}
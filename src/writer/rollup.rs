@@ -0,0 +1,161 @@
+//! Aggregate companion tables computed from the rows a module loaded.
+//!
+//! A source lists `rollups:`; each is a GROUP BY query over the module's
+//! row-level destination table, e.g. daily totals next to the raw orders. A
+//! [`RollupCollector`] sits in the row-level writer's middleware chain and
+//! keeps every batch it sees; once the fetch is done the rollup queries run
+//! over those batches and their results go to the companion tables.
+//!
+//! Rollups cover the rows loaded in the current run only.
+
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use datafusion::arrow::array::{new_null_array, ArrayRef};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::{Field, Schema, SchemaRef};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::datasource::MemTable;
+use datafusion::prelude::SessionContext;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::errors::{ApitapError, Result};
+use crate::writer::middleware::WriterMiddleware;
+use crate::writer::{DataWriter, WriteMode};
+
+/// YAML options for one companion table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollupConfig {
+    /// Destination table for the aggregates.
+    pub table: String,
+    /// Query over the module's destination table, e.g.
+    /// `SELECT date_trunc('day', created_at) AS day, count(*) AS orders FROM orders GROUP BY 1`.
+    pub sql: String,
+    /// Target to write to; defaults to the module's sink.
+    #[serde(default)]
+    pub sink: Option<String>,
+    /// Upsert on this column instead of appending.
+    #[serde(default)]
+    pub primary_key: Option<String>,
+}
+
+impl RollupConfig {
+    pub fn write_mode(&self) -> WriteMode {
+        match self.primary_key {
+            Some(_) => WriteMode::Merge,
+            None => WriteMode::Append,
+        }
+    }
+}
+
+/// A rollup query bound to the writer of its companion table.
+pub struct Rollup {
+    pub table: String,
+    pub sql: String,
+    pub writer: Arc<dyn DataWriter>,
+    pub write_mode: WriteMode,
+}
+
+/// Middleware that keeps the batches written to a module's row-level table.
+pub struct RollupCollector {
+    table: String,
+    batches: Mutex<Vec<RecordBatch>>,
+}
+
+impl RollupCollector {
+    /// `table` is the name rollup queries select from.
+    pub fn new(table: impl Into<String>) -> Self {
+        Self {
+            table: table.into(),
+            batches: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.batches
+            .lock()
+            .map(|b| b.iter().map(RecordBatch::num_rows).sum())
+            .unwrap_or_default()
+    }
+
+    /// Run every rollup over the collected rows, write and commit each
+    /// companion table, and release the batches. Nothing is written when no
+    /// rows were collected.
+    pub async fn write_rollups(&self, rollups: &[Rollup]) -> Result<()> {
+        let batches = std::mem::take(
+            &mut *self
+                .batches
+                .lock()
+                .map_err(|e| ApitapError::PoisonError(e.to_string()))?,
+        );
+        if batches.is_empty() || rollups.is_empty() {
+            return Ok(());
+        }
+
+        let (schema, batches) = unify(batches)?;
+        // A private context, so the queries can use the real table name.
+        let ctx = SessionContext::new();
+        ctx.register_table(
+            self.table.as_str(),
+            Arc::new(MemTable::try_new(schema, vec![batches])?),
+        )?;
+
+        for rollup in rollups {
+            let stream = ctx.sql(&rollup.sql).await?.execute_stream().await?;
+            rollup
+                .writer
+                .write_batches(&rollup.table, stream, rollup.write_mode.clone())
+                .await?;
+            rollup.writer.commit().await?;
+            info!(source_table = %self.table, table = %rollup.table, "rollup written");
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl WriterMiddleware for RollupCollector {
+    fn name(&self) -> &'static str {
+        "rollup_collector"
+    }
+
+    fn map_batch(&self, _table: &str, batch: RecordBatch) -> Result<RecordBatch> {
+        if batch.num_rows() > 0 {
+            self.batches
+                .lock()
+                .map_err(|e| ApitapError::PoisonError(e.to_string()))?
+                .push(batch.clone());
+        }
+        Ok(batch)
+    }
+}
+
+/// Pages infer their schemas separately; align every batch on the merged one.
+fn unify(batches: Vec<RecordBatch>) -> Result<(SchemaRef, Vec<RecordBatch>)> {
+    let merged = Schema::try_merge(batches.iter().map(|b| b.schema().as_ref().clone()))?;
+    let schema: SchemaRef = Arc::new(Schema::new(
+        merged
+            .fields()
+            .iter()
+            .map(|f| Field::new(f.name(), f.data_type().clone(), true))
+            .collect::<Vec<_>>(),
+    ));
+
+    let batches = batches
+        .into_iter()
+        .map(|batch| {
+            let columns = schema
+                .fields()
+                .iter()
+                .map(|field| match batch.column_by_name(field.name()) {
+                    Some(col) if col.data_type() == field.data_type() => Ok(Arc::clone(col)),
+                    Some(col) => cast(col, field.data_type()),
+                    None => Ok(new_null_array(field.data_type(), batch.num_rows())),
+                })
+                .collect::<std::result::Result<Vec<ArrayRef>, _>>()?;
+            Ok(RecordBatch::try_new(Arc::clone(&schema), columns)?)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok((schema, batches))
+}
@@ -0,0 +1,864 @@
+// src/writer/snowflake.rs
+
+use crate::errors::{ApitapError, Result};
+use crate::utils::datafusion_ext::{QueryResult, QueryResultStream};
+use crate::writer::postgres::{PgType, PostgresWriter};
+use crate::writer::redshift::{RedshiftWriter, StagingCleanup};
+use crate::writer::{DataWriter, WriteMode, ROW_HASH_COLUMN};
+use async_trait::async_trait;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use object_store::path::Path as ObjectPath;
+use object_store::{ObjectStore, PutPayload};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio_stream::StreamExt;
+use tracing::{debug, debug_span, info, warn};
+
+/// Snowflake error code returned when the session token has expired.
+const SESSION_EXPIRED_CODE: &str = "390112";
+
+/// Key-pair JWTs may live at most one hour; keep a safety margin.
+const JWT_LIFETIME_SECS: i64 = 59 * 60;
+
+//=============== Session =====================================================//
+
+#[derive(Clone)]
+pub enum SnowflakeCredentials {
+    Password(String),
+    /// PKCS#8 PEM private key plus the `SHA256:...` fingerprint of the public key
+    /// registered on the user (see `DESC USER <name>` → `RSA_PUBLIC_KEY_FP`).
+    KeyPair {
+        private_key_pem: String,
+        public_key_fp: String,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct SnowflakeConnectOptions {
+    pub account: String,
+    pub user: String,
+    pub warehouse: Option<String>,
+    pub database: String,
+    pub schema: String,
+    pub role: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct JwtClaims {
+    iss: String,
+    sub: String,
+    iat: i64,
+    exp: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct SnowflakeResponse {
+    #[serde(default)]
+    success: bool,
+    #[serde(default)]
+    code: Option<String>,
+    #[serde(default)]
+    message: Option<String>,
+    #[serde(default)]
+    data: Value,
+}
+
+/// Tokens from a login. The master token renews an expired session token
+/// without starting a new session.
+#[derive(Clone)]
+struct SessionTokens {
+    session: String,
+    master: Option<String>,
+}
+
+/// A Snowflake session.
+///
+/// Uses the REST session endpoints (`/session/v1/login-request` and
+/// `/queries/v1/query-request`) which accept both password and key-pair
+/// (`SNOWFLAKE_JWT`) authentication. An expired session token is renewed
+/// with the master token, keeping the session; when that fails too, a new
+/// session is logged into and the statements registered with
+/// [`execute_setup`](Self::execute_setup) are run again on it.
+pub struct SnowflakeSession {
+    client: reqwest::Client,
+    base_url: String,
+    options: SnowflakeConnectOptions,
+    credentials: SnowflakeCredentials,
+    tokens: tokio::sync::RwLock<Option<SessionTokens>>,
+    sequence: AtomicU64,
+    setup: Mutex<Vec<String>>,
+    in_transaction: AtomicBool,
+    forks: Mutex<Vec<Arc<SnowflakeSession>>>,
+}
+
+impl std::fmt::Debug for SnowflakeSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SnowflakeSession")
+            .field("base_url", &self.base_url)
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+impl SnowflakeSession {
+    /// Build a session without logging in; the first statement triggers login.
+    pub fn new(options: SnowflakeConnectOptions, credentials: SnowflakeCredentials) -> Self {
+        let base_url = format!(
+            "https://{}.snowflakecomputing.com",
+            options.account.to_lowercase()
+        );
+        Self {
+            client: reqwest::Client::new(),
+            base_url,
+            options,
+            credentials,
+            tokens: tokio::sync::RwLock::new(None),
+            sequence: AtomicU64::new(0),
+            setup: Mutex::default(),
+            in_transaction: AtomicBool::new(false),
+            forks: Mutex::default(),
+        }
+    }
+
+    /// A new session for the same user, logged in on its first statement.
+    /// Transactions and temporary tables belong to a session, so each writer
+    /// takes its own; [`close`](Self::close) logs the forks out as well.
+    pub fn fork(&self) -> Arc<Self> {
+        let fork = Arc::new(Self::new(self.options.clone(), self.credentials.clone()));
+        self.forks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Arc::clone(&fork));
+        fork
+    }
+
+    /// Build a session and log in immediately so bad credentials fail fast.
+    pub async fn connect(
+        options: SnowflakeConnectOptions,
+        credentials: SnowflakeCredentials,
+    ) -> Result<Self> {
+        let session = Self::new(options, credentials);
+        session.login().await?;
+        Ok(session)
+    }
+
+    /// Account name as expected in JWT claims: the locator without region or
+    /// cloud suffix, upper-cased (`xy12345.us-east-1` → `XY12345`).
+    pub fn jwt_account_name(account: &str) -> String {
+        account.split('.').next().unwrap_or(account).to_uppercase()
+    }
+
+    fn key_pair_jwt(&self, private_key_pem: &str, public_key_fp: &str) -> Result<String> {
+        let account = Self::jwt_account_name(&self.options.account);
+        let user = self.options.user.to_uppercase();
+        let fp = if public_key_fp.starts_with("SHA256:") {
+            public_key_fp.to_string()
+        } else {
+            format!("SHA256:{public_key_fp}")
+        };
+        let now = chrono::Utc::now().timestamp();
+        let claims = JwtClaims {
+            iss: format!("{account}.{user}.{fp}"),
+            sub: format!("{account}.{user}"),
+            iat: now,
+            exp: now + JWT_LIFETIME_SECS,
+        };
+        let key = EncodingKey::from_rsa_pem(private_key_pem.as_bytes())
+            .map_err(|e| ApitapError::ConfigError(format!("invalid snowflake private key: {e}")))?;
+        jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &key)
+            .map_err(|e| ApitapError::WriterError(format!("snowflake JWT signing failed: {e}")))
+    }
+
+    async fn login(&self) -> Result<String> {
+        let mut data = json!({
+            "CLIENT_APP_ID": "apitap",
+            "CLIENT_APP_VERSION": env!("CARGO_PKG_VERSION"),
+            "ACCOUNT_NAME": Self::jwt_account_name(&self.options.account),
+            "LOGIN_NAME": self.options.user,
+        });
+        match &self.credentials {
+            SnowflakeCredentials::Password(password) => {
+                data["PASSWORD"] = Value::String(password.clone());
+            }
+            SnowflakeCredentials::KeyPair {
+                private_key_pem,
+                public_key_fp,
+            } => {
+                data["AUTHENTICATOR"] = Value::String("SNOWFLAKE_JWT".into());
+                data["TOKEN"] = Value::String(self.key_pair_jwt(private_key_pem, public_key_fp)?);
+            }
+        }
+
+        let mut query: Vec<(&str, &str)> = vec![
+            ("databaseName", self.options.database.as_str()),
+            ("schemaName", self.options.schema.as_str()),
+        ];
+        if let Some(wh) = &self.options.warehouse {
+            query.push(("warehouse", wh.as_str()));
+        }
+        if let Some(role) = &self.options.role {
+            query.push(("roleName", role.as_str()));
+        }
+
+        let resp: SnowflakeResponse = self
+            .client
+            .post(format!("{}/session/v1/login-request", self.base_url))
+            .query(&query)
+            .json(&json!({ "data": data }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        if !resp.success {
+            return Err(ApitapError::WriterError(format!(
+                "snowflake login failed: {}",
+                resp.message.unwrap_or_default()
+            )));
+        }
+        let token = resp
+            .data
+            .get("token")
+            .and_then(|t| t.as_str())
+            .ok_or_else(|| {
+                ApitapError::WriterError("snowflake login response missing token".into())
+            })?
+            .to_string();
+        let master = resp
+            .data
+            .get("masterToken")
+            .and_then(|t| t.as_str())
+            .map(String::from);
+
+        info!(account = %self.options.account, user = %self.options.user, "snowflake session established");
+        *self.tokens.write().await = Some(SessionTokens {
+            session: token.clone(),
+            master,
+        });
+
+        // A new session has none of the temporary tables of the last one.
+        let setup = self.setup.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        for sql in &setup {
+            Self::check(self.send_query(&token, sql, &Value::Null).await?)?;
+        }
+        Ok(token)
+    }
+
+    /// Trade the master token for a new session token; the session, with its
+    /// open transaction and temporary tables, carries on.
+    async fn renew(&self, expired: &str) -> Result<String> {
+        let master = self
+            .tokens
+            .read()
+            .await
+            .as_ref()
+            .and_then(|t| t.master.clone())
+            .ok_or_else(|| ApitapError::WriterError("no snowflake master token".into()))?;
+        let resp: SnowflakeResponse = self
+            .client
+            .post(format!("{}/session/token-request", self.base_url))
+            .query(&[("requestId", request_id().as_str())])
+            .header(
+                reqwest::header::AUTHORIZATION,
+                format!("Snowflake Token=\"{master}\""),
+            )
+            .header(reqwest::header::ACCEPT, "application/snowflake")
+            .json(&json!({ "oldSessionToken": expired, "requestType": "RENEW" }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let data = Self::check(resp)?;
+        let token = data
+            .get("sessionToken")
+            .and_then(|t| t.as_str())
+            .ok_or_else(|| {
+                ApitapError::WriterError("snowflake renewal response missing sessionToken".into())
+            })?
+            .to_string();
+        if let Some(tokens) = self.tokens.write().await.as_mut() {
+            tokens.session = token.clone();
+        }
+        debug!("snowflake session token renewed");
+        Ok(token)
+    }
+
+    fn check(resp: SnowflakeResponse) -> Result<Value> {
+        if !resp.success {
+            return Err(ApitapError::WriterError(format!(
+                "snowflake statement failed ({}): {}",
+                resp.code.unwrap_or_default(),
+                resp.message.unwrap_or_default()
+            )));
+        }
+        Ok(resp.data)
+    }
+
+    async fn send_query(
+        &self,
+        token: &str,
+        sql: &str,
+        bindings: &Value,
+    ) -> Result<SnowflakeResponse> {
+        let request_id = request_id();
+        let mut body = json!({
+            "sqlText": sql,
+            "sequenceId": self.sequence.fetch_add(1, Ordering::Relaxed) + 1,
+            "asyncExec": false,
+        });
+        if !bindings.is_null() {
+            body["bindings"] = bindings.clone();
+        }
+
+        let resp = self
+            .client
+            .post(format!("{}/queries/v1/query-request", self.base_url))
+            .query(&[("requestId", request_id.as_str())])
+            .header(
+                reqwest::header::AUTHORIZATION,
+                format!("Snowflake Token=\"{token}\""),
+            )
+            .header(reqwest::header::ACCEPT, "application/snowflake")
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(resp)
+    }
+
+    /// Execute a single statement. `bindings` follows the Snowflake wire format
+    /// (`{"1": {"type": "TEXT", "value": [...]}}`); pass `Value::Null` for none.
+    pub async fn execute(&self, sql: &str, bindings: Value) -> Result<Value> {
+        let current = self.tokens.read().await.as_ref().map(|t| t.session.clone());
+        let token = match current {
+            Some(t) => t,
+            None => self.login().await?,
+        };
+
+        let span = debug_span!("sql.execute", sink = "snowflake");
+        let _g = span.enter();
+
+        let mut resp = self.send_query(&token, sql, &bindings).await?;
+        if !resp.success && resp.code.as_deref() == Some(SESSION_EXPIRED_CODE) {
+            let token = match self.renew(&token).await {
+                Ok(token) => token,
+                Err(e) => {
+                    warn!(error = %e, "snowflake session expired; logging in again");
+                    let token = self.login().await?;
+                    // The transaction ended with the old session.
+                    if self.in_transaction.swap(false, Ordering::SeqCst) {
+                        return Err(ApitapError::WriterError(
+                            "snowflake session expired inside a transaction; its statements were not committed".into(),
+                        ));
+                    }
+                    token
+                }
+            };
+            resp = self.send_query(&token, sql, &bindings).await?;
+        }
+        Self::check(resp)
+    }
+
+    /// Execute a DDL statement. Snowflake commits an open transaction before
+    /// DDL, so a new one is begun after it.
+    pub async fn execute_ddl(&self, sql: &str) -> Result<()> {
+        self.execute(sql, Value::Null).await?;
+        if self.in_transaction.load(Ordering::SeqCst) {
+            self.execute("BEGIN", Value::Null).await?;
+        }
+        Ok(())
+    }
+
+    /// Execute a DDL statement now and again after every later login, for
+    /// objects such as temporary tables that only live as long as a session.
+    pub async fn execute_setup(&self, sql: &str) -> Result<()> {
+        self.execute_ddl(sql).await?;
+        let mut setup = self.setup.lock().unwrap_or_else(|e| e.into_inner());
+        if !setup.iter().any(|s| s == sql) {
+            setup.push(sql.to_string());
+        }
+        Ok(())
+    }
+
+    pub async fn begin(&self) -> Result<()> {
+        self.execute("BEGIN", Value::Null).await?;
+        self.in_transaction.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    pub async fn commit(&self) -> Result<()> {
+        let done = self.execute("COMMIT", Value::Null).await;
+        self.in_transaction.store(false, Ordering::SeqCst);
+        done.map(|_| ())
+    }
+
+    pub async fn rollback(&self) -> Result<()> {
+        let done = self.execute("ROLLBACK", Value::Null).await;
+        self.in_transaction.store(false, Ordering::SeqCst);
+        done.map(|_| ())
+    }
+
+    /// Best-effort logout of the session and its forks so the server can
+    /// release them.
+    pub async fn close(&self) -> Result<()> {
+        let forks: Vec<_> = self
+            .forks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .drain(..)
+            .collect();
+        for fork in forks {
+            fork.logout().await;
+        }
+        self.logout().await;
+        Ok(())
+    }
+
+    async fn logout(&self) {
+        let Some(tokens) = self.tokens.write().await.take() else {
+            return;
+        };
+        let _ = self
+            .client
+            .post(format!("{}/session", self.base_url))
+            .query(&[("delete", "true")])
+            .header(
+                reqwest::header::AUTHORIZATION,
+                format!("Snowflake Token=\"{}\"", tokens.session),
+            )
+            .send()
+            .await;
+    }
+}
+
+/// A UUID-shaped id for the `requestId` query parameter.
+fn request_id() -> String {
+    let hex = nanoid::nanoid!(32, &HEX);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+const HEX: [char; 16] = [
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'a', 'b', 'c', 'd', 'e', 'f',
+];
+
+//=============== Snowflake Writer ============================================//
+
+/// Where batches are staged before `COPY INTO`: an external stage and the
+/// object store location it points at.
+#[derive(Debug, Clone)]
+pub struct SnowflakeStaging {
+    pub store: Arc<dyn ObjectStore>,
+    pub prefix: ObjectPath,
+    /// Stage name as referenced after `@`, e.g. `raw.apitap_stage`.
+    pub stage: String,
+    pub cleanup: StagingCleanup,
+}
+
+const FILE_FORMAT: &str = "FILE_FORMAT = (TYPE = JSON COMPRESSION = GZIP)";
+
+/// Writes query results into a Snowflake table.
+///
+/// Each batch is uploaded to the stage's location as gzip-compressed NDJSON
+/// and loaded with `COPY INTO`: straight into the destination, casting each
+/// field out of the staged JSON (append), or into a temporary table that is
+/// then `MERGE`d on the primary key (merge). The writer has its own session,
+/// temporary table and transaction, and runs its loads one at a time.
+/// Staged objects are removed according to the [`StagingCleanup`] policy.
+pub struct SnowflakeWriter {
+    session: Arc<SnowflakeSession>,
+    staging: SnowflakeStaging,
+    pub table_name: String,
+    pub batch_size: usize,
+    pub sample_size: usize,
+    pub auto_create: bool,
+    pub primary_key: Option<String>,
+    columns_cache: tokio::sync::RwLock<Option<BTreeMap<String, PgType>>>,
+    stage_table: String,
+    batch_seq: AtomicUsize,
+    load_lock: tokio::sync::Mutex<()>,
+}
+
+impl SnowflakeWriter {
+    pub fn new(
+        session: Arc<SnowflakeSession>,
+        staging: SnowflakeStaging,
+        table_name: impl Into<String>,
+    ) -> Self {
+        let table_name = table_name.into();
+        let base = table_name.rsplit('.').next().unwrap_or(&table_name);
+        let stage_table = PostgresWriter::quote_ident(&format!(
+            "{base}_apitap_stage_{}",
+            nanoid::nanoid!(8, &HEX)
+        ));
+        Self {
+            session,
+            staging,
+            table_name,
+            batch_size: 5000,
+            sample_size: 10,
+            auto_create: true,
+            primary_key: None,
+            columns_cache: tokio::sync::RwLock::new(None),
+            stage_table,
+            batch_seq: AtomicUsize::new(0),
+            load_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn with_primary_key_single(mut self, name: impl Into<Option<String>>) -> Self {
+        self.primary_key = name.into();
+        self
+    }
+
+    pub fn with_batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size.max(1);
+        self
+    }
+
+    pub fn with_sample_size(mut self, size: usize) -> Self {
+        self.sample_size = size;
+        self
+    }
+
+    pub fn auto_create(mut self, enabled: bool) -> Self {
+        self.auto_create = enabled;
+        self
+    }
+
+    pub fn sf_type(pg_type: &PgType) -> &'static str {
+        match pg_type {
+            PgType::Text => "VARCHAR",
+            PgType::Boolean => "BOOLEAN",
+            PgType::BigInt => "NUMBER(38,0)",
+            PgType::Double => "FLOAT",
+            PgType::Jsonb => "VARIANT",
+            PgType::Bytea => "BINARY",
+        }
+    }
+
+    /// Temporary table merges are staged in, unique to this writer.
+    pub fn stage_table(&self) -> &str {
+        &self.stage_table
+    }
+
+    pub fn create_table_sql(&self, schema: &BTreeMap<String, PgType>) -> String {
+        let mut parts: Vec<String> = schema
+            .iter()
+            .map(|(name, ty)| {
+                format!(
+                    "{} {}",
+                    PostgresWriter::quote_ident(name),
+                    Self::sf_type(ty)
+                )
+            })
+            .collect();
+        if let Some(pk) = &self.primary_key {
+            if schema.contains_key(pk) {
+                parts.push(format!("PRIMARY KEY ({})", PostgresWriter::quote_ident(pk)));
+            }
+        }
+        format!(
+            "CREATE TABLE IF NOT EXISTS {} (\n    {}\n)",
+            PostgresWriter::quote_ident_path(&self.table_name),
+            parts.join(",\n    ")
+        )
+    }
+
+    /// Typed columns cast out of the JSON value `source` (`$1` or `raw`).
+    fn typed_columns(schema: &BTreeMap<String, PgType>, source: &str) -> Vec<String> {
+        schema
+            .iter()
+            .map(|(name, ty)| {
+                let path = format!("{source}:{}", PostgresWriter::quote_ident(name));
+                match ty {
+                    PgType::Jsonb => path,
+                    other => format!("{path}::{}", Self::sf_type(other)),
+                }
+            })
+            .collect()
+    }
+
+    /// `@stage/<relative>` for a staged object.
+    pub fn stage_location(&self, relative: &ObjectPath) -> String {
+        format!(
+            "@{}/{}",
+            self.staging.stage.trim_start_matches('@'),
+            relative
+        )
+    }
+
+    /// `COPY INTO` the destination from one staged object (append).
+    pub fn copy_sql(&self, schema: &BTreeMap<String, PgType>, relative: &ObjectPath) -> String {
+        let cols: Vec<String> = schema
+            .keys()
+            .map(|c| PostgresWriter::quote_ident(c))
+            .collect();
+        format!(
+            "COPY INTO {} ({}) FROM (SELECT {} FROM {}) {FILE_FORMAT}",
+            PostgresWriter::quote_ident_path(&self.table_name),
+            cols.join(", "),
+            Self::typed_columns(schema, "$1").join(", "),
+            self.stage_location(relative)
+        )
+    }
+
+    /// `COPY INTO` the writer's temporary table from one staged object (merge).
+    pub fn copy_to_stage_table_sql(&self, relative: &ObjectPath) -> String {
+        format!(
+            "COPY INTO {} FROM {} {FILE_FORMAT}",
+            self.stage_table,
+            self.stage_location(relative)
+        )
+    }
+
+    pub fn merge_sql(&self, schema: &BTreeMap<String, PgType>) -> Result<String> {
+        let pk = self.primary_key.as_ref().ok_or_else(|| {
+            ApitapError::MergeError("Snowflake: primary key not configured".to_string())
+        })?;
+        let pk_q = PostgresWriter::quote_ident(pk);
+        let cols: Vec<String> = schema
+            .keys()
+            .map(|c| PostgresWriter::quote_ident(c))
+            .collect();
+        let non_pk: Vec<&String> = schema.keys().filter(|c| *c != pk).collect();
+        let select: Vec<String> = Self::typed_columns(schema, "raw")
+            .into_iter()
+            .zip(&cols)
+            .map(|(value, col)| format!("{value} AS {col}"))
+            .collect();
+
+        let mut sql = format!(
+            "MERGE INTO {} AS t\nUSING (SELECT {} FROM {}) AS s\nON t.{pk_q} = s.{pk_q}\n",
+            PostgresWriter::quote_ident_path(&self.table_name),
+            select.join(", "),
+            self.stage_table
+        );
+        if !non_pk.is_empty() {
+            let sets: Vec<String> = non_pk
+                .iter()
+                .map(|c| {
+                    let q = PostgresWriter::quote_ident(c);
+                    format!("t.{q} = s.{q}")
+                })
+                .collect();
+            // Skip matched rows whose `_row_hash` is unchanged.
+            let guard = if schema.contains_key(ROW_HASH_COLUMN) && pk != ROW_HASH_COLUMN {
+                let hash = PostgresWriter::quote_ident(ROW_HASH_COLUMN);
+                format!(" AND t.{hash} IS DISTINCT FROM s.{hash}")
+            } else {
+                String::new()
+            };
+            sql.push_str(&format!(
+                "WHEN MATCHED{guard} THEN UPDATE SET {}\n",
+                sets.join(", ")
+            ));
+        }
+        let values: Vec<String> = cols.iter().map(|c| format!("s.{c}")).collect();
+        sql.push_str(&format!(
+            "WHEN NOT MATCHED THEN INSERT ({}) VALUES ({})",
+            cols.join(", "),
+            values.join(", ")
+        ));
+        Ok(sql)
+    }
+
+    /// Upload a batch as gzip NDJSON; returns its path relative to the stage.
+    pub async fn stage_batch(&self, rows: &[Value]) -> Result<ObjectPath> {
+        let seq = self.batch_seq.fetch_add(1, Ordering::Relaxed);
+        let relative = self
+            .table_name
+            .split('.')
+            .fold(ObjectPath::default(), |p, part| p.child(part))
+            .child(format!(
+                "batch-{}-{}-{:05}.json.gz",
+                chrono::Utc::now().format("%Y%m%dT%H%M%S"),
+                nanoid::nanoid!(8, &HEX),
+                seq
+            ));
+
+        let body = RedshiftWriter::encode_batch(rows)?;
+        let path = self.object_path(&relative);
+        debug!(path = %path, bytes = body.len(), rows = rows.len(), "staging batch");
+        self.staging
+            .store
+            .put(&path, PutPayload::from(body))
+            .await?;
+        Ok(relative)
+    }
+
+    fn object_path(&self, relative: &ObjectPath) -> ObjectPath {
+        self.staging
+            .prefix
+            .parts()
+            .chain(relative.parts())
+            .collect()
+    }
+
+    async fn ensure_table(&self, sample_rows: &[Value]) -> Result<BTreeMap<String, PgType>> {
+        if let Some(schema) = self.columns_cache.read().await.as_ref() {
+            return Ok(schema.clone());
+        }
+        if sample_rows.is_empty() {
+            return Err(ApitapError::PipelineError(
+                "Need sample data to create table".to_string(),
+            ));
+        }
+
+        let schema = PostgresWriter::analyze_schema(sample_rows, self.sample_size)?;
+        if self.auto_create {
+            self.session
+                .execute_ddl(&self.create_table_sql(&schema))
+                .await?;
+        }
+        if self.primary_key.is_some() {
+            // Recreated whenever the session has to log in again.
+            self.session
+                .execute_setup(&format!(
+                    "CREATE TEMPORARY TABLE IF NOT EXISTS {} (raw VARIANT)",
+                    self.stage_table
+                ))
+                .await?;
+        }
+
+        *self.columns_cache.write().await = Some(schema.clone());
+        Ok(schema)
+    }
+
+    async fn load_staged(
+        &self,
+        relative: &ObjectPath,
+        schema: &BTreeMap<String, PgType>,
+        write_mode: &WriteMode,
+    ) -> Result<()> {
+        let _load = self.load_lock.lock().await;
+        match write_mode {
+            WriteMode::Append => {
+                self.session
+                    .execute(&self.copy_sql(schema, relative), Value::Null)
+                    .await?;
+            }
+            WriteMode::Merge => {
+                let merge = self.merge_sql(schema)?;
+                self.session
+                    .execute(&format!("DELETE FROM {}", self.stage_table), Value::Null)
+                    .await?;
+                self.session
+                    .execute(&self.copy_to_stage_table_sql(relative), Value::Null)
+                    .await?;
+                self.session.execute(&merge, Value::Null).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn write_batch(
+        &self,
+        rows: &[Value],
+        schema: &BTreeMap<String, PgType>,
+        write_mode: &WriteMode,
+    ) -> Result<()> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let relative = self.stage_batch(rows).await?;
+        let loaded = self.load_staged(&relative, schema, write_mode).await;
+
+        if self.staging.cleanup.should_delete(loaded.is_ok()) {
+            let path = self.object_path(&relative);
+            if let Err(e) = self.staging.store.delete(&path).await {
+                warn!(path = %path, error = %e, "could not delete staged batch");
+            }
+        }
+        loaded?;
+        debug!(table = %self.table_name, rows = rows.len(), "snowflake batch loaded");
+        Ok(())
+    }
+
+    pub async fn truncate(&self) -> Result<()> {
+        info!(table = %self.table_name, "truncating table");
+        self.session
+            .execute(
+                &format!(
+                    "TRUNCATE TABLE IF EXISTS {}",
+                    PostgresWriter::quote_ident_path(&self.table_name)
+                ),
+                Value::Null,
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DataWriter for SnowflakeWriter {
+    async fn write_stream(
+        &self,
+        mut result: QueryResultStream,
+        write_mode: WriteMode,
+    ) -> Result<()> {
+        let mut buf: Vec<Value> = Vec::with_capacity(self.batch_size);
+        let mut schema: Option<BTreeMap<String, PgType>> = None;
+
+        while let Some(item) = result.data.next().await {
+            buf.push(item?);
+            if buf.len() >= self.batch_size {
+                if schema.is_none() {
+                    schema = Some(self.ensure_table(&buf).await?);
+                }
+                let schema_ref = schema.as_ref().expect("schema just set");
+                self.write_batch(&buf, schema_ref, &write_mode).await?;
+                buf.clear();
+            }
+        }
+
+        if !buf.is_empty() {
+            if schema.is_none() {
+                schema = Some(self.ensure_table(&buf).await?);
+            }
+            let schema_ref = schema.as_ref().expect("schema just set");
+            self.write_batch(&buf, schema_ref, &write_mode).await?;
+        }
+        Ok(())
+    }
+
+    async fn write(&self, result: QueryResult) -> Result<()> {
+        let rows = result
+            .data
+            .as_array()
+            .ok_or_else(|| ApitapError::PipelineError("Expected JSON array".to_string()))?;
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let schema = self.ensure_table(rows).await?;
+        for chunk in rows.chunks(self.batch_size) {
+            self.write_batch(chunk, &schema, &WriteMode::Append).await?;
+        }
+        Ok(())
+    }
+
+    async fn begin(&self) -> Result<()> {
+        self.session.begin().await
+    }
+
+    async fn commit(&self) -> Result<()> {
+        self.session.commit().await
+    }
+
+    async fn rollback(&self) -> Result<()> {
+        self.session.rollback().await
+    }
+}
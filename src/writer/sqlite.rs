@@ -0,0 +1,628 @@
+// src/writer/sqlite.rs
+
+use crate::errors::{ApitapError, Result};
+use crate::pipeline::duplicates::DuplicateKeys;
+use crate::pipeline::freshness::parse_loaded_at;
+use crate::utils::datafusion_ext::{QueryResult, QueryResultStream};
+use crate::writer::key_inference::resolve_key;
+use crate::writer::metadata_columns::MetadataColumn;
+use crate::writer::postgres::{PgType, PostgresWriter};
+use crate::writer::{DataWriter, MergeStats, WriteMode, ROW_HASH_COLUMN};
+use async_trait::async_trait;
+use serde_json::Value;
+use sqlx::sqlite::{Sqlite, SqliteArguments};
+use sqlx::{Row, SqlitePool};
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+use tokio_stream::StreamExt;
+use tracing::{debug, debug_span, info};
+
+/// SQLite's default `SQLITE_MAX_VARIABLE_NUMBER` (3.32+).
+const MAX_BIND_PARAMS: usize = 32_766;
+
+type SqliteQuery<'q> = sqlx::query::Query<'q, Sqlite, SqliteArguments<'q>>;
+
+//=============== SQLite Writer ===============================================//
+
+/// Writes rows into a SQLite database file (or `:memory:`).
+///
+/// Tables are created from the inferred schema using SQLite's storage
+/// classes; booleans are stored as `INTEGER` and nested values as JSON
+/// `TEXT`. Merge mode upserts with `INSERT ... ON CONFLICT DO UPDATE`, which
+/// needs the primary key declared on the table (done when auto-created).
+/// SQLite has no schemas, so `public.users` is written to `users`.
+pub struct SqliteWriter {
+    pool: SqlitePool,
+    pub table_name: String,
+    pub batch_size: usize,
+    pub sample_size: usize,
+    pub auto_create: bool,
+    pub primary_key: Option<String>,
+    /// Guess the merge key from the first batch when `primary_key` is unset.
+    infer_primary_key: bool,
+    inferred_key: OnceLock<String>,
+    columns_cache: tokio::sync::RwLock<Option<BTreeMap<String, PgType>>>,
+    mutations: Option<Mutex<MergeStats>>,
+}
+
+impl SqliteWriter {
+    pub fn new(pool: SqlitePool, table_name: impl Into<String>) -> Self {
+        let table_name: String = table_name.into();
+        let table_name = table_name
+            .rsplit('.')
+            .next()
+            .unwrap_or(&table_name)
+            .to_string();
+        Self {
+            pool,
+            table_name,
+            batch_size: 5000,
+            sample_size: 10,
+            auto_create: true,
+            primary_key: None,
+            infer_primary_key: false,
+            inferred_key: OnceLock::new(),
+            columns_cache: tokio::sync::RwLock::new(None),
+            mutations: None,
+        }
+    }
+
+    pub fn with_primary_key_single(mut self, name: impl Into<Option<String>>) -> Self {
+        self.primary_key = name.into();
+        self
+    }
+
+    /// Merge on a key inferred from the first batch when none is configured;
+    /// without it such merges fail, naming the candidate keys.
+    pub fn with_infer_primary_key(mut self, enabled: bool) -> Self {
+        self.infer_primary_key = enabled;
+        self
+    }
+
+    /// The configured key, or the one inferred from the first batch.
+    pub fn key(&self) -> Option<&str> {
+        self.primary_key
+            .as_deref()
+            .or_else(|| self.inferred_key.get().map(String::as_str))
+    }
+
+    pub fn with_batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size.max(1);
+        self
+    }
+
+    pub fn with_sample_size(mut self, size: usize) -> Self {
+        self.sample_size = size;
+        self
+    }
+
+    pub fn auto_create(mut self, enabled: bool) -> Self {
+        self.auto_create = enabled;
+        self
+    }
+
+    /// Count inserted, changed and identical rows of every upsert; costs one
+    /// extra query per statement.
+    pub fn with_mutation_report(mut self, enabled: bool) -> Self {
+        self.mutations = enabled.then(|| Mutex::new(MergeStats::default()));
+        self
+    }
+
+    pub fn sqlite_type(pg_type: &PgType) -> &'static str {
+        match pg_type {
+            PgType::Text | PgType::Jsonb => "TEXT",
+            PgType::Boolean | PgType::BigInt => "INTEGER",
+            PgType::Double => "REAL",
+            PgType::Bytea => "BLOB",
+        }
+    }
+
+    fn table_sql(&self) -> String {
+        PostgresWriter::quote_ident(&self.table_name)
+    }
+
+    pub fn create_table_sql(&self, schema: &BTreeMap<String, PgType>) -> String {
+        let mut parts: Vec<String> = schema
+            .iter()
+            .map(|(name, ty)| {
+                format!(
+                    "{} {}",
+                    PostgresWriter::quote_ident(name),
+                    Self::sqlite_type(ty)
+                )
+            })
+            .collect();
+        if let Some(pk) = self.key() {
+            if schema.contains_key(pk) {
+                parts.push(format!("PRIMARY KEY ({})", PostgresWriter::quote_ident(pk)));
+            } else {
+                tracing::warn!(
+                    "Primary key '{}' not found in schema for table '{}'; creating without PK",
+                    pk,
+                    self.table_name
+                );
+            }
+        }
+        format!(
+            "CREATE TABLE IF NOT EXISTS {} (\n    {}\n)",
+            self.table_sql(),
+            parts.join(",\n    ")
+        )
+    }
+
+    /// `INSERT` for `rows` rows; with `upsert` an `ON CONFLICT` clause on the primary key.
+    pub fn insert_sql(
+        &self,
+        schema: &BTreeMap<String, PgType>,
+        rows: usize,
+        upsert: bool,
+    ) -> Result<String> {
+        let cols: Vec<String> = schema
+            .keys()
+            .map(|c| PostgresWriter::quote_ident(c))
+            .collect();
+        let row_ph = format!("({})", vec!["?"; cols.len()].join(", "));
+        let mut sql = format!(
+            "INSERT INTO {} ({}) VALUES {}",
+            self.table_sql(),
+            cols.join(", "),
+            vec![row_ph.as_str(); rows].join(", ")
+        );
+
+        if upsert {
+            let pk = self.key().ok_or_else(|| {
+                ApitapError::MergeError("SQLite: primary key not configured".to_string())
+            })?;
+            let sets: Vec<String> = schema
+                .keys()
+                .filter(|c| *c != pk)
+                .map(|c| {
+                    let q = PostgresWriter::quote_ident(c);
+                    format!("{q} = excluded.{q}")
+                })
+                .collect();
+            let pk_q = PostgresWriter::quote_ident(pk);
+            if sets.is_empty() {
+                sql.push_str(&format!(" ON CONFLICT ({pk_q}) DO NOTHING"));
+            } else {
+                sql.push_str(&format!(
+                    " ON CONFLICT ({pk_q}) DO UPDATE SET {}",
+                    sets.join(", ")
+                ));
+                // Leave rows whose `_row_hash` is unchanged untouched.
+                if schema.contains_key(ROW_HASH_COLUMN) && pk != ROW_HASH_COLUMN {
+                    let hash = PostgresWriter::quote_ident(ROW_HASH_COLUMN);
+                    sql.push_str(&format!(" WHERE {hash} IS NOT excluded.{hash}"));
+                }
+            }
+        }
+        Ok(sql)
+    }
+
+    /// Query counting, for `rows` incoming rows, the keys already in the table
+    /// and those among them whose stored values differ in a written column.
+    pub fn mutation_count_sql(
+        &self,
+        schema: &BTreeMap<String, PgType>,
+        rows: usize,
+    ) -> Result<String> {
+        let pk = self.key().ok_or_else(|| {
+            ApitapError::MergeError("SQLite: primary key not configured".to_string())
+        })?;
+        let cols: Vec<String> = schema
+            .keys()
+            .map(|c| PostgresWriter::quote_ident(c))
+            .collect();
+        let row_ph = format!("({})", vec!["?"; cols.len()].join(", "));
+        // Lineage columns change on every load; compare the data only.
+        let differs: Vec<String> = schema
+            .keys()
+            .filter(|c| !MetadataColumn::is_metadata(c))
+            .map(|c| PostgresWriter::quote_ident(c))
+            .map(|c| format!("t.{c} IS NOT s.{c}"))
+            .collect();
+        let pk_q = PostgresWriter::quote_ident(pk);
+
+        Ok(format!(
+            "WITH s ({}) AS (VALUES {}) \
+             SELECT COUNT(t.{pk_q}), COUNT(CASE WHEN {} THEN t.{pk_q} END) \
+             FROM s LEFT JOIN {} AS t ON t.{pk_q} = s.{pk_q}",
+            cols.join(", "),
+            vec![row_ph.as_str(); rows].join(", "),
+            differs.join(" OR "),
+            self.table_sql(),
+        ))
+    }
+
+    /// Classify an upsert chunk against the table before it is written.
+    async fn record_mutations(
+        &self,
+        rows: &[Value],
+        schema: &BTreeMap<String, PgType>,
+    ) -> Result<()> {
+        let Some(mutations) = &self.mutations else {
+            return Ok(());
+        };
+
+        let sql = self.mutation_count_sql(schema, rows.len())?;
+        let mut q = sqlx::query(&sql);
+        for row in rows {
+            for (col, ty) in schema {
+                q = Self::bind_value(q, row.get(col).unwrap_or(&Value::Null), ty);
+            }
+        }
+        let counts = q.fetch_one(&self.pool).await?;
+        let matched = counts.try_get::<i64, _>(0)? as u64;
+        let changed = counts.try_get::<i64, _>(1)? as u64;
+
+        let batch = MergeStats {
+            inserted: (rows.len() as u64).saturating_sub(matched),
+            changed,
+            unchanged: matched - changed,
+        };
+        debug!(table = %self.table_name, ?batch, "upsert mutations");
+        mutations
+            .lock()
+            .map_err(|e| ApitapError::PoisonError(e.to_string()))?
+            .add(batch);
+        Ok(())
+    }
+
+    async fn table_exists(&self) -> Result<bool> {
+        let (count,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?")
+                .bind(&self.table_name)
+                .fetch_one(&self.pool)
+                .await?;
+        Ok(count > 0)
+    }
+
+    async fn ensure_table(&self, sample_rows: &[Value]) -> Result<BTreeMap<String, PgType>> {
+        if let Some(schema) = self.columns_cache.read().await.as_ref() {
+            return Ok(schema.clone());
+        }
+        if sample_rows.is_empty() {
+            return Err(ApitapError::PipelineError(
+                "Need sample data to create table".to_string(),
+            ));
+        }
+
+        let schema = PostgresWriter::analyze_schema(sample_rows, self.sample_size)?;
+        if !self.table_exists().await? {
+            if !self.auto_create {
+                return Err(ApitapError::PipelineError(format!(
+                    "Table '{}' does not exist",
+                    self.table_name
+                )));
+            }
+            let sql = self.create_table_sql(&schema);
+            debug!(%sql, "create table sql");
+            sqlx::query(&sql).execute(&self.pool).await?;
+            info!(table = %self.table_name, columns = schema.len(), "created table");
+        }
+
+        *self.columns_cache.write().await = Some(schema.clone());
+        Ok(schema)
+    }
+
+    /// Before the first merge batch: make sure there is a key to merge on.
+    fn settle_key(&self, rows: &[Value], write_mode: &WriteMode) -> Result<()> {
+        if *write_mode != WriteMode::Merge || self.primary_key.is_some() {
+            return Ok(());
+        }
+        resolve_key(
+            &self.table_name,
+            self.infer_primary_key,
+            &self.inferred_key,
+            rows,
+        )
+    }
+
+    pub async fn truncate(&self) -> Result<()> {
+        if !self.table_exists().await? {
+            tracing::error!(table = %self.table_name, "table does not exist, skipping truncate");
+            return Ok(());
+        }
+        info!(table = %self.table_name, "truncating table");
+        sqlx::query(&format!("DELETE FROM {}", self.table_sql()))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn write_batch(
+        &self,
+        rows: &[Value],
+        schema: &BTreeMap<String, PgType>,
+        write_mode: &WriteMode,
+    ) -> Result<()> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        if schema.is_empty() {
+            return Err(ApitapError::PipelineError(
+                "No columns detected".to_string(),
+            ));
+        }
+
+        let upsert = *write_mode == WriteMode::Merge;
+        let rows_per_stmt = (MAX_BIND_PARAMS / schema.len()).max(1);
+
+        for chunk in rows.chunks(rows_per_stmt) {
+            if upsert {
+                self.record_mutations(chunk, schema).await?;
+            }
+            let sql = self.insert_sql(schema, chunk.len(), upsert)?;
+            let mut q = sqlx::query(&sql);
+            for row in chunk {
+                for (col, ty) in schema {
+                    q = Self::bind_value(q, row.get(col).unwrap_or(&Value::Null), ty);
+                }
+            }
+
+            let span = debug_span!(
+                "sql.execute",
+                statement = if upsert { "upsert" } else { "insert" },
+                table = %self.table_name,
+                batch_rows = chunk.len()
+            );
+            let _g = span.enter();
+            let res = q.execute(&self.pool).await?;
+            debug!(rows_affected = res.rows_affected(), "sqlite write executed");
+        }
+        Ok(())
+    }
+
+    /// Bind a JSON value as the column's storage class.
+    fn bind_value<'q>(query: SqliteQuery<'q>, value: &Value, expected: &PgType) -> SqliteQuery<'q> {
+        match (value, expected) {
+            (Value::Null, _) => query.bind(None::<String>),
+
+            (Value::Bool(b), PgType::Boolean | PgType::BigInt) => query.bind(*b as i64),
+            (Value::Number(n), PgType::BigInt) => query.bind(n.as_i64()),
+            (Value::Number(n), PgType::Double) => query.bind(n.as_f64()),
+            (Value::String(s), PgType::BigInt) => query.bind(s.parse::<i64>().ok()),
+            (Value::String(s), PgType::Double) => query.bind(s.parse::<f64>().ok()),
+            (Value::String(s), PgType::Boolean) => {
+                query.bind((s.eq_ignore_ascii_case("true") || s == "1") as i64)
+            }
+
+            (Value::String(s), PgType::Text) => query.bind(s.clone()),
+            (Value::Array(_) | Value::Object(_), _) | (_, PgType::Jsonb) => {
+                query.bind(serde_json::to_string(value).unwrap_or_default())
+            }
+            (other, _) => query.bind(other.to_string()),
+        }
+    }
+}
+
+#[async_trait]
+impl DataWriter for SqliteWriter {
+    async fn write_stream(
+        &self,
+        mut result: QueryResultStream,
+        write_mode: WriteMode,
+    ) -> Result<()> {
+        let mut buf: Vec<Value> = Vec::with_capacity(self.batch_size);
+        let mut schema: Option<BTreeMap<String, PgType>> = None;
+
+        while let Some(item) = result.data.next().await {
+            buf.push(item?);
+            if buf.len() >= self.batch_size {
+                if schema.is_none() {
+                    self.settle_key(&buf, &write_mode)?;
+                    schema = Some(self.ensure_table(&buf).await?);
+                }
+                let schema_ref = schema.as_ref().expect("schema just set");
+                self.write_batch(&buf, schema_ref, &write_mode).await?;
+                buf.clear();
+            }
+        }
+
+        if !buf.is_empty() {
+            if schema.is_none() {
+                self.settle_key(&buf, &write_mode)?;
+                schema = Some(self.ensure_table(&buf).await?);
+            }
+            let schema_ref = schema.as_ref().expect("schema just set");
+            self.write_batch(&buf, schema_ref, &write_mode).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn newest_loaded_at(
+        &self,
+        column: &str,
+    ) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+        if !self.table_exists().await? {
+            return Ok(None);
+        }
+        let sql = format!(
+            "SELECT CAST(MAX({}) AS TEXT) FROM {}",
+            PostgresWriter::quote_ident(column),
+            self.table_sql()
+        );
+        match sqlx::query_as::<_, (Option<String>,)>(&sql)
+            .fetch_one(&self.pool)
+            .await
+        {
+            Ok((newest,)) => Ok(newest.as_deref().and_then(parse_loaded_at)),
+            Err(sqlx::Error::Database(e)) if e.message().contains("no such column") => {
+                debug!(table = %self.table_name, column, "no load timestamps yet");
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn merge_stats(&self) -> Option<MergeStats> {
+        let mutations = self.mutations.as_ref()?.lock().ok()?;
+        Some(*mutations)
+    }
+
+    async fn purge_before(
+        &self,
+        column: &str,
+        cutoff: chrono::DateTime<chrono::Utc>,
+        detach_partitions: bool,
+    ) -> Result<Option<u64>> {
+        if detach_partitions {
+            debug!(table = %self.table_name, "SQLite has no partitions, deleting rows");
+        }
+        if !self.table_exists().await? {
+            return Ok(Some(0));
+        }
+        // Values julianday() cannot parse (and NULLs) are never purged.
+        let sql = format!(
+            "DELETE FROM {} WHERE julianday({}) < julianday(?)",
+            self.table_sql(),
+            PostgresWriter::quote_ident(column)
+        );
+        let res = sqlx::query(&sql)
+            .bind(cutoff.format("%Y-%m-%d %H:%M:%S").to_string())
+            .execute(&self.pool)
+            .await?;
+        Ok(Some(res.rows_affected()))
+    }
+
+    async fn reconcile(
+        &self,
+        key: &str,
+        seen: &[String],
+        deleted_column: Option<&str>,
+        at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<u64>> {
+        if !self.table_exists().await? {
+            return Ok(Some(0));
+        }
+        let table = self.table_sql();
+        let key = PostgresWriter::quote_ident(key);
+        // One parameter whatever the number of keys.
+        let seen = serde_json::to_string(seen)?;
+        let seen_sql = format!("CAST({key} AS TEXT) IN (SELECT value FROM json_each(?))");
+        let Some(column) = deleted_column else {
+            let res = sqlx::query(&format!("DELETE FROM {table} WHERE NOT ({seen_sql})"))
+                .bind(&seen)
+                .execute(&self.pool)
+                .await?;
+            return Ok(Some(res.rows_affected()));
+        };
+
+        let (exists,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM pragma_table_info(?) WHERE name = ?")
+                .bind(&self.table_name)
+                .bind(column)
+                .fetch_one(&self.pool)
+                .await?;
+        let column = PostgresWriter::quote_ident(column);
+        if exists == 0 {
+            sqlx::query(&format!("ALTER TABLE {table} ADD COLUMN {column} TEXT"))
+                .execute(&self.pool)
+                .await?;
+        }
+        let mut tx = self.pool.begin().await?;
+        let res = sqlx::query(&format!(
+            "UPDATE {table} SET {column} = ? WHERE {column} IS NULL AND NOT ({seen_sql})"
+        ))
+        .bind(at.to_rfc3339_opts(chrono::SecondsFormat::Micros, true))
+        .bind(&seen)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(&format!(
+            "UPDATE {table} SET {column} = NULL WHERE {column} IS NOT NULL AND {seen_sql}"
+        ))
+        .bind(&seen)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(Some(res.rows_affected()))
+    }
+
+    async fn row_count(&self) -> Result<Option<u64>> {
+        if !self.table_exists().await? {
+            return Ok(Some(0));
+        }
+        let sql = format!("SELECT COUNT(*) FROM {}", self.table_sql());
+        let count: i64 = sqlx::query_scalar(&sql).fetch_one(&self.pool).await?;
+        Ok(Some(count as u64))
+    }
+
+    async fn sample_keys(&self, column: &str, limit: usize) -> Result<Option<Vec<String>>> {
+        if !self.table_exists().await? {
+            return Ok(Some(Vec::new()));
+        }
+        let col = PostgresWriter::quote_ident(column);
+        let sql = format!(
+            "SELECT CAST({col} AS TEXT) FROM {} WHERE {col} IS NOT NULL ORDER BY random() LIMIT ?",
+            self.table_sql()
+        );
+        let keys = sqlx::query_scalar(&sql)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(Some(keys))
+    }
+
+    async fn count_keys(&self, column: &str, keys: &[String]) -> Result<Option<u64>> {
+        if !self.table_exists().await? {
+            return Ok(Some(0));
+        }
+        let col = PostgresWriter::quote_ident(column);
+        let mut found = 0;
+        for chunk in keys.chunks(MAX_BIND_PARAMS) {
+            let sql = format!(
+                "SELECT COUNT(DISTINCT CAST({col} AS TEXT)) FROM {} WHERE CAST({col} AS TEXT) IN ({})",
+                self.table_sql(),
+                vec!["?"; chunk.len()].join(", ")
+            );
+            let mut q = sqlx::query_scalar::<_, i64>(&sql);
+            for key in chunk {
+                q = q.bind(key);
+            }
+            found += q.fetch_one(&self.pool).await? as u64;
+        }
+        Ok(Some(found))
+    }
+
+    async fn duplicate_keys(&self, columns: &[String]) -> Result<Option<DuplicateKeys>> {
+        if !self.table_exists().await? {
+            return Ok(Some(DuplicateKeys::default()));
+        }
+        let cols: Vec<String> = columns
+            .iter()
+            .map(|c| PostgresWriter::quote_ident(c))
+            .collect();
+        let sql = format!(
+            "SELECT COUNT(*), COALESCE(SUM(n - 1), 0) FROM \
+             (SELECT COUNT(*) AS n FROM {} WHERE {} GROUP BY {} HAVING COUNT(*) > 1)",
+            self.table_sql(),
+            cols.iter()
+                .map(|c| format!("{c} IS NOT NULL"))
+                .collect::<Vec<_>>()
+                .join(" AND "),
+            cols.join(", ")
+        );
+        let (keys, extra_rows): (i64, i64) = sqlx::query_as(&sql).fetch_one(&self.pool).await?;
+        Ok(Some(DuplicateKeys {
+            keys: keys as u64,
+            extra_rows: extra_rows as u64,
+        }))
+    }
+
+    async fn write(&self, result: QueryResult) -> Result<()> {
+        let rows = result
+            .data
+            .as_array()
+            .ok_or_else(|| ApitapError::PipelineError("Expected JSON array".to_string()))?;
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        let schema = self.ensure_table(rows).await?;
+        for chunk in rows.chunks(self.batch_size) {
+            self.write_batch(chunk, &schema, &WriteMode::Append).await?;
+        }
+        Ok(())
+    }
+}
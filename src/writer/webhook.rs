@@ -0,0 +1,156 @@
+// src/writer/webhook.rs
+
+use crate::errors::{ApitapError, Result};
+use crate::utils::datafusion_ext::{QueryResult, QueryResultStream};
+use crate::writer::{DataWriter, WriteMode};
+use async_trait::async_trait;
+use reqwest_middleware::ClientWithMiddleware;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio_stream::StreamExt;
+use tracing::{debug, info};
+
+//=============== Type Definitions ============================================//
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum WebhookMethod {
+    #[default]
+    Post,
+    Put,
+    Patch,
+}
+
+impl WebhookMethod {
+    pub fn as_method(self) -> reqwest::Method {
+        match self {
+            WebhookMethod::Post => reqwest::Method::POST,
+            WebhookMethod::Put => reqwest::Method::PUT,
+            WebhookMethod::Patch => reqwest::Method::PATCH,
+        }
+    }
+}
+
+/// Header naming the destination table on every request.
+pub const TABLE_HEADER: &str = "X-Apitap-Table";
+/// Header carrying `merge` or `append`, so receivers can upsert or insert.
+pub const WRITE_MODE_HEADER: &str = "X-Apitap-Write-Mode";
+
+//=============== Webhook Writer ==============================================//
+
+/// Sends rows to an HTTP endpoint as JSON arrays of up to `batch_size` rows.
+///
+/// The client carries the sink's auth headers and retries transient failures
+/// (connect errors, 5xx, 429) with exponential backoff; any other non-2xx
+/// response fails the write. `{table}` in the URL is replaced by the
+/// destination table name.
+pub struct WebhookWriter {
+    client: ClientWithMiddleware,
+    pub url: String,
+    pub method: WebhookMethod,
+    pub table: String,
+    pub batch_size: usize,
+}
+
+impl WebhookWriter {
+    pub fn new(client: ClientWithMiddleware, url: &str, table: impl Into<String>) -> Self {
+        let table = table.into();
+        Self {
+            client,
+            url: Self::endpoint(url, &table),
+            method: WebhookMethod::Post,
+            table,
+            batch_size: 500,
+        }
+    }
+
+    pub fn with_method(mut self, method: WebhookMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    pub fn with_batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size.max(1);
+        self
+    }
+
+    /// Expand `{table}` in an endpoint template.
+    pub fn endpoint(template: &str, table: &str) -> String {
+        template.replace("{table}", table)
+    }
+
+    async fn send(&self, rows: &[Value], write_mode: &WriteMode) -> Result<()> {
+        let mode = match write_mode {
+            WriteMode::Merge => "merge",
+            WriteMode::Append => "append",
+        };
+        let resp = self
+            .client
+            .request(self.method.as_method(), &self.url)
+            .header(TABLE_HEADER, &self.table)
+            .header(WRITE_MODE_HEADER, mode)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(rows)?)
+            .send()
+            .await
+            .map_err(|e| {
+                ApitapError::WriterError(format!("webhook request to {} failed: {e}", self.url))
+            })?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.text().await.unwrap_or_default();
+            let snippet: String = body.chars().take(200).collect();
+            return Err(ApitapError::WriterError(format!(
+                "webhook {} returned {status}: {snippet}",
+                self.url
+            )));
+        }
+        debug!(url = %self.url, rows = rows.len(), %status, "webhook batch delivered");
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DataWriter for WebhookWriter {
+    async fn write_stream(
+        &self,
+        mut result: QueryResultStream,
+        write_mode: WriteMode,
+    ) -> Result<()> {
+        let mut batch: Vec<Value> = Vec::with_capacity(self.batch_size);
+        let mut sent = 0usize;
+
+        while let Some(item) = result.data.next().await {
+            batch.push(item?);
+            if batch.len() >= self.batch_size {
+                self.send(&batch, &write_mode).await?;
+                sent += batch.len();
+                batch.clear();
+            }
+        }
+        if !batch.is_empty() {
+            self.send(&batch, &write_mode).await?;
+            sent += batch.len();
+        }
+
+        info!(url = %self.url, rows = sent, "sent to webhook");
+        Ok(())
+    }
+
+    async fn write(&self, result: QueryResult) -> Result<()> {
+        let rows = match result.data {
+            Value::Array(rows) => rows,
+            _ => {
+                return Err(ApitapError::PipelineError(
+                    "Expected JSON array".to_string(),
+                ))
+            }
+        };
+        let stream = QueryResultStream {
+            table_name: result.table_name,
+            data: Box::pin(futures::stream::iter(rows.into_iter().map(Ok))),
+        };
+        self.write_stream(stream, WriteMode::Append).await
+    }
+}
@@ -0,0 +1,32 @@
+use apitap::cmd::completions::{write_completions, write_man_pages};
+use clap_complete::Shell;
+
+#[test]
+fn test_completions_list_subcommands() {
+    for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::PowerShell] {
+        let mut out = Vec::new();
+        write_completions(shell, &mut out);
+        let script = String::from_utf8(out).unwrap();
+        assert!(script.contains("apitap"), "{shell}");
+        for word in ["state", "completions", "yaml-config"] {
+            assert!(script.contains(word), "{shell} lacks {word}");
+        }
+    }
+}
+
+#[test]
+fn test_man_pages_per_subcommand() {
+    let dir = tempfile::tempdir().unwrap();
+    let pages = write_man_pages(dir.path()).unwrap();
+    for page in [
+        "apitap.1",
+        "apitap-state.1",
+        "apitap-state-export.1",
+        "apitap-completions.1",
+    ] {
+        assert!(pages.iter().any(|p| p == page), "missing {page}: {pages:?}");
+    }
+    let top = std::fs::read_to_string(dir.path().join("apitap.1")).unwrap();
+    assert!(top.contains(".TH apitap"));
+    assert!(top.contains("skip\\-if\\-fresh"));
+}
@@ -0,0 +1,158 @@
+use std::fs;
+
+use apitap::cmd::list::{list_modules, render_listing};
+use apitap::cmd::{Cli, Command, OutputFormat};
+use apitap::config::templating::ModuleStatus;
+use clap::Parser;
+use tempfile::TempDir;
+
+const CONFIG: &str = r#"
+sources:
+  - name: orders
+    url: https://api.example.com/orders
+    table_destination_name: orders
+    deprecated: use orders_v2
+    retry: {max_attempts: 1, max_delay_secs: 1, min_delay_secs: 1}
+  - name: orders_v2
+    url: https://api.example.com/v2/orders
+    table_destination_name: orders_v2
+    retry: {max_attempts: 1, max_delay_secs: 1, min_delay_secs: 1}
+  - name: legacy
+    url: https://api.example.com/legacy
+    table_destination_name: legacy
+    enabled: false
+    retry: {max_attempts: 1, max_delay_secs: 1, min_delay_secs: 1}
+targets: []
+"#;
+
+fn project() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("pipelines.yaml"), CONFIG).unwrap();
+    let sql = dir.path().join("sql");
+    fs::create_dir(&sql).unwrap();
+    let module = |source: &str, extra: &str| {
+        format!(
+            "{extra}{{{{ sink(name=\"pg\") }}}}\nSELECT * FROM {{{{ use_source(\"{source}\") }}}}"
+        )
+    };
+    fs::write(sql.join("a_orders.sql"), module("orders", "")).unwrap();
+    fs::write(
+        sql.join("b_orders_v2.sql"),
+        module(
+            "orders_v2",
+            "-- description: Orders, v2 API\n-- owner: @payments\n-- sla: 2h\n",
+        ),
+    )
+    .unwrap();
+    fs::write(sql.join("c_legacy.sql"), module("legacy", "")).unwrap();
+    fs::write(
+        sql.join("d_paused.sql"),
+        module("orders_v2", "{{ module(enabled=false) }}"),
+    )
+    .unwrap();
+    fs::write(
+        sql.join("e_sunset.sql"),
+        module(
+            "orders_v2",
+            "{{ module(deprecated=\"merged into b_orders_v2\") }}",
+        ),
+    )
+    .unwrap();
+    dir
+}
+
+#[test]
+fn test_list_flags_disabled_and_deprecated_modules() {
+    let dir = project();
+    let root = dir.path().join("sql");
+    let cfg = dir.path().join("pipelines.yaml");
+    let modules = list_modules(root.to_str().unwrap(), cfg.to_str().unwrap()).unwrap();
+
+    let statuses: Vec<(&str, &ModuleStatus)> = modules
+        .iter()
+        .map(|m| (m.module.as_str(), &m.status))
+        .collect();
+    assert_eq!(
+        statuses,
+        vec![
+            (
+                "a_orders.sql",
+                &ModuleStatus::Deprecated("use orders_v2".into())
+            ),
+            ("b_orders_v2.sql", &ModuleStatus::Enabled),
+            (
+                "c_legacy.sql",
+                &ModuleStatus::Disabled("source legacy has enabled: false".into())
+            ),
+            (
+                "d_paused.sql",
+                &ModuleStatus::Disabled("module(enabled=false)".into())
+            ),
+            (
+                "e_sunset.sql",
+                &ModuleStatus::Deprecated("merged into b_orders_v2".into())
+            ),
+        ]
+    );
+    assert_eq!(modules[0].destinations, vec!["pg:orders"]);
+    assert!(modules[0].doc.is_empty());
+    assert_eq!(modules[1].doc.owner.as_deref(), Some("@payments"));
+
+    let table = render_listing(&modules);
+    assert!(table.starts_with("MODULE"));
+    assert!(table.contains("deprecated: use orders_v2"));
+    assert!(table.contains("disabled (module(enabled=false))"));
+    let v2 = table
+        .lines()
+        .find(|l| l.starts_with("b_orders_v2"))
+        .unwrap();
+    assert!(v2.contains("@payments"));
+    assert!(v2.contains(" 2h "));
+    assert!(v2.ends_with("Orders, v2 API"));
+
+    let json = serde_json::to_value(&modules).unwrap();
+    assert_eq!(
+        json[1],
+        serde_json::json!({
+            "module": "b_orders_v2.sql",
+            "source": "orders_v2",
+            "destinations": ["pg:orders_v2"],
+            "status": {"state": "enabled"},
+            "description": "Orders, v2 API",
+            "owner": "@payments",
+            "sla": "2h"
+        })
+    );
+    assert_eq!(
+        json[0]["status"],
+        serde_json::json!({"state": "deprecated", "reason": "use orders_v2"})
+    );
+}
+
+#[test]
+fn test_list_takes_module_and_config_paths() {
+    let cli = Cli::parse_from(["apitap-run", "list", "-m", "sql", "-y", "p.yaml"]);
+    assert!(matches!(cli.command, Some(Command::List { .. })));
+    assert_eq!(
+        (cli.modules.as_str(), cli.yaml_config.as_str()),
+        ("sql", "p.yaml")
+    );
+}
+
+#[test]
+fn test_list_output_defaults_to_text() {
+    let cli = Cli::parse_from(["apitap-run", "list"]);
+    assert!(matches!(
+        cli.command,
+        Some(Command::List {
+            output: OutputFormat::Text
+        })
+    ));
+    let cli = Cli::parse_from(["apitap-run", "list", "--output", "json"]);
+    assert!(matches!(
+        cli.command,
+        Some(Command::List {
+            output: OutputFormat::Json
+        })
+    ));
+}
@@ -0,0 +1,3 @@
+mod completions_tests;
+mod describe_tests;
+mod list_tests;
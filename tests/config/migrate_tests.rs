@@ -0,0 +1,106 @@
+use apitap::config::load_config_from_path;
+use apitap::config::migrate::{migrate_str, needs_migration, CONFIG_VERSION};
+use apitap::http::fetcher::Pagination;
+use apitap::pipeline::{Config, Target};
+use std::io::Write;
+
+const LEGACY: &str = r#"
+sources:
+  - name: employees
+    url: https://example.com/api/employees
+    headers:
+      X-API-KEY: secret
+      X-Page-Limit: 100
+    pagination:
+      kind: PageNumber
+      page_param: page
+      size_param: per_page
+    retry:
+      max_attempts: 3
+      max_delay_secs: 5
+      min_delay_secs: 1
+targets:
+  - name: warehouse
+    type: postgres
+    host: localhost
+    database: apitap
+    username: postgres
+    password_env: PG_PASS
+    auth:
+      password_env: PG_PASSWORD
+"#;
+
+#[test]
+fn test_migrate_legacy_config() {
+    assert!(needs_migration(LEGACY));
+    let migration = migrate_str(LEGACY).unwrap();
+    assert_eq!(migration.from_version, 1);
+    let notes: Vec<String> = migration
+        .changes
+        .iter()
+        .map(|c| format!("{}: {}", c.path, c.note))
+        .collect();
+    assert_eq!(
+        notes,
+        vec![
+            "sources[0] (employees).pagination: kind `PageNumber` renamed to `page_number`",
+            "sources[0] (employees).pagination: `size_param` renamed to `per_page_param`",
+            "sources[0] (employees).headers: mapping converted to a list of `key`/`value` entries",
+            "targets[0] (warehouse): `username`, `password_env` moved under `auth`",
+        ]
+    );
+    assert!(migration
+        .yaml
+        .starts_with("# Migrated by `apitap config migrate`"));
+    assert!(migration
+        .yaml
+        .contains(&format!("\nversion: {CONFIG_VERSION}\n")));
+
+    let cfg: Config = serde_yaml::from_str(&migration.yaml).unwrap();
+    assert_eq!(cfg.version, Some(CONFIG_VERSION));
+    let source = &cfg.sources[0];
+    assert!(matches!(
+        &source.pagination,
+        Some(Pagination::PageNumber { per_page_param, .. }) if per_page_param == "per_page"
+    ));
+    let headers = source.headers.as_ref().unwrap();
+    assert_eq!(headers[1].key, "X-Page-Limit");
+    assert_eq!(headers[1].value, "100");
+    let Target::Postgres(pg) = &cfg.targets[0] else {
+        panic!("expected postgres target");
+    };
+    assert_eq!(pg.auth.username.as_deref(), Some("postgres"));
+    // The explicit `auth:` entry is kept.
+    assert_eq!(pg.auth.password_env.as_deref(), Some("PG_PASSWORD"));
+
+    // Migrating again changes nothing.
+    let again = migrate_str(&migration.yaml).unwrap();
+    assert!(again.is_noop());
+    assert!(!needs_migration(&migration.yaml));
+}
+
+#[test]
+fn test_unversioned_current_config_only_stamped() {
+    let migration = migrate_str("sources: []\ntargets: []\n").unwrap();
+    assert!(migration.changes.is_empty());
+    assert!(!migration.is_noop());
+    assert!(migration.yaml.contains("version: 2\nsources: []"));
+}
+
+#[test]
+fn test_load_rejects_newer_and_hints_migration() {
+    let write = |text: &str| {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(text.as_bytes()).unwrap();
+        f
+    };
+    let newer = write("version: 99\nsources: []\ntargets: []\n");
+    let err = load_config_from_path(newer.path()).unwrap_err().to_string();
+    assert!(err.contains("newer than this apitap supports"), "{err}");
+
+    let legacy = write(LEGACY);
+    let err = load_config_from_path(legacy.path())
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("apitap config migrate"), "{err}");
+}
@@ -1 +1,3 @@
+mod migrate_tests;
+mod secrets_tests;
 mod templating_tests;
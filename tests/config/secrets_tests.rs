@@ -0,0 +1,284 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::http::{StubRequest, StubResponse};
+use apitap::config::load_config_with_secrets;
+use apitap::config::secrets::{
+    AwsSecretsManager, GcpSecretManager, SecretRef, SecretResolver, SecretResolvers, Vault,
+};
+use apitap::errors::{ApitapError, Result};
+use apitap::pipeline::Target;
+use async_trait::async_trait;
+use object_store::aws::AwsCredential;
+
+type Seen = Arc<Mutex<Vec<StubRequest>>>;
+
+/// Answers `body` and records each request.
+async fn serve(body: &'static str) -> (String, Seen) {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&seen);
+    let base = crate::http::serve(move |req| {
+        log.lock().unwrap().push(req);
+        StubResponse::json(body)
+    })
+    .await;
+    (base, seen)
+}
+
+fn credential(token: Option<&str>) -> AwsCredential {
+    AwsCredential {
+        key_id: "AKIDEXAMPLE".to_string(),
+        secret_key: "wJalr".to_string(),
+        token: token.map(str::to_string),
+    }
+}
+
+#[tokio::test]
+async fn test_vault_reads_a_kv2_field() {
+    let (addr, seen) =
+        serve(r#"{"data":{"data":{"username":"etl","password":"s3cret"},"metadata":{}}}"#).await;
+    let resolvers = SecretResolvers::new()
+        .with(Vault::new(reqwest::Client::new(), addr, "hvs.token").with_namespace("team-data"));
+
+    let secret = resolvers
+        .resolve("vault://secret/warehouse/pg#password")
+        .await
+        .unwrap();
+    assert_eq!(secret.as_deref(), Some("s3cret"));
+
+    let seen = seen.lock().unwrap();
+    let req = &seen[0];
+    assert_eq!(req.target, "/v1/secret/data/warehouse/pg");
+    assert_eq!(req.header("x-vault-token"), Some("hvs.token"));
+    assert_eq!(req.header("x-vault-namespace"), Some("team-data"));
+}
+
+#[tokio::test]
+async fn test_vault_needs_a_field_when_the_secret_has_several() {
+    let (addr, _) = serve(r#"{"data":{"data":{"username":"etl","password":"s3cret"}}}"#).await;
+    let resolvers =
+        SecretResolvers::new().with(Vault::new(reqwest::Client::new(), addr, "hvs.token"));
+
+    let err = resolvers
+        .resolve("vault://secret/warehouse")
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("several fields"), "{err}");
+    assert!(err.contains("vault://secret/warehouse"), "{err}");
+}
+
+#[tokio::test]
+async fn test_aws_secrets_manager_signs_get_secret_value() {
+    let (endpoint, seen) =
+        serve(r#"{"Name":"prod/stripe","SecretString":"{\"bearer\":\"sk_live\",\"retries\":3}"}"#)
+            .await;
+    let resolvers = SecretResolvers::new().with(
+        AwsSecretsManager::new(
+            reqwest::Client::new(),
+            "eu-west-1",
+            credential(Some("session")),
+        )
+        .with_endpoint(endpoint),
+    );
+
+    let secret = resolvers
+        .resolve("aws-sm://prod/stripe#bearer")
+        .await
+        .unwrap();
+    assert_eq!(secret.as_deref(), Some("sk_live"));
+    let retries = resolvers
+        .resolve("aws-sm://prod/stripe#retries")
+        .await
+        .unwrap();
+    assert_eq!(retries.as_deref(), Some("3"));
+
+    let seen = seen.lock().unwrap();
+    let req = &seen[0];
+    assert_eq!(req.target, "/");
+    assert_eq!(req.body_text(), r#"{"SecretId":"prod/stripe"}"#);
+    assert_eq!(
+        req.header("x-amz-target"),
+        Some("secretsmanager.GetSecretValue")
+    );
+    assert_eq!(req.header("x-amz-security-token"), Some("session"));
+    let authorization = req.header("authorization").unwrap();
+    assert!(
+        authorization.starts_with("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/"),
+        "{authorization}"
+    );
+    assert!(
+        authorization.contains("/eu-west-1/secretsmanager/aws4_request"),
+        "{authorization}"
+    );
+    assert!(
+        authorization.contains(
+            "SignedHeaders=content-type;host;x-amz-date;x-amz-security-token;x-amz-target"
+        ),
+        "{authorization}"
+    );
+}
+
+#[tokio::test]
+async fn test_aws_arn_names_its_region() {
+    let (endpoint, seen) = serve(r#"{"SecretString":"plain"}"#).await;
+    let resolvers = SecretResolvers::new().with(
+        AwsSecretsManager::new(reqwest::Client::new(), "eu-west-1", credential(None))
+            .with_endpoint(endpoint),
+    );
+
+    let secret = resolvers
+        .resolve("aws-sm://arn:aws:secretsmanager:us-east-2:123456789012:secret:db")
+        .await
+        .unwrap();
+    assert_eq!(secret.as_deref(), Some("plain"));
+    let seen = seen.lock().unwrap();
+    let authorization = seen[0].header("authorization").unwrap();
+    assert!(authorization.contains("/us-east-2/"), "{authorization}");
+}
+
+#[tokio::test]
+async fn test_aws_from_env_uses_the_credential_chain() {
+    let (endpoint, seen) = serve(r#"{"SecretString":"plain"}"#).await;
+    std::env::set_var("AWS_REGION", "eu-central-1");
+    std::env::set_var("AWS_ACCESS_KEY_ID", "AKIDCHAIN");
+    std::env::set_var("AWS_SECRET_ACCESS_KEY", "chain-secret");
+    std::env::set_var("AWS_SESSION_TOKEN", "chain-session");
+    std::env::set_var("AWS_ENDPOINT_URL_SECRETS_MANAGER", &endpoint);
+    let resolvers =
+        SecretResolvers::new().with(AwsSecretsManager::from_env(reqwest::Client::new()));
+
+    let secret = resolvers.resolve("aws-sm://prod/db").await.unwrap();
+    assert_eq!(secret.as_deref(), Some("plain"));
+    let seen = seen.lock().unwrap();
+    let authorization = seen[0].header("authorization").unwrap();
+    assert!(
+        authorization.starts_with("AWS4-HMAC-SHA256 Credential=AKIDCHAIN/"),
+        "{authorization}"
+    );
+    assert!(authorization.contains("/eu-central-1/"), "{authorization}");
+    assert_eq!(
+        seen[0].header("x-amz-security-token"),
+        Some("chain-session")
+    );
+}
+
+#[tokio::test]
+async fn test_gcp_secret_manager_accesses_latest_version() {
+    // base64 of `tok-123`
+    let (endpoint, seen) = serve(r#"{"name":"x","payload":{"data":"dG9rLTEyMw=="}}"#).await;
+    let resolvers = SecretResolvers::new().with(
+        GcpSecretManager::new(reqwest::Client::new())
+            .with_endpoint(endpoint)
+            .with_access_token("ya29.token"),
+    );
+
+    let secret = resolvers
+        .resolve("gcp-sm://my-project/shopify-token")
+        .await
+        .unwrap();
+    assert_eq!(secret.as_deref(), Some("tok-123"));
+    resolvers
+        .resolve("gcp-sm://my-project/shopify-token/3")
+        .await
+        .unwrap();
+
+    let seen = seen.lock().unwrap();
+    assert_eq!(
+        seen[0].target,
+        "/v1/projects/my-project/secrets/shopify-token/versions/latest:access"
+    );
+    assert_eq!(
+        seen[1].target,
+        "/v1/projects/my-project/secrets/shopify-token/versions/3:access"
+    );
+    assert_eq!(seen[0].header("authorization"), Some("Bearer ya29.token"));
+}
+
+/// Answers `value-of:<path>` and counts its calls.
+struct Fake {
+    calls: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl SecretResolver for Fake {
+    fn scheme(&self) -> &str {
+        "fake"
+    }
+
+    async fn resolve(&self, reference: &SecretRef) -> Result<String> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        match reference.path.as_str() {
+            "missing" => Err(ApitapError::ConfigError("not found".to_string())),
+            path => Ok(format!("value-of:{path}")),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_references_are_replaced_throughout_the_document() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let resolvers = SecretResolvers::new().with(Fake {
+        calls: Arc::clone(&calls),
+    });
+    let mut doc: serde_yaml::Value = serde_yaml::from_str(
+        r#"
+url: https://api.example.com
+token: fake://api
+nested:
+  - key: Authorization
+    value: fake://api
+  - other: vault://left/alone
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(resolvers.resolve_yaml(&mut doc).await.unwrap(), 2);
+    assert_eq!(calls.load(Ordering::SeqCst), 1, "fetched once");
+    assert_eq!(doc["token"], "value-of:api");
+    assert_eq!(doc["nested"][0]["value"], "value-of:api");
+    assert_eq!(doc["url"], "https://api.example.com");
+    assert_eq!(doc["nested"][1]["other"], "vault://left/alone");
+}
+
+#[tokio::test]
+async fn test_load_config_with_secrets() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("pipeline.yaml");
+    std::fs::write(
+        &path,
+        r#"
+sources: []
+targets:
+  - name: warehouse
+    type: postgres
+    auth:
+      username: etl
+      password: fake://warehouse#password
+    host: localhost
+    database: testdb
+"#,
+    )
+    .unwrap();
+    let resolvers = SecretResolvers::new().with(Fake {
+        calls: Arc::new(AtomicUsize::new(0)),
+    });
+
+    let cfg = load_config_with_secrets(&path, &resolvers).await.unwrap();
+    let Target::Postgres(pg) = &cfg.targets[0] else {
+        panic!("postgres target");
+    };
+    assert_eq!(pg.auth.password.as_deref(), Some("value-of:warehouse"));
+
+    std::fs::write(
+        &path,
+        "sources: []\ntargets: []\nalerts:\n  - name: x\n    kind: webhook\n    url: fake://missing\n",
+    )
+    .unwrap();
+    let err = load_config_with_secrets(&path, &resolvers)
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("fake://missing"), "{err}");
+    assert!(err.contains("not found"), "{err}");
+}
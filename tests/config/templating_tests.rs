@@ -1,6 +1,7 @@
 use apitap::config::templating::{
-    build_env_with_captures, list_sql_templates, render_one, RenderCapture,
+    build_env_with_captures, list_sql_templates, render_one, ModuleDoc, RenderCapture, RoutedSql,
 };
+use apitap::errors::ApitapError;
 use std::fs;
 use std::sync::{Arc, Mutex};
 use tempfile::TempDir;
@@ -186,3 +187,274 @@ fn test_render_one_with_template_variables() {
 
     assert!(result.sql.contains("LIMIT 10"));
 }
+
+#[test]
+fn test_freshness_function_captures_contract() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().to_str().unwrap();
+
+    let sql_content = r#"{{ sink(name="pg") }}
+{{ freshness(max_age="6h", severity="error") }}
+SELECT 1;
+"#;
+    fs::write(temp_dir.path().join("test.sql"), sql_content).unwrap();
+    fs::write(temp_dir.path().join("plain.sql"), "SELECT 1;").unwrap();
+
+    let shared_cap = Arc::new(Mutex::new(RenderCapture::default()));
+    let env = build_env_with_captures(root, &shared_cap);
+
+    let result = render_one(&env, &shared_cap, "test.sql").unwrap();
+    let freshness = result.capture.freshness.expect("freshness captured");
+    assert_eq!(freshness.max_age, std::time::Duration::from_secs(6 * 3600));
+    assert_eq!(freshness.column, "_loaded_at");
+    assert_eq!(
+        freshness.severity,
+        apitap::pipeline::freshness::FreshnessSeverity::Error
+    );
+
+    let result = render_one(&env, &shared_cap, "plain.sql").unwrap();
+    assert!(result.capture.freshness.is_none());
+}
+
+#[test]
+fn test_sla_function_captures_budget() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().to_str().unwrap();
+
+    fs::write(
+        temp_dir.path().join("test.sql"),
+        r#"{{ sla(max_duration="15m", alert="slack") }}
+SELECT 1;
+"#,
+    )
+    .unwrap();
+    fs::write(temp_dir.path().join("plain.sql"), "SELECT 1;").unwrap();
+
+    let shared_cap = Arc::new(Mutex::new(RenderCapture::default()));
+    let env = build_env_with_captures(root, &shared_cap);
+
+    let result = render_one(&env, &shared_cap, "test.sql").unwrap();
+    let sla = result.capture.sla.expect("sla captured");
+    assert_eq!(sla.max_duration, std::time::Duration::from_secs(15 * 60));
+    assert_eq!(sla.alert.as_deref(), Some("slack"));
+
+    let result = render_one(&env, &shared_cap, "plain.sql").unwrap();
+    assert!(result.capture.sla.is_none());
+}
+
+#[test]
+fn test_freshness_function_rejects_bad_duration() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().to_str().unwrap();
+    fs::write(
+        temp_dir.path().join("test.sql"),
+        r#"{{ freshness(max_age="six hours") }}"#,
+    )
+    .unwrap();
+
+    let shared_cap = Arc::new(Mutex::new(RenderCapture::default()));
+    let env = build_env_with_captures(root, &shared_cap);
+
+    assert!(render_one(&env, &shared_cap, "test.sql").is_err());
+}
+
+#[test]
+fn test_transform_function_captures_plugins() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().to_str().unwrap();
+    let sql_content = r#"{{ sink(name="warehouse") }}
+{{ transform("plugins/clean.wasm") }}{{ transform("plugins/enrich.wasm") }}
+SELECT * FROM {{ use_source("users") }};
+"#;
+    fs::write(temp_dir.path().join("a.sql"), sql_content).unwrap();
+    fs::write(temp_dir.path().join("b.sql"), "SELECT 1").unwrap();
+
+    let shared_cap = Arc::new(Mutex::new(RenderCapture::default()));
+    let env = build_env_with_captures(root, &shared_cap);
+
+    let a = render_one(&env, &shared_cap, "a.sql").unwrap();
+    assert_eq!(
+        a.capture.transforms,
+        vec!["plugins/clean.wasm", "plugins/enrich.wasm"]
+    );
+    assert!(!a.sql.contains("wasm"));
+
+    let b = render_one(&env, &shared_cap, "b.sql").unwrap();
+    assert!(b.capture.transforms.is_empty());
+}
+
+#[test]
+fn test_sink_with_table_routes_statements() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().to_str().unwrap();
+
+    let sql_content = r#"{{ sink(name="warehouse", table="paid_orders") }}
+SELECT id, amount FROM {{ use_source("orders") }} WHERE status = 'paid';
+
+{{ sink(name="lake", table="refunds") }}
+SELECT id FROM {{ use_source("orders") }} WHERE status = 'refunded';
+"#;
+    fs::write(temp_dir.path().join("routed.sql"), sql_content).unwrap();
+
+    let shared_cap = Arc::new(Mutex::new(RenderCapture::default()));
+    let env = build_env_with_captures(root, &shared_cap);
+    let result = render_one(&env, &shared_cap, "routed.sql").unwrap();
+
+    let statements = result.statements().unwrap();
+    assert_eq!(
+        statements,
+        vec![
+            RoutedSql {
+                sink: "warehouse".to_string(),
+                table: Some("paid_orders".to_string()),
+                sql: "SELECT id, amount FROM orders WHERE status = 'paid'".to_string(),
+            },
+            RoutedSql {
+                sink: "lake".to_string(),
+                table: Some("refunds".to_string()),
+                sql: "SELECT id FROM orders WHERE status = 'refunded'".to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_plain_module_is_one_statement() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().to_str().unwrap();
+    fs::write(
+        temp_dir.path().join("plain.sql"),
+        r#"{{ sink(name="warehouse") }}
+SELECT * FROM {{ use_source("orders") }};
+"#,
+    )
+    .unwrap();
+
+    let shared_cap = Arc::new(Mutex::new(RenderCapture::default()));
+    let env = build_env_with_captures(root, &shared_cap);
+    let result = render_one(&env, &shared_cap, "plain.sql").unwrap();
+
+    let statements = result.statements().unwrap();
+    assert_eq!(statements.len(), 1);
+    assert_eq!(statements[0].sink, "warehouse");
+    assert_eq!(statements[0].table, None);
+    assert_eq!(statements[0].sql, result.sql);
+}
+
+#[test]
+fn test_routed_module_rejects_unrouted_sql() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().to_str().unwrap();
+    fs::write(
+        temp_dir.path().join("a.sql"),
+        r#"SELECT 1;
+{{ sink(name="warehouse", table="t") }}
+SELECT * FROM {{ use_source("orders") }};
+"#,
+    )
+    .unwrap();
+    fs::write(
+        temp_dir.path().join("b.sql"),
+        r#"{{ sink(name="warehouse", table="t") }}
+SELECT * FROM {{ use_source("orders") }};
+{{ sink(name="warehouse", table="empty") }}
+"#,
+    )
+    .unwrap();
+
+    let shared_cap = Arc::new(Mutex::new(RenderCapture::default()));
+    let env = build_env_with_captures(root, &shared_cap);
+    for name in ["a.sql", "b.sql"] {
+        let result = render_one(&env, &shared_cap, name).unwrap();
+        assert!(matches!(
+            result.statements(),
+            Err(ApitapError::ConfigError(_))
+        ));
+    }
+}
+
+#[test]
+fn test_statements_must_be_read_only() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().to_str().unwrap();
+    let modules = [
+        (
+            "insert.sql",
+            r#"INSERT INTO orders_clean SELECT * FROM {{ use_source("orders") }};"#,
+        ),
+        (
+            "drop.sql",
+            r#"{{ sink(name="warehouse", table="t") }}
+DROP TABLE {{ use_source("orders") }};
+"#,
+        ),
+        (
+            "two.sql",
+            r#"SELECT * FROM {{ use_source("orders") }}; DELETE FROM orders;"#,
+        ),
+    ];
+    for (name, sql) in modules {
+        fs::write(temp_dir.path().join(name), sql).unwrap();
+    }
+    fs::write(
+        temp_dir.path().join("cte.sql"),
+        r#"-- owner: data-eng
+WITH paid AS (SELECT * FROM {{ use_source("orders") }} WHERE status = 'paid')
+SELECT id FROM paid;
+"#,
+    )
+    .unwrap();
+
+    let shared_cap = Arc::new(Mutex::new(RenderCapture::default()));
+    let env = build_env_with_captures(root, &shared_cap);
+    for (name, expected) in [
+        ("insert.sql", "found INSERT"),
+        ("drop.sql", "found DROP"),
+        ("two.sql", "found 2 statements"),
+    ] {
+        let result = render_one(&env, &shared_cap, name).unwrap();
+        match result.statements() {
+            Err(ApitapError::ConfigError(msg)) => {
+                assert!(msg.starts_with(name), "{msg}");
+                assert!(msg.contains(expected), "{msg}");
+            }
+            other => panic!("{name}: expected a config error, got {other:?}"),
+        }
+    }
+    let cte = render_one(&env, &shared_cap, "cte.sql").unwrap();
+    assert_eq!(cte.statements().unwrap().len(), 1);
+}
+
+#[test]
+fn test_module_doc_header() {
+    let doc = ModuleDoc::parse(
+        "\n-- Orders module\n-- Description: Shopify orders, merged on id\n--owner: @data-platform\n\
+         -- sla: 06:00 UTC\n{{ sink(name=\"pg\") }}\n-- owner: not in the header\nSELECT 1",
+    );
+    assert_eq!(
+        doc,
+        ModuleDoc {
+            description: Some("Shopify orders, merged on id".into()),
+            owner: Some("@data-platform".into()),
+            sla: Some("06:00 UTC".into()),
+        }
+    );
+    assert!(ModuleDoc::parse("{{ sink(name=\"pg\") }}\n-- owner: x").is_empty());
+}
+
+#[test]
+fn test_render_one_reads_module_doc() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().to_str().unwrap();
+    fs::write(
+        temp_dir.path().join("orders.sql"),
+        "-- owner: @payments\n{{ sink(name=\"pg\") }}\nSELECT * FROM {{ use_source(\"orders\") }}",
+    )
+    .unwrap();
+
+    let shared_cap = Arc::new(Mutex::new(RenderCapture::default()));
+    let env = build_env_with_captures(root, &shared_cap);
+    let result = render_one(&env, &shared_cap, "orders.sql").unwrap();
+    assert_eq!(result.doc.owner.as_deref(), Some("@payments"));
+    assert_eq!(result.doc.sla, None);
+}
@@ -0,0 +1,175 @@
+use super::{retry, StubRequest, StubResponse};
+use apitap::http::auth::{ApiKeyLocation, SourceAuth};
+use apitap::http::fetcher::{send_page_request_with_headers, RequestOptions};
+use apitap::http::Http;
+use std::sync::{Arc, Mutex};
+
+/// Answers `[]` and records each request.
+async fn serve() -> (String, Arc<Mutex<Vec<StubRequest>>>) {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&seen);
+    let base = super::serve(move |req| {
+        log.lock().unwrap().push(req);
+        StubResponse::ok("[]")
+    })
+    .await;
+    (format!("{base}/items"), seen)
+}
+
+async fn send(
+    yaml: &str,
+    env: &str,
+    query: &[(&str, &str)],
+    headers: &[(&str, &str)],
+) -> StubRequest {
+    std::env::set_var(env, "k-123");
+    let (url, seen) = serve().await;
+    let auth: SourceAuth = serde_yaml::from_str(yaml).unwrap();
+    let client = reqwest::Client::new();
+    let request = RequestOptions {
+        auth: Some(auth.authenticator(&client, None, None).unwrap()),
+        ..Default::default()
+    };
+    let owned = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    };
+    send_page_request_with_headers(
+        &client,
+        &url,
+        &owned(query),
+        &owned(headers),
+        &retry(),
+        &request,
+    )
+    .await
+    .unwrap();
+    let mut seen = seen.lock().unwrap();
+    seen.pop().unwrap()
+}
+
+#[test]
+fn test_api_key_from_yaml() {
+    let auth: SourceAuth =
+        serde_yaml::from_str("kind: api_key\nname: X-Api-Key\nvalue_env: KEY").unwrap();
+    let SourceAuth::ApiKey(config) = auth else {
+        panic!("expected api_key auth");
+    };
+    assert_eq!(config.location, ApiKeyLocation::Header);
+    assert_eq!(config.prefix, None);
+
+    let auth: SourceAuth =
+        serde_yaml::from_str("kind: api_key\nin: cookie\nname: session\nvalue_env: KEY").unwrap();
+    assert!(matches!(
+        auth,
+        SourceAuth::ApiKey(config) if config.location == ApiKeyLocation::Cookie
+    ));
+}
+
+#[test]
+fn test_api_key_needs_its_env_var() {
+    let auth: SourceAuth = serde_yaml::from_str(
+        "kind: api_key\nname: X-Api-Key\nvalue_env: APITAP_TEST_API_KEY_UNSET",
+    )
+    .unwrap();
+    let err = auth
+        .authenticator(&reqwest::Client::new(), None, None)
+        .unwrap_err();
+    assert!(err.to_string().contains("APITAP_TEST_API_KEY_UNSET"));
+}
+
+#[tokio::test]
+async fn test_api_key_in_header() {
+    let req = send(
+        "kind: api_key\nname: Authorization\nprefix: 'Token '\nvalue_env: APITAP_TEST_API_KEY_HEADER",
+        "APITAP_TEST_API_KEY_HEADER",
+        &[],
+        &[],
+    )
+    .await;
+    assert_eq!(req.header("authorization"), Some("Token k-123"));
+}
+
+#[tokio::test]
+async fn test_api_key_in_query() {
+    let req = send(
+        "kind: api_key\nin: query\nname: api_key\nvalue_env: APITAP_TEST_API_KEY_QUERY",
+        "APITAP_TEST_API_KEY_QUERY",
+        &[("page", "2")],
+        &[],
+    )
+    .await;
+    assert_eq!(req.target, "/items?page=2&api_key=k-123");
+}
+
+#[tokio::test]
+async fn test_api_key_in_cookie_keeps_other_cookies() {
+    let req = send(
+        "kind: api_key\nin: cookie\nname: apikey\nvalue_env: APITAP_TEST_API_KEY_COOKIE",
+        "APITAP_TEST_API_KEY_COOKIE",
+        &[],
+        &[("Cookie", "locale=en; apikey=stale")],
+    )
+    .await;
+    assert_eq!(req.header("cookie"), Some("locale=en; apikey=k-123"));
+}
+
+#[tokio::test]
+async fn test_basic_auth() {
+    std::env::set_var("APITAP_TEST_BASIC_USER", "alice");
+    let req = send(
+        "kind: basic\nusername_env: APITAP_TEST_BASIC_USER\npassword_env: APITAP_TEST_BASIC_PASSWORD",
+        "APITAP_TEST_BASIC_PASSWORD",
+        &[],
+        &[],
+    )
+    .await;
+    // base64("alice:k-123")
+    assert_eq!(req.header("authorization"), Some("Basic YWxpY2U6ay0xMjM="));
+}
+
+#[tokio::test]
+async fn test_authorization_template() {
+    let req = send(
+        "kind: authorization\ntemplate: 'Token token=\"${APITAP_TEST_AUTH_TEMPLATE}\"'",
+        "APITAP_TEST_AUTH_TEMPLATE",
+        &[],
+        &[],
+    )
+    .await;
+    assert_eq!(req.header("authorization"), Some("Token token=\"k-123\""));
+}
+
+#[test]
+fn test_authorization_template_needs_its_env_vars() {
+    let auth: SourceAuth =
+        serde_yaml::from_str("kind: authorization\ntemplate: SSWS ${APITAP_TEST_AUTH_UNSET}")
+            .unwrap();
+    let err = auth.authorization().unwrap_err();
+    assert!(err.to_string().contains("APITAP_TEST_AUTH_UNSET"));
+
+    let auth: SourceAuth =
+        serde_yaml::from_str("kind: authorization\ntemplate: SSWS ${OOPS").unwrap();
+    assert!(auth.authorization().is_err());
+}
+
+#[test]
+fn test_only_static_credentials_have_an_authorization() {
+    let auth: SourceAuth =
+        serde_yaml::from_str("kind: api_key\nname: X-Api-Key\nvalue_env: KEY").unwrap();
+    assert_eq!(auth.authorization().unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_client_sends_basic_auth_by_default() {
+    let (url, seen) = serve().await;
+    let client = Http::new(url.clone())
+        .basic_auth("alice", "k-123")
+        .build_client()
+        .unwrap();
+    client.get(&url).send().await.unwrap();
+    let req = seen.lock().unwrap().pop().unwrap();
+    assert_eq!(req.header("authorization"), Some("Basic YWxpY2U6ay0xMjM="));
+}
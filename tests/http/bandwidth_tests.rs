@@ -0,0 +1,38 @@
+// Tests for the --max-bandwidth limiter
+
+use apitap::http::bandwidth::{parse_bandwidth, BandwidthLimiter};
+use std::time::Duration;
+
+#[test]
+fn test_parse_bandwidth_units() {
+    assert_eq!(parse_bandwidth("10MB/s").unwrap(), 10_000_000);
+    assert_eq!(parse_bandwidth("512KiB/s").unwrap(), 512 * 1024);
+    assert_eq!(parse_bandwidth("1.5 mb/s").unwrap(), 1_500_000);
+    assert_eq!(parse_bandwidth("2GiB").unwrap(), 2 * 1024 * 1024 * 1024);
+    assert_eq!(parse_bandwidth("1000000").unwrap(), 1_000_000);
+    assert_eq!(parse_bandwidth("100KBps").unwrap(), 100_000);
+}
+
+#[test]
+fn test_parse_bandwidth_invalid() {
+    assert!(parse_bandwidth("").is_err());
+    assert!(parse_bandwidth("fast").is_err());
+    assert!(parse_bandwidth("10XB/s").is_err());
+    assert!(parse_bandwidth("0MB/s").is_err());
+}
+
+#[tokio::test]
+async fn test_limiter_allows_burst_then_waits() {
+    let limiter = BandwidthLimiter::new(10_000);
+    let start = std::time::Instant::now();
+
+    // One second of burst is free.
+    limiter.consume(10_000).await;
+    assert!(start.elapsed() < Duration::from_millis(50));
+
+    // The next 1000 bytes have to wait for the bucket to refill.
+    limiter.consume(1_000).await;
+    let waited = start.elapsed();
+    assert!(waited >= Duration::from_millis(90), "waited {waited:?}");
+    assert!(waited <= Duration::from_millis(1000), "waited {waited:?}");
+}
@@ -0,0 +1,119 @@
+use super::{retry, serve, StubRequest, StubResponse};
+use apitap::errors::ApitapError;
+use apitap::http::body::{BodyFormat, RequestBody, RequestMethod};
+use apitap::http::fetcher::{ndjson_stream_with, RequestOptions};
+use apitap::pipeline::Config;
+use futures::TryStreamExt;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+/// Serve one JSON response and record the raw request it answered.
+async fn serve_once(response: Value) -> (String, Arc<Mutex<Option<StubRequest>>>) {
+    let received = Arc::new(Mutex::new(None));
+    let log = Arc::clone(&received);
+    let base = serve(move |req| {
+        *log.lock().unwrap() = Some(req);
+        StubResponse::json(&response)
+    })
+    .await;
+    (format!("{base}/search"), received)
+}
+
+#[test]
+fn test_encode_json_body() {
+    let body = RequestBody::encode(
+        &json!({"query": "status:open", "size": 50}),
+        BodyFormat::Json,
+    )
+    .unwrap();
+    assert_eq!(body.content_type(), "application/json");
+    let decoded: Value = serde_json::from_slice(&body.bytes).unwrap();
+    assert_eq!(decoded, json!({"query": "status:open", "size": 50}));
+}
+
+#[test]
+fn test_encode_form_body() {
+    let body = RequestBody::encode(
+        &json!({"q": "a b&c", "limit": 10, "tag": ["x", "y"], "empty": null}),
+        BodyFormat::Form,
+    )
+    .unwrap();
+    assert_eq!(body.content_type(), "application/x-www-form-urlencoded");
+    assert_eq!(
+        String::from_utf8(body.bytes).unwrap(),
+        "q=a+b%26c&limit=10&tag=x&tag=y&empty="
+    );
+}
+
+#[test]
+fn test_form_body_rejects_nested_values() {
+    for body in [json!(["a"]), json!({"filter": {"status": "open"}})] {
+        let err = RequestBody::encode(&body, BodyFormat::Form).unwrap_err();
+        assert!(matches!(err, ApitapError::ConfigError(_)));
+    }
+}
+
+#[test]
+fn test_source_method_and_body_config() {
+    let config_yaml = r#"
+sources:
+  - name: search
+    url: https://api.example.com/search
+    data_path: /hits
+    primary_key_in_dest: id
+    retry: {max_attempts: 3, max_delay_secs: 10, min_delay_secs: 1}
+    method: POST
+    body:
+      query: status:open
+    content_type: form
+  - name: plain
+    url: https://api.example.com/plain
+    data_path: null
+    primary_key_in_dest: id
+    retry: {max_attempts: 3, max_delay_secs: 10, min_delay_secs: 1}
+targets: []
+"#;
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+    let search = config.source("search").unwrap();
+    assert_eq!(search.method, RequestMethod::Post);
+    assert_eq!(search.body, Some(json!({"query": "status:open"})));
+    assert_eq!(search.content_type, BodyFormat::Form);
+
+    let plain = config.source("plain").unwrap();
+    assert_eq!(plain.method, RequestMethod::Get);
+    assert_eq!(plain.body, None);
+    assert_eq!(plain.content_type, BodyFormat::Json);
+}
+
+#[tokio::test]
+async fn test_post_body_is_sent_with_page_request() {
+    let (url, received) = serve_once(json!({"hits": [{"id": 1}, {"id": 2}]})).await;
+    let request = RequestOptions {
+        method: RequestMethod::Post,
+        body: Some(
+            RequestBody::encode(&json!({"query": "status:open"}), BodyFormat::Json).unwrap(),
+        ),
+        ..Default::default()
+    };
+
+    let rows: Vec<Value> = ndjson_stream_with(
+        &reqwest::Client::new(),
+        &url,
+        &[("page".to_string(), "1".to_string())],
+        Some("/hits"),
+        &retry(),
+        &request,
+    )
+    .await
+    .unwrap()
+    .try_collect()
+    .await
+    .unwrap();
+    assert_eq!(rows, vec![json!({"id": 1}), json!({"id": 2})]);
+
+    let req = received.lock().unwrap().take().unwrap();
+    assert_eq!(req.method, "POST");
+    assert_eq!(req.target, "/search?page=1");
+    assert_eq!(req.header("content-type"), Some("application/json"));
+    assert_eq!(req.body_text(), r#"{"query":"status:open"}"#);
+}
@@ -0,0 +1,58 @@
+// Tests for per-source client settings (`http:`)
+
+use std::time::{Duration, Instant};
+
+use super::{serve, StubResponse};
+use apitap::http::client::{ClientSettings, HttpSettings};
+use apitap::http::Http;
+
+#[test]
+fn test_settings_over_defaults() {
+    let settings: HttpSettings = serde_yaml::from_str(
+        "{request_timeout: 2m, connect_timeout: 5s, pool_max_idle_per_host: 4, tcp_keepalive: 0s, http2_prior_knowledge: true}",
+    )
+    .unwrap();
+    assert_eq!(
+        settings.resolve().unwrap(),
+        ClientSettings {
+            request_timeout: Duration::from_secs(120),
+            connect_timeout: Duration::from_secs(5),
+            pool_max_idle_per_host: 4,
+            pool_idle_timeout: Duration::from_secs(90),
+            tcp_keepalive: None,
+            http2_prior_knowledge: true,
+        }
+    );
+    assert_eq!(
+        HttpSettings::default().resolve().unwrap(),
+        ClientSettings::default()
+    );
+}
+
+#[test]
+fn test_invalid_timeouts() {
+    for yaml in ["{request_timeout: 0s}", "{connect_timeout: soon}"] {
+        let settings: HttpSettings = serde_yaml::from_str(yaml).unwrap();
+        assert!(settings.resolve().is_err(), "{yaml} was accepted");
+    }
+}
+
+#[tokio::test]
+async fn test_request_timeout_applies() {
+    // Answers only after the client has given up.
+    let base = serve(|_| StubResponse::json("[]").delayed(Duration::from_secs(30))).await;
+    let url = format!("{base}/slow");
+
+    let settings = HttpSettings {
+        request_timeout: Some("1s".into()),
+        ..Default::default()
+    };
+    let client = Http::new(&url)
+        .settings(settings.resolve().unwrap())
+        .build_client()
+        .unwrap();
+    let started = Instant::now();
+    let err = client.get(&url).send().await.unwrap_err();
+    assert!(err.is_timeout());
+    assert!(started.elapsed() < Duration::from_secs(10));
+}
@@ -0,0 +1,121 @@
+use super::{retry, serve, StubResponse};
+use apitap::errors::{ApitapError, Result};
+use apitap::http::csv_stream::{csv_stream, CsvOptions, ResponseFormat};
+use apitap::http::fetcher::{ndjson_stream_with, RequestOptions};
+use apitap::pipeline::Config;
+use futures::TryStreamExt;
+use serde_json::{json, Value};
+
+/// Feed `body` to the parser in chunks of `size` bytes.
+async fn parse(body: &str, size: usize, options: &CsvOptions) -> Result<Vec<Value>> {
+    let chunks: Vec<std::result::Result<Vec<u8>, ApitapError>> = body
+        .as_bytes()
+        .chunks(size)
+        .map(|c| Ok(c.to_vec()))
+        .collect();
+    csv_stream(futures::stream::iter(chunks), options)?
+        .try_collect()
+        .await
+}
+
+#[tokio::test]
+async fn test_csv_rows_keyed_by_header() {
+    let body = "id,name,score,active,zip,note\n\
+                1,Ada,9.5,true,01234,\n\
+                2,\"Lovelace, A.\",-3,false,90210,\"said \"\"hi\"\"\"\n";
+    let expected = vec![
+        json!({"id": 1, "name": "Ada", "score": 9.5, "active": true, "zip": "01234", "note": null}),
+        json!({"id": 2, "name": "Lovelace, A.", "score": -3, "active": false, "zip": 90210, "note": "said \"hi\""}),
+    ];
+    // Chunk boundaries inside quoted fields and records must not matter.
+    for size in [1, 3, 7, body.len()] {
+        let rows = parse(body, size, &CsvOptions::default()).await.unwrap();
+        assert_eq!(rows, expected, "chunk size {size}");
+    }
+}
+
+#[tokio::test]
+async fn test_csv_multiline_fields_and_missing_newline() {
+    let body = "id;text\r\n1;\"line one\nline two\"\r\n2;last";
+    let options = CsvOptions {
+        delimiter: ';',
+        infer_types: false,
+    };
+    let rows = parse(body, 4, &options).await.unwrap();
+    assert_eq!(
+        rows,
+        vec![
+            json!({"id": "1", "text": "line one\nline two"}),
+            json!({"id": "2", "text": "last"}),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_csv_short_records_and_bad_delimiter() {
+    let rows = parse("a,b,c\n1,2\n", 64, &CsvOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(rows, vec![json!({"a": 1, "b": 2, "c": null})]);
+
+    let options = CsvOptions {
+        delimiter: '→',
+        infer_types: true,
+    };
+    assert!(matches!(
+        parse("a\n1\n", 64, &options).await,
+        Err(ApitapError::ConfigError(_))
+    ));
+}
+
+#[test]
+fn test_csv_source_config() {
+    let config_yaml = r#"
+sources:
+  - name: export
+    url: https://api.example.com/export.csv
+    data_path: null
+    primary_key_in_dest: id
+    retry: {max_attempts: 3, max_delay_secs: 10, min_delay_secs: 1}
+    format: csv
+    csv:
+      delimiter: "\t"
+      infer_types: false
+targets: []
+"#;
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+    let src = config.source("export").unwrap();
+    assert_eq!(src.format, ResponseFormat::Csv);
+    assert_eq!(src.csv.delimiter, '\t');
+    assert!(!src.csv.infer_types);
+}
+
+#[tokio::test]
+async fn test_text_csv_response_is_parsed() {
+    let base = serve(|_| {
+        StubResponse::ok("id,city\n1,Paris\n2,Oslo\n")
+            .header("Content-Type", "text/csv; charset=utf-8")
+    })
+    .await;
+
+    let rows: Vec<Value> = ndjson_stream_with(
+        &reqwest::Client::new(),
+        &format!("{base}/export"),
+        &[],
+        None,
+        &retry(),
+        &RequestOptions::default(),
+    )
+    .await
+    .unwrap()
+    .try_collect()
+    .await
+    .unwrap();
+    assert_eq!(
+        rows,
+        vec![
+            json!({"id": 1, "city": "Paris"}),
+            json!({"id": 2, "city": "Oslo"})
+        ]
+    );
+}
@@ -0,0 +1,122 @@
+use std::io::Write;
+
+use super::{retry, serve, StubResponse};
+use apitap::http::decompress::{decompressed, Encoding};
+use apitap::http::fetcher::{ndjson_stream_with, RequestOptions};
+use apitap::http::Http;
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+use futures::{stream, TryStreamExt};
+use reqwest::header::{HeaderMap, HeaderValue};
+use serde_json::json;
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut enc = GzEncoder::new(Vec::new(), Compression::default());
+    enc.write_all(data).unwrap();
+    enc.finish().unwrap()
+}
+
+async fn collect(chunks: Vec<Vec<u8>>, hint: Option<Encoding>) -> Vec<u8> {
+    let body = stream::iter(chunks.into_iter().map(Ok::<_, std::io::Error>));
+    let out: Vec<_> = decompressed(body, hint).try_collect().await.unwrap();
+    out.concat()
+}
+
+/// Answer with `headers` and `body`.
+async fn serve_once(headers: &'static [(&'static str, &'static str)], body: Vec<u8>) -> String {
+    let base = serve(move |_| {
+        headers
+            .iter()
+            .fold(StubResponse::ok(body.clone()), |resp, (k, v)| {
+                resp.header(k, v)
+            })
+    })
+    .await;
+    format!("{base}/export")
+}
+
+#[test]
+fn test_encoding_from_headers() {
+    let mut headers = HeaderMap::new();
+    assert_eq!(Encoding::from_headers(&headers), None);
+    headers.insert(
+        "content-type",
+        HeaderValue::from_static("application/x-gzip"),
+    );
+    assert_eq!(Encoding::from_headers(&headers), Some(Encoding::Gzip));
+    headers.insert("content-encoding", HeaderValue::from_static("br"));
+    assert_eq!(Encoding::from_headers(&headers), Some(Encoding::Brotli));
+    assert_eq!(Encoding::from_token("deflate"), Some(Encoding::Deflate));
+    assert_eq!(Encoding::from_token("identity"), None);
+}
+
+#[tokio::test]
+async fn test_decompressed_sniffs_and_passes_through() {
+    let data = b"{\"id\":1}\n{\"id\":2}\n".to_vec();
+    let gz = gzip(&data);
+    // Split inside the header, after an empty chunk.
+    let chunks = vec![Vec::new(), gz[..1].to_vec(), gz[1..].to_vec()];
+    assert_eq!(collect(chunks, None).await, data);
+    assert_eq!(collect(vec![data.clone()], None).await, data);
+
+    // Concatenated members decode as one body.
+    let two = [gzip(b"a\n"), gzip(b"b\n")].concat();
+    assert_eq!(collect(vec![two], None).await, b"a\nb\n");
+
+    let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+    zlib.write_all(&data).unwrap();
+    let zlib = zlib.finish().unwrap();
+    assert_eq!(collect(vec![zlib], Some(Encoding::Deflate)).await, data);
+}
+
+#[tokio::test]
+async fn test_gzipped_ndjson_content_encoding() {
+    let body = gzip(b"{\"id\":1}\n{\"id\":2}\n");
+    let url = serve_once(
+        &[
+            ("content-type", "application/x-ndjson"),
+            ("content-encoding", "gzip"),
+        ],
+        body,
+    )
+    .await;
+
+    let rows: Vec<_> = ndjson_stream_with(
+        &Http::new("unused").build_client().unwrap(),
+        &url,
+        &[],
+        None,
+        &retry(),
+        &RequestOptions::default(),
+    )
+    .await
+    .unwrap()
+    .try_collect()
+    .await
+    .unwrap();
+    assert_eq!(rows, vec![json!({"id": 1}), json!({"id": 2})]);
+}
+
+#[tokio::test]
+async fn test_gzip_download_parsed_as_ndjson() {
+    let body = gzip(b"{\"data\":[{\"id\":1}]}\n{\"data\":[{\"id\":2},{\"id\":3}]}\n");
+    let url = serve_once(&[("content-type", "application/gzip")], body).await;
+
+    let rows: Vec<_> = ndjson_stream_with(
+        &Http::new("unused").build_client().unwrap(),
+        &url,
+        &[],
+        Some("/data"),
+        &retry(),
+        &RequestOptions::default(),
+    )
+    .await
+    .unwrap()
+    .try_collect()
+    .await
+    .unwrap();
+    assert_eq!(
+        rows,
+        vec![json!({"id": 1}), json!({"id": 2}), json!({"id": 3})]
+    );
+}
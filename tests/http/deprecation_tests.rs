@@ -0,0 +1,119 @@
+use super::{retry, serve, StubResponse};
+use apitap::http::deprecation::{ApiVersion, DeprecationNotice, DeprecationWatch};
+use apitap::http::fetcher::{ndjson_stream_with, RequestOptions};
+use apitap::http::Http;
+use chrono::{TimeZone, Utc};
+use futures::TryStreamExt;
+use reqwest::header::{HeaderMap, HeaderValue};
+use std::sync::{Arc, Mutex};
+
+fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+    let mut map = HeaderMap::new();
+    for (name, value) in pairs {
+        map.append(*name, HeaderValue::from_str(value).unwrap());
+    }
+    map
+}
+
+#[test]
+fn test_api_version_shorthand() {
+    let version: ApiVersion = serde_yaml::from_str("\"2023-10\"").unwrap();
+    assert_eq!(version.value, "2023-10");
+    assert_eq!(version.header.as_deref(), Some("Api-Version"));
+    assert_eq!(version.param, None);
+}
+
+#[test]
+fn test_api_version_header_or_param() {
+    let version: ApiVersion =
+        serde_yaml::from_str("value: 2023-10-16\nheader: Stripe-Version\n").unwrap();
+    assert_eq!(version.header.as_deref(), Some("Stripe-Version"));
+    assert_eq!(version.param, None);
+
+    let version: ApiVersion =
+        serde_yaml::from_str("value: 2024-02-01\nparam: api-version\n").unwrap();
+    assert_eq!(version.header, None);
+    assert_eq!(version.param.as_deref(), Some("api-version"));
+    let http = version.apply(Http::new("https://api.example.com/items"));
+    assert_eq!(
+        http.get_url(),
+        "https://api.example.com/items?api-version=2024-02-01"
+    );
+}
+
+#[test]
+fn test_deprecation_notice_from_headers() {
+    assert_eq!(DeprecationNotice::from_headers(&headers(&[])), None);
+
+    let notice = DeprecationNotice::from_headers(&headers(&[
+        ("deprecation", "@1688169599"),
+        ("sunset", "Sun, 30 Jun 2024 23:59:59 GMT"),
+        (
+            "link",
+            "<https://api.example.com/items?page=2>; rel=\"next\", <https://developer.example.com/deprecation>; rel=\"deprecation\"",
+        ),
+    ]))
+    .unwrap();
+    assert_eq!(notice.deprecation.as_deref(), Some("@1688169599"));
+    assert_eq!(
+        notice.link.as_deref(),
+        Some("https://developer.example.com/deprecation")
+    );
+    assert_eq!(
+        notice.sunset_at(),
+        Some(Utc.with_ymd_and_hms(2024, 6, 30, 23, 59, 59).unwrap())
+    );
+}
+
+#[test]
+fn test_deprecation_watch_keeps_latest_notice() {
+    let watch = DeprecationWatch::new("orders");
+    watch.observe(&headers(&[("content-type", "application/json")]));
+    assert_eq!(watch.notice(), None);
+
+    watch.observe(&headers(&[("deprecation", "true")]));
+    watch.observe(&headers(&[("deprecation", "true")]));
+    assert_eq!(watch.notice().unwrap().deprecation.as_deref(), Some("true"));
+}
+
+#[tokio::test]
+async fn test_pinned_version_sent_and_sunset_recorded() {
+    let received = Arc::new(Mutex::new(None));
+    let seen = Arc::clone(&received);
+    let base = serve(move |req| {
+        *seen.lock().unwrap() = Some(req);
+        StubResponse::json(r#"[{"id":1}]"#).header("Sunset", "Wed, 31 Dec 2025 00:00:00 GMT")
+    })
+    .await;
+
+    let version: ApiVersion =
+        serde_yaml::from_str("value: \"2023-10\"\nheader: X-Api-Version\n").unwrap();
+    let client = version.apply(Http::new("unused")).build_client().unwrap();
+    let watch = Arc::new(DeprecationWatch::new("items"));
+    let request = RequestOptions {
+        deprecation: Some(Arc::clone(&watch)),
+        ..Default::default()
+    };
+
+    let rows: Vec<_> = ndjson_stream_with(
+        &client,
+        &format!("{base}/items"),
+        &[],
+        None,
+        &retry(),
+        &request,
+    )
+    .await
+    .unwrap()
+    .try_collect()
+    .await
+    .unwrap();
+
+    assert_eq!(rows.len(), 1);
+    let received = received.lock().unwrap().take().unwrap();
+    assert_eq!(received.header("x-api-version"), Some("2023-10"));
+    assert_eq!(
+        watch.notice().unwrap().sunset.as_deref(),
+        Some("Wed, 31 Dec 2025 00:00:00 GMT")
+    );
+}
@@ -0,0 +1,85 @@
+use super::{retry, StubResponse};
+use apitap::http::failure_samples::{redact_url, FailureSample, FailureSampler};
+use apitap::http::fetcher::{send_page_request, RequestOptions};
+use std::sync::Arc;
+
+/// `/ok` answers 200; anything else 503 with an error body and a mix of
+/// harmless and sensitive headers.
+async fn serve() -> String {
+    super::serve(|req| {
+        if req.target.starts_with("/ok") {
+            StubResponse::ok("[]")
+        } else {
+            StubResponse::new("503 Service Unavailable", r#"{"error":"upstream timeout"}"#)
+                .header("Content-Type", "application/json")
+                .header("X-Request-Id", "req-42")
+                .header("Set-Cookie", "sid=abc")
+                .header("X-Api-Key", "k-123")
+        }
+    })
+    .await
+}
+
+#[test]
+fn test_redact_url() {
+    let url = reqwest::Url::parse(
+        "https://user:pw@api.example.com/v1/items?page=2&api_key=s3cret&access_token=t",
+    )
+    .unwrap();
+    assert_eq!(
+        redact_url(&url),
+        "https://api.example.com/v1/items?page=2&api_key=%5Bredacted%5D&access_token=%5Bredacted%5D"
+    );
+}
+
+#[tokio::test]
+async fn test_failed_pages_are_sampled_up_to_the_limit() {
+    let base = serve().await;
+    let dir = tempfile::tempdir().unwrap();
+    let request = RequestOptions {
+        failure_samples: Some(Arc::new(FailureSampler::new(dir.path().join("orders"), 2))),
+        ..Default::default()
+    };
+    let client = reqwest::Client::new();
+    let query = [
+        ("page".to_string(), "1".to_string()),
+        ("token".to_string(), "t-9".to_string()),
+    ];
+
+    send_page_request(&client, &format!("{base}/ok"), &[], &retry(), &request)
+        .await
+        .unwrap();
+    for _ in 0..3 {
+        let err = send_page_request(
+            &client,
+            &format!("{base}/items"),
+            &query,
+            &retry(),
+            &request,
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("503"), "{err}");
+    }
+
+    let mut files: Vec<String> = std::fs::read_dir(dir.path().join("orders"))
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    files.sort();
+    assert_eq!(files, vec!["1.json", "2.json"]);
+
+    let sample: FailureSample =
+        serde_json::from_slice(&std::fs::read(dir.path().join("orders/1.json")).unwrap()).unwrap();
+    assert_eq!(sample.status, 503);
+    assert_eq!(sample.body, r#"{"error":"upstream timeout"}"#);
+    assert!(!sample.truncated);
+    assert!(
+        sample.url.ends_with("/items?page=1&token=%5Bredacted%5D"),
+        "{}",
+        sample.url
+    );
+    assert_eq!(sample.headers["x-request-id"], "req-42");
+    assert_eq!(sample.headers["set-cookie"], "[redacted]");
+    assert_eq!(sample.headers["x-api-key"], "[redacted]");
+}
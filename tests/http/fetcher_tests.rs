@@ -1,4 +1,9 @@
-use apitap::http::fetcher::{FetchStats, Pagination};
+use super::{retry, serve, StubResponse};
+use apitap::http::fetcher::{
+    ndjson_stream_with, push_records, FetchStats, Pagination, RequestOptions,
+};
+use futures::TryStreamExt;
+use serde_json::{json, Value};
 
 #[test]
 fn test_fetch_stats_new() {
@@ -24,6 +29,11 @@ fn test_pagination_limit_offset_serialization() {
     let pagination = Pagination::LimitOffset {
         limit_param: "limit".to_string(),
         offset_param: "offset".to_string(),
+        total_items_pointer: None,
+        total_pages_pointer: None,
+        total_items_header: None,
+        total_pages_header: None,
+        stop_when: None,
     };
 
     let serialized = serde_json::to_string(&pagination).unwrap();
@@ -36,6 +46,7 @@ fn test_pagination_limit_offset_serialization() {
         Pagination::LimitOffset {
             limit_param,
             offset_param,
+            ..
         } => {
             assert_eq!(limit_param, "limit");
             assert_eq!(offset_param, "offset");
@@ -49,6 +60,11 @@ fn test_pagination_page_number_serialization() {
     let pagination = Pagination::PageNumber {
         page_param: "page".to_string(),
         per_page_param: "per_page".to_string(),
+        total_items_pointer: None,
+        total_pages_pointer: None,
+        total_items_header: None,
+        total_pages_header: None,
+        stop_when: None,
     };
 
     let serialized = serde_json::to_string(&pagination).unwrap();
@@ -59,6 +75,7 @@ fn test_pagination_page_number_serialization() {
         Pagination::PageNumber {
             page_param,
             per_page_param,
+            ..
         } => {
             assert_eq!(page_param, "page");
             assert_eq!(per_page_param, "per_page");
@@ -90,6 +107,8 @@ fn test_pagination_cursor_serialization() {
     let pagination = Pagination::Cursor {
         cursor_param: "cursor".to_string(),
         page_size_param: Some("size".to_string()),
+        next_cursor_path: None,
+        next_cursor_header: None,
     };
 
     let serialized = serde_json::to_string(&pagination).unwrap();
@@ -100,6 +119,7 @@ fn test_pagination_cursor_serialization() {
         Pagination::Cursor {
             cursor_param,
             page_size_param,
+            ..
         } => {
             assert_eq!(cursor_param, "cursor");
             assert_eq!(page_size_param, Some("size".to_string()));
@@ -113,12 +133,15 @@ fn test_pagination_cursor_without_page_size() {
     let pagination = Pagination::Cursor {
         cursor_param: "next".to_string(),
         page_size_param: None,
+        next_cursor_path: None,
+        next_cursor_header: None,
     };
 
     match pagination {
         Pagination::Cursor {
             cursor_param,
             page_size_param,
+            ..
         } => {
             assert_eq!(cursor_param, "next");
             assert!(page_size_param.is_none());
@@ -143,6 +166,11 @@ fn test_pagination_debug_format() {
     let pagination = Pagination::LimitOffset {
         limit_param: "limit".to_string(),
         offset_param: "offset".to_string(),
+        total_items_pointer: None,
+        total_pages_pointer: None,
+        total_items_header: None,
+        total_pages_header: None,
+        stop_when: None,
     };
 
     let debug_str = format!("{:?}", pagination);
@@ -154,6 +182,11 @@ fn test_pagination_clone() {
     let pagination = Pagination::PageNumber {
         page_param: "page".to_string(),
         per_page_param: "per_page".to_string(),
+        total_items_pointer: None,
+        total_pages_pointer: None,
+        total_items_header: None,
+        total_pages_header: None,
+        stop_when: None,
     };
 
     let cloned = pagination.clone();
@@ -163,10 +196,12 @@ fn test_pagination_clone() {
             Pagination::PageNumber {
                 page_param: p1,
                 per_page_param: pp1,
+                ..
             },
             Pagination::PageNumber {
                 page_param: p2,
                 per_page_param: pp2,
+                ..
             },
         ) => {
             assert_eq!(p1, p2);
@@ -182,6 +217,7 @@ fn test_fetch_stats_clone() {
         success_count: 5,
         error_count: 2,
         total_items: 100,
+        ..Default::default()
     };
 
     let cloned = stats.clone();
@@ -197,6 +233,7 @@ fn test_fetch_stats_debug() {
         success_count: 3,
         error_count: 1,
         total_items: 50,
+        ..Default::default()
     };
 
     let debug_str = format!("{:?}", stats);
@@ -210,10 +247,20 @@ fn test_pagination_variants() {
         Pagination::LimitOffset {
             limit_param: "limit".to_string(),
             offset_param: "offset".to_string(),
+            total_items_pointer: None,
+            total_pages_pointer: None,
+            total_items_header: None,
+            total_pages_header: None,
+            stop_when: None,
         },
         Pagination::PageNumber {
             page_param: "page".to_string(),
             per_page_param: "size".to_string(),
+            total_items_pointer: None,
+            total_pages_pointer: None,
+            total_items_header: None,
+            total_pages_header: None,
+            stop_when: None,
         },
         Pagination::PageOnly {
             page_param: "p".to_string(),
@@ -221,6 +268,8 @@ fn test_pagination_variants() {
         Pagination::Cursor {
             cursor_param: "cursor".to_string(),
             page_size_param: Some("limit".to_string()),
+            next_cursor_path: None,
+            next_cursor_header: None,
         },
         Pagination::Default,
     ];
@@ -242,6 +291,7 @@ offset_param: skip
         Pagination::LimitOffset {
             limit_param,
             offset_param,
+            ..
         } => {
             assert_eq!(limit_param, "max");
             assert_eq!(offset_param, "skip");
@@ -264,6 +314,7 @@ per_page_param: pageSize
         Pagination::PageNumber {
             page_param,
             per_page_param,
+            ..
         } => {
             assert_eq!(page_param, "pageNum");
             assert_eq!(per_page_param, "pageSize");
@@ -286,6 +337,7 @@ page_size_param: maxResults
         Pagination::Cursor {
             cursor_param,
             page_size_param,
+            ..
         } => {
             assert_eq!(cursor_param, "nextToken");
             assert_eq!(page_size_param, Some("maxResults".to_string()));
@@ -293,3 +345,44 @@ page_size_param: maxResults
         _ => panic!("Expected Cursor"),
     }
 }
+
+#[tokio::test]
+async fn test_missing_data_path_yields_no_records() {
+    let mut rows = Vec::new();
+    push_records(json!({"items": [{"id": 1}]}), Some("/data"), &mut rows);
+    push_records(json!({"data": null}), Some("/data"), &mut rows);
+    assert!(rows.is_empty());
+    push_records(json!({"data": [{"id": 1}]}), Some("/data"), &mut rows);
+    assert_eq!(rows, vec![json!({"id": 1})]);
+
+    // JSON and NDJSON responses take the same records.
+    let base = serve(|req| {
+        if req.target.starts_with("/ndjson") {
+            StubResponse::ok("{\"items\":[{\"id\":1}]}\n{\"data\":[{\"id\":2}]}\n")
+                .header("Content-Type", "application/x-ndjson")
+        } else {
+            StubResponse::json(json!({"items": [{"id": 1}]}))
+        }
+    })
+    .await;
+    for path in ["/json", "/ndjson"] {
+        let rows: Vec<Value> = ndjson_stream_with(
+            &reqwest::Client::new(),
+            &format!("{base}{path}"),
+            &[],
+            Some("/data"),
+            &retry(),
+            &RequestOptions::default(),
+        )
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+        let expected: Vec<Value> = match path {
+            "/json" => vec![],
+            _ => vec![json!({"id": 2})],
+        };
+        assert_eq!(rows, expected, "{path}");
+    }
+}
@@ -0,0 +1,39 @@
+use apitap::http::fetcher::batched;
+use futures::StreamExt;
+use std::time::Duration;
+
+/// Three rows, 150ms apart, like a slow API trickling data.
+fn trickle() -> futures::stream::BoxStream<'static, u32> {
+    async_stream::stream! {
+        for row in 1..=3 {
+            if row > 1 {
+                tokio::time::sleep(Duration::from_millis(150)).await;
+            }
+            yield row;
+        }
+    }
+    .boxed()
+}
+
+#[tokio::test]
+async fn test_batches_wait_for_size_without_interval() {
+    let batches: Vec<Vec<u32>> = batched(trickle(), 10, None).collect().await;
+    assert_eq!(batches, vec![vec![1, 2, 3]]);
+}
+
+#[tokio::test]
+async fn test_flush_interval_cuts_partial_batches() {
+    let batches: Vec<Vec<u32>> = batched(trickle(), 10, Some(Duration::from_millis(30)))
+        .collect()
+        .await;
+    assert_eq!(batches, vec![vec![1], vec![2], vec![3]]);
+}
+
+#[tokio::test]
+async fn test_full_batches_flush_before_interval() {
+    let rows = futures::stream::iter(1..=5).boxed();
+    let batches: Vec<Vec<u32>> = batched(rows, 2, Some(Duration::from_secs(60)))
+        .collect()
+        .await;
+    assert_eq!(batches, vec![vec![1, 2], vec![3, 4], vec![5]]);
+}
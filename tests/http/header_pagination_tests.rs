@@ -0,0 +1,208 @@
+use super::{retry, serve, CollectRows, StubResponse};
+use apitap::http::fetcher::{PaginatedFetcher, Pagination, TotalHint};
+use apitap::writer::WriteMode;
+use reqwest::header::{HeaderMap, HeaderValue};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+/// Seven items by `offset`/`limit` or `page`/`per_page`, GitLab style:
+/// totals in `X-Total-Count` and `X-Total-Pages`, the next page in
+/// `X-Next-Page` (empty on the last page) and, in the body, as `next`.
+/// Returns the url and the request log.
+async fn serve_items() -> (String, Arc<Mutex<Vec<String>>>) {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&seen);
+    let base = serve(move |req| {
+        log.lock().unwrap().push(req.target.clone());
+        let size = req.num("limit").or(req.num("per_page")).unwrap_or(3);
+        let page = req.num("page").unwrap_or(1);
+        let from = req.num("offset").unwrap_or((page - 1) * size).min(7);
+        let to = (from + size).min(7);
+        let items: Vec<Value> = (from..to).map(|id| json!({"id": id})).collect();
+        let next = if to < 7 {
+            (page + 1).to_string()
+        } else {
+            String::new()
+        };
+        StubResponse::json(json!({"items": items, "next": next}))
+            .header("X-Total-Count", 7)
+            .header("X-Total-Pages", 7 / size + u64::from(7 % size != 0))
+            .header("X-Next-Page", next)
+    })
+    .await;
+    (format!("{base}/items"), seen)
+}
+
+#[test]
+fn test_header_totals_from_yaml() {
+    let pagination: Pagination = serde_yaml::from_str(
+        "kind: limit_offset\nlimit_param: limit\noffset_param: offset\ntotal_items_header: X-Total-Count",
+    )
+    .unwrap();
+    assert!(matches!(
+        pagination.total_hint().unwrap(),
+        Some(TotalHint::ItemsHeader { header }) if header == "X-Total-Count"
+    ));
+
+    let both: Pagination = serde_yaml::from_str(
+        "kind: page_number\npage_param: page\nper_page_param: per_page\ntotal_pages_pointer: /meta/pages\ntotal_pages_header: X-Total-Pages",
+    )
+    .unwrap();
+    assert!(both.total_hint().is_err());
+}
+
+#[test]
+fn test_pages_from_header_totals() {
+    let mut headers = HeaderMap::new();
+    headers.insert("x-total-count", HeaderValue::from_static(" 7 "));
+    headers.insert("x-total-pages", HeaderValue::from_static("4"));
+    let items = TotalHint::ItemsHeader {
+        header: "X-Total-Count".into(),
+    };
+    let pages = TotalHint::PagesHeader {
+        header: "X-Total-Pages".into(),
+    };
+    assert_eq!(
+        items.pages_after_response(&headers, &json!({}), 3, 0),
+        Some(3)
+    );
+    assert_eq!(
+        items.pages_after_response(&headers, &json!({}), 3, 3),
+        Some(2)
+    );
+    assert_eq!(
+        pages.pages_after_response(&headers, &json!({}), 2, 0),
+        Some(4)
+    );
+    // A header total is unknown without the headers; body totals ignore them.
+    assert_eq!(items.pages_after(&json!({"total": 7}), 3, 0), None);
+    assert_eq!(
+        TotalHint::Items {
+            pointer: "/total".into()
+        }
+        .pages_after_response(&headers, &json!({"total": 4}), 3, 0),
+        Some(2)
+    );
+}
+
+#[tokio::test]
+async fn test_total_count_header_limits_limit_offset() {
+    let (url, seen) = serve_items().await;
+    let pagination: Pagination = serde_yaml::from_str(
+        "kind: limit_offset\nlimit_param: limit\noffset_param: offset\ntotal_items_header: X-Total-Count",
+    )
+    .unwrap();
+    let fetcher =
+        PaginatedFetcher::new(reqwest::Client::new(), url, 2).with_pagination(pagination.clone());
+    let writer = Arc::new(CollectRows::default());
+    fetcher
+        .fetch_limit_offset(
+            3,
+            Some("/items".into()),
+            None,
+            pagination.total_hint().unwrap(),
+            writer.clone(),
+            WriteMode::Append,
+            &retry(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(writer.ids(), (0..7).collect::<Vec<_>>());
+    // No trailing empty page.
+    assert_eq!(seen.lock().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn test_total_pages_header_limits_page_number() {
+    let (url, seen) = serve_items().await;
+    let pagination: Pagination = serde_yaml::from_str(
+        "kind: page_number\npage_param: page\nper_page_param: per_page\ntotal_pages_header: X-Total-Pages",
+    )
+    .unwrap();
+    let fetcher =
+        PaginatedFetcher::new(reqwest::Client::new(), url, 2).with_pagination(pagination.clone());
+    let writer = Arc::new(CollectRows::default());
+    fetcher
+        .fetch_page_number(
+            2,
+            Some("/items"),
+            None,
+            pagination.total_hint().unwrap(),
+            writer.clone(),
+            WriteMode::Append,
+            &retry(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(writer.ids(), (0..7).collect::<Vec<_>>());
+    assert_eq!(seen.lock().unwrap().len(), 4);
+}
+
+#[tokio::test]
+async fn test_cursor_from_next_page_header() {
+    let (url, seen) = serve_items().await;
+    let pagination: Pagination = serde_yaml::from_str(
+        "kind: cursor\ncursor_param: page\npage_size_param: per_page\nnext_cursor_header: X-Next-Page",
+    )
+    .unwrap();
+    let fetcher = PaginatedFetcher::new(reqwest::Client::new(), url, 1).with_pagination(pagination);
+    let writer = Arc::new(CollectRows::default());
+    fetcher
+        .fetch_follow_next(
+            3,
+            Some("/items"),
+            Some(&[("region".to_string(), "eu".to_string())]),
+            writer.clone(),
+            WriteMode::Append,
+            &retry(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(writer.ids(), (0..7).collect::<Vec<_>>());
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![
+            "/items?region=eu&per_page=3",
+            "/items?region=eu&per_page=3&page=2",
+            "/items?region=eu&per_page=3&page=3",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_cursor_from_body() {
+    let (url, seen) = serve_items().await;
+    let pagination: Pagination = serde_yaml::from_str(
+        "kind: cursor\ncursor_param: page\npage_size_param: per_page\nnext_cursor_path: /next",
+    )
+    .unwrap();
+    let fetcher = PaginatedFetcher::new(reqwest::Client::new(), url, 1).with_pagination(pagination);
+    let writer = Arc::new(CollectRows::default());
+    fetcher
+        .fetch_follow_next(
+            3,
+            Some("/items"),
+            None,
+            writer.clone(),
+            WriteMode::Append,
+            &retry(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(writer.ids(), (0..7).collect::<Vec<_>>());
+    assert_eq!(seen.lock().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn test_cursor_needs_a_source() {
+    let pagination: Pagination =
+        serde_yaml::from_str("kind: cursor\ncursor_param: page\npage_size_param: null").unwrap();
+    let fetcher = PaginatedFetcher::new(reqwest::Client::new(), "http://127.0.0.1:9/", 1)
+        .with_pagination(pagination);
+    let writer = Arc::new(CollectRows::default());
+    let err = fetcher
+        .fetch_follow_next(3, None, None, writer, WriteMode::Append, &retry())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("next_cursor_path"));
+}
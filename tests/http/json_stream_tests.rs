@@ -0,0 +1,74 @@
+use apitap::http::json_stream::json_stream;
+use bytes::Bytes;
+use futures::stream::{self, BoxStream};
+use futures::StreamExt;
+use serde_json::{json, Value};
+
+/// `body` served `chunk` bytes at a time, splitting tokens across chunks.
+fn chunked(body: &str, chunk: usize) -> BoxStream<'static, std::io::Result<Bytes>> {
+    let chunks: Vec<std::io::Result<Bytes>> = body
+        .as_bytes()
+        .chunks(chunk)
+        .map(|c| Ok(Bytes::copy_from_slice(c)))
+        .collect();
+    stream::iter(chunks).boxed()
+}
+
+async fn rows(body: &str, data_path: Option<&str>) -> Vec<Value> {
+    json_stream(chunked(body, 3), data_path)
+        .map(|row| row.unwrap())
+        .collect()
+        .await
+}
+
+#[tokio::test]
+async fn test_top_level_array_rows() {
+    assert_eq!(
+        rows(
+            r#"[{"id": 1, "tags": ["a", "b"]}, {"id": 2, "note": "x\"y"}]"#,
+            None
+        )
+        .await,
+        vec![
+            json!({"id": 1, "tags": ["a", "b"]}),
+            json!({"id": 2, "note": "x\"y"})
+        ]
+    );
+    assert_eq!(
+        rows(r#"{"id": 1}"#, None).await,
+        vec![json!({"id": 1})],
+        "a lone object is one row"
+    );
+}
+
+#[tokio::test]
+async fn test_rows_at_pointer_skip_the_rest() {
+    let body = r#"{"meta": {"data": [0]}, "result": {"items": [{"id": 1}, {"id": 2}], "next": null}, "data~/x": [3]}"#;
+    assert_eq!(
+        rows(body, Some("/result/items")).await,
+        vec![json!({"id": 1}), json!({"id": 2})]
+    );
+    assert_eq!(rows(body, Some("/result/next")).await, Vec::<Value>::new());
+    assert_eq!(rows(body, Some("/missing")).await, Vec::<Value>::new());
+    assert_eq!(
+        rows(body, Some("/result/items/1")).await,
+        vec![json!({"id": 2})]
+    );
+    assert_eq!(rows(body, Some("/data~0~1x")).await, vec![json!(3)]);
+}
+
+#[tokio::test]
+async fn test_concatenated_documents() {
+    assert_eq!(
+        rows("{\"id\": 1}\n{\"id\": 2}\n\n", None).await,
+        vec![json!({"id": 1}), json!({"id": 2})]
+    );
+}
+
+#[tokio::test]
+async fn test_broken_body_errors_after_the_rows_before_it() {
+    let mut stream = json_stream(chunked(r#"[{"id": 1}, {"id": "#, 4), None);
+    assert_eq!(stream.next().await.unwrap().unwrap(), json!({"id": 1}));
+    assert!(stream.next().await.unwrap().is_err());
+    assert!(stream.next().await.is_none());
+}
@@ -0,0 +1,217 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{retry, serve, StubResponse};
+use apitap::http::fetcher::{ndjson_stream_with, RequestOptions};
+use apitap::http::limits::{MemoryBudget, Reservation, ResponseLimits};
+use apitap::pipeline::run::FetchSettings;
+use futures::TryStreamExt;
+
+/// Answer with `body`; without `content-length` the body runs until the
+/// connection closes.
+async fn serve_once(content_type: &'static str, body: String, length: bool) -> String {
+    let base = serve(move |_| {
+        let resp = StubResponse::ok(body.clone()).header("content-type", content_type);
+        if length {
+            resp
+        } else {
+            resp.without_length()
+        }
+    })
+    .await;
+    format!("{base}/items")
+}
+
+async fn fetch(url: &str, request: &RequestOptions) -> apitap::errors::Result<usize> {
+    let client = reqwest::Client::new();
+    let rows: Vec<_> = ndjson_stream_with(&client, url, &[], None, &retry(), request)
+        .await?
+        .try_collect()
+        .await?;
+    Ok(rows.len())
+}
+
+fn rows_json(n: usize) -> String {
+    let rows: Vec<String> = (0..n).map(|i| format!(r#"{{"id":{i}}}"#)).collect();
+    format!("[{}]", rows.join(","))
+}
+
+#[test]
+fn test_limits_from_fetch_settings() {
+    let top: FetchSettings =
+        serde_yaml::from_str("max_body_size: 256MB\nmax_line_length: 1KiB\n").unwrap();
+    let source: FetchSettings =
+        serde_yaml::from_str("max_body_size: 1MB\nmax_in_flight: 2MB\n").unwrap();
+    let limits = ResponseLimits::default()
+        .with_overrides(&top)
+        .unwrap()
+        .with_overrides(&source)
+        .unwrap();
+    assert_eq!(
+        limits,
+        ResponseLimits {
+            max_body_size: Some(1_000_000),
+            max_line_length: Some(1024),
+            max_in_flight: Some(2_000_000),
+        }
+    );
+    assert_eq!(
+        ResponseLimits::default()
+            .with_overrides(&FetchSettings::default())
+            .unwrap(),
+        ResponseLimits::default()
+    );
+
+    let bad: FetchSettings = serde_yaml::from_str("max_body_size: huge\n").unwrap();
+    let err = ResponseLimits::default()
+        .with_overrides(&bad)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("max_body_size must be a size"), "{err}");
+}
+
+#[tokio::test]
+async fn test_max_body_size_by_content_length_and_by_bytes_read() {
+    let request = RequestOptions {
+        limits: ResponseLimits {
+            max_body_size: Some(100),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let url = serve_once("application/json", rows_json(3), true).await;
+    assert_eq!(fetch(&url, &request).await.unwrap(), 3);
+
+    let url = serve_once("application/json", rows_json(50), true).await;
+    let err = fetch(&url, &request).await.unwrap_err().to_string();
+    assert!(
+        err.contains("larger than max_body_size (100 bytes)"),
+        "{err}"
+    );
+
+    // No content-length: caught while reading, for streamed formats too.
+    let url = serve_once("application/json", rows_json(50), false).await;
+    let err = fetch(&url, &request).await.unwrap_err().to_string();
+    assert!(err.contains("larger than max_body_size"), "{err}");
+
+    let ndjson = (0..50).map(|i| format!("{{\"id\":{i}}}\n")).collect();
+    let url = serve_once("application/x-ndjson", ndjson, false).await;
+    let err = fetch(&url, &request).await.unwrap_err().to_string();
+    assert!(err.contains("larger than max_body_size"), "{err}");
+}
+
+#[tokio::test]
+async fn test_max_line_length_fails_long_ndjson_line() {
+    let request = RequestOptions {
+        limits: ResponseLimits {
+            max_line_length: Some(32),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let url = serve_once(
+        "application/x-ndjson",
+        "{\"id\":1}\n{\"id\":2}\n".into(),
+        true,
+    )
+    .await;
+    assert_eq!(fetch(&url, &request).await.unwrap(), 2);
+
+    let long = format!("{{\"id\":1}}\n{{\"name\":\"{}\"}}\n", "x".repeat(100));
+    let url = serve_once("application/x-ndjson", long, true).await;
+    let err = fetch(&url, &request).await.unwrap_err().to_string();
+    assert!(
+        err.contains("longer than max_line_length (32 bytes)"),
+        "{err}"
+    );
+}
+
+#[tokio::test]
+async fn test_memory_budget_reservations_wait() {
+    let budget = Arc::new(MemoryBudget::new(100));
+    let mut first = budget.reservation();
+    first.grow(60).await.unwrap();
+
+    let mut second = budget.reservation();
+    let err = second.grow(150).await.unwrap_err().to_string();
+    assert!(
+        err.contains("larger than max_in_flight (100 bytes)"),
+        "{err}"
+    );
+    second.grow(40).await.unwrap();
+    assert_eq!(budget.used(), 100);
+
+    // A third page waits for the first to be written instead of failing.
+    let waiter = {
+        let budget = Arc::clone(&budget);
+        tokio::spawn(async move {
+            let mut third = budget.reservation();
+            third.grow(50).await.unwrap();
+            third
+        })
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!waiter.is_finished());
+    drop(first);
+    let third = tokio::time::timeout(Duration::from_secs(5), waiter)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(budget.used(), 90);
+    drop(second);
+    drop(third);
+    assert_eq!(budget.used(), 0);
+}
+
+#[tokio::test]
+async fn test_memory_budget_stalled_readers_proceed() {
+    let budget = Arc::new(MemoryBudget::new(100));
+    let mut first = budget.reservation();
+    let mut second = budget.reservation();
+    first.grow(50).await.unwrap();
+    second.grow(50).await.unwrap();
+
+    // Both bodies are half read and want more: one goes over the limit,
+    // and the other follows once the first page is written.
+    let read_and_write = |mut r: Reservation| async move {
+        r.grow(10).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    };
+    let (a, b) = tokio::time::timeout(
+        Duration::from_secs(5),
+        futures::future::join(
+            tokio::spawn(read_and_write(first)),
+            tokio::spawn(read_and_write(second)),
+        ),
+    )
+    .await
+    .unwrap();
+    a.unwrap();
+    b.unwrap();
+    assert_eq!(budget.used(), 0);
+}
+
+#[tokio::test]
+async fn test_buffered_page_holds_budget_until_rows_dropped() {
+    let budget = Arc::new(MemoryBudget::new(1_000));
+    let request = RequestOptions {
+        memory: Some(Arc::clone(&budget)),
+        ..Default::default()
+    };
+    let body = rows_json(5);
+    let size = body.len() as u64;
+    let url = serve_once("application/json", body, true).await;
+    let rows = ndjson_stream_with(&reqwest::Client::new(), &url, &[], None, &retry(), &request)
+        .await
+        .unwrap();
+    assert_eq!(budget.used(), size);
+    let rows: Vec<_> = rows.try_collect().await.unwrap();
+    assert_eq!(rows.len(), 5);
+    assert_eq!(budget.used(), 0);
+
+    let url = serve_once("application/json", rows_json(200), true).await;
+    let err = fetch(&url, &request).await.unwrap_err().to_string();
+    assert!(err.contains("larger than max_in_flight"), "{err}");
+    assert_eq!(budget.used(), 0);
+}
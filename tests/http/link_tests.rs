@@ -0,0 +1,115 @@
+use super::{retry, serve, StubResponse};
+use apitap::http::fetcher::{PaginatedFetcher, Pagination};
+use apitap::http::link::{next_link, parse_link_header, LinkValue};
+use futures::TryStreamExt;
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::Url;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+/// Pages 1..=3 linked by `rel="next"`: absolute, then relative, then none.
+/// Records every request line.
+async fn serve_linked_pages() -> (String, Arc<Mutex<Vec<String>>>) {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&seen);
+    let base = Arc::new(Mutex::new(String::new()));
+    let origin = Arc::clone(&base);
+    let url = serve(move |req| {
+        log.lock().unwrap().push(req.target.clone());
+        let page = req.num("page").unwrap_or(1);
+        let origin = origin.lock().unwrap();
+        let link = match page {
+            1 => format!(
+                "<{origin}/issues?page=2&per_page=2>; rel=\"next\", <{origin}/issues?page=3&per_page=2>; rel=\"last\""
+            ),
+            2 => "</issues?page=1>; rel=\"prev first\", </issues?page=3&per_page=2>; rel=\"next\"".to_string(),
+            _ => "</issues?page=1>; rel=\"first\"".to_string(),
+        };
+        StubResponse::json(json!([{"id": page * 10 + 1}, {"id": page * 10 + 2}])).header("Link", link)
+    })
+    .await;
+    *base.lock().unwrap() = url.clone();
+    (format!("{url}/issues"), seen)
+}
+
+#[test]
+fn test_parse_link_header() {
+    let links = parse_link_header(
+        r#"<https://api.example.com/x?page=2>; rel="next", <https://api.example.com/x?page=9>; rel="last"; title="a, b""#,
+    );
+    assert_eq!(
+        links,
+        vec![
+            LinkValue {
+                uri: "https://api.example.com/x?page=2".into(),
+                rels: vec!["next".into()],
+            },
+            LinkValue {
+                uri: "https://api.example.com/x?page=9".into(),
+                rels: vec!["last".into()],
+            },
+        ]
+    );
+    // Unquoted, multi-valued, mixed case; garbage entries are skipped.
+    let links = parse_link_header("junk, </a>; REL=Next; foo=bar, </b>; rel=\"prev first\"");
+    assert_eq!(links[0].rels, vec!["next"]);
+    assert_eq!(links[1].rels, vec!["prev", "first"]);
+    assert!(parse_link_header("").is_empty());
+}
+
+#[test]
+fn test_next_link_resolves_relative() {
+    let base = Url::parse("https://gitlab.example.com/api/v4/projects?page=1").unwrap();
+    let mut headers = HeaderMap::new();
+    assert_eq!(next_link(&headers, &base), None);
+    headers.append("link", HeaderValue::from_static("</first>; rel=\"first\""));
+    headers.append(
+        "link",
+        HeaderValue::from_static("</api/v4/projects?page=2>; rel=\"next\""),
+    );
+    assert_eq!(
+        next_link(&headers, &base).unwrap().as_str(),
+        "https://gitlab.example.com/api/v4/projects?page=2"
+    );
+}
+
+#[test]
+fn test_link_header_pagination_yaml() {
+    let p: Pagination = serde_yaml::from_str("kind: link_header").unwrap();
+    assert!(matches!(
+        p,
+        Pagination::LinkHeader {
+            page_size_param: None
+        }
+    ));
+    let p: Pagination =
+        serde_yaml::from_str("kind: link_header\npage_size_param: per_page").unwrap();
+    assert!(matches!(
+        p,
+        Pagination::LinkHeader { page_size_param: Some(ref s) } if s == "per_page"
+    ));
+}
+
+#[tokio::test]
+async fn test_follows_next_links_until_exhausted() {
+    let (url, seen) = serve_linked_pages().await;
+    let fetcher = PaginatedFetcher::new(reqwest::Client::new(), url, 1)
+        .with_link_header(Some("per_page".into()));
+    let rows: Vec<Value> = fetcher
+        .follow_next_stream(2, None, Some(&[("state".into(), "open".into())]), &retry())
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    let ids: Vec<u64> = rows.iter().map(|r| r["id"].as_u64().unwrap()).collect();
+    assert_eq!(ids, vec![11, 12, 21, 22, 31, 32]);
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![
+            "/issues?state=open&per_page=2",
+            "/issues?page=2&per_page=2",
+            "/issues?page=3&per_page=2",
+        ]
+    );
+}
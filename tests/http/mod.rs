@@ -1,2 +1,332 @@
 mod arrow_type_tests;
+mod auth_tests;
+mod bandwidth_tests;
+mod body_tests;
+mod client_tests;
+mod csv_stream_tests;
+mod decompress_tests;
+mod deprecation_tests;
+mod failure_samples_tests;
 mod fetcher_tests;
+mod flush_interval_tests;
+mod header_pagination_tests;
+mod json_stream_tests;
+mod jwt_tests;
+mod limits_tests;
+mod link_tests;
+mod ndjson_export_tests;
+mod next_url_tests;
+mod oauth2_tests;
+mod paginator_tests;
+mod proxy_tests;
+mod query_tests;
+mod rate_limit_tests;
+mod routing_tests;
+mod sequence_tests;
+mod session_tests;
+mod signing_tests;
+mod sse_stream_tests;
+mod start_tests;
+mod stop_when_tests;
+mod throttle_tests;
+mod total_hint_tests;
+mod usage_tests;
+mod vcr_tests;
+mod xml_stream_tests;
+
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use apitap::errors::Result;
+use apitap::http::fetcher::PageWriter;
+use apitap::pipeline::Retry;
+use apitap::writer::WriteMode;
+use async_trait::async_trait;
+use futures::{Stream, TryStreamExt};
+use serde_json::Value;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// One attempt, no retries.
+pub fn retry() -> Retry {
+    Retry {
+        max_attempts: 0,
+        max_delay_secs: 1,
+        min_delay_secs: 0,
+        ..Default::default()
+    }
+}
+
+/// Raw value of query parameter `name` in a request target.
+pub fn query_param<'a>(target: &'a str, name: &str) -> Option<&'a str> {
+    let (_, query) = target.split_once('?')?;
+    query
+        .split('&')
+        .find_map(|kv| kv.strip_prefix(name)?.strip_prefix('='))
+}
+
+/// Numeric query parameter `name` in a request target.
+pub fn query_num(target: &str, name: &str) -> Option<u64> {
+    query_param(target, name)?.parse().ok()
+}
+
+/// A [`PageWriter`] keeping the pages it is handed, in write order; a
+/// streamed page is kept as page 0.
+#[derive(Default)]
+pub struct CollectRows {
+    pages: Mutex<Vec<(u64, Vec<Value>)>>,
+    committed: AtomicBool,
+}
+
+impl CollectRows {
+    pub fn pages(&self) -> Vec<(u64, Vec<Value>)> {
+        self.pages.lock().unwrap().clone()
+    }
+
+    pub fn page_numbers(&self) -> Vec<u64> {
+        self.pages
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(page, _)| *page)
+            .collect()
+    }
+
+    /// Every row, in write order.
+    pub fn rows(&self) -> Vec<Value> {
+        self.pages
+            .lock()
+            .unwrap()
+            .iter()
+            .flat_map(|(_, rows)| rows.clone())
+            .collect()
+    }
+
+    /// The rows' numeric `id`s, sorted.
+    pub fn ids(&self) -> Vec<u64> {
+        let mut ids: Vec<u64> = self
+            .rows()
+            .iter()
+            .map(|row| row["id"].as_u64().unwrap())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    pub fn committed(&self) -> bool {
+        self.committed.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl PageWriter for CollectRows {
+    async fn write_page(&self, page: u64, data: Vec<Value>, _mode: WriteMode) -> Result<()> {
+        self.pages.lock().unwrap().push((page, data));
+        Ok(())
+    }
+
+    async fn write_page_stream(
+        &self,
+        stream: Pin<Box<dyn Stream<Item = Result<Value>> + Send>>,
+        _mode: WriteMode,
+    ) -> Result<()> {
+        let rows = stream.try_collect().await?;
+        self.pages.lock().unwrap().push((0, rows));
+        Ok(())
+    }
+
+    async fn commit(&self) -> Result<()> {
+        self.committed.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+/// A request received by [`serve`].
+#[derive(Debug, Clone)]
+pub struct StubRequest {
+    /// Request line and headers as sent.
+    pub head: String,
+    pub method: String,
+    /// Path and query, or the absolute URL of a proxied request.
+    pub target: String,
+    /// Header names lowercased.
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl StubRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// A numeric query parameter.
+    pub fn num(&self, name: &str) -> Option<u64> {
+        query_num(&self.target, name)
+    }
+
+    pub fn body_text(&self) -> String {
+        String::from_utf8_lossy(&self.body).to_string()
+    }
+}
+
+/// The answer [`serve`] sends for one request; always `Connection: close`.
+#[derive(Debug, Clone)]
+pub struct StubResponse {
+    status: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    content_length: bool,
+    cut_at: Option<usize>,
+    delay: Duration,
+    hold_open: Duration,
+}
+
+impl StubResponse {
+    pub fn new(status: &str, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status: status.to_string(),
+            headers: Vec::new(),
+            body: body.into(),
+            content_length: true,
+            cut_at: None,
+            delay: Duration::ZERO,
+            hold_open: Duration::ZERO,
+        }
+    }
+
+    /// `200 OK` with a JSON body.
+    pub fn json(body: impl ToString) -> Self {
+        Self::ok(body.to_string()).header("Content-Type", "application/json")
+    }
+
+    pub fn ok(body: impl Into<Vec<u8>>) -> Self {
+        Self::new("200 OK", body)
+    }
+
+    /// An empty response with this status line, e.g. `"404 Not Found"`.
+    pub fn status(status: &str) -> Self {
+        Self::new(status, Vec::new())
+    }
+
+    pub fn header(mut self, name: &str, value: impl ToString) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Leave out `Content-Length`; the body ends when the connection closes.
+    pub fn without_length(mut self) -> Self {
+        self.content_length = false;
+        self
+    }
+
+    /// Drop the connection after this many body bytes.
+    pub fn cut_at(mut self, bytes: usize) -> Self {
+        self.cut_at = Some(bytes);
+        self
+    }
+
+    /// Wait before answering.
+    pub fn delayed(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Keep the connection open this long after the body.
+    pub fn hold_open(mut self, duration: Duration) -> Self {
+        self.hold_open = duration;
+        self
+    }
+
+    async fn send(self, sock: &mut tokio::net::TcpStream) {
+        tokio::time::sleep(self.delay).await;
+        let mut head = format!("HTTP/1.1 {}\r\n", self.status);
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+        if self.content_length {
+            head.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        }
+        head.push_str("Connection: close\r\n\r\n");
+        let body = match self.cut_at {
+            Some(cut) => &self.body[..cut.min(self.body.len())],
+            None => &self.body[..],
+        };
+        let _ = sock.write_all(head.as_bytes()).await;
+        let _ = sock.write_all(body).await;
+        tokio::time::sleep(self.hold_open).await;
+        let _ = sock.shutdown().await;
+    }
+}
+
+/// Start a local HTTP server answering each request with `handler`, one
+/// connection per request, and return its `http://host:port` base URL.
+pub async fn serve<F>(handler: F) -> String
+where
+    F: Fn(StubRequest) -> StubResponse + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handler = Arc::new(handler);
+    tokio::spawn(async move {
+        while let Ok((mut sock, _)) = listener.accept().await {
+            let handler = Arc::clone(&handler);
+            tokio::spawn(async move {
+                if let Some(request) = read_request(&mut sock).await {
+                    handler(request).send(&mut sock).await;
+                }
+            });
+        }
+    });
+    format!("http://{addr}")
+}
+
+async fn read_request(sock: &mut tokio::net::TcpStream) -> Option<StubRequest> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 8192];
+    let head_end = loop {
+        let n = sock.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+    let head = String::from_utf8_lossy(&buf[..head_end - 4]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split(' ');
+    let method = request_line.next()?.to_string();
+    let target = request_line.next()?.to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| {
+            let (k, v) = line.split_once(':')?;
+            Some((k.trim().to_ascii_lowercase(), v.trim().to_string()))
+        })
+        .collect();
+    let len: usize = headers
+        .iter()
+        .find(|(k, _)| k == "content-length")
+        .and_then(|(_, v)| v.parse().ok())
+        .unwrap_or(0);
+    while buf.len() < head_end + len {
+        let n = sock.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    let body = buf[head_end..].to_vec();
+    Some(StubRequest {
+        head,
+        method,
+        target,
+        headers,
+        body,
+    })
+}
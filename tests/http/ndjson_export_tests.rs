@@ -0,0 +1,196 @@
+use super::{retry, serve, CollectRows, StubResponse};
+use apitap::errors::Result;
+use apitap::http::fetcher::RequestOptions;
+use apitap::http::ndjson_export::{run_ndjson_export, NdjsonOptions};
+use apitap::pipeline::download_state::DownloadTracker;
+use apitap::pipeline::retry_state::RetryStateStore;
+use apitap::writer::WriteMode;
+use reqwest::Url;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// How the export server behaves.
+#[derive(Clone)]
+struct Export {
+    etag: &'static str,
+    /// Advertise and honour byte ranges.
+    ranges: bool,
+    /// Cut the first response off after this many body bytes.
+    cut_first_at: Option<usize>,
+}
+
+/// Ten `{"id":N}` lines, 9 bytes each.
+fn body() -> Vec<u8> {
+    (0..10)
+        .flat_map(|id| format!("{{\"id\":{id}}}\n").into_bytes())
+        .collect()
+}
+
+/// Serve `export`; returns the url and each request's `Range` header.
+async fn serve_export(export: Export) -> (Url, Arc<Mutex<Vec<Option<String>>>>) {
+    let ranges = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&ranges);
+    let served = AtomicUsize::new(0);
+    let base = serve(move |req| {
+        let range = req.header("range").map(String::from);
+        log.lock().unwrap().push(range.clone());
+
+        let full = body();
+        let start = match (&range, req.header("if-range")) {
+            (Some(range), Some(if_range)) if export.ranges && if_range == export.etag => range
+                .trim_start_matches("bytes=")
+                .trim_end_matches('-')
+                .parse::<usize>()
+                .ok(),
+            _ => None,
+        };
+        let len = full.len();
+        let mut resp = match start {
+            Some(start) => StubResponse::new("206 Partial Content", full[start..].to_vec())
+                .header("Content-Range", format!("bytes {start}-{}/{len}", len - 1)),
+            None => StubResponse::ok(full),
+        }
+        .header("Content-Type", "application/x-ndjson")
+        .header("ETag", export.etag);
+        if export.ranges {
+            resp = resp.header("Accept-Ranges", "bytes");
+        }
+        match export.cut_first_at {
+            Some(cut) if served.fetch_add(1, Ordering::SeqCst) == 0 => resp.cut_at(cut),
+            _ => resp,
+        }
+    })
+    .await;
+    (Url::parse(&format!("{base}/export")).unwrap(), ranges)
+}
+
+async fn export(
+    url: &Url,
+    store: &Arc<RetryStateStore>,
+    resume: bool,
+    writer: &CollectRows,
+) -> Result<()> {
+    let request = RequestOptions {
+        downloads: Some(DownloadTracker::new(Arc::clone(store), "events", resume)),
+        ..Default::default()
+    };
+    run_ndjson_export(
+        reqwest::Client::new(),
+        url,
+        Vec::new(),
+        None,
+        &retry(),
+        request,
+        writer,
+        WriteMode::Append,
+        3,
+    )
+    .await
+    .map(|_| ())
+}
+
+#[tokio::test]
+async fn test_interrupted_export_resumes_with_range_request() {
+    let (url, ranges) = serve_export(Export {
+        etag: "\"v1\"",
+        ranges: true,
+        // Seven whole lines and part of the eighth.
+        cut_first_at: Some(66),
+    })
+    .await;
+    let dir = tempfile::tempdir().unwrap();
+    let store = Arc::new(
+        RetryStateStore::open(dir.path().join("state.json"))
+            .await
+            .unwrap(),
+    );
+    let writer = CollectRows::default();
+
+    assert!(export(&url, &store, false, &writer).await.is_err());
+    let checkpoint = store.download_checkpoint("events").await.unwrap();
+    assert_eq!(checkpoint.lines, 6);
+    assert_eq!(checkpoint.bytes, Some(54));
+    assert_eq!(checkpoint.validator.as_deref(), Some("\"v1\""));
+    assert_eq!(writer.ids(), vec![0, 1, 2, 3, 4, 5]);
+
+    export(&url, &store, true, &writer).await.unwrap();
+    assert_eq!(
+        *ranges.lock().unwrap(),
+        vec![None, Some("bytes=54-".to_string())]
+    );
+    assert_eq!(writer.ids(), (0..10).collect::<Vec<_>>());
+    assert_eq!(writer.page_numbers(), vec![1, 2, 3, 4]);
+    assert!(store.download_checkpoint("events").await.is_none());
+}
+
+#[tokio::test]
+async fn test_resume_skips_written_lines_without_range_support() {
+    let (url, ranges) = serve_export(Export {
+        etag: "\"v1\"",
+        ranges: false,
+        cut_first_at: Some(66),
+    })
+    .await;
+    let dir = tempfile::tempdir().unwrap();
+    let store = Arc::new(
+        RetryStateStore::open(dir.path().join("state.db"))
+            .await
+            .unwrap(),
+    );
+    let writer = CollectRows::default();
+
+    assert!(export(&url, &store, false, &writer).await.is_err());
+    let checkpoint = store.download_checkpoint("events").await.unwrap();
+    assert_eq!((checkpoint.lines, checkpoint.bytes), (6, None));
+
+    // Reopened, as a new process would.
+    let store = Arc::new(
+        RetryStateStore::open(dir.path().join("state.db"))
+            .await
+            .unwrap(),
+    );
+    export(&url, &store, true, &writer).await.unwrap();
+    assert_eq!(*ranges.lock().unwrap(), vec![None, None]);
+    assert_eq!(writer.ids(), (0..10).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn test_changed_export_starts_over() {
+    let (url, _) = serve_export(Export {
+        etag: "\"v2\"",
+        ranges: true,
+        cut_first_at: None,
+    })
+    .await;
+    let dir = tempfile::tempdir().unwrap();
+    let store = Arc::new(
+        RetryStateStore::open(dir.path().join("state.json"))
+            .await
+            .unwrap(),
+    );
+    store
+        .save_download_checkpoint(
+            "events",
+            Some(apitap::pipeline::download_state::DownloadCheckpoint {
+                url: url.to_string(),
+                lines: 6,
+                bytes: Some(54),
+                validator: Some("\"v1\"".into()),
+                saved_at: chrono::Utc::now(),
+            }),
+        )
+        .await
+        .unwrap();
+    let writer = CollectRows::default();
+
+    export(&url, &store, true, &writer).await.unwrap();
+    assert_eq!(writer.ids(), (0..10).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_checkpoint_every_must_be_positive() {
+    let opts: NdjsonOptions = serde_yaml::from_str("checkpoint_every: 0").unwrap();
+    assert!(opts.checkpoint_every().is_err());
+    let opts: NdjsonOptions = serde_yaml::from_str("checkpoint_every: 500000").unwrap();
+    assert_eq!(opts.checkpoint_every().unwrap(), Some(500_000));
+}
@@ -0,0 +1,75 @@
+use super::{retry, serve, StubResponse};
+use apitap::http::fetcher::{PaginatedFetcher, Pagination};
+use futures::TryStreamExt;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+/// HubSpot-style pages: `paging.next.link` is absolute on page 1, relative
+/// on page 2 and absent on page 3. With `looping`, page 3 links back to 2.
+async fn serve_pages(looping: bool) -> (String, Arc<Mutex<Vec<String>>>) {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&seen);
+    let base = Arc::new(Mutex::new(String::new()));
+    let origin = Arc::clone(&base);
+    let url = serve(move |req| {
+        log.lock().unwrap().push(req.target.clone());
+        let after = req.num("after").unwrap_or(0);
+        let paging = match after {
+            0 => json!({"next": {"link": format!("{}/contacts?after=2&limit=2", origin.lock().unwrap())}}),
+            2 => json!({"next": {"link": "/contacts?after=4&limit=2"}}),
+            _ if looping => json!({"next": {"link": "/contacts?after=2&limit=2"}}),
+            _ => json!({}),
+        };
+        StubResponse::json(json!({
+            "results": [{"id": after + 1}, {"id": after + 2}],
+            "paging": paging,
+        }))
+    })
+    .await;
+    *base.lock().unwrap() = url.clone();
+    (format!("{url}/contacts"), seen)
+}
+
+async fn ids(url: String) -> Vec<u64> {
+    let fetcher = PaginatedFetcher::new(reqwest::Client::new(), url, 1)
+        .with_next_url("/paging/next/link", Some("limit".into()));
+    let rows: Vec<Value> = fetcher
+        .follow_next_stream(2, Some("/results"), None, &retry())
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    rows.iter().map(|r| r["id"].as_u64().unwrap()).collect()
+}
+
+#[test]
+fn test_next_url_pagination_yaml() {
+    let p: Pagination = serde_yaml::from_str("kind: next_url\nnext_path: /links/next").unwrap();
+    assert!(matches!(
+        p,
+        Pagination::NextUrl { ref next_path, page_size_param: None } if next_path == "/links/next"
+    ));
+    assert!(serde_yaml::from_str::<Pagination>("kind: next_url").is_err());
+}
+
+#[tokio::test]
+async fn test_follows_next_url_in_body() {
+    let (url, seen) = serve_pages(false).await;
+    assert_eq!(ids(url).await, vec![1, 2, 3, 4, 5, 6]);
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![
+            "/contacts?limit=2",
+            "/contacts?after=2&limit=2",
+            "/contacts?after=4&limit=2",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_next_url_loop_stops() {
+    let (url, seen) = serve_pages(true).await;
+    assert_eq!(ids(url).await, vec![1, 2, 3, 4, 5, 6]);
+    assert_eq!(seen.lock().unwrap().len(), 3);
+}
@@ -0,0 +1,309 @@
+use super::{retry, StubResponse};
+use apitap::http::auth::SourceAuth;
+use apitap::http::fetcher::{send_page_request, RequestOptions};
+use apitap::http::oauth2::{OAuth2Config, RefreshTokenSlot, TokenManager};
+use apitap::pipeline::retry_state::RetryStateStore;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// How the token endpoint and API behave.
+#[derive(Clone, Copy)]
+struct Provider {
+    expires_in: u64,
+    /// The API rejects every token after its first use.
+    revoke_after_use: bool,
+    /// Issue a new refresh token, `rt-1`, `rt-2`, ..., with each access token.
+    rotate: bool,
+}
+
+#[derive(Default)]
+struct Seen {
+    /// Bodies and `Authorization` headers of token requests.
+    token_requests: Mutex<Vec<(String, Option<String>)>>,
+    /// `Authorization` headers of API requests.
+    api_auth: Mutex<Vec<Option<String>>>,
+}
+
+/// Serves `POST /token`, issuing `tok-1`, `tok-2`, ..., and `GET /data`,
+/// which needs the newest token.
+async fn serve(provider: Provider) -> (String, Arc<Seen>) {
+    let seen = Arc::new(Seen::default());
+    let log = Arc::clone(&seen);
+    let issued = AtomicU64::new(0);
+    let used = Mutex::new(std::collections::HashSet::new());
+    let base = super::serve(move |req| {
+        let auth = req.header("authorization").map(String::from);
+        let (status, payload) = if req.target == "/token" {
+            log.token_requests
+                .lock()
+                .unwrap()
+                .push((req.body_text(), auth));
+            let n = issued.fetch_add(1, Ordering::SeqCst) + 1;
+            let refresh = if provider.rotate {
+                format!(r#","refresh_token":"rt-{n}""#)
+            } else {
+                String::new()
+            };
+            (
+                "200 OK",
+                format!(
+                    r#"{{"access_token":"tok-{n}","token_type":"bearer","expires_in":{}{refresh}}}"#,
+                    provider.expires_in
+                ),
+            )
+        } else {
+            log.api_auth.lock().unwrap().push(auth.clone());
+            let newest = format!("Bearer tok-{}", issued.load(Ordering::SeqCst));
+            let reused = !used.lock().unwrap().insert(auth.clone());
+            if auth.as_deref() != Some(newest.as_str()) || (provider.revoke_after_use && reused) {
+                ("401 Unauthorized", "{}".to_string())
+            } else {
+                ("200 OK", r#"{"data":[{"id":1}]}"#.to_string())
+            }
+        };
+        StubResponse::new(status, payload).header("Content-Type", "application/json")
+    })
+    .await;
+    (base, seen)
+}
+
+fn config(base: &str, prefix: &str) -> OAuth2Config {
+    std::env::set_var(format!("{prefix}_ID"), "my-client");
+    std::env::set_var(format!("{prefix}_SECRET"), "s3cret");
+    OAuth2Config {
+        token_url: format!("{base}/token"),
+        client_id_env: format!("{prefix}_ID"),
+        client_secret_env: format!("{prefix}_SECRET"),
+        scopes: vec!["orders:read".into(), "orders:list".into()],
+        basic_auth: false,
+        refresh_token_env: None,
+        refresh_every: None,
+    }
+}
+
+fn options(config: OAuth2Config) -> RequestOptions {
+    let client = reqwest::Client::new();
+    RequestOptions {
+        auth: Some(
+            SourceAuth::Oauth2(config)
+                .authenticator(&client, None, None)
+                .unwrap(),
+        ),
+        ..Default::default()
+    }
+}
+
+async fn get(base: &str, request: &RequestOptions) -> apitap::errors::Result<reqwest::Response> {
+    send_page_request(
+        &reqwest::Client::new(),
+        &format!("{base}/data"),
+        &[],
+        &retry(),
+        request,
+    )
+    .await
+}
+
+#[test]
+fn test_oauth2_auth_from_yaml() {
+    let auth: SourceAuth = serde_yaml::from_str(
+        "kind: oauth2\ntoken_url: https://auth.example.com/token\nclient_id_env: ID\nclient_secret_env: SECRET\nscopes: [read]",
+    )
+    .unwrap();
+    let SourceAuth::Oauth2(config) = auth else {
+        panic!("expected oauth2 auth");
+    };
+    assert_eq!(config.scopes, vec!["read"]);
+    assert!(!config.basic_auth);
+}
+
+#[test]
+fn test_missing_credentials_fail_early() {
+    let config = OAuth2Config {
+        token_url: "http://127.0.0.1:9/token".into(),
+        client_id_env: "APITAP_TEST_OAUTH_UNSET_ID".into(),
+        client_secret_env: "APITAP_TEST_OAUTH_UNSET_SECRET".into(),
+        scopes: Vec::new(),
+        basic_auth: false,
+        refresh_token_env: None,
+        refresh_every: None,
+    };
+    let err = TokenManager::new(reqwest::Client::new(), config).unwrap_err();
+    assert!(err.to_string().contains("APITAP_TEST_OAUTH_UNSET_ID"));
+}
+
+#[tokio::test]
+async fn test_token_is_fetched_once_and_reused() {
+    let (base, seen) = serve(Provider {
+        expires_in: 3600,
+        revoke_after_use: false,
+        rotate: false,
+    })
+    .await;
+    let request = options(config(&base, "APITAP_TEST_OAUTH_REUSE"));
+    for _ in 0..3 {
+        get(&base, &request).await.unwrap();
+    }
+    let tokens = seen.token_requests.lock().unwrap();
+    assert_eq!(tokens.len(), 1);
+    let (body, auth) = &tokens[0];
+    assert!(body.contains("grant_type=client_credentials"), "{body}");
+    assert!(body.contains("scope=orders%3Aread+orders%3Alist"), "{body}");
+    assert!(body.contains("client_id=my-client"), "{body}");
+    assert!(auth.is_none());
+    assert_eq!(
+        *seen.api_auth.lock().unwrap(),
+        vec![Some("Bearer tok-1".to_string()); 3]
+    );
+}
+
+#[tokio::test]
+async fn test_expiring_token_is_renewed() {
+    // Within the renewal margin, so every request needs a new token.
+    let (base, seen) = serve(Provider {
+        expires_in: 5,
+        revoke_after_use: false,
+        rotate: false,
+    })
+    .await;
+    let request = options(config(&base, "APITAP_TEST_OAUTH_EXPIRY"));
+    get(&base, &request).await.unwrap();
+    get(&base, &request).await.unwrap();
+    assert_eq!(seen.token_requests.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_unauthorized_response_refreshes_token_and_retries() {
+    let (base, seen) = serve(Provider {
+        expires_in: 3600,
+        revoke_after_use: true,
+        rotate: false,
+    })
+    .await;
+    let request = options(config(&base, "APITAP_TEST_OAUTH_401"));
+    get(&base, &request).await.unwrap();
+    get(&base, &request).await.unwrap();
+    assert_eq!(
+        *seen.api_auth.lock().unwrap(),
+        vec![
+            Some("Bearer tok-1".to_string()),
+            Some("Bearer tok-1".to_string()),
+            Some("Bearer tok-2".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_client_credentials_as_basic_auth() {
+    let (base, seen) = serve(Provider {
+        expires_in: 3600,
+        revoke_after_use: false,
+        rotate: false,
+    })
+    .await;
+    let mut config = config(&base, "APITAP_TEST_OAUTH_BASIC");
+    config.basic_auth = true;
+    get(&base, &options(config)).await.unwrap();
+    let tokens = seen.token_requests.lock().unwrap();
+    let (body, auth) = &tokens[0];
+    assert!(!body.contains("client_secret"), "{body}");
+    // base64("my-client:s3cret")
+    assert_eq!(auth.as_deref(), Some("Basic bXktY2xpZW50OnMzY3JldA=="));
+}
+
+#[test]
+fn test_refresh_token_auth_from_yaml() {
+    let auth: SourceAuth = serde_yaml::from_str(
+        "kind: oauth2\ntoken_url: https://oauth2.googleapis.com/token\nclient_id_env: ID\nclient_secret_env: SECRET\nrefresh_token_env: REFRESH\nrefresh_every: 45m",
+    )
+    .unwrap();
+    let SourceAuth::Oauth2(config) = auth else {
+        panic!("expected oauth2 auth");
+    };
+    assert_eq!(config.refresh_token_env.as_deref(), Some("REFRESH"));
+    assert_eq!(
+        config.refresh_every().unwrap(),
+        Some(std::time::Duration::from_secs(45 * 60))
+    );
+}
+
+async fn refresh_options(
+    config: OAuth2Config,
+    state: &std::path::Path,
+) -> (RequestOptions, Arc<RetryStateStore>) {
+    let store = Arc::new(RetryStateStore::open(state).await.unwrap());
+    let slot = RefreshTokenSlot {
+        store: Arc::clone(&store),
+        source: "xero".into(),
+    };
+    let client = reqwest::Client::new();
+    let request = RequestOptions {
+        auth: Some(
+            SourceAuth::Oauth2(config)
+                .authenticator(&client, Some(slot), None)
+                .unwrap(),
+        ),
+        ..Default::default()
+    };
+    (request, store)
+}
+
+#[tokio::test]
+async fn test_rotated_refresh_token_is_kept_for_the_next_run() {
+    let (base, seen) = serve(Provider {
+        expires_in: 3600,
+        revoke_after_use: false,
+        rotate: true,
+    })
+    .await;
+    let dir = tempfile::tempdir().unwrap();
+    let state = dir.path().join("state.json");
+    std::env::set_var("APITAP_TEST_OAUTH_ROTATE_REFRESH", "rt-0");
+    let mut config = config(&base, "APITAP_TEST_OAUTH_ROTATE");
+    config.refresh_token_env = Some("APITAP_TEST_OAUTH_ROTATE_REFRESH".into());
+
+    let (request, store) = refresh_options(config.clone(), &state).await;
+    get(&base, &request).await.unwrap();
+    assert_eq!(store.refresh_token("xero").await.unwrap().token, "rt-1");
+
+    // The next run exchanges the rotated token, not the configured one.
+    let (request, _) = refresh_options(config.clone(), &state).await;
+    get(&base, &request).await.unwrap();
+
+    // A newly configured token replaces the stored chain.
+    std::env::set_var("APITAP_TEST_OAUTH_ROTATE_REFRESH", "rt-new");
+    let (request, _) = refresh_options(config, &state).await;
+    get(&base, &request).await.unwrap();
+
+    let bodies: Vec<String> = seen
+        .token_requests
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(body, _)| body.clone())
+        .collect();
+    assert_eq!(bodies.len(), 3);
+    assert!(bodies
+        .iter()
+        .all(|b| b.contains("grant_type=refresh_token")));
+    assert!(bodies[0].contains("refresh_token=rt-0"), "{}", bodies[0]);
+    assert!(bodies[1].contains("refresh_token=rt-1"), "{}", bodies[1]);
+    assert!(bodies[2].contains("refresh_token=rt-new"), "{}", bodies[2]);
+}
+
+#[tokio::test]
+async fn test_refresh_every_renews_long_lived_tokens() {
+    let (base, seen) = serve(Provider {
+        expires_in: 3600,
+        revoke_after_use: false,
+        rotate: false,
+    })
+    .await;
+    let mut config = config(&base, "APITAP_TEST_OAUTH_SCHEDULE");
+    // Due again as soon as it is fetched.
+    config.refresh_every = Some("0s".into());
+    let request = options(config);
+    get(&base, &request).await.unwrap();
+    get(&base, &request).await.unwrap();
+    assert_eq!(seen.token_requests.lock().unwrap().len(), 2);
+}
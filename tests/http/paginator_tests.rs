@@ -0,0 +1,163 @@
+use super::{retry, serve, CollectRows, StubResponse};
+use apitap::errors::Result;
+use apitap::http::fetcher::{PaginatedFetcher, Pagination};
+use apitap::http::paginator::{PageRequest, PageResponse, Paginator, Paginators};
+use apitap::writer::WriteMode;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+/// Three pages of two items; the token of the next page comes back in an
+/// `X-Next-Token` header and must be sent as `X-Page-Token`. Returns the
+/// url and each request's target and token.
+async fn serve_tokens() -> (String, Arc<Mutex<Vec<(String, Option<String>)>>>) {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&seen);
+    let base = serve(move |req| {
+        let token = req.header("x-page-token").map(String::from);
+        log.lock()
+            .unwrap()
+            .push((req.target.clone(), token.clone()));
+        let page: u64 = match token.as_deref() {
+            Some("tok-2") => 2,
+            Some("tok-3") => 3,
+            _ => 1,
+        };
+        let items: Vec<Value> = (0..2).map(|i| json!({"id": (page - 1) * 2 + i})).collect();
+        let resp = StubResponse::json(json!({"data": items}));
+        if page < 3 {
+            resp.header("X-Next-Token", format!("tok-{}", page + 1))
+        } else {
+            resp
+        }
+    })
+    .await;
+    (format!("{base}/events"), seen)
+}
+
+/// Echoes the `X-Next-Token` response header as a request header.
+struct HeaderToken;
+
+impl Paginator for HeaderToken {
+    fn first(&self) -> PageRequest {
+        PageRequest::new().with_query("size", "2")
+    }
+
+    fn next(
+        &self,
+        previous: &PageRequest,
+        response: &PageResponse<'_>,
+    ) -> Result<Option<PageRequest>> {
+        let Some(token) = response.headers.get("x-next-token") else {
+            return Ok(None);
+        };
+        let mut next = previous.clone();
+        next.headers = vec![("X-Page-Token".into(), token.to_str().unwrap().to_string())];
+        Ok(Some(next))
+    }
+}
+
+/// Always asks for the same page again.
+struct Stuck;
+
+impl Paginator for Stuck {
+    fn next(
+        &self,
+        previous: &PageRequest,
+        _response: &PageResponse<'_>,
+    ) -> Result<Option<PageRequest>> {
+        Ok(Some(previous.clone()))
+    }
+}
+
+#[test]
+fn test_custom_pagination_from_yaml_and_registry() {
+    let pagination: Pagination =
+        serde_yaml::from_str("kind: custom\npaginator: header_token").unwrap();
+    assert!(matches!(
+        &pagination,
+        Pagination::Custom { paginator } if paginator == "header_token"
+    ));
+
+    let mut paginators = Paginators::new();
+    assert!(paginators.is_empty());
+    paginators.register("header_token", HeaderToken);
+    assert!(paginators.get("header_token").is_some());
+    assert!(paginators.get("other").is_none());
+}
+
+#[tokio::test]
+async fn test_custom_paginator_follows_header_tokens() {
+    let (url, seen) = serve_tokens().await;
+    let fetcher = PaginatedFetcher::new(reqwest::Client::new(), url, 1);
+    let writer = Arc::new(CollectRows::default());
+    let stats = fetcher
+        .fetch_custom(
+            Arc::new(HeaderToken),
+            Some("/data"),
+            Some(&[("region".to_string(), "eu".to_string())]),
+            writer.clone(),
+            WriteMode::Append,
+            &retry(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(stats.success_count, 3);
+    assert_eq!(writer.ids(), vec![0, 1, 2, 3, 4, 5]);
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![
+            ("/events?region=eu&size=2".to_string(), None),
+            ("/events?region=eu&size=2".to_string(), Some("tok-2".into())),
+            ("/events?region=eu&size=2".to_string(), Some("tok-3".into())),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_repeated_request_ends_custom_walk() {
+    let (url, seen) = serve_tokens().await;
+    let fetcher = PaginatedFetcher::new(reqwest::Client::new(), url, 1);
+    let writer = Arc::new(CollectRows::default());
+    let stats = fetcher
+        .fetch_custom(
+            Arc::new(Stuck),
+            Some("/data"),
+            None,
+            writer,
+            WriteMode::Append,
+            &retry(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(stats.success_count, 1);
+    assert_eq!(seen.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_failed_page_ends_custom_walk_and_commits_written_pages() {
+    let base = serve(|req| match req.header("x-page-token") {
+        Some(_) => StubResponse::status("500 Internal Server Error"),
+        None => StubResponse::json(json!({"data": [{"id": 0}, {"id": 1}]}))
+            .header("X-Next-Token", "tok-2"),
+    })
+    .await;
+    let fetcher = PaginatedFetcher::new(reqwest::Client::new(), format!("{base}/events"), 1);
+    let writer = Arc::new(CollectRows::default());
+    let stats = fetcher
+        .fetch_custom(
+            Arc::new(HeaderToken),
+            Some("/data"),
+            None,
+            writer.clone(),
+            WriteMode::Append,
+            &retry(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(stats.success_count, 1);
+    assert_eq!(stats.error_count, 1);
+    assert_eq!(writer.rows().len(), 2);
+    assert!(writer.committed());
+}
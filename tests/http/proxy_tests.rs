@@ -0,0 +1,119 @@
+// Tests for proxy routing (per source and config-wide)
+//
+// The shared stub server stands in for the proxy / origin so requests can be
+// inspected without extra test dependencies.
+
+use super::{serve, StubRequest, StubResponse};
+use apitap::http::Http;
+use apitap::pipeline::{Config, ProxyConfig};
+use std::sync::{Arc, Mutex};
+
+/// Reply with a small JSON body and keep each request.
+async fn serve_recording() -> (String, Arc<Mutex<Vec<StubRequest>>>) {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&seen);
+    let base = serve(move |req| {
+        log.lock().unwrap().push(req);
+        StubResponse::json(r#"{"ok":true}"#)
+    })
+    .await;
+    (base, seen)
+}
+
+fn proxy_config(url: String) -> ProxyConfig {
+    ProxyConfig {
+        url,
+        username: Some("alice".to_string()),
+        username_env: None,
+        password: Some("s3cret".to_string()),
+        password_env: None,
+        no_proxy: Vec::new(),
+    }
+}
+
+#[tokio::test]
+async fn test_requests_go_through_authenticated_proxy() {
+    let (proxy_url, seen) = serve_recording().await;
+
+    let proxy = proxy_config(proxy_url).to_proxy().unwrap();
+    let client = Http::new("http://api.internal.test/data")
+        .proxy(proxy)
+        .build_client()
+        .unwrap();
+    let resp = client
+        .get("http://api.internal.test/data")
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+
+    let req = seen.lock().unwrap().pop().unwrap();
+    assert_eq!(req.method, "GET");
+    assert_eq!(req.target, "http://api.internal.test/data");
+    // base64("alice:s3cret")
+    assert_eq!(
+        req.header("proxy-authorization"),
+        Some("Basic YWxpY2U6czNjcmV0")
+    );
+}
+
+#[tokio::test]
+async fn test_no_proxy_hosts_connect_directly() {
+    let (origin, seen) = serve_recording().await;
+
+    // Nothing listens on port 1; only a direct connection can succeed.
+    let mut cfg = proxy_config("http://127.0.0.1:1".to_string());
+    cfg.no_proxy = vec!["127.0.0.1".to_string()];
+
+    let client = Http::new("http://127.0.0.1/")
+        .proxy(cfg.to_proxy().unwrap())
+        .build_client()
+        .unwrap();
+    let resp = client.get(format!("{origin}/data")).send().await.unwrap();
+    assert!(resp.status().is_success());
+
+    let req = seen.lock().unwrap().pop().unwrap();
+    assert_eq!(req.target, "/data");
+}
+
+#[test]
+fn test_invalid_proxy_url() {
+    let cfg = proxy_config("not a url".to_string());
+    assert!(cfg.to_proxy().is_err());
+}
+
+#[test]
+fn test_proxy_password_required_with_username() {
+    let mut cfg = proxy_config("socks5h://127.0.0.1:1080".to_string());
+    cfg.password = None;
+    assert!(cfg.to_proxy().is_err());
+}
+
+#[test]
+fn test_global_proxy_applies_to_sources_without_their_own() {
+    let config: Config = serde_yaml::from_str(
+        r#"
+proxy:
+  url: http://egress.corp:3128
+  username_env: PROXY_USER
+  password_env: PROXY_PASS
+sources:
+  - name: public
+    url: https://api.example.com/items
+    retry: {max_attempts: 1, max_delay_secs: 1, min_delay_secs: 1}
+  - name: partner
+    url: https://partner.example.com/items
+    proxy: {url: "socks5h://partner-gw:1080"}
+    retry: {max_attempts: 1, max_delay_secs: 1, min_delay_secs: 1}
+targets: []
+"#,
+    )
+    .unwrap();
+    let proxy_url = |name: &str| {
+        config
+            .proxy_for(config.source(name).unwrap())
+            .map(|p| p.url.as_str())
+    };
+    assert_eq!(proxy_url("public"), Some("http://egress.corp:3128"));
+    assert_eq!(proxy_url("partner"), Some("socks5h://partner-gw:1080"));
+}
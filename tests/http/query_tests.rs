@@ -0,0 +1,59 @@
+use apitap::http::Http;
+use apitap::pipeline::{QueryParam, QueryValue};
+
+#[test]
+fn test_get_url_percent_encodes_params() {
+    let url = Http::new("https://api.example.com/search")
+        .param("q", "rust & arrow")
+        .param("city", "Zürich")
+        .get_url();
+    assert_eq!(
+        url,
+        "https://api.example.com/search?q=rust+%26+arrow&city=Z%C3%BCrich"
+    );
+}
+
+#[test]
+fn test_get_url_keeps_repeated_params_in_order() {
+    let url = Http::new("https://api.example.com/items")
+        .param("tag", "a")
+        .param("page", "3")
+        .param("tag", "b")
+        .param("ids[]", "1")
+        .param("ids[]", "2")
+        .get_url();
+    assert_eq!(
+        url,
+        "https://api.example.com/items?tag=a&tag=b&ids%5B%5D=1&ids%5B%5D=2"
+    );
+}
+
+#[test]
+fn test_get_url_appends_to_existing_query() {
+    let url = Http::new("https://api.example.com/items?sort=asc")
+        .param("q", "a b")
+        .get_url();
+    assert_eq!(url, "https://api.example.com/items?sort=asc&q=a+b");
+    assert_eq!(
+        Http::new("https://api.example.com").get_url(),
+        "https://api.example.com"
+    );
+}
+
+#[test]
+fn test_query_param_list_value_repeats_key() {
+    let params: Vec<QueryParam> = serde_yaml::from_str(
+        "- key: status\n  value: open\n- key: \"tags[]\"\n  value: [a, b c]\n",
+    )
+    .unwrap();
+    assert_eq!(params[0].value, QueryValue::from("open"));
+    let pairs: Vec<(String, String)> = params.iter().flat_map(QueryParam::pairs).collect();
+    assert_eq!(
+        pairs,
+        [
+            ("status".to_string(), "open".to_string()),
+            ("tags[]".to_string(), "a".to_string()),
+            ("tags[]".to_string(), "b c".to_string()),
+        ]
+    );
+}
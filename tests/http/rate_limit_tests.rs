@@ -0,0 +1,143 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::{retry, StubResponse};
+use apitap::http::fetcher::{send_page_request, RequestOptions};
+use apitap::http::rate_limit::{RateLimit, RateLimiter};
+use apitap::pipeline::{Config, Retry};
+
+/// Answers every request with an empty JSON array.
+async fn serve() -> String {
+    let base = super::serve(|_| StubResponse::json("[]")).await;
+    format!("{base}/items")
+}
+
+#[test]
+fn test_rate_limit_from_yaml() {
+    let config: Config = serde_yaml::from_str(
+        r#"
+sources:
+  - name: strict
+    url: https://api.example.com/items
+    rate_limit:
+      requests_per_second: 0.5
+      burst: 5
+    retry: {max_attempts: 1, max_delay_secs: 1, min_delay_secs: 1}
+  - name: default_burst
+    url: https://api.example.com/items
+    rate_limit: {requests_per_second: 10}
+    retry: {max_attempts: 1, max_delay_secs: 1, min_delay_secs: 1}
+targets: []
+"#,
+    )
+    .unwrap();
+    assert_eq!(
+        config.sources[0].rate_limit,
+        Some(RateLimit {
+            requests_per_second: 0.5,
+            burst: 5
+        })
+    );
+    assert_eq!(config.sources[1].rate_limit.as_ref().unwrap().burst, 1);
+    assert!(config.sources[0]
+        .rate_limit
+        .as_ref()
+        .unwrap()
+        .limiter()
+        .is_ok());
+
+    let zero = RateLimit {
+        requests_per_second: 0.0,
+        burst: 1,
+    };
+    assert!(zero.limiter().is_err());
+    let no_burst = RateLimit {
+        requests_per_second: 1.0,
+        burst: 0,
+    };
+    assert!(no_burst.limiter().is_err());
+}
+
+#[tokio::test]
+async fn test_burst_then_rate() {
+    let limiter = RateLimiter::new(20.0, 3);
+    let start = Instant::now();
+    for _ in 0..3 {
+        limiter.acquire().await;
+    }
+    assert!(start.elapsed() < Duration::from_millis(30));
+
+    limiter.acquire().await;
+    let waited = start.elapsed();
+    assert!(waited >= Duration::from_millis(40), "waited {waited:?}");
+    assert!(waited < Duration::from_millis(500), "waited {waited:?}");
+}
+
+#[tokio::test]
+async fn test_concurrent_callers_share_the_bucket() {
+    let limiter = Arc::new(RateLimiter::new(20.0, 1));
+    let start = Instant::now();
+    let tasks: Vec<_> = (0..5)
+        .map(|_| {
+            let limiter = Arc::clone(&limiter);
+            tokio::spawn(async move { limiter.acquire().await })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+    // One token up front, then one every 50ms.
+    let waited = start.elapsed();
+    assert!(waited >= Duration::from_millis(190), "waited {waited:?}");
+    assert!(waited < Duration::from_millis(1000), "waited {waited:?}");
+}
+
+#[tokio::test]
+async fn test_page_requests_are_paced() {
+    let url = serve().await;
+    let request = RequestOptions {
+        rate_limit: Some(Arc::new(RateLimiter::new(10.0, 1))),
+        ..Default::default()
+    };
+    let retry = retry();
+    let client = reqwest::Client::new();
+
+    let start = Instant::now();
+    let sends = (0..3).map(|_| send_page_request(&client, &url, &[], &retry, &request));
+    for resp in futures::future::join_all(sends).await {
+        resp.unwrap();
+    }
+    let waited = start.elapsed();
+    assert!(waited >= Duration::from_millis(190), "waited {waited:?}");
+}
+
+#[tokio::test]
+async fn test_retries_are_paced() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&hits);
+    let base = super::serve(move |_| match counter.fetch_add(1, Ordering::SeqCst) {
+        0 | 1 => StubResponse::status("503 Service Unavailable"),
+        _ => StubResponse::json("[]"),
+    })
+    .await;
+    let request = RequestOptions {
+        rate_limit: Some(Arc::new(RateLimiter::new(10.0, 1))),
+        ..Default::default()
+    };
+    let retry = Retry {
+        max_attempts: 2,
+        ..retry()
+    };
+    let client = reqwest::Client::new();
+
+    let start = Instant::now();
+    let resp = send_page_request(&client, &format!("{base}/items"), &[], &retry, &request)
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+    // Each retry waited for a token of its own.
+    let waited = start.elapsed();
+    assert!(waited >= Duration::from_millis(190), "waited {waited:?}");
+}
@@ -0,0 +1,157 @@
+use std::sync::{Arc, Mutex};
+
+use apitap::errors::Result;
+use apitap::http::fetcher::{DataFusionPageWriter, PageWriter};
+use apitap::pipeline::run_history::SchemaCapture;
+use apitap::utils::datafusion_ext::{QueryResult, QueryResultStream};
+use apitap::writer::{DataWriter, WriteMode};
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::{json, Value};
+
+/// Sink that keeps every row it receives, plus the commit count.
+#[derive(Default)]
+struct CaptureWriter {
+    rows: Mutex<Vec<Value>>,
+    commits: Mutex<usize>,
+}
+
+#[async_trait]
+impl DataWriter for CaptureWriter {
+    async fn write(&self, _result: QueryResult) -> Result<()> {
+        Ok(())
+    }
+
+    async fn write_stream(&self, mut result: QueryResultStream, _mode: WriteMode) -> Result<()> {
+        while let Some(row) = result.data.next().await {
+            self.rows.lock().unwrap().push(row?);
+        }
+        Ok(())
+    }
+
+    async fn commit(&self) -> Result<()> {
+        *self.commits.lock().unwrap() += 1;
+        Ok(())
+    }
+}
+
+fn orders() -> Vec<Value> {
+    vec![
+        json!({"id": 1, "status": "paid", "amount": 10}),
+        json!({"id": 2, "status": "refunded", "amount": 4}),
+        json!({"id": 3, "status": "paid", "amount": 7}),
+    ]
+}
+
+fn routed_writer(
+    table: &str,
+    paid: Arc<CaptureWriter>,
+    refunds: Arc<CaptureWriter>,
+) -> DataFusionPageWriter {
+    DataFusionPageWriter::routed(table)
+        .with_route(
+            "paid_orders",
+            format!("SELECT id, amount FROM {table} WHERE status = 'paid' ORDER BY id"),
+            paid,
+        )
+        .with_route(
+            "refunds",
+            format!("SELECT id FROM {table} WHERE status = 'refunded'"),
+            refunds,
+        )
+}
+
+#[tokio::test]
+async fn test_routes_split_one_page_across_writers() {
+    let paid = Arc::new(CaptureWriter::default());
+    let refunds = Arc::new(CaptureWriter::default());
+    let writer = routed_writer("routing_orders_page", paid.clone(), refunds.clone());
+
+    writer
+        .write_page(1, orders(), WriteMode::Append)
+        .await
+        .unwrap();
+    writer.commit().await.unwrap();
+
+    assert_eq!(
+        *paid.rows.lock().unwrap(),
+        vec![
+            json!({"id": 1, "amount": 10}),
+            json!({"id": 3, "amount": 7})
+        ]
+    );
+    assert_eq!(*refunds.rows.lock().unwrap(), vec![json!({"id": 2})]);
+    assert_eq!(*paid.commits.lock().unwrap(), 1);
+    assert_eq!(*refunds.commits.lock().unwrap(), 1);
+}
+
+#[tokio::test]
+async fn test_routes_apply_to_streamed_pages() {
+    let paid = Arc::new(CaptureWriter::default());
+    let refunds = Arc::new(CaptureWriter::default());
+    let writer = routed_writer("routing_orders_stream", paid.clone(), refunds.clone());
+
+    let rows = orders().into_iter().map(Ok);
+    writer
+        .write_page_stream(Box::pin(futures::stream::iter(rows)), WriteMode::Append)
+        .await
+        .unwrap();
+
+    assert_eq!(paid.rows.lock().unwrap().len(), 2);
+    assert_eq!(*refunds.rows.lock().unwrap(), vec![json!({"id": 2})]);
+}
+
+#[tokio::test]
+async fn test_schema_capture_notes_columns_per_route() {
+    let schemas = Arc::new(SchemaCapture::default());
+    let writer = routed_writer(
+        "routing_schema_page",
+        Arc::new(CaptureWriter::default()),
+        Arc::new(CaptureWriter::default()),
+    )
+    .with_schema_capture(schemas.clone());
+
+    writer
+        .write_page(1, orders(), WriteMode::Append)
+        .await
+        .unwrap();
+
+    let tables = schemas.take();
+    assert_eq!(
+        tables["paid_orders"].keys().collect::<Vec<_>>(),
+        vec!["amount", "id"]
+    );
+    assert_eq!(tables["refunds"]["id"], "UInt64");
+    assert!(schemas.take().is_empty());
+}
+
+#[tokio::test]
+async fn test_batch_rows_write_streamed_pages_in_batches() {
+    let counts = Arc::new(CaptureWriter::default());
+    let refunds = Arc::new(CaptureWriter::default());
+    let writer = DataFusionPageWriter::routed("routing_orders_batches")
+        .with_route(
+            "counts",
+            "SELECT COUNT(*) AS n FROM routing_orders_batches",
+            counts.clone(),
+        )
+        .with_route(
+            "refunds",
+            "SELECT id FROM routing_orders_batches WHERE status = 'refunded'",
+            refunds.clone(),
+        )
+        .with_batch_rows(2);
+
+    let page = futures::stream::iter(orders().into_iter().map(Ok)).boxed();
+    writer
+        .write_page_stream(page, WriteMode::Append)
+        .await
+        .unwrap();
+
+    // Each batch goes through the SQL on its own.
+    assert_eq!(
+        *counts.rows.lock().unwrap(),
+        vec![json!({"n": 2}), json!({"n": 1})]
+    );
+    assert_eq!(*refunds.rows.lock().unwrap(), vec![json!({"id": 2})]);
+}
@@ -0,0 +1,114 @@
+use super::{retry, serve, CollectRows, StubResponse};
+use apitap::http::fetcher::{
+    row_sequence, PaginatedFetcher, RequestOptions, TotalHint, SEQUENCE_COLUMN,
+};
+use apitap::writer::WriteMode;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Keeps pages in the order they reached the writer.
+/// Three pages of two rows each; page 2 answers last.
+async fn serve_pages() -> String {
+    let base = serve(|req| {
+        let page = req.num("page").unwrap();
+        let resp = StubResponse::json(json!({
+            "total_pages": 3,
+            "data": [{"id": page * 10 + 1}, {"id": page * 10 + 2}],
+        }));
+        if page == 2 {
+            resp.delayed(Duration::from_millis(300))
+        } else {
+            resp
+        }
+    })
+    .await;
+    format!("{base}/items")
+}
+
+#[test]
+fn test_row_sequence_orders_by_page_then_row() {
+    assert_eq!(row_sequence(1, 0), 1 << 32);
+    assert!(row_sequence(1, 999_999) < row_sequence(2, 0));
+    assert!(row_sequence(2, 0) < row_sequence(2, 1));
+}
+
+#[tokio::test]
+async fn test_sequence_follows_fetch_order_not_completion_order() {
+    let url = serve_pages().await;
+    let request = RequestOptions {
+        sequence: true,
+        ..Default::default()
+    };
+    let fetcher = PaginatedFetcher::new(reqwest::Client::new(), url, 4)
+        .with_page_number("page", "per_page")
+        .with_request_options(request);
+    let retry = retry();
+    let writer = Arc::new(CollectRows::default());
+
+    fetcher
+        .fetch_page_number(
+            2,
+            Some("/data"),
+            None,
+            Some(TotalHint::Pages {
+                pointer: "/total_pages".to_string(),
+            }),
+            writer.clone(),
+            WriteMode::Append,
+            &retry,
+        )
+        .await
+        .unwrap();
+
+    let pages = writer.pages();
+    let arrival: Vec<u64> = pages.iter().map(|(page, _)| *page).collect();
+    assert_eq!(arrival, vec![1, 3, 2]);
+
+    let mut rows: Vec<Value> = pages.into_iter().flat_map(|(_, rows)| rows).collect();
+    rows.sort_by_key(|row| row[SEQUENCE_COLUMN].as_u64().unwrap());
+    let ids: Vec<u64> = rows.iter().map(|row| row["id"].as_u64().unwrap()).collect();
+    assert_eq!(ids, vec![11, 12, 21, 22, 31, 32]);
+    assert_eq!(rows[3][SEQUENCE_COLUMN], json!(row_sequence(2, 1)));
+}
+
+#[tokio::test]
+async fn test_refetched_page_keeps_its_sequence() {
+    let url = serve_pages().await;
+    let retry = retry();
+
+    for sequence in [true, false] {
+        let fetcher = PaginatedFetcher::new(reqwest::Client::new(), url.clone(), 1)
+            .with_page_number("page", "per_page")
+            .with_request_options(RequestOptions {
+                sequence,
+                ..Default::default()
+            });
+        let writer = Arc::new(CollectRows::default());
+        fetcher
+            .fetch_pages(
+                &[3],
+                2,
+                Some("/data"),
+                None,
+                writer.clone(),
+                WriteMode::Append,
+                &retry,
+            )
+            .await
+            .unwrap();
+
+        let pages = writer.pages();
+        let seqs: Vec<Option<u64>> = pages[0]
+            .1
+            .iter()
+            .map(|row| row.get(SEQUENCE_COLUMN).and_then(Value::as_u64))
+            .collect();
+        let expected = if sequence {
+            vec![Some(row_sequence(3, 0)), Some(row_sequence(3, 1))]
+        } else {
+            vec![None, None]
+        };
+        assert_eq!(seqs, expected);
+    }
+}
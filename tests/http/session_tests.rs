@@ -0,0 +1,116 @@
+use std::sync::{Arc, Mutex};
+
+use super::{retry, serve, StubRequest, StubResponse};
+use apitap::http::fetcher::{ndjson_stream_with, RequestOptions};
+use apitap::http::session::PreRequest;
+use apitap::http::Http;
+use apitap::pipeline::Source;
+use futures::TryStreamExt;
+use reqwest::cookie::Jar;
+
+/// A login at `/login` sets `sid`; `/items` wants it back.
+async fn serve_session(requests: Arc<Mutex<Vec<StubRequest>>>) -> String {
+    serve(move |req| {
+        requests.lock().unwrap().push(req.clone());
+        if req.method == "POST" && req.target == "/login" {
+            if req.body_text().contains("password=hunter2") {
+                StubResponse::status("204 No Content").header("Set-Cookie", "sid=abc123; Path=/")
+            } else {
+                StubResponse::status("403 Forbidden")
+            }
+        } else if req.header("cookie") == Some("sid=abc123") {
+            StubResponse::json(r#"[{"id":1},{"id":2}]"#)
+        } else {
+            StubResponse::status("401 Unauthorized")
+        }
+    })
+    .await
+}
+
+fn login(base: &str) -> PreRequest {
+    serde_yaml::from_str(&format!(
+        "url: {base}/login\ncontent_type: form\nbody:\n  username: etl\n  password: \"${{APITAP_TEST_SESSION_PASSWORD}}\"\n"
+    ))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_pre_request_sets_session_cookie_for_pages() {
+    std::env::set_var("APITAP_TEST_SESSION_PASSWORD", "hunter2");
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let base = serve_session(Arc::clone(&requests)).await;
+    let client = Http::new(&base)
+        .cookie_jar(Arc::new(Jar::default()))
+        .build_client()
+        .unwrap();
+
+    login(&base).send(&client).await.unwrap();
+    let rows: Vec<_> = ndjson_stream_with(
+        &client,
+        &format!("{base}/items"),
+        &[],
+        None,
+        &retry(),
+        &RequestOptions::default(),
+    )
+    .await
+    .unwrap()
+    .try_collect()
+    .await
+    .unwrap();
+    assert_eq!(rows.len(), 2);
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests[0].body_text(), "username=etl&password=hunter2");
+    assert_eq!(
+        requests[0].header("content-type"),
+        Some("application/x-www-form-urlencoded")
+    );
+}
+
+#[tokio::test]
+async fn test_without_cookie_jar_session_is_lost() {
+    std::env::set_var("APITAP_TEST_SESSION_PASSWORD", "hunter2");
+    let base = serve_session(Arc::new(Mutex::new(Vec::new()))).await;
+    let client = Http::new(&base).build_client().unwrap();
+    login(&base).send(&client).await.unwrap();
+    let err = ndjson_stream_with(
+        &client,
+        &format!("{base}/items"),
+        &[],
+        None,
+        &retry(),
+        &RequestOptions::default(),
+    )
+    .await
+    .err()
+    .unwrap();
+    assert!(err.to_string().contains("401"), "{err}");
+}
+
+#[tokio::test]
+async fn test_failed_login_is_an_error() {
+    let base = serve_session(Arc::new(Mutex::new(Vec::new()))).await;
+    let client = Http::new(&base)
+        .cookie_jar(Arc::new(Jar::default()))
+        .build_client()
+        .unwrap();
+    let bad: PreRequest = serde_yaml::from_str(&format!(
+        "url: {base}/login\ncontent_type: form\nbody: {{password: wrong}}\n"
+    ))
+    .unwrap();
+    let err = bad.send(&client).await.unwrap_err();
+    assert!(err.to_string().contains("403"), "{err}");
+}
+
+#[test]
+fn test_source_session_config() {
+    let source: Source = serde_yaml::from_str(
+        "name: app\nurl: https://app.example.com/api/items\ntable_destination_name: items\nretry: {max_attempts: 1, max_delay_secs: 1, min_delay_secs: 1}\ncookies: true\npre_request:\n  url: https://app.example.com/login\n  headers:\n    - key: X-Client\n      value: apitap\n",
+    )
+    .unwrap();
+    assert!(source.cookies);
+    let login = source.pre_request.unwrap();
+    assert_eq!(login.method, apitap::http::body::RequestMethod::Post);
+    assert_eq!(login.headers[0].key, "X-Client");
+}
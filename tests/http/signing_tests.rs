@@ -0,0 +1,168 @@
+use std::sync::{Arc, Mutex};
+
+use super::{retry, serve, StubResponse};
+use apitap::http::auth::SourceAuth;
+use apitap::http::fetcher::{send_page_request, RequestOptions};
+use apitap::http::signing::{HmacAlgorithm, HmacConfig, HmacSigner, SignedPart};
+use apitap::pipeline::Retry;
+use reqwest::{Client, Method};
+
+fn config(yaml: &str) -> HmacConfig {
+    match serde_yaml::from_str(yaml).unwrap() {
+        SourceAuth::Hmac(config) => config,
+        other => panic!("expected hmac auth, got {other:?}"),
+    }
+}
+
+fn request(method: Method, url: &str, body: Option<&str>) -> reqwest::Request {
+    let mut builder = Client::new().request(method, url);
+    if let Some(body) = body {
+        builder = builder.body(body.to_string());
+    }
+    builder.build().unwrap()
+}
+
+#[test]
+fn test_hmac_defaults() {
+    let config = config("kind: hmac\nsecret_env: S");
+    assert_eq!(config.algorithm, HmacAlgorithm::Sha256);
+    assert_eq!(
+        config.sign,
+        vec![SignedPart::Method, SignedPart::Path, SignedPart::Query]
+    );
+    assert_eq!(config.separator, "\n");
+    assert_eq!(config.header, "X-Signature");
+}
+
+#[test]
+fn test_hmac_message_joins_parts_in_order() {
+    std::env::set_var("APITAP_TEST_HMAC_MESSAGE", "key");
+    let signer = HmacSigner::new(&config(
+        "kind: hmac\nsecret_env: APITAP_TEST_HMAC_MESSAGE\nsign: [timestamp, method, path, query, body]\nseparator: '|'",
+    ))
+    .unwrap();
+    let req = request(
+        Method::POST,
+        "https://api.example.com/v1/orders?limit=10&page=2",
+        Some(r#"{"a":1}"#),
+    );
+    assert_eq!(
+        String::from_utf8(signer.message(&req, "1700000000")).unwrap(),
+        r#"1700000000|POST|/v1/orders|limit=10&page=2|{"a":1}"#
+    );
+}
+
+#[test]
+fn test_hmac_signature_known_vectors() {
+    std::env::set_var("APITAP_TEST_HMAC_VECTOR", "key");
+    let message = b"The quick brown fox jumps over the lazy dog";
+    let signer =
+        HmacSigner::new(&config("kind: hmac\nsecret_env: APITAP_TEST_HMAC_VECTOR")).unwrap();
+    assert_eq!(
+        signer.signature(message),
+        "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+    );
+
+    let signer = HmacSigner::new(&config(
+        "kind: hmac\nsecret_env: APITAP_TEST_HMAC_VECTOR\nencoding: base64\nprefix: 'sha256='",
+    ))
+    .unwrap();
+    assert_eq!(
+        signer.signature(message),
+        "sha256=97yD9DBThCSxMpjmqm+xQ+9NWaFJRhdZl0edvC0aPNg="
+    );
+
+    let signer = HmacSigner::new(&config(
+        "kind: hmac\nsecret_env: APITAP_TEST_HMAC_VECTOR\nalgorithm: sha512",
+    ))
+    .unwrap();
+    assert!(signer
+        .signature(message)
+        .starts_with("b42af09057bac1e2d41708e48a902e09"));
+}
+
+#[tokio::test]
+async fn test_hmac_sets_signature_timestamp_and_key_headers() {
+    std::env::set_var("APITAP_TEST_HMAC_SECRET", "s3cret");
+    std::env::set_var("APITAP_TEST_HMAC_KEY", "key-1");
+    let auth: SourceAuth = serde_yaml::from_str(
+        "kind: hmac\nsecret_env: APITAP_TEST_HMAC_SECRET\nsign: [timestamp, method, path]\n\
+         header: X-Sig\ntimestamp_header: X-Ts\nkey_header: X-Key\nkey_env: APITAP_TEST_HMAC_KEY",
+    )
+    .unwrap();
+    let authenticator = auth.authenticator(&Client::new(), None, None).unwrap();
+    let mut req = request(Method::GET, "https://api.example.com/v1/orders", None);
+    authenticator.authorize(&mut req).await.unwrap();
+
+    let header = |name: &str| req.headers()[name].to_str().unwrap().to_string();
+    assert_eq!(header("x-key"), "key-1");
+    let timestamp = header("x-ts");
+    assert!(timestamp.parse::<i64>().is_ok());
+
+    let SourceAuth::Hmac(config) = auth else {
+        unreachable!()
+    };
+    let signer = HmacSigner::new(&config).unwrap();
+    let expected = signer.signature(format!("{timestamp}\nGET\n/v1/orders").as_bytes());
+    assert_eq!(header("x-sig"), expected);
+}
+
+#[tokio::test]
+async fn test_hmac_signs_each_retry_again() {
+    std::env::set_var("APITAP_TEST_HMAC_RETRY", "s3cret");
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&seen);
+    let base = serve(move |req| {
+        let mut log = log.lock().unwrap();
+        log.push((
+            req.header("x-ts").unwrap().to_string(),
+            req.header("x-signature").unwrap().to_string(),
+        ));
+        if log.len() == 1 {
+            StubResponse::status("429 Too Many Requests").header("Retry-After", 1)
+        } else {
+            StubResponse::json("[]")
+        }
+    })
+    .await;
+    let auth: SourceAuth = serde_yaml::from_str(
+        "kind: hmac\nsecret_env: APITAP_TEST_HMAC_RETRY\nsign: [timestamp, method, path]\n\
+         timestamp_header: X-Ts",
+    )
+    .unwrap();
+    let client = Client::new();
+    let request = RequestOptions {
+        auth: Some(auth.authenticator(&client, None, None).unwrap()),
+        ..Default::default()
+    };
+    let retry = Retry {
+        max_attempts: 1,
+        ..retry()
+    };
+    let resp = send_page_request(&client, &format!("{base}/orders"), &[], &retry, &request)
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 2);
+    let (first, retried) = (&seen[0], &seen[1]);
+    assert!(
+        retried.0.parse::<i64>().unwrap() > first.0.parse::<i64>().unwrap(),
+        "{seen:?}"
+    );
+    assert_ne!(retried.1, first.1);
+}
+
+#[test]
+fn test_hmac_needs_secret_and_paired_key_settings() {
+    let err =
+        HmacSigner::new(&config("kind: hmac\nsecret_env: APITAP_TEST_HMAC_UNSET")).unwrap_err();
+    assert!(err.to_string().contains("APITAP_TEST_HMAC_UNSET"));
+
+    std::env::set_var("APITAP_TEST_HMAC_PAIRED", "s");
+    assert!(HmacSigner::new(&config(
+        "kind: hmac\nsecret_env: APITAP_TEST_HMAC_PAIRED\nkey_header: X-Key"
+    ))
+    .is_err());
+}
@@ -0,0 +1,150 @@
+use super::{retry, serve, StubResponse};
+use apitap::http::csv_stream::ResponseFormat;
+use apitap::http::fetcher::{ndjson_stream_with, RequestOptions};
+use apitap::http::sse_stream::{event_rows, sse_stream, SseEvent, SseOptions, SseParser};
+use apitap::utils::http_retry::build_client_with_retry;
+use futures::TryStreamExt;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+fn event(data: &str) -> SseEvent {
+    SseEvent {
+        event: None,
+        data: data.to_string(),
+        id: None,
+    }
+}
+
+/// An event stream that ends when the connection closes.
+fn sse(body: &str) -> StubResponse {
+    StubResponse::ok(body)
+        .header("content-type", "text/event-stream")
+        .without_length()
+}
+
+#[test]
+fn test_parser_fields_and_chunk_boundaries() {
+    let mut parser = SseParser::default();
+    assert!(parser
+        .feed(b": keep-alive\n\nretry: 2500\nid: 7\nevent: or")
+        .is_empty());
+    let events = parser.feed(b"der\r\ndata: {\"a\":\ndata:1}\r\n\r\ndata: x\n");
+    assert_eq!(
+        events,
+        vec![SseEvent {
+            event: Some("order".to_string()),
+            data: "{\"a\":\n1}".to_string(),
+            id: Some("7".to_string()),
+        }]
+    );
+    assert_eq!(parser.retry, Some(Duration::from_millis(2500)));
+
+    // The ID carries over; the event type does not.
+    let events = parser.feed(b"\n");
+    assert_eq!(events[0].event, None);
+    assert_eq!(events[0].id.as_deref(), Some("7"));
+    assert_eq!(parser.last_event_id.as_deref(), Some("7"));
+}
+
+#[test]
+fn test_event_rows() {
+    assert_eq!(
+        event_rows(&event(r#"{"id":1}"#), None),
+        vec![json!({"id": 1})]
+    );
+    assert_eq!(
+        event_rows(&event(r#"[{"id":1},2]"#), None),
+        vec![json!({"id": 1}), json!({"data": 2})]
+    );
+    assert_eq!(
+        event_rows(&event("heartbeat"), None),
+        vec![json!({"data": "heartbeat"})]
+    );
+    assert_eq!(
+        event_rows(
+            &event(r#"{"payload":{"items":[{"id":1},{"id":2}]}}"#),
+            Some("/payload/items")
+        ),
+        vec![json!({"id": 1}), json!({"id": 2})]
+    );
+    assert!(event_rows(&event("null"), None).is_empty());
+}
+
+#[test]
+fn test_sse_options() {
+    let opts: SseOptions = serde_yaml::from_str("max_duration: 5m\n").unwrap();
+    assert!(opts.reconnect);
+    assert_eq!(opts.max_duration().unwrap(), Some(Duration::from_secs(300)));
+    let format: ResponseFormat = serde_yaml::from_str("sse").unwrap();
+    assert_eq!(format, ResponseFormat::Sse);
+
+    let bad = SseOptions {
+        max_duration: Some("soon".to_string()),
+        reconnect: true,
+    };
+    assert!(bad.max_duration().is_err());
+}
+
+#[tokio::test]
+async fn test_event_stream_content_type_detected() {
+    let base = serve(|_| sse("data: {\"id\":1}\n\ndata: {\"id\":2}\n\n")).await;
+
+    let rows: Vec<_> = ndjson_stream_with(
+        &reqwest::Client::new(),
+        &format!("{base}/events"),
+        &[],
+        None,
+        &retry(),
+        &RequestOptions::default(),
+    )
+    .await
+    .unwrap()
+    .try_collect()
+    .await
+    .unwrap();
+
+    assert_eq!(rows, vec![json!({"id": 1}), json!({"id": 2})]);
+}
+
+#[tokio::test]
+async fn test_reconnects_with_last_event_id_until_max_duration() {
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&requests);
+    let base = serve(move |req| {
+        let mut seen = seen.lock().unwrap();
+        seen.push(req);
+        if seen.len() == 1 {
+            // Drop the connection after one event.
+            sse("retry: 10\nid: 1\ndata: {\"n\":1}\n\n")
+        } else {
+            // Stay open past max_duration.
+            sse("id: 2\ndata: {\"n\":2}\n\n").hold_open(Duration::from_secs(10))
+        }
+    })
+    .await;
+
+    let request = RequestOptions {
+        format: ResponseFormat::Sse,
+        sse: SseOptions {
+            max_duration: Some("1s".to_string()),
+            reconnect: true,
+        },
+        ..Default::default()
+    };
+    let client = build_client_with_retry(reqwest::Client::new(), &retry());
+    let started = Instant::now();
+    let rows: Vec<_> = sse_stream(client, format!("{base}/events"), Vec::new(), None, request)
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+
+    assert_eq!(rows, vec![json!({"n": 1}), json!({"n": 2})]);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].header("accept"), Some("text/event-stream"));
+    assert_eq!(requests[0].header("last-event-id"), None);
+    assert_eq!(requests[1].header("last-event-id"), Some("1"));
+}
@@ -0,0 +1,206 @@
+use super::{query_num, retry, serve, CollectRows, StubResponse};
+use apitap::http::fetcher::{PaginatedFetcher, Pagination, StartAt, TotalHint};
+use apitap::writer::WriteMode;
+use futures::TryStreamExt;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+/// Ten items (ids 0..10) by `offset`/`limit` or `page`/`per_page`, with
+/// `meta.total`; `after` serves them next_url style, or by the `cursor` in
+/// the body. Returns the url and the request log.
+async fn serve_items() -> (String, Arc<Mutex<Vec<String>>>) {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&seen);
+    let base = serve(move |req| {
+        log.lock().unwrap().push(req.target.clone());
+        let size = req.num("limit").or(req.num("per_page")).unwrap_or(3);
+        let from = req
+            .num("offset")
+            .or(req.num("page").map(|p| (p - 1) * size))
+            .or(req.num("after"))
+            .unwrap_or(0);
+        let to = (from + size).min(10);
+        let items: Vec<Value> = (from..to).map(|id| json!({"id": id})).collect();
+        let next = (to < 10).then(|| format!("/items?after={to}"));
+        let cursor = (to < 10).then(|| to.to_string());
+        StubResponse::json(
+            json!({"meta": {"total": 10}, "items": items, "next": next, "cursor": cursor}),
+        )
+    })
+    .await;
+    (format!("{base}/items"), seen)
+}
+
+#[test]
+fn test_start_from_yaml_and_validation() {
+    let start: StartAt = serde_yaml::from_str("offset: 5000").unwrap();
+    assert_eq!(start.offset, Some(5000));
+    let limit_offset: Pagination =
+        serde_yaml::from_str("kind: limit_offset\nlimit_param: limit\noffset_param: offset")
+            .unwrap();
+    assert!(start.validate(Some(&limit_offset)).is_ok());
+    assert!(StartAt::default().validate(None).is_ok());
+
+    let page = StartAt {
+        page: Some(3),
+        ..Default::default()
+    };
+    let err = page.validate(Some(&limit_offset)).unwrap_err();
+    assert!(err.to_string().contains("start.page needs page_number"));
+    let zero = StartAt {
+        page: Some(0),
+        ..Default::default()
+    };
+    assert!(zero.validate(None).is_err());
+    let both = StartAt {
+        offset: Some(1),
+        page: Some(2),
+        ..Default::default()
+    };
+    assert!(both.validate(Some(&limit_offset)).is_err());
+
+    let cursor: Pagination = serde_yaml::from_str(
+        "kind: cursor\ncursor_param: after\npage_size_param: limit\nnext_cursor_path: /cursor",
+    )
+    .unwrap();
+    let start: StartAt = serde_yaml::from_str("cursor: abc").unwrap();
+    assert!(start.validate(Some(&cursor)).is_ok());
+    let err = start.validate(Some(&limit_offset)).unwrap_err();
+    assert!(err.to_string().contains("start.cursor needs cursor"));
+    let url = StartAt {
+        url: Some("https://api.example.com/items?after=abc".into()),
+        ..Default::default()
+    };
+    assert!(url.validate(Some(&cursor)).is_ok());
+}
+
+#[tokio::test]
+async fn test_limit_offset_starts_at_offset() {
+    let (url, seen) = serve_items().await;
+    for hint in [
+        None,
+        Some(TotalHint::Items {
+            pointer: "/meta/total".into(),
+        }),
+    ] {
+        seen.lock().unwrap().clear();
+        let fetcher = PaginatedFetcher::new(reqwest::Client::new(), url.clone(), 4)
+            .with_limit_offset("limit", "offset")
+            .with_start(StartAt {
+                offset: Some(5),
+                ..Default::default()
+            });
+        let writer = Arc::new(CollectRows::default());
+        let stats = fetcher
+            .fetch_limit_offset(
+                3,
+                Some("/items".into()),
+                None,
+                hint.clone(),
+                writer.clone(),
+                WriteMode::Append,
+                &retry(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(writer.ids(), (5..10).collect::<Vec<_>>(), "{hint:?}");
+        assert_eq!(stats.total_items, 5);
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen[0], "/items?limit=3&offset=5");
+        assert!(seen.iter().all(|t| query_num(t, "offset").unwrap() >= 5));
+    }
+}
+
+#[tokio::test]
+async fn test_page_number_starts_at_page() {
+    let (url, seen) = serve_items().await;
+    let mut pagination: Pagination =
+        serde_yaml::from_str("kind: page_number\npage_param: page\nper_page_param: per_page")
+            .unwrap();
+    if let Pagination::PageNumber {
+        total_items_pointer,
+        ..
+    } = &mut pagination
+    {
+        *total_items_pointer = Some("/meta/total".into());
+    }
+    let fetcher = PaginatedFetcher::new(reqwest::Client::new(), url, 4)
+        .with_pagination(pagination.clone())
+        .with_start(StartAt {
+            page: Some(3),
+            ..Default::default()
+        });
+    let writer = Arc::new(CollectRows::default());
+    fetcher
+        .fetch_page_number(
+            3,
+            Some("/items"),
+            None,
+            pagination.total_hint().unwrap(),
+            writer.clone(),
+            WriteMode::Append,
+            &retry(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(writer.ids(), (6..10).collect::<Vec<_>>());
+    let mut pages: Vec<u64> = seen
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|t| query_num(t, "page").unwrap())
+        .collect();
+    pages.sort();
+    assert_eq!(pages, vec![3, 4]);
+}
+
+#[tokio::test]
+async fn test_next_url_starts_at_url() {
+    let (url, seen) = serve_items().await;
+    let start = url.replace("/items", "/items?after=7");
+    let fetcher = PaginatedFetcher::new(reqwest::Client::new(), url, 1)
+        .with_next_url("/next", Some("limit".into()))
+        .with_start(StartAt {
+            url: Some(start),
+            ..Default::default()
+        });
+    let rows: Vec<Value> = fetcher
+        .follow_next_stream(3, Some("/items"), None, &retry())
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    let ids: Vec<u64> = rows.iter().map(|r| r["id"].as_u64().unwrap()).collect();
+    assert_eq!(ids, vec![7, 8, 9]);
+    assert_eq!(*seen.lock().unwrap(), vec!["/items?after=7"]);
+}
+
+#[tokio::test]
+async fn test_cursor_starts_at_cursor() {
+    let (url, seen) = serve_items().await;
+    let pagination: Pagination = serde_yaml::from_str(
+        "kind: cursor\ncursor_param: after\npage_size_param: limit\nnext_cursor_path: /cursor",
+    )
+    .unwrap();
+    let fetcher = PaginatedFetcher::new(reqwest::Client::new(), url, 1)
+        .with_pagination(pagination)
+        .with_start(StartAt {
+            cursor: Some("4".into()),
+            ..Default::default()
+        });
+    let rows: Vec<Value> = fetcher
+        .follow_next_stream(3, Some("/items"), None, &retry())
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    let ids: Vec<u64> = rows.iter().map(|r| r["id"].as_u64().unwrap()).collect();
+    assert_eq!(ids, (4..10).collect::<Vec<_>>());
+    // The first request carries the start cursor and the page size.
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec!["/items?limit=3&after=4", "/items?limit=3&after=7"]
+    );
+}
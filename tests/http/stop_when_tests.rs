@@ -0,0 +1,185 @@
+use super::{query_num, retry, serve, CollectRows, StubResponse};
+use apitap::http::fetcher::{PaginatedFetcher, Pagination, RequestOptions, StopWhen};
+use apitap::writer::WriteMode;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+/// Seven items by `offset`/`limit` or `page`/`per_page`, each page with
+/// `has_more`. Returns the url and the request log.
+async fn serve_items() -> (String, Arc<Mutex<Vec<String>>>) {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&seen);
+    let base = serve(move |req| {
+        log.lock().unwrap().push(req.target.clone());
+        let size = req.num("limit").or(req.num("per_page")).unwrap_or(3);
+        let from = req
+            .num("offset")
+            .or(req.num("page").map(|p| (p - 1) * size))
+            .unwrap_or(0)
+            .min(7);
+        let to = (from + size).min(7);
+        let items: Vec<Value> = (from..to).map(|id| json!({"id": id})).collect();
+        StubResponse::json(json!({"items": items, "has_more": to < 7}))
+    })
+    .await;
+    (format!("{base}/items"), seen)
+}
+
+fn has_more_flag() -> StopWhen {
+    serde_yaml::from_str("pointer: /has_more").unwrap()
+}
+
+#[test]
+fn test_stop_when_from_yaml() {
+    let pagination: Pagination = serde_yaml::from_str(
+        "kind: page_number\npage_param: page\nper_page_param: per_page\nstop_when:\n  pointer: /has_more\n  equals: false",
+    )
+    .unwrap();
+    let stop = pagination.stop_when().unwrap();
+    assert_eq!(stop.pointer.as_deref(), Some("/has_more"));
+    assert_eq!(stop.equals, json!(false));
+    assert!(!stop.short_page);
+
+    let short: Pagination = serde_yaml::from_str(
+        "kind: limit_offset\nlimit_param: limit\noffset_param: offset\nstop_when:\n  short_page: true",
+    )
+    .unwrap();
+    assert!(short.stop_when().unwrap().short_page);
+
+    let bad: Pagination = serde_yaml::from_str(
+        "kind: limit_offset\nlimit_param: limit\noffset_param: offset\nstop_when:\n  pointer: has_more",
+    )
+    .unwrap();
+    assert!(bad.stop_when().is_err());
+}
+
+#[test]
+fn test_stop_when_more() {
+    let default = StopWhen::default();
+    assert!(default.more(None, 2, 3));
+    assert!(!default.more(None, 0, 3));
+
+    let short = StopWhen {
+        short_page: true,
+        ..Default::default()
+    };
+    assert!(short.more(None, 3, 3));
+    assert!(!short.more(None, 2, 3));
+
+    let flag = has_more_flag();
+    assert!(flag.more(Some(&json!({"has_more": true})), 3, 3));
+    assert!(!flag.more(Some(&json!({"has_more": false})), 3, 3));
+    // Without the field, only an empty page ends the walk.
+    assert!(flag.more(Some(&json!({})), 3, 3));
+}
+
+#[tokio::test]
+async fn test_empty_page_is_the_default_stop() {
+    let (url, seen) = serve_items().await;
+    let fetcher =
+        PaginatedFetcher::new(reqwest::Client::new(), url, 1).with_limit_offset("limit", "offset");
+    let writer = Arc::new(CollectRows::default());
+    fetcher
+        .fetch_limit_offset(
+            3,
+            Some("/items".into()),
+            None,
+            None,
+            writer.clone(),
+            WriteMode::Append,
+            &retry(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(writer.rows().len(), 7);
+    assert_eq!(seen.lock().unwrap().len(), 4);
+}
+
+#[tokio::test]
+async fn test_short_page_stops_limit_offset() {
+    let (url, seen) = serve_items().await;
+    let fetcher = PaginatedFetcher::new(reqwest::Client::new(), url, 1)
+        .with_limit_offset("limit", "offset")
+        .with_stop_when(StopWhen {
+            short_page: true,
+            ..Default::default()
+        });
+    let writer = Arc::new(CollectRows::default());
+    fetcher
+        .fetch_limit_offset(
+            3,
+            Some("/items".into()),
+            None,
+            None,
+            writer.clone(),
+            WriteMode::Append,
+            &retry(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(writer.rows().len(), 7);
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![
+            "/items?limit=3&offset=0",
+            "/items?limit=3&offset=3",
+            "/items?limit=3&offset=6"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_has_more_flag_stops_page_number() {
+    let (url, seen) = serve_items().await;
+    let fetcher = PaginatedFetcher::new(reqwest::Client::new(), url, 1)
+        .with_page_number("page", "per_page")
+        .with_stop_when(has_more_flag());
+    let writer = Arc::new(CollectRows::default());
+    fetcher
+        .fetch_page_number(
+            3,
+            Some("/items"),
+            None,
+            None,
+            writer.clone(),
+            WriteMode::Append,
+            &retry(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(writer.rows().len(), 7);
+    let pages: Vec<u64> = seen
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|t| query_num(t, "page").unwrap())
+        .collect();
+    assert_eq!(pages, vec![1, 2, 3]);
+}
+
+#[tokio::test]
+async fn test_has_more_flag_stops_streamed_json() {
+    let (url, seen) = serve_items().await;
+    let fetcher = PaginatedFetcher::new(reqwest::Client::new(), url, 1)
+        .with_limit_offset("limit", "offset")
+        .with_stop_when(has_more_flag())
+        .with_request_options(RequestOptions {
+            stream_json: true,
+            ..Default::default()
+        });
+    let writer = Arc::new(CollectRows::default());
+    fetcher
+        .fetch_limit_offset(
+            3,
+            Some("/items".into()),
+            None,
+            None,
+            writer.clone(),
+            WriteMode::Append,
+            &retry(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(writer.rows().len(), 7);
+    assert_eq!(seen.lock().unwrap().len(), 3);
+}
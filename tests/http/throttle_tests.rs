@@ -0,0 +1,210 @@
+// Tests for X-RateLimit header throttling
+
+use super::{serve, StubResponse};
+use apitap::http::fetcher::{send_page_request, RequestOptions};
+use apitap::http::throttle::{retry_after, RateBudget, ServerThrottle, Throttled};
+use apitap::pipeline::Retry;
+use reqwest::header::{HeaderMap, HeaderValue};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+    let mut map = HeaderMap::new();
+    for (k, v) in pairs {
+        map.insert(*k, HeaderValue::from_str(v).unwrap());
+    }
+    map
+}
+
+#[test]
+fn test_budget_from_headers_delta_reset() {
+    let budget = RateBudget::from_headers(&headers(&[
+        ("x-ratelimit-remaining", "42"),
+        ("x-ratelimit-limit", "100"),
+        ("x-ratelimit-reset", "30"),
+    ]))
+    .unwrap();
+
+    assert_eq!(budget.remaining, Some(42));
+    assert_eq!(budget.limit, Some(100));
+    assert_eq!(budget.reset_in, Some(Duration::from_secs(30)));
+}
+
+#[test]
+fn test_budget_from_headers_epoch_reset() {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let budget = RateBudget::from_headers(&headers(&[
+        ("ratelimit-remaining", "5"),
+        ("ratelimit-reset", &(now + 60).to_string()),
+    ]))
+    .unwrap();
+
+    let reset = budget.reset_in.unwrap().as_secs();
+    assert!((58..=60).contains(&reset), "reset was {reset}");
+}
+
+#[test]
+fn test_budget_from_headers_missing() {
+    assert!(RateBudget::from_headers(&HeaderMap::new()).is_none());
+    assert!(RateBudget::from_headers(&headers(&[("x-ratelimit-limit", "100")])).is_none());
+}
+
+#[test]
+fn test_pacing_delay() {
+    let plenty = RateBudget {
+        remaining: Some(50),
+        limit: Some(100),
+        reset_in: Some(Duration::from_secs(60)),
+    };
+    assert_eq!(plenty.pacing_delay(), Duration::ZERO);
+
+    let low = RateBudget {
+        remaining: Some(5),
+        ..plenty
+    };
+    assert_eq!(low.pacing_delay(), Duration::from_secs(12));
+
+    let exhausted = RateBudget {
+        remaining: Some(0),
+        ..plenty
+    };
+    assert_eq!(exhausted.pacing_delay(), Duration::from_secs(60));
+
+    let no_reset = RateBudget {
+        remaining: Some(0),
+        limit: None,
+        reset_in: None,
+    };
+    assert_eq!(no_reset.pacing_delay(), Duration::ZERO);
+}
+
+#[tokio::test]
+async fn test_server_throttle_observe() {
+    let throttle = ServerThrottle::new("users");
+    assert!(throttle.budget().await.is_none());
+
+    throttle
+        .observe(&headers(&[
+            ("x-ratelimit-remaining", "99"),
+            ("x-ratelimit-limit", "100"),
+        ]))
+        .await;
+    assert_eq!(throttle.budget().await.unwrap().remaining, Some(99));
+
+    // Plenty of budget left: no waiting.
+    tokio::time::timeout(Duration::from_secs(1), throttle.wait())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_concurrent_waiters_are_spaced() {
+    let throttle = Arc::new(ServerThrottle::new("users"));
+    // 4 requests left for the next second: one every 250ms.
+    throttle
+        .observe(&headers(&[
+            ("x-ratelimit-remaining", "4"),
+            ("x-ratelimit-limit", "100"),
+            ("x-ratelimit-reset", "1"),
+        ]))
+        .await;
+
+    let start = Instant::now();
+    let waiters = (0..4).map(|_| {
+        let throttle = throttle.clone();
+        tokio::spawn(async move {
+            throttle.wait().await;
+            start.elapsed()
+        })
+    });
+    let mut released = Vec::new();
+    for waiter in waiters {
+        released.push(waiter.await.unwrap());
+    }
+    released.sort();
+
+    assert!(released[0] >= Duration::from_millis(200), "{released:?}");
+    for pair in released.windows(2) {
+        assert!(
+            pair[1] - pair[0] >= Duration::from_millis(200),
+            "{released:?}"
+        );
+    }
+}
+
+#[test]
+fn test_retry_after_headers() {
+    assert_eq!(
+        retry_after(&headers(&[("retry-after", "7")])),
+        Some(Duration::from_secs(7))
+    );
+
+    let at = chrono::Utc::now() + chrono::Duration::seconds(30);
+    let wait = retry_after(&headers(&[("retry-after", &at.to_rfc2822())])).unwrap();
+    assert!((28..=30).contains(&wait.as_secs()), "wait was {wait:?}");
+
+    let past = retry_after(&headers(&[(
+        "retry-after",
+        "Wed, 21 Oct 2015 07:28:00 GMT",
+    )]));
+    assert_eq!(past, Some(Duration::ZERO));
+
+    // Without Retry-After, the rate-limit reset says when to come back.
+    assert_eq!(
+        retry_after(&headers(&[("x-ratelimit-reset", "12")])),
+        Some(Duration::from_secs(12))
+    );
+    assert_eq!(retry_after(&HeaderMap::new()), None);
+}
+
+/// Answers the first request with a 429 asking to wait a second, then `[]`.
+async fn serve_throttled() -> (String, Arc<AtomicUsize>) {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let seen = hits.clone();
+    let base = serve(move |_| {
+        if seen.fetch_add(1, Ordering::SeqCst) == 0 {
+            StubResponse::status("429 Too Many Requests").header("Retry-After", 1)
+        } else {
+            StubResponse::json("[]")
+        }
+    })
+    .await;
+    (format!("{base}/items"), hits)
+}
+
+#[tokio::test]
+async fn test_429_waits_for_retry_after() {
+    let (url, hits) = serve_throttled().await;
+    let throttle = Arc::new(ServerThrottle::new("items"));
+    let request = RequestOptions {
+        throttle: Some(throttle.clone()),
+        ..Default::default()
+    };
+    // Exponential backoff alone would retry after 0s.
+    let retry = Retry {
+        max_attempts: 3,
+        max_delay_secs: 0,
+        min_delay_secs: 0,
+        ..Default::default()
+    };
+
+    let started = Instant::now();
+    let resp = send_page_request(&reqwest::Client::new(), &url, &[], &retry, &request)
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), 200);
+    assert!(started.elapsed() >= Duration::from_secs(1));
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+    assert_eq!(
+        throttle.throttled().await,
+        Throttled {
+            responses: 1,
+            waited: Duration::from_secs(1)
+        }
+    );
+}
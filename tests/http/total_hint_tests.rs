@@ -0,0 +1,123 @@
+use super::{retry, serve, CollectRows, StubResponse};
+use apitap::http::fetcher::{PaginatedFetcher, Pagination, TotalHint};
+use apitap::writer::WriteMode;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+/// Seven items served two at a time by `offset`, announcing the total in
+/// `meta.total` only when `with_total`. Returns the url and the request log.
+async fn serve_items(with_total: bool) -> (String, Arc<Mutex<Vec<String>>>) {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&seen);
+    let base = serve(move |req| {
+        log.lock().unwrap().push(req.target.clone());
+        let offset = req.num("offset").unwrap_or(0);
+        let items: Vec<Value> = (offset..(offset + 2).min(7))
+            .map(|id| json!({"id": id}))
+            .collect();
+        if with_total {
+            StubResponse::json(json!({"meta": {"total": 7}, "items": items}))
+        } else {
+            StubResponse::json(json!({"items": items}))
+        }
+    })
+    .await;
+    (format!("{base}/items"), seen)
+}
+
+async fn fetch(url: String, hint: Option<TotalHint>) -> (Vec<u64>, usize) {
+    let fetcher =
+        PaginatedFetcher::new(reqwest::Client::new(), url, 4).with_limit_offset("limit", "offset");
+    let writer = Arc::new(CollectRows::default());
+    let stats = fetcher
+        .fetch_limit_offset(
+            2,
+            Some("/items".into()),
+            None,
+            hint,
+            writer.clone(),
+            WriteMode::Append,
+            &retry(),
+        )
+        .await
+        .unwrap();
+    (writer.ids(), stats.total_items)
+}
+
+#[test]
+fn test_total_pointers_from_yaml() {
+    let p: Pagination = serde_yaml::from_str(
+        "kind: limit_offset\nlimit_param: limit\noffset_param: offset\ntotal_items_pointer: /meta/total",
+    )
+    .unwrap();
+    assert!(matches!(
+        p.total_hint().unwrap(),
+        Some(TotalHint::Items { ref pointer }) if pointer == "/meta/total"
+    ));
+
+    let p: Pagination = serde_yaml::from_str(
+        "kind: page_number\npage_param: page\nper_page_param: per_page\ntotal_pages_pointer: /total_pages",
+    )
+    .unwrap();
+    assert!(matches!(
+        p.total_hint().unwrap(),
+        Some(TotalHint::Pages { ref pointer }) if pointer == "/total_pages"
+    ));
+
+    let both: Pagination = serde_yaml::from_str(
+        "kind: page_number\npage_param: page\nper_page_param: per_page\ntotal_items_pointer: /a\ntotal_pages_pointer: /b",
+    )
+    .unwrap();
+    assert!(both.total_hint().is_err());
+}
+
+#[test]
+fn test_total_pages_from_first_response() {
+    let first = json!({"meta": {"total": 7, "pages": 4}});
+    let items = TotalHint::Items {
+        pointer: "/meta/total".into(),
+    };
+    assert_eq!(items.total_pages(&first, 2), Some(4));
+    assert_eq!(items.total_pages(&first, 7), Some(1));
+    let pages = TotalHint::Pages {
+        pointer: "/meta/pages".into(),
+    };
+    assert_eq!(pages.total_pages(&first, 2), Some(4));
+    assert_eq!(pages.total_pages(&json!({}), 2), None);
+}
+
+#[tokio::test]
+async fn test_limit_offset_with_total_fetches_known_pages() {
+    let (url, seen) = serve_items(true).await;
+    let hint = TotalHint::Items {
+        pointer: "/meta/total".into(),
+    };
+    let (ids, total) = fetch(url, Some(hint)).await;
+    assert_eq!(ids, (0..7).collect::<Vec<_>>());
+    assert_eq!(total, 7);
+    // Exactly four pages: no trailing empty probe.
+    let mut seen = seen.lock().unwrap().clone();
+    seen.sort();
+    assert_eq!(
+        seen,
+        vec![
+            "/items?limit=2&offset=0",
+            "/items?limit=2&offset=2",
+            "/items?limit=2&offset=4",
+            "/items?limit=2&offset=6",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_limit_offset_missing_total_falls_back_to_serial() {
+    let (url, seen) = serve_items(false).await;
+    let hint = TotalHint::Items {
+        pointer: "/meta/total".into(),
+    };
+    let (ids, total) = fetch(url, Some(hint)).await;
+    assert_eq!(ids, (0..7).collect::<Vec<_>>());
+    assert_eq!(total, 7);
+    // Walks until the empty page past the end.
+    assert_eq!(seen.lock().unwrap().len(), 5);
+}
@@ -0,0 +1,99 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use super::{retry, StubResponse};
+use apitap::http::auth::SourceAuth;
+use apitap::http::fetcher::{ndjson_stream_with, send_page_request, RequestOptions};
+use apitap::http::usage::UsageMeter;
+use apitap::pipeline::run::{format_bytes, Schedule};
+use apitap::pipeline::Retry;
+use futures::TryStreamExt;
+use serde_json::json;
+
+const BODY: &str = r#"[{"id":1},{"id":2}]"#;
+
+/// Answer every request with `BODY`.
+async fn serve() -> String {
+    let base = super::serve(|_| StubResponse::json(BODY)).await;
+    format!("{base}/items")
+}
+
+#[tokio::test]
+async fn test_usage_meter_counts_requests_and_bytes() {
+    let url = serve().await;
+    let usage = Arc::new(UsageMeter::default());
+    let request = RequestOptions {
+        usage: Some(Arc::clone(&usage)),
+        ..Default::default()
+    };
+    for _ in 0..3 {
+        let rows: Vec<_> =
+            ndjson_stream_with(&reqwest::Client::new(), &url, &[], None, &retry(), &request)
+                .await
+                .unwrap()
+                .try_collect()
+                .await
+                .unwrap();
+        assert_eq!(rows.len(), 2);
+    }
+    assert_eq!(usage.requests(), 3);
+    assert_eq!(usage.bytes(), 3 * BODY.len() as u64);
+}
+
+#[tokio::test]
+async fn test_usage_meter_counts_retries_and_token_requests() {
+    std::env::set_var("APITAP_TEST_USAGE_CLIENT_ID", "id");
+    std::env::set_var("APITAP_TEST_USAGE_CLIENT_SECRET", "secret");
+    let page_requests = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&page_requests);
+    let base = super::serve(move |req| {
+        if req.target == "/token" {
+            return StubResponse::json(json!({"access_token": "t-1", "expires_in": 3600}));
+        }
+        match counter.fetch_add(1, Ordering::SeqCst) {
+            0 => StubResponse::status("429 Too Many Requests").header("Retry-After", 0),
+            1 => StubResponse::status("503 Service Unavailable"),
+            _ => StubResponse::json(BODY),
+        }
+    })
+    .await;
+    let auth: SourceAuth = serde_yaml::from_str(&format!(
+        "kind: oauth2\ntoken_url: {base}/token\n\
+         client_id_env: APITAP_TEST_USAGE_CLIENT_ID\nclient_secret_env: APITAP_TEST_USAGE_CLIENT_SECRET"
+    ))
+    .unwrap();
+    let usage = Arc::new(UsageMeter::default());
+    let client = reqwest::Client::new();
+    let request = RequestOptions {
+        auth: Some(
+            auth.authenticator(&client, None, Some(Arc::clone(&usage)))
+                .unwrap(),
+        ),
+        usage: Some(Arc::clone(&usage)),
+        ..Default::default()
+    };
+    let retry = Retry {
+        max_attempts: 2,
+        ..retry()
+    };
+    let resp = send_page_request(&client, &format!("{base}/items"), &[], &retry, &request)
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+
+    assert_eq!(page_requests.load(Ordering::SeqCst), 3);
+    // The token request and all three attempts at the page.
+    assert_eq!(usage.requests(), 4);
+}
+
+#[test]
+fn test_schedule_and_byte_formatting() {
+    let schedule: Schedule = serde_yaml::from_str("every: 15m").unwrap();
+    assert_eq!(schedule.interval().unwrap().as_secs(), 900);
+    let zero: Schedule = serde_yaml::from_str("every: 0m").unwrap();
+    assert!(zero.interval().is_err());
+
+    assert_eq!(format_bytes(999), "999 B");
+    assert_eq!(format_bytes(1_234_567), "1.2 MB");
+    assert_eq!(format_bytes(3_000_000_000_000_000), "3000.0 TB");
+}
@@ -0,0 +1,177 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use super::{retry, serve, CollectRows, StubResponse};
+use apitap::http::fetcher::{ndjson_stream_with, PaginatedFetcher, RequestOptions};
+use apitap::http::vcr::{Recording, Vcr, VcrMode};
+use apitap::writer::WriteMode;
+use futures::TryStreamExt;
+use serde_json::{json, Value};
+
+/// Runs a page_number fetch of `url` through `vcr`, returning its rows.
+async fn fetch_page_number(url: &str, vcr: Vcr) -> apitap::errors::Result<Vec<Value>> {
+    let fetcher = PaginatedFetcher::new(reqwest::Client::new(), url, 1)
+        .with_page_number("page", "per_page")
+        .with_request_options(RequestOptions {
+            vcr: Some(vcr),
+            ..Default::default()
+        });
+    let writer = Arc::new(CollectRows::default());
+    fetcher
+        .fetch_page_number(
+            1,
+            Some("/data"),
+            None,
+            None,
+            writer.clone(),
+            WriteMode::Append,
+            &retry(),
+        )
+        .await?;
+    let rows = writer.rows();
+    Ok(rows)
+}
+
+/// Answers every request with the rows of its `page`, counting requests.
+async fn serve_pages(hits: Arc<AtomicUsize>) -> String {
+    let base = serve(move |req| {
+        hits.fetch_add(1, Ordering::SeqCst);
+        let page = req.num("page").unwrap_or(0);
+        StubResponse::json(format!(r#"{{"data":[{{"page":{page}}}]}}"#))
+            .header("x-request-id", format!("r{page}"))
+    })
+    .await;
+    format!("{base}/items")
+}
+
+async fn page(
+    url: &str,
+    page: &str,
+    request: &RequestOptions,
+) -> apitap::errors::Result<Vec<Value>> {
+    let query = [
+        ("page".to_string(), page.to_string()),
+        ("api_key".to_string(), "s3cret".to_string()),
+    ];
+    ndjson_stream_with(
+        &reqwest::Client::new(),
+        url,
+        &query,
+        Some("/data"),
+        &retry(),
+        request,
+    )
+    .await?
+    .try_collect()
+    .await
+}
+
+#[tokio::test]
+async fn test_record_then_replay_without_the_server() {
+    let dir = tempfile::tempdir().unwrap();
+    let hits = Arc::new(AtomicUsize::new(0));
+    let url = serve_pages(Arc::clone(&hits)).await;
+
+    let vcr = Vcr::new(dir.path(), VcrMode::Record).source("items");
+    let record = RequestOptions {
+        vcr: Some(vcr.clone()),
+        ..Default::default()
+    };
+    assert_eq!(
+        page(&url, "1", &record).await.unwrap(),
+        [json!({"page": 1})]
+    );
+    assert_eq!(
+        page(&url, "2", &record).await.unwrap(),
+        [json!({"page": 2})]
+    );
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    let files: Vec<_> = std::fs::read_dir(dir.path().join("items"))
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .collect();
+    assert_eq!(files.len(), 2);
+    for file in &files {
+        let text = std::fs::read_to_string(file).unwrap();
+        assert!(!text.contains("s3cret"), "{text}");
+        let recording: Recording = serde_json::from_str(&text).unwrap();
+        assert_eq!(recording.status, 200);
+        assert!(
+            recording.url.contains("api_key=%5Bredacted%5D"),
+            "{}",
+            recording.url
+        );
+    }
+
+    let replay = RequestOptions {
+        vcr: Some(Vcr::new(dir.path(), VcrMode::Replay).source("items")),
+        ..Default::default()
+    };
+    assert_eq!(
+        page(&url, "2", &replay).await.unwrap(),
+        [json!({"page": 2})]
+    );
+    assert_eq!(
+        page(&url, "1", &replay).await.unwrap(),
+        [json!({"page": 1})]
+    );
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    let err = page(&url, "3", &replay).await.unwrap_err().to_string();
+    assert!(err.contains("no recording of GET"), "{err}");
+    assert!(err.contains("--record"), "{err}");
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_recording_keeps_status_headers_and_binary_body() {
+    let recording: Recording = serde_json::from_value(json!({
+        "method": "GET",
+        "url": "https://api.example.com/items",
+        "status": 503,
+        "headers": {"retry-after": "5"},
+        "body": "/w==",
+        "base64": true,
+        "recorded_at": "2024-05-01T00:00:00Z"
+    }))
+    .unwrap();
+    let resp = recording
+        .response("https://api.example.com/items".parse().unwrap())
+        .unwrap();
+    assert_eq!(resp.status(), 503);
+    assert_eq!(resp.headers()["retry-after"], "5");
+    assert_eq!(resp.url().as_str(), "https://api.example.com/items");
+    assert_eq!(resp.bytes().await.unwrap().as_ref(), [0xff]);
+}
+
+#[tokio::test]
+async fn test_page_number_fetch_replays_without_the_server() {
+    let dir = tempfile::tempdir().unwrap();
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counted = Arc::clone(&hits);
+    let base = serve(move |req| {
+        counted.fetch_add(1, Ordering::SeqCst);
+        let page = req.num("page").unwrap_or(0);
+        let data = if page <= 2 {
+            json!([{ "page": page }])
+        } else {
+            json!([])
+        };
+        StubResponse::json(json!({ "data": data }).to_string())
+    })
+    .await;
+
+    let url = format!("{base}/items");
+    let vcr = Vcr::new(dir.path(), VcrMode::Record).source("pages");
+    let recorded = fetch_page_number(&url, vcr).await.unwrap();
+    assert_eq!(recorded, [json!({"page": 1}), json!({"page": 2})]);
+    let served = hits.load(Ordering::SeqCst);
+    assert!(served > 0);
+
+    // The probe and every page come from the recordings.
+    let vcr = Vcr::new(dir.path(), VcrMode::Replay).source("pages");
+    let replayed = fetch_page_number(&url, vcr).await.unwrap();
+    assert_eq!(replayed, recorded);
+    assert_eq!(hits.load(Ordering::SeqCst), served);
+}
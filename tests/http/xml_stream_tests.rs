@@ -0,0 +1,153 @@
+use super::{retry, serve, StubResponse};
+use apitap::errors::Result;
+use apitap::http::csv_stream::ResponseFormat;
+use apitap::http::fetcher::{ndjson_stream_with, RequestOptions};
+use apitap::http::xml_stream::{xml_stream, XmlOptions};
+use apitap::pipeline::Config;
+use futures::TryStreamExt;
+use serde_json::{json, Value};
+
+const RSS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:dc="http://purl.org/dc/elements/1.1/">
+  <channel>
+    <title>Example feed</title>
+    <item>
+      <title>First &amp; foremost</title>
+      <link>https://example.com/1</link>
+      <category>rust</category>
+      <category>etl</category>
+      <dc:creator><![CDATA[Ada <ada@example.com>]]></dc:creator>
+      <guid isPermaLink="false">a1</guid>
+      <enclosure url="https://example.com/1.mp3" length="12" type="audio/mpeg"/>
+      <comments></comments>
+    </item>
+    <item>
+      <title>Caf&#233;</title>
+    </item>
+  </channel>
+</rss>"#;
+
+async fn parse(body: &str, size: usize, options: &XmlOptions) -> Result<Vec<Value>> {
+    let chunks: Vec<std::result::Result<Vec<u8>, std::io::Error>> = body
+        .as_bytes()
+        .chunks(size)
+        .map(|c| Ok(c.to_vec()))
+        .collect();
+    xml_stream(futures::stream::iter(chunks), options)
+        .try_collect()
+        .await
+}
+
+#[tokio::test]
+async fn test_rss_items_to_rows() {
+    let expected = vec![
+        json!({
+            "title": "First & foremost",
+            "link": "https://example.com/1",
+            "category": ["rust", "etl"],
+            "dc:creator": "Ada <ada@example.com>",
+            "guid": {"@isPermaLink": "false", "#text": "a1"},
+            "enclosure": {"@url": "https://example.com/1.mp3", "@length": "12", "@type": "audio/mpeg"},
+            "comments": null
+        }),
+        json!({"title": "Café"}),
+    ];
+    for size in [1, 5, RSS.len()] {
+        let rows = parse(RSS, size, &XmlOptions::default()).await.unwrap();
+        assert_eq!(rows, expected, "chunk size {size}");
+    }
+}
+
+#[tokio::test]
+async fn test_record_path_and_leaf_records() {
+    let options = XmlOptions {
+        record: "/rss/channel/title".to_string(),
+    };
+    // Only the channel title matches the path, not the item titles.
+    let rows = parse(RSS, 64, &options).await.unwrap();
+    assert_eq!(rows, vec![json!({"#text": "Example feed"})]);
+
+    let sitemap = r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+      <url><loc>https://example.com/</loc><lastmod>2024-01-01</lastmod></url>
+      <url><loc>https://example.com/about</loc></url>
+    </urlset>"#;
+    let options = XmlOptions {
+        record: "url".to_string(),
+    };
+    let rows = parse(sitemap, 16, &options).await.unwrap();
+    assert_eq!(
+        rows,
+        vec![
+            json!({"loc": "https://example.com/", "lastmod": "2024-01-01"}),
+            json!({"loc": "https://example.com/about"}),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_malformed_xml_fails() {
+    let err = parse(
+        "<feed><entry><id>1</entry></feed>",
+        64,
+        &XmlOptions {
+            record: "entry".to_string(),
+        },
+    )
+    .await
+    .unwrap_err();
+    assert!(err.to_string().starts_with("XML error"), "{err}");
+}
+
+#[test]
+fn test_xml_source_config() {
+    let config_yaml = r#"
+sources:
+  - name: releases
+    url: https://example.com/releases.atom
+    data_path: null
+    primary_key_in_dest: id
+    retry: {max_attempts: 3, max_delay_secs: 10, min_delay_secs: 1}
+    format: xml
+    xml:
+      record: entry
+  - name: news
+    url: https://example.com/rss
+    data_path: null
+    primary_key_in_dest: guid
+    retry: {max_attempts: 3, max_delay_secs: 10, min_delay_secs: 1}
+targets: []
+"#;
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+    let src = config.source("releases").unwrap();
+    assert_eq!(src.format, ResponseFormat::Xml);
+    assert_eq!(src.xml.record, "entry");
+    assert_eq!(config.source("news").unwrap().xml.record, "item");
+}
+
+#[tokio::test]
+async fn test_atom_content_type_is_parsed_as_xml() {
+    let body = r#"<feed xmlns="http://www.w3.org/2005/Atom"><entry><id>1</id></entry><entry><id>2</id></entry></feed>"#;
+    let base =
+        serve(move |_| StubResponse::ok(body).header("Content-Type", "application/atom+xml")).await;
+
+    let request = RequestOptions {
+        xml: XmlOptions {
+            record: "entry".to_string(),
+        },
+        ..Default::default()
+    };
+    let rows: Vec<Value> = ndjson_stream_with(
+        &reqwest::Client::new(),
+        &format!("{base}/releases.atom"),
+        &[],
+        None,
+        &retry(),
+        &request,
+    )
+    .await
+    .unwrap()
+    .try_collect()
+    .await
+    .unwrap();
+    assert_eq!(rows, vec![json!({"id": "1"}), json!({"id": "2"})]);
+}
@@ -132,6 +132,7 @@ fn test_retry_configuration_validation() {
         max_attempts: 5,
         max_delay_secs: 300,
         min_delay_secs: 1,
+        ..Default::default()
     };
 
     // Retry configuration should be valid
@@ -85,16 +85,28 @@ fn test_pagination_strategy_configuration() {
     let limit_offset = Pagination::LimitOffset {
         limit_param: "limit".to_string(),
         offset_param: "offset".to_string(),
+        total_items_pointer: None,
+        total_pages_pointer: None,
+        total_items_header: None,
+        total_pages_header: None,
+        stop_when: None,
     };
 
     let page_number = Pagination::PageNumber {
         page_param: "page".to_string(),
         per_page_param: "size".to_string(),
+        total_items_pointer: None,
+        total_pages_pointer: None,
+        total_items_header: None,
+        total_pages_header: None,
+        stop_when: None,
     };
 
     let cursor = Pagination::Cursor {
         cursor_param: "next_cursor".to_string(),
         page_size_param: Some("page_size".to_string()),
+        next_cursor_path: None,
+        next_cursor_header: None,
     };
 
     // All strategies should be configurable
@@ -1,16 +1,31 @@
 // Integration tests for apitap
 //
 // This test suite is organized into modules for better maintainability:
+// - cmd: Tests for CLI completions and man pages
 // - config: Tests for configuration and templating
 // - errors: Tests for error handling and error types
 // - utils: Tests for utility functions (schema inference, streaming)
 // - pipeline: Tests for pipeline configuration and management
 // - http: Tests for HTTP fetcher and pagination
 // - writer: Tests for data writer and write modes
+// - transform: Tests for row-level transforms
+// - log: Tests for the JSON log envelope
 
+#![allow(
+    clippy::approx_constant,
+    clippy::assertions_on_constants,
+    clippy::bool_assert_comparison,
+    clippy::unnecessary_literal_unwrap,
+    clippy::useless_format,
+    clippy::useless_vec
+)]
+
+mod cmd;
 mod config;
 mod errors;
 mod http;
+mod log;
 mod pipeline;
+mod transform;
 mod utils;
 mod writer;
@@ -0,0 +1,157 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use apitap::errors::ApitapError;
+use apitap::http::fetcher::FetchStats;
+use apitap::log::envelope::EnvelopeLayer;
+use apitap::pipeline::run::RunSummary;
+use serde_json::{json, Value};
+use tracing::{info, info_span, warn};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::Registry;
+
+/// Collects everything the layer writes.
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Capture {
+    type Writer = Capture;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+fn capture_lines(f: impl FnOnce()) -> Vec<Value> {
+    let capture = Capture::default();
+    let subscriber = Registry::default().with(EnvelopeLayer::new(capture.clone()));
+    tracing::subscriber::with_default(subscriber, f);
+    let bytes = capture.0.lock().unwrap().clone();
+    String::from_utf8(bytes)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_envelope_outside_spans() {
+    let lines = capture_lines(|| info!(rows = 3, "loaded"));
+    assert_eq!(lines.len(), 1);
+    let line = &lines[0];
+    assert_eq!(line["level"], "INFO");
+    assert_eq!(line["message"], "loaded");
+    assert_eq!(line["run_id"], Value::Null);
+    assert_eq!(line["module"], Value::Null);
+    assert_eq!(line["source"], Value::Null);
+    assert_eq!(line["event"], Value::Null);
+    assert_eq!(line["fields"], json!({"rows": 3}));
+    assert!(line["timestamp"].as_str().unwrap().ends_with('Z'));
+}
+
+#[test]
+fn test_envelope_takes_run_module_and_source_from_spans() {
+    let lines = capture_lines(|| {
+        let run = info_span!("run_pipeline", run_id = tracing::field::Empty);
+        run.record("run_id", "r1");
+        let _run = run.enter();
+        let module = info_span!("module", module = "orders", source = tracing::field::Empty);
+        module.record("source", "orders_api");
+        let _module = module.enter();
+        warn!(table = "orders", "freshness contract violated");
+    });
+    let line = &lines[0];
+    assert_eq!(line["level"], "WARN");
+    assert_eq!(line["run_id"], "r1");
+    assert_eq!(line["module"], "orders");
+    assert_eq!(line["source"], "orders_api");
+    assert_eq!(line["fields"], json!({"table": "orders"}));
+}
+
+#[test]
+fn test_run_completed_event() {
+    let mut summary = RunSummary::default();
+    summary.add_module(&FetchStats {
+        success_count: 4,
+        error_count: 1,
+        total_items: 200,
+        ..Default::default()
+    });
+    summary.modules_skipped = 1;
+
+    let lines = capture_lines(|| {
+        summary.log_completed(Duration::from_millis(1500), &Ok(()));
+        summary.log_completed(
+            Duration::from_millis(10),
+            &Err(ApitapError::PipelineError("boom".to_string())),
+        );
+    });
+
+    assert_eq!(lines[0]["event"], "run_completed");
+    assert_eq!(lines[0]["level"], "INFO");
+    assert_eq!(
+        lines[0]["fields"],
+        json!({
+            "status": "succeeded",
+            "modules_completed": 1,
+            "modules_skipped": 1,
+            "records": 200,
+            "pages": 4,
+            "failed_pages": 1,
+            "throttled": 0,
+            "api_calls": 0,
+            "bytes_downloaded": 0,
+            "sla_breaches": 0,
+            "duration_ms": 1500,
+        })
+    );
+    assert_eq!(lines[1]["event"], "run_completed");
+    assert_eq!(lines[1]["level"], "ERROR");
+    assert_eq!(lines[1]["fields"]["status"], "failed");
+    assert!(lines[1]["fields"]["error"]
+        .as_str()
+        .unwrap()
+        .contains("boom"));
+}
+
+#[test]
+fn test_usage_event_with_monthly_projection() {
+    let mut summary = RunSummary {
+        records: 500,
+        api_calls: 12,
+        bytes_downloaded: 2_500_000,
+        ..Default::default()
+    };
+    let lines = capture_lines(|| summary.log_usage());
+    assert_eq!(lines[0]["event"], "usage");
+    assert_eq!(
+        lines[0]["fields"],
+        json!({"api_calls": 12, "bytes_downloaded": 2_500_000, "records": 500})
+    );
+    assert!(lines[0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("2.5 MB downloaded"));
+
+    summary.schedule = Some(Duration::from_secs(6 * 3600));
+    let month = summary.projected_monthly().unwrap();
+    assert_eq!(month.runs, 120);
+    assert_eq!(month.api_calls, 1440);
+    let lines = capture_lines(|| summary.log_usage());
+    assert_eq!(lines[0]["fields"]["runs_per_month"], 120);
+    assert_eq!(lines[0]["fields"]["monthly_bytes_downloaded"], 300_000_000);
+    assert_eq!(lines[0]["fields"]["monthly_records"], 60_000);
+}
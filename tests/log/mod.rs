@@ -0,0 +1 @@
+mod envelope_tests;
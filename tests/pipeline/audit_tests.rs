@@ -0,0 +1,179 @@
+// Tests for the run audit table (audit:)
+
+use std::sync::Arc;
+
+use crate::http::{serve, StubResponse};
+use apitap::cmd::{run_pipeline_with, RunOptions};
+use apitap::errors::ApitapError;
+use apitap::http::fetcher::FetchStats;
+use apitap::pipeline::audit::{write_audit, ModuleAudit, ModuleOutcome};
+use apitap::pipeline::Config;
+use apitap::writer::sqlite::SqliteWriter;
+use apitap::writer::DataWriter;
+use serde_json::json;
+use sqlx::sqlite::SqlitePoolOptions;
+
+fn stats(pages: usize, failed: usize, rows: usize) -> FetchStats {
+    FetchStats {
+        success_count: pages,
+        error_count: failed,
+        total_items: rows,
+        ..FetchStats::new()
+    }
+}
+
+#[test]
+fn test_audit_from_yaml() {
+    let config: Config = serde_yaml::from_str(
+        r#"
+audit:
+  sink: warehouse
+sources: []
+targets: []
+"#,
+    )
+    .unwrap();
+    let audit = config.audit.unwrap();
+    assert_eq!(audit.sink, "warehouse");
+    assert_eq!(audit.table, "_apitap_runs");
+    let opts = audit.writer_opts();
+    assert_eq!(opts.dest_table, "_apitap_runs");
+    assert!(opts.primary_key.is_none());
+}
+
+#[test]
+fn test_module_rows() {
+    let done = ModuleAudit::start("run-1", "orders.sql", "orders").finished(&stats(4, 1, 38));
+    assert_eq!(done.outcome, ModuleOutcome::Failed);
+    let row = done.to_row();
+    assert_eq!(row["run_id"], "run-1");
+    assert_eq!(row["module"], "orders.sql");
+    assert_eq!(row["source"], "orders");
+    assert_eq!(row["status"], "failed");
+    assert_eq!(row["pages"], 4);
+    assert_eq!(row["failed_pages"], 1);
+    assert_eq!(row["rows"], 38);
+    assert_eq!(row["error"], "");
+    assert!(row["finished_at"].as_str().unwrap() >= row["started_at"].as_str().unwrap());
+
+    let ok = ModuleAudit::start("run-1", "users.sql", "users").finished(&stats(2, 0, 10));
+    assert_eq!(ok.to_row()["status"], "succeeded");
+
+    let stopped = ModuleAudit::start("run-1", "events.sql", "events")
+        .failed(&ApitapError::PipelineError("sink went away".into()));
+    let row = stopped.to_row();
+    assert_eq!(row["status"], "failed");
+    assert_eq!(row["pages"], 0);
+    assert!(row["error"].as_str().unwrap().contains("sink went away"));
+
+    let skipped = ModuleAudit::start("run-1", "users.sql", "users").skipped();
+    assert_eq!(skipped.outcome, ModuleOutcome::Skipped);
+    assert_eq!(skipped.to_row()["status"], "skipped");
+}
+
+#[tokio::test]
+async fn test_audit_rows_are_appended() {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    let writer: Arc<dyn DataWriter> = Arc::new(SqliteWriter::new(pool.clone(), "_apitap_runs"));
+
+    let first = vec![
+        ModuleAudit::start("run-1", "orders.sql", "orders").finished(&stats(3, 0, 30)),
+        ModuleAudit::start("run-1", "users.sql", "users")
+            .failed(&ApitapError::PipelineError("boom".into())),
+    ];
+    write_audit("_apitap_runs", &first, Arc::clone(&writer))
+        .await
+        .unwrap();
+    let second =
+        vec![ModuleAudit::start("run-2", "orders.sql", "orders").finished(&stats(1, 0, 5))];
+    write_audit("_apitap_runs", &second, Arc::clone(&writer))
+        .await
+        .unwrap();
+    write_audit("_apitap_runs", &[], writer).await.unwrap();
+
+    let rows: Vec<(String, String, String, i64)> = sqlx::query_as(
+        r#"SELECT "run_id", "module", "status", "rows" FROM "_apitap_runs" ORDER BY 1, 2"#,
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        json!(rows),
+        json!([
+            ["run-1", "orders.sql", "succeeded", 30],
+            ["run-1", "users.sql", "failed", 0],
+            ["run-2", "orders.sql", "succeeded", 5],
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_fresh_module_skip_is_audited() {
+    let dir = tempfile::tempdir().unwrap();
+    let url = serve(|_| StubResponse::json(json!({"data": [{"id": 1}, {"id": 2}]}))).await;
+    let modules = dir.path().join("modules");
+    std::fs::create_dir_all(&modules).unwrap();
+    std::fs::write(
+        modules.join("users.sql"),
+        "{{ sink(name=\"db\") }}\n{{ freshness(max_age=\"1h\") }}\nselect id from {{ use_source(\"users\") }}",
+    )
+    .unwrap();
+    let db = dir.path().join("users.db");
+    let config = dir.path().join("pipelines.yaml");
+    std::fs::write(
+        &config,
+        format!(
+            r#"audit:
+  sink: db
+sources:
+  - name: users
+    url: {url}/users
+    table_destination_name: users
+    primary_key_in_dest: id
+    data_path: /data
+    pagination:
+      kind: next_url
+      next_path: /next
+    retry:
+      max_attempts: 0
+      max_delay_secs: 1
+      min_delay_secs: 0
+targets:
+  - name: db
+    type: sqlite
+    path: {db}
+    metadata_columns: [_loaded_at]
+"#,
+            db = db.display(),
+        ),
+    )
+    .unwrap();
+    let (modules, config) = (modules.display().to_string(), config.display().to_string());
+    let run = RunOptions {
+        state_path: Some(dir.path().join("state.json").display().to_string()),
+        skip_if_fresh: true,
+        ..Default::default()
+    };
+
+    run_pipeline_with(&modules, &config, &run).await.unwrap();
+    // Loaded moments ago, so the second run skips it.
+    run_pipeline_with(&modules, &config, &run).await.unwrap();
+
+    let pool = sqlx::SqlitePool::connect(&format!("sqlite://{}", db.display()))
+        .await
+        .unwrap();
+    let rows: Vec<(String, String, i64)> = sqlx::query_as(
+        r#"SELECT "module", "status", "rows" FROM "_apitap_runs" ORDER BY "started_at""#,
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        json!(rows),
+        json!([["users.sql", "succeeded", 2], ["users.sql", "skipped", 0]])
+    );
+}
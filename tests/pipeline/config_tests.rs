@@ -1,5 +1,12 @@
 use apitap::http::fetcher::Pagination;
-use apitap::pipeline::{Config, PostgresAuth, Retry, Source, Target};
+use apitap::pipeline::run::{FetchOpts, FetchSettings, LOW_MEMORY_BATCH_SIZE};
+use apitap::pipeline::{Config, PostgresAuth, Retry, Target};
+use apitap::writer::debug::DebugFormat;
+use apitap::writer::kafka::KafkaCompression;
+use apitap::writer::parquet::ParquetCompression;
+use apitap::writer::redshift::StagingCleanup;
+use sqlx::postgres::PgSslMode;
+use std::time::Duration;
 
 #[test]
 fn test_config_source_indexing() {
@@ -73,6 +80,7 @@ targets:
             assert_eq!(pg.port, 5432);
             assert_eq!(pg.database, "testdb");
         }
+        _ => panic!("Expected Postgres target"),
     }
 }
 
@@ -175,6 +183,7 @@ targets:
         Target::Postgres(pg) => {
             assert_eq!(pg.port, 5432); // default port
         }
+        _ => panic!("Expected Postgres target"),
     }
 }
 
@@ -200,15 +209,92 @@ targets:
         Target::Postgres(pg) => {
             assert_eq!(pg.port, 5433);
         }
+        _ => panic!("Expected Postgres target"),
     }
 }
 
+#[test]
+fn test_postgres_connection_options() {
+    let config_yaml = r#"
+sources: []
+targets:
+  - type: postgres
+    name: pg_sink
+    host: db.internal
+    database: testdb
+    auth:
+      username: testuser
+      password: "p@ss/word#1"
+    connection:
+      ssl_mode: require
+      search_path: [analytics, public]
+      statement_timeout: 2m
+      lock_timeout: 10s
+      application_name: apitap-orders
+"#;
+
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+    let Some(Target::Postgres(pg)) = config.target("pg_sink") else {
+        panic!("Expected Postgres target");
+    };
+    let options = pg.connect_options().unwrap();
+    assert_eq!(options.get_host(), "db.internal");
+    assert_eq!(options.get_username(), "testuser");
+    assert_eq!(options.get_database(), Some("testdb"));
+    assert!(matches!(options.get_ssl_mode(), PgSslMode::Require));
+    assert_eq!(options.get_application_name(), Some("apitap-orders"));
+    // Poolers such as PgBouncer reject startup options, so the search path
+    // and timeouts are set on each pooled connection instead.
+    assert_eq!(options.get_options(), None);
+    assert_eq!(
+        pg.connection.session_statements().unwrap(),
+        vec![
+            r#"SET search_path TO "analytics", "public""#.to_string(),
+            "SET statement_timeout = 120000".to_string(),
+            "SET lock_timeout = 10000".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn test_postgres_connection_defaults() {
+    let config_yaml = r#"
+sources: []
+targets:
+  - type: postgres
+    name: pg_sink
+    host: localhost
+    database: testdb
+    auth:
+      username: testuser
+      password: testpass
+"#;
+
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+    let Some(Target::Postgres(pg)) = config.target("pg_sink") else {
+        panic!("Expected Postgres target");
+    };
+    let options = pg.connect_options().unwrap();
+    assert_eq!(options.get_application_name(), Some("apitap"));
+    assert_eq!(options.get_options(), None);
+    assert!(pg.connection.session_statements().unwrap().is_empty());
+
+    let mut bad = pg.clone();
+    bad.connection.statement_timeout = Some("soon".to_string());
+    assert!(bad.connection.session_statements().is_err());
+    let mut bad = pg.clone();
+    bad.connection.lock_timeout = Some("later".to_string());
+    let err = bad.connection.session_statements().unwrap_err().to_string();
+    assert!(err.contains("lock_timeout"), "{err}");
+}
+
 #[test]
 fn test_retry_configuration() {
     let retry = Retry {
         max_attempts: 5,
         max_delay_secs: 120,
         min_delay_secs: 2,
+        ..Default::default()
     };
 
     assert_eq!(retry.max_attempts, 5);
@@ -241,6 +327,7 @@ targets: []
         Pagination::LimitOffset {
             limit_param,
             offset_param,
+            ..
         } => {
             assert_eq!(limit_param, "limit");
             assert_eq!(offset_param, "offset");
@@ -315,6 +402,7 @@ targets: []
         Pagination::PageNumber {
             page_param,
             per_page_param,
+            ..
         } => {
             assert_eq!(page_param, "page");
             assert_eq!(per_page_param, "per_page");
@@ -347,6 +435,7 @@ targets: []
         Pagination::Cursor {
             cursor_param,
             page_size_param,
+            ..
         } => {
             assert_eq!(cursor_param, "cursor");
             assert_eq!(page_size_param, &Some("size".to_string()));
@@ -387,3 +476,524 @@ targets:
     assert_eq!(config.sources.len(), config2.sources.len());
     assert_eq!(config.targets.len(), config2.targets.len());
 }
+
+#[test]
+fn test_snowflake_sink_key_pair_config() {
+    let config_yaml = r#"
+sources: []
+targets:
+  - type: snowflake
+    name: sf_sink
+    account: xy12345.us-east-1
+    warehouse: LOAD_WH
+    database: ANALYTICS
+    auth:
+      username: loader
+      private_key_path: /secrets/rsa_key.p8
+      public_key_fp: SHA256:abc123
+    stage:
+      name: raw.apitap_stage
+      url: s3://my-bucket/snowflake
+"#;
+
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+    match config.target("sf_sink").unwrap() {
+        Target::Snowflake(sf) => {
+            assert_eq!(sf.account, "xy12345.us-east-1");
+            assert_eq!(sf.warehouse.as_deref(), Some("LOAD_WH"));
+            assert_eq!(sf.schema, "PUBLIC"); // default schema
+            assert_eq!(sf.auth.public_key_fp.as_deref(), Some("SHA256:abc123"));
+            assert!(sf.auth.password.is_none());
+            assert_eq!(sf.stage.url, "s3://my-bucket/snowflake");
+        }
+        _ => panic!("Expected Snowflake target"),
+    }
+}
+
+#[test]
+fn test_source_parse_json_fields() {
+    let config_yaml = r#"
+sources:
+  - name: api1
+    url: https://api.example.com/data
+    parse_json_fields: [payload, /meta/raw]
+    retry:
+      max_attempts: 3
+      max_delay_secs: 60
+      min_delay_secs: 1
+targets: []
+"#;
+
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+    let source = config.source("api1").unwrap();
+    assert_eq!(
+        source.parse_json_fields,
+        Some(vec!["payload".to_string(), "/meta/raw".to_string()])
+    );
+}
+
+#[test]
+fn test_parquet_sink_config() {
+    let config_yaml = r#"
+sources: []
+targets:
+  - type: parquet
+    name: lake
+    path: s3://my-bucket/raw
+    compression: zstd
+    partition_by: [country]
+"#;
+
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+    match config.target("lake").unwrap() {
+        Target::Parquet(pq) => {
+            assert_eq!(pq.path, "s3://my-bucket/raw");
+            assert_eq!(pq.compression, ParquetCompression::Zstd);
+            assert_eq!(pq.max_file_size_mb, 128); // default rollover size
+            assert_eq!(pq.partition_by, vec!["country".to_string()]);
+        }
+        _ => panic!("Expected Parquet target"),
+    }
+}
+
+#[test]
+fn test_source_timestamp_normalization() {
+    let config_yaml = r#"
+sources:
+  - name: api1
+    url: https://api.example.com/data
+    timestamp_normalization:
+      assume_tz: America/New_York
+      convert_to: UTC
+    retry:
+      max_attempts: 3
+      max_delay_secs: 60
+      min_delay_secs: 1
+targets: []
+"#;
+
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+    let ts = config
+        .source("api1")
+        .unwrap()
+        .timestamp_normalization
+        .as_ref()
+        .unwrap();
+    assert_eq!(ts.assume_tz, "America/New_York");
+    assert_eq!(ts.convert_to, "UTC");
+    assert!(ts.fields.is_none());
+}
+
+#[test]
+fn test_file_sink_config() {
+    let config_yaml = r#"
+sources: []
+targets:
+  - type: file
+    name: dump
+    path: output/{table}/{date}.csv
+"#;
+
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+    match config.target("dump").unwrap() {
+        Target::File(f) => {
+            assert_eq!(f.path, "output/{table}/{date}.csv");
+            assert!(f.format.is_none());
+        }
+        _ => panic!("Expected File target"),
+    }
+}
+
+#[test]
+fn test_sqlite_sink_config() {
+    let config_yaml = r#"
+sources: []
+targets:
+  - type: sqlite
+    name: local
+    path: ./data/apitap.db
+"#;
+
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+    match config.target("local").unwrap() {
+        Target::Sqlite(lite) => assert_eq!(lite.path, "./data/apitap.db"),
+        _ => panic!("Expected Sqlite target"),
+    }
+}
+
+#[test]
+fn test_kafka_sink_config() {
+    let config_yaml = r#"
+sources: []
+targets:
+  - type: kafka
+    name: events
+    brokers: localhost:9092
+    topic: orders
+    compression: lz4
+    properties:
+      security.protocol: SASL_SSL
+"#;
+
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+    match config.target("events").unwrap() {
+        Target::Kafka(k) => {
+            assert_eq!(k.brokers, "localhost:9092");
+            assert_eq!(k.topic.as_deref(), Some("orders"));
+            assert_eq!(k.compression, KafkaCompression::Lz4);
+            assert_eq!(k.linger_ms, 5);
+            assert_eq!(
+                k.properties.get("security.protocol").map(String::as_str),
+                Some("SASL_SSL")
+            );
+        }
+        _ => panic!("Expected Kafka target"),
+    }
+}
+
+#[test]
+fn test_redshift_sink_config() {
+    let config_yaml = r#"
+sources: []
+targets:
+  - type: redshift
+    name: warehouse
+    host: example.abc123.us-east-1.redshift.amazonaws.com
+    database: dev
+    auth:
+      username: loader
+      password_env: REDSHIFT_PASSWORD
+    staging: s3://my-bucket/apitap/staging
+    iam_role: arn:aws:iam::123456789012:role/RedshiftCopy
+    cleanup: never
+"#;
+
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+    match config.target("warehouse").unwrap() {
+        Target::Redshift(rs) => {
+            assert_eq!(rs.port, 5439);
+            assert_eq!(rs.staging, "s3://my-bucket/apitap/staging");
+            assert_eq!(rs.cleanup, StagingCleanup::Never);
+            assert!(rs.region.is_none());
+        }
+        _ => panic!("Expected Redshift target"),
+    }
+}
+
+#[test]
+fn test_delta_sink_config() {
+    let config_yaml = r#"
+sources: []
+targets:
+  - type: delta
+    name: lake
+    path: s3://my-bucket/lake
+    compression: zstd
+"#;
+
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+    match config.target("lake").unwrap() {
+        Target::Delta(d) => {
+            assert_eq!(d.path, "s3://my-bucket/lake");
+            assert_eq!(d.compression, ParquetCompression::Zstd);
+            assert_eq!(d.max_file_size_mb, 128);
+        }
+        _ => panic!("Expected Delta target"),
+    }
+}
+
+#[test]
+fn test_mongodb_sink_config() {
+    let config_yaml = r#"
+sources: []
+targets:
+  - type: mongodb
+    name: docs
+    uri: mongodb://localhost:27017
+    database: apitap
+"#;
+
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+    match config.target("docs").unwrap() {
+        Target::Mongodb(m) => {
+            assert_eq!(m.uri.as_deref(), Some("mongodb://localhost:27017"));
+            assert_eq!(m.database, "apitap");
+            assert!(m.collection.is_none());
+        }
+        _ => panic!("Expected MongoDB target"),
+    }
+}
+
+#[test]
+fn test_webhook_sink_config() {
+    let config_yaml = r#"
+sources: []
+targets:
+  - type: webhook
+    name: relay
+    url: https://hooks.example.com/ingest
+"#;
+
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+    match config.target("relay").unwrap() {
+        Target::Webhook(w) => {
+            assert_eq!(w.url, "https://hooks.example.com/ingest");
+            assert_eq!(w.batch_size, 500);
+            assert_eq!(w.timeout_secs, 30);
+            assert_eq!(w.retry.max_attempts, 3);
+            assert!(w.headers.is_empty());
+        }
+        _ => panic!("Expected Webhook target"),
+    }
+}
+
+#[test]
+fn test_debug_sink_config() {
+    let config_yaml = r#"
+sources: []
+targets:
+  - type: debug
+    name: console
+    format: ndjson
+"#;
+
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+    match config.target("console").unwrap() {
+        Target::Debug(d) => {
+            assert_eq!(d.format, DebugFormat::Ndjson);
+            assert_eq!(d.limit, 20);
+        }
+        _ => panic!("Expected Debug target"),
+    }
+}
+
+#[test]
+fn test_source_number_normalization() {
+    let config_yaml = r#"
+sources:
+  - name: api1
+    url: https://api.example.com/data
+    number_normalization:
+      - fields: [price, discount]
+      - fields: [amount_cents]
+        divide_by: 100
+    retry:
+      max_attempts: 3
+      max_delay_secs: 60
+      min_delay_secs: 1
+targets: []
+"#;
+
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+    let rules = config
+        .source("api1")
+        .unwrap()
+        .number_normalization
+        .as_ref()
+        .unwrap();
+    assert_eq!(rules.len(), 2);
+    assert_eq!(rules[0].thousands_separator, ",");
+    assert_eq!(rules[0].decimal_separator, ".");
+    assert_eq!(rules[1].divide_by, Some(100.0));
+}
+
+#[test]
+fn test_source_proxy_config() {
+    let config_yaml = r#"
+sources:
+  - name: api1
+    url: https://api.example.com/data
+    proxy:
+      url: http://proxy.corp:3128
+      username: svc
+      password_env: PROXY_PASSWORD
+      no_proxy: [localhost, .internal]
+    retry:
+      max_attempts: 3
+      max_delay_secs: 60
+      min_delay_secs: 1
+targets: []
+"#;
+
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+    let proxy = config.source("api1").unwrap().proxy.as_ref().unwrap();
+    assert_eq!(proxy.url, "http://proxy.corp:3128");
+    assert_eq!(proxy.username.as_deref(), Some("svc"));
+    assert_eq!(proxy.password_env.as_deref(), Some("PROXY_PASSWORD"));
+    assert_eq!(proxy.no_proxy, vec!["localhost", ".internal"]);
+}
+
+#[test]
+fn test_fetch_settings_per_source_over_defaults() {
+    let config_yaml = r#"
+fetch:
+  page_size: 100
+  concurrency: 10
+sources:
+  - name: big
+    url: https://api.example.com/events
+    page_size: 500
+    concurrency: 20
+    retry:
+      max_attempts: 3
+      max_delay_secs: 60
+      min_delay_secs: 1
+  - name: fragile
+    url: https://api.example.com/legacy
+    concurrency: 1
+    retry:
+      max_attempts: 3
+      max_delay_secs: 60
+      min_delay_secs: 1
+targets: []
+"#;
+
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+    let base = FetchOpts {
+        concurrency: 5,
+        default_page_size: 50,
+        fetch_batch_size: 256,
+        flush_interval: None,
+    }
+    .with_overrides(&config.fetch)
+    .unwrap();
+    assert_eq!((base.default_page_size, base.concurrency), (100, 10));
+
+    let big = base
+        .with_overrides(&config.source("big").unwrap().fetch)
+        .unwrap();
+    assert_eq!(
+        (big.default_page_size, big.concurrency, big.fetch_batch_size),
+        (500, 20, 256)
+    );
+    let fragile = base
+        .with_overrides(&config.source("fragile").unwrap().fetch)
+        .unwrap();
+    assert_eq!((fragile.default_page_size, fragile.concurrency), (100, 1));
+
+    let zero = FetchSettings {
+        concurrency: Some(0),
+        ..Default::default()
+    };
+    assert!(base.with_overrides(&zero).is_err());
+
+    // Round-trips without the unset keys.
+    let serialized = serde_yaml::to_string(&config).unwrap();
+    assert!(!serialized.contains("fetch_batch_size"));
+    let again: Config = serde_yaml::from_str(&serialized).unwrap();
+    assert_eq!(again.source("big").unwrap().fetch.page_size, Some(500));
+}
+
+#[test]
+fn test_flush_interval_parsed_and_overridden_per_source() {
+    let config_yaml = r#"
+fetch:
+  flush_interval: 5s
+sources:
+  - name: trickle
+    url: https://api.example.com/events
+    flush_interval: 1s
+    retry:
+      max_attempts: 3
+      max_delay_secs: 60
+      min_delay_secs: 1
+  - name: bulk
+    url: https://api.example.com/bulk
+    retry:
+      max_attempts: 3
+      max_delay_secs: 60
+      min_delay_secs: 1
+targets: []
+"#;
+
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+    let base = FetchOpts {
+        concurrency: 5,
+        default_page_size: 50,
+        fetch_batch_size: 256,
+        flush_interval: None,
+    }
+    .with_overrides(&config.fetch)
+    .unwrap();
+    assert_eq!(base.flush_interval, Some(Duration::from_secs(5)));
+
+    let trickle = base
+        .with_overrides(&config.source("trickle").unwrap().fetch)
+        .unwrap();
+    assert_eq!(trickle.flush_interval, Some(Duration::from_secs(1)));
+    let bulk = base
+        .with_overrides(&config.source("bulk").unwrap().fetch)
+        .unwrap();
+    assert_eq!(bulk.flush_interval, Some(Duration::from_secs(5)));
+
+    let zero = FetchSettings {
+        flush_interval: Some("0s".into()),
+        ..Default::default()
+    };
+    assert!(base.with_overrides(&zero).is_err());
+}
+
+#[test]
+fn test_header_values_from_environment() {
+    std::env::set_var("APITAP_TEST_HEADER_TOKEN", "sk_live_123");
+    let config: Config = serde_yaml::from_str(
+        r#"
+sources:
+  - name: api
+    url: https://api.example.com/users
+    headers:
+      - key: Accept
+        value: application/json
+      - key: X-Api-Key
+        value_env: APITAP_TEST_HEADER_TOKEN
+      - key: Authorization
+        value: "Token ${APITAP_TEST_HEADER_TOKEN}"
+      - key: X-Missing
+        value_env: APITAP_TEST_HEADER_UNSET
+      - key: X-Both
+        value: literal
+        value_env: APITAP_TEST_HEADER_TOKEN
+    retry:
+      max_attempts: 3
+      max_delay_secs: 60
+      min_delay_secs: 1
+targets: []
+"#,
+    )
+    .unwrap();
+    let headers = config.sources[0].headers.as_ref().unwrap();
+
+    assert_eq!(headers[0].resolve().unwrap(), "application/json");
+    assert!(!headers[0].is_secret());
+    assert_eq!(headers[1].resolve().unwrap(), "sk_live_123");
+    assert!(headers[1].is_secret());
+    assert_eq!(headers[2].resolve().unwrap(), "Token sk_live_123");
+    assert!(headers[2].is_secret());
+
+    let err = headers[3].resolve().unwrap_err().to_string();
+    assert!(err.contains("APITAP_TEST_HEADER_UNSET"), "{err}");
+    assert!(err.contains("header 'X-Missing'"), "{err}");
+    let err = headers[4].resolve().unwrap_err().to_string();
+    assert!(err.contains("not both"), "{err}");
+}
+
+#[test]
+fn test_low_memory_fetch_options() {
+    let opts = FetchOpts {
+        concurrency: 8,
+        default_page_size: 500,
+        fetch_batch_size: 5000,
+        flush_interval: Some(Duration::from_secs(2)),
+    };
+    let low = opts.low_memory();
+    assert_eq!(low.concurrency, 1);
+    assert_eq!(low.fetch_batch_size, LOW_MEMORY_BATCH_SIZE);
+    assert_eq!(low.default_page_size, 500);
+    assert_eq!(low.flush_interval, Some(Duration::from_secs(2)));
+
+    let small = FetchOpts {
+        fetch_batch_size: 10,
+        ..opts
+    };
+    assert_eq!(small.low_memory().fetch_batch_size, 10);
+}
@@ -0,0 +1,66 @@
+use apitap::pipeline::connections::TargetConnections;
+use apitap::pipeline::{SqliteSink, Target, TargetConn};
+
+fn sqlite_target(dir: &tempfile::TempDir) -> Target {
+    Target::Sqlite(SqliteSink {
+        name: "lite".to_string(),
+        metadata_columns: Vec::new(),
+        path: dir.path().join("dest.db").to_string_lossy().into_owned(),
+    })
+}
+
+#[tokio::test]
+async fn test_ping_and_close_sqlite_conn() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut conns = TargetConnections::new();
+    let conn = conns.acquire("lite", &sqlite_target(&dir)).await.unwrap();
+    conn.ping().await.unwrap();
+
+    conn.close().await;
+    assert!(conn.ping().await.is_err());
+}
+
+#[tokio::test]
+async fn test_connection_reused_across_modules() {
+    let dir = tempfile::tempdir().unwrap();
+    let target = sqlite_target(&dir);
+    let mut conns = TargetConnections::new();
+
+    let first = match conns.acquire("lite", &target).await.unwrap() {
+        TargetConn::Sqlite { pool, .. } => pool.clone(),
+        other => panic!("unexpected conn: {other:?}"),
+    };
+    let second = match conns.acquire("lite", &target).await.unwrap() {
+        TargetConn::Sqlite { pool, .. } => pool.clone(),
+        other => panic!("unexpected conn: {other:?}"),
+    };
+    assert_eq!(conns.len(), 1);
+    assert!(!first.is_closed());
+    conns.close_all().await;
+    // Both handles point at the single cached pool.
+    assert!(first.is_closed() && second.is_closed());
+    assert!(conns.is_empty());
+}
+
+#[tokio::test]
+async fn test_stale_connection_is_replaced() {
+    let dir = tempfile::tempdir().unwrap();
+    let target = sqlite_target(&dir);
+    let mut conns = TargetConnections::new();
+
+    let stale = match conns.acquire("lite", &target).await.unwrap() {
+        TargetConn::Sqlite { pool, .. } => pool.clone(),
+        other => panic!("unexpected conn: {other:?}"),
+    };
+    // Simulate the pool going away underneath the run.
+    stale.close().await;
+
+    let conn = conns.acquire("lite", &target).await.unwrap();
+    conn.ping().await.unwrap();
+    match conn {
+        TargetConn::Sqlite { pool, .. } => assert!(!pool.is_closed()),
+        other => panic!("unexpected conn: {other:?}"),
+    }
+    assert_eq!(conns.len(), 1);
+    conns.close_all().await;
+}
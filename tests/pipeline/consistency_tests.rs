@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use apitap::pipeline::consistency::{check_consistency, ConsistencyCheck};
+use apitap::utils::datafusion_ext::QueryResultStream;
+use apitap::writer::debug::DebugWriter;
+use apitap::writer::sqlite::SqliteWriter;
+use apitap::writer::{DataWriter, WriteMode};
+use serde_json::{json, Value};
+use sqlx::sqlite::SqlitePoolOptions;
+
+async fn sqlite_sink(rows: Vec<Value>) -> Arc<dyn DataWriter> {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    let writer = SqliteWriter::new(pool, "orders");
+    writer
+        .write_stream(
+            QueryResultStream {
+                table_name: "orders".to_string(),
+                data: Box::pin(futures::stream::iter(rows.into_iter().map(Ok))),
+            },
+            WriteMode::Append,
+        )
+        .await
+        .unwrap();
+    Arc::new(writer)
+}
+
+fn check() -> ConsistencyCheck {
+    serde_yaml::from_str("{}").unwrap()
+}
+
+#[test]
+fn test_consistency_check_defaults() {
+    let check = check();
+    assert_eq!(check.key, None);
+    assert_eq!(check.sample_keys, 100);
+    assert!(!check.fail_on_divergence);
+}
+
+#[tokio::test]
+async fn test_consistent_sinks() {
+    let rows: Vec<Value> = (1..=5)
+        .map(|id| json!({"id": id, "total": id * 10}))
+        .collect();
+    let sinks = vec![
+        ("pg:orders".to_string(), sqlite_sink(rows.clone()).await),
+        ("lake:orders".to_string(), sqlite_sink(rows).await),
+    ];
+
+    let report = check_consistency(&check(), Some("id"), &sinks)
+        .await
+        .unwrap();
+    assert_eq!(report.reference.rows, Some(5));
+    assert_eq!(report.sampled_keys, 5);
+    assert_eq!(report.others[0].rows, Some(5));
+    assert_eq!(report.others[0].missing_keys, Some(0));
+    assert!(report.diverged().is_empty());
+}
+
+#[tokio::test]
+async fn test_diverged_sinks() {
+    let rows: Vec<Value> = (1..=5).map(|id| json!({"id": id})).collect();
+    let sinks = vec![
+        ("pg:orders".to_string(), sqlite_sink(rows.clone()).await),
+        (
+            "lake:orders".to_string(),
+            sqlite_sink(rows[..3].to_vec()).await,
+        ),
+        ("dw:orders".to_string(), sqlite_sink(rows).await),
+    ];
+
+    let report = check_consistency(&check(), Some("id"), &sinks)
+        .await
+        .unwrap();
+    let diverged = report.diverged();
+    assert_eq!(diverged.len(), 1);
+    assert_eq!(diverged[0].label, "lake:orders");
+    assert_eq!(diverged[0].rows, Some(3));
+    assert_eq!(diverged[0].missing_keys, Some(2));
+}
+
+#[tokio::test]
+async fn test_unsupported_sinks_are_not_divergent() {
+    let rows: Vec<Value> = (1..=3).map(|id| json!({"id": id})).collect();
+    let sinks: Vec<(String, Arc<dyn DataWriter>)> = vec![
+        ("pg:orders".to_string(), sqlite_sink(rows).await),
+        (
+            "stdout:orders".to_string(),
+            Arc::new(DebugWriter::new("orders")),
+        ),
+    ];
+
+    let report = check_consistency(&check(), Some("id"), &sinks)
+        .await
+        .unwrap();
+    assert_eq!(report.others[0].rows, None);
+    assert_eq!(report.others[0].missing_keys, None);
+    assert!(report.diverged().is_empty());
+}
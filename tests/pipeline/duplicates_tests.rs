@@ -0,0 +1,110 @@
+use std::sync::Arc;
+
+use apitap::pipeline::duplicates::{check_duplicates, DuplicateCheck, DuplicateKeys};
+use apitap::utils::datafusion_ext::QueryResultStream;
+use apitap::writer::debug::DebugWriter;
+use apitap::writer::sqlite::SqliteWriter;
+use apitap::writer::{DataWriter, WriteMode};
+use serde_json::{json, Value};
+use sqlx::sqlite::SqlitePoolOptions;
+
+async fn sqlite_table(runs: Vec<Vec<Value>>) -> Arc<dyn DataWriter> {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    let writer = SqliteWriter::new(pool, "orders");
+    for rows in runs {
+        writer
+            .write_stream(
+                QueryResultStream {
+                    table_name: "orders".to_string(),
+                    data: Box::pin(futures::stream::iter(rows.into_iter().map(Ok))),
+                },
+                WriteMode::Append,
+            )
+            .await
+            .unwrap();
+    }
+    Arc::new(writer)
+}
+
+#[test]
+fn test_duplicate_check_defaults() {
+    let check: DuplicateCheck = serde_yaml::from_str("{}").unwrap();
+    assert_eq!(check.key, None);
+    assert!(!check.fail_on_duplicates);
+}
+
+#[tokio::test]
+async fn test_duplicates_across_appended_runs() {
+    let run: Vec<Value> = (1..=4).map(|id| json!({"id": id})).collect();
+    let tables = vec![(
+        "pg:orders".to_string(),
+        sqlite_table(vec![run.clone(), run[..2].to_vec(), run[..1].to_vec()]).await,
+    )];
+
+    let report = check_duplicates("id", false, &tables).await.unwrap();
+    assert_eq!(report.columns, vec!["id".to_string()]);
+    // id 1 three times, id 2 twice.
+    assert_eq!(
+        report.tables[0].duplicates,
+        Some(DuplicateKeys {
+            keys: 2,
+            extra_rows: 3
+        })
+    );
+    assert_eq!(report.extra_rows(), 3);
+}
+
+#[tokio::test]
+async fn test_snapshot_tables_group_by_snapshot() {
+    let snapshot = |ts: &str, ids: &[i64]| -> Vec<Value> {
+        ids.iter()
+            .map(|id| json!({"id": id, "snapshot_ts": ts}))
+            .collect()
+    };
+    let tables = vec![(
+        "pg:orders".to_string(),
+        sqlite_table(vec![
+            snapshot("2026-10-01T00:00:00Z", &[1, 2]),
+            // The API paged id 2 twice in the second run.
+            snapshot("2026-10-02T00:00:00Z", &[1, 2, 2]),
+        ])
+        .await,
+    )];
+
+    let report = check_duplicates("id", true, &tables).await.unwrap();
+    assert_eq!(
+        report.tables[0].duplicates,
+        Some(DuplicateKeys {
+            keys: 1,
+            extra_rows: 1
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_unsupported_and_missing_tables() {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    let tables: Vec<(String, Arc<dyn DataWriter>)> = vec![
+        (
+            "lite:orders".to_string(),
+            Arc::new(SqliteWriter::new(pool, "orders")),
+        ),
+        (
+            "debug:orders".to_string(),
+            Arc::new(DebugWriter::new("orders")),
+        ),
+    ];
+
+    let report = check_duplicates("id", false, &tables).await.unwrap();
+    assert_eq!(report.tables[0].duplicates, Some(DuplicateKeys::default()));
+    assert_eq!(report.tables[1].duplicates, None);
+    assert_eq!(report.extra_rows(), 0);
+}
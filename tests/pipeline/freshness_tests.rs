@@ -0,0 +1,71 @@
+use std::time::Duration;
+
+use apitap::pipeline::freshness::{
+    parse_duration, parse_loaded_at, Freshness, FreshnessSeverity, FreshnessStatus,
+};
+use apitap::writer::metadata_columns::MetadataColumn;
+use chrono::{TimeZone, Utc};
+
+#[test]
+fn test_parse_duration_units() {
+    assert_eq!(parse_duration("45s").unwrap(), Duration::from_secs(45));
+    assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(1800));
+    assert_eq!(parse_duration("6h").unwrap(), Duration::from_secs(21_600));
+    assert_eq!(parse_duration("1d").unwrap(), Duration::from_secs(86_400));
+    assert_eq!(parse_duration("1h30m").unwrap(), Duration::from_secs(5400));
+}
+
+#[test]
+fn test_default_column_is_written_by_metadata_columns() {
+    let freshness = Freshness::new(Duration::from_secs(60));
+    assert_eq!(freshness.column, MetadataColumn::LoadedAt.name());
+}
+
+#[test]
+fn test_parse_duration_rejects_invalid() {
+    for bad in ["", "6", "h", "6x", "1.5h", "6h30"] {
+        assert!(parse_duration(bad).is_err(), "{bad} should be rejected");
+    }
+}
+
+#[test]
+fn test_parse_loaded_at_formats() {
+    let expected = Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+    for text in [
+        "2024-01-02T03:04:05Z",
+        "2024-01-02 03:04:05+00",
+        "2024-01-02 05:04:05+02:00",
+        "2024-01-02 03:04:05",
+    ] {
+        assert_eq!(parse_loaded_at(text), Some(expected), "{text}");
+    }
+    assert_eq!(parse_loaded_at("yesterday"), None);
+}
+
+#[test]
+fn test_evaluate_window() {
+    let freshness = Freshness::new(Duration::from_secs(6 * 3600));
+    assert_eq!(freshness.severity, FreshnessSeverity::Warn);
+    let now = Utc.with_ymd_and_hms(2024, 1, 2, 12, 0, 0).unwrap();
+
+    assert_eq!(
+        freshness.evaluate(Some(now - chrono::Duration::hours(2)), now),
+        FreshnessStatus::Fresh {
+            age: Duration::from_secs(7200)
+        }
+    );
+    assert_eq!(
+        freshness.evaluate(Some(now - chrono::Duration::hours(7)), now),
+        FreshnessStatus::Stale {
+            age: Duration::from_secs(7 * 3600)
+        }
+    );
+    // Newest load slightly in the future (clock skew) counts as fresh.
+    assert_eq!(
+        freshness.evaluate(Some(now + chrono::Duration::seconds(5)), now),
+        FreshnessStatus::Fresh {
+            age: Duration::ZERO
+        }
+    );
+    assert_eq!(freshness.evaluate(None, now), FreshnessStatus::Unknown);
+}
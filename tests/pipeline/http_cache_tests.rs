@@ -0,0 +1,198 @@
+// Tests for conditional requests (http_cache)
+
+use crate::http::{retry, serve, CollectRows, StubResponse};
+use apitap::http::fetcher::{PaginatedFetcher, RequestOptions, StopWhen, TotalHint};
+use apitap::pipeline::http_cache::{HttpCache, PageValidator};
+use apitap::pipeline::retry_state::RetryStateStore;
+use apitap::writer::WriteMode;
+use reqwest::header::{HeaderMap, HeaderValue};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Five items, two per page by `offset`, each page tagged `"p<offset>"`
+/// and flagged with `has_more`. The page at offset 2 changes on every request; the others answer 304
+/// to a matching `If-None-Match`. Returns the url and the status log.
+async fn serve_items() -> (String, Arc<Mutex<Vec<(u64, u16)>>>) {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&log);
+    let base = serve(move |req| {
+        let offset = req.num("offset").unwrap_or(0);
+        let etag = format!("\"p{offset}\"");
+        if offset != 2 && req.header("if-none-match") == Some(etag.as_str()) {
+            seen.lock().unwrap().push((offset, 304));
+            return StubResponse::status("304 Not Modified");
+        }
+        seen.lock().unwrap().push((offset, 200));
+        let items: Vec<Value> = (offset..(offset + 2).min(5))
+            .map(|id| json!({"id": id}))
+            .collect();
+        let has_more = offset + 2 < 5;
+        StubResponse::json(json!({ "items": items, "has_more": has_more })).header("ETag", etag)
+    })
+    .await;
+    (format!("{base}/items"), log)
+}
+
+/// One run of the module against the store at `path`, walking pages until
+/// `stop`: ids written and pages skipped.
+async fn run(url: &str, path: &Path, stop: StopWhen) -> (Vec<u64>, usize) {
+    let store = Arc::new(RetryStateStore::open(path).await.unwrap());
+    let cache = HttpCache::new(store, "items.sql");
+    let fetcher = PaginatedFetcher::new(reqwest::Client::new(), url, 1)
+        .with_limit_offset("limit", "offset")
+        .with_stop_when(stop)
+        .with_request_options(RequestOptions {
+            http_cache: Some(cache.clone()),
+            ..Default::default()
+        });
+    let writer = Arc::new(CollectRows::default());
+    fetcher
+        .fetch_limit_offset(
+            2,
+            Some("/items".into()),
+            None,
+            None,
+            writer.clone(),
+            WriteMode::Merge,
+            &retry(),
+        )
+        .await
+        .unwrap();
+    cache.commit().await;
+    (writer.ids(), cache.unchanged_pages())
+}
+
+#[tokio::test]
+async fn test_unchanged_pages_are_skipped() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state.db");
+    let (url, log) = serve_items().await;
+
+    assert_eq!(
+        run(&url, &path, StopWhen::default()).await,
+        (vec![0, 1, 2, 3, 4], 0)
+    );
+
+    log.lock().unwrap().clear();
+    // Only the changed page is written; the walk still ends at the empty
+    // page, from the row counts kept for the unchanged ones.
+    assert_eq!(run(&url, &path, StopWhen::default()).await, (vec![2, 3], 3));
+    assert_eq!(
+        *log.lock().unwrap(),
+        vec![(0, 304), (2, 200), (4, 304), (6, 304)]
+    );
+}
+
+#[tokio::test]
+async fn test_unchanged_pages_are_skipped_with_a_stop_flag() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state.db");
+    let (url, log) = serve_items().await;
+    let has_more: StopWhen = serde_yaml::from_str("pointer: /has_more").unwrap();
+
+    assert_eq!(
+        run(&url, &path, has_more.clone()).await,
+        (vec![0, 1, 2, 3, 4], 0)
+    );
+    assert_eq!(*log.lock().unwrap(), vec![(0, 200), (2, 200), (4, 200)]);
+
+    log.lock().unwrap().clear();
+    // A 304 carries no flag, so the walk goes on to the empty page.
+    assert_eq!(run(&url, &path, has_more).await, (vec![2, 3], 2));
+    assert_eq!(
+        *log.lock().unwrap(),
+        vec![(0, 304), (2, 200), (4, 304), (6, 200)]
+    );
+}
+
+/// Two pages of two items by `page`, announcing `total_pages`; the first
+/// answers 304 to a matching `If-None-Match`, the second changes on every
+/// request. Returns the url and the status log.
+async fn serve_pages() -> (String, Arc<Mutex<Vec<(u64, u16)>>>) {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&log);
+    let base = serve(move |req| {
+        let page = req.num("page").unwrap_or(1);
+        if page == 1 && req.header("if-none-match") == Some("\"p1\"") {
+            seen.lock().unwrap().push((page, 304));
+            return StubResponse::status("304 Not Modified");
+        }
+        seen.lock().unwrap().push((page, 200));
+        let items: Vec<Value> = if page <= 2 {
+            (page * 10..page * 10 + 2)
+                .map(|id| json!({"id": id}))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        StubResponse::json(json!({ "total_pages": 2, "items": items }))
+            .header("ETag", format!("\"p{page}\""))
+    })
+    .await;
+    (format!("{base}/items"), log)
+}
+
+#[tokio::test]
+async fn test_unchanged_first_page_of_a_total_walk_is_skipped() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state.db");
+    let (url, log) = serve_pages().await;
+    let run = || async {
+        let store = Arc::new(RetryStateStore::open(&path).await.unwrap());
+        let cache = HttpCache::new(store, "items.sql");
+        let fetcher = PaginatedFetcher::new(reqwest::Client::new(), url.as_str(), 2)
+            .with_page_number("page", "per_page")
+            .with_request_options(RequestOptions {
+                http_cache: Some(cache.clone()),
+                ..Default::default()
+            });
+        let writer = Arc::new(CollectRows::default());
+        fetcher
+            .fetch_page_number(
+                2,
+                Some("/items"),
+                None,
+                Some(TotalHint::Pages {
+                    pointer: "/total_pages".to_string(),
+                }),
+                writer.clone(),
+                WriteMode::Merge,
+                &retry(),
+            )
+            .await
+            .unwrap();
+        cache.commit().await;
+        (writer.ids(), cache.unchanged_pages())
+    };
+
+    assert_eq!(run().await, (vec![10, 11, 20, 21], 0));
+
+    log.lock().unwrap().clear();
+    // A 304 carries no total, so the walk goes on to the empty page.
+    assert_eq!(run().await, (vec![20, 21], 1));
+    assert_eq!(*log.lock().unwrap(), vec![(1, 304), (2, 200), (3, 200)]);
+}
+
+#[test]
+fn test_validator_headers() {
+    let mut headers = HeaderMap::new();
+    assert!(PageValidator::from_headers(&headers).is_none());
+
+    headers.insert("etag", HeaderValue::from_static("W/\"abc\""));
+    headers.insert(
+        "last-modified",
+        HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+    );
+    let validator = PageValidator::from_headers(&headers).unwrap();
+    assert_eq!(
+        validator.conditional_headers(),
+        vec![
+            ("if-none-match".to_string(), "W/\"abc\"".to_string()),
+            (
+                "if-modified-since".to_string(),
+                "Wed, 21 Oct 2015 07:28:00 GMT".to_string()
+            ),
+        ]
+    );
+}
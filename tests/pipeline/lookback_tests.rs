@@ -0,0 +1,47 @@
+use apitap::pipeline::lookback::Lookback;
+use apitap::writer::WriteMode;
+
+#[test]
+fn test_lookback_from_yaml() {
+    let lookback: Lookback = serde_yaml::from_str("2d").unwrap();
+    assert_eq!(lookback.window(), chrono::Duration::days(2));
+    assert_eq!(serde_yaml::to_string(&lookback).unwrap().trim(), "2d");
+    assert!(serde_yaml::from_str::<Lookback>("two days").is_err());
+}
+
+#[test]
+fn test_rewind_keeps_the_value_shape() {
+    let lookback = Lookback::parse("2d").unwrap();
+    let rewind = |value: &str| lookback.rewind(value).unwrap();
+    assert_eq!(rewind("2024-05-03T10:00:00Z"), "2024-05-01T10:00:00Z");
+    assert_eq!(
+        rewind("2024-05-03T10:00:00.250+02:00"),
+        "2024-05-01T08:00:00.250Z"
+    );
+    assert_eq!(rewind("2024-05-03 10:00:00"), "2024-05-01 10:00:00");
+    assert_eq!(rewind("2024-05-03"), "2024-05-01");
+}
+
+#[test]
+fn test_rewind_rejects_other_cursors() {
+    let lookback = Lookback::parse("2d").unwrap();
+    assert!(lookback.rewind("1234").is_err());
+    assert!(lookback.rewind("abc").is_err());
+
+    let huge = Lookback::parse("100000000w").unwrap();
+    assert!(huge.rewind("2024-05-03").is_err());
+}
+
+#[test]
+fn test_lookback_needs_a_keyed_merge() {
+    let lookback = Lookback::parse("1d").unwrap();
+    assert!(lookback
+        .check_merge("orders", &WriteMode::Merge, Some("id"))
+        .is_ok());
+    assert!(lookback
+        .check_merge("orders", &WriteMode::Merge, None)
+        .is_err());
+    assert!(lookback
+        .check_merge("orders", &WriteMode::Append, Some("id"))
+        .is_err());
+}
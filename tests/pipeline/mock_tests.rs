@@ -0,0 +1,143 @@
+use std::sync::{Arc, Mutex};
+
+use apitap::errors::Result;
+use apitap::http::fetcher::DataFusionPageWriter;
+use apitap::pipeline::mock::{run_mock_fetch, MockSource};
+use apitap::pipeline::Config;
+use apitap::utils::datafusion_ext::{QueryResult, QueryResultStream};
+use apitap::writer::{DataWriter, WriteMode};
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+/// Sink that keeps every row it receives.
+#[derive(Default)]
+struct CaptureWriter {
+    rows: Mutex<Vec<Value>>,
+}
+
+#[async_trait]
+impl DataWriter for CaptureWriter {
+    async fn write(&self, _result: QueryResult) -> Result<()> {
+        Ok(())
+    }
+
+    async fn write_stream(&self, mut result: QueryResultStream, _mode: WriteMode) -> Result<()> {
+        while let Some(row) = result.data.next().await {
+            self.rows.lock().unwrap().push(row?);
+        }
+        Ok(())
+    }
+}
+
+fn fixture(dir: &TempDir, name: &str, text: &str, pages: usize) -> MockSource {
+    let path = dir.path().join(name);
+    std::fs::write(&path, text).unwrap();
+    MockSource {
+        file: path.to_string_lossy().into_owned(),
+        pages,
+    }
+}
+
+#[test]
+fn test_mock_from_yaml() {
+    let config: Config = serde_yaml::from_str(
+        r#"
+sources:
+  - name: users
+    url: https://api.example.com/users
+    table_destination_name: users
+    mock:
+      file: fixtures/users.json
+      pages: 3
+    retry:
+      max_attempts: 3
+      max_delay_secs: 60
+      min_delay_secs: 1
+targets: []
+"#,
+    )
+    .unwrap();
+    let mock = config.sources[0].mock.as_ref().unwrap();
+    assert_eq!(mock.file, "fixtures/users.json");
+    assert_eq!(mock.pages, 3);
+}
+
+#[test]
+fn test_rows_split_over_pages() {
+    let dir = TempDir::new().unwrap();
+    let rows: Vec<Value> = (1..=7).map(|id| json!({ "id": id })).collect();
+    let body = json!({ "data": rows, "meta": { "total": 7 } }).to_string();
+    let mock = fixture(&dir, "users.json", &body, 3);
+
+    let pages = mock.page_rows(Some("/data")).unwrap();
+    let sizes: Vec<usize> = pages.iter().map(Vec::len).collect();
+    assert_eq!(sizes, vec![3, 3, 1]);
+    assert_eq!(pages[2][0], json!({"id": 7}));
+}
+
+#[test]
+fn test_more_pages_than_rows() {
+    let dir = TempDir::new().unwrap();
+    let mock = fixture(&dir, "users.json", r#"[{"id": 1}, {"id": 2}]"#, 5);
+    assert_eq!(mock.page_rows(None).unwrap().len(), 2);
+}
+
+#[test]
+fn test_ndjson_fixture() {
+    let dir = TempDir::new().unwrap();
+    let mock = fixture(&dir, "events.ndjson", "{\"id\":1}\n\n{\"id\":2}\n", 1);
+    assert_eq!(
+        mock.rows(None).unwrap(),
+        vec![json!({"id": 1}), json!({"id": 2})]
+    );
+}
+
+#[test]
+fn test_bad_fixtures_are_config_errors() {
+    let dir = TempDir::new().unwrap();
+    let missing = MockSource {
+        file: dir.path().join("nope.json").to_string_lossy().into_owned(),
+        pages: 1,
+    };
+    assert!(missing
+        .rows(None)
+        .unwrap_err()
+        .to_string()
+        .contains("nope.json"));
+
+    let broken = fixture(&dir, "broken.json", "{", 1);
+    assert!(broken.rows(None).is_err());
+
+    let no_pages = fixture(&dir, "users.json", "[]", 0);
+    assert!(no_pages.page_rows(None).is_err());
+}
+
+#[tokio::test]
+async fn test_run_mock_fetch_writes_pages_through_sql() {
+    let dir = TempDir::new().unwrap();
+    let mock = fixture(
+        &dir,
+        "orders.json",
+        r#"{"items": [{"id": 1, "status": "paid"}, {"id": 2, "status": "open"}, {"id": 3, "status": "paid"}]}"#,
+        2,
+    );
+    let sink = Arc::new(CaptureWriter::default());
+    let writer = DataFusionPageWriter::routed("mock_orders").with_route(
+        "paid",
+        "SELECT id FROM mock_orders WHERE status = 'paid' ORDER BY id",
+        sink.clone(),
+    );
+
+    let stats = run_mock_fetch(&mock, Some("/items"), writer, WriteMode::Append)
+        .await
+        .unwrap();
+
+    assert_eq!(stats.success_count, 2);
+    assert_eq!(stats.total_items, 3);
+    assert_eq!(
+        *sink.rows.lock().unwrap(),
+        vec![json!({"id": 1}), json!({"id": 3})]
+    );
+}
@@ -1 +1,21 @@
+mod audit_tests;
 mod config_tests;
+mod connections_tests;
+mod consistency_tests;
+mod duplicates_tests;
+mod freshness_tests;
+mod http_cache_tests;
+mod lookback_tests;
+mod mock_tests;
+mod page_progress_tests;
+mod query_template_tests;
+mod reconcile_tests;
+mod retention_tests;
+mod retry_state_tests;
+mod run_history_tests;
+mod sla_tests;
+mod snapshot_tests;
+mod sql_source_tests;
+mod state_tests;
+mod tls_tests;
+mod watermark_tests;
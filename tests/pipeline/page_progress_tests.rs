@@ -0,0 +1,161 @@
+// Tests for resuming a run after the pages it already wrote
+
+use crate::http::{retry, serve, StubResponse};
+use apitap::errors::{ApitapError, Result};
+use apitap::http::fetcher::{PageWriter, PaginatedFetcher, RequestOptions};
+use apitap::pipeline::page_progress::ProgressTracker;
+use apitap::pipeline::retry_state::RetryStateStore;
+use apitap::pipeline::state::export_state;
+use apitap::writer::WriteMode;
+use async_trait::async_trait;
+use futures::{Stream, TryStreamExt};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+/// Keeps the rows written; fails once it reaches `fail_at` like a sink
+/// going away.
+struct FlakySink {
+    rows: Mutex<Vec<Value>>,
+    fail_at: Option<u64>,
+}
+
+impl FlakySink {
+    fn new(fail_at: Option<u64>) -> Self {
+        Self {
+            rows: Mutex::default(),
+            fail_at,
+        }
+    }
+
+    fn ids(&self) -> Vec<u64> {
+        let rows = self.rows.lock().unwrap();
+        rows.iter().map(|r| r["id"].as_u64().unwrap()).collect()
+    }
+}
+
+#[async_trait]
+impl PageWriter for FlakySink {
+    async fn write_page(&self, _page: u64, data: Vec<Value>, _mode: WriteMode) -> Result<()> {
+        self.rows.lock().unwrap().extend(data);
+        Ok(())
+    }
+
+    async fn write_page_stream(
+        &self,
+        stream: Pin<Box<dyn Stream<Item = Result<Value>> + Send>>,
+        _mode: WriteMode,
+    ) -> Result<()> {
+        let rows: Vec<Value> = stream.try_collect().await?;
+        if let Some(fail_at) = self.fail_at {
+            if rows.iter().any(|r| r["id"].as_u64() == Some(fail_at)) {
+                return Err(ApitapError::PipelineError("sink went away".into()));
+            }
+        }
+        self.rows.lock().unwrap().extend(rows);
+        Ok(())
+    }
+}
+
+/// Four pages of two items by `page`, then an empty one. Returns the url
+/// and the pages requested.
+async fn serve_pages() -> (String, Arc<Mutex<Vec<u64>>>) {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&log);
+    let base = serve(move |req| {
+        let page = req.num("page").unwrap_or(1);
+        seen.lock().unwrap().push(page);
+        let items: Vec<Value> = if page <= 4 {
+            (page * 10..page * 10 + 2)
+                .map(|id| json!({"id": id}))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        StubResponse::json(json!({ "items": items }))
+    })
+    .await;
+    (format!("{base}/items"), log)
+}
+
+/// One run of the module against the store at `path`.
+async fn run(
+    url: &str,
+    path: &Path,
+    resume: bool,
+    sink: Arc<FlakySink>,
+) -> (Result<usize>, ProgressTracker) {
+    let store = Arc::new(RetryStateStore::open(path).await.unwrap());
+    let progress = ProgressTracker::start(store, "items.sql", resume).await;
+    let fetcher = PaginatedFetcher::new(reqwest::Client::new(), url, 1)
+        .with_page_number("page", "per_page")
+        .with_request_options(RequestOptions {
+            progress: Some(progress.clone()),
+            ..Default::default()
+        });
+    let stats = fetcher
+        .fetch_page_number(
+            2,
+            Some("/items"),
+            None,
+            None,
+            sink,
+            WriteMode::Merge,
+            &retry(),
+        )
+        .await;
+    (stats.map(|s| s.total_items), progress)
+}
+
+#[tokio::test]
+async fn test_resume_skips_pages_already_written() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state.db");
+    let (url, log) = serve_pages().await;
+
+    // The sink goes away on the third page; pages 1 and 2 made it.
+    let sink = Arc::new(FlakySink::new(Some(30)));
+    let (outcome, _) = run(&url, &path, false, sink.clone()).await;
+    assert!(outcome.is_err());
+    assert_eq!(sink.ids(), vec![10, 11, 20, 21]);
+
+    // Resumed: both are skipped without a request.
+    log.lock().unwrap().clear();
+    let sink = Arc::new(FlakySink::new(None));
+    let (outcome, progress) = run(&url, &path, true, sink.clone()).await;
+    assert_eq!(outcome.unwrap(), 4);
+    assert_eq!(sink.ids(), vec![30, 31, 40, 41]);
+    assert_eq!(*log.lock().unwrap(), vec![3, 4, 5]);
+    assert_eq!(progress.skipped_pages(), 2);
+
+    progress.finish(true).await;
+    drop(progress);
+    assert!(export_state(&path).await.unwrap().progress.is_empty());
+}
+
+#[tokio::test]
+async fn test_progress_is_reset_without_resume() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state.json");
+    let (url, log) = serve_pages().await;
+
+    let (outcome, _) = run(&url, &path, false, Arc::new(FlakySink::new(Some(40)))).await;
+    assert!(outcome.is_err());
+    let saved = export_state(&path).await.unwrap().progress;
+    assert_eq!(saved["items.sql"].len(), 3);
+
+    log.lock().unwrap().clear();
+    let sink = Arc::new(FlakySink::new(None));
+    let (outcome, progress) = run(&url, &path, false, sink.clone()).await;
+    assert_eq!(outcome.unwrap(), 8);
+    assert_eq!(*log.lock().unwrap(), vec![1, 2, 3, 4, 5]);
+    assert_eq!(progress.skipped_pages(), 0);
+
+    // A module with a failed page keeps its progress for --resume.
+    progress.finish(false).await;
+    assert_eq!(
+        export_state(&path).await.unwrap().progress["items.sql"].len(),
+        5
+    );
+}
@@ -0,0 +1,117 @@
+use std::collections::BTreeMap;
+
+use apitap::pipeline::query_template::{parse_var, QueryTemplate};
+use apitap::pipeline::{Config, QueryParam, QueryValue};
+use chrono::{TimeZone, Utc};
+
+fn template(vars: &[(&str, &str)]) -> QueryTemplate {
+    let vars: BTreeMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    QueryTemplate::new(Utc.with_ymd_and_hms(2024, 5, 1, 6, 30, 0).unwrap(), vars)
+}
+
+fn params(yaml: &str) -> Vec<QueryParam> {
+    serde_yaml::from_str(yaml).unwrap()
+}
+
+fn values(rendered: &[QueryParam]) -> Vec<QueryValue> {
+    rendered.iter().map(|p| p.value.clone()).collect()
+}
+
+#[test]
+fn test_renders_dates_from_run_start() {
+    let rendered = template(&[])
+        .render(
+            "orders",
+            &params(
+                r#"
+- key: date
+  value: "{{ today() }}"
+- key: since
+  value: "{{ days_ago(7) }}"
+- key: compact
+  value: "{{ today(format='%Y%m%d') }}"
+- key: as_of
+  value: "{{ run_started_at }}"
+- key: window
+  value: ["{{ days_ago(1) }}", "{{ today() }}"]
+"#,
+            ),
+        )
+        .unwrap();
+    assert_eq!(
+        values(&rendered),
+        [
+            QueryValue::from("2024-05-01"),
+            QueryValue::from("2024-04-24"),
+            QueryValue::from("20240501"),
+            QueryValue::from("2024-05-01T06:30:00Z"),
+            QueryValue::Many(vec!["2024-04-30".into(), "2024-05-01".into()]),
+        ]
+    );
+}
+
+#[test]
+fn test_var_uses_vars_then_default() {
+    let rendered = template(&[("region", "eu")])
+        .render(
+            "orders",
+            &params(
+                r#"
+- key: region
+  value: "{{ var('region') }}"
+- key: tier
+  value: "{{ var('tier', default='gold') }}"
+- key: plain
+  value: "a {b} c"
+"#,
+            ),
+        )
+        .unwrap();
+    assert_eq!(
+        values(&rendered),
+        [
+            QueryValue::from("eu"),
+            QueryValue::from("gold"),
+            QueryValue::from("a {b} c"),
+        ]
+    );
+}
+
+#[test]
+fn test_errors_name_source_and_param() {
+    let err = template(&[])
+        .render(
+            "orders",
+            &params("- key: region\n  value: \"{{ var('region') }}\"\n"),
+        )
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("orders: query_params 'region'"), "{err}");
+    assert!(err.contains("var 'region' is not set"), "{err}");
+
+    let err = template(&[])
+        .render(
+            "orders",
+            &params("- key: d\n  value: \"{{ today(format='%Q') }}\"\n"),
+        )
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("invalid date format"), "{err}");
+}
+
+#[test]
+fn test_parse_var_and_config_vars() {
+    assert_eq!(
+        parse_var("region=eu-west=1").unwrap(),
+        ("region".to_string(), "eu-west=1".to_string())
+    );
+    assert!(parse_var("region").is_err());
+    assert!(parse_var("=eu").is_err());
+
+    let cfg: Config =
+        serde_yaml::from_str("vars:\n  region: eu\nsources: []\ntargets: []\n").unwrap();
+    assert_eq!(cfg.vars.get("region").map(String::as_str), Some("eu"));
+}
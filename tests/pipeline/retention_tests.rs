@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use apitap::pipeline::retention::{apply_retention, RetentionConfig};
+use apitap::utils::datafusion_ext::QueryResultStream;
+use apitap::writer::sqlite::SqliteWriter;
+use apitap::writer::{DataWriter, WriteMode};
+use chrono::{TimeZone, Utc};
+use serde_json::json;
+use sqlx::sqlite::SqlitePoolOptions;
+
+fn policy(keep: &str) -> RetentionConfig {
+    RetentionConfig {
+        column: "created_at".to_string(),
+        keep: keep.to_string(),
+        detach_partitions: false,
+    }
+}
+
+#[test]
+fn test_retention_config_from_yaml() {
+    let config: RetentionConfig = serde_yaml::from_str("column: created_at\nkeep: 90d\n").unwrap();
+    assert_eq!(config, policy("90d"));
+    assert!(!config.detach_partitions);
+}
+
+#[test]
+fn test_retention_cutoff() {
+    let now = Utc.with_ymd_and_hms(2024, 4, 30, 12, 0, 0).unwrap();
+    assert_eq!(
+        policy("90d").cutoff(now).unwrap(),
+        Utc.with_ymd_and_hms(2024, 1, 31, 12, 0, 0).unwrap()
+    );
+    assert_eq!(
+        policy("1w").cutoff(now).unwrap(),
+        Utc.with_ymd_and_hms(2024, 4, 23, 12, 0, 0).unwrap()
+    );
+}
+
+#[test]
+fn test_retention_rejects_invalid_keep() {
+    let now = Utc::now();
+    for bad in ["", "90", "0d", "ninety days"] {
+        assert!(policy(bad).cutoff(now).is_err(), "{bad} should be rejected");
+    }
+}
+
+#[tokio::test]
+async fn test_apply_retention_purges_old_rows() {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    let writer: Arc<dyn DataWriter> = Arc::new(SqliteWriter::new(pool.clone(), "events"));
+    let tables = vec![("events".to_string(), Arc::clone(&writer))];
+    let cutoff = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+
+    // No table yet: nothing to purge.
+    apply_retention(&policy("30d"), cutoff, &tables)
+        .await
+        .unwrap();
+
+    let rows = vec![
+        json!({"id": 1, "created_at": "2024-01-15T10:00:00Z"}),
+        json!({"id": 2, "created_at": "2024-02-29 23:59:59"}),
+        json!({"id": 3, "created_at": "2024-03-01T00:00:00Z"}),
+        json!({"id": 4, "created_at": "2024-04-01T00:00:00+02:00"}),
+        json!({"id": 5, "created_at": null}),
+    ];
+    writer
+        .write_stream(
+            QueryResultStream {
+                table_name: "events".to_string(),
+                data: Box::pin(futures::stream::iter(rows.into_iter().map(Ok))),
+            },
+            WriteMode::Append,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        writer
+            .purge_before("created_at", cutoff, false)
+            .await
+            .unwrap(),
+        Some(2)
+    );
+    let ids: Vec<(i64,)> = sqlx::query_as(r#"SELECT "id" FROM "events" ORDER BY "id""#)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(ids, vec![(3,), (4,), (5,)]);
+}
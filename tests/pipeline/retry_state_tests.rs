@@ -0,0 +1,280 @@
+// Tests for retry-state persistence (--resume)
+
+use crate::http::{retry, serve, CollectRows, StubResponse};
+use apitap::errors::{ApitapError, Result};
+use apitap::http::fetcher::{PageWriter, PaginatedFetcher, RequestOptions, TotalHint};
+use apitap::pipeline::retry_state::{RetryStateStore, RetryTracker};
+use apitap::writer::WriteMode;
+use async_trait::async_trait;
+use futures::{Stream, StreamExt, TryStreamExt};
+use serde_json::{json, Value};
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// Takes nothing: every write fails.
+struct FailingPageWriter;
+
+#[async_trait]
+impl PageWriter for FailingPageWriter {
+    async fn write_page(&self, _page: u64, _data: Vec<Value>, _mode: WriteMode) -> Result<()> {
+        Err(ApitapError::PipelineError("sink is down".to_string()))
+    }
+}
+
+/// Fails every write of one page and takes the rest.
+struct RefusePage(u64);
+
+#[async_trait]
+impl PageWriter for RefusePage {
+    async fn write_page(&self, page: u64, _data: Vec<Value>, _mode: WriteMode) -> Result<()> {
+        if page == self.0 {
+            return Err(ApitapError::PipelineError("sink is down".to_string()));
+        }
+        Ok(())
+    }
+
+    async fn write_page_stream(
+        &self,
+        stream: Pin<Box<dyn Stream<Item = Result<Value>> + Send>>,
+        _mode: WriteMode,
+    ) -> Result<()> {
+        stream.try_for_each(|_| async { Ok(()) }).await
+    }
+}
+
+#[tokio::test]
+async fn test_store_persists_failures_across_reopen() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state").join("retry.json");
+
+    let store = RetryStateStore::open(&path).await.unwrap();
+    assert!(store.failed_pages("users.sql").await.is_empty());
+
+    store
+        .record_failure("users.sql", 7, "timeout")
+        .await
+        .unwrap();
+    store.record_failure("users.sql", 3, "503").await.unwrap();
+    store.record_failure("orders.sql", 1, "503").await.unwrap();
+
+    let reopened = RetryStateStore::open(&path).await.unwrap();
+    assert_eq!(reopened.failed_pages("users.sql").await, vec![3, 7]);
+    assert_eq!(reopened.failed_pages("orders.sql").await, vec![1]);
+
+    reopened.clear("users.sql", 3).await.unwrap();
+    reopened.clear("orders.sql", 1).await.unwrap();
+
+    let again = RetryStateStore::open(&path).await.unwrap();
+    assert_eq!(again.failed_pages("users.sql").await, vec![7]);
+    assert!(again.failed_pages("orders.sql").await.is_empty());
+}
+
+#[tokio::test]
+async fn test_tracker_only_resumes_when_asked() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = Arc::new(
+        RetryStateStore::open(dir.path().join("retry.json"))
+            .await
+            .unwrap(),
+    );
+    store.record_failure("users.sql", 4, "boom").await.unwrap();
+
+    let normal = RetryTracker::new(Arc::clone(&store), "users.sql", false);
+    assert!(normal.resume_pages().await.is_empty());
+
+    let resuming = RetryTracker::new(Arc::clone(&store), "users.sql", true);
+    assert_eq!(resuming.resume_pages().await, vec![4]);
+
+    resuming.page_succeeded(4).await;
+    assert!(resuming.resume_pages().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_fetch_pages_records_exhausted_pages() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = Arc::new(
+        RetryStateStore::open(dir.path().join("retry.json"))
+            .await
+            .unwrap(),
+    );
+    let request = RequestOptions {
+        failures: Some(RetryTracker::new(Arc::clone(&store), "users.sql", true)),
+        ..Default::default()
+    };
+
+    // Nothing listens on port 1, so both pages fail.
+    let fetcher = PaginatedFetcher::new(reqwest::Client::new(), "http://127.0.0.1:1/users", 1)
+        .with_page_number("page", "per_page")
+        .with_request_options(request);
+    let retry = retry();
+
+    let stats = fetcher
+        .fetch_pages(
+            &[2, 5],
+            50,
+            None,
+            None,
+            Arc::new(CollectRows::default()),
+            WriteMode::Append,
+            &retry,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(stats.error_count, 2);
+    assert_eq!(store.failed_pages("users.sql").await, vec![2, 5]);
+}
+
+/// A store at a fresh temporary path, and a resuming tracker of
+/// `users.sql` on it.
+async fn tracked(dir: &tempfile::TempDir) -> (Arc<RetryStateStore>, RequestOptions) {
+    let store = Arc::new(
+        RetryStateStore::open(dir.path().join("retry.json"))
+            .await
+            .unwrap(),
+    );
+    let request = RequestOptions {
+        failures: Some(RetryTracker::new(Arc::clone(&store), "users.sql", true)),
+        ..Default::default()
+    };
+    (store, request)
+}
+
+#[tokio::test]
+async fn test_limit_offset_without_a_total_records_failed_pages() {
+    let dir = tempfile::tempdir().unwrap();
+    let (store, request) = tracked(&dir).await;
+    store.record_failure("users.sql", 1, "503").await.unwrap();
+    // No total in the body, so pages are walked until the third fails.
+    let base = serve(|req| match req.num("offset").unwrap_or(0) {
+        4 => StubResponse::status("500 Internal Server Error"),
+        offset => StubResponse::json(json!({"items": [{"id": offset}, {"id": offset + 1}]})),
+    })
+    .await;
+    let fetcher = PaginatedFetcher::new(reqwest::Client::new(), format!("{base}/users"), 1)
+        .with_limit_offset("limit", "offset")
+        .with_request_options(request);
+
+    let result = fetcher
+        .fetch_limit_offset(
+            2,
+            Some("/items".into()),
+            None,
+            Some(TotalHint::Items {
+                pointer: "/total".to_string(),
+            }),
+            Arc::new(CollectRows::default()),
+            WriteMode::Append,
+            &retry(),
+        )
+        .await;
+
+    assert!(result.is_err());
+    assert_eq!(store.failed_pages("users.sql").await, vec![3]);
+}
+
+#[tokio::test]
+async fn test_next_url_records_failed_pages() {
+    let dir = tempfile::tempdir().unwrap();
+    let (store, request) = tracked(&dir).await;
+    store.record_failure("users.sql", 1, "503").await.unwrap();
+    let base = serve(|req| match req.num("page").unwrap_or(1) {
+        1 => StubResponse::json(json!({"items": [{"id": 1}], "next": "/users?page=2"})),
+        _ => StubResponse::status("500 Internal Server Error"),
+    })
+    .await;
+    let fetcher = PaginatedFetcher::new(reqwest::Client::new(), format!("{base}/users"), 1)
+        .with_next_url("/next", None)
+        .with_request_options(request);
+
+    let rows: Result<Vec<Value>> = fetcher
+        .follow_next_stream(1, Some("/items"), None, &retry())
+        .await
+        .unwrap()
+        .try_collect()
+        .await;
+
+    assert!(rows.is_err());
+    assert_eq!(store.failed_pages("users.sql").await, vec![2]);
+}
+
+#[tokio::test]
+async fn test_page_that_fails_to_write_stays_failed() {
+    let dir = tempfile::tempdir().unwrap();
+    let (store, request) = tracked(&dir).await;
+    store.record_failure("users.sql", 1, "503").await.unwrap();
+    let base = serve(|_| StubResponse::json(json!({"items": [{"id": 1}]}))).await;
+    let fetcher = PaginatedFetcher::new(reqwest::Client::new(), format!("{base}/users"), 1)
+        .with_page_number("page", "per_page")
+        .with_request_options(request);
+
+    let result = fetcher
+        .fetch_page_number(
+            1,
+            Some("/items"),
+            None,
+            None,
+            Arc::new(FailingPageWriter),
+            WriteMode::Append,
+            &retry(),
+        )
+        .await;
+
+    assert!(result.is_err());
+    assert_eq!(store.failed_pages("users.sql").await, vec![1]);
+}
+
+#[tokio::test]
+async fn test_page_is_done_only_once_its_rows_were_taken() {
+    let dir = tempfile::tempdir().unwrap();
+    let (store, request) = tracked(&dir).await;
+    store.record_failure("users.sql", 1, "503").await.unwrap();
+    let base = serve(|_| StubResponse::json(json!({"items": [{"id": 1}, {"id": 2}]}))).await;
+    let fetcher = PaginatedFetcher::new(reqwest::Client::new(), format!("{base}/users"), 1)
+        .with_next_url("/next", None)
+        .with_request_options(request);
+
+    let mut rows = fetcher
+        .follow_next_stream(2, Some("/items"), None, &retry())
+        .await
+        .unwrap();
+    rows.next().await.unwrap().unwrap();
+    assert_eq!(store.failed_pages("users.sql").await, vec![1]);
+
+    rows.next().await.unwrap().unwrap();
+    assert!(rows.next().await.is_none());
+    assert!(store.failed_pages("users.sql").await.is_empty());
+}
+
+#[tokio::test]
+async fn test_concurrent_page_that_fails_to_write_is_recorded() {
+    let dir = tempfile::tempdir().unwrap();
+    let (store, request) = tracked(&dir).await;
+    let base = serve(|req| {
+        let page = req.num("page").unwrap_or(1);
+        StubResponse::json(json!({"total_pages": 3, "items": [{"id": page}]}))
+    })
+    .await;
+    let fetcher = PaginatedFetcher::new(reqwest::Client::new(), format!("{base}/users"), 2)
+        .with_page_number("page", "per_page")
+        .with_request_options(request);
+
+    let stats = fetcher
+        .fetch_page_number(
+            1,
+            Some("/items"),
+            None,
+            Some(TotalHint::Pages {
+                pointer: "/total_pages".to_string(),
+            }),
+            Arc::new(RefusePage(2)),
+            WriteMode::Append,
+            &retry(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(stats.success_count, 2);
+    assert_eq!(stats.error_count, 1);
+    assert_eq!(store.failed_pages("users.sql").await, vec![2]);
+}
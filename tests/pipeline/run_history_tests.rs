@@ -0,0 +1,216 @@
+// Tests for the run history and `apitap runs diff`
+
+use std::collections::BTreeMap;
+
+use apitap::cmd::{Cli, Command, OutputFormat, RunsCommand};
+use apitap::pipeline::run_history::{
+    diff_runs, find_run, ModuleRun, RunRecord, RunStatus, SchemaChange, RUN_HISTORY_LIMIT,
+};
+use apitap::pipeline::state::{export_state, record_run};
+use chrono::{Duration, TimeZone, Utc};
+use clap::Parser;
+
+fn module(rows: u64, duration_ms: u64, failed_pages: u64, columns: &[(&str, &str)]) -> ModuleRun {
+    let columns = columns
+        .iter()
+        .map(|(c, t)| (c.to_string(), t.to_string()))
+        .collect();
+    ModuleRun {
+        rows,
+        pages: 1,
+        failed_pages,
+        duration_ms,
+        schema: BTreeMap::from([("orders".to_string(), columns)]),
+        ..Default::default()
+    }
+}
+
+fn run(id: &str, minutes: i64, modules: Vec<(&str, ModuleRun)>) -> RunRecord {
+    RunRecord {
+        run_id: id.to_string(),
+        started_at: Utc.with_ymd_and_hms(2026, 10, 1, 0, 0, 0).unwrap()
+            + Duration::minutes(minutes),
+        duration_ms: 1000,
+        status: RunStatus::Succeeded,
+        error: None,
+        modules: modules
+            .into_iter()
+            .map(|(name, m)| (name.to_string(), m))
+            .collect(),
+    }
+}
+
+#[test]
+fn test_diff_flags_regressions() {
+    let a = run(
+        "aaa111",
+        0,
+        vec![
+            (
+                "orders.sql",
+                module(1000, 2000, 0, &[("id", "Int64"), ("total", "Float64")]),
+            ),
+            ("users.sql", module(50, 100, 0, &[])),
+            ("legacy.sql", module(5, 100, 0, &[])),
+        ],
+    );
+    let mut b = run(
+        "bbb222",
+        60,
+        vec![
+            (
+                "orders.sql",
+                module(
+                    400,
+                    9000,
+                    2,
+                    &[("id", "Int64"), ("total", "Utf8"), ("note", "Utf8")],
+                ),
+            ),
+            ("users.sql", module(52, 110, 0, &[])),
+        ],
+    );
+    b.status = RunStatus::Failed;
+    b.error = Some("interrupted".into());
+
+    let diff = diff_runs(&a, &b);
+    assert!(diff.has_regressions());
+    assert_eq!(diff.regressions, vec!["run failed: interrupted"]);
+
+    let names: Vec<&str> = diff.modules.iter().map(|m| m.module.as_str()).collect();
+    assert_eq!(names, vec!["legacy.sql", "orders.sql", "users.sql"]);
+    assert_eq!(diff.modules[0].regressions, vec!["module did not run"]);
+
+    let orders = &diff.modules[1];
+    assert_eq!(orders.regressions.len(), 4, "{:?}", orders.regressions);
+    assert!(orders.regressions[0].starts_with("rows fell 60%"));
+    assert!(orders.regressions[1].starts_with("4.5x slower"));
+    assert_eq!(
+        orders.schema_changes,
+        vec![
+            SchemaChange::Retyped {
+                table: "orders".into(),
+                column: "total".into(),
+                from: "Float64".into(),
+                to: "Utf8".into(),
+            },
+            SchemaChange::Added {
+                table: "orders".into(),
+                column: "note".into(),
+                ty: "Utf8".into(),
+            },
+        ]
+    );
+
+    assert!(diff.modules[2].regressions.is_empty());
+
+    let report = diff.render();
+    assert!(report.contains("1000 -> 400"));
+    assert!(report.contains("~ orders.total (Float64 -> Utf8)"));
+    assert!(report.contains("6 regression(s)"));
+
+    let json = serde_json::to_value(&diff).unwrap();
+    assert_eq!(json["a"]["run_id"], "aaa111");
+    assert_eq!(json["modules"][1]["b"]["rows"], 400);
+    assert_eq!(
+        json["modules"][1]["schema_changes"][0],
+        serde_json::json!({
+            "change": "retyped", "table": "orders", "column": "total",
+            "from": "Float64", "to": "Utf8"
+        })
+    );
+}
+
+#[test]
+fn test_identical_runs_have_no_regressions() {
+    let a = run(
+        "a",
+        0,
+        vec![("orders.sql", module(10, 100, 0, &[("id", "Int64")]))],
+    );
+    let b = run(
+        "b",
+        1,
+        vec![("orders.sql", module(10, 120, 0, &[("id", "Int64")]))],
+    );
+    assert!(!diff_runs(&a, &b).has_regressions());
+}
+
+#[test]
+fn test_find_run_by_id_prefix_and_latest() {
+    let runs = vec![
+        run("abc123", 0, vec![]),
+        run("abd456", 5, vec![]),
+        run("xyz789", 10, vec![]),
+    ];
+    assert_eq!(find_run(&runs, "abd456").unwrap().run_id, "abd456");
+    assert_eq!(find_run(&runs, "xy").unwrap().run_id, "xyz789");
+    assert!(find_run(&runs, "ab").is_err());
+    assert!(find_run(&runs, "nope").is_err());
+    assert_eq!(find_run(&runs, "latest").unwrap().run_id, "xyz789");
+    assert_eq!(find_run(&runs, "latest~2").unwrap().run_id, "abc123");
+    assert!(find_run(&runs, "latest~3").is_err());
+}
+
+#[tokio::test]
+async fn test_runs_recorded_in_both_backends() {
+    let dir = tempfile::tempdir().unwrap();
+    for path in [dir.path().join("state.db"), dir.path().join("state.json")] {
+        for i in 0..(RUN_HISTORY_LIMIT as i64 + 2) {
+            let id = format!("run{i:03}");
+            let modules = vec![("orders.sql", module(i as u64, 10, 0, &[("id", "Int64")]))];
+            record_run(&path, run(&id, i, modules)).await.unwrap();
+        }
+        let runs = export_state(&path).await.unwrap().runs;
+        assert_eq!(runs.len(), RUN_HISTORY_LIMIT, "{path:?}");
+        assert_eq!(runs[0].run_id, "run002");
+        let latest = find_run(&runs, "latest").unwrap();
+        assert_eq!(
+            latest.modules["orders.sql"].rows,
+            RUN_HISTORY_LIMIT as u64 + 1
+        );
+        assert_eq!(latest.modules["orders.sql"].schema["orders"]["id"], "Int64");
+    }
+}
+
+#[test]
+fn test_runs_diff_command_parses() {
+    let cli = Cli::parse_from(["apitap-run", "runs", "diff", "latest~1", "latest"]);
+    match cli.command {
+        Some(Command::Runs {
+            action:
+                RunsCommand::Diff {
+                    run_a,
+                    run_b,
+                    fail_on_regression,
+                    ..
+                },
+        }) => {
+            assert_eq!((run_a.as_str(), run_b.as_str()), ("latest~1", "latest"));
+            assert!(!fail_on_regression);
+        }
+        other => panic!("unexpected command: {other:?}"),
+    }
+}
+
+#[test]
+fn test_runs_diff_takes_json_output() {
+    let cli = Cli::parse_from([
+        "apitap-run",
+        "runs",
+        "diff",
+        "latest~1",
+        "latest",
+        "--output",
+        "json",
+    ]);
+    assert!(matches!(
+        cli.command,
+        Some(Command::Runs {
+            action: RunsCommand::Diff {
+                output: OutputFormat::Json,
+                ..
+            }
+        })
+    ));
+}
@@ -0,0 +1,172 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::http::StubResponse;
+use apitap::errors::ApitapError;
+use apitap::pipeline::sla::{AlertChannel, AlertKind, Sla, SlaWatch};
+use apitap::pipeline::Config;
+
+/// Answers 200 and records each request body.
+async fn serve() -> (String, Arc<Mutex<Vec<String>>>) {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&seen);
+    let base = crate::http::serve(move |req| {
+        log.lock().unwrap().push(req.body_text());
+        StubResponse::ok("ok")
+    })
+    .await;
+    (format!("{base}/hook"), seen)
+}
+
+fn webhook(url: &str) -> AlertChannel {
+    AlertChannel {
+        name: "oncall".into(),
+        kind: AlertKind::Webhook {
+            url: Some(url.into()),
+            url_env: None,
+        },
+    }
+}
+
+fn sla(millis: u64, alert: Option<&str>) -> Sla {
+    Sla {
+        max_duration: Duration::from_millis(millis),
+        alert: alert.map(str::to_string),
+    }
+}
+
+#[tokio::test]
+async fn test_alert_fires_while_module_still_runs() {
+    let (url, seen) = serve().await;
+    let watch = SlaWatch::start(
+        &sla(50, Some("oncall")),
+        &[webhook(&url)],
+        reqwest::Client::new(),
+        "run-1",
+        "orders",
+        Some("data-eng"),
+    )
+    .unwrap();
+
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    {
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 1, "alert sent before the module finished");
+        let body: serde_json::Value = serde_json::from_str(&seen[0]).unwrap();
+        assert_eq!(body["event"], "sla_breached");
+        assert_eq!(body["breach"]["module"], "orders");
+        assert_eq!(body["breach"]["owner"], "data-eng");
+        assert_eq!(body["breach"]["still_running"], true);
+    }
+
+    assert!(watch.finish().await);
+    assert_eq!(seen.lock().unwrap().len(), 1, "no second alert on finish");
+}
+
+#[tokio::test]
+async fn test_fast_module_sends_nothing() {
+    let (url, seen) = serve().await;
+    let watch = SlaWatch::start(
+        &sla(60_000, Some("oncall")),
+        &[webhook(&url)],
+        reqwest::Client::new(),
+        "run-1",
+        "orders",
+        None,
+    )
+    .unwrap();
+
+    assert!(!watch.finish().await);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(seen.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_slack_channel_gets_a_message() {
+    let (url, seen) = serve().await;
+    let slack = AlertChannel {
+        name: "slack".into(),
+        kind: AlertKind::Slack {
+            webhook_url: Some(url),
+            webhook_url_env: None,
+        },
+    };
+    let watch = SlaWatch::start(
+        &sla(10, Some("slack")),
+        &[slack],
+        reqwest::Client::new(),
+        "run-7",
+        "invoices",
+        None,
+    )
+    .unwrap();
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(watch.finish().await);
+    let seen = seen.lock().unwrap();
+    let body: serde_json::Value = serde_json::from_str(&seen[0]).unwrap();
+    let text = body["text"].as_str().unwrap();
+    assert!(text.contains("invoices"), "{text}");
+    assert!(text.contains("run-7"), "{text}");
+}
+
+#[tokio::test]
+async fn test_unknown_alert_channel_is_config_error() {
+    let err = SlaWatch::start(
+        &sla(1000, Some("pager")),
+        &[webhook("http://127.0.0.1:9/hook")],
+        reqwest::Client::new(),
+        "run-1",
+        "orders",
+        None,
+    )
+    .err()
+    .expect("unknown channel rejected");
+    assert!(matches!(err, ApitapError::ConfigError(ref m) if m.contains("'pager'")));
+}
+
+#[tokio::test]
+async fn test_sla_without_alert_only_reports() {
+    let watch = SlaWatch::start(
+        &sla(0, None),
+        &[],
+        reqwest::Client::new(),
+        "run-1",
+        "orders",
+        None,
+    )
+    .unwrap();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(watch.finish().await);
+}
+
+#[test]
+fn test_config_parses_alert_channels() {
+    let config: Config = serde_yaml::from_str(
+        r#"
+sources: []
+targets: []
+alerts:
+  - name: slack
+    kind: slack
+    webhook_url_env: SLACK_WEBHOOK_URL
+  - name: oncall
+    kind: webhook
+    url: https://alerts.example.com/apitap
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(config.alerts.len(), 2);
+    assert_eq!(
+        config.alerts[0].kind,
+        AlertKind::Slack {
+            webhook_url: None,
+            webhook_url_env: Some("SLACK_WEBHOOK_URL".into()),
+        }
+    );
+    assert_eq!(
+        config.alerts[1],
+        webhook("https://alerts.example.com/apitap")
+    );
+}
@@ -0,0 +1,112 @@
+use std::sync::Arc;
+
+use apitap::pipeline::retention::apply_retention;
+use apitap::pipeline::snapshot::{SnapshotConfig, SNAPSHOT_COLUMN};
+use apitap::pipeline::Source;
+use apitap::utils::datafusion_ext::QueryResultStream;
+use apitap::writer::middleware::WriterMiddleware;
+use apitap::writer::sqlite::SqliteWriter;
+use apitap::writer::{DataWriter, WriteMode};
+use chrono::{TimeZone, Utc};
+use datafusion::arrow::array::{Int64Array, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use serde_json::json;
+use sqlx::sqlite::SqlitePoolOptions;
+
+#[test]
+fn test_snapshot_config_from_yaml() {
+    let on: SnapshotConfig = serde_yaml::from_str("true").unwrap();
+    assert_eq!(
+        on,
+        SnapshotConfig {
+            enabled: true,
+            keep: None
+        }
+    );
+    let pruned: SnapshotConfig = serde_yaml::from_str("keep: 30d").unwrap();
+    assert!(pruned.enabled);
+    assert_eq!(pruned.retention().unwrap().column, SNAPSHOT_COLUMN);
+
+    let off: SnapshotConfig = serde_yaml::from_str("{enabled: false, keep: 30d}").unwrap();
+    assert!(off.retention().is_none());
+    assert!(off.stamp(Utc::now()).is_none());
+
+    let source: Source = serde_yaml::from_str(
+        "name: users\nurl: https://api.example.com/users\ndata_path: null\nretry: {max_attempts: 1, max_delay_secs: 1, min_delay_secs: 1}",
+    )
+    .unwrap();
+    assert!(source.snapshot.is_disabled());
+}
+
+#[test]
+fn test_snapshot_stamp_adds_run_timestamp() {
+    let at = Utc.with_ymd_and_hms(2024, 5, 1, 6, 30, 0).unwrap();
+    let stamp = SnapshotConfig {
+        enabled: true,
+        keep: None,
+    }
+    .stamp(at)
+    .unwrap();
+    let batch = RecordBatch::try_new(
+        Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)])),
+        vec![Arc::new(Int64Array::from(vec![1, 2]))],
+    )
+    .unwrap();
+    let stamped = stamp.map_batch("users", batch).unwrap();
+    let column = stamped
+        .column_by_name(SNAPSHOT_COLUMN)
+        .unwrap()
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert_eq!(column.value(0), "2024-05-01T06:30:00.000000Z");
+    assert_eq!(column.value(1), column.value(0));
+}
+
+#[tokio::test]
+async fn test_snapshot_keep_prunes_old_snapshots() {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    let writer: Arc<dyn DataWriter> = Arc::new(SqliteWriter::new(pool.clone(), "users"));
+    let snapshot = SnapshotConfig {
+        enabled: true,
+        keep: Some("30d".into()),
+    };
+    let ts = |day| {
+        Utc.with_ymd_and_hms(2024, 4, day, 0, 0, 0)
+            .unwrap()
+            .to_rfc3339_opts(chrono::SecondsFormat::Micros, true)
+    };
+    let rows = vec![
+        json!({"id": 1, SNAPSHOT_COLUMN: ts(1)}),
+        json!({"id": 1, SNAPSHOT_COLUMN: ts(20)}),
+        json!({"id": 1, SNAPSHOT_COLUMN: ts(30)}),
+    ];
+    writer
+        .write_stream(
+            QueryResultStream {
+                table_name: "users".to_string(),
+                data: Box::pin(futures::stream::iter(rows.into_iter().map(Ok))),
+            },
+            WriteMode::Append,
+        )
+        .await
+        .unwrap();
+
+    let policy = snapshot.retention().unwrap();
+    let now = Utc.with_ymd_and_hms(2024, 5, 15, 0, 0, 0).unwrap();
+    let tables = vec![("users".to_string(), Arc::clone(&writer))];
+    apply_retention(&policy, policy.cutoff(now).unwrap(), &tables)
+        .await
+        .unwrap();
+
+    let left: Vec<(String,)> = sqlx::query_as(r#"SELECT "snapshot_ts" FROM "users" ORDER BY 1"#)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(left, vec![(ts(20),), (ts(30),)]);
+}
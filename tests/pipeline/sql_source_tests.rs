@@ -0,0 +1,93 @@
+use apitap::http::fetcher::DataFusionPageWriter;
+use apitap::pipeline::sql_source::{postgres_json_query, run_sql_fetch, SqlDialect, SqlSource};
+use apitap::pipeline::Config;
+use apitap::writer::WriteMode;
+
+#[test]
+fn test_sql_source_from_yaml() {
+    let config_yaml = r#"
+sources:
+  - name: legacy_orders
+    table_destination_name: orders
+    sql:
+      url_env: LEGACY_DB_URL
+      query: SELECT id, total FROM orders
+    retry:
+      max_attempts: 3
+      max_delay_secs: 60
+      min_delay_secs: 1
+targets: []
+"#;
+
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+    let source = config.source("legacy_orders").unwrap();
+
+    assert!(source.url.is_empty());
+    let sql = source.sql.as_ref().unwrap();
+    assert_eq!(sql.url_env.as_deref(), Some("LEGACY_DB_URL"));
+    assert_eq!(sql.query, "SELECT id, total FROM orders");
+    assert_eq!(sql.batch_rows, 10_000);
+}
+
+#[test]
+fn test_sql_dialect_from_url() {
+    assert_eq!(
+        SqlDialect::from_url("postgres://u:p@db/app").unwrap(),
+        SqlDialect::Postgres
+    );
+    assert_eq!(
+        SqlDialect::from_url("postgresql://db/app").unwrap(),
+        SqlDialect::Postgres
+    );
+    assert_eq!(
+        SqlDialect::from_url("mysql://u:p@db:3306/app").unwrap(),
+        SqlDialect::MySql
+    );
+    assert!(SqlDialect::from_url("sqlite://app.db").is_err());
+    assert!(SqlDialect::from_url("db.internal").is_err());
+}
+
+#[test]
+fn test_sql_source_url_from_env() {
+    std::env::set_var("APITAP_TEST_SQL_SOURCE_URL", "mysql://reader@db/app");
+    let source = SqlSource {
+        url: Some("postgres://ignored".to_string()),
+        url_env: Some("APITAP_TEST_SQL_SOURCE_URL".to_string()),
+        query: "SELECT 1".to_string(),
+        batch_rows: 100,
+    };
+    assert_eq!(source.connection_url().unwrap(), "mysql://reader@db/app");
+
+    let missing = SqlSource {
+        url: None,
+        url_env: None,
+        ..source
+    };
+    assert!(missing.connection_url().is_err());
+}
+
+#[test]
+fn test_postgres_json_query() {
+    assert_eq!(
+        postgres_json_query("  SELECT id FROM orders WHERE total > 10; "),
+        "SELECT to_jsonb(q) FROM (SELECT id FROM orders WHERE total > 10) AS q"
+    );
+}
+
+#[tokio::test]
+async fn test_run_sql_fetch_rejects_empty_batches() {
+    let source = SqlSource {
+        url: Some("postgres://localhost/app".to_string()),
+        url_env: None,
+        query: "SELECT 1".to_string(),
+        batch_rows: 0,
+    };
+    let err = run_sql_fetch(
+        &source,
+        DataFusionPageWriter::routed("orders"),
+        WriteMode::Append,
+    )
+    .await
+    .unwrap_err();
+    assert!(err.to_string().contains("batch_rows"));
+}
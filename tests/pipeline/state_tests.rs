@@ -0,0 +1,164 @@
+// Tests for the state store backends and export/import
+
+use apitap::cmd::{Cli, Command, StateCommand};
+use apitap::pipeline::retry_state::RetryStateStore;
+use apitap::pipeline::state::{
+    export_state, import_state, StateSnapshot, StoredRefreshToken, STATE_FORMAT_VERSION,
+};
+use clap::Parser;
+
+#[tokio::test]
+async fn test_sqlite_store_persists_across_reopen() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("nested").join("state.db");
+
+    let store = RetryStateStore::open(&path).await.unwrap();
+    store
+        .record_failure("users.sql", 4, "timeout")
+        .await
+        .unwrap();
+    store.record_failure("users.sql", 2, "503").await.unwrap();
+    store.clear("users.sql", 4).await.unwrap();
+    drop(store);
+
+    let reopened = RetryStateStore::open(&path).await.unwrap();
+    assert_eq!(reopened.failed_pages("users.sql").await, vec![2]);
+}
+
+#[tokio::test]
+async fn test_refresh_tokens_survive_reopen_and_export() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("state.db");
+    let token = StoredRefreshToken {
+        token: "rt-2".into(),
+        origin: "abc".into(),
+        rotated_at: chrono::Utc::now(),
+    };
+
+    let store = RetryStateStore::open(&db).await.unwrap();
+    store
+        .save_refresh_token("xero", token.clone())
+        .await
+        .unwrap();
+    drop(store);
+
+    let reopened = RetryStateStore::open(&db).await.unwrap();
+    assert_eq!(reopened.refresh_token("xero").await, Some(token.clone()));
+    assert_eq!(reopened.refresh_token("google").await, None);
+    drop(reopened);
+
+    let json = dir.path().join("state.json");
+    import_state(&json, export_state(&db).await.unwrap(), false)
+        .await
+        .unwrap();
+    let copy = RetryStateStore::open(&json).await.unwrap();
+    assert_eq!(copy.refresh_token("xero").await, Some(token));
+}
+
+#[tokio::test]
+async fn test_export_import_between_backends() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("state.db");
+    let json = dir.path().join("state.json");
+
+    let store = RetryStateStore::open(&db).await.unwrap();
+    store.record_failure("orders.sql", 9, "502").await.unwrap();
+    drop(store);
+
+    let snapshot = export_state(&db).await.unwrap();
+    assert_eq!(snapshot.version, STATE_FORMAT_VERSION);
+    let bytes = snapshot.to_json().unwrap();
+
+    // Import the exported document into a fresh JSON-backed store and back out again.
+    import_state(&json, StateSnapshot::from_json(&bytes).unwrap(), false)
+        .await
+        .unwrap();
+    assert_eq!(export_state(&json).await.unwrap(), snapshot);
+
+    let copy = RetryStateStore::open(&json).await.unwrap();
+    assert_eq!(copy.failed_pages("orders.sql").await, vec![9]);
+}
+
+#[tokio::test]
+async fn test_import_replaces_or_merges() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("state.db");
+    let store = RetryStateStore::open(&db).await.unwrap();
+    store.record_failure("a.sql", 1, "x").await.unwrap();
+    drop(store);
+
+    let other = dir.path().join("other.db");
+    let store = RetryStateStore::open(&other).await.unwrap();
+    store.record_failure("b.sql", 5, "y").await.unwrap();
+    drop(store);
+    let incoming = export_state(&other).await.unwrap();
+
+    import_state(&db, incoming.clone(), true).await.unwrap();
+    let merged = export_state(&db).await.unwrap();
+    assert_eq!(
+        merged.retry.modules.keys().collect::<Vec<_>>(),
+        vec!["a.sql", "b.sql"]
+    );
+
+    import_state(&db, incoming, false).await.unwrap();
+    let replaced = export_state(&db).await.unwrap();
+    assert_eq!(
+        replaced.retry.modules.keys().collect::<Vec<_>>(),
+        vec!["b.sql"]
+    );
+}
+
+#[test]
+fn test_snapshot_accepts_legacy_retry_file() {
+    let legacy =
+        br#"{"modules":{"users.sql":{"3":{"error":"503","failed_at":"2024-01-01T00:00:00Z"}}}}"#;
+    let snapshot = StateSnapshot::from_json(legacy).unwrap();
+    assert_eq!(snapshot.version, STATE_FORMAT_VERSION);
+    assert!(snapshot.retry.modules["users.sql"].contains_key(&3));
+
+    let future = br#"{"version":99,"retry":{"modules":{}}}"#;
+    assert!(StateSnapshot::from_json(future).is_err());
+}
+
+#[test]
+fn test_state_subcommands_parse() {
+    let cli = Cli::try_parse_from(["apitap", "state", "export", "-o", "backup.json"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::State {
+            action: StateCommand::Export { file: Some(ref f) }
+        }) if f == "backup.json"
+    ));
+    let cli = Cli::try_parse_from(["apitap", "state", "export", "--file", "backup.json"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::State {
+            action: StateCommand::Export { file: Some(_) }
+        })
+    ));
+    assert!(Cli::try_parse_from(["apitap", "state", "export", "--output", "backup.json"]).is_err());
+    assert_eq!(cli.state, ".apitap/state.db");
+
+    let cli = Cli::try_parse_from([
+        "apitap",
+        "state",
+        "import",
+        "backup.json",
+        "--merge",
+        "--state",
+        "ci/state.db",
+    ])
+    .unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::State {
+            action: StateCommand::Import { merge: true, .. }
+        })
+    ));
+    assert_eq!(cli.state, "ci/state.db");
+
+    // Old flag name still works.
+    let cli = Cli::try_parse_from(["apitap", "--retry-state", "retry.json"]).unwrap();
+    assert!(cli.command.is_none());
+    assert_eq!(cli.state, "retry.json");
+}
@@ -0,0 +1,518 @@
+// Tests for incremental loads (incremental: / watermarks)
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use crate::http::{retry, serve, StubResponse};
+use apitap::cmd::{run_pipeline_with, RunOptions};
+use apitap::errors::Result;
+use apitap::http::fetcher::{DataFusionPageWriter, Pagination, RequestOptions};
+use apitap::http::paginator::Paginators;
+use apitap::pipeline::lookback::Lookback;
+use apitap::pipeline::mock::{run_mock_fetch, MockSource};
+use apitap::pipeline::query_template::QueryTemplate;
+use apitap::pipeline::retry_state::{RetryStateStore, RetryTracker};
+use apitap::pipeline::run::{run_fetch, FetchOpts};
+use apitap::pipeline::state::{export_state, import_state};
+use apitap::pipeline::watermark::{Incremental, Watermark, WatermarkTracker};
+use apitap::pipeline::{Config, QueryParam};
+use apitap::utils::datafusion_ext::{QueryResult, QueryResultStream};
+use apitap::writer::{DataWriter, WriteMode};
+use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use futures::StreamExt;
+use serde_json::json;
+
+#[derive(Default)]
+struct CountRows {
+    rows: Mutex<usize>,
+}
+
+#[async_trait]
+impl DataWriter for CountRows {
+    async fn write(&self, _result: QueryResult) -> Result<()> {
+        Ok(())
+    }
+
+    async fn write_stream(&self, mut result: QueryResultStream, _mode: WriteMode) -> Result<()> {
+        while let Some(row) = result.data.next().await {
+            row?;
+            *self.rows.lock().unwrap() += 1;
+        }
+        Ok(())
+    }
+}
+
+fn incremental() -> Incremental {
+    Incremental {
+        cursor: "updated_at".into(),
+        param: Some("updated_since".into()),
+        initial: Some("2024-01-01".into()),
+        lookback: None,
+    }
+}
+
+fn mark(value: &str) -> Watermark {
+    Watermark {
+        cursor: "updated_at".into(),
+        value: value.into(),
+        saved_at: Utc::now(),
+    }
+}
+
+#[test]
+fn test_incremental_from_yaml() {
+    let config: Config = serde_yaml::from_str(
+        r#"
+sources:
+  - name: orders
+    url: https://api.example.com/orders
+    table_destination_name: orders
+    incremental:
+      cursor: /meta/updated_at
+      param: updated_since
+      lookback: 2d
+    retry:
+      max_attempts: 3
+      max_delay_secs: 60
+      min_delay_secs: 1
+targets: []
+"#,
+    )
+    .unwrap();
+    let incremental = config.sources[0].incremental.as_ref().unwrap();
+    assert_eq!(incremental.cursor, "/meta/updated_at");
+    assert_eq!(incremental.param.as_deref(), Some("updated_since"));
+    assert_eq!(incremental.initial, None);
+    assert_eq!(incremental.lookback, Some(Lookback::parse("2d").unwrap()));
+}
+
+#[test]
+fn test_tracker_keeps_latest_timestamp() {
+    let tracker = WatermarkTracker::new("updated_at", None);
+    tracker.observe_all(&[
+        json!({"updated_at": "2024-05-01T10:00:00Z"}),
+        // Later as an instant, though earlier as text.
+        json!({"updated_at": "2024-05-01T09:30:00-02:00"}),
+        json!({"updated_at": "2024-04-30T23:59:59Z"}),
+        json!({"updated_at": null}),
+        json!({"id": 4}),
+    ]);
+    assert_eq!(
+        tracker.value().as_deref(),
+        Some("2024-05-01T09:30:00-02:00")
+    );
+}
+
+#[test]
+fn test_tracker_numbers_pointer_and_start() {
+    let tracker = WatermarkTracker::new("/meta/seq", Some("10"));
+    tracker.observe_all(&[
+        json!({"meta": {"seq": 9}}),
+        json!({"meta": {"seq": 100}}),
+        json!({"meta": {"seq": 42}}),
+    ]);
+    assert_eq!(tracker.value().as_deref(), Some("100"));
+
+    // Nothing newer than where it started: the watermark stays put.
+    let tracker = WatermarkTracker::new("updated_at", Some("2024-05-01"));
+    tracker.observe(&json!({"updated_at": "2024-04-01"}));
+    let watermark = tracker.watermark().unwrap();
+    assert_eq!(watermark.cursor, "updated_at");
+    assert_eq!(watermark.value, "2024-05-01");
+}
+
+#[test]
+fn test_since_uses_stored_then_initial() {
+    let inc = incremental();
+    assert_eq!(inc.since(None).unwrap().as_deref(), Some("2024-01-01"));
+    assert_eq!(
+        inc.since(Some(&mark("2024-05-01T10:00:00Z")))
+            .unwrap()
+            .as_deref(),
+        Some("2024-05-01T10:00:00Z")
+    );
+    let first_run = Incremental {
+        initial: None,
+        ..incremental()
+    };
+    assert_eq!(first_run.since(None).unwrap(), None);
+}
+
+#[test]
+fn test_since_moves_back_by_lookback() {
+    let inc = Incremental {
+        lookback: Some(Lookback::parse("2d").unwrap()),
+        ..incremental()
+    };
+    assert_eq!(inc.since(None).unwrap().as_deref(), Some("2023-12-30"));
+    assert_eq!(
+        inc.since(Some(&mark("2024-05-03T10:00:00Z")))
+            .unwrap()
+            .as_deref(),
+        Some("2024-05-01T10:00:00Z")
+    );
+    assert!(inc.since(Some(&mark("1234"))).is_err());
+}
+
+#[test]
+fn test_stored_watermark_of_another_cursor_is_ignored() {
+    let inc = incremental();
+    let other = Watermark {
+        cursor: "id".into(),
+        ..mark("99")
+    };
+    assert_eq!(inc.stored("orders.sql", Some(&other)), None);
+    let same = mark("2024-05-01");
+    assert_eq!(inc.stored("orders.sql", Some(&same)), Some(&same));
+}
+
+#[test]
+fn test_watermark_in_query_templates() {
+    let started_at = Utc.with_ymd_and_hms(2024, 5, 1, 6, 0, 0).unwrap();
+    let template = QueryTemplate::new(started_at, BTreeMap::new());
+    let params = vec![QueryParam {
+        key: "filter".into(),
+        value: "{% if watermark %}updated_at>{{ watermark }}{% else %}all{% endif %}".into(),
+    }];
+
+    let rendered = template
+        .render_with_watermark("orders", &params, Some("2024-04-30"))
+        .unwrap();
+    assert_eq!(rendered[0].value, "updated_at>2024-04-30".into());
+    let rendered = template
+        .render_with_watermark("orders", &params, None)
+        .unwrap();
+    assert_eq!(rendered[0].value, "all".into());
+
+    let query = template
+        .render_query(
+            "legacy",
+            "SELECT * FROM orders WHERE updated_at > '{{ watermark }}'",
+            Some("2024-04-30"),
+        )
+        .unwrap();
+    assert_eq!(
+        query,
+        "SELECT * FROM orders WHERE updated_at > '2024-04-30'"
+    );
+    let err = template
+        .render_query("legacy", "SELECT {{ nope( }}", None)
+        .unwrap_err();
+    assert!(err.to_string().contains("legacy: sql.query"));
+}
+
+#[tokio::test]
+async fn test_page_writer_tracks_raw_rows() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("orders.json");
+    std::fs::write(
+        &path,
+        r#"[{"id": 1, "updated_at": "2024-05-01"}, {"id": 2, "updated_at": "2024-05-03"}, {"id": 3, "updated_at": "2024-05-02"}]"#,
+    )
+    .unwrap();
+    let mock = MockSource {
+        file: path.to_string_lossy().into_owned(),
+        pages: 2,
+    };
+    let sink = Arc::new(CountRows::default());
+    let tracker = Arc::new(WatermarkTracker::new("updated_at", None));
+    // The SQL drops the cursor; the watermark is still taken from the rows fetched.
+    let writer = DataFusionPageWriter::new("orders", "SELECT id FROM orders", sink.clone())
+        .with_watermark(Arc::clone(&tracker));
+
+    run_mock_fetch(&mock, None, writer, WriteMode::Append)
+        .await
+        .unwrap();
+
+    assert_eq!(*sink.rows.lock().unwrap(), 3);
+    assert_eq!(tracker.value().as_deref(), Some("2024-05-03"));
+}
+
+#[tokio::test]
+async fn test_watermarks_survive_reopen_and_export() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("state.db");
+
+    let store = RetryStateStore::open(&db).await.unwrap();
+    assert_eq!(store.watermark("orders.sql").await, None);
+    store
+        .save_watermark("orders.sql", mark("2024-05-01"))
+        .await
+        .unwrap();
+    store
+        .save_watermark("orders.sql", mark("2024-05-03"))
+        .await
+        .unwrap();
+    drop(store);
+
+    let reopened = RetryStateStore::open(&db).await.unwrap();
+    let stored = reopened.watermark("orders.sql").await.unwrap();
+    assert_eq!(stored.value, "2024-05-03");
+    drop(reopened);
+
+    let json = dir.path().join("state.json");
+    import_state(&json, export_state(&db).await.unwrap(), false)
+        .await
+        .unwrap();
+    let copy = RetryStateStore::open(&json).await.unwrap();
+    assert_eq!(copy.watermark("orders.sql").await, Some(stored));
+}
+
+/// Fetch a two-page `page_number` source with `params`; returns the
+/// request targets and the rows written.
+async fn fetch_page_number_with(
+    params: Vec<QueryParam>,
+    request: RequestOptions,
+) -> (Vec<String>, usize) {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&seen);
+    let base = serve(move |req| {
+        log.lock().unwrap().push(req.target.clone());
+        let page = req.num("page").unwrap_or(1);
+        let items: Vec<_> = if page <= 2 {
+            (0..2)
+                .map(|i| json!({"id": page * 10 + i, "updated_at": "2024-05-02"}))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        StubResponse::json(json!({ "items": items }))
+    })
+    .await;
+
+    let pagination: Pagination =
+        serde_yaml::from_str("kind: page_number\npage_param: page\nper_page_param: per_page")
+            .unwrap();
+    let sink = Arc::new(CountRows::default());
+    let writer = DataFusionPageWriter::new("orders", "SELECT id FROM orders", sink.clone());
+    let opts = FetchOpts {
+        concurrency: 1,
+        default_page_size: 2,
+        fetch_batch_size: 100,
+        flush_interval: None,
+    };
+    run_fetch(
+        reqwest::Client::new(),
+        reqwest::Url::parse(&format!("{base}/orders")).unwrap(),
+        Some("/items".into()),
+        Some(params),
+        &Some(pagination),
+        None,
+        writer,
+        WriteMode::Append,
+        &opts,
+        &retry(),
+        request,
+        &Paginators::default(),
+    )
+    .await
+    .unwrap();
+
+    let rows = *sink.rows.lock().unwrap();
+    let seen = seen.lock().unwrap().clone();
+    (seen, rows)
+}
+
+#[tokio::test]
+async fn test_page_number_source_sends_the_watermark_param() {
+    let inc = incremental();
+    let since = inc.since(Some(&mark("2024-05-01"))).unwrap().unwrap();
+    let params = vec![QueryParam {
+        key: inc.param.clone().unwrap(),
+        value: since.as_str().into(),
+    }];
+
+    let (seen, rows) = fetch_page_number_with(params, RequestOptions::default()).await;
+    assert_eq!(rows, 4);
+    assert_eq!(seen.len(), 3);
+    for target in seen {
+        assert!(target.contains("updated_since=2024-05-01"), "{target}");
+    }
+}
+
+#[tokio::test]
+async fn test_page_number_source_sends_rendered_query_params() {
+    let started_at = Utc.with_ymd_and_hms(2024, 5, 1, 6, 0, 0).unwrap();
+    let template = QueryTemplate::new(started_at, BTreeMap::new());
+    let params: Vec<QueryParam> = serde_yaml::from_str(
+        r#"
+- key: since
+  value: "{{ watermark }}"
+- key: day
+  value: "{{ today() }}"
+- key: status
+  value: [open, pending]
+"#,
+    )
+    .unwrap();
+    let rendered = template
+        .render_with_watermark("orders", &params, Some("2024-04-30"))
+        .unwrap();
+
+    let (seen, rows) = fetch_page_number_with(rendered, RequestOptions::default()).await;
+    assert_eq!(rows, 4);
+    assert_eq!(seen.len(), 3);
+    for target in seen {
+        assert!(
+            target.contains("since=2024-04-30&day=2024-05-01&status=open&status=pending"),
+            "{target}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_resumed_page_number_pages_keep_the_watermark_param() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = Arc::new(
+        RetryStateStore::open(dir.path().join("state.db"))
+            .await
+            .unwrap(),
+    );
+    store.record_failure("orders.sql", 2, "boom").await.unwrap();
+    let request = RequestOptions {
+        failures: Some(RetryTracker::new(Arc::clone(&store), "orders.sql", true)),
+        ..Default::default()
+    };
+    let params = vec![QueryParam {
+        key: "updated_since".into(),
+        value: "2024-05-01".into(),
+    }];
+
+    let (seen, rows) = fetch_page_number_with(params, request).await;
+    // Page 2 again from the last run, then pages 1..=3.
+    assert_eq!(rows, 6);
+    assert_eq!(seen.len(), 4);
+    assert!(seen[0].contains("page=2"), "{}", seen[0]);
+    for target in seen {
+        assert!(target.contains("updated_since=2024-05-01"), "{target}");
+    }
+}
+
+/// Orders by `updated_at`, answering `updated_since` with the rows changed
+/// at or after it.
+async fn serve_orders(orders: Arc<Mutex<Vec<serde_json::Value>>>) -> String {
+    let base = serve(move |req| {
+        let url = reqwest::Url::parse(&format!("http://stub{}", req.target)).unwrap();
+        let since = url
+            .query_pairs()
+            .find(|(key, _)| key == "updated_since")
+            .map(|(_, value)| value.into_owned())
+            .unwrap_or_default();
+        let data: Vec<_> = orders
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|row| row["updated_at"].as_str().unwrap() >= since.as_str())
+            .cloned()
+            .collect();
+        StubResponse::json(json!({ "data": data }))
+    })
+    .await;
+    format!("{base}/orders")
+}
+
+/// A project loading `url` into a SQLite table through an incremental
+/// source with a 2 day lookback; `key` is its `primary_key_in_dest`.
+fn lookback_project(dir: &std::path::Path, url: &str, key: Option<&str>) -> (String, String) {
+    let modules = dir.join("modules");
+    std::fs::create_dir_all(&modules).unwrap();
+    std::fs::write(
+        modules.join("orders.sql"),
+        "{{ sink(name=\"db\") }}\nselect id, status, updated_at from {{ use_source(\"orders\") }}",
+    )
+    .unwrap();
+    let key = key
+        .map(|key| format!("    primary_key_in_dest: {key}\n"))
+        .unwrap_or_default();
+    let config = format!(
+        r#"sources:
+  - name: orders
+    url: {url}
+    table_destination_name: orders
+    data_path: /data
+    pagination:
+      kind: next_url
+      next_path: /next
+{key}    incremental:
+      cursor: updated_at
+      param: updated_since
+      lookback: 2d
+    retry:
+      max_attempts: 0
+      max_delay_secs: 1
+      min_delay_secs: 0
+targets:
+  - name: db
+    type: sqlite
+    path: {db}
+"#,
+        db = dir.join("orders.db").display(),
+    );
+    let config_path = dir.join("pipelines.yaml");
+    std::fs::write(&config_path, config).unwrap();
+    (
+        modules.display().to_string(),
+        config_path.display().to_string(),
+    )
+}
+
+#[tokio::test]
+async fn test_lookback_window_merges_without_duplicates() {
+    let dir = tempfile::tempdir().unwrap();
+    let orders = Arc::new(Mutex::new(vec![
+        json!({"id": 1, "status": "open", "updated_at": "2024-05-01T00:00:00Z"}),
+        json!({"id": 2, "status": "open", "updated_at": "2024-05-02T00:00:00Z"}),
+        json!({"id": 3, "status": "open", "updated_at": "2024-05-03T00:00:00Z"}),
+    ]));
+    let url = serve_orders(Arc::clone(&orders)).await;
+    let (modules, config) = lookback_project(dir.path(), &url, Some("id"));
+    let run = RunOptions {
+        state_path: Some(dir.path().join("state.json").display().to_string()),
+        ..Default::default()
+    };
+
+    run_pipeline_with(&modules, &config, &run).await.unwrap();
+    {
+        let mut orders = orders.lock().unwrap();
+        // Changed behind the watermark, and a new order after it.
+        orders[1]["status"] = json!("shipped");
+        orders.push(json!({"id": 4, "status": "open", "updated_at": "2024-05-04T00:00:00Z"}));
+    }
+    run_pipeline_with(&modules, &config, &run).await.unwrap();
+
+    let pool = sqlx::SqlitePool::connect(&format!(
+        "sqlite://{}",
+        dir.path().join("orders.db").display()
+    ))
+    .await
+    .unwrap();
+    let rows: Vec<(i64, String)> = sqlx::query_as("SELECT id, status FROM orders ORDER BY id")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        rows,
+        vec![
+            (1, "open".to_string()),
+            (2, "shipped".to_string()),
+            (3, "open".to_string()),
+            (4, "open".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_lookback_without_a_merge_key_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let url = serve_orders(Arc::new(Mutex::new(Vec::new()))).await;
+    let (modules, config) = lookback_project(dir.path(), &url, None);
+    let run = RunOptions {
+        state_path: Some(dir.path().join("state.json").display().to_string()),
+        ..Default::default()
+    };
+    let err = run_pipeline_with(&modules, &config, &run)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("primary key"), "{err}");
+}
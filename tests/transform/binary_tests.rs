@@ -0,0 +1,77 @@
+use apitap::transform::binary::{decode_base64, encode_base64};
+use apitap::transform::{BinaryFields, BinaryFieldsConfig};
+use serde_json::json;
+
+fn cfg(fields: &[&str]) -> BinaryFieldsConfig {
+    serde_yaml::from_str(&format!("fields: [{}]", fields.join(", "))).unwrap()
+}
+
+#[test]
+fn test_decode_base64_variants() {
+    assert_eq!(decode_base64("aGVsbG8=").as_deref(), Some(&b"hello"[..]));
+    assert_eq!(decode_base64("aGVsbG8").as_deref(), Some(&b"hello"[..]));
+    assert_eq!(
+        decode_base64("data:text/plain;base64,aGVs\nbG8=").as_deref(),
+        Some(&b"hello"[..])
+    );
+    // URL-safe alphabet
+    assert_eq!(decode_base64("-_8=").as_deref(), Some(&[0xfb, 0xff][..]));
+    assert_eq!(decode_base64("not base64!"), None);
+    assert_eq!(decode_base64("data:text/plain,hello"), None);
+    assert_eq!(encode_base64(b"hello"), "aGVsbG8=");
+}
+
+#[test]
+fn test_config_defaults_and_columns() {
+    let c = cfg(&["file", "/doc/content"]);
+    assert_eq!(c.max_inline_bytes, 1024 * 1024);
+    assert_eq!(c.offload, None);
+    assert_eq!(BinaryFields::columns(&c), vec!["file".to_string()]);
+}
+
+#[tokio::test]
+async fn test_inline_values_canonicalized() {
+    let t = BinaryFields::from_config(&cfg(&["file"])).unwrap();
+    let mut rows = vec![
+        json!({"id": 1, "file": "data:image/png;base64,aGVsbG8"}),
+        json!({"id": 2, "file": "plain text"}),
+        json!({"id": 3, "file": null}),
+    ];
+    t.apply_page(&mut rows).await.unwrap();
+    assert_eq!(rows[0], json!({"id": 1, "file": "aGVsbG8="}));
+    assert_eq!(rows[1]["file"], "plain text");
+    assert_eq!(rows[2], json!({"id": 3, "file": null}));
+}
+
+#[tokio::test]
+async fn test_large_values_offloaded() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut c = cfg(&["file", "/doc/content"]);
+    c.offload = Some(dir.path().to_string_lossy().into_owned());
+    c.max_inline_bytes = 4;
+    c.url_prefix = Some("https://cdn.example.com/blobs/".to_string());
+    let t = BinaryFields::from_config(&c).unwrap();
+
+    let big = encode_base64(b"hello world");
+    let mut rows = vec![
+        json!({"id": 1, "file": big, "doc": {"content": "aGk="}}),
+        json!({"id": 2, "file": big}),
+    ];
+    t.apply_page(&mut rows).await.unwrap();
+
+    let url = rows[0]["file_url"].as_str().unwrap().to_string();
+    assert!(url.starts_with("https://cdn.example.com/blobs/"));
+    assert_eq!(rows[0]["file"], json!(null));
+    // Small payloads stay inline, next to a null URL.
+    assert_eq!(
+        rows[0]["doc"],
+        json!({"content": "aGk=", "content_url": null})
+    );
+    // Same content, same object.
+    assert_eq!(rows[1]["file_url"], json!(url));
+
+    let key = url.rsplit('/').next().unwrap();
+    let stored = std::fs::read(dir.path().join(key)).unwrap();
+    assert_eq!(stored, b"hello world");
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+}
@@ -0,0 +1,36 @@
+use apitap::transform::{ParseJsonFields, RowTransform, TransformChain};
+use serde_json::json;
+
+#[test]
+fn test_parse_json_fields_top_level() {
+    let t = ParseJsonFields::new(vec!["payload".to_string()]);
+    let mut row = json!({"id": 1, "payload": "{\"a\": 1, \"tags\": [\"x\"]}"});
+    t.apply(&mut row).unwrap();
+    assert_eq!(row["payload"], json!({"a": 1, "tags": ["x"]}));
+    assert_eq!(row["id"], json!(1));
+}
+
+#[test]
+fn test_parse_json_fields_pointer() {
+    let t = ParseJsonFields::new(vec!["/meta/raw".to_string()]);
+    let mut row = json!({"meta": {"raw": "[1,2,3]"}});
+    t.apply(&mut row).unwrap();
+    assert_eq!(row["meta"]["raw"], json!([1, 2, 3]));
+}
+
+#[test]
+fn test_parse_json_fields_leaves_invalid_and_non_string() {
+    let t = ParseJsonFields::new(vec!["a".into(), "b".into(), "missing".into()]);
+    let mut row = json!({"a": "not json {", "b": {"already": true}});
+    t.apply(&mut row).unwrap();
+    assert_eq!(row, json!({"a": "not json {", "b": {"already": true}}));
+}
+
+#[test]
+fn test_transform_chain_applies_steps() {
+    let chain = TransformChain::new().with(ParseJsonFields::new(vec!["p".into()]));
+    assert_eq!(chain.len(), 1);
+    let out = chain.apply(json!({"p": "{\"k\":\"v\"}"})).unwrap();
+    assert_eq!(out, json!({"p": {"k": "v"}}));
+    assert!(TransformChain::new().is_empty());
+}
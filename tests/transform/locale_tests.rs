@@ -0,0 +1,86 @@
+use apitap::transform::locale::LocaleFormat;
+use apitap::transform::{LocaleParsing, ParseLocale, RowTransform};
+use serde_json::json;
+
+#[test]
+fn test_german_numbers_and_dates() {
+    let de = LocaleFormat::parse("de-DE").unwrap();
+    assert_eq!(de.parse_number("1.234,56"), Some(json!(1234.56)));
+    assert_eq!(de.parse_number("-1.234"), Some(json!(-1234)));
+    assert_eq!(de.parse_number("0,5"), Some(json!(0.5)));
+    assert_eq!(de.parse_number("12.34"), None);
+    assert_eq!(de.parse_number("01234"), None);
+    assert_eq!(de.parse_number("1,2,3"), None);
+    assert_eq!(de.parse_date("31.12.2024").as_deref(), Some("2024-12-31"));
+    assert_eq!(
+        de.parse_date("1.2.2024 14:30").as_deref(),
+        Some("2024-02-01T14:30:00")
+    );
+    assert_eq!(de.parse_date("31.12.24"), None);
+    assert_eq!(de.parse_date("32.12.2024"), None);
+}
+
+#[test]
+fn test_locale_lookup() {
+    let us = LocaleFormat::parse("en-US").unwrap();
+    assert_eq!(us.parse_number("1,234.5"), Some(json!(1234.5)));
+    assert_eq!(us.parse_date("12/31/2024").as_deref(), Some("2024-12-31"));
+
+    // Falls back to the language; `_` is accepted as separator.
+    let gb = LocaleFormat::parse("en_GB").unwrap();
+    assert_eq!(gb.parse_date("31/12/2024").as_deref(), Some("2024-12-31"));
+    let at = LocaleFormat::parse("de-AT").unwrap();
+    assert_eq!(at.parse_number("1.000"), Some(json!(1000)));
+
+    let fr = LocaleFormat::parse("fr-FR").unwrap();
+    assert_eq!(fr.parse_number("1\u{202f}234,5"), Some(json!(1234.5)));
+    let ch = LocaleFormat::parse("de-CH").unwrap();
+    assert_eq!(ch.parse_number("1'234.50"), Some(json!(1234.5)));
+
+    assert!(LocaleFormat::parse("xx-YY").is_err());
+}
+
+#[test]
+fn test_locale_config_forms() {
+    let cfg: LocaleParsing = serde_yaml::from_str("de-DE").unwrap();
+    assert_eq!(cfg.default.as_deref(), Some("de-DE"));
+    assert!(cfg.fields.is_empty());
+
+    let cfg: LocaleParsing =
+        serde_yaml::from_str("fields:\n  price: de-DE\n  /order/placed: en-US\n").unwrap();
+    assert_eq!(cfg.default, None);
+    assert_eq!(cfg.fields.len(), 2);
+}
+
+#[test]
+fn test_parse_locale_per_source_and_field() {
+    let cfg: LocaleParsing =
+        serde_yaml::from_str("default: de-DE\nfields:\n  /order/placed: en-US\n").unwrap();
+    let t = ParseLocale::from_config(&cfg).unwrap();
+    let mut row = json!({
+        "price": "1.234,56",
+        "shipped": "31.12.2024",
+        "zip": "01067",
+        "name": "Müller",
+        "qty": 3,
+        "order": {"placed": "12/30/2024"}
+    });
+    t.apply(&mut row).unwrap();
+    assert_eq!(
+        row,
+        json!({
+            "price": 1234.56,
+            "shipped": "2024-12-31",
+            "zip": "01067",
+            "name": "Müller",
+            "qty": 3,
+            "order": {"placed": "2024-12-30"}
+        })
+    );
+}
+
+#[test]
+fn test_unknown_locale_rejected() {
+    let cfg: LocaleParsing = serde_yaml::from_str("fields:\n  price: tlh\n").unwrap();
+    assert!(ParseLocale::from_config(&cfg).is_err());
+}
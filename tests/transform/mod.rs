@@ -0,0 +1,8 @@
+mod binary_tests;
+mod json_fields_tests;
+mod locale_tests;
+mod numbers_tests;
+mod page_hooks_tests;
+mod script_tests;
+mod timestamps_tests;
+mod wasm_tests;
@@ -0,0 +1,85 @@
+use apitap::transform::{NormalizeNumbers, NumberNormalization, RowTransform};
+use serde_json::json;
+
+fn cfg(fields: &[&str]) -> NumberNormalization {
+    NumberNormalization {
+        fields: fields.iter().map(|f| f.to_string()).collect(),
+        thousands_separator: ",".into(),
+        decimal_separator: ".".into(),
+        divide_by: None,
+    }
+}
+
+#[test]
+fn test_parse_currency_strings() {
+    let t = NormalizeNumbers::from_config(&cfg(&["price"])).unwrap();
+    assert_eq!(t.parse("$1,234.50"), Some(json!(1234.5)));
+    assert_eq!(t.parse("USD 1,000"), Some(json!(1000)));
+    assert_eq!(t.parse("(12.00)"), Some(json!(-12.0)));
+    assert_eq!(t.parse("-7 €"), Some(json!(-7)));
+    assert_eq!(t.parse("n/a"), None);
+    assert_eq!(t.parse("1.2.3"), None);
+    assert_eq!(t.parse("1e3"), Some(json!(1000.0)));
+    assert_eq!(t.parse(" 42 "), Some(json!(42)));
+}
+
+#[test]
+fn test_affixes_only_around_the_number() {
+    let t = NormalizeNumbers::from_config(&cfg(&["code"])).unwrap();
+    // Identifiers with letters or dashes inside are not numbers.
+    assert_eq!(t.parse("SKU-123"), None);
+    assert_eq!(t.parse("555-1234"), None);
+    assert_eq!(t.parse("A1B2"), None);
+    assert_eq!(t.parse("12-34-56"), None);
+    assert_eq!(t.parse("1(2)"), None);
+    assert_eq!(t.parse("(12"), None);
+    assert_eq!(t.parse("--5"), None);
+
+    // Signs, parentheses and currencies before or after it are fine.
+    assert_eq!(t.parse("$-5"), Some(json!(-5)));
+    assert_eq!(t.parse("-$5"), Some(json!(-5)));
+    assert_eq!(t.parse("12-"), Some(json!(-12)));
+    assert_eq!(t.parse("\u{2212}3.5"), Some(json!(-3.5)));
+    assert_eq!(t.parse("(12.00) USD"), Some(json!(-12.0)));
+    assert_eq!(t.parse("R$ 1,500"), Some(json!(1500)));
+    assert_eq!(t.parse("£1,000.25"), Some(json!(1000.25)));
+
+    let mut row = json!({"code": "SKU-123", "phone": "555-1234"});
+    NormalizeNumbers::from_config(&cfg(&["code", "phone"]))
+        .unwrap()
+        .apply(&mut row)
+        .unwrap();
+    assert_eq!(row, json!({"code": "SKU-123", "phone": "555-1234"}));
+}
+
+#[test]
+fn test_parse_european_format() {
+    let mut c = cfg(&["price"]);
+    c.thousands_separator = ".".into();
+    c.decimal_separator = ",".into();
+    let t = NormalizeNumbers::from_config(&c).unwrap();
+    assert_eq!(t.parse("1.234,5 €"), Some(json!(1234.5)));
+    assert_eq!(t.parse("Rp 10.000"), Some(json!(10000)));
+}
+
+#[test]
+fn test_divide_by_cents() {
+    let mut c = cfg(&["amount", "/total/cents"]);
+    c.divide_by = Some(100.0);
+    let t = NormalizeNumbers::from_config(&c).unwrap();
+    let mut row = json!({"amount": 1999, "total": {"cents": "2,500"}, "other": "$5"});
+    t.apply(&mut row).unwrap();
+    assert_eq!(row["amount"], json!(19.99));
+    assert_eq!(row["total"]["cents"], json!(25.0));
+    assert_eq!(row["other"], json!("$5"));
+}
+
+#[test]
+fn test_invalid_config() {
+    let mut c = cfg(&["x"]);
+    c.thousands_separator = ".".into();
+    assert!(NormalizeNumbers::from_config(&c).is_err());
+    let mut c = cfg(&["x"]);
+    c.divide_by = Some(0.0);
+    assert!(NormalizeNumbers::from_config(&c).is_err());
+}
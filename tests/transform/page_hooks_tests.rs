@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use apitap::errors::{ApitapError, Result};
+use apitap::http::fetcher::{DataFusionPageWriter, PageWriter};
+use apitap::transform::{PageHooks, ParseJsonFields, TransformChain};
+use apitap::utils::datafusion_ext::{QueryResult, QueryResultStream};
+use apitap::writer::{DataWriter, WriteMode};
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::{json, Value};
+
+/// Sink that keeps every row it receives.
+#[derive(Default)]
+struct CaptureWriter {
+    rows: Mutex<Vec<Value>>,
+}
+
+#[async_trait]
+impl DataWriter for CaptureWriter {
+    async fn write(&self, _result: QueryResult) -> Result<()> {
+        Ok(())
+    }
+
+    async fn write_stream(&self, mut result: QueryResultStream, _mode: WriteMode) -> Result<()> {
+        while let Some(row) = result.data.next().await {
+            self.rows.lock().unwrap().push(row?);
+        }
+        Ok(())
+    }
+}
+
+/// Join each user against in-memory reference data.
+fn team_lookup() -> impl Fn(Vec<Value>) -> Vec<Value> + Send + Sync + 'static {
+    let teams = HashMap::from([(1, "core"), (2, "infra")]);
+    move |rows| {
+        rows.into_iter()
+            .map(|mut row| {
+                let team = row["id"].as_i64().and_then(|id| teams.get(&id)).copied();
+                row["team"] = json!(team.unwrap_or("unknown"));
+                row
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_page_hooks_run_after_row_transforms() {
+    let mut hooks = PageHooks::new();
+    hooks.register("users", |rows: Vec<Value>| {
+        rows.into_iter()
+            .filter(|r| r["payload"]["keep"] == json!(true))
+            .collect()
+    });
+    let chain = TransformChain::new()
+        .with(ParseJsonFields::new(vec!["payload".to_string()]))
+        .with_page_hooks(hooks.for_source("users").iter().cloned());
+
+    let page = vec![
+        json!({"id": 1, "payload": "{\"keep\": true}"}),
+        json!({"id": 2, "payload": "{\"keep\": false}"}),
+    ];
+    let out = chain.apply_page(page).unwrap();
+    assert_eq!(out, vec![json!({"id": 1, "payload": {"keep": true}})]);
+    assert_eq!(chain.len(), 2);
+    assert!(chain.has_page_hooks());
+}
+
+#[test]
+fn test_page_hooks_per_source_and_errors() {
+    let mut hooks = PageHooks::new();
+    hooks.register("users", team_lookup());
+    hooks.register_fallible("secrets", |_rows| {
+        Err(ApitapError::PipelineError(
+            "cannot decrypt page".to_string(),
+        ))
+    });
+
+    assert_eq!(hooks.for_source("users").len(), 1);
+    assert!(hooks.for_source("other").is_empty());
+
+    let chain = TransformChain::new().with_page_hooks(hooks.for_source("secrets").iter().cloned());
+    assert!(chain.apply_page(vec![json!({"id": 1})]).is_err());
+}
+
+#[tokio::test]
+async fn test_page_hook_applies_to_streamed_pages() {
+    let mut hooks = PageHooks::new();
+    hooks.register("users", team_lookup());
+    let chain = TransformChain::new().with_page_hooks(hooks.for_source("users").iter().cloned());
+
+    let sink = Arc::new(CaptureWriter::default());
+    let writer = DataFusionPageWriter::new(
+        "users",
+        "SELECT id, team FROM users ORDER BY id",
+        sink.clone(),
+    )
+    .with_transforms(Arc::new(chain));
+
+    let rows = vec![
+        Ok(json!({"id": 1})),
+        Ok(json!({"id": 2})),
+        Ok(json!({"id": 3})),
+    ];
+    writer
+        .write_page_stream(Box::pin(futures::stream::iter(rows)), WriteMode::Append)
+        .await
+        .unwrap();
+
+    let written = sink.rows.lock().unwrap().clone();
+    assert_eq!(
+        written,
+        vec![
+            json!({"id": 1, "team": "core"}),
+            json!({"id": 2, "team": "infra"}),
+            json!({"id": 3, "team": "unknown"}),
+        ]
+    );
+}
@@ -0,0 +1,104 @@
+use apitap::errors::ApitapError;
+use apitap::pipeline::Config;
+use apitap::transform::{RhaiScript, TransformChain};
+use serde_json::json;
+
+#[test]
+fn test_script_derives_fields() {
+    let script = RhaiScript::compile(
+        r#"
+        row.full_name = row.first + " " + row.last;
+        row.total = row.price * row.qty;
+        "#,
+    )
+    .unwrap();
+
+    let row = script
+        .apply_row(json!({"first": "Ada", "last": "Lovelace", "price": 2.5, "qty": 4}))
+        .unwrap()
+        .unwrap();
+    assert_eq!(row["full_name"], json!("Ada Lovelace"));
+    assert_eq!(row["total"], json!(10.0));
+    assert_eq!(row["first"], json!("Ada"));
+}
+
+#[test]
+fn test_script_filters_rows() {
+    let script = RhaiScript::compile("row.amount > 0").unwrap();
+    let rows = script
+        .apply_page(vec![
+            json!({"id": 1, "amount": 10}),
+            json!({"id": 2, "amount": 0}),
+            json!({"id": 3, "amount": -5}),
+        ])
+        .unwrap();
+    assert_eq!(rows, vec![json!({"id": 1, "amount": 10})]);
+}
+
+#[test]
+fn test_script_keeps_nested_values() {
+    let script = RhaiScript::compile(
+        r#"
+        row.city = row.address.city;
+        row.tags.push("seen");
+        "#,
+    )
+    .unwrap();
+    let row = script
+        .apply_row(json!({"address": {"city": "Paris"}, "tags": ["a"], "note": null}))
+        .unwrap()
+        .unwrap();
+    assert_eq!(row["city"], json!("Paris"));
+    assert_eq!(row["tags"], json!(["a", "seen"]));
+    assert_eq!(row["note"], json!(null));
+}
+
+#[test]
+fn test_script_syntax_error_is_config_error() {
+    let err = RhaiScript::compile("row.x = ;").unwrap_err();
+    assert!(matches!(err, ApitapError::ConfigError(_)));
+}
+
+#[test]
+fn test_script_runtime_error_fails_page() {
+    let script = RhaiScript::compile("row.missing.field").unwrap();
+    let err = script.apply_page(vec![json!({"id": 1})]).unwrap_err();
+    assert!(matches!(err, ApitapError::PipelineError(_)));
+}
+
+#[test]
+fn test_script_operation_budget() {
+    let script = RhaiScript::compile("loop { row.n = 1; }")
+        .unwrap()
+        .with_max_operations(10_000);
+    assert!(script.apply_row(json!({})).is_err());
+}
+
+#[test]
+fn test_chain_from_source_runs_script_after_row_transforms() {
+    let config_yaml = r#"
+sources:
+  - name: orders
+    url: https://api.example.com/orders
+    data_path: null
+    primary_key_in_dest: id
+    retry: {max_attempts: 3, max_delay_secs: 10, min_delay_secs: 1}
+    parse_json_fields: [meta]
+    transform_script: |
+      row.channel = row.meta.channel;
+      row.status != "cancelled"
+targets: []
+"#;
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+    let chain = TransformChain::from_source(config.source("orders").unwrap()).unwrap();
+    assert!(chain.has_page_hooks());
+
+    let rows = chain
+        .apply_page(vec![
+            json!({"id": 1, "status": "paid", "meta": "{\"channel\":\"web\"}"}),
+            json!({"id": 2, "status": "cancelled", "meta": "{\"channel\":\"app\"}"}),
+        ])
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0]["channel"], json!("web"));
+}
@@ -0,0 +1,72 @@
+use apitap::transform::{NormalizeTimestamps, RowTransform, TimestampNormalization};
+use serde_json::json;
+
+fn ny_to_utc() -> NormalizeTimestamps {
+    NormalizeTimestamps::new("America/New_York", "UTC").unwrap()
+}
+
+#[test]
+fn test_naive_timestamp_assumed_local() {
+    let t = ny_to_utc();
+    // EST is UTC-5 in January
+    assert_eq!(
+        t.normalize("2024-01-15 09:30:00").as_deref(),
+        Some("2024-01-15T14:30:00Z")
+    );
+    // EDT is UTC-4 in July
+    assert_eq!(
+        t.normalize("2024-07-01T08:00:00.250").as_deref(),
+        Some("2024-07-01T12:00:00.250Z")
+    );
+}
+
+#[test]
+fn test_offset_timestamp_converted() {
+    let t = ny_to_utc();
+    assert_eq!(
+        t.normalize("2024-01-15T09:30:00+07:00").as_deref(),
+        Some("2024-01-15T02:30:00Z")
+    );
+}
+
+#[test]
+fn test_non_timestamps_untouched() {
+    let t = ny_to_utc();
+    assert_eq!(t.normalize("2024-01-15"), None);
+    assert_eq!(t.normalize("hello world, long text"), None);
+    // Falls in the spring-forward gap
+    assert_eq!(t.normalize("2024-03-10 02:30:00"), None);
+}
+
+#[test]
+fn test_convert_to_named_zone() {
+    let t = NormalizeTimestamps::new("UTC", "Asia/Jakarta").unwrap();
+    assert_eq!(
+        t.normalize("2024-01-15T00:00:00Z").as_deref(),
+        Some("2024-01-15T07:00:00+07:00")
+    );
+}
+
+#[test]
+fn test_apply_auto_detect_and_fields() {
+    let mut row = json!({"id": 7, "created": "2024-01-15 09:30:00", "note": "x"});
+    ny_to_utc().apply(&mut row).unwrap();
+    assert_eq!(row["created"], json!("2024-01-15T14:30:00Z"));
+    assert_eq!(row["id"], json!(7));
+
+    let cfg = TimestampNormalization {
+        assume_tz: "America/New_York".into(),
+        convert_to: "UTC".into(),
+        fields: Some(vec!["/meta/at".into()]),
+    };
+    let t = NormalizeTimestamps::from_config(&cfg).unwrap();
+    let mut row = json!({"created": "2024-01-15 09:30:00", "meta": {"at": "2024-01-15 09:30:00"}});
+    t.apply(&mut row).unwrap();
+    assert_eq!(row["created"], json!("2024-01-15 09:30:00"));
+    assert_eq!(row["meta"]["at"], json!("2024-01-15T14:30:00Z"));
+}
+
+#[test]
+fn test_unknown_zone_is_config_error() {
+    assert!(NormalizeTimestamps::new("Mars/Olympus", "UTC").is_err());
+}
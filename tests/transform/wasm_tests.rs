@@ -0,0 +1,98 @@
+use apitap::transform::WasmTransform;
+
+#[cfg(not(feature = "wasm"))]
+#[test]
+fn test_wasm_plugin_needs_feature() {
+    let err = WasmTransform::from_bytes("plugins/clean.wasm", b"(module)").unwrap_err();
+    assert!(err.to_string().contains("`wasm` feature"), "{err}");
+}
+
+#[test]
+fn test_missing_plugin_file() {
+    let err = WasmTransform::load("does/not/exist.wasm").unwrap_err();
+    assert!(err.to_string().contains("does/not/exist.wasm"), "{err}");
+}
+
+#[cfg(feature = "wasm")]
+mod runtime {
+    use super::WasmTransform;
+    use apitap::transform::TransformChain;
+    use serde_json::json;
+
+    /// Bump allocator plus a `transform` body supplied by each test.
+    fn plugin(data: &str, transform_body: &str) -> String {
+        format!(
+            r#"(module
+  (memory (export "memory") 1)
+  {data}
+  (global $next (mut i32) (i32.const 1024))
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $p i32)
+    global.get $next
+    local.set $p
+    global.get $next
+    local.get $len
+    i32.add
+    global.set $next
+    local.get $p)
+  (func (export "transform") (param $ptr i32) (param $len i32) (result i64)
+    {transform_body}))"#
+        )
+    }
+
+    const IDENTITY: &str = "local.get $ptr
+    i64.extend_i32_u
+    i64.const 32
+    i64.shl
+    local.get $len
+    i64.extend_i32_u
+    i64.or";
+
+    #[test]
+    fn test_identity_plugin_round_trips_page() {
+        let wat = plugin("", IDENTITY);
+        let t = WasmTransform::from_bytes("identity.wat", wat.as_bytes()).unwrap();
+        let page = vec![json!({"id": 1, "nested": {"a": [1, 2]}}), json!({"id": 2})];
+        assert_eq!(t.apply_page(page.clone()).unwrap(), page);
+    }
+
+    #[test]
+    fn test_plugin_output_replaces_page_in_chain() {
+        let out = r#"[{"id":1,"clean":true}]"#;
+        let wat = plugin(
+            &format!(r#"(data (i32.const 0) "{}")"#, out.replace('"', "\\\"")),
+            &format!("i64.const {}", out.len()),
+        );
+        let plugin = WasmTransform::from_bytes("const.wat", wat.as_bytes()).unwrap();
+        let chain = TransformChain::new().with_page_hooks([plugin.into_page_hook()]);
+
+        let rows = chain
+            .apply_page(vec![json!({"id": 1, "raw": " x "}), json!({"id": 2})])
+            .unwrap();
+        assert_eq!(rows, vec![json!({"id": 1, "clean": true})]);
+    }
+
+    #[test]
+    fn test_trapping_and_runaway_plugins_fail_the_page() {
+        let trap = plugin("", "unreachable");
+        let t = WasmTransform::from_bytes("trap.wat", trap.as_bytes()).unwrap();
+        let err = t.apply_page(vec![json!({"id": 1})]).unwrap_err();
+        assert!(err.to_string().contains("trap.wat"), "{err}");
+
+        let spin = plugin("", "(loop $l (br $l)) i64.const 0");
+        let t = WasmTransform::from_bytes("spin.wat", spin.as_bytes())
+            .unwrap()
+            .with_fuel(100_000);
+        assert!(t.apply_page(vec![json!({"id": 1})]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_module_and_missing_exports() {
+        assert!(WasmTransform::from_bytes("bad.wasm", b"not wasm").is_err());
+
+        let t = WasmTransform::from_bytes("empty.wat", b"(module (memory (export \"memory\") 1))")
+            .unwrap();
+        let err = t.apply_page(vec![]).unwrap_err();
+        assert!(err.to_string().contains("alloc"), "{err}");
+    }
+}
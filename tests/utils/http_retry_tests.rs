@@ -0,0 +1,71 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::http::StubResponse;
+use apitap::pipeline::{Retry, RetryJitter};
+use apitap::utils::http_retry::build_client_with_retry;
+
+/// Answers every request with `status` and counts the requests.
+async fn serve(status: &'static str) -> (String, Arc<AtomicUsize>) {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let seen = hits.clone();
+    let base = crate::http::serve(move |_| {
+        seen.fetch_add(1, Ordering::SeqCst);
+        StubResponse::status(status)
+    })
+    .await;
+    (format!("{base}/items"), hits)
+}
+
+fn retry(retry_on: Vec<u16>) -> Retry {
+    Retry {
+        max_attempts: 2,
+        max_delay_secs: 0,
+        min_delay_secs: 0,
+        jitter: RetryJitter::None,
+        retry_on,
+    }
+}
+
+async fn requests_for(status: &'static str, config: Retry) -> usize {
+    let (url, hits) = serve(status).await;
+    let client = build_client_with_retry(reqwest::Client::new(), &config);
+    client.get(&url).send().await.unwrap();
+    hits.load(Ordering::SeqCst)
+}
+
+#[tokio::test]
+async fn test_default_retryable_statuses() {
+    assert_eq!(
+        requests_for("503 Service Unavailable", retry(vec![])).await,
+        3
+    );
+    assert_eq!(requests_for("404 Not Found", retry(vec![])).await, 1);
+}
+
+#[tokio::test]
+async fn test_configured_retryable_statuses() {
+    assert_eq!(requests_for("404 Not Found", retry(vec![404])).await, 3);
+    assert_eq!(
+        requests_for("503 Service Unavailable", retry(vec![404])).await,
+        1
+    );
+}
+
+#[test]
+fn test_retry_config_from_yaml() {
+    let retry: Retry = serde_yaml::from_str(
+        "{max_attempts: 4, min_delay_secs: 1, max_delay_secs: 8, jitter: bounded, retry_on: [502, 503]}",
+    )
+    .unwrap();
+    assert_eq!(retry.jitter, RetryJitter::Bounded);
+    assert!(retry.retries_status(502));
+    assert!(!retry.retries_status(500));
+
+    let retry: Retry =
+        serde_yaml::from_str("{max_attempts: 4, min_delay_secs: 1, max_delay_secs: 8}").unwrap();
+    assert_eq!(retry.jitter, RetryJitter::Full);
+    assert!(retry.retries_status(429));
+    assert!(retry.retries_status(500));
+    assert!(!retry.retries_status(400));
+}
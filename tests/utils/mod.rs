@@ -1,2 +1,4 @@
+mod http_retry_tests;
 mod schema_tests;
+mod storage_tests;
 mod streaming_tests;
@@ -0,0 +1,71 @@
+use apitap::errors::ApitapError;
+use apitap::pipeline::{FileSink, SinkConn, Target, TargetConn};
+use apitap::utils::storage::{location_url, resolve_object_store, split_cloud_location};
+
+#[test]
+fn test_resolve_gcs_location() {
+    let (store, prefix) = resolve_object_store("gs://archive-bucket/exports/daily").unwrap();
+    assert!(store.to_string().contains("archive-bucket"));
+    assert_eq!(prefix.as_ref(), "exports/daily");
+}
+
+#[test]
+fn test_resolve_azure_abfss_location() {
+    let (store, prefix) =
+        resolve_object_store("abfss://archive@myaccount.dfs.core.windows.net/exports/daily")
+            .unwrap();
+    assert!(store.to_string().contains("archive"));
+    assert_eq!(prefix.as_ref(), "exports/daily");
+}
+
+#[test]
+fn test_resolve_unsupported_scheme() {
+    let err = resolve_object_store("ftp://host/dir").unwrap_err();
+    assert!(matches!(err, ApitapError::ConfigError(ref m) if m.contains("ftp")));
+}
+
+#[test]
+fn test_location_url() {
+    assert_eq!(
+        location_url("s3://bucket/lake").unwrap().as_str(),
+        "s3://bucket/lake"
+    );
+
+    let dir = tempfile::TempDir::new().unwrap();
+    let url = location_url(dir.path().to_str().unwrap()).unwrap();
+    assert_eq!(url.scheme(), "file");
+    assert!(url.path().ends_with('/'));
+}
+
+#[test]
+fn test_split_cloud_location() {
+    assert_eq!(
+        split_cloud_location("gs://archive-bucket/exports/{table}.ndjson"),
+        Some(("gs://archive-bucket", "exports/{table}.ndjson"))
+    );
+    assert_eq!(
+        split_cloud_location("abfss://archive@myaccount.dfs.core.windows.net/{table}.csv"),
+        Some((
+            "abfss://archive@myaccount.dfs.core.windows.net",
+            "{table}.csv"
+        ))
+    );
+    assert_eq!(
+        split_cloud_location("s3://bucket"),
+        Some(("s3://bucket", ""))
+    );
+    assert_eq!(split_cloud_location("output/{table}.csv"), None);
+    assert_eq!(split_cloud_location("file:///tmp/{table}.csv"), None);
+}
+
+#[tokio::test]
+async fn test_file_target_accepts_cloud_paths() {
+    let target = Target::File(FileSink {
+        name: "archive".to_string(),
+        metadata_columns: Vec::new(),
+        path: "gs://archive-bucket/{table}.ndjson".to_string(),
+        format: None,
+    });
+    let conn = target.create_conn().await.unwrap();
+    assert!(matches!(conn, TargetConn::File { store: Some(_), .. }));
+}
@@ -0,0 +1,42 @@
+use apitap::transform::binary::decode_base64;
+use apitap::writer::compress::{compress_json, compress_row, compressed_name, decompress_json};
+use serde_json::json;
+
+#[test]
+fn test_compress_json_round_trip_shrinks_wide_payloads() {
+    let items: Vec<_> = (0..500)
+        .map(|i| json!({"sku": format!("SKU-{i:05}"), "qty": i % 7, "warehouse": "eu-west-1"}))
+        .collect();
+    let payload = json!({"order": 42, "items": items});
+
+    let compressed = compress_json(&payload).unwrap();
+    assert!(compressed.len() * 5 < payload.to_string().len());
+    assert_eq!(decompress_json(&compressed).unwrap(), payload);
+}
+
+#[test]
+fn test_compress_row_moves_columns() {
+    let columns = vec![
+        "payload".to_string(),
+        "raw".to_string(),
+        "absent".to_string(),
+    ];
+    let mut row = json!({"id": 1, "payload": {"a": [1, 2, 3]}, "raw": null});
+    compress_row(&mut row, &columns).unwrap();
+
+    assert_eq!(compressed_name("payload"), "payload_compressed");
+    let object = row.as_object().unwrap();
+    assert_eq!(
+        object.keys().collect::<Vec<_>>(),
+        ["id", "payload_compressed", "raw_compressed"]
+    );
+    assert!(object["raw_compressed"].is_null());
+
+    let bytes = decode_base64(object["payload_compressed"].as_str().unwrap()).unwrap();
+    assert_eq!(decompress_json(&bytes).unwrap(), json!({"a": [1, 2, 3]}));
+}
+
+#[test]
+fn test_decompress_rejects_other_bytes() {
+    assert!(decompress_json(b"{\"not\": \"zstd\"}").is_err());
+}
@@ -0,0 +1,87 @@
+// Tests for Debug Writer
+//
+// These tests cover:
+// - Pretty and NDJSON output
+// - The per-module row limit across pages
+
+use apitap::utils::datafusion_ext::QueryResultStream;
+use apitap::writer::debug::{DebugFormat, DebugWriter};
+use apitap::writer::{DataWriter, WriteMode};
+use serde_json::json;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+/// Cloneable in-memory output.
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Buffer {
+    fn text(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+fn rows(ids: std::ops::Range<i64>) -> QueryResultStream {
+    QueryResultStream {
+        table_name: "users".to_string(),
+        data: Box::pin(futures::stream::iter(
+            ids.map(|id| Ok(json!({"id": id, "name": "x"}))),
+        )),
+    }
+}
+
+#[tokio::test]
+async fn test_ndjson_output_with_limit_across_pages() {
+    let out = Buffer::default();
+    let writer = DebugWriter::new("users")
+        .with_format(DebugFormat::Ndjson)
+        .with_limit(3)
+        .with_output(out.clone());
+
+    writer
+        .write_stream(rows(0..2), WriteMode::Merge)
+        .await
+        .unwrap();
+    writer
+        .write_stream(rows(2..5), WriteMode::Merge)
+        .await
+        .unwrap();
+    writer
+        .write_stream(rows(5..9), WriteMode::Merge)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        out.text(),
+        "{\"id\":0,\"name\":\"x\"}\n{\"id\":1,\"name\":\"x\"}\n{\"id\":2,\"name\":\"x\"}\n"
+    );
+    assert_eq!(writer.printed(), 3);
+}
+
+#[tokio::test]
+async fn test_pretty_output_unlimited() {
+    let out = Buffer::default();
+    let writer = DebugWriter::new("users")
+        .with_limit(0)
+        .with_output(out.clone());
+
+    writer
+        .write_stream(rows(0..30), WriteMode::Append)
+        .await
+        .unwrap();
+
+    let text = out.text();
+    assert!(text.starts_with("{\n  \"id\": 0,\n  \"name\": \"x\"\n}\n"));
+    assert_eq!(text.matches("\"id\"").count(), 30);
+    assert_eq!(writer.printed(), 30);
+}
@@ -0,0 +1,270 @@
+// Tests for Delta Lake Writer
+//
+// These tests cover:
+// - Type normalization and schema evolution helpers
+// - Append commits and the transaction log layout
+// - Schema evolution when new fields appear
+// - Merge on primary key and truncate
+
+use apitap::utils::datafusion_ext::QueryResultStream;
+use apitap::writer::delta::{evolve_schema, normalize_type, DeltaWriter};
+use apitap::writer::{DataWriter, WriteMode};
+use datafusion::arrow::array::{Array, AsArray};
+use datafusion::arrow::datatypes::{DataType, Field, Int64Type, Schema, TimeUnit};
+use datafusion::prelude::SessionContext;
+use deltalake::delta_datafusion::DataFusionMixins;
+use object_store::local::LocalFileSystem;
+use object_store::path::Path as ObjectPath;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tempfile::TempDir;
+use url::Url;
+
+fn writer(dir: &TempDir) -> DeltaWriter {
+    let store = Arc::new(LocalFileSystem::new_with_prefix(dir.path()).unwrap());
+    let location = Url::from_directory_path(dir.path()).unwrap();
+    DeltaWriter::new(store, ObjectPath::default(), location, "events")
+}
+
+fn rows_stream(rows: Vec<Value>) -> QueryResultStream {
+    QueryResultStream {
+        table_name: "events".to_string(),
+        data: Box::pin(futures::stream::iter(rows.into_iter().map(Ok))),
+    }
+}
+
+/// id -> name for every row currently in the table.
+async fn table_rows(writer: &DeltaWriter) -> BTreeMap<i64, Option<String>> {
+    let table = writer.table().await.unwrap().unwrap();
+    let ctx = SessionContext::new();
+    let batches = ctx
+        .read_table(Arc::new(table))
+        .unwrap()
+        .collect()
+        .await
+        .unwrap();
+    let mut out = BTreeMap::new();
+    for batch in batches {
+        let ids = batch
+            .column_by_name("id")
+            .unwrap()
+            .as_primitive::<Int64Type>();
+        let names = batch.column_by_name("name").unwrap().as_string::<i32>();
+        for i in 0..batch.num_rows() {
+            let name = (!names.is_null(i)).then(|| names.value(i).to_string());
+            out.insert(ids.value(i), name);
+        }
+    }
+    out
+}
+
+fn log_actions(dir: &TempDir, version: u64) -> Vec<Value> {
+    let path = dir
+        .path()
+        .join("events")
+        .join("_delta_log")
+        .join(format!("{version:020}.json"));
+    std::fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_normalize_and_evolve() {
+    assert_eq!(normalize_type(&DataType::UInt32), DataType::Int64);
+    assert_eq!(normalize_type(&DataType::Utf8View), DataType::Utf8);
+    assert_eq!(
+        normalize_type(&DataType::Timestamp(TimeUnit::Nanosecond, None)),
+        DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()))
+    );
+
+    let table = Schema::new(vec![Field::new("id", DataType::Int64, true)]);
+    let incoming = Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("extra", DataType::Boolean, false),
+    ]);
+    let (evolved, changed) = evolve_schema(Some(&table), &incoming);
+    assert!(changed);
+    // Existing columns keep the table type; new ones are appended as nullable.
+    assert_eq!(evolved.field(0).data_type(), &DataType::Int64);
+    assert_eq!(evolved.field(1).name(), "extra");
+    assert!(evolved.field(1).is_nullable());
+
+    let (_, changed) = evolve_schema(Some(&table), &table);
+    assert!(!changed);
+}
+
+#[tokio::test]
+async fn test_append_commits_versions() {
+    let dir = TempDir::new().unwrap();
+    let writer = writer(&dir);
+
+    // Nothing is committed for an empty page.
+    writer
+        .write_stream(rows_stream(vec![]), WriteMode::Append)
+        .await
+        .unwrap();
+    assert!(writer.table().await.unwrap().is_none());
+
+    writer
+        .write_stream(
+            rows_stream(vec![
+                json!({"id": 1, "name": "a"}),
+                json!({"id": 2, "name": "b"}),
+            ]),
+            WriteMode::Append,
+        )
+        .await
+        .unwrap();
+    writer
+        .write_stream(
+            rows_stream(vec![json!({"id": 3, "name": "c"})]),
+            WriteMode::Append,
+        )
+        .await
+        .unwrap();
+
+    let first = log_actions(&dir, 0);
+    assert!(first.iter().any(|a| a.get("protocol").is_some()));
+    assert!(first.iter().any(|a| a.get("metaData").is_some()));
+    assert!(first.iter().any(|a| a.get("add").is_some()));
+
+    // Same schema: the second commit only adds files.
+    let second = log_actions(&dir, 1);
+    assert!(second.iter().all(|a| a.get("metaData").is_none()));
+
+    let table = writer.table().await.unwrap().unwrap();
+    assert_eq!(table.version(), 1);
+    assert_eq!(table.get_files_count(), 2);
+    assert_eq!(table_rows(&writer).await.len(), 3);
+}
+
+#[tokio::test]
+async fn test_new_fields_evolve_schema() {
+    let dir = TempDir::new().unwrap();
+    let writer = writer(&dir);
+
+    writer
+        .write_stream(
+            rows_stream(vec![json!({"id": 1, "name": "a"})]),
+            WriteMode::Append,
+        )
+        .await
+        .unwrap();
+    writer
+        .write_stream(
+            rows_stream(vec![json!({"id": 2, "name": "b", "score": 9.5})]),
+            WriteMode::Append,
+        )
+        .await
+        .unwrap();
+
+    assert!(log_actions(&dir, 1)
+        .iter()
+        .any(|a| a.get("metaData").is_some()));
+    let table = writer.table().await.unwrap().unwrap();
+    let schema = table.snapshot().unwrap().arrow_schema().unwrap();
+    let score = schema.field_with_name("score").unwrap();
+    assert_eq!(score.data_type(), &DataType::Float64);
+    assert!(score.is_nullable());
+    assert_eq!(table_rows(&writer).await.len(), 2);
+}
+
+#[tokio::test]
+async fn test_merge_on_primary_key() {
+    let dir = TempDir::new().unwrap();
+    let writer = writer(&dir).with_primary_key_single(Some("id".to_string()));
+
+    writer
+        .write_stream(
+            rows_stream(vec![
+                json!({"id": 1, "name": "a"}),
+                json!({"id": 2, "name": "b"}),
+            ]),
+            WriteMode::Merge,
+        )
+        .await
+        .unwrap();
+    writer
+        .write_stream(
+            rows_stream(vec![
+                json!({"id": 2, "name": "stale"}),
+                json!({"id": 3, "name": "c"}),
+                json!({"id": 2, "name": "bb"}),
+            ]),
+            WriteMode::Merge,
+        )
+        .await
+        .unwrap();
+
+    let rows = table_rows(&writer).await;
+    assert_eq!(
+        rows,
+        BTreeMap::from([
+            (1, Some("a".to_string())),
+            (2, Some("bb".to_string())),
+            (3, Some("c".to_string())),
+        ])
+    );
+
+    // The original file was rewritten and removed in the merge commit.
+    let merge = log_actions(&dir, 1);
+    assert_eq!(
+        merge.iter().filter(|a| a.get("remove").is_some()).count(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_merge_adds_new_columns() {
+    let dir = TempDir::new().unwrap();
+    let writer = writer(&dir).with_primary_key_single(Some("id".to_string()));
+
+    writer
+        .write_stream(
+            rows_stream(vec![json!({"id": 1, "name": "a"})]),
+            WriteMode::Merge,
+        )
+        .await
+        .unwrap();
+    writer
+        .write_stream(
+            rows_stream(vec![json!({"id": 1, "name": "a2", "active": true})]),
+            WriteMode::Merge,
+        )
+        .await
+        .unwrap();
+
+    let table = writer.table().await.unwrap().unwrap();
+    let schema = table.snapshot().unwrap().arrow_schema().unwrap();
+    assert!(schema.field_with_name("active").is_ok());
+    assert_eq!(
+        table_rows(&writer).await,
+        BTreeMap::from([(1, Some("a2".to_string()))])
+    );
+}
+
+#[tokio::test]
+async fn test_truncate_removes_active_files() {
+    let dir = TempDir::new().unwrap();
+    let writer = writer(&dir);
+
+    // Nothing to do on a missing table.
+    writer.truncate().await.unwrap();
+
+    writer
+        .write_stream(
+            rows_stream(vec![json!({"id": 1, "name": "a"})]),
+            WriteMode::Append,
+        )
+        .await
+        .unwrap();
+    writer.truncate().await.unwrap();
+
+    let table = writer.table().await.unwrap().unwrap();
+    assert_eq!(table.version(), 1);
+    assert_eq!(table.get_files_count(), 0);
+}
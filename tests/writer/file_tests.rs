@@ -0,0 +1,235 @@
+// Tests for File Writer (CSV / NDJSON)
+
+use apitap::utils::datafusion_ext::QueryResultStream;
+use apitap::writer::file::{render_path_template, FileFormat, FileWriter};
+use apitap::writer::{DataWriter, WriteMode};
+use futures::{stream, TryStreamExt};
+use object_store::memory::InMemory;
+use object_store::path::Path as ObjectPath;
+use object_store::ObjectStore;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+fn rows_stream(rows: Vec<Value>) -> QueryResultStream {
+    QueryResultStream {
+        table_name: "users".to_string(),
+        data: Box::pin(stream::iter(rows.into_iter().map(Ok))),
+    }
+}
+
+#[test]
+fn test_format_from_path() {
+    assert_eq!(FileFormat::from_path("out/{table}.csv"), FileFormat::Csv);
+    assert_eq!(
+        FileFormat::from_path("out/{table}.ndjson"),
+        FileFormat::Ndjson
+    );
+    assert_eq!(
+        FileFormat::from_path("out/{table}.jsonl"),
+        FileFormat::Ndjson
+    );
+}
+
+#[test]
+fn test_render_path_template() {
+    let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
+    assert_eq!(
+        render_path_template("output/{table}/{date}.ndjson", "users"),
+        format!("output/users/{today}.ndjson")
+    );
+}
+
+#[test]
+fn test_csv_cells() {
+    let cols = vec!["id".to_string(), "name".to_string(), "tags".to_string()];
+    let cells = FileWriter::csv_cells(&json!({"id": 1, "name": "a", "tags": ["x"]}), &cols);
+    assert_eq!(cells, vec!["1", "a", "[\"x\"]"]);
+    let cells = FileWriter::csv_cells(&json!({"id": 2, "name": null}), &cols);
+    assert_eq!(cells, vec!["2", "", ""]);
+}
+
+#[tokio::test]
+async fn test_ndjson_appends_across_writes() {
+    let dir = tempfile::tempdir().unwrap();
+    let template = format!("{}/{{table}}/out.ndjson", dir.path().display());
+    let writer = FileWriter::new(&template, "users", None);
+
+    for _ in 0..2 {
+        writer
+            .write_stream(
+                rows_stream(vec![json!({"id": 1}), json!({"id": 2})]),
+                WriteMode::Append,
+            )
+            .await
+            .unwrap();
+    }
+
+    let text = std::fs::read_to_string(dir.path().join("users/out.ndjson")).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(
+        serde_json::from_str::<Value>(lines[0]).unwrap(),
+        json!({"id": 1})
+    );
+}
+
+#[tokio::test]
+async fn test_csv_header_written_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let template = format!("{}/{{table}}.csv", dir.path().display());
+    let writer = FileWriter::new(&template, "users", None);
+
+    writer
+        .write_stream(
+            rows_stream(vec![json!({"id": 1, "name": "a,b"})]),
+            WriteMode::Append,
+        )
+        .await
+        .unwrap();
+    writer
+        .write_stream(
+            rows_stream(vec![json!({"name": "c", "id": 2})]),
+            WriteMode::Merge,
+        )
+        .await
+        .unwrap();
+
+    let text = std::fs::read_to_string(dir.path().join("users.csv")).unwrap();
+    assert_eq!(text, "id,name\n1,\"a,b\"\n2,c\n");
+
+    writer.truncate().await.unwrap();
+    assert!(!dir.path().join("users.csv").exists());
+}
+
+#[tokio::test]
+async fn test_concurrent_pages_share_one_header() {
+    let dir = tempfile::tempdir().unwrap();
+    let template = format!("{}/{{table}}.csv", dir.path().display());
+    let writer = std::sync::Arc::new(FileWriter::new(&template, "users", None));
+
+    let pages = (0..8).map(|page| {
+        let writer = writer.clone();
+        async move {
+            let rows = (0..50)
+                .map(|i| json!({"id": page * 100 + i, "name": format!("user {i}")}))
+                .collect();
+            writer
+                .write_stream(rows_stream(rows), WriteMode::Append)
+                .await
+        }
+    });
+    for done in futures::future::join_all(pages).await {
+        done.unwrap();
+    }
+
+    let text = std::fs::read_to_string(dir.path().join("users.csv")).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 1 + 8 * 50);
+    assert_eq!(lines.iter().filter(|l| **l == "id,name").count(), 1);
+    assert_eq!(lines[0], "id,name");
+    assert!(lines[1..].iter().all(|l| l.ends_with(&format!(
+        "user {}",
+        l.split(',').next().unwrap().parse::<u32>().unwrap() % 100
+    ))));
+}
+
+/// Objects under `dir` in `store`, sorted by name, with their contents.
+async fn objects(store: &InMemory, dir: &str) -> Vec<(String, String)> {
+    let listed: Vec<_> = store
+        .list(Some(&ObjectPath::from(dir)))
+        .try_collect()
+        .await
+        .unwrap();
+    let mut out = Vec::new();
+    for meta in listed {
+        let bytes = store
+            .get(&meta.location)
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        out.push((
+            meta.location.to_string(),
+            String::from_utf8(bytes.to_vec()).unwrap(),
+        ));
+    }
+    out.sort();
+    out
+}
+
+#[tokio::test]
+async fn test_object_store_page_per_object() {
+    let store = Arc::new(InMemory::new());
+    let writer = FileWriter::in_store(store.clone(), "exports/{table}.csv", "users", None);
+
+    writer
+        .write_stream(
+            rows_stream(vec![json!({"id": 1, "name": "a"})]),
+            WriteMode::Append,
+        )
+        .await
+        .unwrap();
+    writer
+        .write_stream(
+            rows_stream(vec![json!({"id": 2, "name": "b"}), json!({"id": 3})]),
+            WriteMode::Append,
+        )
+        .await
+        .unwrap();
+    // A page without rows leaves no object.
+    writer
+        .write_stream(rows_stream(Vec::new()), WriteMode::Append)
+        .await
+        .unwrap();
+
+    let written = objects(&store, "exports").await;
+    assert_eq!(written.len(), 2);
+    for (path, _) in &written {
+        assert!(path.starts_with("exports/users-"), "{path}");
+        assert!(path.ends_with(".csv"), "{path}");
+    }
+    let mut bodies: Vec<&str> = written.iter().map(|(_, body)| body.as_str()).collect();
+    bodies.sort();
+    assert_eq!(bodies, ["id,name\n1,a\n", "id,name\n2,b\n3,\n"]);
+
+    // Objects of other paths in the same directory are kept.
+    store
+        .put(&ObjectPath::from("exports/orders.csv"), "id\n".into())
+        .await
+        .unwrap();
+    writer.truncate().await.unwrap();
+    let left = objects(&store, "exports").await;
+    assert_eq!(left.len(), 1);
+    assert_eq!(left[0].0, "exports/orders.csv");
+}
+
+#[tokio::test]
+async fn test_object_store_failed_page_leaves_nothing() {
+    let store = Arc::new(InMemory::new());
+    let writer = FileWriter::in_store(store.clone(), "{table}.ndjson", "users", None);
+
+    let rows: Vec<apitap::errors::Result<Value>> = vec![
+        Ok(json!({"id": 1})),
+        Err(apitap::errors::ApitapError::PipelineError("boom".into())),
+    ];
+    let failing = QueryResultStream {
+        table_name: "users".to_string(),
+        data: Box::pin(stream::iter(rows)),
+    };
+    assert!(writer
+        .write_stream(failing, WriteMode::Append)
+        .await
+        .is_err());
+    assert!(objects(&store, "").await.is_empty());
+
+    writer
+        .write_stream(rows_stream(vec![json!({"id": 1})]), WriteMode::Append)
+        .await
+        .unwrap();
+    let written = objects(&store, "").await;
+    assert_eq!(written.len(), 1);
+    assert!(written[0].0.starts_with("users-"));
+    assert!(written[0].0.ends_with(".ndjson"));
+    assert_eq!(written[0].1, "{\"id\":1}\n");
+}
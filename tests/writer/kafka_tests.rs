@@ -0,0 +1,77 @@
+// Tests for Kafka Writer
+//
+// These tests cover:
+// - Message key extraction
+// - Producer configuration
+// - Delivery failures surfacing as a write error
+
+use apitap::pipeline::KafkaSink;
+use apitap::utils::datafusion_ext::QueryResultStream;
+use apitap::writer::kafka::{KafkaCompression, KafkaWriter};
+use apitap::writer::{DataWriter, WriteMode};
+use rdkafka::producer::FutureProducer;
+use serde_json::json;
+use std::collections::BTreeMap;
+
+fn sink(brokers: &str) -> KafkaSink {
+    KafkaSink {
+        name: "events".to_string(),
+        metadata_columns: Vec::new(),
+        brokers: brokers.to_string(),
+        topic: None,
+        compression: KafkaCompression::Lz4,
+        linger_ms: 5,
+        batch_size: 100,
+        properties: BTreeMap::from([("message.timeout.ms".to_string(), "200".to_string())]),
+    }
+}
+
+#[test]
+fn test_message_key() {
+    let row = json!({"id": 42, "sku": "A-1", "missing": null});
+    assert_eq!(
+        KafkaWriter::message_key(&row, Some("id")),
+        Some("42".to_string())
+    );
+    assert_eq!(
+        KafkaWriter::message_key(&row, Some("sku")),
+        Some("A-1".to_string())
+    );
+    assert_eq!(KafkaWriter::message_key(&row, Some("missing")), None);
+    assert_eq!(KafkaWriter::message_key(&row, Some("nope")), None);
+    assert_eq!(KafkaWriter::message_key(&row, None), None);
+}
+
+#[test]
+fn test_client_config() {
+    let cfg = sink("broker-1:9092,broker-2:9092").client_config();
+    assert_eq!(
+        cfg.get("bootstrap.servers"),
+        Some("broker-1:9092,broker-2:9092")
+    );
+    assert_eq!(cfg.get("compression.type"), Some("lz4"));
+    assert_eq!(cfg.get("batch.num.messages"), Some("100"));
+    assert_eq!(cfg.get("message.timeout.ms"), Some("200"));
+}
+
+#[tokio::test]
+async fn test_undelivered_messages_fail_the_write() {
+    // Nothing listens on port 1, so every delivery times out.
+    let producer: FutureProducer = sink("127.0.0.1:1").client_config().create().unwrap();
+    let writer = KafkaWriter::new(producer, "events").with_key_field(Some("id".to_string()));
+
+    let rows = vec![json!({"id": 1}), json!({"id": 2})];
+    let stream = QueryResultStream {
+        table_name: "events".to_string(),
+        data: Box::pin(futures::stream::iter(rows.into_iter().map(Ok))),
+    };
+
+    let err = writer
+        .write_stream(stream, WriteMode::Append)
+        .await
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("2 of 2 messages"),
+        "unexpected error: {err}"
+    );
+}
@@ -0,0 +1,125 @@
+// Tests for primary key inference
+//
+// These tests cover:
+// - Ranking of single-column candidates and the composite fallback
+// - The error suggesting candidates
+// - SQLite merges without a configured key, with and without inference
+
+use apitap::errors::ApitapError;
+use apitap::utils::datafusion_ext::QueryResultStream;
+use apitap::writer::key_inference::{candidate_keys, infer_key, missing_key_error};
+use apitap::writer::sqlite::SqliteWriter;
+use apitap::writer::{DataWriter, WriteMode};
+use serde_json::{json, Value};
+use sqlx::sqlite::SqlitePoolOptions;
+
+fn keys(candidates: Vec<Vec<String>>) -> Vec<String> {
+    candidates.into_iter().map(|c| c.join("+")).collect()
+}
+
+#[test]
+fn test_candidates_prefer_id_then_uuids() {
+    let rows = vec![
+        json!({"customerId": 7, "ref": "0b5e2a5c-3f6e-4c1a-9d55-7a0f0e1b2c3d", "id": 1, "name": "a"}),
+        json!({"customerId": 8, "ref": "5f1c7f4e-8b0a-4a59-bc0e-2d9e1f3a4b5c", "id": 2, "name": "b"}),
+    ];
+    assert_eq!(keys(candidate_keys(&rows)), ["id", "ref", "customerId"]);
+}
+
+#[test]
+fn test_candidates_skip_repeated_null_and_unnamed_columns() {
+    let rows = vec![
+        json!({"id": 1, "order_id": 10, "sku_key": null, "amount": 5}),
+        json!({"id": 1, "order_id": 11, "sku_key": "x", "amount": 6}),
+    ];
+    // `id` repeats, `sku_key` is null once, `amount` is not named like a key.
+    assert_eq!(keys(candidate_keys(&rows)), ["order_id"]);
+    assert!(candidate_keys(&[]).is_empty());
+}
+
+#[test]
+fn test_candidates_fall_back_to_pairs() {
+    let rows = vec![
+        json!({"order_id": 1, "line": 1, "paid": true}),
+        json!({"order_id": 1, "line": 2, "paid": true}),
+        json!({"order_id": 2, "line": 1, "paid": false}),
+    ];
+    assert_eq!(keys(candidate_keys(&rows)), ["order_id+line"]);
+    match infer_key("order_lines", &rows) {
+        Err(ApitapError::MergeError(msg)) => {
+            assert!(msg.contains("(order_id, line)"), "{msg}")
+        }
+        other => panic!("expected a merge error, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_missing_key_error_names_candidates() {
+    let rows = vec![json!({"id": 1}), json!({"id": 2})];
+    let ApitapError::MergeError(msg) = missing_key_error("orders", &rows) else {
+        panic!("expected a merge error");
+    };
+    assert!(
+        msg.contains("merging into orders needs primary_key_in_dest"),
+        "{msg}"
+    );
+    assert!(
+        msg.contains("candidates from the first 2 rows: id"),
+        "{msg}"
+    );
+
+    let rows = vec![json!({"name": "a"}), json!({"name": "a"})];
+    let ApitapError::MergeError(msg) = missing_key_error("orders", &rows) else {
+        panic!("expected a merge error");
+    };
+    assert!(
+        msg.contains("no column of the first 2 rows looks like a key"),
+        "{msg}"
+    );
+}
+
+async fn merge(writer: &SqliteWriter, rows: Vec<Value>) -> apitap::errors::Result<()> {
+    let stream = QueryResultStream {
+        table_name: "orders".to_string(),
+        data: Box::pin(futures::stream::iter(rows.into_iter().map(Ok))),
+    };
+    writer.write_stream(stream, WriteMode::Merge).await
+}
+
+#[tokio::test]
+async fn test_sqlite_merge_without_key() {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+
+    let strict = SqliteWriter::new(pool.clone(), "orders");
+    let err = merge(&strict, vec![json!({"id": 1, "status": "new"})])
+        .await
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("candidates from the first 1 rows: id"));
+
+    let inferring = SqliteWriter::new(pool.clone(), "orders").with_infer_primary_key(true);
+    merge(
+        &inferring,
+        vec![
+            json!({"id": 1, "status": "new"}),
+            json!({"id": 2, "status": "new"}),
+        ],
+    )
+    .await
+    .unwrap();
+    assert_eq!(inferring.key(), Some("id"));
+    merge(&inferring, vec![json!({"id": 1, "status": "paid"})])
+        .await
+        .unwrap();
+
+    let rows: Vec<(i64, String)> = sqlx::query_as("SELECT id, status FROM orders ORDER BY id")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(rows, [(1, "paid".to_string()), (2, "new".to_string())]);
+}
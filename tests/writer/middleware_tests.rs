@@ -0,0 +1,305 @@
+// Tests for Writer Middleware
+//
+// These tests cover:
+// - Masking and metadata columns rewriting batches before the sink
+// - Row hashes of the non-key columns
+// - Row counting and hook order around a write
+// - Chains built from source config
+
+use apitap::errors::Result;
+use apitap::pipeline::Config;
+use apitap::utils::datafusion_ext::QueryResult;
+use apitap::writer::middleware::{
+    InjectMetadata, MaskColumns, MiddlewareChain, RowCounter, RowHash, WriteStats, WriterMiddleware,
+};
+use apitap::writer::{DataWriter, WriteMode};
+use async_trait::async_trait;
+use datafusion::arrow::array::{Array, Int64Array, RecordBatch, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::execution::SendableRecordBatchStream;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use futures::{stream, StreamExt};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+fn sample_batch() -> RecordBatch {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("email", DataType::Utf8, true),
+    ]));
+    RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int64Array::from(vec![1, 2, 3])),
+            Arc::new(StringArray::from(vec![
+                Some("a@example.com"),
+                None,
+                Some("c@example.com"),
+            ])),
+        ],
+    )
+    .unwrap()
+}
+
+fn batch_stream(batches: Vec<RecordBatch>) -> SendableRecordBatchStream {
+    let schema = batches[0].schema();
+    Box::pin(RecordBatchStreamAdapter::new(
+        schema,
+        stream::iter(batches.into_iter().map(Ok)),
+    ))
+}
+
+/// Sink that keeps every batch it receives.
+#[derive(Default)]
+struct CaptureWriter {
+    batches: Mutex<Vec<RecordBatch>>,
+}
+
+#[async_trait]
+impl DataWriter for CaptureWriter {
+    async fn write(&self, _result: QueryResult) -> Result<()> {
+        Ok(())
+    }
+
+    async fn write_batches(
+        &self,
+        _table_name: &str,
+        mut batches: SendableRecordBatchStream,
+        _write_mode: WriteMode,
+    ) -> Result<()> {
+        while let Some(batch) = batches.next().await {
+            self.batches.lock().unwrap().push(batch?);
+        }
+        Ok(())
+    }
+}
+
+/// Records hook calls so their order can be asserted.
+struct Trace {
+    label: &'static str,
+    log: Arc<Mutex<Vec<String>>>,
+}
+
+#[async_trait]
+impl WriterMiddleware for Trace {
+    fn name(&self) -> &'static str {
+        self.label
+    }
+
+    async fn before_write(&self, _table: &str) -> Result<()> {
+        self.log
+            .lock()
+            .unwrap()
+            .push(format!("before:{}", self.label));
+        Ok(())
+    }
+
+    async fn after_write(&self, _table: &str, stats: &WriteStats, ok: bool) -> Result<()> {
+        self.log
+            .lock()
+            .unwrap()
+            .push(format!("after:{}:{}:{}", self.label, stats.rows, ok));
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_mask_and_metadata() {
+    let sink = Arc::new(CaptureWriter::default());
+    let writer = MiddlewareChain::new()
+        .with(MaskColumns::new(vec!["email".to_string()]))
+        .with(InjectMetadata::new(BTreeMap::from([(
+            "_source".to_string(),
+            "users_api".to_string(),
+        )])))
+        .wrap(sink.clone());
+
+    writer
+        .write_batches(
+            "users",
+            batch_stream(vec![sample_batch()]),
+            WriteMode::Append,
+        )
+        .await
+        .unwrap();
+
+    let batches = sink.batches.lock().unwrap();
+    let batch = &batches[0];
+    let names: Vec<_> = batch
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| f.name().clone())
+        .collect();
+    assert_eq!(names, vec!["id", "email", "_source"]);
+
+    let email = batch
+        .column(1)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert_eq!(email.value(0), "***");
+    assert!(email.is_null(1));
+    assert_eq!(email.value(2), "***");
+
+    let source = batch
+        .column(2)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert!((0..3).all(|i| source.value(i) == "users_api"));
+}
+
+#[tokio::test]
+async fn test_row_counter_and_hook_order() {
+    let sink = Arc::new(CaptureWriter::default());
+    let counter = Arc::new(RowCounter::new());
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let writer = MiddlewareChain::new()
+        .with(Trace {
+            label: "outer",
+            log: Arc::clone(&log),
+        })
+        .with_shared(counter.clone())
+        .with(Trace {
+            label: "inner",
+            log: Arc::clone(&log),
+        })
+        .wrap(sink);
+
+    for _ in 0..2 {
+        writer
+            .write_batches(
+                "users",
+                batch_stream(vec![sample_batch(), sample_batch()]),
+                WriteMode::Merge,
+            )
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(counter.rows(), 12);
+    assert_eq!(counter.writes(), 2);
+    assert_eq!(
+        log.lock().unwrap()[..4],
+        [
+            "before:outer",
+            "before:inner",
+            "after:inner:6:true",
+            "after:outer:6:true"
+        ]
+    );
+}
+
+fn row_hashes(batch: &RecordBatch) -> Vec<String> {
+    let hashes = batch
+        .column_by_name("_row_hash")
+        .unwrap()
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    hashes.iter().map(|h| h.unwrap().to_string()).collect()
+}
+
+#[test]
+fn test_row_hash_ignores_key_excluded_and_column_order() {
+    let step = RowHash::new(["id".to_string(), "seen_at".to_string()]);
+    let batch = |ids: Vec<i64>, names: Vec<Option<&str>>, seen: Vec<&str>, swap: bool| {
+        let mut fields = vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("seen_at", DataType::Utf8, false),
+        ];
+        let mut columns: Vec<Arc<dyn Array>> = vec![
+            Arc::new(Int64Array::from(ids)),
+            Arc::new(StringArray::from(names)),
+            Arc::new(StringArray::from(seen)),
+        ];
+        if swap {
+            fields.reverse();
+            columns.reverse();
+        }
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).unwrap()
+    };
+
+    let first = step
+        .map_batch(
+            "users",
+            batch(
+                vec![1, 2, 3],
+                vec![Some("a"), Some(""), None],
+                vec!["t1"; 3],
+                false,
+            ),
+        )
+        .unwrap();
+    let hashes = row_hashes(&first);
+    assert_eq!(first.schema().field(3).name(), "_row_hash");
+    assert_eq!(hashes[0].len(), 64);
+    // An empty string and a null hash differently.
+    assert_ne!(hashes[1], hashes[2]);
+
+    // Other keys, a bumped excluded column and reordered columns: same hashes.
+    let again = step
+        .map_batch(
+            "users",
+            batch(
+                vec![7, 8, 9],
+                vec![Some("a"), Some(""), None],
+                vec!["t2"; 3],
+                true,
+            ),
+        )
+        .unwrap();
+    assert_eq!(row_hashes(&again), hashes);
+
+    // Re-hashing replaces the column instead of adding a second one.
+    let rehashed = step.map_batch("users", again).unwrap();
+    assert_eq!(rehashed.num_columns(), 4);
+    assert_eq!(row_hashes(&rehashed), hashes);
+
+    let changed = step
+        .map_batch("users", batch(vec![1], vec![Some("b")], vec!["t1"], false))
+        .unwrap();
+    assert_ne!(row_hashes(&changed)[0], hashes[0]);
+}
+
+#[test]
+fn test_chain_from_source_config() {
+    let config_yaml = r#"
+sources:
+  - name: users
+    url: https://api.example.com/users
+    data_path: null
+    primary_key_in_dest: id
+    retry: {max_attempts: 3, max_delay_secs: 10, min_delay_secs: 1}
+    middleware:
+      mask_columns: [email]
+      metadata:
+        _source: users_api
+      row_hash: true
+      row_hash_exclude: [updated_at]
+      timing: true
+  - name: plain
+    url: https://api.example.com/plain
+    data_path: null
+    primary_key_in_dest: id
+    retry: {max_attempts: 3, max_delay_secs: 10, min_delay_secs: 1}
+targets: []
+"#;
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+
+    let chain = MiddlewareChain::from_source(config.source("users").unwrap());
+    assert_eq!(
+        format!("{chain:?}"),
+        r#"["mask_columns", "inject_metadata", "row_hash", "timing"]"#
+    );
+    assert!(MiddlewareChain::from_source(config.source("plain").unwrap()).is_empty());
+
+    let schema = chain
+        .output_schema("users", sample_batch().schema())
+        .unwrap();
+    assert_eq!(schema.field(1).data_type(), &DataType::Utf8);
+    assert_eq!(schema.field(2).name(), "_source");
+    assert_eq!(schema.field(3).name(), "_row_hash");
+}
@@ -1,2 +1,18 @@
+mod coercion_tests;
+mod compress_tests;
+mod debug_tests;
+mod delta_tests;
+mod file_tests;
+mod kafka_tests;
+mod key_inference_tests;
+mod metadata_columns_tests;
+mod middleware_tests;
+mod mongodb_tests;
+mod parquet_tests;
 mod postgres_tests;
+mod redshift_tests;
+mod rollup_tests;
+mod snowflake_tests;
+mod sqlite_tests;
+mod webhook_tests;
 mod writer_tests;
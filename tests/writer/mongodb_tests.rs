@@ -0,0 +1,90 @@
+// Tests for MongoDB Writer
+//
+// These tests cover:
+// - JSON to BSON conversion keeping nested structure
+// - Bulk upsert command construction
+// - Connection failures surfacing as a write error
+
+use apitap::utils::datafusion_ext::QueryResultStream;
+use apitap::writer::mongodb::MongoWriter;
+use apitap::writer::{DataWriter, WriteMode};
+use mongodb::bson::{doc, Bson};
+use serde_json::json;
+
+#[test]
+fn test_to_document_preserves_nesting() {
+    let row = json!({
+        "id": 1,
+        "name": "Leanne",
+        "address": {"city": "Gwenborough", "geo": {"lat": "-37.3159"}},
+        "tags": ["a", "b"],
+        "score": 1.5,
+        "deleted": null
+    });
+    let d = MongoWriter::to_document(&row).unwrap();
+
+    assert_eq!(d.get_i64("id").unwrap(), 1);
+    let address = d.get_document("address").unwrap();
+    assert_eq!(address.get_str("city").unwrap(), "Gwenborough");
+    assert_eq!(
+        address.get_document("geo").unwrap().get_str("lat").unwrap(),
+        "-37.3159"
+    );
+    assert_eq!(
+        d.get_array("tags").unwrap(),
+        &vec![Bson::String("a".into()), Bson::String("b".into())]
+    );
+    assert_eq!(d.get_f64("score").unwrap(), 1.5);
+    assert_eq!(d.get("deleted"), Some(&Bson::Null));
+}
+
+#[test]
+fn test_to_document_rejects_non_objects() {
+    assert!(MongoWriter::to_document(&json!([1, 2])).is_err());
+    assert!(MongoWriter::to_document(&json!("x")).is_err());
+}
+
+#[test]
+fn test_upsert_command() {
+    let docs = vec![
+        doc! {"id": 1, "v": "old"},
+        doc! {"id": 2, "v": "b"},
+        doc! {"id": 1, "v": "new"},
+    ];
+    let cmd = MongoWriter::upsert_command("users", "id", docs);
+
+    assert_eq!(cmd.get_str("update").unwrap(), "users");
+    assert!(!cmd.get_bool("ordered").unwrap());
+    let updates = cmd.get_array("updates").unwrap();
+    // Duplicate keys within a batch collapse to the last row.
+    assert_eq!(updates.len(), 2);
+
+    let first = updates[0].as_document().unwrap();
+    assert_eq!(first.get_document("q").unwrap(), &doc! {"id": 1});
+    assert_eq!(
+        first.get_document("u").unwrap(),
+        &doc! {"id": 1, "v": "new"}
+    );
+    assert!(first.get_bool("upsert").unwrap());
+    assert_eq!(
+        updates[1].as_document().unwrap().get_document("q").unwrap(),
+        &doc! {"id": 2}
+    );
+}
+
+#[tokio::test]
+async fn test_unreachable_server_fails_write() {
+    let client =
+        mongodb::Client::with_uri_str("mongodb://127.0.0.1:1/?serverSelectionTimeoutMS=200")
+            .await
+            .unwrap();
+    let writer = MongoWriter::new(client.database("apitap"), "users")
+        .with_primary_key_single(Some("id".to_string()));
+
+    let rows = vec![Ok(json!({"id": 1}))];
+    let stream = QueryResultStream {
+        table_name: "users".to_string(),
+        data: Box::pin(futures::stream::iter(rows)),
+    };
+    assert!(writer.write_stream(stream, WriteMode::Merge).await.is_err());
+}
@@ -0,0 +1,157 @@
+// Tests for Parquet Writer
+//
+// These tests cover:
+// - Compression mapping
+// - Hive partition directory naming
+// - Writing batches to a local directory (with partitions and rollover)
+
+use apitap::writer::parquet::{ParquetCompression, ParquetWriter};
+use apitap::writer::{DataWriter, WriteMode};
+use datafusion::arrow::array::{Int64Array, RecordBatch, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use datafusion::parquet::basic::Compression;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use futures::stream;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+fn sample_batch() -> RecordBatch {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("country", DataType::Utf8, true),
+    ]));
+    RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int64Array::from(vec![1, 2, 3, 4])),
+            Arc::new(StringArray::from(vec![
+                Some("id"),
+                Some("sg"),
+                Some("id"),
+                None,
+            ])),
+        ],
+    )
+    .unwrap()
+}
+
+fn batch_stream(batches: Vec<RecordBatch>) -> datafusion::execution::SendableRecordBatchStream {
+    let schema = batches[0].schema();
+    Box::pin(RecordBatchStreamAdapter::new(
+        schema,
+        stream::iter(batches.into_iter().map(Ok)),
+    ))
+}
+
+fn parquet_files(dir: &Path) -> Vec<PathBuf> {
+    let mut out = Vec::new();
+    for entry in walkdir::WalkDir::new(dir) {
+        let entry = entry.unwrap();
+        if entry.path().extension().is_some_and(|e| e == "parquet") {
+            out.push(entry.path().to_path_buf());
+        }
+    }
+    out.sort();
+    out
+}
+
+fn count_rows(path: &Path) -> usize {
+    let file = std::fs::File::open(path).unwrap();
+    ParquetRecordBatchReaderBuilder::try_new(file)
+        .unwrap()
+        .build()
+        .unwrap()
+        .map(|b| b.unwrap().num_rows())
+        .sum()
+}
+
+#[test]
+fn test_compression_mapping() {
+    assert_eq!(ParquetCompression::default(), ParquetCompression::Snappy);
+    assert_eq!(
+        ParquetCompression::None.to_parquet(),
+        Compression::UNCOMPRESSED
+    );
+    assert_eq!(ParquetCompression::Lz4.to_parquet(), Compression::LZ4_RAW);
+}
+
+#[test]
+fn test_partition_segment() {
+    assert_eq!(
+        ParquetWriter::partition_segment("country", Some("id")),
+        "country=id"
+    );
+    assert_eq!(
+        ParquetWriter::partition_segment("country", Some("")),
+        "country=__HIVE_DEFAULT_PARTITION__"
+    );
+    assert_eq!(
+        ParquetWriter::partition_segment("country", None),
+        "country=__HIVE_DEFAULT_PARTITION__"
+    );
+}
+
+#[tokio::test]
+async fn test_write_batches_single_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let writer = ParquetWriter::from_location(dir.path().to_str().unwrap(), "users").unwrap();
+
+    writer
+        .write_batches(
+            "users",
+            batch_stream(vec![sample_batch()]),
+            WriteMode::Append,
+        )
+        .await
+        .unwrap();
+
+    let files = parquet_files(&dir.path().join("users"));
+    assert_eq!(files.len(), 1);
+    assert_eq!(count_rows(&files[0]), 4);
+}
+
+#[tokio::test]
+async fn test_write_batches_partitioned() {
+    let dir = tempfile::tempdir().unwrap();
+    let writer = ParquetWriter::from_location(dir.path().to_str().unwrap(), "users")
+        .unwrap()
+        .with_partition_by(vec!["country".to_string()]);
+
+    writer
+        .write_batches(
+            "users",
+            batch_stream(vec![sample_batch()]),
+            WriteMode::Append,
+        )
+        .await
+        .unwrap();
+
+    let base = dir.path().join("users");
+    assert_eq!(count_rows(&parquet_files(&base.join("country=id"))[0]), 2);
+    assert_eq!(count_rows(&parquet_files(&base.join("country=sg"))[0]), 1);
+    assert_eq!(
+        parquet_files(&base.join("country=__HIVE_DEFAULT_PARTITION__")).len(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_write_batches_rolls_over_on_size() {
+    let dir = tempfile::tempdir().unwrap();
+    let writer = ParquetWriter::from_location(dir.path().to_str().unwrap(), "users")
+        .unwrap()
+        .with_compression(ParquetCompression::Zstd)
+        .with_max_file_size(1);
+
+    let batches = vec![sample_batch(), sample_batch(), sample_batch()];
+    writer
+        .write_batches("users", batch_stream(batches), WriteMode::Append)
+        .await
+        .unwrap();
+
+    let files = parquet_files(&dir.path().join("users"));
+    assert_eq!(files.len(), 3);
+    let total: usize = files.iter().map(|f| count_rows(f)).sum();
+    assert_eq!(total, 12);
+}
@@ -0,0 +1,175 @@
+use apitap::errors::Result;
+use apitap::pipeline::Config;
+use apitap::utils::datafusion_ext::QueryResult;
+use apitap::writer::middleware::MiddlewareChain;
+use apitap::writer::rollup::{Rollup, RollupCollector};
+use apitap::writer::{DataWriter, WriteMode};
+use async_trait::async_trait;
+use datafusion::arrow::array::{Array, Float64Array, Int64Array, RecordBatch, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::execution::SendableRecordBatchStream;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use futures::{stream, StreamExt};
+use std::sync::{Arc, Mutex};
+
+/// Sink that keeps every batch it receives and counts commits.
+#[derive(Default)]
+struct CaptureWriter {
+    batches: Mutex<Vec<RecordBatch>>,
+    commits: Mutex<usize>,
+}
+
+#[async_trait]
+impl DataWriter for CaptureWriter {
+    async fn write(&self, _result: QueryResult) -> Result<()> {
+        Ok(())
+    }
+
+    async fn write_batches(
+        &self,
+        _table_name: &str,
+        mut batches: SendableRecordBatchStream,
+        _write_mode: WriteMode,
+    ) -> Result<()> {
+        while let Some(batch) = batches.next().await {
+            self.batches.lock().unwrap().push(batch?);
+        }
+        Ok(())
+    }
+
+    async fn commit(&self) -> Result<()> {
+        *self.commits.lock().unwrap() += 1;
+        Ok(())
+    }
+}
+
+fn page(days: Vec<&str>, amounts: Vec<f64>, notes: Option<Vec<Option<&str>>>) -> RecordBatch {
+    let mut fields = vec![
+        Field::new("day", DataType::Utf8, false),
+        Field::new("amount", DataType::Float64, false),
+    ];
+    let mut columns: Vec<Arc<dyn Array>> = vec![
+        Arc::new(StringArray::from(days)),
+        Arc::new(Float64Array::from(amounts)),
+    ];
+    if let Some(notes) = notes {
+        fields.push(Field::new("note", DataType::Utf8, true));
+        columns.push(Arc::new(StringArray::from(notes)));
+    }
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).unwrap()
+}
+
+fn batch_stream(batch: RecordBatch) -> SendableRecordBatchStream {
+    Box::pin(RecordBatchStreamAdapter::new(
+        batch.schema(),
+        stream::iter(vec![Ok(batch)]),
+    ))
+}
+
+#[tokio::test]
+async fn test_rollup_aggregates_all_pages() {
+    let rows = Arc::new(CaptureWriter::default());
+    let daily = Arc::new(CaptureWriter::default());
+    let collector = Arc::new(RollupCollector::new("orders"));
+    let writer = MiddlewareChain::new()
+        .with_shared(collector.clone())
+        .wrap(rows.clone());
+
+    // Pages infer different schemas: the second one has an extra column.
+    for batch in [
+        page(vec!["2024-01-01", "2024-01-02"], vec![10.0, 5.0], None),
+        page(
+            vec!["2024-01-01", "2024-01-01"],
+            vec![2.5, 1.5],
+            Some(vec![Some("gift"), None]),
+        ),
+    ] {
+        writer
+            .write_batches("orders", batch_stream(batch), WriteMode::Append)
+            .await
+            .unwrap();
+    }
+    assert_eq!(collector.rows(), 4);
+    assert_eq!(rows.batches.lock().unwrap().len(), 2);
+
+    let rollups = vec![Rollup {
+        table: "orders_daily".to_string(),
+        sql: "SELECT day, count(*) AS orders, sum(amount) AS total, count(note) AS notes \
+              FROM orders GROUP BY day ORDER BY day"
+            .to_string(),
+        writer: daily.clone(),
+        write_mode: WriteMode::Merge,
+    }];
+    collector.write_rollups(&rollups).await.unwrap();
+
+    let out = daily.batches.lock().unwrap().clone();
+    let out = datafusion::arrow::compute::concat_batches(&out[0].schema(), &out).unwrap();
+    let days = out
+        .column(0)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    let orders = out.column(1).as_any().downcast_ref::<Int64Array>().unwrap();
+    let totals = out
+        .column(2)
+        .as_any()
+        .downcast_ref::<Float64Array>()
+        .unwrap();
+    let notes = out.column(3).as_any().downcast_ref::<Int64Array>().unwrap();
+    assert_eq!(days.value(0), "2024-01-01");
+    assert_eq!(
+        (orders.value(0), totals.value(0), notes.value(0)),
+        (3, 14.0, 1)
+    );
+    assert_eq!(days.value(1), "2024-01-02");
+    assert_eq!(
+        (orders.value(1), totals.value(1), notes.value(1)),
+        (1, 5.0, 0)
+    );
+    assert_eq!(*daily.commits.lock().unwrap(), 1);
+
+    // Batches are released once the rollups ran.
+    assert_eq!(collector.rows(), 0);
+}
+
+#[tokio::test]
+async fn test_rollup_skipped_without_rows() {
+    let daily = Arc::new(CaptureWriter::default());
+    let collector = RollupCollector::new("orders");
+    let rollups = vec![Rollup {
+        table: "orders_daily".to_string(),
+        sql: "SELECT count(*) AS n FROM orders".to_string(),
+        writer: daily.clone(),
+        write_mode: WriteMode::Append,
+    }];
+    collector.write_rollups(&rollups).await.unwrap();
+    assert!(daily.batches.lock().unwrap().is_empty());
+    assert_eq!(*daily.commits.lock().unwrap(), 0);
+}
+
+#[test]
+fn test_rollups_config() {
+    let config_yaml = r#"
+sources:
+  - name: orders
+    url: https://api.example.com/orders
+    data_path: null
+    primary_key_in_dest: id
+    retry: {max_attempts: 3, max_delay_secs: 10, min_delay_secs: 1}
+    rollups:
+      - table: orders_daily
+        sql: SELECT day, count(*) AS orders FROM orders GROUP BY day
+        primary_key: day
+      - table: orders_by_status
+        sql: SELECT status, count(*) AS orders FROM orders GROUP BY status
+        sink: lake
+targets: []
+"#;
+    let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+    let rollups = &config.source("orders").unwrap().rollups;
+    assert_eq!(rollups.len(), 2);
+    assert_eq!(rollups[0].write_mode(), WriteMode::Merge);
+    assert_eq!(rollups[0].sink, None);
+    assert_eq!(rollups[1].write_mode(), WriteMode::Append);
+    assert_eq!(rollups[1].sink.as_deref(), Some("lake"));
+}
@@ -0,0 +1,168 @@
+// Tests for Snowflake Writer
+//
+// These tests cover:
+// - JWT account name normalization
+// - Type mapping from PgType
+// - Generated DDL, COPY INTO and MERGE statements
+// - Gzip NDJSON staging and per-writer stage tables
+
+use apitap::writer::postgres::PgType;
+use apitap::writer::redshift::StagingCleanup;
+use apitap::writer::snowflake::{
+    SnowflakeConnectOptions, SnowflakeCredentials, SnowflakeSession, SnowflakeStaging,
+    SnowflakeWriter,
+};
+use flate2::read::GzDecoder;
+use object_store::memory::InMemory;
+use object_store::path::Path as ObjectPath;
+use object_store::ObjectStore;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::sync::Arc;
+
+fn writer(table: &str) -> SnowflakeWriter {
+    writer_with_store(table, Arc::new(InMemory::new()))
+}
+
+fn writer_with_store(table: &str, store: Arc<dyn ObjectStore>) -> SnowflakeWriter {
+    let session = SnowflakeSession::new(
+        SnowflakeConnectOptions {
+            account: "xy12345.us-east-1".to_string(),
+            user: "loader".to_string(),
+            warehouse: None,
+            database: "ANALYTICS".to_string(),
+            schema: "PUBLIC".to_string(),
+            role: None,
+        },
+        SnowflakeCredentials::Password("secret".to_string()),
+    );
+    let staging = SnowflakeStaging {
+        store,
+        prefix: ObjectPath::from("staging"),
+        stage: "@raw.apitap_stage".to_string(),
+        cleanup: StagingCleanup::OnSuccess,
+    };
+    SnowflakeWriter::new(Arc::new(session), staging, table)
+}
+
+fn schema() -> BTreeMap<String, PgType> {
+    let mut s = BTreeMap::new();
+    s.insert("id".to_string(), PgType::BigInt);
+    s.insert("name".to_string(), PgType::Text);
+    s.insert("meta".to_string(), PgType::Jsonb);
+    s
+}
+
+#[test]
+fn test_jwt_account_name() {
+    assert_eq!(
+        SnowflakeSession::jwt_account_name("xy12345.us-east-1"),
+        "XY12345"
+    );
+    assert_eq!(
+        SnowflakeSession::jwt_account_name("myorg-myaccount"),
+        "MYORG-MYACCOUNT"
+    );
+}
+
+#[test]
+fn test_sf_type_mapping() {
+    assert_eq!(SnowflakeWriter::sf_type(&PgType::Text), "VARCHAR");
+    assert_eq!(SnowflakeWriter::sf_type(&PgType::Boolean), "BOOLEAN");
+    assert_eq!(SnowflakeWriter::sf_type(&PgType::BigInt), "NUMBER(38,0)");
+    assert_eq!(SnowflakeWriter::sf_type(&PgType::Double), "FLOAT");
+    assert_eq!(SnowflakeWriter::sf_type(&PgType::Jsonb), "VARIANT");
+}
+
+#[test]
+fn test_create_table_sql_with_primary_key() {
+    let w = writer("raw.users").with_primary_key_single(Some("id".to_string()));
+    let sql = w.create_table_sql(&schema());
+    assert!(sql.starts_with(r#"CREATE TABLE IF NOT EXISTS "raw"."users""#));
+    assert!(sql.contains(r#""id" NUMBER(38,0)"#));
+    assert!(sql.contains(r#""meta" VARIANT"#));
+    assert!(sql.contains(r#"PRIMARY KEY ("id")"#));
+}
+
+#[test]
+fn test_copy_sql_loads_staged_file() {
+    let w = writer("raw.users");
+    let sql = w.copy_sql(&schema(), &ObjectPath::from("raw/users/batch-1.json.gz"));
+    assert_eq!(
+        sql,
+        r#"COPY INTO "raw"."users" ("id", "meta", "name") FROM (SELECT $1:"id"::NUMBER(38,0), $1:"meta", $1:"name"::VARCHAR FROM @raw.apitap_stage/raw/users/batch-1.json.gz) FILE_FORMAT = (TYPE = JSON COMPRESSION = GZIP)"#
+    );
+}
+
+#[test]
+fn test_stage_table_is_per_writer() {
+    let a = writer("raw.users");
+    let b = writer("raw.users");
+    assert!(a.stage_table().starts_with(r#""users_apitap_stage_"#));
+    assert_ne!(a.stage_table(), b.stage_table());
+    let sql = a.copy_to_stage_table_sql(&ObjectPath::from("raw/users/batch-1.json.gz"));
+    assert_eq!(
+        sql,
+        format!(
+            "COPY INTO {} FROM @raw.apitap_stage/raw/users/batch-1.json.gz FILE_FORMAT = (TYPE = JSON COMPRESSION = GZIP)",
+            a.stage_table()
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_stage_batch_writes_gzip_ndjson() {
+    let store = Arc::new(InMemory::new());
+    let w = writer_with_store("raw.users", store.clone());
+    let rows = vec![json!({"id": 1, "name": "a"}), json!({"id": 2, "name": "b"})];
+    let relative = w.stage_batch(&rows).await.unwrap();
+    assert!(relative.as_ref().starts_with("raw/users/batch-"));
+    assert!(relative.as_ref().ends_with(".json.gz"));
+
+    let path = ObjectPath::from(format!("staging/{relative}"));
+    let bytes = store.get(&path).await.unwrap().bytes().await.unwrap();
+    let mut text = String::new();
+    GzDecoder::new(&bytes[..])
+        .read_to_string(&mut text)
+        .unwrap();
+    let staged: Vec<Value> = text
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(staged, rows);
+}
+
+#[test]
+fn test_merge_sql_updates_non_key_columns() {
+    let w = writer("users").with_primary_key_single(Some("id".to_string()));
+    let sql = w.merge_sql(&schema()).unwrap();
+    assert!(sql.starts_with(r#"MERGE INTO "users" AS t"#));
+    assert!(sql.contains(&format!(
+        r#"USING (SELECT raw:"id"::NUMBER(38,0) AS "id", raw:"meta" AS "meta", raw:"name"::VARCHAR AS "name" FROM {}) AS s"#,
+        w.stage_table()
+    )));
+    assert!(sql.contains(r#"ON t."id" = s."id""#));
+    assert!(
+        sql.contains(r#"WHEN MATCHED THEN UPDATE SET t."meta" = s."meta", t."name" = s."name""#)
+    );
+    assert!(!sql.contains(r#"t."id" = s."id","#));
+    assert!(sql.contains("WHEN NOT MATCHED THEN INSERT"));
+}
+
+#[test]
+fn test_merge_sql_skips_unchanged_row_hash() {
+    let w = writer("users").with_primary_key_single(Some("id".to_string()));
+    let mut schema = schema();
+    schema.insert("_row_hash".to_string(), PgType::Text);
+    let sql = w.merge_sql(&schema).unwrap();
+    assert!(sql.contains(
+        r#"WHEN MATCHED AND t."_row_hash" IS DISTINCT FROM s."_row_hash" THEN UPDATE SET"#
+    ));
+}
+
+#[test]
+fn test_merge_sql_requires_primary_key() {
+    let w = writer("users");
+    assert!(w.merge_sql(&schema()).is_err());
+}
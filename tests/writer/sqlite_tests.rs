@@ -0,0 +1,251 @@
+// Tests for SQLite Writer
+//
+// These tests cover:
+// - Type mapping and generated SQL
+// - Auto table creation, append and upsert against an in-memory database
+// - Truncate
+
+use apitap::utils::datafusion_ext::QueryResultStream;
+use apitap::writer::postgres::{PgType, PostgresWriter};
+use apitap::writer::sqlite::SqliteWriter;
+use apitap::writer::{DataWriter, MergeStats, WriteMode};
+use serde_json::{json, Value};
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::SqlitePool;
+use std::collections::BTreeMap;
+
+async fn memory_pool() -> SqlitePool {
+    SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap()
+}
+
+fn rows_stream(rows: Vec<Value>) -> QueryResultStream {
+    QueryResultStream {
+        table_name: "users".to_string(),
+        data: Box::pin(futures::stream::iter(rows.into_iter().map(Ok))),
+    }
+}
+
+#[test]
+fn test_sqlite_type_mapping() {
+    assert_eq!(SqliteWriter::sqlite_type(&PgType::BigInt), "INTEGER");
+    assert_eq!(SqliteWriter::sqlite_type(&PgType::Boolean), "INTEGER");
+    assert_eq!(SqliteWriter::sqlite_type(&PgType::Double), "REAL");
+    assert_eq!(SqliteWriter::sqlite_type(&PgType::Jsonb), "TEXT");
+}
+
+#[tokio::test]
+async fn test_insert_sql_upsert_clause() {
+    let writer = SqliteWriter::new(memory_pool().await, "public.users")
+        .with_primary_key_single(Some("id".to_string()));
+    assert_eq!(writer.table_name, "users");
+
+    let rows = vec![json!({"id": 1, "name": "a"})];
+    let schema: BTreeMap<String, PgType> = PostgresWriter::analyze_schema(&rows, 10).unwrap();
+
+    let sql = writer.insert_sql(&schema, 2, true).unwrap();
+    assert_eq!(
+        sql,
+        r#"INSERT INTO "users" ("id", "name") VALUES (?, ?), (?, ?) ON CONFLICT ("id") DO UPDATE SET "name" = excluded."name""#
+    );
+
+    let no_pk = SqliteWriter::new(memory_pool().await, "users");
+    assert!(no_pk.insert_sql(&schema, 1, true).is_err());
+}
+
+#[tokio::test]
+async fn test_write_stream_creates_table_and_upserts() {
+    let pool = memory_pool().await;
+    let writer = SqliteWriter::new(pool.clone(), "users")
+        .with_primary_key_single(Some("id".to_string()))
+        .with_batch_size(2);
+
+    writer
+        .write_stream(
+            rows_stream(vec![
+                json!({"id": 1, "name": "alice", "active": true, "tags": ["a"]}),
+                json!({"id": 2, "name": "bob", "active": false, "tags": []}),
+                json!({"id": 3, "name": "carol", "active": true, "tags": null}),
+            ]),
+            WriteMode::Append,
+        )
+        .await
+        .unwrap();
+
+    writer
+        .write_stream(
+            rows_stream(vec![
+                json!({"id": 2, "name": "bobby", "active": true, "tags": ["b"]}),
+                json!({"id": 4, "name": "dave", "active": false, "tags": []}),
+            ]),
+            WriteMode::Merge,
+        )
+        .await
+        .unwrap();
+
+    let rows: Vec<(i64, String, i64, Option<String>)> =
+        sqlx::query_as(r#"SELECT "id", "name", "active", "tags" FROM "users" ORDER BY "id""#)
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+
+    assert_eq!(rows.len(), 4);
+    assert_eq!(
+        rows[1],
+        (2, "bobby".to_string(), 1, Some(r#"["b"]"#.to_string()))
+    );
+    assert_eq!(rows[2].3, None);
+}
+
+#[tokio::test]
+async fn test_mutation_report_counts_changed_and_identical_rows() {
+    let pool = memory_pool().await;
+    let writer = SqliteWriter::new(pool, "users")
+        .with_primary_key_single(Some("id".to_string()))
+        .with_mutation_report(true);
+
+    writer
+        .write_stream(
+            rows_stream(vec![
+                json!({"id": 1, "name": "alice", "score": 1.5}),
+                json!({"id": 2, "name": "bob", "score": null}),
+            ]),
+            WriteMode::Merge,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        writer.merge_stats(),
+        Some(MergeStats {
+            inserted: 2,
+            changed: 0,
+            unchanged: 0
+        })
+    );
+
+    writer
+        .write_stream(
+            rows_stream(vec![
+                json!({"id": 1, "name": "alice", "score": 2.5}),
+                json!({"id": 2, "name": "bob", "score": null}),
+                json!({"id": 3, "name": "carol", "score": 0.5}),
+            ]),
+            WriteMode::Merge,
+        )
+        .await
+        .unwrap();
+    let stats = writer.merge_stats().unwrap();
+    assert_eq!(
+        stats,
+        MergeStats {
+            inserted: 3,
+            changed: 1,
+            unchanged: 1
+        }
+    );
+    assert_eq!(stats.churn(), Some(0.5));
+
+    // Appends are not merges and are not classified.
+    let plain = SqliteWriter::new(memory_pool().await, "users");
+    assert_eq!(plain.merge_stats(), None);
+}
+
+#[tokio::test]
+async fn test_upsert_skips_rows_with_unchanged_row_hash() {
+    let pool = memory_pool().await;
+    let writer =
+        SqliteWriter::new(pool.clone(), "users").with_primary_key_single(Some("id".to_string()));
+
+    let rows = vec![json!({"id": 1, "_row_hash": "h1", "updated_at": "t1"})];
+    let schema = PostgresWriter::analyze_schema(&rows, 10).unwrap();
+    assert!(writer
+        .insert_sql(&schema, 1, true)
+        .unwrap()
+        .ends_with(r#"WHERE "_row_hash" IS NOT excluded."_row_hash""#));
+
+    writer
+        .write_stream(rows_stream(rows), WriteMode::Merge)
+        .await
+        .unwrap();
+    writer
+        .write_stream(
+            rows_stream(vec![
+                json!({"id": 1, "_row_hash": "h1", "updated_at": "t2"}),
+                json!({"id": 2, "_row_hash": "h2", "updated_at": "t2"}),
+            ]),
+            WriteMode::Merge,
+        )
+        .await
+        .unwrap();
+    writer
+        .write_stream(
+            rows_stream(vec![
+                json!({"id": 2, "_row_hash": "h3", "updated_at": "t3"}),
+            ]),
+            WriteMode::Merge,
+        )
+        .await
+        .unwrap();
+
+    let rows: Vec<(i64, String)> =
+        sqlx::query_as(r#"SELECT "id", "updated_at" FROM "users" ORDER BY "id""#)
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    // Same hash: the update is skipped; new hash: the row is rewritten.
+    assert_eq!(rows, vec![(1, "t1".to_string()), (2, "t3".to_string())]);
+}
+
+#[tokio::test]
+async fn test_truncate() {
+    let pool = memory_pool().await;
+    let writer = SqliteWriter::new(pool.clone(), "events");
+
+    // Missing table is not an error.
+    writer.truncate().await.unwrap();
+
+    writer
+        .write_stream(rows_stream(vec![json!({"id": 1})]), WriteMode::Append)
+        .await
+        .unwrap();
+    writer.truncate().await.unwrap();
+
+    let (count,): (i64,) = sqlx::query_as(r#"SELECT COUNT(*) FROM "events""#)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 0);
+}
+
+#[tokio::test]
+async fn test_newest_loaded_at() {
+    let pool = memory_pool().await;
+    let writer = SqliteWriter::new(pool, "events");
+
+    // No table yet.
+    assert_eq!(writer.newest_loaded_at("_loaded_at").await.unwrap(), None);
+
+    writer
+        .write_stream(
+            rows_stream(vec![
+                json!({"id": 1, "_loaded_at": "2024-05-01T08:00:00Z"}),
+                json!({"id": 2, "_loaded_at": "2024-05-02T09:30:00Z"}),
+            ]),
+            WriteMode::Append,
+        )
+        .await
+        .unwrap();
+
+    let newest = writer
+        .newest_loaded_at("_loaded_at")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(newest.to_rfc3339(), "2024-05-02T09:30:00+00:00");
+
+    // Missing column is treated as unknown, not an error.
+    assert_eq!(writer.newest_loaded_at("synced_at").await.unwrap(), None);
+}
@@ -0,0 +1,159 @@
+// Tests for Webhook Writer
+//
+// These tests cover:
+// - Endpoint templating
+// - Batching rows into JSON array request bodies with auth headers
+// - Retrying transient failures and failing on client errors
+
+use crate::http::StubResponse;
+use apitap::pipeline::{Config, SinkConn, Target, TargetConn};
+use apitap::utils::datafusion_ext::QueryResultStream;
+use apitap::writer::webhook::{WebhookMethod, WebhookWriter};
+use apitap::writer::{DataWriter, WriteMode};
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone)]
+struct Received {
+    request_line: String,
+    headers: HashMap<String, String>,
+    body: Value,
+}
+
+/// Answers with scripted statuses (then 200).
+async fn serve(statuses: Vec<u16>) -> (String, Arc<Mutex<Vec<Received>>>) {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let statuses = Mutex::new(VecDeque::from(statuses));
+
+    let log = Arc::clone(&received);
+    let base = crate::http::serve(move |req| {
+        log.lock().unwrap().push(Received {
+            request_line: req.head.lines().next().unwrap().to_string(),
+            headers: req.headers.into_iter().collect(),
+            body: serde_json::from_slice(&req.body).unwrap_or(Value::Null),
+        });
+
+        let status = statuses.lock().unwrap().pop_front().unwrap_or(200);
+        StubResponse::new(&format!("{status} X"), "nope")
+    })
+    .await;
+    (base, received)
+}
+
+async fn webhook_conn(url: &str, extra: &str) -> TargetConn {
+    let yaml = format!(
+        r#"
+sources: []
+targets:
+  - type: webhook
+    name: relay
+    url: {url}/ingest/{{table}}
+    headers:
+      - key: X-Api-Key
+        value: secret
+    retry:
+      max_attempts: 2
+      min_delay_secs: 0
+      max_delay_secs: 1
+{extra}
+"#
+    );
+    let config: Config = serde_yaml::from_str(&yaml).unwrap();
+    match config.target("relay").unwrap() {
+        t @ Target::Webhook(_) => t.create_conn().await.unwrap(),
+        _ => panic!("Expected Webhook target"),
+    }
+}
+
+fn rows(n: usize) -> QueryResultStream {
+    QueryResultStream {
+        table_name: "users".to_string(),
+        data: Box::pin(futures::stream::iter(
+            (0..n).map(|i| Ok(json!({"id": i, "tags": ["a"]}))),
+        )),
+    }
+}
+
+fn writer(conn: &TargetConn) -> WebhookWriter {
+    match conn {
+        TargetConn::Webhook { client, options } => {
+            WebhookWriter::new(client.clone(), &options.url, "users")
+                .with_method(options.method)
+                .with_batch_size(options.batch_size)
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_endpoint_template() {
+    assert_eq!(
+        WebhookWriter::endpoint("https://h/ingest/{table}", "users"),
+        "https://h/ingest/users"
+    );
+    assert_eq!(
+        WebhookWriter::endpoint("https://h/all", "users"),
+        "https://h/all"
+    );
+}
+
+#[tokio::test]
+async fn test_posts_batches_with_headers() {
+    std::env::set_var("APITAP_TEST_WEBHOOK_TOKEN", "tok123");
+    let (url, received) = serve(vec![]).await;
+    let conn = webhook_conn(
+        &url,
+        "    batch_size: 2\n    method: PUT\n    bearer_token_env: APITAP_TEST_WEBHOOK_TOKEN",
+    )
+    .await;
+
+    writer(&conn)
+        .write_stream(rows(5), WriteMode::Merge)
+        .await
+        .unwrap();
+
+    let received = received.lock().unwrap().clone();
+    assert_eq!(received.len(), 3);
+    assert_eq!(received[0].request_line, "PUT /ingest/users HTTP/1.1");
+    assert_eq!(received[0].headers["x-api-key"], "secret");
+    assert_eq!(received[0].headers["authorization"], "Bearer tok123");
+    assert_eq!(received[0].headers["x-apitap-table"], "users");
+    assert_eq!(received[0].headers["x-apitap-write-mode"], "merge");
+    assert_eq!(received[0].headers["content-type"], "application/json");
+    assert_eq!(
+        received[0].body,
+        json!([{"id": 0, "tags": ["a"]}, {"id": 1, "tags": ["a"]}])
+    );
+    assert_eq!(received[2].body, json!([{"id": 4, "tags": ["a"]}]));
+}
+
+#[tokio::test]
+async fn test_retries_transient_failures() {
+    let (url, received) = serve(vec![503]).await;
+    let conn = webhook_conn(&url, "").await;
+
+    writer(&conn)
+        .write_stream(rows(1), WriteMode::Append)
+        .await
+        .unwrap();
+    assert_eq!(received.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_client_error_fails_write() {
+    let (url, received) = serve(vec![400]).await;
+    let conn = webhook_conn(&url, "").await;
+
+    let err = writer(&conn)
+        .write_stream(rows(1), WriteMode::Append)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("400"), "{err}");
+    assert_eq!(received.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn test_webhook_method_default() {
+    assert_eq!(WebhookMethod::default(), WebhookMethod::Post);
+}
@@ -1,4 +1,4 @@
-use apitap::writer::WriteMode;
+use apitap::writer::{MergeStats, WriteMode};
 
 #[test]
 fn test_write_mode_merge() {
@@ -123,3 +123,23 @@ fn test_write_mode_in_result() {
 
     assert_eq!(ok_mode.unwrap(), WriteMode::Append);
 }
+
+#[test]
+fn test_merge_stats_churn() {
+    let mut stats = MergeStats::default();
+    assert_eq!(stats.churn(), None);
+
+    stats.add(MergeStats {
+        inserted: 5,
+        changed: 1,
+        unchanged: 3,
+    });
+    stats.add(MergeStats {
+        inserted: 0,
+        changed: 3,
+        unchanged: 1,
+    });
+    assert_eq!(stats.matched(), 8);
+    assert_eq!(stats.inserted, 5);
+    assert_eq!(stats.churn(), Some(0.5));
+}
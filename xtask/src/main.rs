@@ -0,0 +1,36 @@
+//! Release tasks, run as `cargo xtask <task>`.
+//!
+//! - `man [DIR]`: render `apitap.1` and one page per subcommand into `DIR`
+//!   (`target/man` by default) for packaging, from the same CLI definition
+//!   the binary is built from
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use apitap::cmd::completions::write_man_pages;
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        Some("man") => {
+            let dir = args
+                .next()
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from("target/man"));
+            match write_man_pages(&dir) {
+                Ok(pages) => {
+                    println!("{} man pages written to {}", pages.len(), dir.display());
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    eprintln!("man pages: {e}");
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        _ => {
+            eprintln!("usage: cargo xtask man [DIR]");
+            ExitCode::from(2)
+        }
+    }
+}